@@ -7,8 +7,11 @@ import { NotePage } from './pages/NotePage'
 import { SearchPage } from './pages/SearchPage'
 import { TagsPage } from './pages/TagsPage'
 import { StatsPage } from './pages/StatsPage'
+import { useVaultEvents } from './hooks/useVaultEvents'
 
 function App() {
+  useVaultEvents()
+
   return (
     <ErrorBoundary>
       <Routes>
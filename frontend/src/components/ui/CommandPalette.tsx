@@ -14,6 +14,7 @@ import {
 import { useUIStore } from '@/stores/uiStore'
 import { useSettingsStore } from '@/stores/settingsStore'
 import { useSearch } from '@/hooks/useSearch'
+import { HighlightedSnippet } from '@/components/ui/HighlightedSnippet'
 import { cn } from '@/utils/cn'
 
 export function CommandPalette() {
@@ -176,7 +177,7 @@ export function CommandPalette() {
                         {result.title || 'Untitled'}
                       </div>
                       <div className="text-xs text-text-muted line-clamp-1 mt-0.5">
-                        {result.snippet}
+                        <HighlightedSnippet snippet={result.snippet} highlights={result.highlights} />
                       </div>
                     </div>
                     <span className={cn(
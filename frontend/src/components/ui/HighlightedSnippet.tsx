@@ -0,0 +1,33 @@
+import type { MatchRange } from '@/hooks/useSearch'
+
+interface HighlightedSnippetProps {
+  snippet: string
+  highlights?: MatchRange[]
+}
+
+const encoder = new TextEncoder()
+const decoder = new TextDecoder()
+
+// Highlight ranges are byte offsets into the UTF-8 snippet
+export function HighlightedSnippet({ snippet, highlights }: HighlightedSnippetProps) {
+  if (!highlights || highlights.length === 0) {
+    return <>{snippet}</>
+  }
+
+  const bytes = encoder.encode(snippet)
+  const parts: React.ReactNode[] = []
+  let last = 0
+  highlights.forEach(({ start, end }, i) => {
+    if (start < last) return
+    parts.push(decoder.decode(bytes.slice(last, start)))
+    parts.push(
+      <mark key={i} className="bg-fulltext/20 text-text-primary rounded-sm">
+        {decoder.decode(bytes.slice(start, end))}
+      </mark>
+    )
+    last = end
+  })
+  parts.push(decoder.decode(bytes.slice(last)))
+
+  return <>{parts}</>
+}
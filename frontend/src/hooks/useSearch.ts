@@ -1,5 +1,10 @@
 import { useQuery } from '@tanstack/react-query'
 
+export interface MatchRange {
+  start: number
+  end: number
+}
+
 export interface SearchResult {
   note_id: string
   title: string
@@ -8,6 +13,7 @@ export interface SearchResult {
   chunk_type?: string
   tags: string[]
   updated_at?: string
+  highlights?: MatchRange[]
 }
 
 interface SearchResponse {
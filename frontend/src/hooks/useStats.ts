@@ -2,7 +2,7 @@ import { useQuery } from '@tanstack/react-query'
 
 interface StatsResponse {
   note_count: number
-  chunk_count: number
+  chunk_count?: number
   tag_count: number
 }
 
@@ -0,0 +1,31 @@
+import { useEffect } from 'react'
+import { useQueryClient } from '@tanstack/react-query'
+
+const NOTE_EVENTS = ['note_created', 'note_captured', 'note_updated', 'note_deleted', 'resync'] as const
+
+// Refresh cached notes, tags and stats when the vault changes, including
+// edits made through MCP, the CLI or another editor
+export function useVaultEvents() {
+  const queryClient = useQueryClient()
+
+  useEffect(() => {
+    const source = new EventSource('/api/events')
+
+    const refreshNotes = () => {
+      queryClient.invalidateQueries({ queryKey: ['notes'] })
+      queryClient.invalidateQueries({ queryKey: ['tags'] })
+      queryClient.invalidateQueries({ queryKey: ['stats'] })
+      queryClient.invalidateQueries({ queryKey: ['search'] })
+    }
+    const refreshSearch = () => {
+      queryClient.invalidateQueries({ queryKey: ['search'] })
+    }
+
+    for (const name of NOTE_EVENTS) {
+      source.addEventListener(name, refreshNotes)
+    }
+    source.addEventListener('note_indexed', refreshSearch)
+
+    return () => source.close()
+  }, [queryClient])
+}
@@ -5,6 +5,7 @@ import { useSearchStore } from '@/stores/searchStore'
 import { useSettingsStore } from '@/stores/settingsStore'
 import { useSearch } from '@/hooks/useSearch'
 import { Skeleton } from '@/components/ui/Skeleton'
+import { HighlightedSnippet } from '@/components/ui/HighlightedSnippet'
 import { Tooltip } from '@/components/ui/Tooltip'
 import { cn } from '@/utils/cn'
 
@@ -137,7 +138,7 @@ export function SearchPage() {
 
               {/* Snippet */}
               <p className="text-sm text-text-secondary mt-2 line-clamp-2">
-                {result.snippet}
+                <HighlightedSnippet snippet={result.snippet} highlights={result.highlights} />
               </p>
 
               {/* Metadata row - same style as notes list */}
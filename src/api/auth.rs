@@ -0,0 +1,284 @@
+//! Authentication with API keys, web UI login sessions and MCP client tokens
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, Extensions, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+    Json,
+};
+use base64::Engine;
+
+use super::handlers::ErrorResponse;
+use super::session::{passes_csrf_check, session_token, Sessions};
+use super::token::ApiToken;
+use crate::mcp::auth::{self as mcp_auth, McpAuth};
+use crate::store::{Permissions, Users};
+
+/// Who may use a server: API key accounts, password login for the web UI,
+/// MCP clients for the `/mcp` endpoint, and holders of the vault-wide API token
+#[derive(Clone)]
+pub struct AuthState {
+    pub users: Arc<Users>,
+    pub sessions: Arc<Sessions>,
+    pub mcp: Arc<McpAuth>,
+    pub api_token: Arc<ApiToken>,
+}
+
+impl From<Arc<Users>> for AuthState {
+    /// API keys only, without password login or MCP clients
+    fn from(users: Arc<Users>) -> Self {
+        Self {
+            users,
+            sessions: Arc::new(Sessions::disabled()),
+            mcp: Arc::new(McpAuth::disabled()),
+            api_token: Arc::new(ApiToken::disabled()),
+        }
+    }
+}
+
+/// The user a request was authenticated as
+///
+/// Inserted into the request extensions by [`authenticate`]; absent when the
+/// vault has no user accounts. Requests from MCP clients are attributed to the
+/// client's name, with access to all notes.
+#[derive(Debug, Clone)]
+pub struct CurrentUser {
+    pub name: String,
+    /// Notes the user's scopes give access to
+    pub permissions: Permissions,
+}
+
+/// The permissions of a request's user, or unrestricted if there is none
+pub fn permissions(extensions: &Extensions) -> Permissions {
+    extensions
+        .get::<CurrentUser>()
+        .map(|user| user.permissions.clone())
+        .unwrap_or_default()
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Permissions {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(permissions(&parts.extensions))
+    }
+}
+
+/// Require an API key or a login session on every request except health
+/// checks once the vault has user accounts or a password
+///
+/// Keys are accepted as `Authorization: Bearer <key>` or as the password of
+/// HTTP Basic auth, which lets browsers sign in to the web UI with their
+/// built-in prompt when no password is configured. With a password, browsers
+/// sign in on `/login` instead and state-changing requests made with the
+/// session cookie must pass a CSRF check.
+///
+/// Once MCP clients are configured, `/mcp` takes a client's bearer token (or a
+/// user's API key) regardless of the other settings.
+///
+/// The vault-wide API token, when configured, is required on `/api/*` and
+/// `/mcp` and accepted wherever an API key is, with access to all notes.
+pub async fn authenticate(State(auth): State<AuthState>, mut req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    if auth.mcp.is_enabled() && is_mcp_path(path) {
+        return authenticate_mcp(&auth, req, next).await;
+    }
+
+    let login = auth.sessions.is_enabled();
+    let token = auth.api_token.is_enabled() && is_api_path(path);
+    if (auth.users.is_empty() && !login && !token)
+        || path == "/health"
+        || mcp_auth::is_public_path(path)
+        || (login && is_login_path(path))
+    {
+        return next.run(req).await;
+    }
+    if has_api_token(&auth.api_token, &req) {
+        return next.run(req).await;
+    }
+
+    if login {
+        if let Some(session) = session_token(req.headers()).and_then(|t| auth.sessions.get(&t)) {
+            if is_mutating(req.method()) && !passes_csrf_check(req.headers(), &session) {
+                return (
+                    StatusCode::FORBIDDEN,
+                    Json(ErrorResponse {
+                        error: "CSRF check failed".into(),
+                    }),
+                )
+                    .into_response();
+            }
+            return next.run(req).await;
+        }
+    }
+
+    match user_from_api_key(&auth.users, &req) {
+        Some(user) => {
+            req.extensions_mut().insert(user);
+            next.run(req).await
+        }
+        // Send browsers opening the web UI to the login page
+        None if login && req.method() == Method::GET && !is_api_path(req.uri().path()) => {
+            let next = req.uri().path_and_query().map_or("/", |p| p.as_str());
+            Redirect::to(&format!("/login?next={}", encode_query_value(next))).into_response()
+        }
+        None if login => (
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                error: "Not signed in".into(),
+            }),
+        )
+            .into_response(),
+        None => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Basic realm=\"Notidium\"")],
+            Json(ErrorResponse {
+                error: "Missing or invalid API key".into(),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// Require an MCP client token or a user API key on the MCP endpoint
+async fn authenticate_mcp(auth: &AuthState, mut req: Request, next: Next) -> Response {
+    if has_api_token(&auth.api_token, &req) {
+        return next.run(req).await;
+    }
+
+    let client = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|token| auth.mcp.authenticate(token.trim()));
+
+    let user = match client {
+        Some(name) => Some(CurrentUser {
+            name,
+            permissions: Permissions::default(),
+        }),
+        None => user_from_api_key(&auth.users, &req),
+    };
+
+    match user {
+        Some(user) => {
+            req.extensions_mut().insert(user);
+            next.run(req).await
+        }
+        None => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, mcp_auth::challenge(req.headers()))],
+            Json(ErrorResponse {
+                error: "Missing or invalid MCP access token".into(),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// The user whose API key a request carries
+fn user_from_api_key(users: &Users, req: &Request) -> Option<CurrentUser> {
+    let (name, key) = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(credentials)?;
+    let user = users
+        .authenticate(&key)
+        .filter(|user| name.is_none_or(|name| name == user.name))?;
+    Some(CurrentUser {
+        name: user.name.clone(),
+        permissions: user.permissions(),
+    })
+}
+
+/// Whether a request carries the vault-wide API token, as a bearer token or
+/// the password of HTTP Basic auth
+fn has_api_token(token: &ApiToken, req: &Request) -> bool {
+    token.is_enabled()
+        && req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(credentials)
+            .is_some_and(|(_, key)| token.verify(&key))
+}
+
+/// Paths reachable without signing in when password login is enabled
+fn is_login_path(path: &str) -> bool {
+    matches!(path, "/login" | "/api/auth/login" | "/api/auth/session")
+}
+
+fn is_api_path(path: &str) -> bool {
+    path.starts_with("/api/") || is_mcp_path(path)
+}
+
+fn is_mcp_path(path: &str) -> bool {
+    path == "/mcp" || path.starts_with("/mcp/")
+}
+
+fn is_mutating(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Percent-encode a value for use in a query string
+fn encode_query_value(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Parse an `Authorization` header into an optional user name and an API key
+fn credentials(value: &str) -> Option<(Option<String>, String)> {
+    if let Some(key) = value.strip_prefix("Bearer ") {
+        return Some((None, key.trim().to_string()));
+    }
+
+    let encoded = value.strip_prefix("Basic ")?;
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (name, key) = decoded.split_once(':')?;
+    Some((Some(name.to_string()), key.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bearer_credentials() {
+        assert_eq!(
+            credentials("Bearer ntd_abc"),
+            Some((None, "ntd_abc".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_encode_query_value() {
+        assert_eq!(encode_query_value("/notes/a b?x=1&y"), "/notes/a%20b%3Fx%3D1%26y");
+    }
+
+    #[test]
+    fn test_basic_credentials() {
+        let encoded = base64::engine::general_purpose::STANDARD.encode("alice:ntd_abc");
+        assert_eq!(
+            credentials(&format!("Basic {}", encoded)),
+            Some((Some("alice".to_string()), "ntd_abc".to_string()))
+        );
+        assert_eq!(credentials("Basic !!!"), None);
+        assert_eq!(credentials("Token ntd_abc"), None);
+    }
+}
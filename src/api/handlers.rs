@@ -1,15 +1,40 @@
 //! API request handlers
 
+use std::convert::Infallible;
+use std::path::PathBuf;
+
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
-    Json,
+    http::{header, HeaderMap, StatusCode},
+    response::sse::{Event, KeepAlive, Sse},
+    Extension, Json,
 };
+use futures::Stream;
 use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
 use utoipa::{IntoParams, ToSchema};
 
+use super::auth::CurrentUser;
+use super::indexer::IndexStatus;
 use super::routes::AppState;
-use crate::types::{ChunkType, Note, NoteMeta, SearchResult};
+use super::session::session_token;
+use crate::error::Error;
+use crate::embed::heading_paths;
+use crate::export::note::{self as note_export, NoteFormat};
+use crate::export::{self, ExportFormat};
+use crate::search::{
+    canvas_id, canvas_path, default_mode, feedback_factor, feedback_terms, grep_matches, Classification, ClassificationDecision, SearchClient, SearchDispatcher,
+    DateFilter, DateOp, GrepMatch, GrepOptions, SearchQuery, SearchRequest, SearchStrategy, TagMatch,
+};
+pub use crate::search::SearchMode;
+use crate::store::{
+    frontmatter_tags, AccessLevel, AttachmentInfo, Board, BoardItems, Canvas, CanvasInfo, Contribution, NoteChange, Permissions,
+    DEFAULT_BOARD_PROPERTY, DONE_COLUMN, MOC_TAG, TODO_COLUMN,
+};
+use crate::types::{
+    Annotation, Backlink, Chunk, FeedbackKind, NoteSort, SortField, SortOrder, Highlight, Note, NoteMeta, NoteRelation, NoteVersion, QueryType, ReadPosition, RelationKind, SearchResult,
+    TrashedNote,
+};
 
 // Query parameters
 
@@ -23,20 +48,106 @@ pub struct ListParams {
     pub offset: usize,
     /// Filter by tag name
     pub tag: Option<String>,
+    /// Only notes created or last modified by this user
+    pub author: Option<String>,
+    /// Order of the notes (default and `relevance`: last modified first)
+    #[serde(default)]
+    #[param(inline)]
+    pub sort: SortField,
+    /// Direction of `sort` (default: A to Z for titles, newest first
+    /// otherwise)
+    #[param(inline)]
+    pub order: Option<SortOrder>,
 }
 
 #[derive(Debug, Deserialize, IntoParams)]
 pub struct SearchParams {
-    /// Search query string
+    /// Search query, with optional filters: `tag:rust`, `path:inbox/`,
+    /// `created:>2024-01-01`, `updated:<=2024-06-30`, `"exact phrase"`
     pub q: String,
     /// Maximum number of results to return
     #[serde(default = "default_limit")]
     pub limit: usize,
+    /// Ranking strategy (default: `search.client_modes` for the caller or
+    /// `http`, then `search.default_mode`, then full-text)
+    #[param(inline)]
+    pub mode: Option<SearchMode>,
+    /// Only notes created or last modified by this user
+    pub author: Option<String>,
+    /// Snippet length in characters (capped by `search.max_snippet_len`)
+    pub snippet_len: Option<usize>,
+    /// Also return the full text of each matched chunk
+    #[serde(default)]
+    pub include_full_chunk: bool,
+    /// Ranking profile from `[search.profiles]` weighting full-text fields
+    /// (full-text and hybrid modes)
+    pub profile: Option<String>,
+    /// Also match words a typo or two away from the query's, so
+    /// `kubernets` finds Kubernetes (full-text and hybrid modes)
+    #[serde(default)]
+    pub fuzzy: bool,
+    /// Match the query as prose, code or hybrid instead of classifying it
+    /// (semantic and hybrid modes)
+    pub query_type: Option<QueryType>,
+    /// Search the whole query at once, or split it into sub-queries and
+    /// fuse their results (semantic and hybrid modes)
+    #[serde(default)]
+    #[param(inline)]
+    pub strategy: SearchStrategy,
+    /// Favor recently updated notes (semantic and hybrid modes)
+    #[serde(default)]
+    pub recency: bool,
+    /// Days after which the recency bonus halves (default:
+    /// `search.recency.half_life_days`)
+    pub half_life_days: Option<f32>,
+    /// Comma-separated tags results must have
+    pub tags: Option<String>,
+    /// Whether results need `all` of `tags` (default) or `any` of them
+    #[serde(default)]
+    #[param(inline)]
+    pub tag_match: TagMatch,
+    /// Only notes created after this day (YYYY-MM-DD)
+    pub created_after: Option<String>,
+    /// Only notes created before this day (YYYY-MM-DD)
+    pub created_before: Option<String>,
+    /// Only notes last modified after this day (YYYY-MM-DD)
+    pub updated_after: Option<String>,
+    /// Only notes last modified before this day (YYYY-MM-DD)
+    pub updated_before: Option<String>,
+    /// Order of the results (default: relevance). Full-text search sorts
+    /// every match; semantic and hybrid search sort their best matches
+    #[serde(default)]
+    #[param(inline)]
+    pub sort: SortField,
+    /// Direction of `sort` (default: A to Z for titles, best or newest
+    /// first otherwise)
+    #[param(inline)]
+    pub order: Option<SortOrder>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct DeleteParams {
+    /// Skip the trash and remove the note and everything indexed about it
+    #[serde(default)]
+    pub permanent: bool,
+    /// Also delete the attachments the note embeds that no other note or
+    /// template uses
+    #[serde(default)]
+    pub delete_attachments: bool,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SuggestParams {
+    /// Partial title to complete
+    pub q: String,
+    /// Maximum number of suggestions to return
+    #[serde(default = "default_suggest_limit")]
+    pub limit: usize,
 }
 
 // Request bodies
 
-#[derive(Debug, Deserialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct CreateNoteRequest {
     /// Title of the note
     pub title: String,
@@ -44,6 +155,8 @@ pub struct CreateNoteRequest {
     pub content: String,
     /// Optional tags to assign
     pub tags: Option<Vec<String>>,
+    /// Folder to create the note in, relative to the notes directory
+    pub folder: Option<String>,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -60,6 +173,24 @@ pub struct UpdateNoteRequest {
     pub is_archived: Option<bool>,
 }
 
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct LoginRequest {
+    /// The vault's web UI password
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FromTemplateRequest {
+    /// Name of the template (file name in `templates/` without `.md`)
+    pub template: String,
+    /// Title of the new note, also used for `{{title}}`
+    pub title: String,
+    /// Folder to create the note in, relative to the notes directory
+    pub folder: Option<String>,
+    /// Text for the `{{clipboard}}` placeholder
+    pub clipboard: Option<String>,
+}
+
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct CaptureRequest {
     /// Content to capture
@@ -69,6 +200,12 @@ pub struct CaptureRequest {
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
+pub struct CaptureUrlRequest {
+    /// Page to clip, http or https
+    pub url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct UploadAttachmentRequest {
     /// Base64-encoded image data
     pub data: String,
@@ -80,7 +217,7 @@ pub struct UploadAttachmentRequest {
 
 // Response types
 
-#[derive(Debug, Serialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct NoteResponse {
     /// Unique note identifier
     pub id: String,
@@ -96,13 +233,22 @@ pub struct NoteResponse {
     pub created_at: String,
     /// ISO 8601 last update timestamp
     pub updated_at: String,
+    /// User who created the note, in multi-user vaults
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    /// User who last modified the note, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_by: Option<String>,
     /// Whether note is pinned
     pub is_pinned: bool,
     /// Whether note is archived
     pub is_archived: bool,
+    /// Where the requesting user left off reading (returned by get_note)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<ReadPosition>,
 }
 
-#[derive(Debug, Serialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ListResponse {
     /// List of note metadata
     pub notes: Vec<NoteMeta>,
@@ -114,7 +260,7 @@ pub struct ListResponse {
     pub limit: usize,
 }
 
-#[derive(Debug, Serialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct SearchResponse {
     /// Search results with scores
     pub results: Vec<SearchResult>,
@@ -122,23 +268,219 @@ pub struct SearchResponse {
     pub total: usize,
 }
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TitleSuggestion {
+    /// Note ID
+    pub id: String,
+    /// Note title
+    pub title: String,
+    /// Match score (1.0 for an exact match)
+    pub score: f32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SuggestResponse {
+    /// Ranked title suggestions
+    pub suggestions: Vec<TitleSuggestion>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BacklinksResponse {
+    /// Notes linking to the requested note
+    pub backlinks: Vec<Backlink>,
+    pub total: usize,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct RelationParams {
+    /// Only relations of this kind
+    #[serde(rename = "type")]
+    #[param(inline)]
+    pub kind: Option<RelationKind>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RelationsResponse {
+    /// Relations the note declares, then those declared to it
+    pub relations: Vec<NoteRelation>,
+    pub total: usize,
+}
+
+/// A note's chunks as semantic search sees them
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NoteChunksResponse {
+    pub note_id: String,
+    pub chunks: Vec<ChunkInfo>,
+    pub total: usize,
+}
+
+/// A chunk of a note and its embeddings
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChunkInfo {
+    pub id: String,
+    /// Prose, Heading, CodeBlock, ListItem or Blockquote, with details
+    pub chunk_type: String,
+    /// Headings the chunk sits under, outermost first
+    pub heading_path: Vec<String>,
+    pub start_line: u32,
+    pub end_line: u32,
+    pub content: String,
+    pub embedding_model: Option<String>,
+    pub embedded_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Length of the prose embedding; zero or missing means search can't match it
+    pub prose_norm: Option<f32>,
+    /// Length of the code embedding, for code blocks
+    pub code_norm: Option<f32>,
+}
+
+/// Notes and the links between them
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GraphResponse {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GraphNode {
+    pub id: String,
+    pub title: String,
+    pub tags: Vec<String>,
+}
+
+/// A link from one note to another
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GraphEdge {
+    pub source: String,
+    pub target: String,
+    /// `link` for links in the text, or the relation kind (`supersedes`, ...)
+    pub label: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CoAccessedNote {
+    #[serde(flatten)]
+    pub note: NoteMeta,
+    /// Number of times both notes were opened within the same half hour
+    pub count: u32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CoAccessedResponse {
+    /// Notes frequently opened together with the requested note
+    pub notes: Vec<CoAccessedNote>,
+    pub total: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VersionsResponse {
+    /// Saved versions of the note, newest first
+    pub versions: Vec<NoteVersion>,
+    pub total: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NoteVersionContent {
+    #[serde(flatten)]
+    pub version: NoteVersion,
+    /// Full markdown content of the note at this version
+    pub content: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TrashResponse {
+    /// Deleted notes, most recently deleted first
+    pub notes: Vec<TrashedNote>,
+    pub total: usize,
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct TagsResponse {
     /// List of all tags
     pub tags: Vec<String>,
+    /// Number of notes carrying each tag
+    pub counts: std::collections::BTreeMap<String, u32>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TagFacetsParams {
+    /// Count tags among the notes matching this query, with its filters
+    /// (default: every note)
+    pub q: Option<String>,
+}
+
+/// How many matching notes carry a tag
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TagFacet {
+    pub tag: String,
+    pub count: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TagFacetsResponse {
+    /// Tags of the matching notes, most common first
+    pub facets: Vec<TagFacet>,
+    /// Number of matching notes
+    pub matches: usize,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RenameTagRequest {
+    /// New name for the tag
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MergeTagsRequest {
+    /// Tags to merge away
+    pub tags: Vec<String>,
+    /// Tag that replaces them
+    pub into: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TagChangeResponse {
+    /// IDs of the notes whose tags were rewritten
+    pub updated: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RefreshResponse {
+    /// IDs of the notes reloaded from disk
+    pub updated: Vec<String>,
+    /// IDs of the notes whose file was gone
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionResponse {
+    /// Whether the request carries a live login session
+    pub authenticated: bool,
+    /// Token to send in `X-CSRF-Token` on state-changing requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub csrf_token: Option<String>,
+    /// When the session ends (RFC 3339)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TemplatesResponse {
+    /// Names of the vault's note templates
+    pub templates: Vec<String>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct StatsResponse {
     /// Total number of notes
     pub note_count: usize,
-    /// Total number of indexed chunks
-    pub chunk_count: usize,
+    /// Total number of indexed chunks; omitted for scoped keys
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunk_count: Option<usize>,
     /// Total number of unique tags
     pub tag_count: usize,
 }
 
-#[derive(Debug, Serialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct HealthResponse {
     /// Service status
     pub status: String,
@@ -146,13 +488,13 @@ pub struct HealthResponse {
     pub version: String,
 }
 
-#[derive(Debug, Serialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ErrorResponse {
     /// Error message
     pub error: String,
 }
 
-#[derive(Debug, Serialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct AttachmentResponse {
     /// Filename of the uploaded attachment
     pub filename: String,
@@ -162,64 +504,204 @@ pub struct AttachmentResponse {
     pub markdown: String,
 }
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NoteAttachmentsResponse {
+    /// Embedded attachments, sorted by file name
+    pub attachments: Vec<AttachmentInfo>,
+    /// File names the note embeds that aren't in the attachments directory
+    pub missing: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AttachmentsResponse {
+    /// Attachments sorted by file name
+    pub attachments: Vec<AttachmentInfo>,
+    pub total: usize,
+    /// Combined size in bytes
+    pub total_size: u64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AttachmentGcResponse {
+    /// Attachments no note or template references
+    pub orphans: Vec<AttachmentInfo>,
+    /// How many of them were deleted
+    pub deleted: usize,
+    /// Bytes freed by deleting them
+    pub freed_bytes: u64,
+}
+
 // Default values
 
 fn default_limit() -> usize {
     50
 }
 
-// Helper function to chunk and embed a note
-async fn index_note_chunks(state: &AppState, note: &Note) {
-    // Create chunks from the note
-    let chunks = state.chunker.chunk_note(note);
+fn default_suggest_limit() -> usize {
+    10
+}
 
-    if chunks.is_empty() {
-        return;
-    }
+// Helper function to queue a note for chunking and embedding
+pub(crate) fn index_note_chunks(state: &AppState, note: &Note) {
+    state.indexer.index(note);
+}
 
-    // Embed each chunk
-    for mut chunk in chunks {
-        // Always embed with prose model
-        match state.embedder.embed_prose(&chunk.content).await {
-            Ok(embedding) => {
-                chunk.prose_embedding = Some(embedding);
-                chunk.embedded_at = Some(chrono::Utc::now());
-            }
-            Err(e) => {
-                tracing::warn!("Failed to embed chunk: {}", e);
-                continue;
-            }
-        }
+// Helper function to queue removal of a note's chunks
+pub(crate) fn remove_note_chunks(state: &AppState, note_id: uuid::Uuid) {
+    state.indexer.remove(note_id);
+}
 
-        // For code blocks, also embed with code model
-        if matches!(chunk.chunk_type, ChunkType::CodeBlock { .. }) {
-            match state.embedder.embed_code(&chunk.content).await {
-                Ok(embedding) => {
-                    chunk.code_embedding = Some(embedding);
-                }
-                Err(e) => {
-                    tracing::warn!("Failed to embed code chunk: {}", e);
-                }
-            }
+// Helper function to credit the signed-in user, if any, with a note change
+async fn attribute_change(
+    state: &AppState,
+    user: Option<Extension<CurrentUser>>,
+    note: Note,
+    contribution: Contribution,
+) -> Note {
+    let Some(Extension(CurrentUser { name: user, .. })) = user else {
+        return note;
+    };
+    match state.store.attribute(note.id, &user, contribution).await {
+        Ok(note) => note,
+        Err(e) => {
+            tracing::warn!("Failed to attribute change to {}: {}", user, e);
+            note
         }
-
-        // Add to semantic search
-        let mut semantic = state.semantic.write().await;
-        semantic.add_chunk(chunk);
     }
+}
+
+// Helper function to enforce the caller's scopes on a note
+fn authorize(
+    permissions: &Permissions,
+    access: AccessLevel,
+    note: &Note,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    permissions.check_note(access, note).map_err(access_error)
+}
 
-    tracing::debug!("Indexed chunks for note {}", note.id);
+// Helper function to require write access to a note once its tags become
+// `tags`, so scoped keys can't move notes into other tag scopes
+fn authorize_tags(
+    permissions: &Permissions,
+    note: &Note,
+    tags: &[String],
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    if permissions.allows(AccessLevel::Write, &note.file_path, tags) {
+        Ok(())
+    } else {
+        Err(access_error(Error::AccessDenied(format!(
+            "no write access to notes tagged {}",
+            tags.join(", ")
+        ))))
+    }
 }
 
-// Helper function to remove chunks for a note
-async fn remove_note_chunks(state: &AppState, note_id: uuid::Uuid) {
-    let mut semantic = state.semantic.write().await;
-    semantic.remove_chunks_for_note(note_id);
-    tracing::debug!("Removed chunks for note {}", note_id);
+fn access_error(e: Error) -> (StatusCode, Json<ErrorResponse>) {
+    let status = match e {
+        Error::NoteNotFound(_) => StatusCode::NOT_FOUND,
+        Error::AccessDenied(_) => StatusCode::FORBIDDEN,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
 }
 
 // Handlers
 
+/// Sign in to the web UI with the vault password
+#[utoipa::path(
+    post,
+    path = "/api/auth/login",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Signed in; the session cookie is set", body = SessionResponse),
+        (status = 401, description = "Wrong password", body = ErrorResponse),
+        (status = 404, description = "Password login is not enabled", body = ErrorResponse)
+    ),
+    tag = "auth"
+)]
+pub async fn login(
+    State(state): State<AppState>,
+    Json(req): Json<LoginRequest>,
+) -> Result<([(header::HeaderName, String); 1], Json<SessionResponse>), (StatusCode, Json<ErrorResponse>)> {
+    if !state.sessions.is_enabled() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Password login is not enabled".into(),
+            }),
+        ));
+    }
+
+    // Argon2 is deliberately slow; keep it off the async workers
+    let sessions = state.sessions.clone();
+    let valid = tokio::task::spawn_blocking(move || sessions.verify_password(&req.password))
+        .await
+        .unwrap_or(false);
+    if !valid {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                error: "Wrong password".into(),
+            }),
+        ));
+    }
+
+    let (token, session) = state.sessions.create();
+    Ok((
+        [(header::SET_COOKIE, state.sessions.cookie(&token))],
+        Json(SessionResponse {
+            authenticated: true,
+            csrf_token: Some(session.csrf_token),
+            expires_at: Some(session.expires_at.to_rfc3339()),
+        }),
+    ))
+}
+
+/// Sign out of the web UI
+#[utoipa::path(
+    post,
+    path = "/api/auth/logout",
+    responses(
+        (status = 204, description = "Signed out; the session cookie is cleared")
+    ),
+    tag = "auth"
+)]
+pub async fn logout(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> (StatusCode, [(header::HeaderName, String); 1]) {
+    if let Some(token) = session_token(&headers) {
+        state.sessions.remove(&token);
+    }
+    (
+        StatusCode::NO_CONTENT,
+        [(header::SET_COOKIE, state.sessions.clear_cookie())],
+    )
+}
+
+/// Get the current login session, including its CSRF token
+#[utoipa::path(
+    get,
+    path = "/api/auth/session",
+    responses(
+        (status = 200, description = "Session state", body = SessionResponse)
+    ),
+    tag = "auth"
+)]
+pub async fn get_session(State(state): State<AppState>, headers: HeaderMap) -> Json<SessionResponse> {
+    let session = session_token(&headers).and_then(|token| state.sessions.get(&token));
+    Json(SessionResponse {
+        authenticated: session.is_some(),
+        expires_at: session.as_ref().map(|s| s.expires_at.to_rfc3339()),
+        csrf_token: session.map(|s| s.csrf_token),
+    })
+}
+
 /// Health check endpoint
 #[utoipa::path(
     get,
@@ -248,15 +730,31 @@ pub async fn health() -> Json<HealthResponse> {
 )]
 pub async fn list_notes(
     State(state): State<AppState>,
+    permissions: Permissions,
     Query(params): Query<ListParams>,
 ) -> Json<ListResponse> {
-    let notes = state
+    // Page after applying scopes so restricted users still get full pages
+    let notes: Vec<Note> = state
         .store
-        .list_paginated(params.offset, params.limit, params.tag.as_deref())
-        .await;
+        .list_paginated(
+            0,
+            usize::MAX,
+            params.tag.as_deref(),
+            params.author.as_deref(),
+            NoteSort::new(params.sort, params.order),
+        )
+        .await
+        .into_iter()
+        .filter(|n| permissions.can_read(n))
+        .skip(params.offset)
+        .take(params.limit)
+        .collect();
 
     let all_notes = state.store.list().await;
-    let total = all_notes.iter().filter(|n| !n.is_deleted && !n.is_archived).count();
+    let total = all_notes
+        .iter()
+        .filter(|n| !n.is_deleted && !n.is_archived && permissions.can_read(n))
+        .count();
 
     Json(ListResponse {
         notes: notes.iter().map(NoteMeta::from).collect(),
@@ -282,6 +780,8 @@ pub async fn list_notes(
 )]
 pub async fn get_note(
     State(state): State<AppState>,
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
     Path(id): Path<String>,
 ) -> Result<Json<NoteResponse>, (StatusCode, Json<ErrorResponse>)> {
     let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
@@ -301,6 +801,18 @@ pub async fn get_note(
             }),
         )
     })?;
+    authorize(&permissions, AccessLevel::Read, &note)?;
+
+    if let Err(e) = state.store.record_access(uuid).await {
+        tracing::warn!("Failed to record note access: {}", e);
+    }
+    let position = state
+        .store
+        .read_position(uuid, &user_key(user.as_ref()))
+        .unwrap_or_else(|e| {
+            tracing::warn!("Failed to read position: {}", e);
+            None
+        });
 
     let tags = note.tags();
     Ok(Json(NoteResponse {
@@ -311,148 +823,174 @@ pub async fn get_note(
         tags,
         created_at: note.created_at.to_rfc3339(),
         updated_at: note.updated_at.to_rfc3339(),
+        created_by: note.created_by,
+        updated_by: note.updated_by,
         is_pinned: note.is_pinned,
         is_archived: note.is_archived,
+        position,
     }))
 }
 
-/// Create a new note
+/// Request to save where the reader is in a note
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ReadPositionRequest {
+    /// Heading of the section being read
+    pub heading: Option<String>,
+    /// Line at the top of the view, from 1
+    pub line: Option<u32>,
+    /// How far down the note the view is scrolled, from 0 to 1
+    pub scroll: Option<f64>,
+}
+
+/// Key personal state like read positions and stars is saved under: the
+/// user, so it follows them across devices, or a shared one in single-user
+/// vaults
+fn user_key(user: Option<&Extension<CurrentUser>>) -> String {
+    user.map(|Extension(user)| user.name.clone()).unwrap_or_default()
+}
+
+/// Save where the reader left off in a note
+///
+/// The position replaces the user's last one for the note and comes back
+/// with the note from get_note, on any device.
 #[utoipa::path(
-    post,
-    path = "/api/notes",
-    request_body = CreateNoteRequest,
+    put,
+    path = "/api/notes/{id}/position",
+    params(
+        ("id" = String, Path, description = "Note UUID")
+    ),
+    request_body = ReadPositionRequest,
     responses(
-        (status = 201, description = "Note created", body = NoteResponse),
-        (status = 500, description = "Internal error", body = ErrorResponse)
+        (status = 200, description = "Position saved", body = ReadPosition),
+        (status = 400, description = "Invalid note ID or position", body = ErrorResponse),
+        (status = 404, description = "Note not found", body = ErrorResponse)
     ),
     tag = "notes"
 )]
-pub async fn create_note(
+pub async fn set_read_position(
     State(state): State<AppState>,
-    Json(req): Json<CreateNoteRequest>,
-) -> Result<(StatusCode, Json<NoteResponse>), (StatusCode, Json<ErrorResponse>)> {
-    let note = state
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
+    Path(id): Path<String>,
+    Json(req): Json<ReadPositionRequest>,
+) -> Result<Json<ReadPosition>, (StatusCode, Json<ErrorResponse>)> {
+    let bad_request = |error: &str| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: error.into(),
+            }),
+        )
+    };
+    let uuid = id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| bad_request("Invalid note ID"))?;
+    if req.heading.is_none() && req.line.is_none() && req.scroll.is_none() {
+        return Err(bad_request("Give a heading, line or scroll position"));
+    }
+    if req.scroll.is_some_and(|scroll| !(0.0..=1.0).contains(&scroll)) {
+        return Err(bad_request("scroll must be between 0 and 1"));
+    }
+    if let Some(note) = state.store.get(uuid).await {
+        authorize(&permissions, AccessLevel::Read, &note)?;
+    }
+
+    let position = state
         .store
-        .create(req.title, req.content, req.tags)
+        .set_read_position(uuid, &user_key(user.as_ref()), req.heading, req.line, req.scroll)
         .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: e.to_string(),
-                }),
-            )
-        })?;
-
-    // Index the note for fulltext search
-    if let Err(e) = state.fulltext.index_note(&note) {
-        tracing::warn!("Failed to index note: {}", e);
-    }
-    let _ = state.fulltext.commit();
+        .map_err(access_error)?;
+    Ok(Json(position))
+}
 
-    // Index chunks for semantic search
-    index_note_chunks(&state, &note).await;
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StarredResponse {
+    /// Starred notes, most recently starred first
+    pub notes: Vec<NoteMeta>,
+    pub total: usize,
+}
 
-    let tags = note.tags();
-    Ok((
-        StatusCode::CREATED,
-        Json(NoteResponse {
-            id: note.id.to_string(),
-            title: note.title,
-            slug: note.slug,
-            content: note.content,
-            tags,
-            created_at: note.created_at.to_rfc3339(),
-            updated_at: note.updated_at.to_rfc3339(),
-            is_pinned: note.is_pinned,
-            is_archived: note.is_archived,
-        }),
-    ))
+/// List the notes the user starred
+#[utoipa::path(
+    get,
+    path = "/api/notes/starred",
+    responses(
+        (status = 200, description = "Starred notes", body = StarredResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "notes"
+)]
+pub async fn list_starred(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
+) -> Result<Json<StarredResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let notes: Vec<NoteMeta> = state
+        .store
+        .starred(&user_key(user.as_ref()))
+        .await
+        .map_err(access_error)?
+        .iter()
+        .filter(|n| permissions.can_read(n))
+        .map(NoteMeta::from)
+        .collect();
+
+    let total = notes.len();
+    Ok(Json(StarredResponse { notes, total }))
 }
 
-/// Update an existing note
+/// Star a note for the user
 #[utoipa::path(
     put,
-    path = "/api/notes/{id}",
+    path = "/api/notes/{id}/star",
     params(
         ("id" = String, Path, description = "Note UUID")
     ),
-    request_body = UpdateNoteRequest,
     responses(
-        (status = 200, description = "Note updated", body = NoteResponse),
+        (status = 204, description = "Note starred"),
         (status = 400, description = "Invalid note ID", body = ErrorResponse),
-        (status = 500, description = "Internal error", body = ErrorResponse)
+        (status = 404, description = "Note not found", body = ErrorResponse)
     ),
     tag = "notes"
 )]
-pub async fn update_note(
+pub async fn star_note(
     State(state): State<AppState>,
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
     Path(id): Path<String>,
-    Json(req): Json<UpdateNoteRequest>,
-) -> Result<Json<NoteResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: "Invalid note ID".into(),
-            }),
-        )
-    })?;
-
-    let note = state
-        .store
-        .update_full(uuid, req.title, req.content, req.tags, req.is_pinned, req.is_archived)
-        .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: e.to_string(),
-                }),
-            )
-        })?;
-
-    // Re-index for fulltext search
-    if let Err(e) = state.fulltext.index_note(&note) {
-        tracing::warn!("Failed to re-index note: {}", e);
-    }
-    let _ = state.fulltext.commit();
-
-    // Re-index chunks for semantic search (remove old, add new)
-    remove_note_chunks(&state, uuid).await;
-    index_note_chunks(&state, &note).await;
-
-    let tags = note.tags();
-    Ok(Json(NoteResponse {
-        id: note.id.to_string(),
-        title: note.title,
-        slug: note.slug,
-        content: note.content,
-        tags,
-        created_at: note.created_at.to_rfc3339(),
-        updated_at: note.updated_at.to_rfc3339(),
-        is_pinned: note.is_pinned,
-        is_archived: note.is_archived,
-    }))
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    set_starred(&state, &permissions, user.as_ref(), &id, true).await
 }
 
-/// Delete a note (soft delete)
+/// Remove the user's star from a note
 #[utoipa::path(
     delete,
-    path = "/api/notes/{id}",
+    path = "/api/notes/{id}/star",
     params(
         ("id" = String, Path, description = "Note UUID")
     ),
     responses(
-        (status = 204, description = "Note deleted"),
+        (status = 204, description = "Star removed"),
         (status = 400, description = "Invalid note ID", body = ErrorResponse),
-        (status = 500, description = "Internal error", body = ErrorResponse)
+        (status = 404, description = "Note not found", body = ErrorResponse)
     ),
     tag = "notes"
 )]
-pub async fn delete_note(
+pub async fn unstar_note(
     State(state): State<AppState>,
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
     Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    set_starred(&state, &permissions, user.as_ref(), &id, false).await
+}
+
+async fn set_starred(
+    state: &AppState,
+    permissions: &Permissions,
+    user: Option<&Extension<CurrentUser>>,
+    id: &str,
+    starred: bool,
 ) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
     let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
         (
@@ -462,186 +1000,295 @@ pub async fn delete_note(
             }),
         )
     })?;
+    if let Some(note) = state.store.get(uuid).await {
+        authorize(permissions, AccessLevel::Read, &note)?;
+    }
+
+    state
+        .store
+        .set_starred(uuid, &user_key(user), starred)
+        .await
+        .map_err(access_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AnnotationsResponse {
+    /// Annotations on the note, oldest first
+    pub annotations: Vec<Annotation>,
+    pub total: usize,
+}
+
+/// Request to comment on a note
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateAnnotationRequest {
+    pub body: String,
+    /// Heading of the section to comment on
+    pub heading: Option<String>,
+    /// First line to comment on, from 1 over the whole file
+    pub line_start: Option<u32>,
+    /// Last line to comment on, inclusive; defaults to line_start
+    pub line_end: Option<u32>,
+    /// Annotation to reply to; the reply takes its anchor
+    pub reply_to: Option<String>,
+}
+
+/// Request to edit an annotation
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateAnnotationRequest {
+    pub body: String,
+}
+
+fn annotation_error(e: Error) -> (StatusCode, Json<ErrorResponse>) {
+    let status = match e {
+        Error::NoteNotFound(_) | Error::AnnotationNotFound(_) => StatusCode::NOT_FOUND,
+        Error::InvalidAnnotation(_) => StatusCode::BAD_REQUEST,
+        Error::AccessDenied(_) => StatusCode::FORBIDDEN,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
+}
 
-    state.store.delete(uuid).await.map_err(|e| {
+/// The note an annotation request is about, once the caller may read it
+async fn annotated_note(
+    state: &AppState,
+    permissions: &Permissions,
+    id: &str,
+) -> Result<uuid::Uuid, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
         (
-            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::BAD_REQUEST,
             Json(ErrorResponse {
-                error: e.to_string(),
+                error: "Invalid note ID".into(),
             }),
         )
     })?;
+    let note = state
+        .store
+        .get(uuid)
+        .await
+        .filter(|n| !n.is_deleted)
+        .ok_or_else(|| annotation_error(Error::NoteNotFound(id.to_string())))?;
+    authorize(permissions, AccessLevel::Read, &note)?;
+    Ok(uuid)
+}
 
-    // Remove from fulltext index
-    let _ = state.fulltext.delete_note(&id);
-    let _ = state.fulltext.commit();
-
-    // Remove chunks from semantic search
-    remove_note_chunks(&state, uuid).await;
-
-    Ok(StatusCode::NO_CONTENT)
+/// Only an annotation's author may change it, unless the caller can reach
+/// every note
+fn authorize_author(
+    permissions: &Permissions,
+    user: Option<&Extension<CurrentUser>>,
+    annotation: &Annotation,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let author = annotation.author.clone().unwrap_or_default();
+    if permissions.is_restricted() && author != user_key(user) {
+        return Err(annotation_error(Error::AccessDenied(
+            "only the author can change an annotation".into(),
+        )));
+    }
+    Ok(())
 }
 
-/// Full-text search across notes
+/// List the annotations on a note
 #[utoipa::path(
     get,
-    path = "/api/search",
-    params(SearchParams),
+    path = "/api/notes/{id}/annotations",
+    params(
+        ("id" = String, Path, description = "Note UUID")
+    ),
     responses(
-        (status = 200, description = "Search results", body = SearchResponse)
+        (status = 200, description = "Annotations on the note", body = AnnotationsResponse),
+        (status = 400, description = "Invalid note ID", body = ErrorResponse),
+        (status = 403, description = "Caller can't read the note", body = ErrorResponse),
+        (status = 404, description = "Note not found", body = ErrorResponse)
     ),
-    tag = "search"
+    tag = "notes"
 )]
-pub async fn search(
+pub async fn list_annotations(
     State(state): State<AppState>,
-    Query(params): Query<SearchParams>,
-) -> Json<SearchResponse> {
-    let results = state
-        .fulltext
-        .search(&params.q, params.limit)
-        .unwrap_or_default();
+    permissions: Permissions,
+    Path(id): Path<String>,
+) -> Result<Json<AnnotationsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = annotated_note(&state, &permissions, &id).await?;
+    let annotations = state.store.annotations(uuid).map_err(annotation_error)?;
+    let total = annotations.len();
+    Ok(Json(AnnotationsResponse { annotations, total }))
+}
 
-    // Enrich with note metadata
-    let mut enriched = Vec::new();
-    for mut result in results {
-        if let Ok(uuid) = result.note_id.parse::<uuid::Uuid>() {
-            if let Some(note) = state.store.get(uuid).await {
-                result.tags = note.tags();
-                result.updated_at = Some(note.updated_at.to_rfc3339());
-                enriched.push(result);
-            }
-        }
+/// Comment on a note without editing it
+///
+/// Anyone who can read the note can annotate it. The annotation is kept in
+/// the metadata database, never in the note's file.
+#[utoipa::path(
+    post,
+    path = "/api/notes/{id}/annotations",
+    params(
+        ("id" = String, Path, description = "Note UUID")
+    ),
+    request_body = CreateAnnotationRequest,
+    responses(
+        (status = 201, description = "Annotation added", body = Annotation),
+        (status = 400, description = "Invalid note ID, empty body or unknown anchor", body = ErrorResponse),
+        (status = 403, description = "Caller can't read the note", body = ErrorResponse),
+        (status = 404, description = "Note or annotation replied to not found", body = ErrorResponse)
+    ),
+    tag = "notes"
+)]
+pub async fn create_annotation(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
+    Path(id): Path<String>,
+    Json(req): Json<CreateAnnotationRequest>,
+) -> Result<(StatusCode, Json<Annotation>), (StatusCode, Json<ErrorResponse>)> {
+    let uuid = annotated_note(&state, &permissions, &id).await?;
+    let lines = req
+        .line_start
+        .map(|start| (start, req.line_end.unwrap_or(start)));
+    if lines.is_none() && req.line_end.is_some() {
+        return Err(annotation_error(Error::InvalidAnnotation(
+            "line_end needs a line_start".into(),
+        )));
     }
+    let author = Some(user_key(user.as_ref())).filter(|name| !name.is_empty());
 
-    let total = enriched.len();
-    Json(SearchResponse { results: enriched, total })
+    let annotation = state
+        .store
+        .annotate(uuid, author, req.body, req.heading, lines, req.reply_to)
+        .await
+        .map_err(annotation_error)?;
+    Ok((StatusCode::CREATED, Json(annotation)))
 }
 
-/// Semantic search using embeddings
+/// Edit an annotation's text
 #[utoipa::path(
-    get,
-    path = "/api/search/semantic",
-    params(SearchParams),
+    put,
+    path = "/api/notes/{id}/annotations/{annotation_id}",
+    params(
+        ("id" = String, Path, description = "Note UUID"),
+        ("annotation_id" = String, Path, description = "Annotation ID")
+    ),
+    request_body = UpdateAnnotationRequest,
     responses(
-        (status = 200, description = "Semantic search results", body = SearchResponse)
+        (status = 200, description = "Annotation updated", body = Annotation),
+        (status = 400, description = "Invalid note ID or empty body", body = ErrorResponse),
+        (status = 403, description = "Caller didn't write the annotation", body = ErrorResponse),
+        (status = 404, description = "Note or annotation not found", body = ErrorResponse)
     ),
-    tag = "search"
+    tag = "notes"
 )]
-pub async fn semantic_search(
+pub async fn update_annotation(
     State(state): State<AppState>,
-    Query(params): Query<SearchParams>,
-) -> Json<SearchResponse> {
-    let semantic = state.semantic.read().await;
-    let results = semantic
-        .search(&params.q, params.limit)
-        .await
-        .unwrap_or_default();
-
-    // Enrich with note metadata and filter out results where note doesn't exist
-    let mut enriched = Vec::new();
-    for mut result in results {
-        if let Ok(uuid) = result.note_id.parse::<uuid::Uuid>() {
-            if let Some(note) = state.store.get(uuid).await {
-                result.title = note.title.clone();
-                result.tags = note.tags();
-                result.updated_at = Some(note.updated_at.to_rfc3339());
-                enriched.push(result);
-            } else {
-                // Skip results where the note no longer exists
-                tracing::debug!("Skipping search result for missing note: {}", result.note_id);
-            }
-        }
-    }
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
+    Path((id, annotation_id)): Path<(String, String)>,
+    Json(req): Json<UpdateAnnotationRequest>,
+) -> Result<Json<Annotation>, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = annotated_note(&state, &permissions, &id).await?;
+    let annotation = state
+        .store
+        .annotation(uuid, &annotation_id)
+        .map_err(annotation_error)?;
+    authorize_author(&permissions, user.as_ref(), &annotation)?;
 
-    let total = enriched.len();
-    Json(SearchResponse {
-        results: enriched,
-        total,
-    })
+    let annotation = state
+        .store
+        .update_annotation(uuid, &annotation_id, req.body)
+        .map_err(annotation_error)?;
+    Ok(Json(annotation))
 }
 
-/// Find notes related to a given note
+/// Delete an annotation, and its replies if it opened a thread
 #[utoipa::path(
-    get,
-    path = "/api/notes/{id}/related",
+    delete,
+    path = "/api/notes/{id}/annotations/{annotation_id}",
     params(
         ("id" = String, Path, description = "Note UUID"),
-        ListParams
+        ("annotation_id" = String, Path, description = "Annotation ID")
     ),
     responses(
-        (status = 200, description = "Related notes", body = SearchResponse),
+        (status = 204, description = "Annotation deleted"),
         (status = 400, description = "Invalid note ID", body = ErrorResponse),
-        (status = 500, description = "Internal error", body = ErrorResponse)
+        (status = 403, description = "Caller didn't write the annotation", body = ErrorResponse),
+        (status = 404, description = "Note or annotation not found", body = ErrorResponse)
     ),
-    tag = "search"
+    tag = "notes"
 )]
-pub async fn find_related(
+pub async fn delete_annotation(
     State(state): State<AppState>,
-    Path(id): Path<String>,
-    Query(params): Query<ListParams>,
-) -> Result<Json<SearchResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: "Invalid note ID".into(),
-            }),
-        )
-    })?;
-
-    let semantic = state.semantic.read().await;
-    let results = semantic
-        .find_similar(uuid, params.limit)
-        .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: e.to_string(),
-                }),
-            )
-        })?;
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
+    Path((id, annotation_id)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = annotated_note(&state, &permissions, &id).await?;
+    let annotation = state
+        .store
+        .annotation(uuid, &annotation_id)
+        .map_err(annotation_error)?;
+    authorize_author(&permissions, user.as_ref(), &annotation)?;
 
-    let total = results.len();
-    Ok(Json(SearchResponse { results, total }))
+    state
+        .store
+        .delete_annotation(uuid, &annotation_id)
+        .map_err(annotation_error)?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
-/// Quick capture content as a new note
+/// Create a new note
 #[utoipa::path(
     post,
-    path = "/api/capture",
-    request_body = CaptureRequest,
+    path = "/api/notes",
+    request_body = CreateNoteRequest,
     responses(
-        (status = 201, description = "Capture created", body = NoteResponse),
+        (status = 201, description = "Note created", body = NoteResponse),
+        (status = 400, description = "Invalid folder", body = ErrorResponse),
+        (status = 403, description = "No write access to the folder", body = ErrorResponse),
         (status = 500, description = "Internal error", body = ErrorResponse)
     ),
     tag = "notes"
 )]
-pub async fn quick_capture(
+pub async fn create_note(
     State(state): State<AppState>,
-    Json(req): Json<CaptureRequest>,
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
+    Json(req): Json<CreateNoteRequest>,
 ) -> Result<(StatusCode, Json<NoteResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let folder = PathBuf::from(req.folder.unwrap_or_default());
+    permissions
+        .check_create(&folder, req.tags.as_deref().unwrap_or_default())
+        .map_err(access_error)?;
+
     let note = state
         .store
-        .quick_capture(req.content, req.source)
+        .create_in(&folder, req.title, req.content, req.tags)
         .await
         .map_err(|e| {
+            let status = match e {
+                Error::InvalidNotePath(_) => StatusCode::BAD_REQUEST,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
             (
-                StatusCode::INTERNAL_SERVER_ERROR,
+                status,
                 Json(ErrorResponse {
                     error: e.to_string(),
                 }),
             )
         })?;
+    let note = attribute_change(&state, user, note, Contribution::Created).await;
 
-    // Index for fulltext search
+    // Index the note for fulltext search
     if let Err(e) = state.fulltext.index_note(&note) {
-        tracing::warn!("Failed to index capture: {}", e);
+        tracing::warn!("Failed to index note: {}", e);
     }
     let _ = state.fulltext.commit();
 
     // Index chunks for semantic search
-    index_note_chunks(&state, &note).await;
+    index_note_chunks(&state, &note);
 
     let tags = note.tags();
     Ok((
@@ -654,260 +1301,3164 @@ pub async fn quick_capture(
             tags,
             created_at: note.created_at.to_rfc3339(),
             updated_at: note.updated_at.to_rfc3339(),
+            created_by: note.created_by,
+            updated_by: note.updated_by,
             is_pinned: note.is_pinned,
             is_archived: note.is_archived,
+            position: None,
         }),
     ))
 }
 
-/// List all unique tags
+/// Create a note from a template
 #[utoipa::path(
-    get,
-    path = "/api/tags",
+    post,
+    path = "/api/notes/from-template",
+    request_body = FromTemplateRequest,
     responses(
-        (status = 200, description = "List of tags", body = TagsResponse)
+        (status = 201, description = "Note created", body = NoteResponse),
+        (status = 400, description = "Invalid folder", body = ErrorResponse),
+        (status = 403, description = "No write access to the folder", body = ErrorResponse),
+        (status = 404, description = "Template not found", body = ErrorResponse),
+        (status = 409, description = "A note with this title exists", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
     ),
-    tag = "metadata"
+    tag = "templates"
 )]
-pub async fn list_tags(State(state): State<AppState>) -> Json<TagsResponse> {
-    let notes = state.store.list().await;
-    let mut tags = std::collections::HashSet::new();
+pub async fn create_from_template(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
+    Json(req): Json<FromTemplateRequest>,
+) -> Result<(StatusCode, Json<NoteResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let folder = PathBuf::from(req.folder.unwrap_or_default());
+    permissions.check_create(&folder, &[]).map_err(access_error)?;
 
-    for note in &notes {
-        for tag in note.tags() {
-            tags.insert(tag);
-        }
+    let note = state
+        .store
+        .create_from_template(&req.template, &folder, req.title, req.clipboard)
+        .await
+        .map_err(|e| {
+            let status = match e {
+                Error::TemplateNotFound(_) => StatusCode::NOT_FOUND,
+                Error::InvalidNotePath(_) => StatusCode::BAD_REQUEST,
+                Error::NoteAlreadyExists(_) => StatusCode::CONFLICT,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (
+                status,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+        })?;
+    let note = attribute_change(&state, user, note, Contribution::Created).await;
+
+    // Index the note for fulltext search
+    if let Err(e) = state.fulltext.index_note(&note) {
+        tracing::warn!("Failed to index note: {}", e);
     }
+    let _ = state.fulltext.commit();
 
-    let mut sorted: Vec<_> = tags.into_iter().collect();
-    sorted.sort();
+    // Index chunks for semantic search
+    index_note_chunks(&state, &note);
 
-    Json(TagsResponse { tags: sorted })
+    let tags = note.tags();
+    Ok((
+        StatusCode::CREATED,
+        Json(NoteResponse {
+            id: note.id.to_string(),
+            title: note.title,
+            slug: note.slug,
+            content: note.content,
+            tags,
+            created_at: note.created_at.to_rfc3339(),
+            updated_at: note.updated_at.to_rfc3339(),
+            created_by: note.created_by,
+            updated_by: note.updated_by,
+            is_pinned: note.is_pinned,
+            is_archived: note.is_archived,
+            position: None,
+        }),
+    ))
 }
 
-/// Get vault statistics
+/// List note templates
 #[utoipa::path(
     get,
-    path = "/api/stats",
+    path = "/api/templates",
     responses(
-        (status = 200, description = "Vault statistics", body = StatsResponse)
+        (status = 200, description = "Template names", body = TemplatesResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
     ),
-    tag = "metadata"
+    tag = "templates"
 )]
-pub async fn get_stats(State(state): State<AppState>) -> Json<StatsResponse> {
-    let notes = state.store.list().await;
-    let note_count = notes.iter().filter(|n| !n.is_deleted).count();
-
-    let semantic = state.semantic.read().await;
-    let chunk_count = semantic.chunk_count();
-
-    let mut tags = std::collections::HashSet::new();
-    for note in &notes {
-        for tag in note.tags() {
-            tags.insert(tag.to_lowercase());
-        }
-    }
-
-    Json(StatsResponse {
-        note_count,
-        chunk_count,
-        tag_count: tags.len(),
-    })
-}
-
-/// Detect image type from magic bytes
-fn detect_image_type(data: &[u8]) -> Option<&'static str> {
-    if data.len() < 8 {
-        return None;
-    }
-
-    // PNG: 89 50 4E 47 0D 0A 1A 0A
-    if data.starts_with(&[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A]) {
-        return Some("png");
-    }
-
-    // JPEG: FF D8 FF
-    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
-        return Some("jpg");
-    }
-
-    // GIF: GIF87a or GIF89a
-    if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
-        return Some("gif");
-    }
-
-    // WebP: RIFF....WEBP
-    if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
-        return Some("webp");
-    }
-
-    // SVG: starts with <?xml or <svg (with possible whitespace)
-    let text_start = String::from_utf8_lossy(&data[..data.len().min(100)]);
-    let trimmed = text_start.trim_start();
-    if trimmed.starts_with("<?xml") || trimmed.starts_with("<svg") {
-        return Some("svg");
-    }
+pub async fn list_templates(
+    State(state): State<AppState>,
+) -> Result<Json<TemplatesResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let templates = state.store.templates().list().await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    })?;
 
-    None
+    Ok(Json(TemplatesResponse { templates }))
 }
 
-/// Upload an attachment (image)
+/// Update an existing note
 #[utoipa::path(
-    post,
-    path = "/api/attachments",
-    request_body = UploadAttachmentRequest,
+    put,
+    path = "/api/notes/{id}",
+    params(
+        ("id" = String, Path, description = "Note UUID")
+    ),
+    request_body = UpdateNoteRequest,
     responses(
-        (status = 201, description = "Attachment uploaded", body = AttachmentResponse),
-        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 200, description = "Note updated", body = NoteResponse),
+        (status = 400, description = "Invalid note ID", body = ErrorResponse),
+        (status = 403, description = "No write access to the note", body = ErrorResponse),
         (status = 500, description = "Internal error", body = ErrorResponse)
     ),
-    tag = "attachments"
+    tag = "notes"
 )]
-pub async fn upload_attachment(
+pub async fn update_note(
     State(state): State<AppState>,
-    Json(req): Json<UploadAttachmentRequest>,
-) -> Result<(StatusCode, Json<AttachmentResponse>), (StatusCode, Json<ErrorResponse>)> {
-    use base64::Engine;
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
+    Path(id): Path<String>,
+    Json(req): Json<UpdateNoteRequest>,
+) -> Result<Json<NoteResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Invalid note ID".into(),
+            }),
+        )
+    })?;
+    if let Some(note) = state.store.get(uuid).await {
+        authorize(&permissions, AccessLevel::Write, &note)?;
+        let mut tags = req.tags.clone().unwrap_or_else(|| note.tags());
+        tags.extend(req.content.as_deref().map(frontmatter_tags).unwrap_or_default());
+        authorize_tags(&permissions, &note, &tags)?;
+    }
 
-    // Decode base64 data first so we can detect image type from magic bytes
-    let data = base64::engine::general_purpose::STANDARD
-        .decode(&req.data)
+    let note = state
+        .store
+        .update_full(uuid, req.title, req.content, req.tags, req.is_pinned, req.is_archived)
+        .await
         .map_err(|e| {
             (
-                StatusCode::BAD_REQUEST,
+                StatusCode::INTERNAL_SERVER_ERROR,
                 Json(ErrorResponse {
-                    error: format!("Invalid base64 data: {}", e),
+                    error: e.to_string(),
                 }),
             )
         })?;
+    let note = attribute_change(&state, user, note, Contribution::Updated).await;
 
-    // Detect image type from magic bytes if mime_type is empty or not provided
-    let extension = if !req.mime_type.is_empty() {
-        match req.mime_type.as_str() {
-            "image/png" => "png",
-            "image/jpeg" | "image/jpg" => "jpg",
-            "image/gif" => "gif",
-            "image/webp" => "webp",
-            "image/svg+xml" => "svg",
-            _ => {
-                return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(ErrorResponse {
-                        error: format!("Unsupported image type: {}", req.mime_type),
-                    }),
-                ))
-            }
-        }
-    } else {
-        // Detect from magic bytes
-        detect_image_type(&data).ok_or_else(|| {
-            (
-                StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: "Could not detect image type. Please provide mime_type.".to_string(),
-                }),
-            )
-        })?
-    };
+    // Re-index for fulltext search
+    if let Err(e) = state.fulltext.index_note(&note) {
+        tracing::warn!("Failed to re-index note: {}", e);
+    }
+    let _ = state.fulltext.commit();
 
-    // Generate filename
-    let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S");
-    let uuid_suffix = uuid::Uuid::new_v4().to_string()[..8].to_string();
-    let filename = req.filename.map(|f| {
-        // Sanitize and use provided filename
-        let stem = std::path::Path::new(&f)
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("image");
-        let sanitized: String = stem
-            .chars()
-            .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
-            .take(50)
-            .collect();
-        format!("{}_{}.{}", sanitized, uuid_suffix, extension)
-    }).unwrap_or_else(|| format!("{}_{}.{}", timestamp, uuid_suffix, extension));
+    // Re-index chunks for semantic search (remove old, add new)
+    remove_note_chunks(&state, uuid);
+    index_note_chunks(&state, &note);
 
-    // Ensure attachments directory exists
-    std::fs::create_dir_all(&state.attachments_path).map_err(|e| {
+    let tags = note.tags();
+    Ok(Json(NoteResponse {
+        id: note.id.to_string(),
+        title: note.title,
+        slug: note.slug,
+        content: note.content,
+        tags,
+        created_at: note.created_at.to_rfc3339(),
+        updated_at: note.updated_at.to_rfc3339(),
+        created_by: note.created_by,
+        updated_by: note.updated_by,
+        is_pinned: note.is_pinned,
+        is_archived: note.is_archived,
+        position: None,
+    }))
+}
+
+/// Delete a note (moves it to the trash unless `permanent` is set)
+#[utoipa::path(
+    delete,
+    path = "/api/notes/{id}",
+    params(
+        ("id" = String, Path, description = "Note UUID"),
+        DeleteParams
+    ),
+    responses(
+        (status = 204, description = "Note deleted"),
+        (status = 400, description = "Invalid note ID", body = ErrorResponse),
+        (status = 403, description = "No write access to the note", body = ErrorResponse),
+        (status = 404, description = "Note not found", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "notes"
+)]
+pub async fn delete_note(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path(id): Path<String>,
+    Query(params): Query<DeleteParams>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
         (
-            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::BAD_REQUEST,
             Json(ErrorResponse {
-                error: format!("Failed to create attachments directory: {}", e),
+                error: "Invalid note ID".into(),
             }),
         )
     })?;
+    match state.store.get(uuid).await {
+        Some(note) => authorize(&permissions, AccessLevel::Write, &note)?,
+        // Trashed notes aren't cached after a restart
+        None => authorize_trashed(&state, &permissions, uuid).await?,
+    }
+    let attachments = if params.delete_attachments {
+        state.store.note_attachments(uuid).await.unwrap_or_default()
+    } else {
+        Vec::new()
+    };
 
-    // Write file
-    let file_path = state.attachments_path.join(&filename);
-    std::fs::write(&file_path, &data).map_err(|e| {
+    let result = if params.permanent {
+        state.store.delete_permanently(uuid).await
+    } else {
+        state.store.delete(uuid).await
+    };
+    result.map_err(|e| {
+        let status = match e {
+            Error::NoteNotFound(_) => StatusCode::NOT_FOUND,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
         (
-            StatusCode::INTERNAL_SERVER_ERROR,
+            status,
             Json(ErrorResponse {
-                error: format!("Failed to write attachment: {}", e),
+                error: e.to_string(),
             }),
         )
     })?;
 
-    let url = format!("/api/attachments/{}", filename);
-    let markdown = format!("![{}]({})", filename, url);
-
-    tracing::info!("Uploaded attachment: {}", filename);
+    // The indexing queue purges the note from the search indexes
+    if let Err(e) = state
+        .store
+        .delete_unshared_attachments(uuid, &attachments)
+        .await
+    {
+        tracing::warn!("Failed to delete attachments of {}: {}", uuid, e);
+    }
 
-    Ok((
-        StatusCode::CREATED,
-        Json(AttachmentResponse {
-            filename,
-            url,
-            markdown,
-        }),
-    ))
+    Ok(StatusCode::NO_CONTENT)
 }
 
-/// Get an attachment by filename
-pub async fn get_attachment(
+/// List notes in the trash
+#[utoipa::path(
+    get,
+    path = "/api/trash",
+    responses(
+        (status = 200, description = "Deleted notes", body = TrashResponse)
+    ),
+    tag = "trash"
+)]
+pub async fn list_trash(
     State(state): State<AppState>,
-    Path(filename): Path<String>,
-) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<ErrorResponse>)> {
-    use axum::http::header;
-
-    // Sanitize filename to prevent directory traversal
-    let sanitized: String = filename
-        .chars()
-        .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_' || *c == '.')
+    permissions: Permissions,
+) -> Json<TrashResponse> {
+    // Tags aren't kept for trashed notes, so only folder scopes apply
+    let notes: Vec<TrashedNote> = state
+        .store
+        .list_trash()
+        .await
+        .iter()
+        .filter(|e| permissions.allows(AccessLevel::Read, &e.original_path, &[]))
+        .map(TrashedNote::from)
         .collect();
 
-    if sanitized.is_empty() || sanitized.contains("..") {
-        return Err((
+    let total = notes.len();
+    Json(TrashResponse { notes, total })
+}
+
+/// Restore a deleted note to its original location
+#[utoipa::path(
+    post,
+    path = "/api/trash/{id}/restore",
+    params(
+        ("id" = String, Path, description = "Note UUID")
+    ),
+    responses(
+        (status = 200, description = "Note restored", body = NoteResponse),
+        (status = 400, description = "Invalid note ID", body = ErrorResponse),
+        (status = 403, description = "No write access to the original folder", body = ErrorResponse),
+        (status = 404, description = "Note not in trash", body = ErrorResponse),
+        (status = 409, description = "Original path is taken", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "trash"
+)]
+pub async fn restore_from_trash(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path(id): Path<String>,
+) -> Result<Json<NoteResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
+        (
             StatusCode::BAD_REQUEST,
             Json(ErrorResponse {
-                error: "Invalid filename".into(),
+                error: "Invalid note ID".into(),
             }),
-        ));
+        )
+    })?;
+    authorize_trashed(&state, &permissions, uuid).await?;
+
+    let note = state.store.restore_from_trash(uuid).await.map_err(|e| {
+        let status = match e {
+            Error::NoteNotFound(_) => StatusCode::NOT_FOUND,
+            Error::NoteAlreadyExists(_) => StatusCode::CONFLICT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (
+            status,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    })?;
+
+    // Index the note for fulltext search
+    if let Err(e) = state.fulltext.index_note(&note) {
+        tracing::warn!("Failed to index note: {}", e);
     }
+    let _ = state.fulltext.commit();
 
-    let file_path = state.attachments_path.join(&sanitized);
+    // Index chunks for semantic search
+    index_note_chunks(&state, &note);
 
-    if !file_path.exists() {
-        return Err((
-            StatusCode::NOT_FOUND,
+    let tags = note.tags();
+    Ok(Json(NoteResponse {
+        id: note.id.to_string(),
+        title: note.title,
+        slug: note.slug,
+        content: note.content,
+        tags,
+        created_at: note.created_at.to_rfc3339(),
+        updated_at: note.updated_at.to_rfc3339(),
+        created_by: note.created_by,
+        updated_by: note.updated_by,
+        is_pinned: note.is_pinned,
+        is_archived: note.is_archived,
+        position: None,
+    }))
+}
+
+/// Permanently delete a note from the trash
+#[utoipa::path(
+    delete,
+    path = "/api/trash/{id}",
+    params(
+        ("id" = String, Path, description = "Note UUID")
+    ),
+    responses(
+        (status = 204, description = "Note purged"),
+        (status = 400, description = "Invalid note ID", body = ErrorResponse),
+        (status = 403, description = "No write access to the original folder", body = ErrorResponse),
+        (status = 404, description = "Note not in trash", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "trash"
+)]
+pub async fn purge_from_trash(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
             Json(ErrorResponse {
-                error: "Attachment not found".into(),
+                error: "Invalid note ID".into(),
             }),
-        ));
+        )
+    })?;
+    authorize_trashed(&state, &permissions, uuid).await?;
+
+    state.store.purge_from_trash(uuid).await.map_err(|e| {
+        let status = match e {
+            Error::NoteNotFound(_) => StatusCode::NOT_FOUND,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (
+            status,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    })?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Require write access to where a trashed note was deleted from
+async fn authorize_trashed(
+    state: &AppState,
+    permissions: &Permissions,
+    id: uuid::Uuid,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    match state.store.get_trashed(id).await {
+        Some(entry) => permissions
+            .check(AccessLevel::Write, id, &entry.original_path, &[])
+            .map_err(access_error),
+        None => Ok(()),
     }
+}
 
-    let data = std::fs::read(&file_path).map_err(|e| {
+/// Drop items referring to notes the caller can't read
+async fn readable_only<T>(
+    state: &AppState,
+    permissions: &Permissions,
+    items: Vec<T>,
+    note_id: impl Fn(&T) -> &String,
+) -> Vec<T> {
+    if !permissions.is_restricted() {
+        return items;
+    }
+    let mut readable = Vec::new();
+    for item in items {
+        let note = match note_id(&item).parse::<uuid::Uuid>() {
+            Ok(uuid) => state.store.get(uuid).await,
+            Err(_) => None,
+        };
+        if note.is_some_and(|n| permissions.can_read(&n)) {
+            readable.push(item);
+        }
+    }
+    readable
+}
+
+/// Search across notes (in the caller's default mode, or full-text,
+/// semantic or hybrid via `mode`)
+#[utoipa::path(
+    get,
+    path = "/api/search",
+    params(SearchParams),
+    responses(
+        (status = 200, description = "Search results", body = SearchResponse),
+        (status = 400, description = "Unknown ranking profile", body = ErrorResponse)
+    ),
+    tag = "search"
+)]
+pub async fn search(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<SearchResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let config = &state.store.config().search;
+    let mode = params.mode.unwrap_or_else(|| {
+        let user = user.as_ref().map(|Extension(user)| user.name.as_str());
+        default_mode(config, SearchClient::Http, user)
+    });
+    let request = search_request(&state, &params, mode, &permissions)?;
+    Ok(Json(run_search(&state, &request, &params, &permissions).await))
+}
+
+/// Semantic search using embeddings
+#[utoipa::path(
+    get,
+    path = "/api/search/semantic",
+    params(SearchParams),
+    responses(
+        (status = 200, description = "Semantic search results", body = SearchResponse),
+        (status = 400, description = "Unknown ranking profile", body = ErrorResponse)
+    ),
+    tag = "search"
+)]
+pub async fn semantic_search(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<SearchResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let request = search_request(&state, &params, SearchMode::Semantic, &permissions)?;
+    Ok(Json(run_search(&state, &request, &params, &permissions).await))
+}
+
+/// The search `params` ask for, in `mode`
+fn search_request(
+    state: &AppState,
+    params: &SearchParams,
+    mode: SearchMode,
+    permissions: &Permissions,
+) -> Result<SearchRequest, (StatusCode, Json<ErrorResponse>)> {
+    let config = &state.store.config().search;
+    let bad_request = |e: Error| {
         (
-            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse { error: e.to_string() }),
+        )
+    };
+    SearchQuery::parse(&params.q).map_err(bad_request)?;
+    let dates = |after: &Option<String>, before: &Option<String>| {
+        [(after, DateOp::After), (before, DateOp::Before)]
+            .into_iter()
+            .filter_map(|(date, op)| date.as_deref().map(|date| DateFilter::new(op, date)))
+            .collect::<crate::error::Result<Vec<_>>>()
+    };
+    let created = dates(&params.created_after, &params.created_before).map_err(bad_request)?;
+    let updated = dates(&params.updated_after, &params.updated_before).map_err(bad_request)?;
+    let boosts = config.field_boosts(params.profile.as_deref()).ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
             Json(ErrorResponse {
-                error: format!("Failed to read attachment: {}", e),
+                error: format!("Unknown ranking profile: {}", params.profile.as_deref().unwrap_or("")),
             }),
         )
     })?;
+    let recency = params.recency.then(|| {
+        let mut recency = config.recency.clone();
+        if let Some(half_life_days) = params.half_life_days {
+            recency.half_life_days = half_life_days;
+        }
+        recency
+    });
+    Ok(SearchRequest {
+        query: params.q.clone(),
+        mode,
+        limit: candidate_limit(state, params, permissions),
+        snippets: config.snippet_options(params.snippet_len, params.include_full_chunk),
+        boosts,
+        fuzzy: params.fuzzy,
+        query_type: params.query_type,
+        strategy: params.strategy,
+        recency,
+        tags: params
+            .tags
+            .iter()
+            .flat_map(|t| t.split(','))
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect(),
+        tag_match: params.tag_match,
+        created,
+        updated,
+        sort: NoteSort::new(params.sort, params.order),
+    })
+}
 
-    let mime = mime_guess::from_path(&file_path)
-        .first_or_octet_stream()
-        .to_string();
+/// Run `request`, answering failed searches with no results
+async fn run_search(
+    state: &AppState,
+    request: &SearchRequest,
+    params: &SearchParams,
+    permissions: &Permissions,
+) -> SearchResponse {
+    let results = SearchDispatcher::new(&state.store, &state.fulltext, &state.semantic)
+        .search(request)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!("{} search failed: {}", request.mode, e);
+            Vec::new()
+        });
+    let enriched = enrich_results(state, results, params, permissions).await;
+    let total = enriched.len();
+    SearchResponse { results: enriched, total }
+}
 
-    Ok(([(header::CONTENT_TYPE, mime)], data))
+/// How many results to fetch before filtering by author and scopes
+///
+/// Neither authorship nor scopes are indexed, so filtering searches as deep
+/// as allowed and trims the survivors to the requested limit.
+fn candidate_limit(state: &AppState, params: &SearchParams, permissions: &Permissions) -> usize {
+    if params.author.is_some() || permissions.is_restricted() {
+        state.store.config().search.max_limit.max(params.limit)
+    } else {
+        params.limit
+    }
+}
+
+/// Fill in note metadata and drop results whose note no longer exists,
+/// doesn't match the author filter or isn't readable by the caller
+async fn enrich_results(
+    state: &AppState,
+    results: Vec<SearchResult>,
+    params: &SearchParams,
+    permissions: &Permissions,
+) -> Vec<SearchResult> {
+    let mut enriched = Vec::new();
+    for mut result in results {
+        if let Some(path) = canvas_path(&result.note_id) {
+            // Canvases have no author, so an author filter leaves them out
+            if params.author.is_none()
+                && permissions.allows(AccessLevel::Read, std::path::Path::new(path), &[])
+                && state.store.canvases().exists(path)
+            {
+                result.chunk_type = Some("canvas".to_string());
+                enriched.push(result);
+            }
+            continue;
+        }
+        if let Ok(uuid) = result.note_id.parse::<uuid::Uuid>() {
+            if let Some(note) = state.store.get(uuid).await {
+                if params.author.as_deref().is_some_and(|a| !note.is_authored_by(a))
+                    || !permissions.can_read(&note)
+                {
+                    continue;
+                }
+                result.title = note.title.clone();
+                result.tags = note.tags();
+                result.updated_at = Some(note.updated_at.to_rfc3339());
+                enriched.push(result);
+            } else {
+                tracing::debug!("Skipping search result for missing note: {}", result.note_id);
+            }
+        }
+    }
+    enriched.truncate(params.limit);
+    enriched
+}
+
+/// List notes that link to a note
+#[utoipa::path(
+    get,
+    path = "/api/notes/{id}/backlinks",
+    params(
+        ("id" = String, Path, description = "Note UUID")
+    ),
+    responses(
+        (status = 200, description = "Linking notes", body = BacklinksResponse),
+        (status = 400, description = "Invalid note ID", body = ErrorResponse),
+        (status = 404, description = "Note not found", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "notes"
+)]
+pub async fn get_backlinks(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path(id): Path<String>,
+) -> Result<Json<BacklinksResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Invalid note ID".into(),
+            }),
+        )
+    })?;
+    if let Some(note) = state.store.get(uuid).await {
+        authorize(&permissions, AccessLevel::Read, &note)?;
+    }
+
+    let backlinks = state.store.backlinks(uuid).await.map_err(|e| {
+        let status = match e {
+            Error::NoteNotFound(_) => StatusCode::NOT_FOUND,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (
+            status,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    })?;
+    let backlinks = readable_only(&state, &permissions, backlinks, |b| &b.note_id).await;
+
+    let total = backlinks.len();
+    Ok(Json(BacklinksResponse { backlinks, total }))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct HighlightsParams {
+    /// Only highlights in notes with this tag
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HighlightsResponse {
+    /// Highlighted passages, by note title then line
+    pub highlights: Vec<Highlight>,
+    pub total: usize,
+}
+
+/// List the `==highlighted==` passages across the vault
+#[utoipa::path(
+    get,
+    path = "/api/highlights",
+    params(HighlightsParams),
+    responses(
+        (status = 200, description = "Highlighted passages", body = HighlightsResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "notes"
+)]
+pub async fn get_highlights(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Query(params): Query<HighlightsParams>,
+) -> Result<Json<HighlightsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let highlights = state
+        .store
+        .highlights(params.tag.as_deref())
+        .await
+        .map_err(access_error)?;
+    let highlights = readable_only(&state, &permissions, highlights, |h| &h.note_id).await;
+
+    let total = highlights.len();
+    Ok(Json(HighlightsResponse { highlights, total }))
+}
+
+/// List a note's typed relations (`relates_to`, `supersedes`, `blocked_by`)
+#[utoipa::path(
+    get,
+    path = "/api/notes/{id}/relations",
+    params(
+        ("id" = String, Path, description = "Note UUID"),
+        RelationParams
+    ),
+    responses(
+        (status = 200, description = "Relations of the note", body = RelationsResponse),
+        (status = 400, description = "Invalid note ID", body = ErrorResponse),
+        (status = 404, description = "Note not found", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "notes"
+)]
+pub async fn get_relations(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path(id): Path<String>,
+    Query(params): Query<RelationParams>,
+) -> Result<Json<RelationsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Invalid note ID".into(),
+            }),
+        )
+    })?;
+    if let Some(note) = state.store.get(uuid).await {
+        authorize(&permissions, AccessLevel::Read, &note)?;
+    }
+
+    let relations = state
+        .store
+        .relations(uuid, params.kind)
+        .await
+        .map_err(access_error)?;
+
+    // Targets that match no note reveal nothing; keep those
+    let mut readable = Vec::with_capacity(relations.len());
+    for relation in relations {
+        let other = match relation.note_id.as_ref().map(|id| id.parse::<uuid::Uuid>()) {
+            Some(Ok(other)) => state.store.get(other).await,
+            _ => None,
+        };
+        if relation.note_id.is_none() || other.is_some_and(|n| permissions.can_read(&n)) {
+            readable.push(relation);
+        }
+    }
+
+    let total = readable.len();
+    Ok(Json(RelationsResponse {
+        relations: readable,
+        total,
+    }))
+}
+
+/// The note graph: readable notes, and the links and typed relations between them
+#[utoipa::path(
+    get,
+    path = "/api/graph",
+    responses(
+        (status = 200, description = "Notes and labelled edges", body = GraphResponse)
+    ),
+    tag = "notes"
+)]
+pub async fn get_graph(
+    State(state): State<AppState>,
+    permissions: Permissions,
+) -> Json<GraphResponse> {
+    let mut notes: Vec<Note> = state
+        .store
+        .list()
+        .await
+        .into_iter()
+        .filter(|n| !n.is_deleted && permissions.can_read(n))
+        .collect();
+    notes.sort_by(|a, b| a.title.cmp(&b.title));
+    let ids: std::collections::HashSet<uuid::Uuid> = notes.iter().map(|n| n.id).collect();
+
+    let mut seen = std::collections::HashSet::new();
+    let mut edges = Vec::new();
+    for link in state.store.links().await {
+        let Some(target) = link.target_note_id else {
+            continue;
+        };
+        if target == link.source_note_id || !ids.contains(&link.source_note_id) || !ids.contains(&target) {
+            continue;
+        }
+        let label = link.relation.map_or("link", |r| r.as_str());
+        if seen.insert((link.source_note_id, target, label)) {
+            edges.push(GraphEdge {
+                source: link.source_note_id.to_string(),
+                target: target.to_string(),
+                label: label.to_string(),
+            });
+        }
+    }
+    edges.sort_by(|a, b| (&a.source, &a.target, &a.label).cmp(&(&b.source, &b.target, &b.label)));
+
+    let nodes = notes
+        .into_iter()
+        .map(|n| GraphNode {
+            id: n.id.to_string(),
+            tags: n.tags(),
+            title: n.title,
+        })
+        .collect();
+    Json(GraphResponse { nodes, edges })
+}
+
+/// Find notes related to a given note
+#[utoipa::path(
+    get,
+    path = "/api/notes/{id}/related",
+    params(
+        ("id" = String, Path, description = "Note UUID"),
+        ListParams
+    ),
+    responses(
+        (status = 200, description = "Related notes", body = SearchResponse),
+        (status = 400, description = "Invalid note ID", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "search"
+)]
+pub async fn find_related(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path(id): Path<String>,
+    Query(params): Query<ListParams>,
+) -> Result<Json<SearchResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Invalid note ID".into(),
+            }),
+        )
+    })?;
+    if let Some(note) = state.store.get(uuid).await {
+        authorize(&permissions, AccessLevel::Read, &note)?;
+    }
+
+    let depth = if permissions.is_restricted() {
+        state.store.config().search.max_limit.max(params.limit)
+    } else {
+        params.limit
+    };
+    let semantic = state.semantic.read().await;
+    let results = semantic
+        .find_similar(uuid, depth)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+        })?;
+    drop(semantic);
+    let mut results = readable_only(&state, &permissions, results, |r| &r.note_id).await;
+    results.truncate(params.limit);
+
+    let total = results.len();
+    Ok(Json(SearchResponse { results, total }))
+}
+
+/// List the chunks semantic search holds for a note
+#[utoipa::path(
+    get,
+    path = "/api/notes/{id}/chunks",
+    params(
+        ("id" = String, Path, description = "Note UUID")
+    ),
+    responses(
+        (status = 200, description = "Chunks of the note", body = NoteChunksResponse),
+        (status = 400, description = "Invalid note ID", body = ErrorResponse),
+        (status = 404, description = "Note not found", body = ErrorResponse)
+    ),
+    tag = "search"
+)]
+pub async fn get_note_chunks(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path(id): Path<String>,
+) -> Result<Json<NoteChunksResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Invalid note ID".into(),
+            }),
+        )
+    })?;
+    let note = state
+        .store
+        .get(uuid)
+        .await
+        .filter(|n| !n.is_deleted)
+        .ok_or_else(|| access_error(Error::NoteNotFound(id.clone())))?;
+    authorize(&permissions, AccessLevel::Read, &note)?;
+
+    let semantic = state.semantic.read().await;
+    let chunks = semantic.chunks_for_note(uuid);
+    let chunks: Vec<&Chunk> = chunks.iter().collect();
+    let norm = |v: &Vec<f32>| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let chunks: Vec<ChunkInfo> = chunks
+        .iter()
+        .zip(heading_paths(&chunks))
+        .map(|(chunk, heading_path)| ChunkInfo {
+            id: chunk.id.to_string(),
+            chunk_type: format!("{:?}", chunk.chunk_type),
+            heading_path,
+            start_line: chunk.start_line,
+            end_line: chunk.end_line,
+            content: chunk.content.clone(),
+            embedding_model: chunk.embedding_model.clone(),
+            embedded_at: chunk.embedded_at,
+            prose_norm: chunk.prose_embedding.as_ref().map(norm),
+            code_norm: chunk.code_embedding.as_ref().map(norm),
+        })
+        .collect();
+
+    let total = chunks.len();
+    Ok(Json(NoteChunksResponse {
+        note_id: uuid.to_string(),
+        chunks,
+        total,
+    }))
+}
+
+/// Queue a note to be chunked and embedded again
+#[utoipa::path(
+    post,
+    path = "/api/notes/{id}/rechunk",
+    params(
+        ("id" = String, Path, description = "Note UUID")
+    ),
+    responses(
+        (status = 202, description = "Note queued for indexing", body = IndexStatus),
+        (status = 400, description = "Invalid note ID", body = ErrorResponse),
+        (status = 404, description = "Note not found", body = ErrorResponse)
+    ),
+    tag = "search"
+)]
+pub async fn rechunk_note(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<IndexStatus>), (StatusCode, Json<ErrorResponse>)> {
+    let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Invalid note ID".into(),
+            }),
+        )
+    })?;
+    let note = state
+        .store
+        .get(uuid)
+        .await
+        .filter(|n| !n.is_deleted)
+        .ok_or_else(|| access_error(Error::NoteNotFound(id.clone())))?;
+    authorize(&permissions, AccessLevel::Write, &note)?;
+
+    index_note_chunks(&state, &note);
+    Ok((StatusCode::ACCEPTED, Json(state.indexer.status())))
+}
+
+/// List saved versions of a note
+#[utoipa::path(
+    get,
+    path = "/api/notes/{id}/versions",
+    params(
+        ("id" = String, Path, description = "Note UUID")
+    ),
+    responses(
+        (status = 200, description = "Versions from the vault's git history (empty if disabled)", body = VersionsResponse),
+        (status = 400, description = "Invalid note ID", body = ErrorResponse),
+        (status = 404, description = "Note not found", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "history"
+)]
+pub async fn list_versions(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path(id): Path<String>,
+) -> Result<Json<VersionsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Invalid note ID".into(),
+            }),
+        )
+    })?;
+    if let Some(note) = state.store.get(uuid).await {
+        authorize(&permissions, AccessLevel::Read, &note)?;
+    }
+
+    let versions: Vec<NoteVersion> = state
+        .store
+        .versions(uuid)
+        .await
+        .map_err(version_error)?
+        .iter()
+        .map(NoteVersion::from)
+        .collect();
+
+    let total = versions.len();
+    Ok(Json(VersionsResponse { versions, total }))
+}
+
+/// Get a note's content at a saved version
+#[utoipa::path(
+    get,
+    path = "/api/notes/{id}/versions/{rev}",
+    params(
+        ("id" = String, Path, description = "Note UUID"),
+        ("rev" = String, Path, description = "Version commit hash (may be abbreviated)")
+    ),
+    responses(
+        (status = 200, description = "Note content at this version", body = NoteVersionContent),
+        (status = 400, description = "Invalid note ID", body = ErrorResponse),
+        (status = 404, description = "Note or version not found", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "history"
+)]
+pub async fn get_version(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path((id, rev)): Path<(String, String)>,
+) -> Result<Json<NoteVersionContent>, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Invalid note ID".into(),
+            }),
+        )
+    })?;
+    if let Some(note) = state.store.get(uuid).await {
+        authorize(&permissions, AccessLevel::Read, &note)?;
+    }
+
+    let (entry, content) = state
+        .store
+        .version(uuid, &rev)
+        .await
+        .map_err(version_error)?;
+
+    Ok(Json(NoteVersionContent {
+        version: NoteVersion::from(&entry),
+        content,
+    }))
+}
+
+/// Restore a note to a saved version
+#[utoipa::path(
+    post,
+    path = "/api/notes/{id}/versions/{rev}/restore",
+    params(
+        ("id" = String, Path, description = "Note UUID"),
+        ("rev" = String, Path, description = "Version commit hash (may be abbreviated)")
+    ),
+    responses(
+        (status = 200, description = "Note restored", body = NoteResponse),
+        (status = 400, description = "Invalid note ID", body = ErrorResponse),
+        (status = 403, description = "No write access to the note", body = ErrorResponse),
+        (status = 404, description = "Note or version not found", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "history"
+)]
+pub async fn restore_version(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
+    Path((id, rev)): Path<(String, String)>,
+) -> Result<Json<NoteResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Invalid note ID".into(),
+            }),
+        )
+    })?;
+    if let Some(note) = state.store.get(uuid).await {
+        authorize(&permissions, AccessLevel::Write, &note)?;
+        // The old version brings its own frontmatter tags back
+        let (_, content) = state.store.version(uuid, &rev).await.map_err(version_error)?;
+        authorize_tags(&permissions, &note, &frontmatter_tags(&content))?;
+    }
+
+    let note = state
+        .store
+        .restore_version(uuid, &rev)
+        .await
+        .map_err(version_error)?;
+    let note = attribute_change(&state, user, note, Contribution::Updated).await;
+
+    // Re-index for fulltext search
+    if let Err(e) = state.fulltext.index_note(&note) {
+        tracing::warn!("Failed to re-index note: {}", e);
+    }
+    let _ = state.fulltext.commit();
+
+    // Re-index chunks for semantic search (remove old, add new)
+    remove_note_chunks(&state, uuid);
+    index_note_chunks(&state, &note);
+
+    let tags = note.tags();
+    Ok(Json(NoteResponse {
+        id: note.id.to_string(),
+        title: note.title,
+        slug: note.slug,
+        content: note.content,
+        tags,
+        created_at: note.created_at.to_rfc3339(),
+        updated_at: note.updated_at.to_rfc3339(),
+        created_by: note.created_by,
+        updated_by: note.updated_by,
+        is_pinned: note.is_pinned,
+        is_archived: note.is_archived,
+        position: None,
+    }))
+}
+
+fn version_error(e: Error) -> (StatusCode, Json<ErrorResponse>) {
+    let status = match e {
+        Error::NoteNotFound(_) | Error::VersionNotFound(_) => StatusCode::NOT_FOUND,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
+}
+
+/// Notes frequently opened together with a note
+#[utoipa::path(
+    get,
+    path = "/api/notes/{id}/co-accessed",
+    params(
+        ("id" = String, Path, description = "Note UUID"),
+        ListParams
+    ),
+    responses(
+        (status = 200, description = "Notes opened around the same time", body = CoAccessedResponse),
+        (status = 400, description = "Invalid note ID", body = ErrorResponse),
+        (status = 404, description = "Note not found", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "search"
+)]
+pub async fn co_accessed(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path(id): Path<String>,
+    Query(params): Query<ListParams>,
+) -> Result<Json<CoAccessedResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Invalid note ID".into(),
+            }),
+        )
+    })?;
+    if let Some(note) = state.store.get(uuid).await {
+        authorize(&permissions, AccessLevel::Read, &note)?;
+    }
+
+    let notes: Vec<CoAccessedNote> = state
+        .store
+        .co_accessed(uuid, params.limit)
+        .await
+        .map_err(|e| {
+            let status = match e {
+                Error::NoteNotFound(_) => StatusCode::NOT_FOUND,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (
+                status,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+        })?
+        .iter()
+        .filter(|(note, _)| permissions.can_read(note))
+        .map(|(note, count)| CoAccessedNote {
+            note: NoteMeta::from(note),
+            count: *count,
+        })
+        .collect();
+
+    let total = notes.len();
+    Ok(Json(CoAccessedResponse { notes, total }))
+}
+
+/// Suggest note titles for autocomplete
+#[utoipa::path(
+    get,
+    path = "/api/notes/suggest",
+    params(SuggestParams),
+    responses(
+        (status = 200, description = "Ranked title suggestions", body = SuggestResponse)
+    ),
+    tag = "notes"
+)]
+pub async fn suggest_titles(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Query(params): Query<SuggestParams>,
+) -> Json<SuggestResponse> {
+    let depth = if permissions.is_restricted() {
+        usize::MAX
+    } else {
+        params.limit
+    };
+    let suggestions = state
+        .store
+        .search_titles(&params.q, depth)
+        .await
+        .into_iter()
+        .filter(|(note, _)| permissions.can_read(note))
+        .take(params.limit)
+        .map(|(note, score)| TitleSuggestion {
+            id: note.id.to_string(),
+            title: note.title,
+            score,
+        })
+        .collect();
+
+    Json(SuggestResponse { suggestions })
+}
+
+/// Quick capture content as a new note
+#[utoipa::path(
+    post,
+    path = "/api/capture",
+    request_body = CaptureRequest,
+    responses(
+        (status = 201, description = "Capture created", body = NoteResponse),
+        (status = 403, description = "No write access to the inbox", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "notes"
+)]
+pub async fn quick_capture(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
+    Json(req): Json<CaptureRequest>,
+) -> Result<(StatusCode, Json<NoteResponse>), (StatusCode, Json<ErrorResponse>)> {
+    permissions
+        .check_create(std::path::Path::new("inbox"), &[])
+        .map_err(access_error)?;
+
+    store_capture(&state, user, req.content, req.source).await
+}
+
+/// Clip a web page into a new note
+#[utoipa::path(
+    post,
+    path = "/api/capture/url",
+    request_body = CaptureUrlRequest,
+    responses(
+        (status = 201, description = "Page captured", body = NoteResponse),
+        (status = 400, description = "Invalid URL", body = ErrorResponse),
+        (status = 403, description = "No write access to the inbox", body = ErrorResponse),
+        (status = 502, description = "Page could not be fetched", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "notes"
+)]
+pub async fn capture_url(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
+    Json(req): Json<CaptureUrlRequest>,
+) -> Result<(StatusCode, Json<NoteResponse>), (StatusCode, Json<ErrorResponse>)> {
+    permissions
+        .check_create(std::path::Path::new("inbox"), &[])
+        .map_err(access_error)?;
+
+    let url = crate::clip::parse_url(&req.url).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    })?;
+    let content = crate::clip::clip(&url, &state.attachments_path, &state.store.config().clip)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::BAD_GATEWAY,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+        })?;
+
+    store_capture(&state, user, content, Some(url.to_string())).await
+}
+
+/// Save a capture to the inbox and index it
+async fn store_capture(
+    state: &AppState,
+    user: Option<Extension<CurrentUser>>,
+    content: String,
+    source: Option<String>,
+) -> Result<(StatusCode, Json<NoteResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let note = state
+        .store
+        .quick_capture(content, source)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+        })?;
+    let note = attribute_change(state, user, note, Contribution::Created).await;
+
+    // Index for fulltext search
+    if let Err(e) = state.fulltext.index_note(&note) {
+        tracing::warn!("Failed to index capture: {}", e);
+    }
+    let _ = state.fulltext.commit();
+
+    // Index chunks for semantic search
+    index_note_chunks(state, &note);
+
+    let tags = note.tags();
+    Ok((
+        StatusCode::CREATED,
+        Json(NoteResponse {
+            id: note.id.to_string(),
+            title: note.title,
+            slug: note.slug,
+            content: note.content,
+            tags,
+            created_at: note.created_at.to_rfc3339(),
+            updated_at: note.updated_at.to_rfc3339(),
+            created_by: note.created_by,
+            updated_by: note.updated_by,
+            is_pinned: note.is_pinned,
+            is_archived: note.is_archived,
+            position: None,
+        }),
+    ))
+}
+
+/// List all unique tags
+#[utoipa::path(
+    get,
+    path = "/api/tags",
+    responses(
+        (status = 200, description = "List of tags", body = TagsResponse)
+    ),
+    tag = "metadata"
+)]
+pub async fn list_tags(
+    State(state): State<AppState>,
+    permissions: Permissions,
+) -> Json<TagsResponse> {
+    let counts = state.store.tag_counts(&permissions).await;
+
+    Json(TagsResponse {
+        tags: counts.iter().map(|(tag, _)| tag.clone()).collect(),
+        counts: counts.into_iter().collect(),
+    })
+}
+
+/// Count the tags of the notes matching a query
+#[utoipa::path(
+    get,
+    path = "/api/tags/facets",
+    params(TagFacetsParams),
+    responses(
+        (status = 200, description = "Tag counts among the matching notes", body = TagFacetsResponse),
+        (status = 400, description = "Invalid query", body = ErrorResponse)
+    ),
+    tag = "metadata"
+)]
+pub async fn tag_facets(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Query(params): Query<TagFacetsParams>,
+) -> Result<Json<TagFacetsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let query = params.q.unwrap_or_default();
+    SearchQuery::parse(&query).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse { error: e.to_string() }),
+        )
+    })?;
+    // Like searches, a query the index can't run matches nothing
+    let matching = SearchDispatcher::new(&state.store, &state.fulltext, &state.semantic)
+        .matching(&query)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!("Facet search failed: {}", e);
+            Default::default()
+        });
+
+    let mut matches = 0;
+    for id in &matching {
+        if state.store.get(*id).await.is_some_and(|note| permissions.can_read(&note)) {
+            matches += 1;
+        }
+    }
+    let mut facets: Vec<TagFacet> = state
+        .store
+        .tag_counts_among(&matching, &permissions)
+        .await
+        .into_iter()
+        .map(|(tag, count)| TagFacet { tag, count })
+        .collect();
+    facets.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+
+    Ok(Json(TagFacetsResponse { facets, matches }))
+}
+
+/// Rename a tag on every note carrying it
+#[utoipa::path(
+    patch,
+    path = "/api/tags/{name}",
+    params(
+        ("name" = String, Path, description = "Tag to rename")
+    ),
+    request_body = RenameTagRequest,
+    responses(
+        (status = 200, description = "Tag renamed", body = TagChangeResponse),
+        (status = 400, description = "Invalid tag name", body = ErrorResponse),
+        (status = 403, description = "Tag used by notes the caller can't edit", body = ErrorResponse),
+        (status = 404, description = "Tag not found", body = ErrorResponse)
+    ),
+    tag = "metadata"
+)]
+pub async fn rename_tag(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
+    Path(name): Path<String>,
+    Json(req): Json<RenameTagRequest>,
+) -> Result<Json<TagChangeResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let tags = [name];
+    authorize_retag(&state, &permissions, &tags).await?;
+    let notes = state.store.rename_tag(&tags[0], &req.name).await.map_err(tag_error)?;
+    Ok(Json(reindex_retagged(&state, user, notes).await))
+}
+
+/// Merge several tags into one on every note carrying any of them
+#[utoipa::path(
+    post,
+    path = "/api/tags/merge",
+    request_body = MergeTagsRequest,
+    responses(
+        (status = 200, description = "Tags merged", body = TagChangeResponse),
+        (status = 400, description = "Invalid tag name", body = ErrorResponse),
+        (status = 403, description = "Tags used by notes the caller can't edit", body = ErrorResponse),
+        (status = 404, description = "None of the tags are used", body = ErrorResponse)
+    ),
+    tag = "metadata"
+)]
+pub async fn merge_tags(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
+    Json(req): Json<MergeTagsRequest>,
+) -> Result<Json<TagChangeResponse>, (StatusCode, Json<ErrorResponse>)> {
+    authorize_retag(&state, &permissions, &req.tags).await?;
+    let notes = state.store.merge_tags(&req.tags, &req.into).await.map_err(tag_error)?;
+    Ok(Json(reindex_retagged(&state, user, notes).await))
+}
+
+/// Remove a tag from every note carrying it
+#[utoipa::path(
+    delete,
+    path = "/api/tags/{name}",
+    params(
+        ("name" = String, Path, description = "Tag to remove")
+    ),
+    responses(
+        (status = 200, description = "Tag removed", body = TagChangeResponse),
+        (status = 403, description = "Tag used by notes the caller can't edit", body = ErrorResponse),
+        (status = 404, description = "Tag not found", body = ErrorResponse)
+    ),
+    tag = "metadata"
+)]
+pub async fn delete_tag(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
+    Path(name): Path<String>,
+) -> Result<Json<TagChangeResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let tags = [name];
+    authorize_retag(&state, &permissions, &tags).await?;
+    let notes = state.store.delete_tag(&tags[0]).await.map_err(tag_error)?;
+    Ok(Json(reindex_retagged(&state, user, notes).await))
+}
+
+// Helper function to require write access to every note carrying any of
+// `tags` before rewriting them; tags only on unreadable notes are not found
+async fn authorize_retag(
+    state: &AppState,
+    permissions: &Permissions,
+    tags: &[String],
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    if !permissions.is_restricted() {
+        return Ok(());
+    }
+    let tags: Vec<String> = tags.iter().map(|t| t.trim().to_lowercase()).collect();
+    let notes: Vec<Note> = state
+        .store
+        .list()
+        .await
+        .into_iter()
+        .filter(|n| n.tags().iter().any(|t| tags.contains(&t.to_lowercase())))
+        .collect();
+
+    if !notes.iter().any(|n| permissions.can_read(n)) {
+        return Err(tag_error(Error::TagNotFound(tags.join(", "))));
+    }
+    if !notes.iter().all(|n| permissions.can_write(n)) {
+        return Err(tag_error(Error::AccessDenied(
+            "tag is used by notes you can't edit".into(),
+        )));
+    }
+    Ok(())
+}
+
+// Helper function to refresh the search indexes of notes whose tags changed
+async fn reindex_retagged(
+    state: &AppState,
+    user: Option<Extension<CurrentUser>>,
+    notes: Vec<Note>,
+) -> TagChangeResponse {
+    let mut updated = Vec::with_capacity(notes.len());
+    for note in notes {
+        let note = attribute_change(state, user.clone(), note, Contribution::Updated).await;
+        if let Err(e) = state.fulltext.index_note(&note) {
+            tracing::warn!("Failed to re-index note: {}", e);
+        }
+        remove_note_chunks(state, note.id);
+        index_note_chunks(state, &note);
+        updated.push(note.id.to_string());
+    }
+    let _ = state.fulltext.commit();
+
+    TagChangeResponse { updated }
+}
+
+fn tag_error(e: Error) -> (StatusCode, Json<ErrorResponse>) {
+    let status = match e {
+        Error::TagNotFound(_) => StatusCode::NOT_FOUND,
+        Error::InvalidTag(_) => StatusCode::BAD_REQUEST,
+        Error::AccessDenied(_) => StatusCode::FORBIDDEN,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
+}
+
+/// Get vault statistics
+///
+/// Scoped keys only count the notes and tags they can read.
+#[utoipa::path(
+    get,
+    path = "/api/stats",
+    responses(
+        (status = 200, description = "Vault statistics", body = StatsResponse)
+    ),
+    tag = "metadata"
+)]
+pub async fn get_stats(State(state): State<AppState>, permissions: Permissions) -> Json<StatsResponse> {
+    let notes: Vec<Note> = state.store.list().await.into_iter().filter(|n| permissions.can_read(n)).collect();
+    let note_count = notes.iter().filter(|n| !n.is_deleted).count();
+
+    // Chunks are counted across the whole vault
+    let chunk_count = if permissions.is_restricted() {
+        None
+    } else {
+        Some(state.semantic.read().await.chunk_count())
+    };
+
+    let mut tags = std::collections::HashSet::new();
+    for note in &notes {
+        for tag in note.tags() {
+            tags.insert(tag.to_lowercase());
+        }
+    }
+
+    Json(StatsResponse {
+        note_count,
+        chunk_count,
+        tag_count: tags.len(),
+    })
+}
+
+/// Get progress of the background indexing queue
+#[utoipa::path(
+    get,
+    path = "/api/index/status",
+    responses(
+        (status = 200, description = "Indexing queue progress", body = IndexStatus),
+        (status = 403, description = "The caller can't read every note", body = ErrorResponse)
+    ),
+    tag = "metadata"
+)]
+pub async fn get_index_status(
+    State(state): State<AppState>,
+    permissions: Permissions,
+) -> Result<Json<IndexStatus>, (StatusCode, Json<ErrorResponse>)> {
+    if permissions.is_restricted() {
+        return Err(access_error(Error::AccessDenied(
+            "index status requires access to all notes".into(),
+        )));
+    }
+    Ok(Json(state.indexer.status()))
+}
+
+/// Rescan the vault for notes changed outside the server
+///
+/// Every note file whose content no longer matches the manifest is reloaded
+/// and reindexed, and notes whose file is gone are dropped, without a restart.
+#[utoipa::path(
+    post,
+    path = "/api/admin/refresh",
+    responses(
+        (status = 200, description = "Changed notes were reloaded", body = RefreshResponse),
+        (status = 403, description = "The caller can't read every note", body = ErrorResponse)
+    ),
+    tag = "admin"
+)]
+pub async fn refresh_vault(
+    State(state): State<AppState>,
+    permissions: Permissions,
+) -> Result<Json<RefreshResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if permissions.is_restricted() {
+        return Err(access_error(Error::AccessDenied(
+            "refreshing the vault requires access to all notes".into(),
+        )));
+    }
+
+    let changes = state.store.rescan().await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    })?;
+    crate::watcher::reindex_changes(&state, &changes);
+
+    let mut response = RefreshResponse {
+        updated: Vec::new(),
+        removed: Vec::new(),
+    };
+    for change in changes {
+        match change {
+            NoteChange::Updated(note) => response.updated.push(note.id.to_string()),
+            NoteChange::Removed(id) => response.removed.push(id.to_string()),
+        }
+    }
+    Ok(Json(response))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ClassifyParams {
+    /// Query to classify with the vault's rules
+    pub q: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ClassifyResponse {
+    /// How `q` would be classified
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub classification: Option<Classification>,
+    /// Query types picked by the most recent semantic searches, oldest first;
+    /// empty for scoped keys since they hold other users' queries
+    pub recent: Vec<ClassificationDecision>,
+}
+
+/// Show how queries are classified as prose or code
+#[utoipa::path(
+    get,
+    path = "/api/search/classify",
+    params(ClassifyParams),
+    responses(
+        (status = 200, description = "Classification of the query and recent decisions", body = ClassifyResponse)
+    ),
+    tag = "search"
+)]
+pub async fn classify_query(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Query(params): Query<ClassifyParams>,
+) -> Json<ClassifyResponse> {
+    let semantic = state.semantic.read().await;
+    Json(ClassifyResponse {
+        classification: params.q.as_deref().map(|q| semantic.classifier().classify(q)),
+        recent: if permissions.is_restricted() { Vec::new() } else { semantic.recent_decisions() },
+    })
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct RegexSearchParams {
+    /// Regular expression to match against each line
+    pub pattern: String,
+    /// Match regardless of letter case
+    #[serde(default)]
+    pub ignore_case: bool,
+    /// Match the pattern as a literal string
+    #[serde(default)]
+    pub fixed_strings: bool,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RegexSearchResponse {
+    /// Matching lines, ordered by file and line
+    pub matches: Vec<GrepMatch>,
+    pub total: usize,
+}
+
+/// Find the lines of note files matching a regular expression
+#[utoipa::path(
+    get,
+    path = "/api/search/regex",
+    params(RegexSearchParams),
+    responses(
+        (status = 200, description = "Matching lines", body = RegexSearchResponse),
+        (status = 400, description = "Invalid pattern", body = ErrorResponse)
+    ),
+    tag = "search"
+)]
+pub async fn regex_search(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Query(params): Query<RegexSearchParams>,
+) -> Result<Json<RegexSearchResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let config = state.store.config();
+    let notes: Vec<Note> = state
+        .store
+        .list()
+        .await
+        .into_iter()
+        .filter(|n| permissions.can_read(n))
+        .collect();
+    let notes_path = config.notes_path();
+    let options = GrepOptions {
+        ignore_case: params.ignore_case,
+        fixed_strings: params.fixed_strings,
+        max_file_size: Some(config.search.grep_max_file_kb * 1024),
+    };
+    let limit = params.limit.min(config.search.max_limit);
+
+    // Scanning files blocks; keep it off the async workers
+    let matches = tokio::task::spawn_blocking(move || grep_matches(&notes_path, &notes, &params.pattern, options, limit))
+        .await
+        .map_err(|e| access_error(Error::Search(e.to_string())))?
+        .map_err(|e| {
+            let status = match e {
+                Error::Search(_) => StatusCode::BAD_REQUEST,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, Json(ErrorResponse { error: e.to_string() }))
+        })?;
+    let total = matches.len();
+    Ok(Json(RegexSearchResponse { matches, total }))
+}
+
+/// Feedback on one result of a search
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SearchFeedbackRequest {
+    /// The query as searched
+    pub query: String,
+    /// Note the result pointed to
+    pub note_id: String,
+    pub kind: FeedbackKind,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SearchFeedbackParams {
+    /// Only what was learned for this query word
+    pub term: Option<String>,
+}
+
+/// What feedback on one term taught the ranking about one note
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FeedbackAdjustment {
+    pub term: String,
+    pub note_id: String,
+    pub title: String,
+    pub clicks: u32,
+    pub irrelevant: u32,
+    /// Queries the feedback came from
+    pub queries: Vec<String>,
+    /// What the note's score is multiplied by for a query of this term alone
+    pub factor: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SearchFeedbackResponse {
+    /// Adjustments, furthest from 1 first
+    pub adjustments: Vec<FeedbackAdjustment>,
+    pub total: usize,
+    /// Whether adjustments are applied to searches (`search.feedback.enabled`)
+    pub enabled: bool,
+}
+
+/// Report that a search result was opened or doesn't belong with the query
+///
+/// Feedback is kept against each word of the query. Notes marked irrelevant
+/// for a word again and again sink in later searches for it; notes opened
+/// from its results rise a little.
+#[utoipa::path(
+    post,
+    path = "/api/search/feedback",
+    request_body = SearchFeedbackRequest,
+    responses(
+        (status = 204, description = "Feedback recorded"),
+        (status = 400, description = "Invalid note ID or a query without words", body = ErrorResponse),
+        (status = 403, description = "Caller can't read the note", body = ErrorResponse),
+        (status = 404, description = "Note not found", body = ErrorResponse)
+    ),
+    tag = "search"
+)]
+pub async fn record_search_feedback(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
+    Json(req): Json<SearchFeedbackRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let bad_request = |error: &str| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: error.into(),
+            }),
+        )
+    };
+    let uuid = req
+        .note_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| bad_request("Invalid note ID"))?;
+    let terms = feedback_terms(&req.query);
+    if terms.is_empty() {
+        return Err(bad_request("Query has no words to learn from"));
+    }
+    if let Some(note) = state.store.get(uuid).await {
+        authorize(&permissions, AccessLevel::Read, &note)?;
+    }
+
+    state
+        .store
+        .record_search_feedback(&req.query, &terms, uuid, req.kind, &user_key(user.as_ref()))
+        .await
+        .map_err(access_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Show what feedback on results taught the ranking
+#[utoipa::path(
+    get,
+    path = "/api/search/feedback",
+    params(SearchFeedbackParams),
+    responses(
+        (status = 200, description = "Learned ranking adjustments", body = SearchFeedbackResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "search"
+)]
+pub async fn list_search_feedback(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Query(params): Query<SearchFeedbackParams>,
+) -> Result<Json<SearchFeedbackResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let config = &state.store.config().search.feedback;
+    let terms = params.term.as_deref().map(feedback_terms);
+    let feedback = state
+        .store
+        .term_feedback(terms.as_deref())
+        .map_err(access_error)?;
+
+    let mut adjustments = Vec::new();
+    for entry in feedback {
+        let note = match entry.note_id.parse::<uuid::Uuid>() {
+            Ok(uuid) => state.store.get(uuid).await,
+            Err(_) => None,
+        };
+        let Some(note) = note.filter(|n| permissions.can_read(n)) else {
+            continue;
+        };
+        adjustments.push(FeedbackAdjustment {
+            factor: feedback_factor(entry.clicks, entry.irrelevant, config),
+            term: entry.term,
+            note_id: entry.note_id,
+            title: note.title,
+            clicks: entry.clicks,
+            irrelevant: entry.irrelevant,
+            queries: entry.queries,
+        });
+    }
+    // Stable sort keeps terms in order among equal adjustments
+    adjustments.sort_by(|a, b| {
+        (b.factor.ln().abs())
+            .partial_cmp(&a.factor.ln().abs())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let total = adjustments.len();
+    Ok(Json(SearchFeedbackResponse {
+        adjustments,
+        total,
+        enabled: config.enabled,
+    }))
+}
+
+/// Forget all feedback on search results
+#[utoipa::path(
+    delete,
+    path = "/api/search/feedback",
+    responses(
+        (status = 204, description = "Feedback cleared"),
+        (status = 403, description = "Caller can't reach every note", body = ErrorResponse)
+    ),
+    tag = "search"
+)]
+pub async fn clear_search_feedback(
+    State(state): State<AppState>,
+    permissions: Permissions,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    if permissions.is_restricted() {
+        return Err(access_error(Error::AccessDenied(
+            "clearing search feedback requires access to all notes".into(),
+        )));
+    }
+    state.store.clear_search_feedback().map_err(access_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Detect image type from magic bytes
+fn detect_image_type(data: &[u8]) -> Option<&'static str> {
+    if data.len() < 8 {
+        return None;
+    }
+
+    // PNG: 89 50 4E 47 0D 0A 1A 0A
+    if data.starts_with(&[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A]) {
+        return Some("png");
+    }
+
+    // JPEG: FF D8 FF
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return Some("jpg");
+    }
+
+    // GIF: GIF87a or GIF89a
+    if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        return Some("gif");
+    }
+
+    // WebP: RIFF....WEBP
+    if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        return Some("webp");
+    }
+
+    // SVG: starts with <?xml or <svg (with possible whitespace)
+    let text_start = String::from_utf8_lossy(&data[..data.len().min(100)]);
+    let trimmed = text_start.trim_start();
+    if trimmed.starts_with("<?xml") || trimmed.starts_with("<svg") {
+        return Some("svg");
+    }
+
+    None
+}
+
+/// Upload an attachment (image)
+#[utoipa::path(
+    post,
+    path = "/api/attachments",
+    request_body = UploadAttachmentRequest,
+    responses(
+        (status = 201, description = "Attachment uploaded", body = AttachmentResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Caller can't write any notes", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "attachments"
+)]
+pub async fn upload_attachment(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Json(req): Json<UploadAttachmentRequest>,
+) -> Result<(StatusCode, Json<AttachmentResponse>), (StatusCode, Json<ErrorResponse>)> {
+    use base64::Engine;
+
+    if !permissions.can_write_any() {
+        return Err(access_error(Error::AccessDenied(
+            "uploading attachments requires write access".into(),
+        )));
+    }
+
+    // Decode base64 data first so we can detect image type from magic bytes
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(&req.data)
+        .map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: format!("Invalid base64 data: {}", e),
+                }),
+            )
+        })?;
+
+    // Detect image type from magic bytes if mime_type is empty or not provided
+    let extension = if !req.mime_type.is_empty() {
+        match req.mime_type.as_str() {
+            "image/png" => "png",
+            "image/jpeg" | "image/jpg" => "jpg",
+            "image/gif" => "gif",
+            "image/webp" => "webp",
+            "image/svg+xml" => "svg",
+            _ => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(ErrorResponse {
+                        error: format!("Unsupported image type: {}", req.mime_type),
+                    }),
+                ))
+            }
+        }
+    } else {
+        // Detect from magic bytes
+        detect_image_type(&data).ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: "Could not detect image type. Please provide mime_type.".to_string(),
+                }),
+            )
+        })?
+    };
+
+    // Generate filename
+    let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S");
+    let uuid_suffix = uuid::Uuid::new_v4().to_string()[..8].to_string();
+    let filename = req.filename.map(|f| {
+        // Sanitize and use provided filename
+        let stem = std::path::Path::new(&f)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("image");
+        let sanitized: String = stem
+            .chars()
+            .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+            .take(50)
+            .collect();
+        format!("{}_{}.{}", sanitized, uuid_suffix, extension)
+    }).unwrap_or_else(|| format!("{}_{}.{}", timestamp, uuid_suffix, extension));
+
+    // Ensure attachments directory exists
+    std::fs::create_dir_all(&state.attachments_path).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Failed to create attachments directory: {}", e),
+            }),
+        )
+    })?;
+
+    // Write file
+    let file_path = state.attachments_path.join(&filename);
+    std::fs::write(&file_path, &data).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Failed to write attachment: {}", e),
+            }),
+        )
+    })?;
+
+    let url = format!("/api/attachments/{}", filename);
+    let markdown = format!("![{}]({})", filename, url);
+
+    tracing::info!("Uploaded attachment: {}", filename);
+
+    Ok((
+        StatusCode::CREATED,
+        Json(AttachmentResponse {
+            filename,
+            url,
+            markdown,
+        }),
+    ))
+}
+
+/// Get an attachment by filename
+pub async fn get_attachment(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path(filename): Path<String>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    use axum::http::header;
+
+    // Sanitize filename to prevent directory traversal
+    let sanitized: String = filename
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_' || *c == '.')
+        .collect();
+
+    if sanitized.is_empty() || sanitized.contains("..") {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Invalid filename".into(),
+            }),
+        ));
+    }
+
+    // Scoped keys only get the attachments of notes they can read
+    if permissions.is_restricted() {
+        let notes = state
+            .store
+            .attachment_notes(&sanitized)
+            .await
+            .map_err(access_error)?;
+        if !notes.iter().any(|n| permissions.can_read(n)) {
+            return Err(access_error(Error::AccessDenied(format!(
+                "no readable note embeds {}",
+                sanitized
+            ))));
+        }
+    }
+
+    let file_path = state.attachments_path.join(&sanitized);
+
+    if !file_path.exists() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Attachment not found".into(),
+            }),
+        ));
+    }
+
+    let data = std::fs::read(&file_path).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Failed to read attachment: {}", e),
+            }),
+        )
+    })?;
+
+    let mime = mime_guess::from_path(&file_path)
+        .first_or_octet_stream()
+        .to_string();
+
+    Ok(([(header::CONTENT_TYPE, mime)], data))
+}
+
+/// List attachments with their size and modification time
+///
+/// Keys limited by scopes only get the attachments embedded in notes they
+/// can read.
+#[utoipa::path(
+    get,
+    path = "/api/attachments",
+    responses(
+        (status = 200, description = "Attachments", body = AttachmentsResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "attachments"
+)]
+pub async fn list_attachments(
+    State(state): State<AppState>,
+    permissions: Permissions,
+) -> Result<Json<AttachmentsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let mut attachments = state
+        .store
+        .attachments()
+        .list()
+        .await
+        .map_err(access_error)?;
+
+    // Scoped keys only see the attachments of notes they can read
+    if permissions.is_restricted() {
+        let mut embedded = std::collections::HashSet::new();
+        for note in state.store.list().await {
+            if !note.is_deleted && permissions.can_read(&note) {
+                embedded.extend(state.store.note_attachments(note.id).await.map_err(access_error)?);
+            }
+        }
+        attachments.retain(|a| embedded.contains(&a.filename));
+    }
+
+    Ok(Json(AttachmentsResponse {
+        total: attachments.len(),
+        total_size: attachments.iter().map(|a| a.size).sum(),
+        attachments,
+    }))
+}
+
+/// Attachments a note embeds
+#[utoipa::path(
+    get,
+    path = "/api/notes/{id}/attachments",
+    params(
+        ("id" = String, Path, description = "Note ID")
+    ),
+    responses(
+        (status = 200, description = "Attachments the note embeds", body = NoteAttachmentsResponse),
+        (status = 400, description = "Invalid note ID", body = ErrorResponse),
+        (status = 403, description = "Caller can't read the note", body = ErrorResponse),
+        (status = 404, description = "Note not found", body = ErrorResponse)
+    ),
+    tag = "attachments"
+)]
+pub async fn note_attachments(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path(id): Path<String>,
+) -> Result<Json<NoteAttachmentsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Invalid note ID".into(),
+            }),
+        )
+    })?;
+    let note = state
+        .store
+        .get(uuid)
+        .await
+        .ok_or_else(|| access_error(Error::NoteNotFound(id.clone())))?;
+    authorize(&permissions, AccessLevel::Read, &note)?;
+
+    let filenames = state
+        .store
+        .note_attachments(uuid)
+        .await
+        .map_err(access_error)?;
+    let mut attachments = state
+        .store
+        .attachments()
+        .list()
+        .await
+        .map_err(access_error)?;
+    attachments.retain(|a| filenames.contains(&a.filename));
+    let missing = filenames
+        .into_iter()
+        .filter(|f| !attachments.iter().any(|a| a.filename == *f))
+        .collect();
+
+    Ok(Json(NoteAttachmentsResponse {
+        attachments,
+        missing,
+    }))
+}
+
+/// Delete an attachment
+#[utoipa::path(
+    delete,
+    path = "/api/attachments/{filename}",
+    params(
+        ("filename" = String, Path, description = "Attachment file name")
+    ),
+    responses(
+        (status = 204, description = "Attachment deleted"),
+        (status = 403, description = "Caller can't access all notes", body = ErrorResponse),
+        (status = 404, description = "Attachment not found", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "attachments"
+)]
+pub async fn delete_attachment(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path(filename): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    // Notes the caller can't see may embed it
+    if permissions.is_restricted() {
+        return Err(access_error(Error::AccessDenied(
+            "deleting attachments requires access to all notes".into(),
+        )));
+    }
+
+    state
+        .store
+        .attachments()
+        .delete(&filename)
+        .await
+        .map_err(|e| {
+            let status = match e {
+                Error::AttachmentNotFound(_) => StatusCode::NOT_FOUND,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (
+                status,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+        })?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Query parameters for attachment garbage collection
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AttachmentGcParams {
+    /// Delete the orphaned attachments instead of only listing them
+    #[serde(default)]
+    pub delete: bool,
+}
+
+/// Find attachments no note references, optionally deleting them
+#[utoipa::path(
+    post,
+    path = "/api/attachments/gc",
+    params(AttachmentGcParams),
+    responses(
+        (status = 200, description = "Orphaned attachments", body = AttachmentGcResponse),
+        (status = 403, description = "Caller can't access all notes", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "attachments"
+)]
+pub async fn gc_attachments(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Query(params): Query<AttachmentGcParams>,
+) -> Result<Json<AttachmentGcResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if permissions.is_restricted() {
+        return Err(access_error(Error::AccessDenied(
+            "collecting attachments requires access to all notes".into(),
+        )));
+    }
+
+    let orphans = state
+        .store
+        .orphaned_attachments()
+        .await
+        .map_err(access_error)?;
+
+    let (mut deleted, mut freed_bytes) = (0, 0);
+    if params.delete {
+        for orphan in &orphans {
+            match state.store.attachments().delete(&orphan.filename).await {
+                Ok(()) => {
+                    deleted += 1;
+                    freed_bytes += orphan.size;
+                }
+                // Already gone
+                Err(Error::AttachmentNotFound(_)) => {}
+                Err(e) => return Err(access_error(e)),
+            }
+        }
+    }
+
+    Ok(Json(AttachmentGcResponse {
+        orphans,
+        deleted,
+        freed_bytes,
+    }))
+}
+
+/// Query parameters for a vault export
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ExportParams {
+    /// `zip` for an archive of the vault's files, `json` for a dump of the
+    /// notes with their metadata and links
+    #[serde(default)]
+    #[param(inline)]
+    pub format: ExportFormat,
+}
+
+/// Download a backup of the whole vault
+#[utoipa::path(
+    get,
+    path = "/api/export",
+    params(ExportParams),
+    responses(
+        (status = 200, description = "Zip archive of the vault, or a VaultDump for format=json"),
+        (status = 403, description = "Caller can't read every note", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "export"
+)]
+pub async fn export_vault(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Query(params): Query<ExportParams>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    if permissions.is_restricted() {
+        return Err(access_error(Error::AccessDenied(
+            "exporting the vault requires access to all notes".into(),
+        )));
+    }
+
+    let data = match params.format {
+        ExportFormat::Json => {
+            let dump = export::dump(&state.store).await;
+            serde_json::to_vec_pretty(&dump).map_err(|e| access_error(e.into()))?
+        }
+        ExportFormat::Zip => {
+            let config = state.store.config().clone();
+            tokio::task::spawn_blocking(move || {
+                let mut buffer = std::io::Cursor::new(Vec::new());
+                export::write_zip(&config, &mut buffer).map(|_| buffer.into_inner())
+            })
+            .await
+            .map_err(|e| access_error(Error::Export(e.to_string())))?
+            .map_err(access_error)?
+        }
+    };
+
+    let content_type = match params.format {
+        ExportFormat::Zip => "application/zip",
+        ExportFormat::Json => "application/json",
+    };
+    let disposition = format!(
+        "attachment; filename=\"notidium-export-{}.{}\"",
+        chrono::Local::now().format("%Y-%m-%d"),
+        params.format.extension()
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        data,
+    ))
+}
+
+/// Query parameters for a single note export
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct NoteExportParams {
+    /// `html`, `pdf` or `docx`
+    #[serde(default)]
+    #[param(inline)]
+    pub format: NoteFormat,
+}
+
+/// Download a note as a standalone document with its images and embedded notes
+/// inlined
+#[utoipa::path(
+    get,
+    path = "/api/notes/{id}/export",
+    params(
+        ("id" = String, Path, description = "Note ID"),
+        NoteExportParams
+    ),
+    responses(
+        (status = 200, description = "The note as HTML, PDF or DOCX"),
+        (status = 400, description = "Invalid note ID", body = ErrorResponse),
+        (status = 403, description = "Caller can't read the note", body = ErrorResponse),
+        (status = 404, description = "Note not found", body = ErrorResponse),
+        (status = 500, description = "No converter installed for the format", body = ErrorResponse)
+    ),
+    tag = "export"
+)]
+pub async fn export_note(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path(id): Path<String>,
+    Query(params): Query<NoteExportParams>,
+) -> Result<impl axum::response::IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = id.parse::<uuid::Uuid>().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Invalid note ID".into(),
+            }),
+        )
+    })?;
+
+    let mut note = state
+        .store
+        .get(uuid)
+        .await
+        .ok_or_else(|| access_error(Error::NoteNotFound(id.clone())))?;
+    authorize(&permissions, AccessLevel::Read, &note)?;
+    let mut annotations = state.store.annotations(uuid).map_err(access_error)?;
+    let original = std::mem::take(&mut note.content);
+    note.content = state
+        .store
+        .transclude(uuid, &original, |n| permissions.can_read(n))
+        .await;
+    if note.content != original {
+        // Line anchors count lines of the stored note; move them past what
+        // embedding other notes added above them
+        for annotation in &mut annotations {
+            let Some(start) = annotation.line_start else {
+                continue;
+            };
+            let above: String = original.split_inclusive('\n').take(start as usize - 1).collect();
+            let expanded = state
+                .store
+                .transclude(uuid, &above, |n| permissions.can_read(n))
+                .await;
+            let added = expanded.matches('\n').count().saturating_sub(above.matches('\n').count()) as u32;
+            annotation.line_start = Some(start + added);
+            annotation.line_end = annotation.line_end.map(|end| end + added);
+        }
+    }
+
+    let data = note_export::render(state.store.config(), &note, &annotations, params.format)
+        .await
+        .map_err(access_error)?;
+    let disposition = format!(
+        "attachment; filename=\"{}.{}\"",
+        note.slug,
+        params.format.extension()
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, params.format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        data,
+    ))
+}
+
+/// Stream changes to the vault as server-sent events
+///
+/// Each event is named after its type (`note_created`, `note_updated`,
+/// `note_deleted` or `note_indexed`) and carries it as JSON. A `resync` event,
+/// whose data is the number of events dropped, means the client fell behind
+/// and should refetch what it shows.
+#[utoipa::path(
+    get,
+    path = "/api/events",
+    responses(
+        (status = 200, description = "Stream of vault changes", content_type = "text/event-stream", body = String)
+    ),
+    tag = "events"
+)]
+pub async fn events(
+    State(state): State<AppState>,
+    permissions: Permissions,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = state.store.subscribe_events();
+    let stream = futures::stream::unfold(
+        (receiver, state, permissions),
+        |(mut receiver, state, permissions)| async move {
+            loop {
+                let event = match receiver.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(missed)) => {
+                        let resync = Event::default().event("resync").data(missed.to_string());
+                        return Some((Ok(resync), (receiver, state, permissions)));
+                    }
+                    Err(RecvError::Closed) => return None,
+                };
+
+                // Scoped users only hear about notes they can read
+                if permissions.is_restricted() {
+                    let readable = state
+                        .store
+                        .get(event.note_id())
+                        .await
+                        .is_some_and(|note| permissions.can_read(&note));
+                    if !readable {
+                        continue;
+                    }
+                }
+
+                let Ok(sse) = Event::default().event(event.kind()).json_data(&event) else {
+                    continue;
+                };
+                return Some((Ok(sse), (receiver, state, permissions)));
+            }
+        },
+    );
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Canvases in the vault
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CanvasesResponse {
+    pub canvases: Vec<CanvasInfo>,
+}
+
+/// A canvas with the notes its file nodes show
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CanvasResponse {
+    /// Path relative to the notes directory
+    pub path: String,
+    #[serde(flatten)]
+    pub canvas: Canvas,
+    /// File nodes that show a note the caller can read
+    pub notes: Vec<CanvasNoteRef>,
+}
+
+/// A file node showing a note
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CanvasNoteRef {
+    /// ID of the canvas node
+    pub node: String,
+    /// ID of the note
+    pub id: String,
+    pub title: String,
+}
+
+/// Request to create a canvas
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateCanvasRequest {
+    /// Path relative to the notes directory, ending in `.canvas`
+    pub path: String,
+    #[serde(flatten)]
+    pub canvas: Canvas,
+}
+
+fn canvas_error(e: Error) -> (StatusCode, Json<ErrorResponse>) {
+    let status = match e {
+        Error::CanvasNotFound(_) => StatusCode::NOT_FOUND,
+        Error::CanvasAlreadyExists(_) => StatusCode::CONFLICT,
+        Error::InvalidCanvas(_) | Error::InvalidNotePath(_) | Error::Json(_) => StatusCode::BAD_REQUEST,
+        Error::AccessDenied(_) => StatusCode::FORBIDDEN,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
+}
+
+// Helper function to enforce the caller's folder scopes on a canvas; canvases
+// the caller can't read are reported as not found
+fn authorize_canvas(
+    permissions: &Permissions,
+    access: AccessLevel,
+    path: &str,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let file = std::path::Path::new(path);
+    if !permissions.allows(AccessLevel::Read, file, &[]) {
+        return Err(canvas_error(Error::CanvasNotFound(path.to_string())));
+    }
+    if !permissions.allows(access, file, &[]) {
+        return Err(canvas_error(Error::AccessDenied(format!("no write access to {}", path))));
+    }
+    Ok(())
+}
+
+// Helper function to pair a canvas with the readable notes its file nodes show
+async fn canvas_response(
+    state: &AppState,
+    permissions: &Permissions,
+    path: String,
+    canvas: Canvas,
+) -> CanvasResponse {
+    let notes: std::collections::HashMap<PathBuf, Note> = state
+        .store
+        .list()
+        .await
+        .into_iter()
+        .filter(|n| !n.is_deleted && permissions.can_read(n))
+        .map(|n| (n.file_path.clone(), n))
+        .collect();
+    let refs = canvas
+        .nodes
+        .iter()
+        .filter_map(|node| {
+            let note = notes.get(std::path::Path::new(node.file.as_deref()?))?;
+            Some(CanvasNoteRef {
+                node: node.id.clone(),
+                id: note.id.to_string(),
+                title: note.title.clone(),
+            })
+        })
+        .collect();
+
+    CanvasResponse {
+        path,
+        canvas,
+        notes: refs,
+    }
+}
+
+// Helper function to keep a canvas's text searchable
+fn index_canvas(state: &AppState, path: &str, canvas: &Canvas) {
+    if let Err(e) = state.fulltext.index_canvas(path, &canvas.text()) {
+        tracing::warn!("Failed to index canvas {}: {}", path, e);
+    }
+    let _ = state.fulltext.commit();
+}
+
+/// List the canvases in the vault
+#[utoipa::path(
+    get,
+    path = "/api/canvases",
+    responses(
+        (status = 200, description = "Canvases the caller can read", body = CanvasesResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "canvases"
+)]
+pub async fn list_canvases(
+    State(state): State<AppState>,
+    permissions: Permissions,
+) -> Result<Json<CanvasesResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let canvases = state
+        .store
+        .canvases()
+        .list()
+        .await
+        .map_err(canvas_error)?
+        .into_iter()
+        .filter(|c| permissions.allows(AccessLevel::Read, std::path::Path::new(&c.path), &[]))
+        .collect();
+    Ok(Json(CanvasesResponse { canvases }))
+}
+
+/// Create a canvas
+#[utoipa::path(
+    post,
+    path = "/api/canvases",
+    request_body = CreateCanvasRequest,
+    responses(
+        (status = 201, description = "Canvas created", body = CanvasResponse),
+        (status = 400, description = "Invalid path or canvas", body = ErrorResponse),
+        (status = 403, description = "No write access to the folder", body = ErrorResponse),
+        (status = 409, description = "A canvas already exists at the path", body = ErrorResponse)
+    ),
+    tag = "canvases"
+)]
+pub async fn create_canvas(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Json(req): Json<CreateCanvasRequest>,
+) -> Result<(StatusCode, Json<CanvasResponse>), (StatusCode, Json<ErrorResponse>)> {
+    authorize_canvas(&permissions, AccessLevel::Write, &req.path)?;
+    state
+        .store
+        .canvases()
+        .create(&req.path, &req.canvas)
+        .await
+        .map_err(canvas_error)?;
+    index_canvas(&state, &req.path, &req.canvas);
+
+    let response = canvas_response(&state, &permissions, req.path, req.canvas).await;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Get a canvas
+#[utoipa::path(
+    get,
+    path = "/api/canvases/{path}",
+    params(
+        ("path" = String, Path, description = "Canvas path relative to the notes directory")
+    ),
+    responses(
+        (status = 200, description = "The canvas", body = CanvasResponse),
+        (status = 400, description = "Invalid path or canvas file", body = ErrorResponse),
+        (status = 404, description = "Canvas not found", body = ErrorResponse)
+    ),
+    tag = "canvases"
+)]
+pub async fn get_canvas(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path(path): Path<String>,
+) -> Result<Json<CanvasResponse>, (StatusCode, Json<ErrorResponse>)> {
+    authorize_canvas(&permissions, AccessLevel::Read, &path)?;
+    let canvas = state.store.canvases().get(&path).await.map_err(canvas_error)?;
+    Ok(Json(canvas_response(&state, &permissions, path, canvas).await))
+}
+
+/// Replace a canvas's nodes and edges
+#[utoipa::path(
+    put,
+    path = "/api/canvases/{path}",
+    params(
+        ("path" = String, Path, description = "Canvas path relative to the notes directory")
+    ),
+    request_body = Canvas,
+    responses(
+        (status = 200, description = "Canvas updated", body = CanvasResponse),
+        (status = 400, description = "Invalid path or canvas", body = ErrorResponse),
+        (status = 403, description = "No write access to the canvas", body = ErrorResponse),
+        (status = 404, description = "Canvas not found", body = ErrorResponse)
+    ),
+    tag = "canvases"
+)]
+pub async fn update_canvas(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path(path): Path<String>,
+    Json(canvas): Json<Canvas>,
+) -> Result<Json<CanvasResponse>, (StatusCode, Json<ErrorResponse>)> {
+    authorize_canvas(&permissions, AccessLevel::Write, &path)?;
+    state
+        .store
+        .canvases()
+        .update(&path, &canvas)
+        .await
+        .map_err(canvas_error)?;
+    index_canvas(&state, &path, &canvas);
+    Ok(Json(canvas_response(&state, &permissions, path, canvas).await))
+}
+
+/// Delete a canvas
+#[utoipa::path(
+    delete,
+    path = "/api/canvases/{path}",
+    params(
+        ("path" = String, Path, description = "Canvas path relative to the notes directory")
+    ),
+    responses(
+        (status = 204, description = "Canvas deleted"),
+        (status = 403, description = "No write access to the canvas", body = ErrorResponse),
+        (status = 404, description = "Canvas not found", body = ErrorResponse)
+    ),
+    tag = "canvases"
+)]
+pub async fn delete_canvas(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path(path): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    authorize_canvas(&permissions, AccessLevel::Write, &path)?;
+    state.store.canvases().delete(&path).await.map_err(canvas_error)?;
+    if let Err(e) = state.fulltext.delete_note(&canvas_id(&path)) {
+        tracing::warn!("Failed to remove canvas {} from the index: {}", path, e);
+    }
+    let _ = state.fulltext.commit();
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct BoardParams {
+    /// Whether the cards are the tagged notes or the tasks in them
+    #[serde(default)]
+    #[param(inline)]
+    pub items: BoardItems,
+    /// Frontmatter property notes are grouped by (defaults to `status`)
+    pub property: Option<String>,
+    /// Comma-separated columns to show first, in order, even when empty
+    pub columns: Option<String>,
+}
+
+/// Request to move a card to another column
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MoveCardRequest {
+    /// ID of the note the card stands for or holds the task
+    pub note_id: String,
+    /// Column to move the card to; `todo` or `done` for tasks
+    pub column: String,
+    /// Line of the task, for task cards
+    pub line: Option<usize>,
+    /// Frontmatter property to set (defaults to `status`), for note cards
+    pub property: Option<String>,
+}
+
+fn board_error(e: Error) -> (StatusCode, Json<ErrorResponse>) {
+    let status = match e {
+        Error::InvalidBoardMove(_) | Error::Yaml(_) => StatusCode::BAD_REQUEST,
+        _ => return access_error(e),
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
+}
+
+/// Kanban board of the notes tagged `tag`, or of the tasks in them
+#[utoipa::path(
+    get,
+    path = "/api/boards/{tag}",
+    params(
+        ("tag" = String, Path, description = "Tag whose notes are on the board"),
+        BoardParams
+    ),
+    responses(
+        (status = 200, description = "The board", body = Board)
+    ),
+    tag = "boards"
+)]
+pub async fn get_board(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    Path(tag): Path<String>,
+    Query(params): Query<BoardParams>,
+) -> Json<Board> {
+    let notes: Vec<Note> = state
+        .store
+        .list()
+        .await
+        .into_iter()
+        .filter(|n| permissions.can_read(n))
+        .collect();
+
+    let board = match params.items {
+        BoardItems::Notes => {
+            let property = params.property.as_deref().unwrap_or(DEFAULT_BOARD_PROPERTY);
+            let columns: Vec<String> = params
+                .columns
+                .iter()
+                .flat_map(|c| c.split(','))
+                .map(str::trim)
+                .filter(|c| !c.is_empty())
+                .map(str::to_string)
+                .collect();
+            Board::of_notes(&tag, &notes, property, &columns)
+        }
+        BoardItems::Tasks => Board::of_tasks(&tag, &notes),
+    };
+    Json(board)
+}
+
+/// Move a card to another column
+///
+/// Note cards get the column as their frontmatter property; task cards are
+/// ticked when moved to `done` and unticked when moved to `todo`.
+#[utoipa::path(
+    post,
+    path = "/api/boards/{tag}/move",
+    params(
+        ("tag" = String, Path, description = "Tag whose notes are on the board")
+    ),
+    request_body = MoveCardRequest,
+    responses(
+        (status = 200, description = "Card moved", body = NoteResponse),
+        (status = 400, description = "Invalid note ID, column or task line", body = ErrorResponse),
+        (status = 403, description = "No write access to the note", body = ErrorResponse),
+        (status = 404, description = "Note not found on the board", body = ErrorResponse)
+    ),
+    tag = "boards"
+)]
+pub async fn move_card(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
+    Path(tag): Path<String>,
+    Json(req): Json<MoveCardRequest>,
+) -> Result<Json<NoteResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let uuid = req.note_id.parse::<uuid::Uuid>().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Invalid note ID".into(),
+            }),
+        )
+    })?;
+    let note = state
+        .store
+        .get(uuid)
+        .await
+        .filter(|n| !n.is_deleted && n.tags().iter().any(|t| t.eq_ignore_ascii_case(&tag)))
+        .ok_or_else(|| access_error(Error::NoteNotFound(req.note_id.clone())))?;
+    authorize(&permissions, AccessLevel::Write, &note)?;
+
+    let moved = match req.line {
+        Some(line) => {
+            let done = if req.column.eq_ignore_ascii_case(DONE_COLUMN) {
+                true
+            } else if req.column.eq_ignore_ascii_case(TODO_COLUMN) {
+                false
+            } else {
+                return Err(board_error(Error::InvalidBoardMove(format!(
+                    "tasks can only move to {} or {}",
+                    TODO_COLUMN, DONE_COLUMN
+                ))));
+            };
+            state.store.set_task(uuid, line, done).await
+        }
+        None => {
+            let property = req.property.as_deref().unwrap_or(DEFAULT_BOARD_PROPERTY);
+            state.store.set_property(uuid, property, &req.column).await
+        }
+    };
+    let note = moved.map_err(board_error)?;
+    let note = attribute_change(&state, user, note, Contribution::Updated).await;
+
+    if let Err(e) = state.fulltext.index_note(&note) {
+        tracing::warn!("Failed to re-index note: {}", e);
+    }
+    let _ = state.fulltext.commit();
+    remove_note_chunks(&state, uuid);
+    index_note_chunks(&state, &note);
+
+    let tags = note.tags();
+    Ok(Json(NoteResponse {
+        id: note.id.to_string(),
+        title: note.title,
+        slug: note.slug,
+        content: note.content,
+        tags,
+        created_at: note.created_at.to_rfc3339(),
+        updated_at: note.updated_at.to_rfc3339(),
+        created_by: note.created_by,
+        updated_by: note.updated_by,
+        is_pinned: note.is_pinned,
+        is_archived: note.is_archived,
+        position: None,
+    }))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct MocParams {
+    /// Tag whose notes are listed
+    pub tag: String,
+    /// Title of the map of content (defaults to `<tag> MOC`)
+    pub title: Option<String>,
+}
+
+/// A generated map of content
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MocResponse {
+    pub id: String,
+    pub title: String,
+    /// Notes listed
+    pub listed: usize,
+    /// Whether the note was created rather than refreshed
+    pub created: bool,
+    /// Whether the note changed
+    pub changed: bool,
+}
+
+/// Create or refresh the map of content for a tag
+///
+/// The note links to every note carrying the tag, grouped by folder, with a
+/// one-line summary of each. Only the list between its `<!-- moc:begin -->`
+/// and `<!-- moc:end -->` markers is rewritten, and only when it changed.
+#[utoipa::path(
+    post,
+    path = "/api/moc/generate",
+    params(MocParams),
+    responses(
+        (status = 200, description = "Map of content refreshed", body = MocResponse),
+        (status = 201, description = "Map of content created", body = MocResponse),
+        (status = 400, description = "Invalid tag", body = ErrorResponse),
+        (status = 403, description = "No write access to the map of content", body = ErrorResponse),
+        (status = 404, description = "No notes carry the tag", body = ErrorResponse),
+        (status = 500, description = "Internal error", body = ErrorResponse)
+    ),
+    tag = "notes"
+)]
+pub async fn generate_moc(
+    State(state): State<AppState>,
+    permissions: Permissions,
+    user: Option<Extension<CurrentUser>>,
+    Query(params): Query<MocParams>,
+) -> Result<(StatusCode, Json<MocResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let title = params
+        .title
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| format!("{} MOC", params.tag.trim()));
+
+    let existing = state
+        .store
+        .list()
+        .await
+        .into_iter()
+        .find(|n| !n.is_deleted && n.title.eq_ignore_ascii_case(&title));
+    match &existing {
+        Some(note) => authorize(&permissions, AccessLevel::Write, note)?,
+        None => permissions
+            .check_create(std::path::Path::new(""), &[MOC_TAG.to_string()])
+            .map_err(access_error)?,
+    }
+
+    let outcome = state
+        .store
+        .generate_moc(&params.tag, &title, |n| permissions.can_read(n))
+        .await
+        .map_err(|e| {
+            let status = match e {
+                Error::InvalidTag(_) => StatusCode::BAD_REQUEST,
+                Error::TagNotFound(_) => StatusCode::NOT_FOUND,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (
+                status,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+        })?;
+
+    let mut note = outcome.note;
+    if outcome.changed {
+        let contribution = if outcome.created {
+            Contribution::Created
+        } else {
+            Contribution::Updated
+        };
+        note = attribute_change(&state, user, note, contribution).await;
+
+        if let Err(e) = state.fulltext.index_note(&note) {
+            tracing::warn!("Failed to index note: {}", e);
+        }
+        let _ = state.fulltext.commit();
+        remove_note_chunks(&state, note.id);
+        index_note_chunks(&state, &note);
+    }
+
+    let status = if outcome.created {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+    Ok((
+        status,
+        Json(MocResponse {
+            id: note.id.to_string(),
+            title: note.title,
+            listed: outcome.listed,
+            created: outcome.created,
+            changed: outcome.changed,
+        }),
+    ))
 }
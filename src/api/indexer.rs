@@ -0,0 +1,290 @@
+//! Background indexing queue
+//!
+//! Embedding a note's chunks takes far longer than saving it, so handlers
+//! queue the work instead of waiting for it. One worker takes jobs in the
+//! order they were queued: a note edited twice in a row is embedded twice,
+//! with the later content winning, and a deletion queued after an edit is
+//! never undone by it. Chunks are saved to the vector store as well as the
+//! in-memory index, so they are still there after a restart.
+//!
+//! The worker also follows the store's events: whenever a note leaves the
+//! vault, however it was deleted, its full-text document and chunks are
+//! purged in turn with the other jobs.
+
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{mpsc, RwLock};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::embed::{Chunker, Embedder};
+use crate::search::{canvas_path, delete_note_chunks, save_note_chunks, FullTextIndex, SemanticSearch};
+use crate::store::{NoteStore, VaultEvent};
+use crate::types::{ChunkType, Note};
+
+/// Work for the indexing worker
+enum IndexJob {
+    /// Replace a note's chunks with freshly embedded ones
+    Index(Box<Note>),
+    /// Drop a note's chunks
+    Remove(Uuid),
+    /// Drop a deleted note's full-text document and chunks
+    Purge(Uuid),
+    /// Drop the full-text documents and chunks of every note that is gone,
+    /// after deletions were missed
+    Prune,
+}
+
+/// Progress of the indexing queue since the server started
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct IndexStatus {
+    /// Jobs queued or in progress
+    pub pending: usize,
+    /// Jobs finished, whether or not every chunk embedded
+    pub completed: usize,
+    /// Note being embedded right now
+    pub current: Option<Uuid>,
+    /// Most recent embedding failure
+    pub last_error: Option<IndexError>,
+}
+
+/// An embedding failure
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct IndexError {
+    pub note_id: Uuid,
+    pub message: String,
+    pub at: DateTime<Utc>,
+}
+
+/// Handle for queueing indexing jobs; cheap to clone
+#[derive(Clone)]
+pub struct IndexQueue {
+    jobs: mpsc::UnboundedSender<IndexJob>,
+    status: Arc<Mutex<IndexStatus>>,
+}
+
+impl IndexQueue {
+    /// Start the worker; it stops once every handle is dropped
+    pub fn spawn(
+        store: Arc<NoteStore>,
+        fulltext: Arc<FullTextIndex>,
+        semantic: Arc<RwLock<SemanticSearch>>,
+        embedder: Arc<Embedder>,
+        chunker: Arc<Chunker>,
+    ) -> Self {
+        let (jobs, mut queue) = mpsc::unbounded_channel();
+        let status = Arc::new(Mutex::new(IndexStatus::default()));
+
+        // Deletions are queued from the store's events, without keeping the
+        // worker alive once the handles are gone
+        let mut events = store.subscribe_events();
+        let weak_jobs = jobs.downgrade();
+        let purge_status = status.clone();
+        tokio::spawn(async move {
+            loop {
+                let job = match events.recv().await {
+                    Ok(VaultEvent::NoteDeleted { id }) => IndexJob::Purge(id),
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(missed)) => {
+                        tracing::warn!("Indexing fell behind by {} events; pruning deleted notes", missed);
+                        IndexJob::Prune
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                let Some(jobs) = weak_jobs.upgrade() else {
+                    break;
+                };
+                let queue = IndexQueue {
+                    jobs,
+                    status: purge_status.clone(),
+                };
+                queue.send(job);
+            }
+        });
+
+        let worker = Worker {
+            store,
+            fulltext,
+            semantic,
+            embedder,
+            chunker,
+            status: status.clone(),
+        };
+        tokio::spawn(async move {
+            while let Some(job) = queue.recv().await {
+                worker.run(job).await;
+            }
+        });
+
+        Self { jobs, status }
+    }
+
+    /// Queue a note for embedding, replacing its current chunks when done
+    pub fn index(&self, note: &Note) {
+        self.send(IndexJob::Index(Box::new(note.clone())));
+    }
+
+    /// Queue removal of a note's chunks
+    pub fn remove(&self, note_id: Uuid) {
+        self.send(IndexJob::Remove(note_id));
+    }
+
+    /// Current progress
+    pub fn status(&self) -> IndexStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    fn send(&self, job: IndexJob) {
+        self.status.lock().unwrap().pending += 1;
+        if self.jobs.send(job).is_err() {
+            tracing::warn!("Indexing worker has stopped; dropping job");
+            self.status.lock().unwrap().pending -= 1;
+        }
+    }
+}
+
+struct Worker {
+    store: Arc<NoteStore>,
+    fulltext: Arc<FullTextIndex>,
+    semantic: Arc<RwLock<SemanticSearch>>,
+    embedder: Arc<Embedder>,
+    chunker: Arc<Chunker>,
+    status: Arc<Mutex<IndexStatus>>,
+}
+
+impl Worker {
+    async fn run(&self, job: IndexJob) {
+        match job {
+            IndexJob::Index(note) => {
+                self.status.lock().unwrap().current = Some(note.id);
+                self.index(&note).await;
+            }
+            IndexJob::Remove(note_id) => {
+                if let Err(e) = delete_note_chunks(&self.semantic, note_id).await {
+                    tracing::warn!("Failed to delete stored chunks: {}", e);
+                    self.fail(note_id, e.to_string());
+                }
+                tracing::debug!("Removed chunks for note {}", note_id);
+            }
+            IndexJob::Purge(note_id) => self.purge(note_id).await,
+            IndexJob::Prune => self.prune().await,
+        }
+
+        let mut status = self.status.lock().unwrap();
+        status.pending -= 1;
+        status.completed += 1;
+        status.current = None;
+    }
+
+    async fn index(&self, note: &Note) {
+        if !self.store.config().embedding.enabled {
+            self.store.notify(VaultEvent::NoteIndexed { id: note.id, chunks: 0 });
+            return;
+        }
+        let mut chunks = self.chunker.chunk_note(note);
+        match self.embedder.token_budget().await {
+            Ok(Some(budget)) => chunks = budget.fit(chunks),
+            Ok(None) => {}
+            Err(e) => {
+                tracing::warn!("Failed to load tokenizer: {}", e);
+                self.fail(note.id, e.to_string());
+                return;
+            }
+        }
+
+        let mut embedded = Vec::new();
+        for mut chunk in chunks {
+            // Always embed with prose model
+            match self.embedder.embed_prose(&chunk.embedding_text()).await {
+                Ok(embedding) => {
+                    chunk.prose_embedding = Some(embedding);
+                    chunk.embedding_model = Some(self.embedder.prose_model().to_string());
+                    chunk.embedded_at = Some(Utc::now());
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to embed chunk: {}", e);
+                    self.fail(note.id, e.to_string());
+                    continue;
+                }
+            }
+
+            // For code blocks, also embed with code model
+            if matches!(chunk.chunk_type, ChunkType::CodeBlock { .. }) && self.embedder.has_code_model() {
+                match self.embedder.embed_code(&chunk.embedding_text()).await {
+                    Ok(embedding) => chunk.code_embedding = Some(embedding),
+                    Err(e) => {
+                        tracing::warn!("Failed to embed code chunk: {}", e);
+                        self.fail(note.id, e.to_string());
+                    }
+                }
+            }
+            embedded.push(chunk);
+        }
+
+        // Swap the chunks in one go, so searches never see the note half indexed
+        let chunks = embedded.len();
+        if let Err(e) = save_note_chunks(&self.semantic, note.id, embedded).await {
+            tracing::warn!("Failed to store chunks: {}", e);
+            self.fail(note.id, e.to_string());
+        }
+
+        tracing::debug!("Indexed chunks for note {}", note.id);
+        self.store.notify(VaultEvent::NoteIndexed { id: note.id, chunks });
+    }
+
+    /// Drop a deleted note's full-text document and chunks, unless it was
+    /// restored in the meantime
+    async fn purge(&self, note_id: Uuid) {
+        if self.store.get(note_id).await.is_some_and(|n| !n.is_deleted) {
+            return;
+        }
+        if let Err(e) = self
+            .fulltext
+            .delete_note(&note_id.to_string())
+            .and_then(|_| self.fulltext.commit())
+        {
+            tracing::warn!("Failed to remove note from the full-text index: {}", e);
+            self.fail(note_id, e.to_string());
+        }
+        if let Err(e) = delete_note_chunks(&self.semantic, note_id).await {
+            tracing::warn!("Failed to delete stored chunks: {}", e);
+            self.fail(note_id, e.to_string());
+        }
+        tracing::debug!("Purged deleted note {}", note_id);
+    }
+
+    /// Purge every note found in the indexes but no longer in the store
+    async fn prune(&self) {
+        let live: std::collections::HashSet<Uuid> = self
+            .store
+            .list()
+            .await
+            .into_iter()
+            .filter(|n| !n.is_deleted)
+            .map(|n| n.id)
+            .collect();
+        let mut gone: std::collections::HashSet<Uuid> = self.semantic.read().await.note_ids();
+        match self.fulltext.document_ids() {
+            Ok(ids) => gone.extend(
+                ids.iter()
+                    .filter(|id| canvas_path(id).is_none())
+                    .filter_map(|id| id.parse::<Uuid>().ok()),
+            ),
+            Err(e) => tracing::warn!("Failed to list full-text documents: {}", e),
+        }
+        for note_id in gone.into_iter().filter(|id| !live.contains(id)) {
+            self.purge(note_id).await;
+        }
+    }
+
+    fn fail(&self, note_id: Uuid, message: String) {
+        self.status.lock().unwrap().last_error = Some(IndexError {
+            note_id,
+            message,
+            at: Utc::now(),
+        });
+    }
+}
@@ -2,5 +2,21 @@
 
 mod routes;
 mod handlers;
+mod indexer;
+mod auth;
+mod session;
+mod token;
+mod rate_limit;
 
 pub use routes::{create_router, create_router_with_mcp, AppState};
+pub use handlers::{
+    AttachmentResponse, CreateNoteRequest, ErrorResponse, HealthResponse, ListResponse, NoteResponse,
+    AnnotationsResponse, RefreshResponse, SearchFeedbackResponse, SearchResponse, StarredResponse,
+    RegexSearchResponse, TagFacetsResponse, UploadAttachmentRequest,
+};
+pub use auth::{authenticate, permissions, AuthState, CurrentUser};
+pub use session::{hash_password, Sessions};
+pub use token::{generate_api_token, ApiToken};
+pub use indexer::{IndexError, IndexQueue, IndexStatus};
+pub use rate_limit::{rate_limit, RateLimiter};
+pub(crate) use handlers::{index_note_chunks, remove_note_chunks};
@@ -0,0 +1,306 @@
+//! Per-client rate limiting
+//!
+//! Each client IP gets a token bucket refilled at the configured rate. Requests
+//! that run the embedder or fetch remote pages also draw from a second, much
+//! smaller bucket, so one runaway script can't keep the model busy for everyone.
+//! Clients over the limit get `429 Too Many Requests` with a `Retry-After`.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Json, Router,
+};
+
+use super::handlers::ErrorResponse;
+use crate::config::{RateLimitConfig, SearchConfig};
+use crate::search::{default_mode, SearchClient, SearchMode};
+
+/// Clients tracked before idle buckets are dropped
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Token buckets per client IP
+pub struct RateLimiter {
+    general: Limit,
+    expensive: Limit,
+    /// Whether `/api/search` without a `mode` may run the embedder
+    default_search_expensive: bool,
+    buckets: Mutex<HashMap<(IpAddr, Kind), Bucket>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    General,
+    Expensive,
+}
+
+#[derive(Clone, Copy)]
+struct Limit {
+    rate: f64,
+    burst: f64,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Limit {
+    fn new(rate: f64, burst: u32) -> Self {
+        Self {
+            rate: rate.max(f64::MIN_POSITIVE),
+            burst: f64::from(burst.max(1)),
+        }
+    }
+
+    /// Tokens in `bucket` at `now`, after refilling since it was last used
+    fn tokens(&self, bucket: Option<&Bucket>, now: Instant) -> f64 {
+        match bucket {
+            Some(bucket) => {
+                let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+                (bucket.tokens + elapsed * self.rate).min(self.burst)
+            }
+            None => self.burst,
+        }
+    }
+
+    /// How long until a bucket holding `tokens` has a whole token again
+    fn wait(&self, tokens: f64) -> Duration {
+        Duration::from_secs_f64((1.0 - tokens) / self.rate)
+    }
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            general: Limit::new(config.requests_per_second, config.burst),
+            expensive: Limit::new(config.expensive_requests_per_second, config.expensive_burst),
+            default_search_expensive: true,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Charge searches that don't name a mode by the modes `search` can
+    /// default them to
+    ///
+    /// Limits apply before authentication, so a search counts as cheap only
+    /// when neither the HTTP default nor any user's override is semantic or
+    /// hybrid.
+    pub fn with_search_defaults(mut self, search: &SearchConfig) -> Self {
+        let user_modes = search
+            .client_modes
+            .iter()
+            .filter(|(client, _)| ![SearchClient::Mcp.key(), SearchClient::Cli.key()].contains(&client.as_str()))
+            .map(|(_, mode)| *mode);
+        self.default_search_expensive = std::iter::once(default_mode(search, SearchClient::Http, None))
+            .chain(user_modes)
+            .any(|mode| mode != SearchMode::Fulltext);
+        self
+    }
+
+    /// Take a token for a request from `ip`, or return how long the client
+    /// should wait before retrying
+    pub fn check(&self, ip: IpAddr, expensive: bool, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            self.prune(&mut buckets, now);
+        }
+
+        let mut kinds = vec![(Kind::General, self.general)];
+        if expensive {
+            kinds.push((Kind::Expensive, self.expensive));
+        }
+
+        // Check every bucket before taking from any, so a refused request
+        // doesn't use up the client's allowance
+        let mut tokens = Vec::with_capacity(kinds.len());
+        for &(kind, limit) in &kinds {
+            let available = limit.tokens(buckets.get(&(ip, kind)), now);
+            if available < 1.0 {
+                return Err(limit.wait(available));
+            }
+            tokens.push(available);
+        }
+
+        for ((kind, _), available) in kinds.into_iter().zip(tokens) {
+            buckets.insert(
+                (ip, kind),
+                Bucket {
+                    tokens: available - 1.0,
+                    updated: now,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Drop buckets that have refilled completely; they behave the same as
+    /// no bucket at all
+    fn prune(&self, buckets: &mut HashMap<(IpAddr, Kind), Bucket>, now: Instant) {
+        buckets.retain(|(_, kind), bucket| {
+            let limit = match kind {
+                Kind::General => self.general,
+                Kind::Expensive => self.expensive,
+            };
+            limit.tokens(Some(bucket), now) < limit.burst
+        });
+    }
+}
+
+/// Whether a request runs the embedding model or fetches a remote page
+///
+/// `/api/search` without a mode runs in the vault's or the user's default
+/// mode, expensive when `default_search_expensive`.
+fn is_expensive(uri: &Uri, default_search_expensive: bool) -> bool {
+    match uri.path() {
+        "/api/search/semantic" | "/api/capture" | "/api/capture/url" => true,
+        "/api/search" => {
+            let mode = uri
+                .query()
+                .and_then(|query| query.split('&').find_map(|pair| pair.strip_prefix("mode=")));
+            match mode {
+                Some(mode) => mode != "fulltext",
+                None => default_search_expensive,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Middleware refusing requests from clients over their limit
+///
+/// Requests without a known peer address (the server wasn't started with
+/// connect info) pass through unlimited.
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(ConnectInfo(addr)) = request.extensions().get::<ConnectInfo<SocketAddr>>().copied() else {
+        return next.run(request).await;
+    };
+
+    let expensive = is_expensive(request.uri(), limiter.default_search_expensive);
+    match limiter.check(addr.ip(), expensive, Instant::now()) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => {
+            let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                Json(ErrorResponse {
+                    error: "Too many requests".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Add the rate limiting layer to `router` if it's enabled in `config`
+pub fn apply<S>(router: Router<S>, config: &RateLimitConfig, search: &SearchConfig) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    if !config.enabled {
+        return router;
+    }
+    let limiter = Arc::new(RateLimiter::new(config).with_search_defaults(search));
+    router.layer(middleware::from_fn_with_state(limiter, rate_limit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(rate: f64, burst: u32, expensive_rate: f64, expensive_burst: u32) -> RateLimiter {
+        RateLimiter::new(&RateLimitConfig {
+            enabled: true,
+            requests_per_second: rate,
+            burst,
+            expensive_requests_per_second: expensive_rate,
+            expensive_burst,
+        })
+    }
+
+    #[test]
+    fn test_burst_then_refill() {
+        let limiter = limiter(2.0, 3, 1.0, 1);
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.check(ip, false, start).is_ok());
+        }
+        let wait = limiter.check(ip, false, start).unwrap_err();
+        assert_eq!(wait, Duration::from_millis(500));
+
+        // Other clients have their own bucket
+        assert!(limiter.check(other, false, start).is_ok());
+
+        // Half a second refills one token at 2/s
+        let later = start + Duration::from_millis(500);
+        assert!(limiter.check(ip, false, later).is_ok());
+        assert!(limiter.check(ip, false, later).is_err());
+    }
+
+    #[test]
+    fn test_expensive_requests_have_their_own_limit() {
+        let limiter = limiter(10.0, 10, 1.0, 1);
+        let ip: IpAddr = "::1".parse().unwrap();
+        let start = Instant::now();
+
+        assert!(limiter.check(ip, true, start).is_ok());
+        assert_eq!(limiter.check(ip, true, start), Err(Duration::from_secs(1)));
+
+        // The refused expensive request didn't cost a general token
+        for _ in 0..9 {
+            assert!(limiter.check(ip, false, start).is_ok());
+        }
+        assert!(limiter.check(ip, false, start).is_err());
+    }
+
+    #[test]
+    fn test_is_expensive() {
+        let expensive = |uri: &str| is_expensive(&uri.parse().unwrap(), false);
+        assert!(expensive("/api/search/semantic?q=rust"));
+        assert!(expensive("/api/capture"));
+        assert!(expensive("/api/capture/url"));
+        assert!(expensive("/api/search?q=rust&mode=hybrid"));
+        assert!(!expensive("/api/search?q=rust&mode=fulltext"));
+        assert!(!expensive("/api/search?q=rust"));
+        assert!(!expensive("/api/search?q=mode=semantic"));
+        assert!(!expensive("/api/notes"));
+
+        // Searches without a mode cost what the vault defaults them to
+        assert!(is_expensive(&"/api/search?q=rust".parse().unwrap(), true));
+        assert!(!is_expensive(&"/api/search?q=rust&mode=fulltext".parse().unwrap(), true));
+    }
+
+    #[test]
+    fn test_default_search_cost_follows_config() {
+        let config = RateLimitConfig {
+            enabled: true,
+            ..RateLimitConfig::default()
+        };
+        let charged = |search: SearchConfig| RateLimiter::new(&config).with_search_defaults(&search).default_search_expensive;
+
+        assert!(!charged(SearchConfig::default()));
+        assert!(charged(SearchConfig {
+            default_mode: Some(SearchMode::Hybrid),
+            ..SearchConfig::default()
+        }));
+        let mut search = SearchConfig::default();
+        search.client_modes.insert("mcp".to_string(), SearchMode::Semantic);
+        assert!(!charged(search.clone()));
+        search.client_modes.insert("alice".to_string(), SearchMode::Semantic);
+        assert!(charged(search));
+    }
+}
@@ -2,8 +2,9 @@
 
 use axum::{
     Router,
-    routing::{get, post, put, delete},
-    response::IntoResponse,
+    middleware,
+    routing::{get, post, put, patch, delete},
+    response::{Html, IntoResponse},
     http::{StatusCode, Uri, header},
 };
 use rust_embed::RustEmbed;
@@ -14,16 +15,29 @@ use tower_http::trace::TraceLayer;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
+use super::auth::{self, AuthState};
+use super::indexer::{IndexError, IndexQueue, IndexStatus};
+use super::rate_limit;
+use super::session::Sessions;
+use super::token::ApiToken;
 use super::handlers::{
-    self, AttachmentResponse, CaptureRequest, CreateNoteRequest, ErrorResponse, HealthResponse,
-    ListResponse, NoteResponse, SearchResponse, StatsResponse, TagsResponse, UpdateNoteRequest,
-    UploadAttachmentRequest,
+    self, AttachmentGcResponse, ClassifyResponse, RegexSearchResponse, SearchFeedbackRequest, FeedbackAdjustment, SearchFeedbackResponse, AttachmentResponse, AttachmentsResponse, BacklinksResponse, HighlightsResponse, ReadPositionRequest, StarredResponse, AnnotationsResponse, CreateAnnotationRequest, UpdateAnnotationRequest, CanvasNoteRef, CanvasResponse, CanvasesResponse,
+    CaptureRequest, CaptureUrlRequest, CoAccessedNote, CoAccessedResponse, CreateCanvasRequest, CreateNoteRequest, GraphEdge, GraphNode, GraphResponse, MocResponse, MoveCardRequest, RelationsResponse, NoteChunksResponse, ChunkInfo, ErrorResponse, FromTemplateRequest, HealthResponse, ListResponse, LoginRequest, MergeTagsRequest, NoteAttachmentsResponse, NoteResponse, RefreshResponse, SearchResponse,
+    NoteVersionContent, RenameTagRequest, SessionResponse, StatsResponse, SuggestResponse, TagChangeResponse, TagFacet, TagFacetsResponse, TagsResponse, TemplatesResponse, TitleSuggestion,
+    TrashResponse,
+    UpdateNoteRequest, UploadAttachmentRequest, VersionsResponse,
 };
 use crate::embed::{Chunker, Embedder};
-use crate::mcp::NotidiumServer;
-use crate::store::NoteStore;
-use crate::search::{FullTextIndex, SemanticSearch};
-use crate::types::{NoteMeta, SearchResult};
+use crate::export::note::NoteFormat;
+use crate::export::{ExportFormat, LinkDump, NoteDump, VaultDump};
+use crate::mcp::{self, McpAuth, NotidiumServer, ToolFilter};
+use crate::store::{AttachmentInfo, Board, BoardCard, BoardColumn, BoardItems, Canvas, CanvasEdge, CanvasInfo, CanvasNode, CanvasNodeType, NoteStore, Users};
+use crate::search::{Classification, ClassificationDecision, FullTextIndex, GrepMatch, SemanticSearch};
+use crate::telemetry;
+use crate::types::{
+    Annotation, Backlink, FeedbackKind, Highlight, LinkType, MatchRange, NoteMeta, ReadPosition, NoteRelation, NoteVersion, QueryType, RelationDirection, RelationKind, SearchResult,
+    TrashedNote,
+};
 
 /// Embedded frontend assets (built from frontend/dist)
 #[derive(RustEmbed)]
@@ -40,41 +54,182 @@ struct Asset;
     ),
     tags(
         (name = "notes", description = "Note management"),
+        (name = "trash", description = "Deleted note recovery"),
+        (name = "history", description = "Note version history"),
+        (name = "templates", description = "Note templates"),
         (name = "search", description = "Search operations"),
         (name = "metadata", description = "Tags and statistics"),
         (name = "attachments", description = "Attachment management"),
+        (name = "export", description = "Vault backups"),
+        (name = "auth", description = "Web UI login"),
+        (name = "events", description = "Live vault change notifications"),
+        (name = "canvases", description = "Canvas boards over notes"),
+        (name = "boards", description = "Kanban boards of tagged notes and tasks"),
+        (name = "admin", description = "Vault maintenance"),
         (name = "health", description = "Health checks")
     ),
     paths(
         handlers::health,
+        handlers::login,
+        handlers::logout,
+        handlers::get_session,
         handlers::list_notes,
         handlers::get_note,
         handlers::create_note,
+        handlers::create_from_template,
+        handlers::list_templates,
         handlers::update_note,
         handlers::delete_note,
+        handlers::get_backlinks,
+        handlers::get_relations,
+        handlers::get_highlights,
+        handlers::set_read_position,
+        handlers::list_starred,
+        handlers::star_note,
+        handlers::unstar_note,
+        handlers::list_annotations,
+        handlers::create_annotation,
+        handlers::update_annotation,
+        handlers::delete_annotation,
+        handlers::get_graph,
+        handlers::generate_moc,
+        handlers::list_trash,
+        handlers::restore_from_trash,
+        handlers::purge_from_trash,
+        handlers::list_versions,
+        handlers::get_version,
+        handlers::restore_version,
         handlers::search,
         handlers::semantic_search,
         handlers::find_related,
+        handlers::get_note_chunks,
+        handlers::rechunk_note,
+        handlers::co_accessed,
+        handlers::suggest_titles,
         handlers::quick_capture,
+        handlers::capture_url,
         handlers::list_tags,
+        handlers::tag_facets,
+        handlers::rename_tag,
+        handlers::merge_tags,
+        handlers::delete_tag,
         handlers::get_stats,
+        handlers::get_index_status,
+        handlers::refresh_vault,
+        handlers::classify_query,
+        handlers::regex_search,
+        handlers::record_search_feedback,
+        handlers::list_search_feedback,
+        handlers::clear_search_feedback,
         handlers::upload_attachment,
+        handlers::list_attachments,
+        handlers::note_attachments,
+        handlers::delete_attachment,
+        handlers::gc_attachments,
+        handlers::export_vault,
+        handlers::export_note,
+        handlers::events,
+        handlers::list_canvases,
+        handlers::create_canvas,
+        handlers::get_canvas,
+        handlers::update_canvas,
+        handlers::delete_canvas,
+        handlers::get_board,
+        handlers::move_card,
     ),
     components(schemas(
         NoteMeta,
         SearchResult,
+        MatchRange,
         NoteResponse,
         ListResponse,
         SearchResponse,
         TagsResponse,
+        TagFacet,
+        TagFacetsResponse,
+        RenameTagRequest,
+        MergeTagsRequest,
+        TagChangeResponse,
+        RefreshResponse,
+        TitleSuggestion,
+        SuggestResponse,
+        Backlink,
+        LinkType,
+        BacklinksResponse,
+        RelationKind,
+        RelationDirection,
+        NoteRelation,
+        RelationsResponse,
+        Highlight,
+        HighlightsResponse,
+        ReadPosition,
+        ReadPositionRequest,
+        StarredResponse,
+        Annotation,
+        AnnotationsResponse,
+        CreateAnnotationRequest,
+        UpdateAnnotationRequest,
+        NoteChunksResponse,
+        ChunkInfo,
+        GraphResponse,
+        GraphNode,
+        GraphEdge,
+        MocResponse,
+        CoAccessedNote,
+        CoAccessedResponse,
+        TrashedNote,
+        TrashResponse,
+        NoteVersion,
+        VersionsResponse,
+        NoteVersionContent,
         StatsResponse,
+        IndexStatus,
+        IndexError,
+        ClassifyResponse,
+        RegexSearchResponse,
+        GrepMatch,
+        FeedbackKind,
+        SearchFeedbackRequest,
+        FeedbackAdjustment,
+        SearchFeedbackResponse,
+        Classification,
+        ClassificationDecision,
+        QueryType,
         HealthResponse,
         ErrorResponse,
+        LoginRequest,
+        SessionResponse,
         CreateNoteRequest,
+        FromTemplateRequest,
+        TemplatesResponse,
         UpdateNoteRequest,
         CaptureRequest,
+        CaptureUrlRequest,
         UploadAttachmentRequest,
         AttachmentResponse,
+        AttachmentsResponse,
+        NoteAttachmentsResponse,
+        AttachmentGcResponse,
+        AttachmentInfo,
+        ExportFormat,
+        NoteFormat,
+        VaultDump,
+        NoteDump,
+        LinkDump,
+        Canvas,
+        CanvasNode,
+        CanvasNodeType,
+        CanvasEdge,
+        CanvasInfo,
+        CanvasesResponse,
+        CanvasResponse,
+        CanvasNoteRef,
+        CreateCanvasRequest,
+        Board,
+        BoardItems,
+        BoardColumn,
+        BoardCard,
+        MoveCardRequest,
     ))
 )]
 pub struct ApiDoc;
@@ -105,6 +260,12 @@ async fn static_handler(uri: Uri) -> impl IntoResponse {
     }
 }
 
+/// Sign-in page for password login, self-contained so it works before the
+/// frontend's assets may be loaded
+async fn login_page() -> Html<&'static str> {
+    Html(include_str!("login.html"))
+}
+
 /// Shared application state
 #[derive(Clone)]
 pub struct AppState {
@@ -114,6 +275,30 @@ pub struct AppState {
     pub embedder: Arc<Embedder>,
     pub chunker: Arc<Chunker>,
     pub attachments_path: std::path::PathBuf,
+    /// Accounts allowed to use the API; empty in single-user mode
+    pub users: Arc<Users>,
+    /// Web UI login sessions; disabled unless a password is configured
+    pub sessions: Arc<Sessions>,
+    /// MCP clients allowed on `/mcp`; disabled unless clients are configured
+    pub mcp_auth: Arc<McpAuth>,
+    /// Vault-wide token for `/api/*` and `/mcp`; disabled unless configured
+    pub api_token: Arc<ApiToken>,
+    /// Tools offered on `/mcp`
+    pub mcp_tools: ToolFilter,
+    /// Background queue embedding note chunks for semantic search
+    pub indexer: IndexQueue,
+}
+
+impl AppState {
+    /// Everything the authentication layer checks requests against
+    pub fn auth(&self) -> AuthState {
+        AuthState {
+            users: self.users.clone(),
+            sessions: self.sessions.clone(),
+            mcp: self.mcp_auth.clone(),
+            api_token: self.api_token.clone(),
+        }
+    }
 }
 
 /// Create the API router
@@ -125,29 +310,102 @@ pub fn create_router(state: AppState) -> Router {
 
     let openapi = ApiDoc::openapi();
 
-    Router::new()
+    let router = Router::new()
         // Notes CRUD
         .route("/api/notes", get(handlers::list_notes))
         .route("/api/notes", post(handlers::create_note))
+        .route("/api/notes/suggest", get(handlers::suggest_titles))
+        .route("/api/notes/starred", get(handlers::list_starred))
+        .route("/api/notes/from-template", post(handlers::create_from_template))
         .route("/api/notes/{id}", get(handlers::get_note))
         .route("/api/notes/{id}", put(handlers::update_note))
         .route("/api/notes/{id}", delete(handlers::delete_note))
+        .route("/api/notes/{id}/backlinks", get(handlers::get_backlinks))
+        .route("/api/notes/{id}/relations", get(handlers::get_relations))
+        .route("/api/notes/{id}/position", put(handlers::set_read_position))
+        .route("/api/notes/{id}/star", put(handlers::star_note).delete(handlers::unstar_note))
+        .route(
+            "/api/notes/{id}/annotations",
+            get(handlers::list_annotations).post(handlers::create_annotation),
+        )
+        .route(
+            "/api/notes/{id}/annotations/{annotation_id}",
+            put(handlers::update_annotation).delete(handlers::delete_annotation),
+        )
+        .route("/api/highlights", get(handlers::get_highlights))
+        .route("/api/graph", get(handlers::get_graph))
+        .route("/api/moc/generate", post(handlers::generate_moc))
+
+        // Trash
+        .route("/api/trash", get(handlers::list_trash))
+        .route("/api/trash/{id}/restore", post(handlers::restore_from_trash))
+        .route("/api/trash/{id}", delete(handlers::purge_from_trash))
+
+        // Version history
+        .route("/api/notes/{id}/versions", get(handlers::list_versions))
+        .route("/api/notes/{id}/versions/{rev}", get(handlers::get_version))
+        .route("/api/notes/{id}/versions/{rev}/restore", post(handlers::restore_version))
 
         // Search
         .route("/api/search", get(handlers::search))
         .route("/api/search/semantic", get(handlers::semantic_search))
+        .route("/api/search/classify", get(handlers::classify_query))
+        .route("/api/search/regex", get(handlers::regex_search))
+        .route(
+            "/api/search/feedback",
+            get(handlers::list_search_feedback)
+                .post(handlers::record_search_feedback)
+                .delete(handlers::clear_search_feedback),
+        )
         .route("/api/notes/{id}/related", get(handlers::find_related))
+        .route("/api/notes/{id}/chunks", get(handlers::get_note_chunks))
+        .route("/api/notes/{id}/rechunk", post(handlers::rechunk_note))
+        .route("/api/notes/{id}/co-accessed", get(handlers::co_accessed))
 
         // Quick actions
         .route("/api/capture", post(handlers::quick_capture))
+        .route("/api/capture/url", post(handlers::capture_url))
 
         // Attachments
-        .route("/api/attachments", post(handlers::upload_attachment))
-        .route("/api/attachments/{filename}", get(handlers::get_attachment))
+        .route(
+            "/api/attachments",
+            get(handlers::list_attachments).post(handlers::upload_attachment),
+        )
+        .route("/api/attachments/gc", post(handlers::gc_attachments))
+        .route(
+            "/api/attachments/{filename}",
+            get(handlers::get_attachment).delete(handlers::delete_attachment),
+        )
+        .route("/api/export", get(handlers::export_vault))
+        .route("/api/notes/{id}/export", get(handlers::export_note))
+        .route("/api/events", get(handlers::events))
+        .route("/api/canvases", get(handlers::list_canvases))
+        .route("/api/canvases", post(handlers::create_canvas))
+        .route("/api/canvases/{*path}", get(handlers::get_canvas))
+        .route("/api/canvases/{*path}", put(handlers::update_canvas))
+        .route("/api/canvases/{*path}", delete(handlers::delete_canvas))
+        .route("/api/boards/{tag}", get(handlers::get_board))
+        .route("/api/boards/{tag}/move", post(handlers::move_card))
+        .route("/api/notes/{id}/attachments", get(handlers::note_attachments))
+
+        // Templates
+        .route("/api/templates", get(handlers::list_templates))
 
         // Metadata
         .route("/api/tags", get(handlers::list_tags))
+        .route("/api/tags/facets", get(handlers::tag_facets))
+        .route("/api/tags/merge", post(handlers::merge_tags))
+        .route("/api/tags/{name}", patch(handlers::rename_tag))
+        .route("/api/tags/{name}", delete(handlers::delete_tag))
         .route("/api/stats", get(handlers::get_stats))
+        .route("/api/index/status", get(handlers::get_index_status))
+        .route("/api/admin/refresh", post(handlers::refresh_vault))
+
+        // Web UI login
+        .route("/login", get(login_page))
+        .route("/api/auth/login", post(handlers::login))
+        .route("/api/auth/logout", post(handlers::logout))
+        .route("/api/auth/session", get(handlers::get_session))
 
         // Health
         .route("/health", get(handlers::health))
@@ -158,8 +416,11 @@ pub fn create_router(state: AppState) -> Router {
         // Static files (frontend)
         .fallback(static_handler)
 
+        .layer(middleware::from_fn_with_state(state.auth(), auth::authenticate));
+
+    rate_limit::apply(router, &state.store.config().rate_limit, &state.store.config().search)
         .layer(cors)
-        .layer(TraceLayer::new_for_http())
+        .layer(TraceLayer::new_for_http().make_span_with(telemetry::http_request_span))
         .with_state(state)
 }
 
@@ -176,12 +437,16 @@ pub fn create_router_with_mcp(state: AppState) -> Router {
 
     let openapi = ApiDoc::openapi();
 
-    // Clone state components for MCP service factory
-    let store = state.store.clone();
-    let fulltext = state.fulltext.clone();
-    let semantic = state.semantic.clone();
-    let embedder = state.embedder.clone();
-    let chunker = state.chunker.clone();
+    // Each MCP session gets a copy of the server
+    let server = NotidiumServer::new(
+        state.store.clone(),
+        state.fulltext.clone(),
+        state.semantic.clone(),
+        state.embedder.clone(),
+        state.chunker.clone(),
+    )
+    .with_tools(&state.mcp_tools)
+    .with_indexer(state.indexer.clone());
 
     let ct = CancellationToken::new();
 
@@ -191,34 +456,107 @@ pub fn create_router_with_mcp(state: AppState) -> Router {
     };
 
     let mcp_service = StreamableHttpService::new(
-        move || Ok(NotidiumServer::new(store.clone(), fulltext.clone(), semantic.clone(), embedder.clone(), chunker.clone())),
+        move || Ok(server.clone()),
         Arc::new(LocalSessionManager::default()),
         config,
     );
 
-    Router::new()
+    let router = Router::new()
         // Notes CRUD
         .route("/api/notes", get(handlers::list_notes))
         .route("/api/notes", post(handlers::create_note))
+        .route("/api/notes/suggest", get(handlers::suggest_titles))
+        .route("/api/notes/starred", get(handlers::list_starred))
+        .route("/api/notes/from-template", post(handlers::create_from_template))
         .route("/api/notes/{id}", get(handlers::get_note))
         .route("/api/notes/{id}", put(handlers::update_note))
         .route("/api/notes/{id}", delete(handlers::delete_note))
+        .route("/api/notes/{id}/backlinks", get(handlers::get_backlinks))
+        .route("/api/notes/{id}/relations", get(handlers::get_relations))
+        .route("/api/notes/{id}/position", put(handlers::set_read_position))
+        .route("/api/notes/{id}/star", put(handlers::star_note).delete(handlers::unstar_note))
+        .route(
+            "/api/notes/{id}/annotations",
+            get(handlers::list_annotations).post(handlers::create_annotation),
+        )
+        .route(
+            "/api/notes/{id}/annotations/{annotation_id}",
+            put(handlers::update_annotation).delete(handlers::delete_annotation),
+        )
+        .route("/api/highlights", get(handlers::get_highlights))
+        .route("/api/graph", get(handlers::get_graph))
+        .route("/api/moc/generate", post(handlers::generate_moc))
+
+        // Trash
+        .route("/api/trash", get(handlers::list_trash))
+        .route("/api/trash/{id}/restore", post(handlers::restore_from_trash))
+        .route("/api/trash/{id}", delete(handlers::purge_from_trash))
+
+        // Version history
+        .route("/api/notes/{id}/versions", get(handlers::list_versions))
+        .route("/api/notes/{id}/versions/{rev}", get(handlers::get_version))
+        .route("/api/notes/{id}/versions/{rev}/restore", post(handlers::restore_version))
 
         // Search
         .route("/api/search", get(handlers::search))
         .route("/api/search/semantic", get(handlers::semantic_search))
+        .route("/api/search/classify", get(handlers::classify_query))
+        .route("/api/search/regex", get(handlers::regex_search))
+        .route(
+            "/api/search/feedback",
+            get(handlers::list_search_feedback)
+                .post(handlers::record_search_feedback)
+                .delete(handlers::clear_search_feedback),
+        )
         .route("/api/notes/{id}/related", get(handlers::find_related))
+        .route("/api/notes/{id}/chunks", get(handlers::get_note_chunks))
+        .route("/api/notes/{id}/rechunk", post(handlers::rechunk_note))
+        .route("/api/notes/{id}/co-accessed", get(handlers::co_accessed))
 
         // Quick actions
         .route("/api/capture", post(handlers::quick_capture))
+        .route("/api/capture/url", post(handlers::capture_url))
 
         // Attachments
-        .route("/api/attachments", post(handlers::upload_attachment))
-        .route("/api/attachments/{filename}", get(handlers::get_attachment))
+        .route(
+            "/api/attachments",
+            get(handlers::list_attachments).post(handlers::upload_attachment),
+        )
+        .route("/api/attachments/gc", post(handlers::gc_attachments))
+        .route(
+            "/api/attachments/{filename}",
+            get(handlers::get_attachment).delete(handlers::delete_attachment),
+        )
+        .route("/api/export", get(handlers::export_vault))
+        .route("/api/notes/{id}/export", get(handlers::export_note))
+        .route("/api/events", get(handlers::events))
+        .route("/api/canvases", get(handlers::list_canvases))
+        .route("/api/canvases", post(handlers::create_canvas))
+        .route("/api/canvases/{*path}", get(handlers::get_canvas))
+        .route("/api/canvases/{*path}", put(handlers::update_canvas))
+        .route("/api/canvases/{*path}", delete(handlers::delete_canvas))
+        .route("/api/boards/{tag}", get(handlers::get_board))
+        .route("/api/boards/{tag}/move", post(handlers::move_card))
+        .route("/api/notes/{id}/attachments", get(handlers::note_attachments))
+
+        // Templates
+        .route("/api/templates", get(handlers::list_templates))
 
         // Metadata
         .route("/api/tags", get(handlers::list_tags))
+        .route("/api/tags/facets", get(handlers::tag_facets))
+        .route("/api/tags/merge", post(handlers::merge_tags))
+        .route("/api/tags/{name}", patch(handlers::rename_tag))
+        .route("/api/tags/{name}", delete(handlers::delete_tag))
         .route("/api/stats", get(handlers::get_stats))
+        .route("/api/index/status", get(handlers::get_index_status))
+        .route("/api/admin/refresh", post(handlers::refresh_vault))
+
+        // Web UI login
+        .route("/login", get(login_page))
+        .route("/api/auth/login", post(handlers::login))
+        .route("/api/auth/logout", post(handlers::logout))
+        .route("/api/auth/session", get(handlers::get_session))
 
         // Health
         .route("/health", get(handlers::health))
@@ -226,13 +564,17 @@ pub fn create_router_with_mcp(state: AppState) -> Router {
         // OpenAPI spec and Swagger UI
         .merge(SwaggerUi::new("/api/docs").url("/api/openapi.json", openapi))
 
-        // MCP endpoint
+        // MCP endpoint, with OAuth token issuing for its clients
         .nest_service("/mcp", mcp_service)
+        .merge(mcp::auth::routes(state.mcp_auth.clone()))
 
         // Static files (frontend)
         .fallback(static_handler)
 
+        .layer(middleware::from_fn_with_state(state.auth(), auth::authenticate));
+
+    rate_limit::apply(router, &state.store.config().rate_limit, &state.store.config().search)
         .layer(cors)
-        .layer(TraceLayer::new_for_http())
+        .layer(TraceLayer::new_for_http().make_span_with(telemetry::http_request_span))
         .with_state(state)
 }
@@ -0,0 +1,257 @@
+//! Password login sessions for the web UI
+//!
+//! Browsers sign in once with the vault password (stored as an Argon2 hash in
+//! the config) and get an HttpOnly session cookie. Sessions live in memory, so
+//! restarting the server signs everybody out.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::http::{header, HeaderMap};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::config::AuthConfig;
+use crate::error::{Error, Result};
+
+/// Name of the session cookie
+pub const SESSION_COOKIE: &str = "notidium_session";
+
+/// Header carrying a session's CSRF token on state-changing requests
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// A signed-in browser
+#[derive(Debug, Clone)]
+pub struct Session {
+    /// Token state-changing requests must echo in [`CSRF_HEADER`]
+    pub csrf_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Active login sessions, keyed by the SHA-256 hash of their cookie token
+pub struct Sessions {
+    password_hash: Option<String>,
+    lifetime: Duration,
+    secure_cookie: bool,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl Sessions {
+    pub fn new(config: &AuthConfig) -> Self {
+        Self {
+            password_hash: config.password_hash.clone(),
+            lifetime: Duration::hours(config.session_hours.min(i64::MAX as u64) as i64),
+            secure_cookie: config.secure_cookie,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sessions for a server without password login
+    pub fn disabled() -> Self {
+        Self::new(&AuthConfig::default())
+    }
+
+    /// Whether a password is configured, and so the web UI requires login
+    pub fn is_enabled(&self) -> bool {
+        self.password_hash.is_some()
+    }
+
+    /// Check a password against the configured hash
+    pub fn verify_password(&self, password: &str) -> bool {
+        let Some(hash) = &self.password_hash else {
+            return false;
+        };
+        match PasswordHash::new(hash) {
+            Ok(hash) => Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok(),
+            Err(e) => {
+                tracing::warn!("Invalid password hash in config: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Start a session, returning its cookie token and the session
+    pub fn create(&self) -> (String, Session) {
+        let token = random_token();
+        let session = Session {
+            csrf_token: random_token(),
+            expires_at: Utc::now() + self.lifetime,
+        };
+
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Utc::now();
+        sessions.retain(|_, s| s.expires_at > now);
+        sessions.insert(hash_token(&token), session.clone());
+        (token, session)
+    }
+
+    /// The live session a cookie token belongs to
+    pub fn get(&self, token: &str) -> Option<Session> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let key = hash_token(token);
+        match sessions.get(&key) {
+            Some(session) if session.expires_at > Utc::now() => Some(session.clone()),
+            Some(_) => {
+                sessions.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// End a session
+    pub fn remove(&self, token: &str) {
+        self.sessions.lock().unwrap().remove(&hash_token(token));
+    }
+
+    /// `Set-Cookie` value handing a session token to the browser
+    pub fn cookie(&self, token: &str) -> String {
+        format!(
+            "{}={}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}{}",
+            SESSION_COOKIE,
+            token,
+            self.lifetime.num_seconds(),
+            if self.secure_cookie { "; Secure" } else { "" }
+        )
+    }
+
+    /// `Set-Cookie` value removing the session cookie
+    pub fn clear_cookie(&self) -> String {
+        format!(
+            "{}=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0{}",
+            SESSION_COOKIE,
+            if self.secure_cookie { "; Secure" } else { "" }
+        )
+    }
+}
+
+/// Hash a password for the `[auth] password_hash` config setting
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| Error::Other(format!("Failed to hash password: {}", e)))
+}
+
+/// The session token in a request's cookies
+pub fn session_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, token)| token.to_string())
+        .filter(|token| !token.is_empty())
+}
+
+/// Whether a cookie-authenticated request may change state
+///
+/// Requires the session's CSRF token in [`CSRF_HEADER`], or browser-set
+/// headers showing the request came from the server's own pages, which other
+/// sites can't forge.
+pub fn passes_csrf_check(headers: &HeaderMap, session: &Session) -> bool {
+    if let Some(token) = headers.get(CSRF_HEADER).and_then(|v| v.to_str().ok()) {
+        return token == session.csrf_token;
+    }
+    if let Some(site) = headers.get("sec-fetch-site").and_then(|v| v.to_str().ok()) {
+        return site == "same-origin";
+    }
+
+    let origin = headers.get(header::ORIGIN).and_then(|v| v.to_str().ok());
+    let host = headers.get(header::HOST).and_then(|v| v.to_str().ok());
+    match (origin, host) {
+        (Some(origin), Some(host)) => origin
+            .split_once("://")
+            .is_some_and(|(_, origin_host)| origin_host == host),
+        _ => false,
+    }
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+fn hash_token(token: &str) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn sessions_with_password(password: &str) -> Sessions {
+        Sessions::new(&AuthConfig {
+            password_hash: Some(hash_password(password).unwrap()),
+            ..AuthConfig::default()
+        })
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_verify_password() {
+        let sessions = sessions_with_password("hunter2");
+        assert!(sessions.is_enabled());
+        assert!(sessions.verify_password("hunter2"));
+        assert!(!sessions.verify_password("hunter3"));
+        assert!(!Sessions::disabled().verify_password(""));
+    }
+
+    #[test]
+    fn test_session_lifecycle() {
+        let sessions = sessions_with_password("pw");
+        let (token, session) = sessions.create();
+        assert_eq!(sessions.get(&token).unwrap().csrf_token, session.csrf_token);
+        assert!(sessions.get("unknown").is_none());
+
+        sessions.remove(&token);
+        assert!(sessions.get(&token).is_none());
+    }
+
+    #[test]
+    fn test_session_token_from_cookies() {
+        let h = headers(&[("cookie", "theme=dark; notidium_session=abc123")]);
+        assert_eq!(session_token(&h).as_deref(), Some("abc123"));
+        assert_eq!(session_token(&headers(&[("cookie", "notidium_session=")])), None);
+        assert_eq!(session_token(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_csrf_check() {
+        let session = Session {
+            csrf_token: "token".into(),
+            expires_at: Utc::now(),
+        };
+
+        assert!(passes_csrf_check(&headers(&[("x-csrf-token", "token")]), &session));
+        assert!(!passes_csrf_check(&headers(&[("x-csrf-token", "wrong")]), &session));
+        assert!(passes_csrf_check(&headers(&[("sec-fetch-site", "same-origin")]), &session));
+        assert!(!passes_csrf_check(&headers(&[("sec-fetch-site", "cross-site")]), &session));
+        assert!(passes_csrf_check(
+            &headers(&[("origin", "http://localhost:3939"), ("host", "localhost:3939")]),
+            &session
+        ));
+        assert!(!passes_csrf_check(
+            &headers(&[("origin", "https://evil.example"), ("host", "localhost:3939")]),
+            &session
+        ));
+        assert!(!passes_csrf_check(&HeaderMap::new(), &session));
+    }
+}
@@ -0,0 +1,72 @@
+//! Vault-wide API token
+//!
+//! A single token, kept as a SHA-256 hash in the config, that grants full
+//! access to `/api/*` and `/mcp`. It's the quick way to lock down a server
+//! reachable from the network without setting up user accounts; the web UI
+//! sends it as the password of the browser's sign-in prompt.
+
+use uuid::Uuid;
+
+use crate::config::AuthConfig;
+
+const TOKEN_PREFIX: &str = "ntdt_";
+
+/// The configured API token, if any
+pub struct ApiToken {
+    hash: Option<String>,
+}
+
+impl ApiToken {
+    pub fn new(config: &AuthConfig) -> Self {
+        Self {
+            hash: config.api_token_hash.clone(),
+        }
+    }
+
+    /// No API token, for servers relying on other authentication or none
+    pub fn disabled() -> Self {
+        Self { hash: None }
+    }
+
+    /// Whether a token is configured, and so the API requires one
+    pub fn is_enabled(&self) -> bool {
+        self.hash.is_some()
+    }
+
+    /// Whether `token` is the configured token
+    pub fn verify(&self, token: &str) -> bool {
+        self.hash.as_deref() == Some(hash_token(token).as_str())
+    }
+}
+
+/// Generate a new API token, returning it and the hash to store in the config
+pub fn generate_api_token() -> (String, String) {
+    let token = format!("{}{}", TOKEN_PREFIX, Uuid::new_v4().simple());
+    let hash = hash_token(&token);
+    (token, hash)
+}
+
+fn hash_token(token: &str) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_generated_token() {
+        let (token, hash) = generate_api_token();
+        assert!(token.starts_with(TOKEN_PREFIX));
+
+        let api_token = ApiToken::new(&AuthConfig {
+            api_token_hash: Some(hash),
+            ..AuthConfig::default()
+        });
+        assert!(api_token.is_enabled());
+        assert!(api_token.verify(&token));
+        assert!(!api_token.verify("ntdt_wrong"));
+        assert!(!ApiToken::disabled().verify(&token));
+    }
+}
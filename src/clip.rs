@@ -0,0 +1,776 @@
+//! Web clipper
+//!
+//! Turns a web page into a note the way browser reader modes do: the
+//! element holding most of the page's prose is picked as the article,
+//! navigation and scripts are dropped, and what remains is converted to
+//! markdown. Images are downloaded into the attachments directory so the
+//! note still shows them when the page changes or goes away.
+//!
+//! Pages are fetched on behalf of API and MCP clients, so only hosts with
+//! public addresses are contacted unless `clip.allow_private_hosts` is set:
+//! names are resolved through a resolver that drops other addresses, and
+//! IP addresses in URLs are checked before every request and redirect.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ego_tree::NodeRef;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use scraper::{ElementRef, Html, Node, Selector};
+
+use crate::config::ClipConfig;
+use crate::error::{Error, Result};
+
+/// Largest page or image downloaded, in bytes
+const MAX_DOWNLOAD_BYTES: usize = 10 * 1024 * 1024;
+
+/// Most images downloaded for one page
+const MAX_IMAGES: usize = 50;
+
+/// Most redirects followed for one request
+const MAX_REDIRECTS: usize = 10;
+
+/// Elements that never hold article content
+const SKIPPED: &[&str] = &[
+    "script", "style", "noscript", "template", "nav", "header", "footer", "aside", "form",
+    "iframe", "svg", "button", "select", "input", "textarea", "dialog", "head",
+];
+
+/// Elements converted as blocks of their own
+const BLOCKS: &[&str] = &[
+    "address", "article", "blockquote", "dd", "details", "div", "dl", "dt", "figcaption",
+    "figure", "h1", "h2", "h3", "h4", "h5", "h6", "hr", "li", "main", "ol", "p", "pre",
+    "section", "summary", "table", "ul",
+];
+
+/// A page reduced to its article
+#[derive(Debug, Clone)]
+pub struct ClippedPage {
+    pub title: Option<String>,
+    pub markdown: String,
+    /// Absolute URLs of the images the markdown embeds
+    pub images: Vec<Url>,
+}
+
+/// Parse a URL to clip, which must be http or https
+pub fn parse_url(url: &str) -> Result<Url> {
+    let parsed = Url::parse(url.trim())
+        .map_err(|e| Error::Capture(format!("Invalid URL {:?}: {}", url, e)))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(parsed),
+        scheme => Err(Error::Capture(format!("Unsupported URL scheme: {}", scheme))),
+    }
+}
+
+/// Fetch a page and turn it into note content, downloading its images into
+/// `attachments_dir`
+pub async fn clip(url: &Url, attachments_dir: &Path, config: &ClipConfig) -> Result<String> {
+    let mut builder = reqwest::Client::builder()
+        .user_agent(concat!("notidium/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(30));
+    if !config.allow_private_hosts {
+        builder = builder.dns_resolver(Arc::new(PublicResolver)).redirect(Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if let Err(e) = check_address(attempt.url()) {
+                attempt.error(e)
+            } else {
+                attempt.follow()
+            }
+        }));
+    }
+    let client = builder.build().map_err(fetch_error)?;
+    let checked = |url: &Url| if config.allow_private_hosts { Ok(()) } else { check_address(url) };
+
+    checked(url)?;
+    let response = client
+        .get(url.clone())
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(fetch_error)?;
+    let is_html = content_type(&response).is_none_or(|t| t.contains("html"));
+    if !is_html {
+        return Err(Error::Capture(format!("{} is not an HTML page", url)));
+    }
+    // Relative links resolve against where redirects ended up
+    let base = response.url().clone();
+    let body = read_limited(response).await?;
+    let page = extract(&String::from_utf8_lossy(&body), &base);
+
+    let mut markdown = page.markdown;
+    let images: Vec<Url> = page
+        .images
+        .into_iter()
+        .filter(|image| match checked(image) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!("Not downloading image {}: {}", image, e);
+                false
+            }
+        })
+        .collect();
+    for (image, name) in download_images(&client, &images, attachments_dir).await {
+        markdown = markdown.replace(
+            &format!("]({})", image),
+            &format!("](/api/attachments/{})", name),
+        );
+    }
+
+    Ok(match page.title {
+        Some(title) => format!("# {}\n\n{}\n", title, markdown),
+        None => format!("{}\n", markdown),
+    })
+}
+
+/// Pick the article out of a page and convert it to markdown, resolving
+/// links and images against `base`
+pub fn extract(html: &str, base: &Url) -> ClippedPage {
+    let document = Html::parse_document(html);
+    let title = page_title(&document);
+
+    let mut converter = Converter {
+        base,
+        images: Vec::new(),
+    };
+    let markdown = match article_root(&document) {
+        Some(root) => converter.blocks(*root).join("\n\n"),
+        None => String::new(),
+    };
+
+    // The title is shown as the note's heading
+    let markdown = match &title {
+        Some(title) => markdown
+            .strip_prefix(&format!("# {}", escape(title)))
+            .map_or(markdown.clone(), |rest| rest.trim_start().to_string()),
+        None => markdown,
+    };
+
+    ClippedPage {
+        title,
+        markdown,
+        images: converter.images,
+    }
+}
+
+fn page_title(document: &Html) -> Option<String> {
+    let selectors = [
+        ("meta[property=\"og:title\"]", true),
+        ("title", false),
+        ("h1", false),
+    ];
+    selectors.iter().find_map(|(selector, is_meta)| {
+        let element = document.select(&Selector::parse(selector).ok()?).next()?;
+        let text = if *is_meta {
+            element.value().attr("content")?.to_string()
+        } else {
+            element.text().collect()
+        };
+        let text = collapse_whitespace(&text).trim().to_string();
+        (!text.is_empty()).then_some(text)
+    })
+}
+
+/// The element holding the page's article: `<article>` or `<main>` when the
+/// page marks it, otherwise the element whose paragraphs hold the most text
+fn article_root(document: &Html) -> Option<ElementRef<'_>> {
+    for selector in ["article", "main", "[role=\"main\"]"] {
+        let selector = Selector::parse(selector).expect("valid selector");
+        if let Some(element) = document.select(&selector).find(|e| !is_skipped(e)) {
+            return Some(element);
+        }
+    }
+
+    // Paragraphs count fully for their parent and half for its parent, less
+    // the share of their text that is link text
+    let mut scores: HashMap<ego_tree::NodeId, f64> = HashMap::new();
+    let paragraphs = Selector::parse("p, pre, td").expect("valid selector");
+    for paragraph in document.select(&paragraphs).filter(|e| !is_skipped(e)) {
+        let text: String = paragraph.text().collect();
+        let len = text.trim().chars().count() as f64;
+        if len < 25.0 {
+            continue;
+        }
+        let links = Selector::parse("a").expect("valid selector");
+        let link_len: usize = paragraph
+            .select(&links)
+            .map(|a| a.text().map(|t| t.trim().chars().count()).sum::<usize>())
+            .sum();
+        let score = len * (1.0 - link_len as f64 / len).max(0.0);
+
+        let mut ancestors = paragraph.ancestors().filter(|n| n.value().is_element());
+        if let Some(parent) = ancestors.next() {
+            *scores.entry(parent.id()).or_default() += score;
+            if let Some(grandparent) = ancestors.next() {
+                *scores.entry(grandparent.id()).or_default() += score / 2.0;
+            }
+        }
+    }
+
+    let best = scores
+        .into_iter()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .and_then(|(id, _)| document.tree.get(id))
+        .and_then(ElementRef::wrap);
+    best.or_else(|| {
+        let body = Selector::parse("body").expect("valid selector");
+        document.select(&body).next()
+    })
+}
+
+/// Whether an element is or sits inside one that never holds content
+fn is_skipped(element: &ElementRef) -> bool {
+    std::iter::once(**element)
+        .chain(element.ancestors())
+        .filter_map(|n| n.value().as_element())
+        .any(|e| SKIPPED.contains(&e.name()))
+}
+
+struct Converter<'a> {
+    base: &'a Url,
+    images: Vec<Url>,
+}
+
+impl Converter<'_> {
+    /// Markdown blocks of a node's children, with runs of inline content
+    /// gathered into paragraphs
+    fn blocks(&mut self, node: NodeRef<Node>) -> Vec<String> {
+        let mut blocks = Vec::new();
+        let mut paragraph = String::new();
+
+        for child in node.children() {
+            let block = child
+                .value()
+                .as_element()
+                .is_some_and(|e| BLOCKS.contains(&e.name()));
+            if block {
+                push_paragraph(&mut blocks, &mut paragraph);
+                blocks.extend(self.block(child));
+            } else {
+                let text = self.inline(child);
+                if paragraph.ends_with([' ', '\n']) {
+                    paragraph.push_str(text.trim_start_matches(' '));
+                } else {
+                    paragraph.push_str(&text);
+                }
+            }
+        }
+        push_paragraph(&mut blocks, &mut paragraph);
+
+        blocks
+    }
+
+    fn block(&mut self, node: NodeRef<Node>) -> Option<String> {
+        let element = node.value().as_element()?;
+        let markdown = match element.name() {
+            name @ ("h1" | "h2" | "h3" | "h4" | "h5" | "h6") => {
+                let level: usize = name[1..].parse().unwrap_or(1);
+                let text = self.inline_children(node);
+                let text = collapse_whitespace(&text).trim().to_string();
+                (!text.is_empty()).then(|| format!("{} {}", "#".repeat(level), text))?
+            }
+            "hr" => "---".to_string(),
+            "pre" => {
+                let code: String = ElementRef::wrap(node)?.text().collect();
+                let language = ElementRef::wrap(node)?
+                    .select(&Selector::parse("code").expect("valid selector"))
+                    .next()
+                    .and_then(|code| code_language(code.value().attr("class")?))
+                    .or_else(|| code_language(element.attr("class")?))
+                    .unwrap_or_default();
+                format!("```{}\n{}\n```", language, code.trim_end_matches('\n'))
+            }
+            "blockquote" => {
+                let inner = self.blocks(node).join("\n\n");
+                inner
+                    .lines()
+                    .map(|line| if line.is_empty() { ">".to_string() } else { format!("> {}", line) })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            "ul" | "ol" => self.list(node, element.name() == "ol"),
+            "table" => self.table(node)?,
+            _ => self.blocks(node).join("\n\n"),
+        };
+        (!markdown.trim().is_empty()).then_some(markdown)
+    }
+
+    fn list(&mut self, node: NodeRef<Node>, ordered: bool) -> String {
+        let items = node
+            .children()
+            .filter(|c| c.value().as_element().is_some_and(|e| e.name() == "li"));
+        let mut lines = Vec::new();
+        for (i, item) in items.enumerate() {
+            let marker = if ordered { format!("{}. ", i + 1) } else { "- ".to_string() };
+            let indent = " ".repeat(marker.len());
+            let content = self.blocks(item).join("\n");
+            for (j, line) in content.lines().enumerate() {
+                if j == 0 {
+                    lines.push(format!("{}{}", marker, line));
+                } else if line.is_empty() {
+                    lines.push(String::new());
+                } else {
+                    lines.push(format!("{}{}", indent, line));
+                }
+            }
+        }
+        lines.join("\n")
+    }
+
+    fn table(&mut self, node: NodeRef<Node>) -> Option<String> {
+        let rows_selector = Selector::parse("tr").expect("valid selector");
+        let rows: Vec<Vec<String>> = ElementRef::wrap(node)?
+            .select(&rows_selector)
+            .map(|row| {
+                row.children()
+                    .filter(|c| {
+                        c.value()
+                            .as_element()
+                            .is_some_and(|e| e.name() == "td" || e.name() == "th")
+                    })
+                    .map(|cell| {
+                        let text = self.inline_children(cell);
+                        collapse_whitespace(&text).trim().replace('|', "\\|")
+                    })
+                    .collect()
+            })
+            .filter(|cells: &Vec<String>| !cells.is_empty())
+            .collect();
+
+        let columns = rows.iter().map(Vec::len).max()?;
+        let line = |cells: &[String]| {
+            let mut cells = cells.to_vec();
+            cells.resize(columns, String::new());
+            format!("| {} |", cells.join(" | "))
+        };
+        let mut lines = vec![line(&rows[0]), line(&vec!["---".to_string(); columns])];
+        lines.extend(rows[1..].iter().map(|row| line(row)));
+        Some(lines.join("\n"))
+    }
+
+    fn inline_children(&mut self, node: NodeRef<Node>) -> String {
+        node.children().map(|child| self.inline(child)).collect()
+    }
+
+    fn inline(&mut self, node: NodeRef<Node>) -> String {
+        let element = match node.value() {
+            Node::Text(text) => return escape(&collapse_whitespace(text)),
+            Node::Element(element) => element,
+            _ => return String::new(),
+        };
+
+        match element.name() {
+            name if SKIPPED.contains(&name) => String::new(),
+            "br" => "  \n".to_string(),
+            "img" => self.image(element).unwrap_or_default(),
+            "code" | "kbd" | "samp" => {
+                let code: String = ElementRef::wrap(node)
+                    .map(|e| e.text().collect())
+                    .unwrap_or_default();
+                let code = collapse_whitespace(&code);
+                if code.trim().is_empty() {
+                    String::new()
+                } else if code.contains('`') {
+                    format!("`` {} ``", code.trim())
+                } else {
+                    format!("`{}`", code.trim())
+                }
+            }
+            "strong" | "b" => {
+                let text = self.inline_children(node);
+                wrap(&text, "**")
+            }
+            "em" | "i" => {
+                let text = self.inline_children(node);
+                wrap(&text, "*")
+            }
+            "del" | "s" | "strike" => {
+                let text = self.inline_children(node);
+                wrap(&text, "~~")
+            }
+            "a" => {
+                let text = self.inline_children(node);
+                let href = element
+                    .attr("href")
+                    .filter(|h| !h.starts_with('#') && !h.starts_with("javascript:"))
+                    .and_then(|h| self.base.join(h).ok());
+                match href {
+                    Some(href) if !text.trim().is_empty() => {
+                        let (lead, inner, trail) = split_spaces(&text);
+                        format!("{}[{}]({}){}", lead, inner, href, trail)
+                    }
+                    _ => text,
+                }
+            }
+            _ => self.inline_children(node),
+        }
+    }
+
+    fn image(&mut self, element: &scraper::node::Element) -> Option<String> {
+        let src = element
+            .attr("data-src")
+            .or_else(|| element.attr("src"))
+            .filter(|s| !s.starts_with("data:"))?;
+        let url = self.base.join(src).ok()?;
+        let alt = collapse_whitespace(element.attr("alt").unwrap_or_default());
+        let markdown = format!("![{}]({})", escape(alt.trim()), url);
+        if !self.images.contains(&url) {
+            self.images.push(url);
+        }
+        Some(markdown)
+    }
+}
+
+/// Download images into `dir`, returning the file name each one was saved
+/// under. Images that fail to download keep their remote URL.
+async fn download_images(client: &reqwest::Client, images: &[Url], dir: &Path) -> Vec<(Url, String)> {
+    if images.is_empty() {
+        return Vec::new();
+    }
+    if let Err(e) = tokio::fs::create_dir_all(dir).await {
+        tracing::warn!("Failed to create attachments directory: {}", e);
+        return Vec::new();
+    }
+
+    let mut saved = Vec::new();
+    for url in images.iter().take(MAX_IMAGES) {
+        match download_image(client, url, dir).await {
+            Ok(name) => saved.push((url.clone(), name)),
+            Err(e) => tracing::warn!("Failed to download image {}: {}", url, e),
+        }
+    }
+    saved
+}
+
+async fn download_image(client: &reqwest::Client, url: &Url, dir: &Path) -> Result<String> {
+    let response = client
+        .get(url.clone())
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(fetch_error)?;
+
+    let path_extension = Path::new(url.path())
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    let extension = content_type(&response)
+        .and_then(|t| image_extension(&t))
+        .or_else(|| path_extension.as_deref().and_then(image_extension_of))
+        .ok_or_else(|| Error::Capture("not an image".to_string()))?;
+    let data = read_limited(response).await?;
+
+    let stem = Path::new(url.path())
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("image");
+    let sanitized: String = stem
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+        .take(50)
+        .collect();
+    let sanitized = if sanitized.is_empty() { "image".to_string() } else { sanitized };
+    let uuid_suffix = &uuid::Uuid::new_v4().to_string()[..8];
+    let name = format!("{}_{}.{}", sanitized, uuid_suffix, extension);
+
+    tokio::fs::write(dir.join(&name), &data).await?;
+    Ok(name)
+}
+
+fn content_type(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)?
+        .to_str()
+        .ok()
+        .map(|t| t.to_lowercase())
+}
+
+fn image_extension(content_type: &str) -> Option<&'static str> {
+    let mime = content_type.split(';').next()?.trim();
+    match mime {
+        "image/png" => Some("png"),
+        "image/jpeg" | "image/jpg" => Some("jpg"),
+        "image/gif" => Some("gif"),
+        "image/webp" => Some("webp"),
+        "image/svg+xml" => Some("svg"),
+        _ => None,
+    }
+}
+
+fn image_extension_of(extension: &str) -> Option<&'static str> {
+    match extension {
+        "png" => Some("png"),
+        "jpg" | "jpeg" => Some("jpg"),
+        "gif" => Some("gif"),
+        "webp" => Some("webp"),
+        "svg" => Some("svg"),
+        _ => None,
+    }
+}
+
+/// Read a response body, refusing ones over [`MAX_DOWNLOAD_BYTES`]
+async fn read_limited(mut response: reqwest::Response) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(fetch_error)? {
+        body.extend_from_slice(&chunk);
+        if body.len() > MAX_DOWNLOAD_BYTES {
+            return Err(Error::Capture(format!(
+                "{} is larger than {} MB",
+                response.url(),
+                MAX_DOWNLOAD_BYTES / (1024 * 1024)
+            )));
+        }
+    }
+    Ok(body)
+}
+
+fn fetch_error(e: reqwest::Error) -> Error {
+    // The cause says why, e.g. that a host only has private addresses
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(&e);
+    while let Some(cause) = source {
+        message = format!("{}: {}", message, cause);
+        source = cause.source();
+    }
+    Error::Capture(message)
+}
+
+/// Reject URLs naming a loopback, private or otherwise non-public IP
+/// address; host names are checked when [`PublicResolver`] resolves them
+fn check_address(url: &Url) -> Result<()> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| Error::Capture(format!("{} has no host", url)))?;
+    let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() else {
+        return Ok(());
+    };
+    if is_public(ip) {
+        Ok(())
+    } else {
+        Err(Error::Capture(format!("{} is not a public address", ip)))
+    }
+}
+
+/// Whether `ip` is routable on the internet
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                // Shared address space (RFC 6598), benchmarking (RFC 2544),
+                // reserved
+                || (a == 100 && (64..128).contains(&b))
+                || (a == 198 && (18..20).contains(&b))
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(v4));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                // Unique local, link-local, documentation
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+                || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+        }
+    }
+}
+
+/// Resolves host names to their public addresses only, so a name can't
+/// point the clipper at the server's own network
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", host).into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// Language of a code block from a `language-rust` or `lang-rust` class
+fn code_language(class: &str) -> Option<String> {
+    class.split_whitespace().find_map(|c| {
+        c.strip_prefix("language-")
+            .or_else(|| c.strip_prefix("lang-"))
+            .map(str::to_string)
+    })
+}
+
+fn push_paragraph(blocks: &mut Vec<String>, paragraph: &mut String) {
+    let text = paragraph
+        .lines()
+        .map(str::trim_start)
+        .collect::<Vec<_>>()
+        .join("\n");
+    let text = text.trim();
+    if !text.is_empty() {
+        blocks.push(text.to_string());
+    }
+    paragraph.clear();
+}
+
+/// Wrap text in emphasis markers, keeping its surrounding spaces outside
+fn wrap(text: &str, marker: &str) -> String {
+    let (lead, inner, trail) = split_spaces(text);
+    if inner.is_empty() {
+        return text.to_string();
+    }
+    format!("{}{}{}{}{}", lead, marker, inner, marker, trail)
+}
+
+fn split_spaces(text: &str) -> (&str, &str, &str) {
+    let inner = text.trim();
+    let lead = &text[..text.len() - text.trim_start().len()];
+    let trail = &text[text.trim_end().len()..];
+    (if lead.is_empty() { "" } else { " " }, inner, if trail.is_empty() { "" } else { " " })
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !in_space {
+                collapsed.push(' ');
+            }
+            in_space = true;
+        } else {
+            collapsed.push(c);
+            in_space = false;
+        }
+    }
+    collapsed
+}
+
+/// Escape characters markdown would read as formatting
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> Url {
+        Url::parse("https://example.com/blog/post").unwrap()
+    }
+
+    #[test]
+    fn test_only_public_addresses_are_fetched() {
+        for ip in ["93.184.216.34", "2606:2800:220:1::1"] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1", "10.0.0.8", "172.16.4.2", "192.168.1.1", "169.254.169.254", "100.64.0.1",
+            "0.0.0.0", "::1", "fd00::1", "fe80::1", "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        assert!(check_address(&Url::parse("http://[::1]:8080/").unwrap()).is_err());
+        assert!(check_address(&Url::parse("http://example.com/").unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_extract_prefers_article_and_drops_chrome() {
+        let html = r#"<html><head><title>Ignored</title>
+            <meta property="og:title" content="Async Rust">
+            <script>var x = 1;</script></head>
+            <body><nav><a href="/">Home</a></nav>
+            <article><h1>Async Rust</h1>
+            <p>Futures are <strong>lazy</strong>, see <a href="/docs">the docs</a>.</p>
+            <aside>Subscribe!</aside>
+            <img src="img/diagram.png" alt="Diagram"></article>
+            <footer>Copyright</footer></body></html>"#;
+        let page = extract(html, &base());
+
+        assert_eq!(page.title.as_deref(), Some("Async Rust"));
+        assert_eq!(
+            page.markdown,
+            "Futures are **lazy**, see [the docs](https://example.com/docs).\n\n\
+             ![Diagram](https://example.com/blog/img/diagram.png)"
+        );
+        assert_eq!(page.images, [Url::parse("https://example.com/blog/img/diagram.png").unwrap()]);
+    }
+
+    #[test]
+    fn test_extract_scores_paragraphs_without_article() {
+        let html = r#"<body>
+            <div id="menu"><p><a href="/a">A link that is long enough to count</a></p></div>
+            <div id="content">
+              <p>The first paragraph of the post has plenty of text in it.</p>
+              <p>The second paragraph keeps going with more prose to read.</p>
+            </div></body>"#;
+        let page = extract(html, &base());
+
+        assert!(page.markdown.starts_with("The first paragraph"));
+        assert!(!page.markdown.contains("A link"));
+    }
+
+    #[test]
+    fn test_extract_converts_blocks() {
+        let html = r#"<article>
+            <h2>Setup</h2>
+            <ol><li>Install</li><li>Run <code>cargo build</code></li></ol>
+            <ul><li>One<ul><li>Nested</li></ul></li></ul>
+            <pre><code class="language-rust">fn main() {
+    println!("hi");
+}
+</code></pre>
+            <blockquote><p>Quoted</p><p>Twice</p></blockquote>
+            <table><tr><th>Key</th><th>Value</th></tr><tr><td>a|b</td><td>1</td></tr></table>
+            <p>snake_case<br>next line</p>
+            </article>"#;
+        let page = extract(html, &base());
+
+        assert_eq!(
+            page.markdown,
+            "## Setup\n\n\
+             1. Install\n2. Run `cargo build`\n\n\
+             - One\n  - Nested\n\n\
+             ```rust\nfn main() {\n    println!(\"hi\");\n}\n```\n\n\
+             > Quoted\n>\n> Twice\n\n\
+             | Key | Value |\n| --- | --- |\n| a\\|b | 1 |\n\n\
+             snake\\_case  \nnext line"
+        );
+    }
+
+    #[test]
+    fn test_parse_url_requires_http() {
+        assert!(parse_url("https://example.com").is_ok());
+        assert!(parse_url("file:///etc/passwd").is_err());
+        assert!(parse_url("not a url").is_err());
+    }
+}
@@ -1,9 +1,12 @@
 //! Configuration for Notidium
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr};
 use std::path::PathBuf;
 
 use crate::error::{Error, Result};
+use crate::search::{SearchMode, SnippetOptions};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -22,6 +25,11 @@ pub struct Config {
     #[serde(default = "default_templates_dir")]
     pub templates_dir: String,
 
+    /// Address the HTTP and MCP servers listen on; the default, 127.0.0.1,
+    /// only accepts connections from this machine
+    #[serde(default = "default_bind_address")]
+    pub bind_address: IpAddr,
+
     /// HTTP server port
     #[serde(default = "default_http_port")]
     pub http_port: u16,
@@ -37,17 +45,212 @@ pub struct Config {
     /// Search settings
     #[serde(default)]
     pub search: SearchConfig,
+
+    /// Snapshot settings
+    #[serde(default)]
+    pub snapshot: SnapshotConfig,
+
+    /// Version history settings
+    #[serde(default)]
+    pub history: HistoryConfig,
+
+    /// Markdown formatting applied when notes are saved
+    #[serde(default)]
+    pub format: FormatConfig,
+
+    /// Web UI login settings
+    #[serde(default)]
+    pub auth: AuthConfig,
+
+    /// HTTP MCP endpoint access settings
+    #[serde(default)]
+    pub mcp: McpConfig,
+
+    /// Trace export settings
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+
+    /// Per-client request limits for the HTTP API
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+
+    /// URLs notified of note changes
+    #[serde(default)]
+    pub webhooks: WebhookConfig,
+
+    /// Web clipper settings
+    #[serde(default)]
+    pub clip: ClipConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingConfig {
-    /// Model for prose embeddings
+    /// Embed notes for semantic search; when off, notes are only indexed
+    /// for full-text search and no model is downloaded
+    #[serde(default = "default_embedding_enabled")]
+    pub enabled: bool,
+
+    /// Model for prose embeddings, one of fastembed's; switching models
+    /// needs `notidium index --force`
     #[serde(default = "default_prose_model")]
     pub prose_model: String,
 
+    /// Model for code embeddings, one of fastembed's
+    #[serde(default = "default_code_model")]
+    pub code_model: String,
+
+    /// Embed code blocks with `code_model` as well; when off, code is
+    /// matched by its prose embeddings and the code model is never loaded
+    #[serde(default = "default_code_embeddings")]
+    pub code_embeddings: bool,
+
     /// Batch size for embedding
     #[serde(default = "default_batch_size")]
     pub batch_size: usize,
+
+    /// Words a prose chunk cut at the word limit shares with the next one
+    #[serde(default)]
+    pub chunk_overlap_words: usize,
+
+    /// Words below which the end of a long prose passage is merged into the
+    /// chunk before it rather than embedded on its own
+    #[serde(default = "default_min_chunk_words")]
+    pub min_chunk_words: usize,
+
+    /// Embed chunks after the note title and the headings they sit under,
+    /// like `Title > Deployment > Kubernetes: ...`
+    #[serde(default = "default_heading_context")]
+    pub heading_context: bool,
+
+    /// Split code blocks at their functions, classes and other top-level
+    /// definitions, one chunk per symbol; needs the `code-symbols` feature
+    #[serde(default = "default_code_symbols")]
+    pub code_symbols: bool,
+
+    /// Most tokens of the prose model's tokenizer a chunk may hold; longer
+    /// chunks are split before embedding instead of being truncated by the
+    /// model. 0 turns the limit off
+    #[serde(default = "default_max_chunk_tokens")]
+    pub max_chunk_tokens: usize,
+
+    /// Batches `notidium index` embeds at once, keeping a GPU or a remote
+    /// API busy while the next batch is chunked and sent
+    #[serde(default = "default_concurrent_batches")]
+    pub concurrent_batches: usize,
+
+    /// Precision semantic search keeps embeddings at in memory
+    #[serde(default)]
+    pub quantization: Quantization,
+
+    /// Megabytes of chunk content and embeddings semantic search holds in
+    /// memory; past it they are searched from a file under
+    /// `.notidium/spill`. 0 for no limit
+    #[serde(default)]
+    pub memory_budget_mb: usize,
+
+    /// Where embedded chunks are saved between restarts
+    #[serde(default)]
+    pub vector_store: VectorBackend,
+
+    /// Where embeddings are computed
+    #[serde(default)]
+    pub provider: EmbeddingProvider,
+
+    /// Hardware local models run on; GPUs need notidium built with the
+    /// matching feature (`cuda`, `coreml` or `directml`)
+    #[serde(default)]
+    pub execution_provider: ExecutionProvider,
+
+    /// Batches local models embed in parallel, each on ONNX Runtime's own
+    /// threads; one per CPU when unset
+    #[serde(default)]
+    pub threads: Option<usize>,
+
+    /// OpenAI-compatible API used with `provider = "remote"`
+    #[serde(default)]
+    pub remote: RemoteEmbeddingConfig,
+}
+
+/// Where embeddings are computed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddingProvider {
+    /// fastembed's ONNX models, downloaded on first use
+    #[default]
+    Local,
+    /// An OpenAI-compatible `/embeddings` endpoint; `prose_model` and
+    /// `code_model` name the API's models
+    Remote,
+}
+
+/// Hardware running local embedding models
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionProvider {
+    #[default]
+    Cpu,
+    /// NVIDIA GPUs on Linux and Windows
+    Cuda,
+    /// Apple's Neural Engine and GPUs on macOS
+    #[serde(rename = "coreml")]
+    CoreMl,
+    /// DirectX 12 GPUs on Windows
+    #[serde(rename = "directml")]
+    DirectMl,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteEmbeddingConfig {
+    /// Base URL of the API, `/embeddings` is appended
+    #[serde(default = "default_remote_embedding_url")]
+    pub url: String,
+
+    /// API key; when unset, read from the `api_key_env` environment variable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+
+    /// Environment variable holding the API key
+    #[serde(default = "default_remote_embedding_key_env")]
+    pub api_key_env: String,
+
+    /// Embedding size to ask for, for models that can shorten theirs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<usize>,
+
+    /// Request timeout in seconds
+    #[serde(default = "default_remote_embedding_timeout")]
+    pub timeout_secs: u64,
+}
+
+/// In-memory precision of embeddings
+///
+/// The vector store always keeps full precision, so switching needs no
+/// re-embedding, only a restart.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quantization {
+    /// 32-bit floats
+    #[default]
+    F32,
+    /// 16-bit floats; half the memory, scores within about 0.001
+    F16,
+    /// 8-bit integers scaled per vector; a quarter of the memory, scores
+    /// within about 0.01
+    Int8,
+}
+
+/// Storage for embedded chunks
+///
+/// Switching starts from an empty store, so it needs `notidium index`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VectorBackend {
+    /// A LanceDB table in `.notidium/vectors/`
+    #[default]
+    Lance,
+    /// A table of `.notidium/index.db`, with the sqlite-vec extension, so
+    /// chunks live in one file with the rest of the metadata
+    Sqlite,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,6 +262,448 @@ pub struct SearchConfig {
     /// Maximum number of results
     #[serde(default = "default_max_limit")]
     pub max_limit: usize,
+
+    /// Mode of searches that don't name one; when unset, MCP tools search
+    /// semantically and the web UI and CLI by keywords
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_mode: Option<SearchMode>,
+
+    /// Default modes overriding `default_mode` for one interface (`http`,
+    /// `mcp` or `cli`) or for one API key user or MCP client by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub client_modes: BTreeMap<String, SearchMode>,
+
+    /// Share of the semantic ranking in hybrid search (0.0 = full-text only,
+    /// 1.0 = semantic only)
+    #[serde(default = "default_semantic_weight")]
+    pub semantic_weight: f32,
+
+    /// How semantic search finds the chunks nearest a query
+    #[serde(default)]
+    pub semantic_index: SemanticIndex,
+
+    /// Rules deciding whether a semantic query is matched as prose or code
+    #[serde(default)]
+    pub classifier: ClassifierConfig,
+
+    /// How `strategy=multi` splits queries into sub-queries
+    #[serde(default)]
+    pub multi_query: MultiQueryConfig,
+
+    /// How much `recency=true` favors recently updated notes
+    #[serde(default)]
+    pub recency: RecencyConfig,
+
+    /// How feedback on results adjusts later rankings
+    #[serde(default)]
+    pub feedback: FeedbackConfig,
+
+    /// Default snippet length of search results, in characters
+    #[serde(default = "default_snippet_len")]
+    pub snippet_len: usize,
+
+    /// Longest snippet a search may ask for
+    #[serde(default = "default_max_snippet_len")]
+    pub max_snippet_len: usize,
+
+    /// Largest file `grep` and regex searches read, in KiB
+    #[serde(default = "default_grep_max_file_kb")]
+    pub grep_max_file_kb: u64,
+
+    /// Full-text ranking weight of each field
+    #[serde(default)]
+    pub boosts: FieldBoosts,
+
+    /// Named boost overrides searches can pick with `profile`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, RankingProfile>,
+
+    /// How note text is split into full-text index terms
+    #[serde(default)]
+    pub analyzer: AnalyzerConfig,
+
+    /// Full-text index tuning and startup warm-up
+    #[serde(default)]
+    pub index: IndexConfig,
+}
+
+/// Full-text index tuning
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexConfig {
+    /// Memory budget of the index writer, in megabytes (at least 15)
+    #[serde(default = "default_writer_heap_mb")]
+    pub writer_heap_mb: usize,
+
+    /// When searches see new commits
+    #[serde(default)]
+    pub reader_reload: ReaderReload,
+
+    /// Run a cheap search and embedding at startup, so the first real query
+    /// doesn't pay for loading the index and models
+    #[serde(default = "default_warm_up")]
+    pub warm_up: bool,
+}
+
+/// When the full-text index reader picks up new commits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReaderReload {
+    /// Shortly after any commit, including ones from other processes like
+    /// `notidium new`
+    #[default]
+    OnCommit,
+    /// Only right after this process commits
+    Manual,
+}
+
+/// How semantic search finds the chunks nearest a query
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SemanticIndex {
+    /// Approximate nearest neighbor graph, kept up to date as notes are
+    /// embedded; fast on large vaults, rarely misses a close match
+    #[default]
+    Hnsw,
+    /// Compare the query with every chunk; slower, but never misses
+    Exact,
+}
+
+/// Query classifier rules
+///
+/// Every signal found in a query adds its weight to the query's score,
+/// which picks prose, hybrid or code matching.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassifierConfig {
+    /// Extra signals, on top of the built-in ones; a negative weight marks
+    /// prose
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub signals: Vec<ClassifierSignal>,
+
+    /// Weight of each built-in signal
+    #[serde(default = "default_builtin_weight")]
+    pub builtin_weight: f32,
+
+    /// Score from which a query is matched as code
+    #[serde(default = "default_code_threshold")]
+    pub code_threshold: f32,
+
+    /// Score from which a query is matched as both prose and code
+    #[serde(default = "default_hybrid_threshold")]
+    pub hybrid_threshold: f32,
+}
+
+/// Query decomposition for multi-query retrieval
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MultiQueryConfig {
+    /// Most sub-queries searched per query
+    #[serde(default = "default_max_queries")]
+    pub max_queries: usize,
+
+    /// Program and arguments reading the query on stdin and printing one
+    /// sub-query per line; queries split at sentence ends when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+}
+
+/// Recency weighting of semantic results
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecencyConfig {
+    /// Days after which a note's recency bonus has halved
+    #[serde(default = "default_half_life_days")]
+    pub half_life_days: f32,
+
+    /// Most of its score a long-untouched note can lose (0.0 to 1.0)
+    #[serde(default = "default_recency_weight")]
+    pub weight: f32,
+}
+
+/// Ranking adjustments learned from feedback on search results
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeedbackConfig {
+    /// Adjust rankings by feedback at all; it is recorded either way
+    #[serde(default = "default_feedback_enabled")]
+    pub enabled: bool,
+
+    /// Irrelevant marks a note needs for a term before it is demoted
+    #[serde(default = "default_feedback_min_marks")]
+    pub min_marks: u32,
+
+    /// Score kept per irrelevant mark beyond the clicks (0.0 to 1.0)
+    #[serde(default = "default_feedback_demote")]
+    pub demote: f32,
+
+    /// Score gained per log of the clicks beyond the irrelevant marks
+    #[serde(default = "default_feedback_promote")]
+    pub promote: f32,
+}
+
+/// A text that hints at the kind of query it appears in
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassifierSignal {
+    /// Matched as a case-sensitive substring
+    pub pattern: String,
+    #[serde(default = "default_builtin_weight")]
+    pub weight: f32,
+}
+
+/// Full-text analysis settings; changing them needs `notidium index --force`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AnalyzerConfig {
+    /// Words left out of the index and queries, matched case-insensitively
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stopwords: Vec<String>,
+
+    /// Terms indexed whole, never split at punctuation or stemmed (`C++`,
+    /// `node.js`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub protected_terms: Vec<String>,
+
+    /// Stem words for this language (e.g. `english`); off when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stemmer: Option<String>,
+}
+
+/// How much a full-text match in each field counts towards a note's score
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FieldBoosts {
+    #[serde(default = "default_title_boost")]
+    pub title: f32,
+
+    #[serde(default = "default_content_boost")]
+    pub content: f32,
+
+    #[serde(default = "default_tags_boost")]
+    pub tags: f32,
+
+    /// Matches inside code blocks, on top of their content match
+    #[serde(default = "default_code_boost")]
+    pub code: f32,
+}
+
+/// Boosts a ranking profile changes; unset fields keep `[search.boosts]`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RankingProfile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<f32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<f32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<f32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotConfig {
+    /// Take a daily snapshot while the server is running
+    #[serde(default = "default_snapshot_enabled")]
+    pub enabled: bool,
+
+    /// Number of most recent daily snapshots to keep
+    #[serde(default = "default_keep_daily")]
+    pub keep_daily: usize,
+
+    /// Number of older weekly snapshots to keep
+    #[serde(default = "default_keep_weekly")]
+    pub keep_weekly: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryConfig {
+    /// Commit the vault to a git history on every change made through Notidium
+    #[serde(default = "default_history_enabled")]
+    pub enabled: bool,
+}
+
+/// Normalization of note markdown on create and update, so edits by people
+/// and agents don't fight over formatting. Code blocks are never touched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormatConfig {
+    /// Format notes saved through Notidium; edits made directly on disk
+    /// are left alone
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// One blank line around headings, one space after the `#`s and no
+    /// closing `#`s
+    #[serde(default = "default_format_rule")]
+    pub headings: bool,
+
+    /// Use `list_marker` for bullets and `1.` style ordered list markers
+    #[serde(default = "default_format_rule")]
+    pub lists: bool,
+
+    /// Bullet for unordered lists: `-`, `*` or `+`
+    #[serde(default = "default_list_marker")]
+    pub list_marker: char,
+
+    /// Lowercase code fence languages and spell aliases like `rs` or `py`
+    /// out
+    #[serde(default = "default_format_rule")]
+    pub fences: bool,
+
+    /// Pad table cells so columns line up
+    #[serde(default = "default_format_rule")]
+    pub tables: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthConfig {
+    /// Argon2 hash of the web UI password; password login is off when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_hash: Option<String>,
+
+    /// SHA-256 hash of the vault-wide API token; `/api/*` and `/mcp` are open
+    /// to the network when unset (unless users or a password are configured)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_token_hash: Option<String>,
+
+    /// How long a login session lasts, in hours
+    #[serde(default = "default_session_hours")]
+    pub session_hours: u64,
+
+    /// Only send the session cookie over HTTPS
+    #[serde(default = "default_secure_cookie")]
+    pub secure_cookie: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpConfig {
+    /// How long OAuth access tokens issued to clients last, in minutes
+    #[serde(default = "default_access_token_minutes")]
+    pub access_token_minutes: u64,
+
+    /// Clients allowed to use the `/mcp` endpoint; when there are none, it's
+    /// open (or protected by user API keys like the rest of the API)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clients: Vec<McpClientConfig>,
+
+    /// Tools offered on every transport; all of them when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub enabled_tools: Vec<String>,
+
+    /// Tools withheld on every transport, e.g. `delete_note`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disabled_tools: Vec<String>,
+
+    /// Narrows the tools further for `notidium mcp`
+    #[serde(default, skip_serializing_if = "McpToolsConfig::is_empty")]
+    pub stdio: McpToolsConfig,
+
+    /// Narrows the tools further for `/mcp` of `notidium serve` and
+    /// `notidium mcp-http`
+    #[serde(default, skip_serializing_if = "McpToolsConfig::is_empty")]
+    pub http: McpToolsConfig,
+}
+
+/// Tools offered on one MCP transport
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct McpToolsConfig {
+    /// Tools offered; all of them when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub enabled_tools: Vec<String>,
+
+    /// Tools withheld
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disabled_tools: Vec<String>,
+}
+
+impl McpToolsConfig {
+    pub fn is_empty(&self) -> bool {
+        self.enabled_tools.is_empty() && self.disabled_tools.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpClientConfig {
+    /// Client ID, used for attribution and the client-credentials grant
+    pub name: String,
+
+    /// SHA-256 hash of the client's secret
+    pub secret_hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    /// OTLP/HTTP collector to export traces to (e.g. `http://localhost:4318`);
+    /// tracing is off when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub otlp_endpoint: Option<String>,
+
+    /// Service name reported with exported traces
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+
+    /// Fraction of new traces to sample; traces started by callers follow
+    /// the caller's decision
+    #[serde(default = "default_sample_ratio")]
+    pub sample_ratio: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// Limit how fast each client IP can call the API
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Sustained requests per second allowed per client
+    #[serde(default = "default_requests_per_second")]
+    pub requests_per_second: f64,
+
+    /// Requests a client can make at once before the sustained rate applies
+    #[serde(default = "default_burst")]
+    pub burst: u32,
+
+    /// Sustained rate for requests that run the embedder or fetch pages
+    /// (semantic and hybrid search, including searches defaulting to those
+    /// modes, quick capture, URL capture)
+    #[serde(default = "default_expensive_requests_per_second")]
+    pub expensive_requests_per_second: f64,
+
+    /// Burst size for those expensive requests
+    #[serde(default = "default_expensive_burst")]
+    pub expensive_burst: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClipConfig {
+    /// Let URL capture fetch loopback, private and link-local addresses;
+    /// off so API clients can't reach services behind the server
+    #[serde(default)]
+    pub allow_private_hosts: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    /// Attempts at delivering each event, including the first
+    #[serde(default = "default_webhook_attempts")]
+    pub max_attempts: u32,
+
+    /// Wait before the first retry, in milliseconds; it doubles after each
+    /// failed attempt
+    #[serde(default = "default_webhook_retry_delay_ms")]
+    pub retry_delay_ms: u64,
+
+    /// How long to wait for an endpoint to answer, in seconds
+    #[serde(default = "default_webhook_timeout_secs")]
+    pub timeout_secs: u64,
+
+    /// Endpoints to POST events to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub endpoints: Vec<WebhookEndpoint>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookEndpoint {
+    pub url: String,
+
+    /// Events to send (`note_created`, `note_updated`, `note_deleted`,
+    /// `note_captured`); all of them when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<String>,
 }
 
 impl Default for Config {
@@ -69,10 +714,20 @@ impl Default for Config {
             notes_dir: default_notes_dir(),
             attachments_dir: default_attachments_dir(),
             templates_dir: default_templates_dir(),
+            bind_address: default_bind_address(),
             http_port: default_http_port(),
             mcp_port: default_mcp_port(),
             embedding: EmbeddingConfig::default(),
             search: SearchConfig::default(),
+            snapshot: SnapshotConfig::default(),
+            history: HistoryConfig::default(),
+            format: FormatConfig::default(),
+            auth: AuthConfig::default(),
+            mcp: McpConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            webhooks: WebhookConfig::default(),
+            clip: ClipConfig::default(),
         }
     }
 }
@@ -80,8 +735,36 @@ impl Default for Config {
 impl Default for EmbeddingConfig {
     fn default() -> Self {
         Self {
+            enabled: default_embedding_enabled(),
             prose_model: default_prose_model(),
+            code_model: default_code_model(),
+            code_embeddings: default_code_embeddings(),
             batch_size: default_batch_size(),
+            chunk_overlap_words: 0,
+            min_chunk_words: default_min_chunk_words(),
+            heading_context: default_heading_context(),
+            code_symbols: default_code_symbols(),
+            max_chunk_tokens: default_max_chunk_tokens(),
+            concurrent_batches: default_concurrent_batches(),
+            quantization: Quantization::default(),
+            memory_budget_mb: 0,
+            vector_store: VectorBackend::default(),
+            provider: EmbeddingProvider::default(),
+            execution_provider: ExecutionProvider::default(),
+            threads: None,
+            remote: RemoteEmbeddingConfig::default(),
+        }
+    }
+}
+
+impl Default for RemoteEmbeddingConfig {
+    fn default() -> Self {
+        Self {
+            url: default_remote_embedding_url(),
+            api_key: None,
+            api_key_env: default_remote_embedding_key_env(),
+            dimensions: None,
+            timeout_secs: default_remote_embedding_timeout(),
         }
     }
 }
@@ -91,10 +774,200 @@ impl Default for SearchConfig {
         Self {
             default_limit: default_search_limit(),
             max_limit: default_max_limit(),
+            default_mode: None,
+            client_modes: BTreeMap::new(),
+            semantic_weight: default_semantic_weight(),
+            semantic_index: SemanticIndex::default(),
+            classifier: ClassifierConfig::default(),
+            multi_query: MultiQueryConfig::default(),
+            recency: RecencyConfig::default(),
+            feedback: FeedbackConfig::default(),
+            snippet_len: default_snippet_len(),
+            max_snippet_len: default_max_snippet_len(),
+            grep_max_file_kb: default_grep_max_file_kb(),
+            boosts: FieldBoosts::default(),
+            profiles: BTreeMap::new(),
+            analyzer: AnalyzerConfig::default(),
+            index: IndexConfig::default(),
+        }
+    }
+}
+
+impl Default for ClassifierConfig {
+    fn default() -> Self {
+        Self {
+            signals: Vec::new(),
+            builtin_weight: default_builtin_weight(),
+            code_threshold: default_code_threshold(),
+            hybrid_threshold: default_hybrid_threshold(),
         }
     }
 }
 
+impl Default for MultiQueryConfig {
+    fn default() -> Self {
+        Self {
+            max_queries: default_max_queries(),
+            command: Vec::new(),
+        }
+    }
+}
+
+impl Default for RecencyConfig {
+    fn default() -> Self {
+        Self {
+            half_life_days: default_half_life_days(),
+            weight: default_recency_weight(),
+        }
+    }
+}
+
+impl Default for FeedbackConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_feedback_enabled(),
+            min_marks: default_feedback_min_marks(),
+            demote: default_feedback_demote(),
+            promote: default_feedback_promote(),
+        }
+    }
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            writer_heap_mb: default_writer_heap_mb(),
+            reader_reload: ReaderReload::default(),
+            warm_up: default_warm_up(),
+        }
+    }
+}
+
+impl Default for FieldBoosts {
+    fn default() -> Self {
+        Self {
+            title: default_title_boost(),
+            content: default_content_boost(),
+            tags: default_tags_boost(),
+            code: default_code_boost(),
+        }
+    }
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_snapshot_enabled(),
+            keep_daily: default_keep_daily(),
+            keep_weekly: default_keep_weekly(),
+        }
+    }
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_history_enabled(),
+        }
+    }
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            headings: default_format_rule(),
+            lists: default_format_rule(),
+            list_marker: default_list_marker(),
+            fences: default_format_rule(),
+            tables: default_format_rule(),
+        }
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            password_hash: None,
+            api_token_hash: None,
+            session_hours: default_session_hours(),
+            secure_cookie: default_secure_cookie(),
+        }
+    }
+}
+
+impl Default for McpConfig {
+    fn default() -> Self {
+        Self {
+            access_token_minutes: default_access_token_minutes(),
+            clients: Vec::new(),
+            enabled_tools: Vec::new(),
+            disabled_tools: Vec::new(),
+            stdio: McpToolsConfig::default(),
+            http: McpToolsConfig::default(),
+        }
+    }
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: default_service_name(),
+            sample_ratio: default_sample_ratio(),
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            requests_per_second: default_requests_per_second(),
+            burst: default_burst(),
+            expensive_requests_per_second: default_expensive_requests_per_second(),
+            expensive_burst: default_expensive_burst(),
+        }
+    }
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_webhook_attempts(),
+            retry_delay_ms: default_webhook_retry_delay_ms(),
+            timeout_secs: default_webhook_timeout_secs(),
+            endpoints: Vec::new(),
+        }
+    }
+}
+
+impl SearchConfig {
+    /// Snippet options for a search asking for `len` characters, capped at
+    /// [`max_snippet_len`](Self::max_snippet_len)
+    pub fn snippet_options(&self, len: Option<usize>, full_chunk: bool) -> SnippetOptions {
+        SnippetOptions {
+            len: len.unwrap_or(self.snippet_len).min(self.max_snippet_len),
+            full_chunk,
+        }
+    }
+
+    /// Field boosts of a ranking profile, or the base boosts without one;
+    /// `None` for an unknown profile
+    pub fn field_boosts(&self, profile: Option<&str>) -> Option<FieldBoosts> {
+        let Some(name) = profile else {
+            return Some(self.boosts);
+        };
+        let profile = self.profiles.get(name)?;
+        Some(FieldBoosts {
+            title: profile.title.unwrap_or(self.boosts.title),
+            content: profile.content.unwrap_or(self.boosts.content),
+            tags: profile.tags.unwrap_or(self.boosts.tags),
+            code: profile.code.unwrap_or(self.boosts.code),
+        })
+    }
+}
+
 impl Config {
     /// Load config from file or create default
     pub fn load() -> Result<Self> {
@@ -170,9 +1043,13 @@ impl Config {
         self.data_dir().join("index.db")
     }
 
-    /// Path to vector store directory
+    /// Path to the vector store: a directory for LanceDB, or the SQLite
+    /// database with `embedding.vector_store = "sqlite"`
     pub fn vectors_path(&self) -> PathBuf {
-        self.data_dir().join("vectors")
+        match self.embedding.vector_store {
+            VectorBackend::Lance => self.data_dir().join("vectors"),
+            VectorBackend::Sqlite => self.db_path(),
+        }
     }
 
     /// Path to tantivy index directory
@@ -180,6 +1057,41 @@ impl Config {
         self.data_dir().join("tantivy")
     }
 
+    /// Path to the note checksum snapshot used for sync conflict detection
+    pub fn checksums_path(&self) -> PathBuf {
+        self.data_dir().join("checksums.json")
+    }
+
+    /// Path to the user accounts file for shared vaults
+    pub fn users_path(&self) -> PathBuf {
+        self.data_dir().join("users.json")
+    }
+
+    /// Path to trash directory for deleted notes
+    pub fn trash_path(&self) -> PathBuf {
+        self.data_dir().join("trash")
+    }
+
+    /// Path to the journal of multi-step operations in progress
+    pub fn journal_path(&self) -> PathBuf {
+        self.data_dir().join("journal")
+    }
+
+    /// Path to snapshots directory
+    pub fn snapshots_path(&self) -> PathBuf {
+        self.data_dir().join("snapshots")
+    }
+
+    /// Path to the git repository holding version history
+    pub fn history_path(&self) -> PathBuf {
+        self.data_dir().join("history")
+    }
+
+    /// Directory chunks are spilled to past `embedding.memory_budget_mb`
+    pub fn spill_path(&self) -> PathBuf {
+        self.data_dir().join("spill")
+    }
+
     /// Path to cache directory
     pub fn cache_path(&self) -> PathBuf {
         self.data_dir().join("cache")
@@ -197,7 +1109,9 @@ impl Config {
         std::fs::create_dir_all(self.attachments_path())?;
         std::fs::create_dir_all(self.templates_path())?;
         std::fs::create_dir_all(self.data_dir())?;
-        std::fs::create_dir_all(self.vectors_path())?;
+        if self.embedding.vector_store == VectorBackend::Lance {
+            std::fs::create_dir_all(self.vectors_path())?;
+        }
         std::fs::create_dir_all(self.tantivy_path())?;
         std::fs::create_dir_all(self.cache_path())?;
         std::fs::create_dir_all(self.logs_path())?;
@@ -229,6 +1143,10 @@ fn default_templates_dir() -> String {
     "templates".to_string()
 }
 
+fn default_bind_address() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::LOCALHOST)
+}
+
 fn default_http_port() -> u16 {
     3939
 }
@@ -241,10 +1159,55 @@ fn default_prose_model() -> String {
     "BAAI/bge-small-en-v1.5".to_string()
 }
 
+fn default_code_model() -> String {
+    "jinaai/jina-embeddings-v2-base-code".to_string()
+}
+
+fn default_code_embeddings() -> bool {
+    true
+}
+
+fn default_embedding_enabled() -> bool {
+    true
+}
+
+fn default_remote_embedding_url() -> String {
+    "https://api.openai.com/v1".to_string()
+}
+
+fn default_remote_embedding_key_env() -> String {
+    "OPENAI_API_KEY".to_string()
+}
+
+fn default_remote_embedding_timeout() -> u64 {
+    60
+}
+
 fn default_batch_size() -> usize {
     32
 }
 
+fn default_concurrent_batches() -> usize {
+    4
+}
+
+fn default_heading_context() -> bool {
+    true
+}
+
+fn default_min_chunk_words() -> usize {
+    50
+}
+
+fn default_code_symbols() -> bool {
+    true
+}
+
+/// BGE-small reads 512 tokens, two of them its [CLS] and [SEP] markers
+fn default_max_chunk_tokens() -> usize {
+    510
+}
+
 fn default_search_limit() -> usize {
     10
 }
@@ -252,3 +1215,155 @@ fn default_search_limit() -> usize {
 fn default_max_limit() -> usize {
     100
 }
+
+fn default_semantic_weight() -> f32 {
+    0.5
+}
+
+fn default_builtin_weight() -> f32 {
+    1.0
+}
+
+fn default_code_threshold() -> f32 {
+    2.0
+}
+
+fn default_hybrid_threshold() -> f32 {
+    1.0
+}
+
+fn default_max_queries() -> usize {
+    4
+}
+
+fn default_half_life_days() -> f32 {
+    30.0
+}
+
+fn default_recency_weight() -> f32 {
+    0.5
+}
+
+fn default_feedback_enabled() -> bool {
+    true
+}
+
+fn default_feedback_min_marks() -> u32 {
+    2
+}
+
+fn default_feedback_demote() -> f32 {
+    0.5
+}
+
+fn default_feedback_promote() -> f32 {
+    0.1
+}
+
+fn default_snippet_len() -> usize {
+    crate::search::DEFAULT_SNIPPET_LEN
+}
+
+fn default_max_snippet_len() -> usize {
+    2000
+}
+
+fn default_grep_max_file_kb() -> u64 {
+    1024
+}
+
+fn default_writer_heap_mb() -> usize {
+    50
+}
+
+fn default_warm_up() -> bool {
+    true
+}
+
+fn default_title_boost() -> f32 {
+    2.0
+}
+
+fn default_content_boost() -> f32 {
+    1.0
+}
+
+fn default_tags_boost() -> f32 {
+    1.5
+}
+
+fn default_code_boost() -> f32 {
+    1.0
+}
+
+fn default_snapshot_enabled() -> bool {
+    true
+}
+
+fn default_keep_daily() -> usize {
+    7
+}
+
+fn default_keep_weekly() -> usize {
+    4
+}
+
+fn default_history_enabled() -> bool {
+    false
+}
+
+fn default_format_rule() -> bool {
+    true
+}
+
+fn default_list_marker() -> char {
+    '-'
+}
+
+fn default_session_hours() -> u64 {
+    24 * 7
+}
+
+fn default_secure_cookie() -> bool {
+    false
+}
+
+fn default_access_token_minutes() -> u64 {
+    60
+}
+
+fn default_service_name() -> String {
+    "notidium".to_string()
+}
+
+fn default_sample_ratio() -> f64 {
+    1.0
+}
+
+fn default_requests_per_second() -> f64 {
+    20.0
+}
+
+fn default_burst() -> u32 {
+    100
+}
+
+fn default_expensive_requests_per_second() -> f64 {
+    1.0
+}
+
+fn default_expensive_burst() -> u32 {
+    10
+}
+
+fn default_webhook_attempts() -> u32 {
+    5
+}
+
+fn default_webhook_retry_delay_ms() -> u64 {
+    1000
+}
+
+fn default_webhook_timeout_secs() -> u64 {
+    10
+}
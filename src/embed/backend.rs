@@ -0,0 +1,268 @@
+//! Embedding backends
+//!
+//! Embeddings come from fastembed's ONNX models running in-process, or from
+//! an OpenAI-compatible `/embeddings` endpoint with `embedding.provider =
+//! "remote"`, which needs no model download.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use fastembed::{ExecutionProviderDispatch, InitOptions, TextEmbedding};
+use ort::execution_providers::{
+    CUDAExecutionProvider, CoreMLExecutionProvider, DirectMLExecutionProvider,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::OnceCell;
+
+use crate::config::{EmbeddingConfig, ExecutionProvider, RemoteEmbeddingConfig};
+use crate::error::{Error, Result};
+
+use super::chunker::TokenCounter;
+use super::embedder::{resolve_model, ModelChoice};
+
+/// Turns texts into embeddings with one model
+#[async_trait]
+pub trait EmbedderBackend: Send + Sync {
+    /// Embed `texts`, one embedding each, in order
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>>;
+
+    /// Size of the embeddings; 0 while a remote model hasn't answered yet
+    fn dimension(&self) -> usize;
+
+    /// The model's tokenizer, when it is known; remote models have none
+    async fn tokenizer(&self) -> Result<Option<Arc<dyn TokenCounter>>> {
+        Ok(None)
+    }
+}
+
+/// A fastembed model running in-process
+///
+/// The model is loaded, and downloaded if need be, when it first embeds
+/// something, so a model that is never used costs neither time nor memory.
+/// Loading and embedding run on blocking threads, and several batches can be
+/// embedded at once by the same model.
+pub struct LocalBackend {
+    name: String,
+    choice: ModelChoice,
+    execution_provider: ExecutionProvider,
+    /// Threads for `embedding.threads`, rayon's global pool otherwise
+    pool: Option<Arc<rayon::ThreadPool>>,
+    model: OnceCell<Arc<TextEmbedding>>,
+    tokenizer: OnceCell<Arc<dyn TokenCounter>>,
+}
+
+impl LocalBackend {
+    /// Backend for the fastembed model called `name`, run as `config` says
+    pub fn new(name: &str, config: &EmbeddingConfig) -> Result<Self> {
+        let pool = config
+            .threads
+            .map(|threads| {
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .thread_name(|i| format!("embed-{}", i))
+                    .build()
+                    .map(Arc::new)
+                    .map_err(|e| Error::Embedding(e.to_string()))
+            })
+            .transpose()?;
+        Ok(Self {
+            name: name.to_string(),
+            choice: resolve_model(name)?,
+            execution_provider: config.execution_provider,
+            pool,
+            model: OnceCell::new(),
+            tokenizer: OnceCell::new(),
+        })
+    }
+
+    /// The model, loaded by the first caller while others wait for it
+    async fn model(&self) -> Result<Arc<TextEmbedding>> {
+        self.model
+            .get_or_try_init(|| async {
+                tracing::info!("Loading embedding model {}", self.name);
+                let name = self.name.clone();
+                let options = InitOptions::new(self.choice.model.clone())
+                    .with_execution_providers(execution_providers(self.execution_provider))
+                    .with_show_download_progress(true);
+                tokio::task::spawn_blocking(move || {
+                    TextEmbedding::try_new(options)
+                        .map(Arc::new)
+                        .map_err(|e| Error::Embedding(format!("Failed to load {}: {}", name, e)))
+                })
+                .await
+                .map_err(|e| Error::Embedding(e.to_string()))?
+            })
+            .await
+            .cloned()
+    }
+}
+
+/// ONNX Runtime providers for `provider`; CPU needs none, and a GPU that
+/// can't be used falls back to it with a warning
+fn execution_providers(provider: ExecutionProvider) -> Vec<ExecutionProviderDispatch> {
+    let (dispatch, feature, enabled) = match provider {
+        ExecutionProvider::Cpu => return Vec::new(),
+        ExecutionProvider::Cuda => (CUDAExecutionProvider::default().build(), "cuda", cfg!(feature = "cuda")),
+        ExecutionProvider::CoreMl => (CoreMLExecutionProvider::default().build(), "coreml", cfg!(feature = "coreml")),
+        ExecutionProvider::DirectMl => {
+            (DirectMLExecutionProvider::default().build(), "directml", cfg!(feature = "directml"))
+        }
+    };
+    if !enabled {
+        tracing::warn!("notidium was built without the `{}` feature, embedding on the CPU", feature);
+        return Vec::new();
+    }
+    vec![dispatch]
+}
+
+#[async_trait]
+impl EmbedderBackend for LocalBackend {
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let model = self.model().await?;
+        let pool = self.pool.clone();
+        let embeddings = tokio::task::spawn_blocking(move || match pool {
+            Some(pool) => pool.install(|| model.embed(texts, None)),
+            None => model.embed(texts, None),
+        })
+        .await
+        .map_err(|e| Error::Embedding(e.to_string()))?;
+        embeddings.map_err(|e| Error::Embedding(e.to_string()))
+    }
+
+    fn dimension(&self) -> usize {
+        self.choice.dimension
+    }
+
+    async fn tokenizer(&self) -> Result<Option<Arc<dyn TokenCounter>>> {
+        let tokenizer = self
+            .tokenizer
+            .get_or_try_init(|| async {
+                // fastembed truncates and pads what it feeds the model, which
+                // would hide how long a text really is
+                let mut tokenizer = self.model().await?.tokenizer.clone();
+                tokenizer
+                    .with_truncation(None)
+                    .map_err(|e| Error::Embedding(e.to_string()))?
+                    .with_padding(None);
+                Ok::<_, Error>(Arc::new(tokenizer) as Arc<dyn TokenCounter>)
+            })
+            .await?;
+        Ok(Some(tokenizer.clone()))
+    }
+}
+
+/// A model behind an OpenAI-compatible embeddings API
+pub struct RemoteBackend {
+    client: reqwest::Client,
+    endpoint: String,
+    api_key: Option<String>,
+    model: String,
+    dimensions: Option<usize>,
+    /// Size of the last embeddings received
+    dimension: AtomicUsize,
+}
+
+#[derive(Serialize)]
+struct EmbeddingsRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingsResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+    #[serde(default)]
+    index: usize,
+}
+
+impl RemoteBackend {
+    /// Client for `model` at the API `config` points to
+    pub fn new(config: &RemoteEmbeddingConfig, model: &str) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("notidium/", env!("CARGO_PKG_VERSION")))
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .map_err(|e| Error::Embedding(e.to_string()))?;
+        let api_key = config
+            .api_key
+            .clone()
+            .or_else(|| std::env::var(&config.api_key_env).ok())
+            .filter(|key| !key.is_empty());
+        Ok(Self {
+            client,
+            endpoint: format!("{}/embeddings", config.url.trim_end_matches('/')),
+            api_key,
+            model: model.to_string(),
+            dimensions: config.dimensions,
+            dimension: AtomicUsize::new(config.dimensions.unwrap_or(0)),
+        })
+    }
+}
+
+#[async_trait]
+impl EmbedderBackend for RemoteBackend {
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let body = serde_json::to_vec(&EmbeddingsRequest {
+            model: &self.model,
+            input: &texts,
+            dimensions: self.dimensions,
+        })?;
+        let mut request = self
+            .client
+            .post(&self.endpoint)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::Embedding(format!("{}: {}", self.endpoint, e)))?;
+        let status = response.status();
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| Error::Embedding(format!("{}: {}", self.endpoint, e)))?;
+        if !status.is_success() {
+            let message = String::from_utf8_lossy(&bytes);
+            return Err(Error::Embedding(format!(
+                "{} returned {}: {}",
+                self.endpoint,
+                status,
+                message.chars().take(200).collect::<String>()
+            )));
+        }
+
+        let mut data = serde_json::from_slice::<EmbeddingsResponse>(&bytes)
+            .map_err(|e| Error::Embedding(format!("Unexpected response from {}: {}", self.endpoint, e)))?
+            .data;
+        if data.len() != texts.len() {
+            return Err(Error::Embedding(format!(
+                "{} returned {} embeddings for {} texts",
+                self.endpoint,
+                data.len(),
+                texts.len()
+            )));
+        }
+        data.sort_by_key(|d| d.index);
+        if let Some(first) = data.first() {
+            self.dimension.store(first.embedding.len(), Ordering::Relaxed);
+        }
+        Ok(data.into_iter().map(|d| d.embedding).collect())
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension.load(Ordering::Relaxed)
+    }
+}
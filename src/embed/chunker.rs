@@ -1,25 +1,86 @@
 //! Content chunking for embeddings
 
+use std::sync::Arc;
+
 use pulldown_cmark::{Event, Parser, Tag, TagEnd};
 use uuid::Uuid;
 
+use crate::config::EmbeddingConfig;
 use crate::types::{Chunk, ChunkType, Note};
 
+use super::code::split_code;
+
 /// Chunker for splitting notes into embeddable chunks
 pub struct Chunker {
     /// Target words per chunk
     target_words: usize,
+    /// Words of a prose chunk cut at `target_words` repeated at the start
+    /// of the next one
+    overlap_words: usize,
+    /// Words below which the end of a cut passage joins the chunk before
+    min_words: usize,
+    /// Give chunks the note title and headings they sit under as context
+    heading_context: bool,
+    /// Split code blocks at the functions and classes they define
+    code_symbols: bool,
 }
 
 impl Default for Chunker {
     fn default() -> Self {
-        Self { target_words: 250 }
+        Self::new(250)
     }
 }
 
 impl Chunker {
     pub fn new(target_words: usize) -> Self {
-        Self { target_words }
+        Self {
+            target_words,
+            overlap_words: 0,
+            min_words: target_words / 5,
+            heading_context: true,
+            code_symbols: true,
+        }
+    }
+
+    /// Chunker with the overlap and heading context `config` asks for
+    pub fn from_config(config: &EmbeddingConfig) -> Self {
+        Self::default()
+            .with_overlap(config.chunk_overlap_words)
+            .with_min_words(config.min_chunk_words)
+            .with_heading_context(config.heading_context)
+            .with_code_symbols(config.code_symbols)
+    }
+
+    /// Start each prose chunk that continues a longer passage with the last
+    /// `overlap_words` words of the chunk before it, so a thought cut at the
+    /// word limit is still whole in one of them
+    pub fn with_overlap(mut self, overlap_words: usize) -> Self {
+        self.overlap_words = overlap_words;
+        self
+    }
+
+    /// Merge the end of a passage cut at the word limit into the chunk
+    /// before it when it has fewer than `min_words` words of its own, rather
+    /// than embedding a scrap
+    pub fn with_min_words(mut self, min_words: usize) -> Self {
+        self.min_words = min_words;
+        self
+    }
+
+    /// Whether chunks get the note title and the headings they sit under
+    /// as [`Chunk::context`], so a paragraph under `## Deployment` / `###
+    /// Kubernetes` is embedded as being about Kubernetes deployment
+    pub fn with_heading_context(mut self, heading_context: bool) -> Self {
+        self.heading_context = heading_context;
+        self
+    }
+
+    /// Whether code blocks are split at their top-level definitions, one
+    /// chunk per function, class or other symbol, named in the chunk's
+    /// [`ChunkType::CodeBlock`] title (with the `code-symbols` feature)
+    pub fn with_code_symbols(mut self, code_symbols: bool) -> Self {
+        self.code_symbols = code_symbols;
+        self
     }
 
     /// Chunk a note into embeddable pieces
@@ -33,53 +94,78 @@ impl Chunker {
         let mut code_language = String::new();
         let mut line_number = 1u32;
         let mut chunk_start_line = 1u32;
+        // Words at the start of `current_text` repeated from the chunk before
+        let mut overlap = 0;
+        // Bytes of those words when `current_text` continues a passage cut
+        // at the word limit
+        let mut tail: Option<usize> = None;
+        // Levels and text of the headings the current chunk sits under
+        let mut headings: Vec<(u8, String)> = Vec::new();
+        // The lists the parser is in, innermost last, with the number of the
+        // next item of ordered ones
+        let mut lists: Vec<Option<u64>> = Vec::new();
 
         for event in parser {
             match event {
                 Event::Start(Tag::Heading { level, .. }) => {
                     // Flush current chunk
-                    if !current_text.trim().is_empty() {
-                        chunks.push(self.create_chunk(
-                            note.id,
+                    if current_text.split_whitespace().count() > overlap {
+                        self.push_chunk(&mut chunks, tail, self.create_chunk(
+                            note,
+                            &headings,
                             &current_text,
                             current_type.clone(),
                             chunk_start_line,
                             line_number,
                         ));
-                        current_text.clear();
                     }
+                    current_text.clear();
+                    overlap = 0;
+                    tail = None;
                     current_type = ChunkType::Heading {
                         level: level as u8,
                     };
                     chunk_start_line = line_number;
                 }
-                Event::End(TagEnd::Heading(_)) => {
+                Event::End(TagEnd::Heading(level)) => {
+                    // A heading sits under the headings above its level
+                    let level = level as u8;
+                    while headings.last().is_some_and(|(l, _)| *l >= level) {
+                        headings.pop();
+                    }
                     // Heading is its own chunk
-                    if !current_text.trim().is_empty() {
-                        chunks.push(self.create_chunk(
-                            note.id,
+                    if current_text.split_whitespace().count() > overlap {
+                        self.push_chunk(&mut chunks, tail, self.create_chunk(
+                            note,
+                            &headings,
                             &current_text,
                             current_type.clone(),
                             chunk_start_line,
                             line_number,
                         ));
-                        current_text.clear();
                     }
+                    headings.push((level, current_text.trim().to_string()));
+                    current_text.clear();
+                    overlap = 0;
+                    tail = None;
                     current_type = ChunkType::Prose;
                     chunk_start_line = line_number;
                 }
                 Event::Start(Tag::CodeBlock(kind)) => {
                     // Flush current chunk
-                    if !current_text.trim().is_empty() {
-                        chunks.push(self.create_chunk(
-                            note.id,
+                    if current_text.split_whitespace().count() > overlap {
+                        self.push_chunk(&mut chunks, tail, self.create_chunk(
+                            note,
+                            &headings,
                             &current_text,
                             current_type.clone(),
                             chunk_start_line,
                             line_number,
                         ));
-                        current_text.clear();
                     }
+                    current_text.clear();
+                    overlap = 0;
+                    tail = None;
 
                     in_code_block = true;
                     code_language = match kind {
@@ -89,71 +175,204 @@ impl Chunker {
                     chunk_start_line = line_number;
                 }
                 Event::End(TagEnd::CodeBlock) => {
-                    // Code block is its own chunk
-                    if !current_text.trim().is_empty() {
-                        chunks.push(self.create_chunk(
-                            note.id,
-                            &current_text,
-                            ChunkType::CodeBlock {
-                                language: code_language.clone(),
-                                title: None,
-                            },
-                            chunk_start_line,
-                            line_number,
-                        ));
-                        current_text.clear();
+                    // Code block is its own chunk, or a chunk per symbol it defines
+                    if current_text.split_whitespace().count() > overlap {
+                        let symbols = self
+                            .code_symbols
+                            .then(|| split_code(&code_language, &current_text))
+                            .flatten();
+                        match symbols {
+                            Some(symbols) => {
+                                for symbol in symbols {
+                                    let mut chunk = self.create_chunk(
+                                        note,
+                                        &headings,
+                                        &symbol.text,
+                                        ChunkType::CodeBlock {
+                                            language: code_language.clone(),
+                                            title: symbol.name.clone(),
+                                        },
+                                        chunk_start_line + symbol.start_row,
+                                        chunk_start_line + symbol.end_row,
+                                    );
+                                    if let (Some(context), Some(name)) = (&mut chunk.context, &symbol.name) {
+                                        context.push_str(" > ");
+                                        context.push_str(name);
+                                    }
+                                    chunks.push(chunk);
+                                }
+                            }
+                            None => chunks.push(self.create_chunk(
+                                note,
+                                &headings,
+                                &current_text,
+                                ChunkType::CodeBlock {
+                                    language: code_language.clone(),
+                                    title: None,
+                                },
+                                chunk_start_line,
+                                line_number,
+                            )),
+                        }
                     }
+                    current_text.clear();
+                    overlap = 0;
+                    tail = None;
 
                     in_code_block = false;
                     code_language.clear();
-                    current_type = ChunkType::Prose;
+                    current_type = if lists.is_empty() {
+                        ChunkType::Prose
+                    } else {
+                        ChunkType::ListItem
+                    };
                     chunk_start_line = line_number;
                 }
                 Event::Start(Tag::BlockQuote(_)) => {
-                    if !current_text.trim().is_empty() {
-                        chunks.push(self.create_chunk(
-                            note.id,
+                    if current_text.split_whitespace().count() > overlap {
+                        self.push_chunk(&mut chunks, tail, self.create_chunk(
+                            note,
+                            &headings,
                             &current_text,
                             current_type.clone(),
                             chunk_start_line,
                             line_number,
                         ));
-                        current_text.clear();
                     }
+                    current_text.clear();
+                    overlap = 0;
+                    tail = None;
                     current_type = ChunkType::Blockquote;
                     chunk_start_line = line_number;
                 }
                 Event::End(TagEnd::BlockQuote(_)) => {
-                    if !current_text.trim().is_empty() {
-                        chunks.push(self.create_chunk(
-                            note.id,
+                    if current_text.split_whitespace().count() > overlap {
+                        self.push_chunk(&mut chunks, tail, self.create_chunk(
+                            note,
+                            &headings,
                             &current_text,
                             current_type.clone(),
                             chunk_start_line,
                             line_number,
                         ));
-                        current_text.clear();
                     }
-                    current_type = ChunkType::Prose;
+                    current_text.clear();
+                    overlap = 0;
+                    tail = None;
+                    current_type = if lists.is_empty() {
+                        ChunkType::Prose
+                    } else {
+                        ChunkType::ListItem
+                    };
                     chunk_start_line = line_number;
                 }
-                Event::Text(text) | Event::Code(text) => {
-                    current_text.push_str(&text);
-                    line_number += text.matches('\n').count() as u32;
-
-                    // Check if we should split (for prose only)
-                    if !in_code_block && !matches!(current_type, ChunkType::Heading { .. }) {
-                        let word_count = current_text.split_whitespace().count();
-                        if word_count >= self.target_words {
+                Event::Start(Tag::List(first_number)) => {
+                    // A list is its own chunk, unless it's quoted
+                    if lists.is_empty() && current_type != ChunkType::Blockquote {
+                        if current_text.split_whitespace().count() > overlap {
+                            self.push_chunk(&mut chunks, tail, self.create_chunk(
+                                note,
+                                &headings,
+                                &current_text,
+                                current_type.clone(),
+                                chunk_start_line,
+                                line_number,
+                            ));
+                        }
+                        current_text.clear();
+                        overlap = 0;
+                        tail = None;
+                        current_type = ChunkType::ListItem;
+                        chunk_start_line = line_number;
+                    }
+                    lists.push(first_number);
+                }
+                Event::End(TagEnd::List(_)) => {
+                    lists.pop();
+                    if lists.is_empty() && current_type == ChunkType::ListItem {
+                        if current_text.split_whitespace().count() > 0 {
                             chunks.push(self.create_chunk(
-                                note.id,
+                                note,
+                                &headings,
                                 &current_text,
                                 current_type.clone(),
                                 chunk_start_line,
                                 line_number,
                             ));
-                            current_text.clear();
-                            chunk_start_line = line_number;
+                        }
+                        current_text.clear();
+                        current_type = ChunkType::Prose;
+                        chunk_start_line = line_number;
+                    }
+                }
+                Event::Start(Tag::Item) => {
+                    // Every item on its own line, marked and indented as in
+                    // the note, so nesting and numbering survive
+                    if !current_text.is_empty() && !current_text.ends_with('\n') {
+                        current_text.push('\n');
+                    }
+                    current_text.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                    match lists.last_mut() {
+                        Some(Some(number)) => {
+                            current_text.push_str(&format!("{}. ", number));
+                            *number += 1;
+                        }
+                        _ => current_text.push_str("- "),
+                    }
+                }
+                Event::End(TagEnd::Item) => {
+                    // Long lists are cut between top-level items, keeping
+                    // each bullet whole with the bullets nested under it
+                    if lists.len() == 1
+                        && current_type == ChunkType::ListItem
+                        && current_text.split_whitespace().count() >= self.target_words
+                    {
+                        chunks.push(self.create_chunk(
+                            note,
+                            &headings,
+                            &current_text,
+                            current_type.clone(),
+                            chunk_start_line,
+                            line_number,
+                        ));
+                        current_text.clear();
+                        chunk_start_line = line_number + 1;
+                    }
+                    line_number += 1;
+                }
+                Event::Text(text) | Event::Code(text) => {
+                    current_text.push_str(&text);
+                    line_number += text.matches('\n').count() as u32;
+
+                    // Split long prose, at sentence ends where possible
+                    if !in_code_block
+                        && !matches!(current_type, ChunkType::Heading { .. } | ChunkType::ListItem)
+                    {
+                        while let Some(cut) = self.split_point(&current_text, overlap) {
+                            let head = &current_text[..cut];
+                            let end_line = chunk_start_line + head.matches('\n').count() as u32;
+                            self.push_chunk(
+                                &mut chunks,
+                                tail,
+                                self.create_chunk(
+                                    note,
+                                    &headings,
+                                    head,
+                                    current_type.clone(),
+                                    chunk_start_line,
+                                    end_line,
+                                ),
+                            );
+                            let words: Vec<&str> = head.split_whitespace().collect();
+                            overlap = self.overlap_words.min(words.len());
+                            let mut carried = words[words.len() - overlap..].join(" ");
+                            if overlap > 0 {
+                                carried.push(' ');
+                            }
+                            tail = Some(carried.len());
+                            carried.push_str(current_text[cut..].trim_start());
+                            current_text = carried;
+                            chunk_start_line = end_line;
                         }
                     }
                 }
@@ -166,9 +385,10 @@ impl Chunker {
         }
 
         // Flush remaining content
-        if !current_text.trim().is_empty() {
-            chunks.push(self.create_chunk(
-                note.id,
+        if current_text.split_whitespace().count() > overlap {
+            self.push_chunk(&mut chunks, tail, self.create_chunk(
+                note,
+                &headings,
                 &current_text,
                 current_type,
                 chunk_start_line,
@@ -179,9 +399,46 @@ impl Chunker {
         chunks
     }
 
+    /// Where to cut `text`, whose first `overlap` words are repeated from
+    /// the chunk before, once it has `target_words` words of its own
+    ///
+    /// The cut is after the last sentence ending within the limit, or at the
+    /// limit when the passage has a sentence longer than that.
+    fn split_point(&self, text: &str, overlap: usize) -> Option<usize> {
+        let words = word_ends(text);
+        if self.target_words == 0 || words.len() < overlap + self.target_words {
+            return None;
+        }
+        let limit = overlap + self.target_words - 1;
+        let cut = (overlap..=limit)
+            .rev()
+            .find(|&i| ends_sentence(&text[..words[i]]))
+            .unwrap_or(limit);
+        Some(words[cut])
+    }
+
+    /// Add `chunk`, merging it into the chunk before when it's the short end
+    /// of a passage cut at the word limit; `tail` is the length of the words
+    /// it repeats from that chunk
+    fn push_chunk(&self, chunks: &mut Vec<Chunk>, tail: Option<usize>, chunk: Chunk) {
+        if let (Some(tail), Some(previous)) = (tail, chunks.last_mut()) {
+            let rest = chunk.content.get(tail..).unwrap_or_default().trim();
+            if rest.split_whitespace().count() < self.min_words {
+                if !rest.is_empty() {
+                    previous.content.push(' ');
+                    previous.content.push_str(rest);
+                }
+                previous.end_line = chunk.end_line;
+                return;
+            }
+        }
+        chunks.push(chunk);
+    }
+
     fn create_chunk(
         &self,
-        note_id: Uuid,
+        note: &Note,
+        headings: &[(u8, String)],
         content: &str,
         chunk_type: ChunkType,
         start_line: u32,
@@ -194,9 +451,16 @@ impl Chunker {
             _ => None,
         };
 
+        let context = self.heading_context.then(|| {
+            std::iter::once(note.title.as_str())
+                .chain(headings.iter().map(|(_, heading)| heading.as_str()))
+                .collect::<Vec<_>>()
+                .join(" > ")
+        });
+
         Chunk {
             id: Uuid::new_v4(),
-            note_id,
+            note_id: note.id,
             content: content.trim().to_string(),
             chunk_type,
             language,
@@ -208,6 +472,140 @@ impl Chunker {
             code_embedding: None,
             embedding_model: None,
             embedded_at: None,
+            context,
         }
     }
 }
+
+/// Byte offsets where the words of `text` end
+fn word_ends(text: &str) -> Vec<usize> {
+    let mut ends = Vec::new();
+    let mut in_word = false;
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            if in_word {
+                ends.push(i);
+            }
+            in_word = false;
+        } else {
+            in_word = true;
+        }
+    }
+    if in_word {
+        ends.push(text.len());
+    }
+    ends
+}
+
+/// Whether `text` ends with a sentence: a full stop, question or
+/// exclamation mark, maybe followed by closing quotes, brackets or emphasis
+fn ends_sentence(text: &str) -> bool {
+    text.trim_end_matches(['"', '\'', ')', ']', '*', '_', '\u{201d}', '\u{2019}'])
+        .ends_with(['.', '!', '?'])
+}
+
+/// Splits text into a model's tokens
+pub trait TokenCounter: Send + Sync {
+    /// Byte ranges of the tokens of `text`, without the markers a model adds
+    /// around every input
+    fn token_offsets(&self, text: &str) -> Vec<(usize, usize)>;
+}
+
+impl TokenCounter for tokenizers::Tokenizer {
+    fn token_offsets(&self, text: &str) -> Vec<(usize, usize)> {
+        self.encode(text, false)
+            .map(|encoding| encoding.get_offsets().to_vec())
+            .unwrap_or_default()
+    }
+}
+
+/// The most tokens a chunk may hold, as a model's tokenizer counts them
+///
+/// Words are a rough stand-in for tokens: code, URLs and non-English text
+/// take several tokens a word, so a chunk within [`Chunker`]'s word target
+/// can still be longer than a model reads. [`TokenBudget::fit`] splits such
+/// chunks rather than letting the model drop their ends.
+#[derive(Clone)]
+pub struct TokenBudget {
+    pub max_tokens: usize,
+    pub counter: Arc<dyn TokenCounter>,
+}
+
+impl TokenBudget {
+    /// Split the chunks longer than `max_tokens`, context included, into
+    /// pieces that fit, cut at line breaks or spaces where possible
+    pub fn fit(&self, chunks: Vec<Chunk>) -> Vec<Chunk> {
+        chunks.into_iter().flat_map(|chunk| self.split(chunk)).collect()
+    }
+
+    fn split(&self, chunk: Chunk) -> Vec<Chunk> {
+        // The context is embedded ahead of every piece
+        let context_tokens = chunk
+            .context
+            .as_ref()
+            .map_or(0, |context| self.counter.token_offsets(&format!("{}: ", context)).len());
+        let max_tokens = self.max_tokens.saturating_sub(context_tokens).max(1);
+        let offsets = self.counter.token_offsets(&chunk.content);
+        if self.max_tokens == 0 || offsets.len() <= max_tokens {
+            return vec![chunk];
+        }
+
+        let content = &chunk.content;
+        let line_at = |byte: usize| chunk.start_line + content[..byte].matches('\n').count() as u32;
+        let mut pieces = Vec::new();
+        let (mut token, mut start) = (0, 0);
+        while token < offsets.len() {
+            let end = match offsets.get(token + max_tokens) {
+                None => content.len(),
+                Some(&(cut, _)) => {
+                    // Keep the piece's first token whatever happens, so every
+                    // piece makes progress
+                    let first = offsets[token].0;
+                    let window = &content[start..cut];
+                    [window.rfind('\n'), window.rfind(char::is_whitespace)]
+                        .into_iter()
+                        .flatten()
+                        .map(|i| start + i)
+                        .find(|&i| i > first)
+                        .unwrap_or(cut)
+                }
+            };
+            let text = content[start..end].trim();
+            if !text.is_empty() {
+                pieces.push(Chunk {
+                    id: Uuid::new_v4(),
+                    content: text.to_string(),
+                    start_line: line_at(start),
+                    end_line: if end == content.len() { chunk.end_line } else { line_at(end) },
+                    ..chunk.clone()
+                });
+            }
+            start = end;
+            token = offsets.partition_point(|&(s, _)| s < end);
+        }
+        pieces
+    }
+}
+
+/// Headings each of a note's chunks sits under, outermost first
+///
+/// `chunks` are the note's chunks in order, as [`Chunker::chunk_note`]
+/// produces them; heading chunks are under their parent headings.
+pub fn heading_paths(chunks: &[&Chunk]) -> Vec<Vec<String>> {
+    let mut stack: Vec<(u8, String)> = Vec::new();
+    chunks
+        .iter()
+        .map(|chunk| {
+            if let ChunkType::Heading { level } = chunk.chunk_type {
+                while stack.last().is_some_and(|(l, _)| *l >= level) {
+                    stack.pop();
+                }
+                let path = stack.iter().map(|(_, h)| h.clone()).collect();
+                stack.push((level, chunk.content.clone()));
+                path
+            } else {
+                stack.iter().map(|(_, h)| h.clone()).collect()
+            }
+        })
+        .collect()
+}
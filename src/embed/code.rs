@@ -0,0 +1,223 @@
+//! Splitting code blocks at definitions
+//!
+//! With the `code-symbols` feature, code blocks in a language tree-sitter
+//! knows (Rust, Python, JavaScript, TypeScript and Go) are parsed and cut at
+//! their top-level functions, classes and other definitions, so a long block
+//! becomes one chunk per symbol, named after it, instead of one blob.
+//! Comments and attributes right above a definition go with it; code between
+//! definitions, like imports and constants, is kept together.
+
+/// A piece of a code block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeSymbol {
+    /// The function, class or other definition the piece holds; `None` for
+    /// code between definitions
+    pub name: Option<String>,
+    pub text: String,
+    /// First line of the piece within the block, from 0
+    pub start_row: u32,
+    /// Last line of the piece within the block
+    pub end_row: u32,
+}
+
+/// Split `code`, written in `language` as a code fence names it, at its
+/// top-level definitions
+///
+/// `None` when the language isn't supported, the code doesn't parse cleanly
+/// (as with a fragment of a function) or it defines nothing: such blocks are
+/// best kept whole.
+#[cfg(feature = "code-symbols")]
+pub fn split_code(language: &str, code: &str) -> Option<Vec<CodeSymbol>> {
+    use tree_sitter::Parser;
+
+    let (grammar, definitions) = grammar(language)?;
+    let mut parser = Parser::new();
+    parser.set_language(&grammar).ok()?;
+    let tree = parser.parse(code, None)?;
+    let root = tree.root_node();
+    if root.has_error() {
+        return None;
+    }
+
+    let source = code.as_bytes();
+    let piece = |name: Option<String>, (start, start_row): (usize, usize), end: tree_sitter::Node| CodeSymbol {
+        name,
+        text: code[start..end.end_byte()].to_string(),
+        start_row: start_row as u32,
+        end_row: end.end_position().row as u32,
+    };
+    let mut pieces = Vec::new();
+    // Code since the last definition, as where it starts and its last node
+    let mut between: Option<((usize, usize), tree_sitter::Node)> = None;
+    // Comments and attributes that may lead into a definition
+    let mut leading: Option<(usize, usize)> = None;
+    let mut last_leading = None;
+
+    let mut cursor = root.walk();
+    for node in root.named_children(&mut cursor) {
+        if node.kind().contains("comment") || node.kind() == "attribute_item" {
+            leading.get_or_insert((node.start_byte(), node.start_position().row));
+            last_leading = Some(node);
+            continue;
+        }
+        let start = leading.take().unwrap_or((node.start_byte(), node.start_position().row));
+        let name = definitions
+            .contains(&node.kind())
+            .then(|| symbol_name(node, source))
+            .flatten();
+        match name {
+            Some(name) => {
+                if let Some((start, end)) = between.take() {
+                    pieces.push(piece(None, start, end));
+                }
+                pieces.push(piece(Some(name), start, node));
+            }
+            None => {
+                let start = between.map_or(start, |(start, _)| start);
+                between = Some((start, node));
+            }
+        }
+    }
+    if let (Some(start), Some(end)) = (leading, last_leading) {
+        let start = between.map_or(start, |(start, _)| start);
+        between = Some((start, end));
+    }
+    if let Some((start, end)) = between {
+        pieces.push(piece(None, start, end));
+    }
+
+    pieces.iter().any(|p| p.name.is_some()).then_some(pieces)
+}
+
+/// Without the `code-symbols` feature code blocks are always kept whole
+#[cfg(not(feature = "code-symbols"))]
+pub fn split_code(_language: &str, _code: &str) -> Option<Vec<CodeSymbol>> {
+    None
+}
+
+/// The grammar for a code fence's language and the node kinds that define
+/// symbols at the top level of its files
+#[cfg(feature = "code-symbols")]
+fn grammar(language: &str) -> Option<(tree_sitter::Language, &'static [&'static str])> {
+    const JAVASCRIPT: &[&str] = &[
+        "function_declaration",
+        "generator_function_declaration",
+        "class_declaration",
+        "export_statement",
+    ];
+    const TYPESCRIPT: &[&str] = &[
+        "function_declaration",
+        "generator_function_declaration",
+        "class_declaration",
+        "abstract_class_declaration",
+        "interface_declaration",
+        "type_alias_declaration",
+        "enum_declaration",
+        "internal_module",
+        "export_statement",
+    ];
+
+    // Fences may carry more than the language, as in ```rust,ignore
+    let language = language
+        .split(|c: char| c == ',' || c == '{' || c.is_whitespace())
+        .next()?
+        .to_lowercase();
+    let (grammar, definitions): (tree_sitter::Language, &[&str]) = match language.as_str() {
+        "rust" | "rs" => (
+            tree_sitter_rust::LANGUAGE.into(),
+            &[
+                "function_item",
+                "struct_item",
+                "enum_item",
+                "union_item",
+                "trait_item",
+                "impl_item",
+                "mod_item",
+                "type_item",
+                "macro_definition",
+            ],
+        ),
+        "python" | "py" => (
+            tree_sitter_python::LANGUAGE.into(),
+            &["function_definition", "class_definition", "decorated_definition"],
+        ),
+        "javascript" | "js" | "jsx" | "mjs" => (tree_sitter_javascript::LANGUAGE.into(), JAVASCRIPT),
+        "typescript" | "ts" => (tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(), TYPESCRIPT),
+        "tsx" => (tree_sitter_typescript::LANGUAGE_TSX.into(), TYPESCRIPT),
+        "go" | "golang" => (
+            tree_sitter_go::LANGUAGE.into(),
+            &["function_declaration", "method_declaration", "type_declaration"],
+        ),
+        _ => return None,
+    };
+    Some((grammar, definitions))
+}
+
+/// Name of the symbol a definition node defines
+#[cfg(feature = "code-symbols")]
+fn symbol_name(node: tree_sitter::Node, source: &[u8]) -> Option<String> {
+    let text = |node: tree_sitter::Node| node.utf8_text(source).ok().map(str::to_string);
+    if node.kind() == "impl_item" {
+        let ty = text(node.child_by_field_name("type")?)?;
+        return Some(match node.child_by_field_name("trait").and_then(text) {
+            Some(implemented) => format!("{} for {}", implemented, ty),
+            None => ty,
+        });
+    }
+    if let Some(name) = node.child_by_field_name("name") {
+        return text(name);
+    }
+    // Decorated definitions, exports and Go type declarations wrap the node
+    // that has the name
+    let mut cursor = node.walk();
+    let inner = node
+        .child_by_field_name("definition")
+        .or_else(|| node.child_by_field_name("declaration"))
+        .or_else(|| node.named_children(&mut cursor).find(|c| c.kind() == "type_spec"))?;
+    symbol_name(inner, source)
+}
+
+#[cfg(all(test, feature = "code-symbols"))]
+mod tests {
+    use super::*;
+
+    fn names(symbols: &[CodeSymbol]) -> Vec<Option<&str>> {
+        symbols.iter().map(|s| s.name.as_deref()).collect()
+    }
+
+    #[test]
+    fn test_split_rust_at_items() {
+        let code = "use std::fmt;\n\n/// A point\n#[derive(Debug)]\nstruct Point { x: i32 }\n\nimpl fmt::Display for Point {\n    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, \"{}\", self.x) }\n}\n\nfn main() {}\n";
+        let symbols = split_code("rust", code).unwrap();
+        assert_eq!(
+            names(&symbols),
+            vec![None, Some("Point"), Some("fmt::Display for Point"), Some("main")]
+        );
+        assert_eq!(symbols[0].text, "use std::fmt;");
+        assert!(symbols[1].text.starts_with("/// A point\n#[derive(Debug)]\nstruct Point"));
+        assert_eq!((symbols[1].start_row, symbols[1].end_row), (2, 4));
+        assert_eq!(symbols[3].start_row, 10);
+    }
+
+    #[test]
+    fn test_split_python_and_typescript() {
+        let python = "import os\n\n@cache\ndef load(path):\n    return os.path\n\nclass Store:\n    pass\n";
+        assert_eq!(
+            names(&split_code("py", python).unwrap()),
+            vec![None, Some("load"), Some("Store")]
+        );
+
+        let typescript = "export interface User { id: string }\nexport function greet(user: User) {}\n";
+        assert_eq!(
+            names(&split_code("typescript", typescript).unwrap()),
+            vec![Some("User"), Some("greet")]
+        );
+    }
+
+    #[test]
+    fn test_blocks_kept_whole() {
+        assert!(split_code("rust", "let x = 1;\nprintln!(\"{}\", x);").is_none());
+        assert!(split_code("haskell", "main = putStrLn \"hi\"").is_none());
+        assert!(split_code("rust", "fn broken( {").is_none());
+    }
+}
@@ -1,14 +1,88 @@
-//! Text embedder using fastembed
+//! Text embedder
+//!
+//! `[embedding] prose_model` and `code_model` pick among fastembed's models,
+//! named like `BAAI/bge-small-en-v1.5` or just `bge-small-en-v1.5`, or the
+//! remote API's models with `provider = "remote"`. Chunks record the prose
+//! model as configured, and embeddings of different models are never mixed:
+//! a vault embedded with one model must be reindexed with `notidium index
+//! --force` after switching.
 
-use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
-use std::sync::Mutex;
+use fastembed::{EmbeddingModel, TextEmbedding};
 
+use crate::config::{EmbeddingConfig, EmbeddingProvider};
 use crate::error::{Error, Result};
 
+use super::backend::{EmbedderBackend, LocalBackend, RemoteBackend};
+use super::chunker::TokenBudget;
+
+/// Default model behind prose embeddings
+pub const PROSE_MODEL: &str = "BAAI/bge-small-en-v1.5";
+
+/// Default model behind code embeddings
+pub const CODE_MODEL: &str = "jinaai/jina-embeddings-v2-base-code";
+
+/// A fastembed model and the size of its embeddings
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelChoice {
+    pub model: EmbeddingModel,
+    pub dimension: usize,
+}
+
+/// Find the fastembed model called `name`
+///
+/// Names match fastembed's model codes ignoring case; the organization
+/// part may differ or be left out, since fastembed often ships a converted
+/// copy of a model (`Xenova/bge-small-en-v1.5` for `BAAI/bge-small-en-v1.5`).
+pub fn resolve_model(name: &str) -> Result<ModelChoice> {
+    let models = TextEmbedding::list_supported_models();
+    let repo = |code: &str| code.rsplit('/').next().unwrap_or(code).to_lowercase();
+    let wanted = repo(name);
+    models
+        .iter()
+        .find(|m| m.model_code.eq_ignore_ascii_case(name))
+        .or_else(|| models.iter().find(|m| repo(&m.model_code) == wanted))
+        .map(|m| ModelChoice {
+            model: m.model.clone(),
+            dimension: m.dim,
+        })
+        .ok_or_else(|| {
+            let mut codes: Vec<&str> = models.iter().map(|m| m.model_code.as_str()).collect();
+            codes.dedup();
+            Error::Embedding(format!(
+                "Unknown embedding model {}; available: {}",
+                name,
+                codes.join(", ")
+            ))
+        })
+}
+
+/// Fail if chunks were embedded with another model than `model`
+///
+/// Chunks from before models were recorded are assumed to match.
+pub fn check_models<'a>(recorded: impl IntoIterator<Item = &'a str>, model: &str) -> Result<()> {
+    let mut others: Vec<&str> = recorded.into_iter().filter(|m| *m != model).collect();
+    if others.is_empty() {
+        return Ok(());
+    }
+    others.sort();
+    others.dedup();
+    Err(Error::Embedding(format!(
+        "Chunks were embedded with {}, but the configured model is {}; run `notidium index --force` to re-embed them",
+        others.join(", "),
+        model
+    )))
+}
+
 /// Text embedder wrapper with separate models for prose and code
+///
+/// Without a code model (`embedding.code_embeddings = false`), code is
+/// embedded with the prose model.
 pub struct Embedder {
-    prose_model: Mutex<TextEmbedding>,
-    code_model: Mutex<TextEmbedding>,
+    prose: Box<dyn EmbedderBackend>,
+    code: Option<Box<dyn EmbedderBackend>>,
+    prose_name: String,
+    /// `embedding.max_chunk_tokens`; 0 for no limit
+    max_chunk_tokens: usize,
 }
 
 impl Embedder {
@@ -16,55 +90,97 @@ impl Embedder {
     /// - Prose: BGE-small-en-v1.5 (384 dimensions)
     /// - Code: Jina-embeddings-v2-base-code (768 dimensions)
     pub fn new() -> Result<Self> {
-        let prose_options = InitOptions::new(EmbeddingModel::BGESmallENV15)
-            .with_show_download_progress(true);
-        let prose_model = TextEmbedding::try_new(prose_options)
-            .map_err(|e| Error::Embedding(format!("Failed to load prose model: {}", e)))?;
-
-        let code_options = InitOptions::new(EmbeddingModel::JinaEmbeddingsV2BaseCode)
-            .with_show_download_progress(true);
-        let code_model = TextEmbedding::try_new(code_options)
-            .map_err(|e| Error::Embedding(format!("Failed to load code model: {}", e)))?;
+        Self::from_config(&EmbeddingConfig::default())
+    }
 
+    /// Create an embedder with the provider and models `config` names;
+    /// local models are loaded when first used
+    pub fn from_config(config: &EmbeddingConfig) -> Result<Self> {
+        let backend = |model: &str| -> Result<Box<dyn EmbedderBackend>> {
+            Ok(match config.provider {
+                EmbeddingProvider::Local => Box::new(LocalBackend::new(model, config)?),
+                EmbeddingProvider::Remote => Box::new(RemoteBackend::new(&config.remote, model)?),
+            })
+        };
         Ok(Self {
-            prose_model: Mutex::new(prose_model),
-            code_model: Mutex::new(code_model),
+            prose: backend(&config.prose_model)?,
+            code: if config.code_embeddings {
+                Some(backend(&config.code_model)?)
+            } else {
+                None
+            },
+            prose_name: config.prose_model.clone(),
+            max_chunk_tokens: config.max_chunk_tokens,
         })
     }
 
+    /// Create an embedder from backends directly, such as a stand-in for
+    /// tests; without `code`, code is embedded by `prose`
+    pub fn from_backends(
+        prose_name: &str,
+        prose: Box<dyn EmbedderBackend>,
+        code: Option<Box<dyn EmbedderBackend>>,
+    ) -> Self {
+        Self {
+            prose,
+            code,
+            prose_name: prose_name.to_string(),
+            max_chunk_tokens: 0,
+        }
+    }
+
+    /// Split chunks longer than `max_tokens` of the prose model's tokens
+    /// before embedding them; 0 for no limit
+    pub fn with_max_chunk_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_chunk_tokens = max_tokens;
+        self
+    }
+
+    /// Prose model as configured, recorded on the chunks it embeds
+    pub fn prose_model(&self) -> &str {
+        &self.prose_name
+    }
+
+    /// Whether code gets embeddings of its own, rather than prose ones
+    pub fn has_code_model(&self) -> bool {
+        self.code.is_some()
+    }
+
+    /// The token limit chunks must fit before they are embedded, counted
+    /// with the prose model's tokenizer, which loads the model
+    ///
+    /// `None` without a limit or when the tokenizer isn't known, as with
+    /// remote models. Code chunks are embedded by the prose model too, so
+    /// the prose model's limit is the one that matters.
+    pub async fn token_budget(&self) -> Result<Option<TokenBudget>> {
+        if self.max_chunk_tokens == 0 {
+            return Ok(None);
+        }
+        Ok(self.prose.tokenizer().await?.map(|counter| TokenBudget {
+            max_tokens: self.max_chunk_tokens,
+            counter,
+        }))
+    }
+
+    fn code_backend(&self) -> &dyn EmbedderBackend {
+        self.code.as_deref().unwrap_or(self.prose.as_ref())
+    }
+
     /// Embed a single text using the prose model
     pub async fn embed(&self, text: &str) -> Result<Vec<f32>> {
         self.embed_prose(text).await
     }
 
     /// Embed a single text using the prose model
+    #[tracing::instrument(skip_all, fields(chars = text.len()))]
     pub async fn embed_prose(&self, text: &str) -> Result<Vec<f32>> {
-        let text = text.to_string();
-        let model = self.prose_model.lock().unwrap();
-
-        let embeddings = model
-            .embed(vec![text], None)
-            .map_err(|e| Error::Embedding(e.to_string()))?;
-
-        embeddings
-            .into_iter()
-            .next()
-            .ok_or_else(|| Error::Embedding("No embedding generated".into()))
+        first(self.prose.embed(vec![text.to_string()]).await?)
     }
 
     /// Embed a single text using the code model
+    #[tracing::instrument(skip_all, fields(chars = text.len()))]
     pub async fn embed_code(&self, text: &str) -> Result<Vec<f32>> {
-        let text = text.to_string();
-        let model = self.code_model.lock().unwrap();
-
-        let embeddings = model
-            .embed(vec![text], None)
-            .map_err(|e| Error::Embedding(e.to_string()))?;
-
-        embeddings
-            .into_iter()
-            .next()
-            .ok_or_else(|| Error::Embedding("No embedding generated".into()))
+        first(self.code_backend().embed(vec![text.to_string()]).await?)
     }
 
     /// Embed a batch of texts using the prose model
@@ -73,39 +189,31 @@ impl Embedder {
     }
 
     /// Embed a batch of texts using the prose model
+    #[tracing::instrument(skip_all, fields(count = texts.len()))]
     pub async fn embed_batch_prose(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
         if texts.is_empty() {
             return Ok(Vec::new());
         }
-
-        let model = self.prose_model.lock().unwrap();
-
-        model
-            .embed(texts, None)
-            .map_err(|e| Error::Embedding(e.to_string()))
+        self.prose.embed(texts).await
     }
 
     /// Embed a batch of texts using the code model
+    #[tracing::instrument(skip_all, fields(count = texts.len()))]
     pub async fn embed_batch_code(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
         if texts.is_empty() {
             return Ok(Vec::new());
         }
-
-        let model = self.code_model.lock().unwrap();
-
-        model
-            .embed(texts, None)
-            .map_err(|e| Error::Embedding(e.to_string()))
+        self.code_backend().embed(texts).await
     }
 
     /// Get prose embedding dimension
     pub fn prose_dimension(&self) -> usize {
-        384 // BGE-small-en-v1.5 dimension
+        self.prose.dimension()
     }
 
     /// Get code embedding dimension
     pub fn code_dimension(&self) -> usize {
-        768 // Jina-embeddings-v2-base-code dimension
+        self.code_backend().dimension()
     }
 
     /// Get embedding dimension (prose, for backwards compatibility)
@@ -119,3 +227,34 @@ impl Default for Embedder {
         Self::new().expect("Failed to create embedder")
     }
 }
+
+fn first(embeddings: Vec<Vec<f32>>) -> Result<Vec<f32>> {
+    embeddings
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::Embedding("No embedding generated".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_model() {
+        let prose = resolve_model(PROSE_MODEL).unwrap();
+        assert_eq!(prose.model, EmbeddingModel::BGESmallENV15);
+        assert_eq!(prose.dimension, 384);
+        assert_eq!(resolve_model("bge-small-en-v1.5").unwrap(), prose);
+        assert_eq!(resolve_model(CODE_MODEL).unwrap().dimension, 768);
+        assert_eq!(resolve_model("Xenova/bge-base-en-v1.5").unwrap().dimension, 768);
+        assert!(resolve_model("openai/text-embedding-3-small").is_err());
+    }
+
+    #[test]
+    fn test_check_models() {
+        assert!(check_models([PROSE_MODEL, PROSE_MODEL], PROSE_MODEL).is_ok());
+        assert!(check_models([], PROSE_MODEL).is_ok());
+        let err = check_models([PROSE_MODEL, "bge-base-en-v1.5"], PROSE_MODEL).unwrap_err();
+        assert!(err.to_string().contains("bge-base-en-v1.5"));
+    }
+}
@@ -1,7 +1,13 @@
 //! Embedding generation
 
+mod backend;
 mod embedder;
 mod chunker;
+mod code;
+mod pipeline;
 
-pub use embedder::Embedder;
-pub use chunker::Chunker;
+pub use backend::{EmbedderBackend, LocalBackend, RemoteBackend};
+pub use embedder::{check_models, resolve_model, Embedder, ModelChoice, CODE_MODEL, PROSE_MODEL};
+pub use code::{split_code, CodeSymbol};
+pub use chunker::{heading_paths, Chunker, TokenBudget, TokenCounter};
+pub use pipeline::{embed_chunks, embed_notes};
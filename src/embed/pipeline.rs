@@ -0,0 +1,202 @@
+//! Embedding a vault's worth of notes
+//!
+//! [`embed_notes`] streams notes through chunking, batching and embedding
+//! instead of doing each step for the whole vault in turn: chunks are cut as
+//! batches are needed, and up to `concurrent_batches` batches are embedded
+//! at once, so a model's sessions, a GPU or a remote API never sit idle
+//! waiting for the next batch.
+
+use chrono::Utc;
+use futures::stream::{self, StreamExt, TryStreamExt};
+
+use crate::error::Result;
+use crate::types::{Chunk, ChunkType, Note};
+
+use super::{Chunker, Embedder};
+
+/// Embed `chunks`: prose embeddings for all of them, code embeddings for
+/// code blocks when `embedder` has a code model
+pub async fn embed_chunks(embedder: &Embedder, chunks: &mut [Chunk]) -> Result<()> {
+    let texts = chunks.iter().map(|c| c.embedding_text()).collect();
+    let embeddings = embedder.embed_batch_prose(texts).await?;
+    let now = Utc::now();
+    for (chunk, embedding) in chunks.iter_mut().zip(embeddings) {
+        chunk.prose_embedding = Some(embedding);
+        chunk.embedding_model = Some(embedder.prose_model().to_string());
+        chunk.embedded_at = Some(now);
+    }
+
+    if embedder.has_code_model() {
+        let mut code: Vec<&mut Chunk> = chunks
+            .iter_mut()
+            .filter(|c| matches!(c.chunk_type, ChunkType::CodeBlock { .. }))
+            .collect();
+        let texts = code.iter().map(|c| c.embedding_text()).collect();
+        let embeddings = embedder.embed_batch_code(texts).await?;
+        for (chunk, embedding) in code.iter_mut().zip(embeddings) {
+            chunk.code_embedding = Some(embedding);
+        }
+    }
+    Ok(())
+}
+
+/// Chunk and embed `notes`, `batch_size` chunks at a time with up to
+/// `concurrent_batches` batches in flight
+///
+/// Chunks are split to fit the embedder's [`Embedder::token_budget`].
+/// `progress` is called with the number of chunks embedded so far after
+/// each batch. Chunks come back in the order their batches finished.
+pub async fn embed_notes(
+    embedder: &Embedder,
+    chunker: &Chunker,
+    notes: &[Note],
+    batch_size: usize,
+    concurrent_batches: usize,
+    mut progress: impl FnMut(usize),
+) -> Result<Vec<Chunk>> {
+    let budget = embedder.token_budget().await?;
+    let budget = budget.as_ref();
+    let mut batches = stream::iter(notes)
+        .flat_map(|note| {
+            let chunks = chunker.chunk_note(note);
+            stream::iter(match budget {
+                Some(budget) => budget.fit(chunks),
+                None => chunks,
+            })
+        })
+        .chunks(batch_size.max(1))
+        .map(|mut batch| async move {
+            embed_chunks(embedder, &mut batch).await?;
+            Ok::<_, crate::error::Error>(batch)
+        })
+        .buffer_unordered(concurrent_batches.max(1));
+
+    let mut chunks = Vec::new();
+    while let Some(batch) = batches.try_next().await? {
+        chunks.extend(batch);
+        progress(chunks.len());
+    }
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::embed::{EmbedderBackend, TokenCounter};
+
+    /// Embeds each text as its length, tracking how many calls overlap
+    struct SlowBackend {
+        in_flight: Arc<AtomicUsize>,
+        most_in_flight: Arc<AtomicUsize>,
+        value: f32,
+    }
+
+    #[async_trait]
+    impl EmbedderBackend for SlowBackend {
+        async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.most_in_flight.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(texts.iter().map(|t| vec![t.len() as f32 * self.value]).collect())
+        }
+
+        fn dimension(&self) -> usize {
+            1
+        }
+
+        async fn tokenizer(&self) -> Result<Option<Arc<dyn TokenCounter>>> {
+            Ok(Some(Arc::new(WordTokens)))
+        }
+    }
+
+    /// Every word is a token
+    struct WordTokens;
+
+    impl TokenCounter for WordTokens {
+        fn token_offsets(&self, text: &str) -> Vec<(usize, usize)> {
+            text.split_whitespace()
+                .map(|word| {
+                    let start = word.as_ptr() as usize - text.as_ptr() as usize;
+                    (start, start + word.len())
+                })
+                .collect()
+        }
+    }
+
+    fn embedder(most_in_flight: &Arc<AtomicUsize>) -> Embedder {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let backend = |value| {
+            Box::new(SlowBackend {
+                in_flight: in_flight.clone(),
+                most_in_flight: most_in_flight.clone(),
+                value,
+            })
+        };
+        Embedder::from_backends("slow", backend(1.0), Some(backend(-1.0)))
+    }
+
+    #[tokio::test]
+    async fn test_embed_notes_in_concurrent_batches() {
+        let most_in_flight = Arc::new(AtomicUsize::new(0));
+        let embedder = embedder(&most_in_flight);
+        let notes: Vec<Note> = (0..8)
+            .map(|i| {
+                let path = format!("note-{}.md", i).into();
+                Note::new(format!("Note {}", i), format!("Paragraph {} of prose.", i), path)
+            })
+            .collect();
+
+        let mut reported = Vec::new();
+        let chunks = embed_notes(&embedder, &Chunker::default(), &notes, 2, 3, |done| reported.push(done))
+            .await
+            .unwrap();
+
+        assert_eq!(chunks.len(), notes.len());
+        assert!(chunks.iter().all(|c| c.prose_embedding.is_some() && c.embedding_model.as_deref() == Some("slow")));
+        assert_eq!(reported, vec![2, 4, 6, 8]);
+        let most = most_in_flight.load(Ordering::SeqCst);
+        assert!(most > 1 && most <= 3, "{} batches in flight", most);
+    }
+
+    #[tokio::test]
+    async fn test_code_blocks_get_code_embeddings() {
+        let embedder = embedder(&Arc::new(AtomicUsize::new(0)));
+        let note = Note::new(
+            "Snippet".to_string(),
+            "Some prose about the snippet.\n\n```rust\nfn main() {}\n```\n".to_string(),
+            "snippet.md".into(),
+        );
+
+        let chunks = embed_notes(&embedder, &Chunker::default(), &[note], 32, 1, |_| ()).await.unwrap();
+        let code: Vec<&Chunk> = chunks
+            .iter()
+            .filter(|c| matches!(c.chunk_type, ChunkType::CodeBlock { .. }))
+            .collect();
+        assert_eq!(code.len(), 1);
+        assert!(code[0].code_embedding.as_ref().unwrap()[0] < 0.0);
+        assert!(chunks.iter().filter(|c| c.code_embedding.is_none()).all(|c| c.prose_embedding.is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_chunks_split_to_token_budget() {
+        // One of the four tokens goes to the "Counting" context
+        let embedder = embedder(&Arc::new(AtomicUsize::new(0))).with_max_chunk_tokens(4);
+        let note = Note::new(
+            "Counting".to_string(),
+            "one two three four five six seven".to_string(),
+            "counting.md".into(),
+        );
+
+        let chunks = embed_notes(&embedder, &Chunker::default(), &[note], 32, 1, |_| ()).await.unwrap();
+        let contents: Vec<&str> = chunks.iter().map(|c| c.content.as_str()).collect();
+        assert_eq!(contents, vec!["one two three", "four five six", "seven"]);
+        assert!(chunks.iter().all(|c| c.prose_embedding.is_some()));
+    }
+}
@@ -34,6 +34,9 @@ pub enum Error {
     #[error("Search error: {0}")]
     Search(String),
 
+    #[error("Vector store error: {0}")]
+    VectorStore(String),
+
     #[error("Embedding error: {0}")]
     Embedding(String),
 
@@ -49,6 +52,57 @@ pub enum Error {
     #[error("Service error: {0}")]
     Service(String),
 
+    #[error("Snapshot error: {0}")]
+    Snapshot(String),
+
+    #[error("Export error: {0}")]
+    Export(String),
+
+    #[error("Capture error: {0}")]
+    Capture(String),
+
+    #[error("Telemetry error: {0}")]
+    Telemetry(String),
+
+    #[error("History error: {0}")]
+    History(String),
+
+    #[error("Version not found: {0}")]
+    VersionNotFound(String),
+
+    #[error("Template not found: {0}")]
+    TemplateNotFound(String),
+
+    #[error("Attachment not found: {0}")]
+    AttachmentNotFound(String),
+
+    #[error("Canvas not found: {0}")]
+    CanvasNotFound(String),
+
+    #[error("Canvas already exists: {0}")]
+    CanvasAlreadyExists(String),
+
+    #[error("Invalid canvas: {0}")]
+    InvalidCanvas(String),
+
+    #[error("Invalid board move: {0}")]
+    InvalidBoardMove(String),
+
+    #[error("Tag not found: {0}")]
+    TagNotFound(String),
+
+    #[error("Invalid tag: {0}")]
+    InvalidTag(String),
+
+    #[error("Annotation not found: {0}")]
+    AnnotationNotFound(String),
+
+    #[error("Invalid annotation: {0}")]
+    InvalidAnnotation(String),
+
+    #[error("Access denied: {0}")]
+    AccessDenied(String),
+
     #[error("{0}")]
     Other(String),
 }
@@ -72,3 +126,15 @@ impl From<notify::Error> for Error {
         Error::Watcher(err.to_string())
     }
 }
+
+impl From<lancedb::Error> for Error {
+    fn from(err: lancedb::Error) -> Self {
+        Error::VectorStore(err.to_string())
+    }
+}
+
+impl From<arrow_schema::ArrowError> for Error {
+    fn from(err: arrow_schema::ArrowError) -> Self {
+        Error::VectorStore(err.to_string())
+    }
+}
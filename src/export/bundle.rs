@@ -0,0 +1,187 @@
+//! Offline reading bundle
+//!
+//! `notidium export bundle` writes the whole vault into one HTML file that
+//! any browser opens without a server, network or embedding models: every
+//! live note rendered with its embeds inlined and its images as data URIs,
+//! a note list, and search over a term index built at export time. Wiki
+//! links between notes jump to each other inside the file. The bundle is a
+//! read-only copy; nothing in it writes back to the vault.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Write;
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::note::{data_uri, escape, has_title_heading, render_markdown, strip_frontmatter, STYLE};
+use super::site::{plain_text, rewrite_wiki_links, terms};
+use crate::error::Result;
+use crate::store::NoteStore;
+use crate::types::Note;
+
+const BUNDLE_STYLE: &str = "\
+body { max-width: none; margin: 0; padding: 0; display: flex; min-height: 100vh; }
+nav { width: 18em; flex-shrink: 0; padding: 1em; border-right: 1px solid #d1d9e0; overflow-y: auto; max-height: 100vh; position: sticky; top: 0; box-sizing: border-box; }
+main { flex: 1; max-width: 46em; padding: 2em 3em; }
+nav h1 { font-size: 1.1em; }
+#search { width: 100%; box-sizing: border-box; padding: .4em; margin-bottom: 1em; }
+#list { list-style: none; padding: 0; margin: 0; }
+#list li { margin-bottom: .5em; }
+.meta { color: #59636e; font-size: .9em; }
+@media (prefers-color-scheme: dark) {
+  body { background: #0d1117; color: #e6edf3; }
+  nav { border-color: #3d444d; }
+  a { color: #58a6ff; }
+  pre, code { background: #161b22; }
+  .meta, blockquote { color: #9198a1; }
+}
+";
+
+const READER_SCRIPT: &str = r#"(function () {
+  var index = JSON.parse(document.getElementById('index').textContent);
+  var input = document.getElementById('search');
+  var items = Array.prototype.slice.call(document.querySelectorAll('#list li'));
+  var notes = Array.prototype.slice.call(document.querySelectorAll('section.note'));
+
+  function show() {
+    var id = location.hash.slice(1);
+    if (!document.getElementById(id) && notes.length > 0) {
+      id = notes[0].id;
+    }
+    notes.forEach(function (note) { note.hidden = note.id !== id; });
+    window.scrollTo(0, 0);
+  }
+
+  function tokens(text) {
+    return text.toLowerCase().split(/[^\p{L}\p{N}]+/u).filter(function (t) { return t.length > 1; });
+  }
+
+  input.addEventListener('input', function () {
+    var query = tokens(input.value);
+    var terms = Object.keys(index.terms);
+    var matches = null;
+    query.forEach(function (token) {
+      var docs = new Set();
+      terms.forEach(function (term) {
+        if (term.startsWith(token)) {
+          index.terms[term].forEach(function (doc) { docs.add(doc); });
+        }
+      });
+      matches = matches === null ? docs : new Set(Array.from(matches).filter(function (doc) { return docs.has(doc); }));
+    });
+    items.forEach(function (item, doc) { item.hidden = matches !== null && !matches.has(doc); });
+  });
+
+  window.addEventListener('hashchange', show);
+  show();
+})();
+"#;
+
+/// What a bundle holds
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BundleSummary {
+    pub notes: usize,
+    /// Images inlined into the notes
+    pub images: usize,
+}
+
+/// Term index for the bundle's search box
+#[derive(Serialize)]
+struct BundleIndex {
+    /// Lowercased word -> positions of notes in the list
+    terms: BTreeMap<String, BTreeSet<usize>>,
+}
+
+/// Write every live note of `store` into a single HTML file, most recently
+/// updated first
+pub async fn write_bundle<W: Write>(store: &NoteStore, title: &str, mut writer: W) -> Result<BundleSummary> {
+    let mut notes: Vec<Note> = store.list().await.into_iter().filter(|n| !n.is_deleted).collect();
+    notes.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then_with(|| a.title.cmp(&b.title)));
+
+    let anchors: HashMap<Uuid, String> = notes.iter().map(|n| (n.id, format!("#{}", anchor(n)))).collect();
+
+    let config = store.config();
+    let attachments_dir = config.attachments_path();
+    let mut summary = BundleSummary::default();
+    let mut index = BundleIndex { terms: BTreeMap::new() };
+    let mut list = String::new();
+    let mut sections = String::new();
+    for (position, note) in notes.iter().enumerate() {
+        let content = store.transclude(note.id, &note.content, |_| true).await;
+        let links = store.resolve_links(note.id, &content).await;
+        let content = rewrite_wiki_links(&content, &links, |id| anchors.get(&id).map(String::as_str));
+        let body = strip_frontmatter(&content);
+        let full_path = config.notes_path().join(&note.file_path);
+        let note_dir = full_path.parent().unwrap_or(&config.vault_path);
+
+        let html = render_markdown(body, |dest| {
+            let uri = data_uri(dest, note_dir, &attachments_dir)?;
+            summary.images += 1;
+            Some(uri)
+        });
+
+        let text = format!("{} {} {}", note.title, note.tags().join(" "), plain_text(body, false));
+        for term in terms(&text) {
+            index.terms.entry(term).or_default().insert(position);
+        }
+
+        list.push_str(&format!(
+            "<li><a href=\"#{}\">{}</a> <span class=\"meta\">{}</span></li>\n",
+            anchor(note),
+            escape(&note.title),
+            note.updated_at.format("%Y-%m-%d")
+        ));
+        sections.push_str(&section(note, &html));
+        summary.notes += 1;
+    }
+
+    let index = serde_json::to_string(&index)?;
+    write!(
+        writer,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{title}</title>\n<style>\n{style}{bundle_style}</style>\n</head>\n<body>\n\
+         <nav>\n<h1>{title}</h1>\n\
+         <input id=\"search\" type=\"search\" placeholder=\"Search notes\" autocomplete=\"off\">\n\
+         <ul id=\"list\">\n{list}</ul>\n</nav>\n<main>\n{sections}</main>\n\
+         <script type=\"application/json\" id=\"index\">{index}</script>\n\
+         <script>\n{script}</script>\n</body>\n</html>\n",
+        title = escape(title),
+        style = STYLE,
+        bundle_style = BUNDLE_STYLE,
+        list = list,
+        sections = sections,
+        index = index,
+        script = READER_SCRIPT
+    )?;
+    writer.flush()?;
+
+    Ok(summary)
+}
+
+/// Element ID of a note's section
+fn anchor(note: &Note) -> String {
+    format!("note-{}", note.id)
+}
+
+fn section(note: &Note, html: &str) -> String {
+    let mut content = String::new();
+    if !has_title_heading(strip_frontmatter(&note.content)) {
+        content.push_str(&format!("<h1>{}</h1>\n", escape(&note.title)));
+    }
+    content.push_str(html);
+
+    let mut meta = format!("Updated {}", note.updated_at.format("%Y-%m-%d"));
+    if note.is_archived {
+        meta.push_str(" · Archived");
+    }
+    for tag in note.tags() {
+        meta.push_str(&format!(" · #{}", escape(&tag)));
+    }
+    format!(
+        "<section class=\"note\" id=\"{}\" hidden>\n<article>\n{}</article>\n<p class=\"meta\">{}</p>\n</section>\n",
+        anchor(note),
+        content,
+        meta
+    )
+}
@@ -0,0 +1,210 @@
+//! Vault export
+//!
+//! The zip archive holds the vault's notes, attachments and templates plus
+//! the manifest, laid out as in the vault: unpack it and run
+//! `notidium index` to get a working vault with the same note IDs. The JSON
+//! dump is for other tools, with every note's metadata, tags and resolved
+//! links alongside its content. Single notes export to HTML, PDF or DOCX
+//! through [`note`], tagged notes publish as a static site through
+//! [`site`], [`bundle`] packs the vault into one HTML file for reading
+//! offline, and [`stats`] writes per-note metrics for analysis.
+
+use std::collections::HashMap;
+use std::io::{Seek, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::store::NoteStore;
+use crate::types::{LinkType, RelationKind};
+
+pub mod bundle;
+pub mod note;
+pub mod site;
+pub mod stats;
+
+/// What an export produces
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Archive of the vault's files
+    #[default]
+    Zip,
+    /// Notes with their metadata as one JSON document
+    Json,
+}
+
+impl ExportFormat {
+    /// File extension of an export in this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Zip => "zip",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "zip" => Ok(ExportFormat::Zip),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err(Error::Export(format!(
+                "Unknown export format {:?}, expected zip or json",
+                s
+            ))),
+        }
+    }
+}
+
+/// All notes of a vault
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct VaultDump {
+    pub exported_at: DateTime<Utc>,
+    pub notes: Vec<NoteDump>,
+}
+
+/// A note with its metadata
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NoteDump {
+    pub id: Uuid,
+    pub title: String,
+    /// Path relative to the notes directory
+    pub path: String,
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_by: Option<String>,
+    pub is_pinned: bool,
+    pub is_archived: bool,
+    /// Full markdown content, frontmatter included
+    pub content: String,
+    pub links: Vec<LinkDump>,
+}
+
+/// A link from a note
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LinkDump {
+    /// Link target as written
+    pub target: String,
+    /// Note the target resolves to, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_id: Option<Uuid>,
+    pub link_type: LinkType,
+    /// Kind of relation, for relations declared in frontmatter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relation: Option<RelationKind>,
+}
+
+/// Dump the live notes of a store, ordered by path
+pub async fn dump(store: &NoteStore) -> VaultDump {
+    let mut links: HashMap<Uuid, Vec<LinkDump>> = HashMap::new();
+    let mut all_links = store.links().await;
+    all_links.sort_by_key(|l| l.position);
+    for link in all_links {
+        links.entry(link.source_note_id).or_default().push(LinkDump {
+            target: link.target_raw,
+            target_id: link.target_note_id,
+            link_type: link.link_type,
+            relation: link.relation,
+        });
+    }
+
+    let mut notes: Vec<NoteDump> = store
+        .list()
+        .await
+        .into_iter()
+        .filter(|n| !n.is_deleted)
+        .map(|note| NoteDump {
+            id: note.id,
+            path: slash_path(&note.file_path),
+            tags: note.tags(),
+            aliases: note.aliases().to_vec(),
+            links: links.remove(&note.id).unwrap_or_default(),
+            title: note.title,
+            created_at: note.created_at,
+            updated_at: note.updated_at,
+            created_by: note.created_by,
+            updated_by: note.updated_by,
+            is_pinned: note.is_pinned,
+            is_archived: note.is_archived,
+            content: note.content,
+        })
+        .collect();
+    notes.sort_by(|a, b| a.path.cmp(&b.path));
+
+    VaultDump {
+        exported_at: Utc::now(),
+        notes,
+    }
+}
+
+/// Write a zip archive of the vault, returning the number of files in it
+pub fn write_zip<W: Write + Seek>(config: &Config, writer: W) -> Result<usize> {
+    let mut zip = ZipWriter::new(writer);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let sources = [
+        PathBuf::from(&config.notes_dir),
+        PathBuf::from(&config.attachments_dir),
+        PathBuf::from(&config.templates_dir),
+        PathBuf::from(".notidium").join("manifest.json"),
+    ];
+    let mut files = Vec::new();
+    for relative in sources {
+        collect_files(&config.vault_path, &relative, &mut files)?;
+    }
+    files.sort();
+
+    for relative in &files {
+        zip.start_file(slash_path(relative), options)
+            .map_err(zip_error)?;
+        let mut file = std::fs::File::open(config.vault_path.join(relative))?;
+        std::io::copy(&mut file, &mut zip)?;
+    }
+    zip.finish().map_err(zip_error)?;
+
+    Ok(files.len())
+}
+
+/// Vault-relative paths of the files at `relative`, skipping hidden entries
+fn collect_files(vault: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let path = vault.join(relative);
+    if path.is_file() {
+        files.push(relative.to_path_buf());
+    } else if path.is_dir() {
+        for entry in std::fs::read_dir(&path)? {
+            let name = entry?.file_name();
+            if !name.to_string_lossy().starts_with('.') {
+                collect_files(vault, &relative.join(name), files)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn zip_error(e: zip::result::ZipError) -> Error {
+    Error::Export(e.to_string())
+}
+
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,521 @@
+//! Single-note export
+//!
+//! A note renders to a standalone HTML page: its markdown body, a print
+//! friendly stylesheet, and the images it embeds from the vault inlined as
+//! data URIs so the file works on its own. ```` ```mermaid ```` blocks and
+//! `$...$`/`$$...$$` math are emitted as containers that Mermaid and KaTeX,
+//! loaded from a CDN only by pages that need them, render in the browser. PDF and DOCX are converted from
+//! that page by whichever external tool is installed: a headless Chromium
+//! or Chrome, or `wkhtmltopdf`, for PDF and `pandoc` for DOCX.
+//!
+//! Annotations on the note appear as margin notes beside the block they are
+//! anchored to, or after the note when they aren't anchored or their anchor
+//! is gone.
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use base64::Engine;
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::lint::attachment_path;
+use crate::store::heading_section;
+use crate::types::{Annotation, Note};
+
+/// Headless browsers tried for PDF output, in order
+const BROWSERS: &[&str] = &[
+    "chromium",
+    "chromium-browser",
+    "google-chrome",
+    "google-chrome-stable",
+    "chrome",
+];
+
+pub(crate) const STYLE: &str = "\
+body { font-family: -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; line-height: 1.6; \
+color: #1f2328; max-width: 46em; margin: 2em auto; padding: 0 1em; }
+h1, h2, h3 { line-height: 1.25; }
+h1 { border-bottom: 1px solid #d0d7de; padding-bottom: .3em; }
+pre, code { font-family: ui-monospace, 'SF Mono', Menlo, Consolas, monospace; font-size: .9em; }
+pre { background: #f6f8fa; padding: 1em; overflow-x: auto; border-radius: 6px; white-space: pre-wrap; }
+code { background: #f6f8fa; padding: .1em .3em; border-radius: 4px; }
+pre code { background: none; padding: 0; }
+blockquote { margin: 0; padding: 0 1em; color: #59636e; border-left: .25em solid #d0d7de; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #d0d7de; padding: .4em .8em; }
+img { max-width: 100%; }
+@media print { body { margin: 0; max-width: none; } pre { page-break-inside: avoid; } }
+";
+
+const ANNOTATION_STYLE: &str = "\
+aside.annotation { float: right; clear: right; width: 14em; margin: 0 -16em 1em 1em; padding: .4em .8em; \
+font-size: .8em; background: #fff8c5; border-left: 3px solid #d4a72c; }
+.annotation p { margin: .3em 0; }
+.annotation-meta { color: #59636e; }
+.annotation-reply { border-top: 1px solid #eac54f; }
+@media (max-width: 80em) { aside.annotation { float: none; width: auto; margin: 1em 0; } }
+@media print { aside.annotation { float: none; width: auto; margin: 1em 0; } }
+";
+
+const MERMAID_SCRIPT: &str = "<script type=\"module\">\n\
+import mermaid from 'https://cdn.jsdelivr.net/npm/mermaid@11/dist/mermaid.esm.min.mjs';\n\
+mermaid.initialize({ startOnLoad: true });\n\
+</script>\n";
+
+const KATEX_SCRIPT: &str = "\
+<link rel=\"stylesheet\" href=\"https://cdn.jsdelivr.net/npm/katex@0.16/dist/katex.min.css\">\n\
+<script src=\"https://cdn.jsdelivr.net/npm/katex@0.16/dist/katex.min.js\"></script>\n\
+<script>\n\
+document.addEventListener('DOMContentLoaded', function () {\n\
+  document.querySelectorAll('.math').forEach(function (el) {\n\
+    katex.render(el.textContent, el, { displayMode: el.classList.contains('math-display'), throwOnError: false });\n\
+  });\n\
+});\n\
+</script>\n";
+
+/// What a single note is exported as
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteFormat {
+    /// Standalone HTML page
+    #[default]
+    Html,
+    /// PDF printed by a headless browser or wkhtmltopdf
+    Pdf,
+    /// Word document converted by pandoc
+    Docx,
+}
+
+impl NoteFormat {
+    /// File extension of an export in this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            NoteFormat::Html => "html",
+            NoteFormat::Pdf => "pdf",
+            NoteFormat::Docx => "docx",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            NoteFormat::Html => "text/html; charset=utf-8",
+            NoteFormat::Pdf => "application/pdf",
+            NoteFormat::Docx => {
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+            }
+        }
+    }
+}
+
+impl FromStr for NoteFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "html" => Ok(NoteFormat::Html),
+            "pdf" => Ok(NoteFormat::Pdf),
+            "docx" => Ok(NoteFormat::Docx),
+            _ => Err(Error::Export(format!(
+                "Unknown note format {:?}, expected html, pdf or docx",
+                s
+            ))),
+        }
+    }
+}
+
+/// Render a note of the vault in `format`, with `annotations` in its margin
+pub async fn render(
+    config: &Config,
+    note: &Note,
+    annotations: &[Annotation],
+    format: NoteFormat,
+) -> Result<Vec<u8>> {
+    let full_path = config.notes_path().join(&note.file_path);
+    let note_dir = full_path.parent().unwrap_or(&config.vault_path);
+    let html = render_html(note, annotations, note_dir, &config.attachments_path());
+    match format {
+        NoteFormat::Html => Ok(html.into_bytes()),
+        NoteFormat::Pdf => convert(&html, "pdf", pdf_commands).await,
+        NoteFormat::Docx => convert(&html, "docx", |input, output| {
+            vec![vec![
+                "pandoc".to_string(),
+                "--from=html".to_string(),
+                format!("--output={}", output.display()),
+                input.display().to_string(),
+            ]]
+        })
+        .await,
+    }
+}
+
+/// Standalone HTML page of a note, with local images inlined and
+/// `annotations` in the margin. Relative image paths resolve against
+/// `note_dir`, `/api/attachments/` ones against `attachments_dir`.
+pub fn render_html(
+    note: &Note,
+    annotations: &[Annotation],
+    note_dir: &Path,
+    attachments_dir: &Path,
+) -> String {
+    let body = strip_frontmatter(&note.content);
+    let mut rendered = String::new();
+    if !has_title_heading(body) {
+        rendered.push_str(&format!("<h1>{}</h1>\n", escape(&note.title)));
+    }
+    let margin = margin_notes(&note.content, body, annotations);
+    rendered.push_str(&render_blocks(
+        body,
+        |dest| data_uri(dest, note_dir, attachments_dir),
+        &margin,
+    ));
+
+    let style = if annotations.is_empty() {
+        STYLE.to_string()
+    } else {
+        format!("{}{}", STYLE, ANNOTATION_STYLE)
+    };
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{}</title>\n<style>\n{}</style>\n{}</head>\n<body>\n<article>\n{}</article>\n</body>\n</html>\n",
+        escape(&note.title),
+        style,
+        scripts(&rendered),
+        rendered
+    )
+}
+
+/// Markdown rendered to HTML, with image sources replaced by `image_src`
+/// where it returns one
+pub(crate) fn render_markdown(
+    markdown: &str,
+    image_src: impl FnMut(&str) -> Option<String>,
+) -> String {
+    render_blocks(markdown, image_src, &[])
+}
+
+/// Margin notes for the annotation threads on a note, each with the offset
+/// into `body` it is anchored at, in order
+///
+/// Threads without an anchor in the note are placed at its end.
+fn margin_notes(content: &str, body: &str, annotations: &[Annotation]) -> Vec<(usize, String)> {
+    let frontmatter = content.len() - body.len();
+    let mut notes: Vec<(usize, String)> = annotations
+        .iter()
+        .filter(|a| a.reply_to.is_none())
+        .map(|thread| {
+            let anchor = match (&thread.heading, thread.line_end) {
+                (Some(heading), _) => heading_section(body, heading)
+                    .map(|section| section.as_ptr() as usize - body.as_ptr() as usize),
+                (None, Some(line)) if (line as usize) <= content.lines().count() => Some(
+                    content
+                        .split_inclusive('\n')
+                        .take(line as usize - 1)
+                        .map(str::len)
+                        .sum::<usize>()
+                        .saturating_sub(frontmatter),
+                ),
+                _ => None,
+            };
+            let replies = annotations
+                .iter()
+                .filter(|a| a.reply_to.as_deref() == Some(thread.id.as_str()));
+            let mut html = String::from("<aside class=\"annotation\">\n");
+            html.push_str(&annotation_html(thread));
+            for reply in replies {
+                html.push_str("<div class=\"annotation-reply\">\n");
+                html.push_str(&annotation_html(reply));
+                html.push_str("</div>\n");
+            }
+            html.push_str("</aside>\n");
+            (anchor.unwrap_or(usize::MAX), html)
+        })
+        .collect();
+    notes.sort_by_key(|(offset, _)| *offset);
+    notes
+}
+
+fn annotation_html(annotation: &Annotation) -> String {
+    let date = annotation.created_at.get(..10).unwrap_or(&annotation.created_at);
+    let meta = match &annotation.author {
+        Some(author) => format!("{} · {}", escape(author), date),
+        None => date.to_string(),
+    };
+    format!(
+        "<p class=\"annotation-meta\">{}</p>\n<p>{}</p>\n",
+        meta,
+        escape(annotation.body.trim()).replace('\n', "<br>\n")
+    )
+}
+
+/// Markdown rendered to HTML, with each of `margin` inserted after the
+/// top-level block its offset falls in
+fn render_blocks(
+    markdown: &str,
+    mut image_src: impl FnMut(&str) -> Option<String>,
+    margin: &[(usize, String)],
+) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_MATH;
+    let mut diagram: Option<String> = None;
+    let mut margin = margin.iter().peekable();
+    let mut depth = 0usize;
+    let mut events = Vec::new();
+    for (event, range) in Parser::new_ext(markdown, options).into_offset_iter() {
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => {}
+        }
+        let block_done = depth == 0 && !matches!(event, Event::Start(_));
+        events.extend(rewrite_event(event, &mut diagram, &mut image_src));
+        if block_done {
+            while let Some((_, html)) = margin.next_if(|(offset, _)| *offset < range.end) {
+                events.push(Event::Html(html.as_str().into()));
+            }
+        }
+    }
+    events.extend(margin.map(|(_, html)| Event::Html(html.as_str().into())));
+
+    let mut rendered = String::new();
+    html::push_html(&mut rendered, events.into_iter());
+    rendered
+}
+
+/// A parser event with mermaid blocks collected into `diagram` and image
+/// sources swapped, `None` for events swallowed into a diagram
+fn rewrite_event<'a>(
+    event: Event<'a>,
+    diagram: &mut Option<String>,
+    image_src: &mut impl FnMut(&str) -> Option<String>,
+) -> Option<Event<'a>> {
+    match event {
+        Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref lang)))
+            if lang.split_whitespace().next() == Some("mermaid") =>
+        {
+            *diagram = Some(String::new());
+            None
+        }
+        Event::Text(ref text) if diagram.is_some() => {
+            diagram.as_mut().unwrap().push_str(text);
+            None
+        }
+        Event::End(TagEnd::CodeBlock) if diagram.is_some() => {
+            let source = diagram.take().unwrap_or_default();
+            Some(Event::Html(
+                format!("<pre class=\"mermaid\">{}</pre>\n", escape(&source)).into(),
+            ))
+        }
+        Event::Start(Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => {
+            let dest_url = image_src(&dest_url).map(CowStr::from).unwrap_or(dest_url);
+            Some(Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }))
+        }
+        event => Some(event),
+    }
+}
+
+/// Scripts rendering the diagrams and math in a page's HTML, for its `<head>`
+pub(crate) fn scripts(html: &str) -> String {
+    let mut scripts = String::new();
+    if html.contains("<pre class=\"mermaid\">") {
+        scripts.push_str(MERMAID_SCRIPT);
+    }
+    if html.contains("<span class=\"math math-") {
+        scripts.push_str(KATEX_SCRIPT);
+    }
+    scripts
+}
+
+/// Whether a note body opens with its title as a heading
+pub(crate) fn has_title_heading(body: &str) -> bool {
+    body.trim_start().starts_with("# ")
+}
+
+/// Note content without its frontmatter
+pub(crate) fn strip_frontmatter(content: &str) -> &str {
+    if !content.starts_with("---\n") {
+        return content;
+    }
+    match content[3..].find("\n---") {
+        Some(end) => {
+            let block_end = 3 + end + 4;
+            content[block_end..]
+                .find('\n')
+                .map_or("", |i| &content[block_end + i + 1..])
+        }
+        None => content,
+    }
+}
+
+/// An image embedded as a data URI, if it's a readable local file
+pub(crate) fn data_uri(dest: &str, note_dir: &Path, attachments_dir: &Path) -> Option<String> {
+    let path = attachment_path(dest, note_dir, attachments_dir)?;
+    let data = std::fs::read(&path).ok()?;
+    let mime = mime_guess::from_path(&path).first_or_octet_stream();
+    Some(format!(
+        "data:{};base64,{}",
+        mime,
+        base64::engine::general_purpose::STANDARD.encode(data)
+    ))
+}
+
+/// Commands that print `input` to the PDF `output`, in order of preference
+fn pdf_commands(input: &Path, output: &Path) -> Vec<Vec<String>> {
+    let mut commands: Vec<Vec<String>> = BROWSERS
+        .iter()
+        .map(|browser| {
+            vec![
+                browser.to_string(),
+                "--headless".to_string(),
+                "--disable-gpu".to_string(),
+                "--no-pdf-header-footer".to_string(),
+                // Let Mermaid and KaTeX render before printing
+                "--virtual-time-budget=10000".to_string(),
+                format!("--print-to-pdf={}", output.display()),
+                format!("file://{}", input.display()),
+            ]
+        })
+        .collect();
+    commands.push(vec![
+        "wkhtmltopdf".to_string(),
+        "--quiet".to_string(),
+        "--enable-local-file-access".to_string(),
+        "--javascript-delay".to_string(),
+        "2000".to_string(),
+        input.display().to_string(),
+        output.display().to_string(),
+    ]);
+    commands
+}
+
+/// Convert an HTML page with the first of `commands` that's installed
+async fn convert(
+    html: &str,
+    extension: &str,
+    commands: impl Fn(&Path, &Path) -> Vec<Vec<String>>,
+) -> Result<Vec<u8>> {
+    let dir = ScratchDir::new()?;
+    let input = dir.0.join("note.html");
+    let output = dir.0.join(format!("note.{}", extension));
+    tokio::fs::write(&input, html).await?;
+
+    let mut tried = Vec::new();
+    for command in commands(&input, &output) {
+        let (program, args) = command.split_first().expect("command has a program");
+        let result = tokio::process::Command::new(program)
+            .args(args)
+            .stdin(std::process::Stdio::null())
+            .output()
+            .await;
+        match result {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => tried.push(program.clone()),
+            Err(e) => return Err(Error::Export(format!("Failed to run {}: {}", program, e))),
+            Ok(out) if out.status.success() && output.exists() => {
+                return Ok(tokio::fs::read(&output).await?);
+            }
+            Ok(out) => {
+                return Err(Error::Export(format!(
+                    "{} failed: {}",
+                    program,
+                    String::from_utf8_lossy(&out.stderr).trim()
+                )))
+            }
+        }
+    }
+    Err(Error::Export(format!(
+        "{} export needs one of these installed: {}",
+        extension.to_uppercase(),
+        tried.join(", ")
+    )))
+}
+
+/// Temporary directory removed when dropped
+struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    fn new() -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!("notidium-export-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self(dir))
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(title: &str, content: &str) -> Note {
+        Note::new(title.to_string(), content.to_string(), PathBuf::from("note.md"))
+    }
+
+    #[test]
+    fn test_strip_frontmatter() {
+        assert_eq!(strip_frontmatter("---\ntags: [a]\n---\n# T\n"), "# T\n");
+        assert_eq!(strip_frontmatter("# T\n---\n"), "# T\n---\n");
+    }
+
+    #[test]
+    fn test_render_html_adds_missing_title() {
+        let html = render_html(&note("A & B", "Just text"), &[], Path::new("/n"), Path::new("/a"));
+        assert!(html.contains("<title>A &amp; B</title>"));
+        assert!(html.contains("<h1>A &amp; B</h1>"));
+
+        let html = render_html(&note("T", "# T\n\nText"), &[], Path::new("/n"), Path::new("/a"));
+        assert_eq!(html.matches("<h1>").count(), 1);
+    }
+
+    #[test]
+    fn test_render_diagrams_and_math() {
+        let content = "Euler: $e^{i\\pi} + 1 = 0$\n\n$$\\sum x < y$$\n\n```mermaid\ngraph TD\n  A-->B\n```\n\n```rust\nfn main() {}\n```";
+        let html = render_html(&note("T", content), &[], Path::new("/n"), Path::new("/a"));
+        assert!(html.contains("<span class=\"math math-inline\">e^{i\\pi} + 1 = 0</span>"));
+        assert!(html.contains("<span class=\"math math-display\">\\sum x &lt; y</span>"));
+        assert!(html.contains("<pre class=\"mermaid\">graph TD\n  A--&gt;B\n</pre>"));
+        assert!(html.contains("<code class=\"language-rust\">"));
+        assert!(html.contains("mermaid.initialize"));
+        assert!(html.contains("katex.render"));
+
+        let plain = render_html(&note("T", "No $ signs here"), &[], Path::new("/n"), Path::new("/a"));
+        assert!(!plain.contains("<script"));
+    }
+
+    #[test]
+    fn test_render_html_inlines_images() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("dot.png"), b"png").unwrap();
+        let content = "![dot](/api/attachments/dot.png) ![gone](/api/attachments/x.png) ![web](https://e.com/i.png)";
+        let html = render_html(&note("T", content), &[], dir.path(), dir.path());
+        assert!(html.contains("src=\"data:image/png;base64,cG5n\""));
+        assert!(html.contains("src=\"/api/attachments/x.png\""));
+        assert!(html.contains("src=\"https://e.com/i.png\""));
+    }
+}
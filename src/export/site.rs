@@ -0,0 +1,515 @@
+//! Static site publishing
+//!
+//! `notidium publish` turns the notes carrying some tags into a static site
+//! that can be served from anywhere: an index, a page per tag and per note,
+//! an RSS feed and client-side search over a prebuilt index. Published notes
+//! embedded with `![[...]]` are inlined. Wiki links between published notes
+//! become links between their pages; links to notes that aren't published
+//! are left as plain text, so nothing private leaks beyond its title. Images the notes embed are copied into the site.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::PathBuf;
+
+use pulldown_cmark::{Event, Parser, TagEnd};
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::note::{escape, has_title_heading, render_markdown, scripts, strip_frontmatter, STYLE};
+use crate::error::Result;
+use crate::lint::attachment_path;
+use crate::store::NoteStore;
+use crate::types::{Link, Note};
+
+/// Most recent notes listed in the RSS feed
+const FEED_ITEMS: usize = 50;
+
+/// Length of the excerpts on index pages and in the feed, in characters
+const EXCERPT_CHARS: usize = 200;
+
+const SITE_STYLE: &str = "\
+header { margin-bottom: 2em; font-weight: 600; }
+header a { color: inherit; text-decoration: none; }
+.meta { color: #59636e; font-size: .9em; }
+.tags a { margin-right: .6em; }
+ul.notes { list-style: none; padding: 0; }
+ul.notes li { margin-bottom: 1.2em; }
+ul.notes p { margin: .2em 0; }
+#search { width: 100%; box-sizing: border-box; padding: .5em; font-size: 1em; margin-bottom: 1.5em; }
+@media (prefers-color-scheme: dark) {
+  body { background: #0d1117; color: #e6edf3; }
+  a { color: #58a6ff; }
+  pre, code { background: #161b22; }
+  .meta, blockquote { color: #9198a1; }
+}
+";
+
+const SEARCH_SCRIPT: &str = r#"(function () {
+  var input = document.getElementById('search');
+  var results = document.getElementById('results');
+  var notes = document.getElementById('notes');
+  var index = null;
+  fetch('search-index.json')
+    .then(function (response) { return response.json(); })
+    .then(function (data) { index = data; });
+
+  function tokens(text) {
+    return text.toLowerCase().split(/[^\p{L}\p{N}]+/u).filter(function (t) { return t.length > 1; });
+  }
+
+  input.addEventListener('input', function () {
+    var query = tokens(input.value);
+    if (!index || query.length === 0) {
+      results.hidden = true;
+      notes.hidden = false;
+      return;
+    }
+    var terms = Object.keys(index.terms);
+    var matches = null;
+    query.forEach(function (token) {
+      var docs = new Set();
+      terms.forEach(function (term) {
+        if (term.startsWith(token)) {
+          index.terms[term].forEach(function (doc) { docs.add(doc); });
+        }
+      });
+      matches = matches === null ? docs : new Set(Array.from(matches).filter(function (doc) { return docs.has(doc); }));
+    });
+
+    results.innerHTML = '';
+    matches.forEach(function (doc) {
+      var item = document.createElement('li');
+      var link = document.createElement('a');
+      link.href = index.docs[doc].url;
+      link.textContent = index.docs[doc].title;
+      item.appendChild(link);
+      results.appendChild(item);
+    });
+    if (matches.size === 0) {
+      results.innerHTML = '<li>No matching notes</li>';
+    }
+    results.hidden = false;
+    notes.hidden = true;
+  });
+})();
+"#;
+
+/// What to publish and where
+#[derive(Debug, Clone)]
+pub struct PublishOptions {
+    /// Notes with any of these tags are published
+    pub tags: Vec<String>,
+    /// Directory the site is written to
+    pub out: PathBuf,
+    /// Site name shown on every page and in the feed
+    pub title: String,
+    /// URL the site will be served from; without it the feed links are relative
+    pub base_url: Option<String>,
+}
+
+/// What a publish run wrote
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PublishSummary {
+    pub notes: usize,
+    pub tags: usize,
+    pub attachments: usize,
+}
+
+/// A published note
+struct Entry<'a> {
+    note: &'a Note,
+    /// Page file name under `notes/`
+    file: String,
+    excerpt: String,
+    text: String,
+}
+
+/// Prebuilt index for the site's search box
+#[derive(Serialize)]
+struct SearchIndex {
+    docs: Vec<SearchDoc>,
+    /// Lowercased word -> indexes into `docs`
+    terms: BTreeMap<String, BTreeSet<usize>>,
+}
+
+#[derive(Serialize)]
+struct SearchDoc {
+    title: String,
+    url: String,
+}
+
+/// Write the site for the live notes of `store` carrying any of the
+/// selected tags
+///
+/// Files already in the output directory are overwritten but never removed.
+pub async fn publish(store: &NoteStore, options: &PublishOptions) -> Result<PublishSummary> {
+    let selected: HashSet<String> = options.tags.iter().map(|t| t.to_lowercase()).collect();
+    let mut notes: Vec<Note> = store
+        .list()
+        .await
+        .into_iter()
+        .filter(|n| !n.is_deleted && n.tags().iter().any(|t| selected.contains(&t.to_lowercase())))
+        .collect();
+    notes.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then_with(|| a.title.cmp(&b.title)));
+
+    let mut files: HashMap<Uuid, String> = HashMap::new();
+    let mut taken: HashSet<String> = HashSet::new();
+    for note in &notes {
+        files.insert(note.id, unique_file(&note.slug, &mut taken));
+    }
+
+    let out = &options.out;
+    for dir in ["notes", "tags", "attachments"] {
+        std::fs::create_dir_all(out.join(dir))?;
+    }
+
+    let config = store.config();
+    let attachments_dir = config.attachments_path();
+    let mut copied: HashSet<PathBuf> = HashSet::new();
+    let mut entries = Vec::with_capacity(notes.len());
+    for note in &notes {
+        // Links are found after inlining so embedded text gets its links
+        // rewritten too
+        let content = store
+            .transclude(note.id, &note.content, |n| files.contains_key(&n.id))
+            .await;
+        let links = store.resolve_links(note.id, &content).await;
+        let content = rewrite_wiki_links(&content, &links, |id| files.get(&id).map(String::as_str));
+        let body = strip_frontmatter(&content);
+        let full_path = config.notes_path().join(&note.file_path);
+        let note_dir = full_path.parent().unwrap_or(&config.vault_path);
+
+        let mut copy_error = None;
+        let html = render_markdown(body, |dest| {
+            let source = attachment_path(dest, note_dir, &attachments_dir).filter(|p| p.is_file())?;
+            let name = source.file_name()?.to_str()?.to_string();
+            if copied.insert(source.clone()) {
+                if let Err(e) = std::fs::copy(&source, out.join("attachments").join(&name)) {
+                    copy_error.get_or_insert(e);
+                }
+            }
+            Some(format!("../attachments/{}", name.replace(' ', "%20")))
+        });
+        if let Some(e) = copy_error {
+            return Err(e.into());
+        }
+
+        let text = plain_text(body, has_title_heading(body));
+        let entry = Entry {
+            note,
+            file: files[&note.id].clone(),
+            excerpt: excerpt(&text),
+            text,
+        };
+        write_note_page(options, &entry, &html)?;
+        entries.push(entry);
+    }
+
+    let mut tags: BTreeMap<String, (String, Vec<&Entry>)> = BTreeMap::new();
+    for entry in &entries {
+        for tag in entry.note.tags() {
+            tags.entry(tag.to_lowercase())
+                .or_insert_with(|| (tag.clone(), Vec::new()))
+                .1
+                .push(entry);
+        }
+    }
+    for (tag, tagged) in tags.values() {
+        let content = format!(
+            "<h1>#{}</h1>\n{}",
+            escape(tag),
+            note_list(tagged, "../notes/")
+        );
+        let html = page(&options.title, &format!("#{}", tag), "../", &content);
+        std::fs::write(out.join("tags").join(tag_file(tag)), html)?;
+    }
+
+    write_index(options, &entries, &tags)?;
+    std::fs::write(out.join("feed.xml"), feed(options, &entries))?;
+    std::fs::write(out.join("search-index.json"), serde_json::to_vec(&search_index(&entries))?)?;
+    std::fs::write(out.join("search.js"), SEARCH_SCRIPT)?;
+    std::fs::write(out.join("style.css"), format!("{}{}", STYLE, SITE_STYLE))?;
+
+    Ok(PublishSummary {
+        notes: entries.len(),
+        tags: tags.len(),
+        attachments: copied.len(),
+    })
+}
+
+/// Replace the wiki links among `links` (found in `content`) with markdown
+/// links to the pages of their targets, or with their text when the target
+/// has no page. Embeds (`![[...]]`) are left alone.
+pub(super) fn rewrite_wiki_links<'a>(
+    content: &str,
+    links: &[Link],
+    page: impl Fn(Uuid) -> Option<&'a str>,
+) -> String {
+    let mut links: Vec<&Link> = links.iter().collect();
+    links.sort_by_key(|l| l.position);
+
+    let mut rewritten = String::with_capacity(content.len());
+    let mut cursor = 0;
+    for link in links {
+        let start = link.position as usize;
+        if start < cursor || !content[start..].starts_with("[[") || content[..start].ends_with('!') {
+            continue;
+        }
+        let Some(len) = content[start + 2..].find("]]") else {
+            continue;
+        };
+        let inner = &content[start + 2..start + 2 + len];
+        let text = inner.split_once('|').map_or(inner, |(_, alias)| alias).trim();
+
+        rewritten.push_str(&content[cursor..start]);
+        match link.target_note_id.and_then(&page) {
+            Some(file) => rewritten.push_str(&format!("[{}]({})", text, file)),
+            None => rewritten.push_str(text),
+        }
+        cursor = start + 2 + len + 2;
+    }
+    rewritten.push_str(&content[cursor..]);
+    rewritten
+}
+
+/// A page file name for `slug` not in `taken`
+fn unique_file(slug: &str, taken: &mut HashSet<String>) -> String {
+    let stem = if slug.is_empty() { "note" } else { slug };
+    let mut file = format!("{}.html", stem);
+    let mut n = 2;
+    while !taken.insert(file.clone()) {
+        file = format!("{}-{}.html", stem, n);
+        n += 1;
+    }
+    file
+}
+
+fn tag_file(tag: &str) -> String {
+    let slug = slug::slugify(tag);
+    format!("{}.html", if slug.is_empty() { "tag" } else { &slug })
+}
+
+/// Text of a markdown body, without the title heading when `skip_title`
+pub(super) fn plain_text(markdown: &str, skip_title: bool) -> String {
+    let mut text = String::new();
+    let mut in_title = false;
+    let mut seen_block = false;
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(pulldown_cmark::Tag::Heading { .. }) if skip_title && !seen_block => {
+                in_title = true;
+            }
+            Event::End(TagEnd::Heading(_)) if in_title => {
+                in_title = false;
+                seen_block = true;
+            }
+            Event::Text(t) | Event::Code(t) if !in_title => {
+                text.push_str(&t);
+                seen_block = true;
+            }
+            Event::SoftBreak | Event::HardBreak | Event::End(_) => text.push(' '),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn excerpt(text: &str) -> String {
+    match text.char_indices().nth(EXCERPT_CHARS) {
+        Some((i, _)) => format!("{}…", text[..i].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+fn date(note: &Note) -> String {
+    note.updated_at.format("%Y-%m-%d").to_string()
+}
+
+fn write_note_page(options: &PublishOptions, entry: &Entry, html: &str) -> Result<()> {
+    let note = entry.note;
+    let mut content = String::new();
+    if !has_title_heading(strip_frontmatter(&note.content)) {
+        content.push_str(&format!("<h1>{}</h1>\n", escape(&note.title)));
+    }
+    content.push_str(html);
+    content.push_str(&format!(
+        "<p class=\"meta\">Updated {}{}</p>\n",
+        date(note),
+        tag_links(&note.tags(), "../tags/")
+    ));
+
+    let page = page(&options.title, &note.title, "../", &format!("<article>\n{}</article>\n", content));
+    std::fs::write(options.out.join("notes").join(&entry.file), page)?;
+    Ok(())
+}
+
+fn write_index(
+    options: &PublishOptions,
+    entries: &[Entry],
+    tags: &BTreeMap<String, (String, Vec<&Entry>)>,
+) -> Result<()> {
+    let tag_names: Vec<String> = tags.values().map(|(tag, _)| tag.clone()).collect();
+    let mut content = format!("<h1>{}</h1>\n", escape(&options.title));
+    if !tag_names.is_empty() {
+        content.push_str(&format!("<p class=\"tags\">{}</p>\n", tag_links(&tag_names, "tags/").trim_start_matches(" · ")));
+    }
+    content.push_str("<input id=\"search\" type=\"search\" placeholder=\"Search notes\" autocomplete=\"off\">\n");
+    content.push_str("<ul id=\"results\" class=\"notes\" hidden></ul>\n");
+    content.push_str(&note_list(&entries.iter().collect::<Vec<_>>(), "notes/").replacen("<ul class=\"notes\">", "<ul id=\"notes\" class=\"notes\">", 1));
+    content.push_str("<script src=\"search.js\"></script>\n");
+
+    std::fs::write(options.out.join("index.html"), page(&options.title, &options.title, "", &content))?;
+    Ok(())
+}
+
+/// Links to tag pages under `dir`, each preceded by a separator
+fn tag_links(tags: &[String], dir: &str) -> String {
+    tags.iter()
+        .map(|tag| format!(" · <a href=\"{}{}\">#{}</a>", dir, tag_file(tag), escape(tag)))
+        .collect()
+}
+
+fn note_list(entries: &[&Entry], dir: &str) -> String {
+    let mut list = String::from("<ul class=\"notes\">\n");
+    for entry in entries {
+        list.push_str(&format!(
+            "<li><a href=\"{}{}\">{}</a> <span class=\"meta\">{}</span><p>{}</p></li>\n",
+            dir,
+            entry.file,
+            escape(&entry.note.title),
+            date(entry.note),
+            escape(&entry.excerpt)
+        ));
+    }
+    list.push_str("</ul>\n");
+    list
+}
+
+/// A page of the site; `root` leads from the page back to the site root
+fn page(site_title: &str, title: &str, root: &str, content: &str) -> String {
+    let title = if title == site_title {
+        escape(title)
+    } else {
+        format!("{} · {}", escape(title), escape(site_title))
+    };
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{title}</title>\n\
+         <link rel=\"stylesheet\" href=\"{root}style.css\">\n\
+         <link rel=\"alternate\" type=\"application/rss+xml\" title=\"{site}\" href=\"{root}feed.xml\">\n\
+         {scripts}</head>\n<body>\n<header><a href=\"{root}index.html\">{site}</a></header>\n<main>\n{content}</main>\n</body>\n</html>\n",
+        title = title,
+        root = root,
+        site = escape(site_title),
+        scripts = scripts(content),
+        content = content
+    )
+}
+
+/// RSS 2.0 feed of the most recently updated notes
+fn feed(options: &PublishOptions, entries: &[Entry]) -> String {
+    let base = options
+        .base_url
+        .as_deref()
+        .map(|url| format!("{}/", url.trim_end_matches('/')))
+        .unwrap_or_default();
+    let mut items = String::new();
+    for entry in entries.iter().take(FEED_ITEMS) {
+        items.push_str(&format!(
+            "<item>\n<title>{}</title>\n<link>{}notes/{}</link>\n\
+             <guid isPermaLink=\"false\">{}</guid>\n<pubDate>{}</pubDate>\n\
+             <description>{}</description>\n</item>\n",
+            escape(&entry.note.title),
+            escape(&base),
+            entry.file,
+            entry.note.id,
+            entry.note.updated_at.to_rfc2822(),
+            escape(&entry.excerpt)
+        ));
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\">\n<channel>\n\
+         <title>{title}</title>\n<link>{link}</link>\n<description>{title}</description>\n{items}</channel>\n</rss>\n",
+        title = escape(&options.title),
+        link = if base.is_empty() { "index.html".to_string() } else { escape(&base) },
+        items = items
+    )
+}
+
+fn search_index(entries: &[Entry]) -> SearchIndex {
+    let mut index = SearchIndex {
+        docs: Vec::with_capacity(entries.len()),
+        terms: BTreeMap::new(),
+    };
+    for (i, entry) in entries.iter().enumerate() {
+        index.docs.push(SearchDoc {
+            title: entry.note.title.clone(),
+            url: format!("notes/{}", entry.file),
+        });
+        let text = format!("{} {} {}", entry.note.title, entry.note.tags().join(" "), entry.text);
+        for term in terms(&text) {
+            index.terms.entry(term).or_default().insert(i);
+        }
+    }
+    index
+}
+
+/// Lowercased words of at least two characters
+pub(super) fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 1)
+        .map(str::to_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::LinkType;
+
+    fn wiki_link(position: usize, target: &str, to: Option<Uuid>) -> Link {
+        Link {
+            id: Uuid::new_v4(),
+            source_note_id: Uuid::nil(),
+            target_note_id: to,
+            target_raw: target.to_string(),
+            link_type: LinkType::WikiLink,
+            position: position as u32,
+            relation: None,
+        }
+    }
+
+    #[test]
+    fn test_rewrite_wiki_links() {
+        let public = Uuid::new_v4();
+        let private = Uuid::new_v4();
+        let content = "See [[Public|the garden]], [[Private]] and ![[Public]].";
+        let links = vec![
+            wiki_link(content.find("[[Public").unwrap(), "Public", Some(public)),
+            wiki_link(content.find("[[Private").unwrap(), "Private", Some(private)),
+            wiki_link(content.find("![[").unwrap() + 1, "Public", Some(public)),
+        ];
+        let rewritten = rewrite_wiki_links(content, &links, |id| (id == public).then_some("public.html"));
+        assert_eq!(rewritten, "See [the garden](public.html), Private and ![[Public]].");
+    }
+
+    #[test]
+    fn test_unique_file() {
+        let mut taken = HashSet::new();
+        assert_eq!(unique_file("ideas", &mut taken), "ideas.html");
+        assert_eq!(unique_file("ideas", &mut taken), "ideas-2.html");
+        assert_eq!(unique_file("", &mut taken), "note.html");
+    }
+
+    #[test]
+    fn test_plain_text_and_excerpt() {
+        let text = plain_text("# Title\n\nSome *emphasis* and `code`.\n\n- item", true);
+        assert_eq!(text, "Some emphasis and code. item");
+        assert_eq!(excerpt(&"word ".repeat(100)).chars().count(), EXCERPT_CHARS);
+    }
+
+    #[test]
+    fn test_terms() {
+        let terms: Vec<String> = terms("Rust's ownership, a Café!").collect();
+        assert_eq!(terms, ["rust", "ownership", "café"]);
+    }
+}
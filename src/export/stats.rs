@@ -0,0 +1,257 @@
+//! Per-note statistics
+//!
+//! One row per note with its size, links, tags, timestamps and how often it
+//! was opened, as CSV or Parquet for loading into a dataframe. Tags are a
+//! single `;`-separated column so both formats share the same flat schema.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, RecordBatch, StringArray, TimestampMillisecondArray, UInt32Array};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chrono::{DateTime, Utc};
+use parquet::arrow::ArrowWriter;
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::note::strip_frontmatter;
+use super::slash_path;
+use crate::error::{Error, Result};
+use crate::store::NoteStore;
+
+/// File format of a statistics export
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StatsFormat {
+    #[default]
+    Csv,
+    Parquet,
+}
+
+impl StatsFormat {
+    /// File extension of an export in this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            StatsFormat::Csv => "csv",
+            StatsFormat::Parquet => "parquet",
+        }
+    }
+}
+
+impl FromStr for StatsFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "csv" => Ok(StatsFormat::Csv),
+            "parquet" => Ok(StatsFormat::Parquet),
+            _ => Err(Error::Export(format!(
+                "Unknown statistics format {:?}, expected csv or parquet",
+                s
+            ))),
+        }
+    }
+}
+
+/// Metrics of one note
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NoteStats {
+    pub id: Uuid,
+    pub title: String,
+    /// Path relative to the notes directory
+    pub path: String,
+    /// Words in the body, frontmatter excluded
+    pub words: u32,
+    /// Links and relations from this note
+    pub links: u32,
+    /// Links and relations to this note from other notes
+    pub backlinks: u32,
+    /// Tags, separated by `;`
+    pub tags: String,
+    pub tag_count: u32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Times the note was opened while the access log kept it
+    pub accesses: u32,
+}
+
+/// Statistics of every live note, sorted by path
+pub async fn collect(store: &NoteStore) -> Result<Vec<NoteStats>> {
+    let mut outgoing: HashMap<Uuid, u32> = HashMap::new();
+    let mut incoming: HashMap<Uuid, u32> = HashMap::new();
+    for link in store.links().await {
+        *outgoing.entry(link.source_note_id).or_default() += 1;
+        if let Some(target) = link.target_note_id.filter(|t| *t != link.source_note_id) {
+            *incoming.entry(target).or_default() += 1;
+        }
+    }
+    let accesses = store.access_counts().await?;
+
+    let mut rows: Vec<NoteStats> = store
+        .list()
+        .await
+        .into_iter()
+        .filter(|n| !n.is_deleted)
+        .map(|note| {
+            let tags = note.tags();
+            NoteStats {
+                id: note.id,
+                title: note.title.clone(),
+                path: slash_path(&note.file_path),
+                words: strip_frontmatter(&note.content).split_whitespace().count() as u32,
+                links: outgoing.get(&note.id).copied().unwrap_or(0),
+                backlinks: incoming.get(&note.id).copied().unwrap_or(0),
+                tag_count: tags.len() as u32,
+                tags: tags.join(";"),
+                created_at: note.created_at,
+                updated_at: note.updated_at,
+                accesses: accesses.get(&note.id).copied().unwrap_or(0),
+            }
+        })
+        .collect();
+    rows.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(rows)
+}
+
+/// Write `rows` to `out` in `format`
+pub fn write<W: Write + Send>(rows: &[NoteStats], format: StatsFormat, out: W) -> Result<()> {
+    match format {
+        StatsFormat::Csv => write_csv(rows, out),
+        StatsFormat::Parquet => write_parquet(rows, out),
+    }
+}
+
+fn write_csv<W: Write>(rows: &[NoteStats], out: W) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    for row in rows {
+        writer.serialize(row).map_err(export_error)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_parquet<W: Write + Send>(rows: &[NoteStats], out: W) -> Result<()> {
+    let timestamp = DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into()));
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("title", DataType::Utf8, false),
+        Field::new("path", DataType::Utf8, false),
+        Field::new("words", DataType::UInt32, false),
+        Field::new("links", DataType::UInt32, false),
+        Field::new("backlinks", DataType::UInt32, false),
+        Field::new("tags", DataType::Utf8, false),
+        Field::new("tag_count", DataType::UInt32, false),
+        Field::new("created_at", timestamp.clone(), false),
+        Field::new("updated_at", timestamp, false),
+        Field::new("accesses", DataType::UInt32, false),
+    ]));
+
+    let strings = |f: fn(&NoteStats) -> String| -> ArrayRef {
+        Arc::new(StringArray::from_iter_values(rows.iter().map(f)))
+    };
+    let counts = |f: fn(&NoteStats) -> u32| -> ArrayRef {
+        Arc::new(UInt32Array::from_iter_values(rows.iter().map(f)))
+    };
+    let times = |f: fn(&NoteStats) -> DateTime<Utc>| -> ArrayRef {
+        Arc::new(
+            TimestampMillisecondArray::from_iter_values(rows.iter().map(|r| f(r).timestamp_millis()))
+                .with_timezone("UTC"),
+        )
+    };
+
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            strings(|r| r.id.to_string()),
+            strings(|r| r.title.clone()),
+            strings(|r| r.path.clone()),
+            counts(|r| r.words),
+            counts(|r| r.links),
+            counts(|r| r.backlinks),
+            strings(|r| r.tags.clone()),
+            counts(|r| r.tag_count),
+            times(|r| r.created_at),
+            times(|r| r.updated_at),
+            counts(|r| r.accesses),
+        ],
+    )
+    .map_err(export_error)?;
+
+    let mut writer = ArrowWriter::try_new(out, schema, None).map_err(export_error)?;
+    writer.write(&batch).map_err(export_error)?;
+    writer.into_inner().map_err(export_error)?.flush()?;
+    Ok(())
+}
+
+fn export_error(e: impl std::fmt::Display) -> Error {
+    Error::Export(format!("Failed to write statistics: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    fn row(title: &str, tags: &str) -> NoteStats {
+        let at = DateTime::parse_from_rfc3339("2026-01-02T03:04:05Z").unwrap().with_timezone(&Utc);
+        NoteStats {
+            id: Uuid::from_u128(1),
+            title: title.to_string(),
+            path: format!("{}.md", title.to_lowercase()),
+            words: 12,
+            links: 2,
+            backlinks: 1,
+            tags: tags.to_string(),
+            tag_count: tags.split(';').filter(|t| !t.is_empty()).count() as u32,
+            created_at: at,
+            updated_at: at,
+            accesses: 3,
+        }
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!("csv".parse::<StatsFormat>().unwrap(), StatsFormat::Csv);
+        assert_eq!("parquet".parse::<StatsFormat>().unwrap(), StatsFormat::Parquet);
+        assert!("xlsx".parse::<StatsFormat>().is_err());
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let mut out = Vec::new();
+        write(&[row("Rust, Notes", "rust;notes")], StatsFormat::Csv, &mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("id,title,path,words,links,backlinks,tags,tag_count,created_at,updated_at,accesses")
+        );
+        assert_eq!(
+            lines.next(),
+            Some(concat!(
+                "00000000-0000-0000-0000-000000000001,\"Rust, Notes\",\"rust, notes.md\",",
+                "12,2,1,rust;notes,2,2026-01-02T03:04:05Z,2026-01-02T03:04:05Z,3"
+            ))
+        );
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn test_write_parquet() {
+        let file = tempfile::tempfile().unwrap();
+        write(&[row("A", ""), row("B", "x")], StatsFormat::Parquet, &file).unwrap();
+        let reader = SerializedFileReader::new(file).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.file_metadata().num_rows(), 2);
+        let columns: Vec<_> = metadata
+            .file_metadata()
+            .schema_descr()
+            .columns()
+            .iter()
+            .map(|c| c.name().to_string())
+            .collect();
+        assert_eq!(columns[0], "id");
+        assert_eq!(columns.len(), 11);
+    }
+}
@@ -0,0 +1,397 @@
+//! Markdown normalization on save
+//!
+//! A light formatter rather than a full re-render: the note is parsed to
+//! find its headings, list items, code fences and tables, and only those
+//! lines are rewritten, so everything else keeps the author's spelling.
+//! Frontmatter and the contents of code blocks are never changed, and
+//! formatting a formatted note changes nothing.
+
+use std::collections::HashSet;
+
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag};
+
+use crate::config::FormatConfig;
+
+/// Languages written by a common alias, and their canonical name
+const LANGUAGE_ALIASES: &[(&str, &str)] = &[
+    ("c++", "cpp"),
+    ("console", "shell"),
+    ("golang", "go"),
+    ("js", "javascript"),
+    ("jsx", "javascript"),
+    ("md", "markdown"),
+    ("py", "python"),
+    ("rb", "ruby"),
+    ("rs", "rust"),
+    ("sh", "bash"),
+    ("ts", "typescript"),
+    ("yml", "yaml"),
+    ("zsh", "bash"),
+];
+
+/// Apply the enabled `[format]` rules to a note's content
+pub fn format_markdown(content: &str, config: &FormatConfig) -> String {
+    let body_start = frontmatter_end(content);
+    let (frontmatter, body) = content.split_at(body_start);
+    let structure = Structure::parse(body);
+
+    let mut body = body.to_string();
+    if config.lists {
+        // Markers keep their width, so list continuation lines stay aligned
+        for &offset in structure.list_markers.iter().rev() {
+            replace_marker(&mut body, offset, config.list_marker);
+        }
+    }
+
+    let mut lines: Vec<String> = body.split('\n').map(str::to_string).collect();
+    if config.fences {
+        for &line in &structure.fences {
+            lines[line] = normalize_fence(&lines[line]);
+        }
+    }
+    if config.tables {
+        for &(first, last) in &structure.tables {
+            align_table(&mut lines[first..=last]);
+        }
+    }
+    if config.headings {
+        for &line in &structure.headings {
+            lines[line] = normalize_heading(&lines[line]);
+        }
+        lines = space_headings(lines, &structure);
+    }
+
+    format!("{}{}", frontmatter, lines.join("\n"))
+}
+
+/// Byte offset where the body starts after a `---` frontmatter block
+fn frontmatter_end(content: &str) -> usize {
+    if !content.starts_with("---\n") {
+        return 0;
+    }
+    match content[3..].find("\n---") {
+        Some(end) => {
+            let block_end = 3 + end + 4;
+            content[block_end..]
+                .find('\n')
+                .map_or(content.len(), |i| block_end + i + 1)
+        }
+        None => 0,
+    }
+}
+
+/// Where the blocks the formatter rewrites are
+struct Structure {
+    /// Lines of top-level ATX headings
+    headings: Vec<usize>,
+    /// Byte offsets of list item markers
+    list_markers: Vec<usize>,
+    /// Opening lines of fenced code blocks
+    fences: Vec<usize>,
+    /// First and last lines of top-level tables
+    tables: Vec<(usize, usize)>,
+    /// Lines inside code and HTML blocks
+    verbatim: HashSet<usize>,
+}
+
+impl Structure {
+    fn parse(body: &str) -> Self {
+        let mut structure = Structure {
+            headings: Vec::new(),
+            list_markers: Vec::new(),
+            fences: Vec::new(),
+            tables: Vec::new(),
+            verbatim: HashSet::new(),
+        };
+        let line_at = |offset: usize| body[..offset].matches('\n').count();
+        let last_line = |range: &std::ops::Range<usize>| {
+            line_at(range.end.saturating_sub(1).max(range.start))
+        };
+        let at_line_start = |offset: usize| offset == 0 || body.as_bytes()[offset - 1] == b'\n';
+
+        let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+        for (event, range) in Parser::new_ext(body, options).into_offset_iter() {
+            match event {
+                Event::Start(Tag::Heading { .. })
+                    if at_line_start(range.start) && body[range.start..].starts_with('#') =>
+                {
+                    structure.headings.push(line_at(range.start));
+                }
+                Event::Start(Tag::Item) => structure.list_markers.push(range.start),
+                Event::Start(Tag::CodeBlock(kind)) => {
+                    let (first, last) = (line_at(range.start), last_line(&range));
+                    let body_lines = match kind {
+                        CodeBlockKind::Fenced(_) => {
+                            structure.fences.push(first);
+                            first + 1..last
+                        }
+                        CodeBlockKind::Indented => first..last + 1,
+                    };
+                    structure.verbatim.extend(body_lines);
+                }
+                Event::Start(Tag::HtmlBlock) => {
+                    structure.verbatim.extend(line_at(range.start)..=last_line(&range));
+                }
+                Event::Start(Tag::Table(_)) if at_line_start(range.start) => {
+                    structure.tables.push((line_at(range.start), last_line(&range)));
+                }
+                _ => {}
+            }
+        }
+        structure
+    }
+}
+
+fn replace_marker(body: &mut String, offset: usize, bullet: char) {
+    // Items of nested lists start at their indentation
+    let offset = offset + body[offset..].len() - body[offset..].trim_start_matches([' ', '\t']).len();
+    let Some(marker) = body[offset..].chars().next() else {
+        return;
+    };
+    if matches!(marker, '-' | '*' | '+') {
+        if matches!(bullet, '-' | '*' | '+') && marker != bullet {
+            body.replace_range(offset..offset + 1, bullet.encode_utf8(&mut [0; 4]));
+        }
+    } else if marker.is_ascii_digit() {
+        let digits = body[offset..].find(|c: char| !c.is_ascii_digit()).unwrap_or(0);
+        if body[offset + digits..].starts_with(')') {
+            body.replace_range(offset + digits..offset + digits + 1, ".");
+        }
+    }
+}
+
+/// `#` run, one space and the heading text without closing `#`s
+fn normalize_heading(line: &str) -> String {
+    let line = line.trim();
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let text = line[level..].trim();
+    // A closing sequence must be separated from the text by a space
+    let text = match text.trim_end_matches('#') {
+        "" => "",
+        rest if rest.ends_with([' ', '\t']) => rest.trim_end(),
+        _ => text,
+    };
+    if text.is_empty() {
+        "#".repeat(level)
+    } else {
+        format!("{} {}", "#".repeat(level), text)
+    }
+}
+
+/// Exactly one blank line between headings and their neighbours
+fn space_headings(lines: Vec<String>, structure: &Structure) -> Vec<String> {
+    let headings: HashSet<usize> = structure.headings.iter().copied().collect();
+    let blank = |i: usize| lines[i].trim().is_empty() && !structure.verbatim.contains(&i);
+
+    let mut spaced = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        if blank(i) {
+            // Runs of blank lines next to a heading shrink to one
+            let previous = (0..i).rev().find(|&j| !blank(j));
+            let next = (i + 1..lines.len()).find(|&j| !blank(j));
+            let near_heading = previous.is_some_and(|j| headings.contains(&j))
+                || next.is_some_and(|j| headings.contains(&j));
+            if near_heading && i > 0 && blank(i - 1) {
+                continue;
+            }
+        } else if headings.contains(&i) && i > 0 && !blank(i - 1) {
+            spaced.push(String::new());
+        }
+
+        spaced.push(line.clone());
+        if headings.contains(&i) && i + 1 < lines.len() && !blank(i + 1) {
+            spaced.push(String::new());
+        }
+    }
+    spaced
+}
+
+/// Lowercase the language of a fence's info string and resolve aliases
+fn normalize_fence(line: &str) -> String {
+    let indent = &line[..line.len() - line.trim_start().len()];
+    let rest = line.trim_start();
+    let fence_char = rest.chars().next().unwrap_or('`');
+    let fence_len = rest.chars().take_while(|&c| c == fence_char).count();
+    let (fence, info) = rest.split_at(fence_len);
+    let info = info.trim();
+    if info.is_empty() {
+        return format!("{}{}", indent, fence);
+    }
+
+    let (language, attributes) = info.split_once(char::is_whitespace).unwrap_or((info, ""));
+    let language = language.to_lowercase();
+    let language = LANGUAGE_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == language)
+        .map_or(language.as_str(), |(_, name)| name);
+    let attributes = attributes.trim();
+    if attributes.is_empty() {
+        format!("{}{}{}", indent, fence, language)
+    } else {
+        format!("{}{}{} {}", indent, fence, language, attributes)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Align {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+/// Pad the cells of a table's rows so the pipes line up
+fn align_table(rows: &mut [String]) {
+    let mut cells: Vec<Vec<String>> = rows.iter().map(|row| split_row(row)).collect();
+    let Some(delimiter) = cells.get(1) else {
+        return;
+    };
+    let aligns: Vec<Align> = delimiter
+        .iter()
+        .map(|cell| match (cell.starts_with(':'), cell.ends_with(':') && cell.len() > 1) {
+            (true, true) => Align::Center,
+            (true, false) => Align::Left,
+            (false, true) => Align::Right,
+            (false, false) => Align::None,
+        })
+        .collect();
+    let columns = aligns.len();
+
+    for row in cells.iter_mut() {
+        row.resize(columns.max(row.len()), String::new());
+        row.truncate(columns);
+    }
+    let widths: Vec<usize> = (0..columns)
+        .map(|col| {
+            cells
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != 1)
+                .map(|(_, row)| row[col].chars().count())
+                .max()
+                .unwrap_or(0)
+                .max(3)
+        })
+        .collect();
+
+    for (i, row) in cells.iter().enumerate() {
+        let padded: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(col, cell)| {
+                let width = widths[col];
+                if i == 1 {
+                    return delimiter_cell(aligns[col], width);
+                }
+                let fill = width - cell.chars().count();
+                match aligns[col] {
+                    Align::Right => format!("{}{}", " ".repeat(fill), cell),
+                    Align::Center => {
+                        format!("{}{}{}", " ".repeat(fill / 2), cell, " ".repeat(fill - fill / 2))
+                    }
+                    Align::None | Align::Left => format!("{}{}", cell, " ".repeat(fill)),
+                }
+            })
+            .collect();
+        rows[i] = format!("| {} |", padded.join(" | "));
+    }
+}
+
+fn delimiter_cell(align: Align, width: usize) -> String {
+    match align {
+        Align::None => "-".repeat(width),
+        Align::Left => format!(":{}", "-".repeat(width - 1)),
+        Align::Right => format!("{}:", "-".repeat(width - 1)),
+        Align::Center => format!(":{}:", "-".repeat(width - 2)),
+    }
+}
+
+/// Trimmed cells of a table row, split at pipes that aren't escaped
+fn split_row(row: &str) -> Vec<String> {
+    let row = row.trim();
+    let row = row.strip_prefix('|').unwrap_or(row);
+    let row = match row.strip_suffix('|') {
+        Some(rest) if !rest.ends_with('\\') => rest,
+        _ => row,
+    };
+
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut escaped = false;
+    for c in row.chars() {
+        if c == '|' && !escaped {
+            cells.push(cell.trim().to_string());
+            cell.clear();
+        } else {
+            cell.push(c);
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    cells.push(cell.trim().to_string());
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(content: &str) -> String {
+        let config = FormatConfig {
+            enabled: true,
+            ..FormatConfig::default()
+        };
+        let formatted = format_markdown(content, &config);
+        assert_eq!(format_markdown(&formatted, &config), formatted, "formatting is stable");
+        formatted
+    }
+
+    #[test]
+    fn test_headings() {
+        assert_eq!(
+            format("# Title #\nIntro\n\n\n\n##   Setup\n- step\n###No space"),
+            "# Title\n\nIntro\n\n## Setup\n\n- step\n###No space"
+        );
+        assert_eq!(format("# C#\n"), "# C#\n");
+    }
+
+    #[test]
+    fn test_list_markers_keep_alignment() {
+        assert_eq!(
+            format("* one\n  continued\n+ two\n    * nested\n\n1) first\n2) second\n\n***\n"),
+            "- one\n  continued\n- two\n    - nested\n\n1. first\n2. second\n\n***\n"
+        );
+    }
+
+    #[test]
+    fn test_fences_and_code_untouched() {
+        let content = "```RS\n*  not a list\n# not a heading\n```\n\n~~~py title=\"x\"\n~~~\n\n    * indented code\n";
+        assert_eq!(
+            format(content),
+            "```rust\n*  not a list\n# not a heading\n```\n\n~~~python title=\"x\"\n~~~\n\n    * indented code\n"
+        );
+    }
+
+    #[test]
+    fn test_tables() {
+        assert_eq!(
+            format("| a | long header |\n|:-|--:|\n| `x\\|y` | 1 |\n|wide cell|\n"),
+            "| a         | long header |\n\
+             | :-------- | ----------: |\n\
+             | `x\\|y`    |           1 |\n\
+             | wide cell |             |\n"
+        );
+    }
+
+    #[test]
+    fn test_frontmatter_and_disabled_rules() {
+        let config = FormatConfig {
+            enabled: true,
+            headings: false,
+            lists: false,
+            ..FormatConfig::default()
+        };
+        let content = "---\ntags: [a]\n---\n# Title #\n* item\n";
+        assert_eq!(format_markdown(content, &config), content);
+        assert_eq!(format(content), "---\ntags: [a]\n---\n# Title\n\n- item\n");
+    }
+}
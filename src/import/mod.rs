@@ -0,0 +1,5 @@
+//! Importers bringing notes over from other apps
+
+pub mod obsidian;
+
+pub use obsidian::ImportSummary;
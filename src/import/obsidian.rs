@@ -0,0 +1,590 @@
+//! Obsidian vault import
+//!
+//! Markdown files are copied into the notes directory keeping their folder
+//! layout, so path-style wiki links still resolve. Every other file is an
+//! attachment: it's copied into the flat attachments directory under a
+//! sanitized name, and embeds and links pointing at it are rewritten to its
+//! `/api/attachments/` URL. Frontmatter is normalized to the list form
+//! Notidium reads (`tags: a, b` and `tag:` become a `tags` list, `alias:` an
+//! `aliases` list). Note file names are sanitized the way Notidium names its
+//! own files. Notes that already exist in the vault are left alone, and so
+//! are notes whose path differs only in case from another note, which would
+//! overwrite it on case-insensitive file systems.
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use pulldown_cmark::{Event, Parser, Tag};
+use serde_yaml::{Mapping, Value};
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::store::{case_collisions, sanitize_file_stem};
+
+/// What an import copied into the vault
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    /// Notes written to the notes directory
+    pub notes: usize,
+    /// Files copied into the attachments directory
+    pub attachments: usize,
+    /// Notes not imported because the vault already has a file at their path
+    pub skipped: Vec<PathBuf>,
+    /// Notes not imported because their path differs only in case from
+    /// another note, with the path of the note that was kept
+    pub case_collisions: Vec<(PathBuf, PathBuf)>,
+}
+
+/// Import the Obsidian vault at `source` into the vault of `config`
+///
+/// Only writes files; loading the notes (which assigns their IDs) and
+/// indexing them is up to the caller.
+pub fn import_vault(source: &Path, config: &Config) -> Result<ImportSummary> {
+    if !source.is_dir() {
+        return Err(Error::Other(format!(
+            "Not a directory: {}",
+            source.display()
+        )));
+    }
+    if source.canonicalize()?.starts_with(config.vault_path.canonicalize()?) {
+        return Err(Error::Other(
+            "Can't import a directory inside the vault itself".to_string(),
+        ));
+    }
+
+    let mut notes = Vec::new();
+    let mut files = Vec::new();
+    collect_files(source, source, &mut notes, &mut files)?;
+    notes.sort();
+    files.sort();
+
+    let mut summary = ImportSummary::default();
+    let attachments_path = config.attachments_path();
+    std::fs::create_dir_all(&attachments_path)?;
+
+    let mut attachments = Attachments::default();
+    let mut taken = HashSet::new();
+    for relative in &files {
+        let from = source.join(relative);
+        let name = attachment_name(&attachments_path, &from, &mut taken)?;
+        let to = attachments_path.join(&name);
+        if !to.exists() {
+            std::fs::copy(&from, &to)?;
+            summary.attachments += 1;
+        }
+        attachments.insert(relative, name);
+    }
+
+    let notes_path = config.notes_path();
+    let targets: Vec<PathBuf> = notes.iter().map(|n| note_file_path(n)).collect();
+    let keepers = case_keepers(&notes_path, &targets)?;
+    for (relative, target) in notes.iter().zip(&targets) {
+        if let Some(keeper) = keepers.get(&target.to_string_lossy().to_lowercase()) {
+            if keeper != target {
+                tracing::warn!(
+                    "Not importing {:?}: its path differs only in case from {:?}",
+                    relative,
+                    keeper
+                );
+                summary.case_collisions.push((relative.clone(), keeper.clone()));
+                continue;
+            }
+        }
+
+        let to = notes_path.join(target);
+        if to.exists() {
+            summary.skipped.push(relative.clone());
+            continue;
+        }
+
+        let from = source.join(relative);
+        let content = std::fs::read_to_string(&from)?;
+        let note_dir = relative.parent().unwrap_or(Path::new(""));
+        let converted = convert_note(&content, note_dir, &attachments);
+
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&to, converted)?;
+        keep_modified_time(&from, &to);
+        summary.notes += 1;
+    }
+
+    Ok(summary)
+}
+
+/// Vault-relative path an imported note is written to, with its file name
+/// sanitized
+fn note_file_path(relative: &Path) -> PathBuf {
+    let stem = relative
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+    let name = format!("{}.md", sanitize_file_stem(&stem));
+    match relative.parent() {
+        Some(parent) => parent.join(name),
+        None => PathBuf::from(name),
+    }
+}
+
+/// The path kept for each group of note paths that differ only in case,
+/// by lowercased path
+///
+/// A note already in the vault is kept over imported ones; otherwise the
+/// first imported note in path order is.
+fn case_keepers(notes_path: &Path, targets: &[PathBuf]) -> Result<HashMap<String, PathBuf>> {
+    let mut existing = Vec::new();
+    if notes_path.is_dir() {
+        collect_files(notes_path, notes_path, &mut existing, &mut Vec::new())?;
+    }
+    let existing: HashSet<PathBuf> = existing.into_iter().collect();
+    let paths: HashSet<&Path> = existing
+        .iter()
+        .chain(targets)
+        .map(PathBuf::as_path)
+        .collect();
+
+    let mut keepers = HashMap::new();
+    for group in case_collisions(paths) {
+        let keeper = group
+            .iter()
+            .find(|p| existing.contains(*p))
+            .or_else(|| targets.iter().find(|t| group.contains(t)))
+            .cloned();
+        if let Some(keeper) = keeper {
+            keepers.insert(keeper.to_string_lossy().to_lowercase(), keeper);
+        }
+    }
+    Ok(keepers)
+}
+
+/// Split the files of a vault into notes and attachments, as paths relative
+/// to `root`, skipping hidden files and directories like `.obsidian`
+fn collect_files(
+    root: &Path,
+    dir: &Path,
+    notes: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with('.'));
+        if hidden {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_files(root, &path, notes, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            if path.extension().and_then(|e| e.to_str()) == Some("md") {
+                notes.push(relative.to_path_buf());
+            } else {
+                files.push(relative.to_path_buf());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Name for an attachment in the flat attachments directory
+///
+/// Keeps only the characters attachment URLs allow, and numbers names that
+/// clash with another imported file or a different file already there.
+fn attachment_name(dir: &Path, from: &Path, taken: &mut HashSet<String>) -> Result<String> {
+    let sanitize = |s: &str| -> String {
+        s.chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect()
+    };
+    let stem = from
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .map(sanitize)
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "attachment".to_string());
+    let extension = from
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| format!(".{}", sanitize(e)))
+        .unwrap_or_default();
+
+    for n in 1.. {
+        let name = if n == 1 {
+            format!("{}{}", stem, extension)
+        } else {
+            format!("{}_{}{}", stem, n, extension)
+        };
+        if taken.contains(&name) {
+            continue;
+        }
+        let existing = dir.join(&name);
+        if !existing.exists() || std::fs::read(&existing)? == std::fs::read(from)? {
+            taken.insert(name.clone());
+            return Ok(name);
+        }
+    }
+    unreachable!("ran out of attachment names")
+}
+
+/// Carry a file's modification time over, which the manifest uses as the
+/// note's timestamps
+fn keep_modified_time(from: &Path, to: &Path) {
+    let modified = std::fs::metadata(from).and_then(|m| m.modified());
+    let file = std::fs::File::options().write(true).open(to);
+    if let (Ok(modified), Ok(file)) = (modified, file) {
+        if let Err(e) = file.set_modified(modified) {
+            tracing::debug!("Failed to keep modification time of {:?}: {}", to, e);
+        }
+    }
+}
+
+/// Imported attachments by their path in the source vault
+#[derive(Debug, Default)]
+struct Attachments {
+    /// Attachment names by lowercased vault-relative path
+    by_path: HashMap<String, String>,
+    /// Attachment names by lowercased file name, for links that only give
+    /// the name (the first file in path order wins, as in Obsidian)
+    by_name: HashMap<String, String>,
+}
+
+impl Attachments {
+    fn insert(&mut self, relative: &Path, name: String) {
+        let path = slash_path(relative).to_lowercase();
+        if let Some(file_name) = relative.file_name().and_then(|n| n.to_str()) {
+            self.by_name
+                .entry(file_name.to_lowercase())
+                .or_insert_with(|| name.clone());
+        }
+        self.by_path.insert(path, name);
+    }
+
+    /// URL of the attachment a link in a note in `note_dir` points at
+    fn resolve(&self, note_dir: &Path, target: &str) -> Option<String> {
+        let target = target.trim().trim_start_matches("./").trim_start_matches('/');
+        let target = target.to_lowercase();
+        let next_to_note = slash_path(&normalize(&note_dir.join(&target))).to_lowercase();
+        let file_name = target.rsplit('/').next().unwrap_or(&target);
+
+        self.by_path
+            .get(&next_to_note)
+            .or_else(|| self.by_path.get(&target))
+            .or_else(|| self.by_name.get(file_name))
+            .map(|name| format!("/api/attachments/{}", name))
+    }
+}
+
+/// Rewrite an Obsidian note's frontmatter and links for Notidium
+fn convert_note(content: &str, note_dir: &Path, attachments: &Attachments) -> String {
+    let (frontmatter, body) = split_frontmatter(content);
+    let mut converted = String::with_capacity(content.len());
+    if let Some(yaml) = frontmatter {
+        converted.push_str(&convert_frontmatter(yaml));
+    }
+    converted.push_str(&convert_links(body, note_dir, attachments));
+    converted
+}
+
+/// Split off the raw `---` delimited frontmatter block, delimiters included
+fn split_frontmatter(content: &str) -> (Option<&str>, &str) {
+    if !content.starts_with("---") {
+        return (None, content);
+    }
+    match content[3..].find("\n---") {
+        Some(end) => {
+            let block_end = 3 + end + 4;
+            let body_start = content[block_end..]
+                .find('\n')
+                .map(|i| block_end + i + 1)
+                .unwrap_or(content.len());
+            (Some(&content[..body_start]), &content[body_start..])
+        }
+        None => (None, content),
+    }
+}
+
+/// Normalize a frontmatter block, returning it unchanged when it isn't a
+/// YAML mapping
+fn convert_frontmatter(block: &str) -> String {
+    let yaml = block
+        .trim_start_matches("---")
+        .trim_end()
+        .trim_end_matches("---");
+    let Ok(mut mapping) = serde_yaml::from_str::<Mapping>(yaml) else {
+        return block.to_string();
+    };
+
+    let mut tags = take_list(&mut mapping, "tags");
+    tags.extend(take_list(&mut mapping, "tag"));
+    let tags: Vec<String> = dedup(
+        tags.into_iter()
+            .map(|t| t.trim_start_matches('#').to_string())
+            .filter(|t| !t.is_empty()),
+    );
+    let mut aliases = take_list(&mut mapping, "aliases");
+    aliases.extend(take_list(&mut mapping, "alias"));
+    let aliases = dedup(aliases.into_iter());
+
+    let mut normalized = Mapping::new();
+    if !tags.is_empty() {
+        normalized.insert("tags".into(), tags.into_iter().map(Value::from).collect());
+    }
+    if !aliases.is_empty() {
+        normalized.insert("aliases".into(), aliases.into_iter().map(Value::from).collect());
+    }
+    normalized.extend(mapping);
+
+    if normalized.is_empty() {
+        return String::new();
+    }
+    match serde_yaml::to_string(&normalized) {
+        Ok(yaml) => format!("---\n{}---\n", yaml),
+        Err(_) => block.to_string(),
+    }
+}
+
+/// Remove a key holding a list, or a comma or space separated string
+fn take_list(mapping: &mut Mapping, key: &str) -> Vec<String> {
+    match mapping.remove(key) {
+        Some(Value::Sequence(items)) => items
+            .into_iter()
+            .filter_map(|v| match v {
+                Value::String(s) => Some(s),
+                Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            })
+            .collect(),
+        Some(Value::String(s)) if key.starts_with("tag") => s
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|t| !t.is_empty())
+            .map(String::from)
+            .collect(),
+        Some(Value::String(s)) => s
+            .split(',')
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .map(String::from)
+            .collect(),
+        Some(Value::Number(n)) => vec![n.to_string()],
+        _ => Vec::new(),
+    }
+}
+
+fn dedup(items: impl Iterator<Item = String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    items.filter(|i| seen.insert(i.to_lowercase())).collect()
+}
+
+/// Rewrite wiki links and markdown links of a note body
+///
+/// Wiki links to notes stay wiki links, minus any `.md` suffix; embeds and
+/// links of attachments become markdown images and links to the attachment
+/// URL. Code is left alone.
+fn convert_links(body: &str, note_dir: &Path, attachments: &Attachments) -> String {
+    let mut code_ranges: Vec<Range<usize>> = Vec::new();
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+
+    for (event, range) in Parser::new(body).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) | Event::Code(_) => code_ranges.push(range),
+            Event::Start(Tag::Image { dest_url, .. }) | Event::Start(Tag::Link { dest_url, .. }) => {
+                if dest_url.contains("://") || dest_url.starts_with('#') || dest_url.starts_with("mailto:") {
+                    continue;
+                }
+                let Some(url) = attachments.resolve(note_dir, &percent_decode(&dest_url)) else {
+                    continue;
+                };
+                if let Some(dest) = link_destination(body, &range) {
+                    edits.push((dest, url));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut search_from = 0;
+    while let Some(found) = body[search_from..].find("[[") {
+        let start = search_from + found;
+        let inner_start = start + 2;
+        let Some(len) = body[inner_start..].find("]]") else {
+            break;
+        };
+        let inner = &body[inner_start..inner_start + len];
+        let end = inner_start + len + 2;
+        search_from = end;
+
+        if inner.contains('\n') || inner.contains("[[") {
+            search_from = inner_start;
+            continue;
+        }
+        if code_ranges.iter().any(|r| r.contains(&start)) {
+            continue;
+        }
+
+        let embed = start > 0 && body.as_bytes()[start - 1] == b'!';
+        let (target, label) = match inner.split_once('|') {
+            Some((target, label)) => (target.trim_end_matches('\\').trim(), Some(label.trim())),
+            None => (inner.trim(), None),
+        };
+
+        if let Some(url) = attachments.resolve(note_dir, target) {
+            let text = match label {
+                // `![[image.png|300]]` gives a display width, not a caption
+                Some(label) if !label.is_empty() && !label.chars().all(|c| c.is_ascii_digit() || c == 'x') => label,
+                _ => target.rsplit('/').next().unwrap_or(target),
+            };
+            let range = if embed { start - 1..end } else { start..end };
+            let bang = if embed { "!" } else { "" };
+            edits.push((range, format!("{}[{}]({})", bang, text, url)));
+        } else if let Some((page, rest)) = split_md_suffix(inner) {
+            edits.push((start..end, format!("[[{}{}]]", page, rest)));
+        }
+    }
+
+    edits.sort_by_key(|(range, _)| range.start);
+    let mut converted = String::with_capacity(body.len());
+    let mut copied = 0;
+    for (range, replacement) in edits {
+        if range.start < copied {
+            continue;
+        }
+        converted.push_str(&body[copied..range.start]);
+        converted.push_str(&replacement);
+        copied = range.end;
+    }
+    converted.push_str(&body[copied..]);
+    converted
+}
+
+/// A wiki link target's page with its `.md` suffix dropped, and the rest of
+/// the link; `None` when the target has no such suffix
+fn split_md_suffix(inner: &str) -> Option<(&str, &str)> {
+    let page_end = inner.find(['#', '|']).unwrap_or(inner.len());
+    let page = inner[..page_end].trim_end();
+    page.strip_suffix(".md")
+        .map(|stripped| (stripped, &inner[page_end..]))
+}
+
+/// Byte range of the destination in a markdown link or image spanning
+/// `range`, like `img.png` in `![alt](img.png "title")`
+fn link_destination(body: &str, range: &Range<usize>) -> Option<Range<usize>> {
+    let text = &body[range.clone()];
+    if !text.ends_with(')') {
+        // Reference-style links keep their destination elsewhere
+        return None;
+    }
+    let open = text.rfind("](")? + 2;
+    let inner = &text[open..text.len() - 1];
+    let leading = inner.len() - inner.trim_start().len();
+    let inner = inner.trim_start();
+
+    let (start, len) = if let Some(rest) = inner.strip_prefix('<') {
+        (1, rest.find('>')?)
+    } else {
+        (0, inner.find(char::is_whitespace).unwrap_or(inner.len()))
+    };
+    let dest_start = range.start + open + leading + start;
+    Some(dest_start..dest_start + len)
+}
+
+/// Decode `%XX` escapes, as in `My%20Image.png`
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%')
+            .then(|| s.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).unwrap_or_else(|_| s.to_string())
+}
+
+/// Resolve `.` and `..` components without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|c| c.as_os_str().to_str())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attachments() -> Attachments {
+        let mut attachments = Attachments::default();
+        attachments.insert(Path::new("assets/Screen Shot.png"), "Screen_Shot.png".into());
+        attachments.insert(Path::new("projects/diagram.svg"), "diagram.svg".into());
+        attachments
+    }
+
+    #[test]
+    fn test_convert_frontmatter() {
+        let converted =
+            convert_frontmatter("---\ntags: rust, cli\ntag: '#todo'\nalias: Tool\nstatus: draft\n---\n");
+        assert_eq!(
+            converted,
+            "---\ntags:\n- rust\n- cli\n- todo\naliases:\n- Tool\nstatus: draft\n---\n"
+        );
+        assert_eq!(convert_frontmatter("---\n: [\n---\n"), "---\n: [\n---\n");
+    }
+
+    #[test]
+    fn test_convert_embeds_and_links() {
+        let body = "![[Screen Shot.png|400]] and [[diagram.svg|the diagram]]\n\
+                    ![alt](../assets/Screen%20Shot.png) [[Other Note.md#Heading|see]]\n\
+                    `[[diagram.svg]]`\n";
+        let converted = convert_links(body, Path::new("projects"), &attachments());
+        assert_eq!(
+            converted,
+            "![Screen Shot.png](/api/attachments/Screen_Shot.png) and \
+             [the diagram](/api/attachments/diagram.svg)\n\
+             ![alt](/api/attachments/Screen_Shot.png) [[Other Note#Heading|see]]\n\
+             `[[diagram.svg]]`\n"
+        );
+    }
+
+    #[test]
+    fn test_note_links_stay_wiki_links() {
+        let body = "[[Note]] [[folder/Note|alias]] ![[Embedded Note]]";
+        assert_eq!(convert_links(body, Path::new(""), &attachments()), body);
+    }
+
+    #[test]
+    fn test_split_frontmatter() {
+        let (fm, body) = split_frontmatter("---\ntags: [a]\n---\n# Title\n");
+        assert_eq!(fm, Some("---\ntags: [a]\n---\n"));
+        assert_eq!(body, "# Title\n");
+        assert_eq!(split_frontmatter("# Title"), (None, "# Title"));
+    }
+}
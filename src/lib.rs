@@ -7,9 +7,20 @@ pub mod types;
 pub mod store;
 pub mod search;
 pub mod embed;
+pub mod import;
 pub mod mcp;
 pub mod api;
 pub mod service;
+pub mod snapshot;
+pub mod export;
+pub mod clip;
+pub mod lint;
+pub mod maintain;
+pub mod formatter;
+pub mod seed;
+pub mod telemetry;
+pub mod watcher;
+pub mod webhooks;
 
 pub use config::Config;
 pub use error::{Error, Result};
@@ -0,0 +1,490 @@
+//! Note content linting
+//!
+//! Checks for problems that don't stop a note from rendering but make it
+//! harder to navigate or rot over time: frontmatter that doesn't parse,
+//! repeated headings and skipped heading levels, bare URLs, images pointing
+//! at missing attachments and stray trailing whitespace. Bare URLs and
+//! whitespace can be fixed without changing what the note says; the rest is
+//! reported for a person to decide.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use serde::Serialize;
+
+/// A lint check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintRule {
+    /// Frontmatter that isn't a closed YAML mapping
+    InvalidFrontmatter,
+    /// A heading with the same text as an earlier one
+    DuplicateHeading,
+    /// A heading more than one level below the previous one
+    SkippedHeadingLevel,
+    /// A URL in text that isn't a link
+    BareUrl,
+    /// An image whose file doesn't exist
+    MissingAttachment,
+    /// Whitespace at line ends that isn't a hard break, or blank lines at
+    /// the end of the file
+    TrailingWhitespace,
+}
+
+impl LintRule {
+    pub fn name(self) -> &'static str {
+        match self {
+            LintRule::InvalidFrontmatter => "invalid-frontmatter",
+            LintRule::DuplicateHeading => "duplicate-heading",
+            LintRule::SkippedHeadingLevel => "skipped-heading-level",
+            LintRule::BareUrl => "bare-url",
+            LintRule::MissingAttachment => "missing-attachment",
+            LintRule::TrailingWhitespace => "trailing-whitespace",
+        }
+    }
+
+    /// Whether [`fix`] resolves issues of this rule
+    pub fn fixable(self) -> bool {
+        matches!(self, LintRule::BareUrl | LintRule::TrailingWhitespace)
+    }
+}
+
+impl fmt::Display for LintRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A problem found in a note
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LintIssue {
+    pub rule: LintRule,
+    /// 1-based line number
+    pub line: usize,
+    pub message: String,
+}
+
+/// Check a note's content. Relative image paths resolve against `note_dir`,
+/// `/api/attachments/` ones against `attachments_dir`.
+pub fn lint(content: &str, note_dir: &Path, attachments_dir: &Path) -> Vec<LintIssue> {
+    let scan = scan(content);
+    let mut issues = scan.issues;
+
+    for (dest, line) in scan.images {
+        let Some(path) = attachment_path(&dest, note_dir, attachments_dir) else {
+            continue;
+        };
+        if !path.exists() {
+            issues.push(LintIssue {
+                rule: LintRule::MissingAttachment,
+                line,
+                message: format!("Image {} points to a missing file", dest),
+            });
+        }
+    }
+
+    issues.extend(whitespace_issues(content, &scan.code_lines));
+    issues.sort_by_key(|i| i.line);
+    issues
+}
+
+/// Apply the safe fixes: wrap bare URLs in `<>` and normalize trailing
+/// whitespace
+pub fn fix(content: &str) -> String {
+    let scan = scan(content);
+    let mut fixed = content.to_string();
+    for range in scan.bare_urls.iter().rev() {
+        fixed.insert(range.end, '>');
+        fixed.insert(range.start, '<');
+    }
+
+    // Wrapping URLs doesn't move lines, so code blocks stay where they were
+    let lines: Vec<&str> = fixed.split('\n').collect();
+    let mut normalized: Vec<String> = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            if scan.code_lines.contains(&i) {
+                line.to_string()
+            } else {
+                normalize_line(line, lines.get(i + 1).copied()).to_string()
+            }
+        })
+        .collect();
+    while normalized.last().is_some_and(|l| l.trim().is_empty()) {
+        normalized.pop();
+    }
+    if normalized.is_empty() {
+        return String::new();
+    }
+    normalized.join("\n") + "\n"
+}
+
+/// What one pass over the markdown finds
+struct Scan {
+    issues: Vec<LintIssue>,
+    /// Byte ranges of bare URLs in the content
+    bare_urls: Vec<Range<usize>>,
+    /// Image destinations and their lines
+    images: Vec<(String, usize)>,
+    /// 0-based indexes of lines inside code blocks
+    code_lines: HashSet<usize>,
+}
+
+fn scan(content: &str) -> Scan {
+    let mut scan = Scan {
+        issues: Vec::new(),
+        bare_urls: Vec::new(),
+        images: Vec::new(),
+        code_lines: HashSet::new(),
+    };
+    let line_at = |offset: usize| content[..offset].matches('\n').count() + 1;
+
+    let body_start = match frontmatter(content) {
+        Frontmatter::None => 0,
+        Frontmatter::Valid(body_start) => body_start,
+        Frontmatter::Invalid(message, body_start) => {
+            scan.issues.push(LintIssue {
+                rule: LintRule::InvalidFrontmatter,
+                line: 1,
+                message,
+            });
+            body_start
+        }
+    };
+    scan_body(scan, content, body_start, line_at)
+}
+
+fn scan_body(
+    mut scan: Scan,
+    content: &str,
+    body_start: usize,
+    line_at: impl Fn(usize) -> usize,
+) -> Scan {
+    let body = &content[body_start..];
+    let mut headings: HashMap<String, usize> = HashMap::new();
+    let mut previous_level = None;
+    let mut heading: Option<(usize, String, usize)> = None;
+    let mut link_depth = 0;
+    let mut in_code = false;
+    // Pulldown splits text at characters like `_`, so URLs are looked for in
+    // runs of adjacent text
+    let mut text_run: Option<Range<usize>> = None;
+
+    for (event, range) in Parser::new_ext(body, Options::all()).into_offset_iter() {
+        let start = body_start + range.start;
+        let continues_run = matches!(event, Event::Text(_)) && link_depth == 0 && !in_code;
+        if !continues_run || text_run.as_ref().is_some_and(|run| run.end != start) {
+            if let Some(run) = text_run.take() {
+                push_bare_urls(&mut scan, content, run, &line_at);
+            }
+        }
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                heading = Some((level as usize, String::new(), line_at(start)));
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                let Some((level, text, line)) = heading.take() else {
+                    continue;
+                };
+                if let Some(previous) = previous_level {
+                    if level > previous + 1 {
+                        scan.issues.push(LintIssue {
+                            rule: LintRule::SkippedHeadingLevel,
+                            line,
+                            message: format!("Heading level jumps from H{} to H{}", previous, level),
+                        });
+                    }
+                }
+                previous_level = Some(level);
+
+                let key = text.trim().to_lowercase();
+                if key.is_empty() {
+                    continue;
+                }
+                match headings.get(&key) {
+                    Some(first) => scan.issues.push(LintIssue {
+                        rule: LintRule::DuplicateHeading,
+                        line,
+                        message: format!("Heading \"{}\" already used on line {}", text.trim(), first),
+                    }),
+                    None => {
+                        headings.insert(key, line);
+                    }
+                }
+            }
+            Event::Start(Tag::Link { .. }) => link_depth += 1,
+            Event::End(TagEnd::Link) => link_depth -= 1,
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                link_depth += 1;
+                scan.images.push((dest_url.to_string(), line_at(start)));
+            }
+            Event::End(TagEnd::Image) => link_depth -= 1,
+            Event::Start(Tag::CodeBlock(_)) => {
+                in_code = true;
+                let end = body_start + range.end;
+                let first = line_at(start) - 1;
+                // A block ending in a newline doesn't reach the next line
+                let last = line_at(end.saturating_sub(1).max(start)) - 1;
+                scan.code_lines.extend(first..=last);
+            }
+            Event::End(TagEnd::CodeBlock) => in_code = false,
+            Event::Text(text) => {
+                if let Some((_, heading_text, _)) = heading.as_mut() {
+                    heading_text.push_str(&text);
+                }
+                if continues_run {
+                    let end = body_start + range.end;
+                    text_run = Some(text_run.map_or(start, |run| run.start)..end);
+                }
+            }
+            Event::Code(code) => {
+                if let Some((_, heading_text, _)) = heading.as_mut() {
+                    heading_text.push_str(&code);
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(run) = text_run {
+        push_bare_urls(&mut scan, content, run, &line_at);
+    }
+
+    scan
+}
+
+fn push_bare_urls(scan: &mut Scan, content: &str, run: Range<usize>, line_at: impl Fn(usize) -> usize) {
+    for url in bare_urls(&content[run.clone()]) {
+        let url = run.start + url.start..run.start + url.end;
+        scan.issues.push(LintIssue {
+            rule: LintRule::BareUrl,
+            line: line_at(url.start),
+            message: format!("Bare URL {} should be a link", &content[url.clone()]),
+        });
+        scan.bare_urls.push(url);
+    }
+}
+
+enum Frontmatter {
+    None,
+    /// Byte offset where the body starts
+    Valid(usize),
+    /// What's wrong, and where the body starts if the block is closed
+    Invalid(String, usize),
+}
+
+fn frontmatter(content: &str) -> Frontmatter {
+    if !content.starts_with("---\n") && !content.starts_with("---\r\n") {
+        return Frontmatter::None;
+    }
+    let Some(end) = content[3..].find("\n---") else {
+        return Frontmatter::Invalid("Frontmatter is never closed with ---".to_string(), 0);
+    };
+    let yaml = &content[3..3 + end];
+    let block_end = 3 + end + 4;
+    let body_start = content[block_end..]
+        .find('\n')
+        .map_or(content.len(), |i| block_end + i + 1);
+
+    match serde_yaml::from_str::<serde_yaml::Value>(yaml) {
+        Ok(serde_yaml::Value::Mapping(_)) | Ok(serde_yaml::Value::Null) => Frontmatter::Valid(body_start),
+        Ok(_) => Frontmatter::Invalid(
+            "Frontmatter isn't a list of key: value pairs".to_string(),
+            body_start,
+        ),
+        Err(e) => Frontmatter::Invalid(format!("Frontmatter isn't valid YAML: {}", e), body_start),
+    }
+}
+
+/// Byte ranges of http(s) URLs in a run of markdown source
+fn bare_urls(source: &str) -> Vec<Range<usize>> {
+    let mut urls = Vec::new();
+    let mut from = 0;
+    while let Some(found) = ["http://", "https://"]
+        .iter()
+        .filter_map(|scheme| source[from..].find(scheme))
+        .min()
+    {
+        let start = from + found;
+        let mut end = source[start..]
+            .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"' | '`'))
+            .map_or(source.len(), |len| start + len);
+        // Sentence punctuation after a URL isn't part of it
+        while let Some(last) = source[start..end].chars().next_back() {
+            let unbalanced = last == ')' && !source[start..end].contains('(');
+            if matches!(last, '.' | ',' | ';' | ':' | '!' | '?' | '\'') || unbalanced {
+                end -= last.len_utf8();
+            } else {
+                break;
+            }
+        }
+
+        let word_start = !source[..start].chars().next_back().is_some_and(char::is_alphanumeric);
+        let has_host = end > start + source[start..].find("//").unwrap_or(0) + 2;
+        if word_start && has_host {
+            urls.push(start..end);
+        }
+        from = end.max(start + 1);
+    }
+    urls
+}
+
+/// File an image destination refers to, if it's a local one
+pub(crate) fn attachment_path(dest: &str, note_dir: &Path, attachments_dir: &Path) -> Option<PathBuf> {
+    if dest.is_empty() || dest.contains("://") || dest.starts_with("data:") || dest.starts_with("mailto:") {
+        return None;
+    }
+    let path = dest.split(['?', '#']).next()?;
+    let path = percent_decode(path);
+    match path.strip_prefix("/api/attachments/") {
+        Some(name) => Some(attachments_dir.join(name)),
+        None if path.starts_with('/') => None,
+        None => Some(note_dir.join(path)),
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn whitespace_issues(content: &str, code_lines: &HashSet<usize>) -> Vec<LintIssue> {
+    let lines: Vec<&str> = content.split('\n').collect();
+    let mut issues: Vec<LintIssue> = lines
+        .iter()
+        .enumerate()
+        .filter(|(i, line)| {
+            !code_lines.contains(i) && normalize_line(line, lines.get(i + 1).copied()) != **line
+        })
+        .map(|(i, _)| LintIssue {
+            rule: LintRule::TrailingWhitespace,
+            line: i + 1,
+            message: "Trailing whitespace".to_string(),
+        })
+        .collect();
+
+    let trailing_blank = lines
+        .iter()
+        .rev()
+        .skip(1)
+        .take_while(|l| l.trim().is_empty())
+        .count();
+    if !content.is_empty() && (trailing_blank > 0 || !content.ends_with('\n')) {
+        issues.push(LintIssue {
+            rule: LintRule::TrailingWhitespace,
+            line: lines.len() - trailing_blank.min(lines.len() - 1),
+            message: "File should end with a single newline".to_string(),
+        });
+    }
+    issues
+}
+
+/// A line without trailing whitespace, keeping two spaces where they make a
+/// hard break before the next line
+fn normalize_line<'a>(line: &'a str, next: Option<&str>) -> std::borrow::Cow<'a, str> {
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    let trimmed = line.trim_end();
+    if trimmed.len() == line.len() {
+        return line.into();
+    }
+    let hard_break = !trimmed.is_empty()
+        && line[trimmed.len()..].chars().all(|c| c == ' ')
+        && line.len() - trimmed.len() >= 2
+        && next.is_some_and(|n| !n.trim().is_empty());
+    if hard_break {
+        format!("{}  ", trimmed).into()
+    } else {
+        trimmed.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(content: &str) -> Vec<(LintRule, usize)> {
+        let dir = Path::new("/nonexistent");
+        lint(content, dir, dir).into_iter().map(|i| (i.rule, i.line)).collect()
+    }
+
+    #[test]
+    fn test_heading_structure() {
+        let content = "# Title\n\n### Deep\n\n## Setup\n\n## setup\n";
+        assert_eq!(
+            rules(content),
+            [(LintRule::SkippedHeadingLevel, 3), (LintRule::DuplicateHeading, 7)]
+        );
+    }
+
+    #[test]
+    fn test_bare_urls_skip_links_and_code() {
+        let content = "See https://example.com/a_(b). Or [x](https://x.org), <https://y.org>,\n\
+                       `https://code.org` and https://z.org/path.\n\n```\nhttps://block.org\n```\n";
+        let issues = lint(content, Path::new("/"), Path::new("/"));
+        let urls: Vec<&str> = issues.iter().map(|i| i.message.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "Bare URL https://example.com/a_(b) should be a link",
+                "Bare URL https://z.org/path should be a link"
+            ]
+        );
+        assert_eq!(
+            fix(content),
+            "See <https://example.com/a_(b)>. Or [x](https://x.org), <https://y.org>,\n\
+             `https://code.org` and <https://z.org/path>.\n\n```\nhttps://block.org\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_trailing_whitespace_keeps_hard_breaks_and_code() {
+        let content = "line one  \nline two \t\n\n```\ncode   \n```\nend   \n\n\n";
+        assert_eq!(
+            rules(content),
+            [
+                (LintRule::TrailingWhitespace, 2),
+                (LintRule::TrailingWhitespace, 7),
+                (LintRule::TrailingWhitespace, 8)
+            ]
+        );
+        let fixed = fix(content);
+        assert_eq!(fixed, "line one  \nline two\n\n```\ncode   \n```\nend\n");
+        assert!(rules(&fixed).is_empty());
+    }
+
+    #[test]
+    fn test_frontmatter_and_missing_attachments() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("here.png"), b"png").unwrap();
+        let content = "---\ntags: [a\n---\n\n![ok](/api/attachments/here.png)\n\
+                       ![gone](/api/attachments/gone%20now.png)\n![web](https://x.org/a.png)\n";
+        let issues = lint(content, dir.path(), dir.path());
+        let found: Vec<(LintRule, usize)> = issues.iter().map(|i| (i.rule, i.line)).collect();
+        assert_eq!(
+            found,
+            [(LintRule::InvalidFrontmatter, 1), (LintRule::MissingAttachment, 6)]
+        );
+        assert!(issues[1].message.contains("gone%20now.png"));
+    }
+}
@@ -1,19 +1,33 @@
 //! Notidium - Developer-focused, local-first note-taking with semantic search and MCP integration
 
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
 use std::sync::Arc;
 
 use clap::{Parser, Subcommand};
 use tokio::sync::RwLock;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use notidium::api::{self, AppState};
 use notidium::config::Config;
-use notidium::embed::{Chunker, Embedder};
-use notidium::mcp::NotidiumServer;
-use notidium::search::{FullTextIndex, SemanticSearch};
+use notidium::embed::{self, Chunker, Embedder};
+use notidium::export::bundle;
+use notidium::export::stats::{self, StatsFormat};
+use notidium::export::ExportFormat;
+use notidium::lint;
+use notidium::maintain;
+use notidium::mcp::{McpTransport, NotidiumServer, ToolFilter};
+use notidium::search::{
+    canvas_path, default_mode, grep_matches, grep_notes, FullTextIndex, GrepOptions, QueryClassifier, SearchClient, SearchDispatcher, SearchMode,
+    SearchRequest, SemanticSearch,
+};
+use notidium::seed;
 use notidium::service::{self, ServiceSpec, ServiceState};
-use notidium::store::NoteStore;
+use notidium::snapshot::{self, SnapshotManager};
+use notidium::telemetry;
+use notidium::types::NoteSort;
+use notidium::webhooks;
+use notidium::store::{Manifest, NoteStore, Scope, Users, VectorStore};
+use notidium::watcher::VaultWatcher;
 
 #[derive(Parser)]
 #[command(name = "notidium")]
@@ -38,6 +52,14 @@ enum Commands {
     Init {
         /// Path for the new vault
         path: Option<PathBuf>,
+
+        /// Generate an API token that `serve` then requires on /api and /mcp
+        #[arg(long)]
+        api_token: bool,
+
+        /// Take the defaults instead of asking, as when not run in a terminal
+        #[arg(short, long)]
+        yes: bool,
     },
 
     /// Start the HTTP server (includes MCP at /mcp)
@@ -45,13 +67,26 @@ enum Commands {
         /// Path to vault directory
         path: Option<PathBuf>,
 
-        /// Port to listen on
-        #[arg(short, long, default_value = "3939")]
-        port: u16,
+        /// Port to listen on (default: `http_port` from the config, 3939)
+        #[arg(short, long)]
+        port: Option<u16>,
+
+        /// Address to listen on (default: `bind_address` from the config,
+        /// 127.0.0.1); 0.0.0.0 accepts connections from other machines
+        #[arg(long)]
+        bind: Option<IpAddr>,
+
+        /// Open the web UI in the browser once the server is listening
+        #[arg(long)]
+        open: bool,
 
         /// Disable MCP endpoint
         #[arg(long)]
         no_mcp: bool,
+
+        /// Don't watch the vault for external changes
+        #[arg(long)]
+        no_watch: bool,
     },
 
     /// Start the MCP server (stdio mode for Claude Desktop)
@@ -68,6 +103,11 @@ enum Commands {
         /// Port to listen on
         #[arg(short, long, default_value = "3940")]
         port: u16,
+
+        /// Address to listen on (default: `bind_address` from the config,
+        /// 127.0.0.1); 0.0.0.0 accepts connections from other machines
+        #[arg(long)]
+        bind: Option<IpAddr>,
     },
 
     /// Index all notes
@@ -77,23 +117,126 @@ enum Commands {
         force: bool,
     },
 
+    /// Import notes from another app
+    Import {
+        #[command(subcommand)]
+        source: ImportSource,
+    },
+
+    /// Export the vault as a zip archive or a JSON dump
+    Export {
+        #[command(subcommand)]
+        what: Option<ExportTarget>,
+
+        /// zip (notes, attachments, templates and manifest) or json (notes
+        /// with metadata, tags and links)
+        #[arg(short = 'F', long, default_value = "zip")]
+        format: ExportFormat,
+
+        /// File to write (default: notidium-export-<date>.<format>)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Publish notes with some tags as a static site
+    Publish {
+        /// Publish notes with this tag (repeatable)
+        #[arg(short, long = "tag", value_name = "TAG", required = true)]
+        tags: Vec<String>,
+
+        /// Directory to write the site to
+        #[arg(short, long, default_value = "site")]
+        out: PathBuf,
+
+        /// Site name shown on every page and in the feed
+        #[arg(long, default_value = "Notes")]
+        title: String,
+
+        /// URL the site will be served from, for absolute links in the feed
+        #[arg(long)]
+        base_url: Option<String>,
+    },
+
     /// Search notes
     Search {
         /// Search query
         query: String,
 
-        /// Use semantic search
-        #[arg(short, long)]
+        /// Use semantic search (same as `--mode semantic`)
+        #[arg(short, long, conflicts_with = "mode")]
         semantic: bool,
 
+        /// fulltext, semantic or hybrid (default: `search.client_modes.cli`,
+        /// then `search.default_mode`, then fulltext)
+        #[arg(short, long)]
+        mode: Option<SearchMode>,
+
         /// Maximum results
         #[arg(short, long, default_value = "10")]
         limit: usize,
+
+        /// Also match words a typo or two away, so "kubernets" finds
+        /// Kubernetes (fulltext and hybrid modes)
+        #[arg(short, long)]
+        fuzzy: bool,
+    },
+
+    /// Search note files line by line with a regex, using ripgrep when installed
+    Grep {
+        /// Regular expression to match
+        pattern: String,
+
+        /// Match regardless of letter case
+        #[arg(short, long)]
+        ignore_case: bool,
+
+        /// Match the pattern as a literal string
+        #[arg(short = 'F', long)]
+        fixed_strings: bool,
+
+        /// Maximum matching lines
+        #[arg(short, long, default_value = "50")]
+        limit: usize,
+
+        /// Print the results as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Fill the vault with generated notes, for demos and benchmarks
+    Seed {
+        /// Number of notes to generate
+        #[arg(long, default_value = "100")]
+        notes: usize,
+
+        /// Share of notes with code blocks, from 0 to 1
+        #[arg(long, default_value = "0.3")]
+        code_ratio: f32,
+
+        /// Seed of the generator; the same seed gives the same notes
+        #[arg(long, default_value = "42")]
+        seed: u64,
     },
 
     /// Show vault statistics
     Stats,
 
+    /// Check notes for broken frontmatter, heading structure, bare URLs,
+    /// missing images and trailing whitespace
+    Lint {
+        /// Wrap bare URLs in <> and normalize trailing whitespace
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Check that the manifest, the full-text index and the vector store
+    /// match the notes, repair drift and compact the vector store
+    Maintain {
+        /// Only report drift, failing if there is any
+        #[arg(long)]
+        check: bool,
+    },
+
     /// List all notes
     List {
         /// Maximum results
@@ -103,6 +246,70 @@ enum Commands {
         /// Filter by tag
         #[arg(short, long)]
         tag: Option<String>,
+
+        /// Only notes created or last modified by this user
+        #[arg(short, long)]
+        author: Option<String>,
+    },
+
+    /// Create a note, optionally from a template
+    New {
+        /// Note title
+        title: String,
+
+        /// Template to fill in (file name in templates/ without .md)
+        #[arg(short, long)]
+        template: Option<String>,
+
+        /// Folder to create the note in, relative to the notes directory
+        #[arg(short, long)]
+        folder: Option<PathBuf>,
+    },
+
+    /// Take a vault snapshot, or list/restore existing ones
+    Snapshot {
+        #[command(subcommand)]
+        action: Option<SnapshotAction>,
+    },
+
+    /// List deleted notes, or restore/purge one
+    Trash {
+        #[command(subcommand)]
+        action: Option<TrashAction>,
+    },
+
+    /// Manage user accounts for a shared vault
+    User {
+        #[command(subcommand)]
+        action: Option<UserAction>,
+    },
+
+    /// Manage clients allowed to use the HTTP MCP endpoint
+    McpClient {
+        #[command(subcommand)]
+        action: Option<McpClientAction>,
+    },
+
+    /// List the MCP tools with their schemas, or call one against the vault
+    McpInspect {
+        /// Tool to call; lists every tool when omitted
+        tool: Option<String>,
+
+        /// JSON file with the tool's arguments (`-` reads stdin)
+        #[arg(short, long)]
+        args: Option<PathBuf>,
+    },
+
+    /// Manage the web UI login password
+    Password {
+        #[command(subcommand)]
+        action: PasswordAction,
+    },
+
+    /// Manage the vault-wide API token required on /api and /mcp
+    ApiToken {
+        #[command(subcommand)]
+        action: ApiTokenAction,
     },
 
     /// Install the auto-start service (runs notidium serve at login)
@@ -131,19 +338,152 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum ImportSource {
+    /// Import an Obsidian vault: notes, attachments and frontmatter
+    Obsidian {
+        /// Path to the Obsidian vault
+        path: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum ExportTarget {
+    /// Per-note metrics (words, links, tags, timestamps, opens) for analysis
+    Stats {
+        /// csv or parquet
+        #[arg(short = 'F', long, default_value = "csv")]
+        format: StatsFormat,
+
+        /// File to write (default: notidium-stats-<date>.<format>)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Embedded chunks as JSON, in the layout `serve` imports from a
+    /// legacy chunks.json
+    Chunks {
+        /// File to write (default: notidium-chunks-<date>.json)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Every note in one self-contained HTML file with search, readable
+    /// offline in any browser
+    Bundle {
+        /// Title shown in the bundle
+        #[arg(long, default_value = "Notes")]
+        title: String,
+
+        /// File to write (default: notidium-bundle-<date>.html)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SnapshotAction {
+    /// Snapshot the vault now (replacing today's) and prune old snapshots
+    Create,
+
+    /// List existing snapshots
+    List,
+
+    /// Restore a single file from a snapshot
+    Restore {
+        /// Snapshot date (YYYY-MM-DD)
+        date: String,
+
+        /// File path relative to the vault (e.g. notes/todo.md)
+        path: PathBuf,
+
+        /// Write the file here instead of its original location
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TrashAction {
+    /// List notes in the trash
+    List,
+
+    /// Move a deleted note back to where it was
+    Restore {
+        /// Note ID (as shown by `notidium trash list`)
+        id: uuid::Uuid,
+    },
+
+    /// Permanently delete a note from the trash
+    Purge {
+        /// Note ID (as shown by `notidium trash list`)
+        id: uuid::Uuid,
+    },
+}
+
+#[derive(Subcommand)]
+enum UserAction {
+    /// List user accounts
+    List,
+
+    /// Add a user and print their API key
+    Add {
+        /// User name
+        name: String,
+
+        /// Limit the user to some notes, e.g. `write:reports/` or `read:#public`
+        /// (repeatable; without scopes the user can access all notes)
+        #[arg(long = "scope", value_name = "SCOPE")]
+        scopes: Vec<Scope>,
+    },
+
+    /// Remove a user, revoking their API key
+    Remove {
+        /// User name
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum McpClientAction {
+    /// List MCP clients
+    List,
+
+    /// Add a client and print its secret
+    Add {
+        /// Client name (its OAuth client ID)
+        name: String,
+    },
+
+    /// Remove a client, revoking its secret
+    Remove {
+        /// Client name
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum PasswordAction {
+    /// Set the password (read from stdin) and require login for the web UI
+    Set,
+
+    /// Remove the password, turning login off
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum ApiTokenAction {
+    /// Generate a new token and print it, replacing any previous one
+    Generate,
+
+    /// Remove the token, leaving the API open unless users or a password are set
+    Clear,
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
-    // Initialize logging
-    let log_level = if cli.verbose { "debug" } else { "info" };
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
-            format!("notidium={},tower_http=debug", log_level).into()
-        }))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-
     // Load environment
     let _ = dotenvy::dotenv();
 
@@ -154,30 +494,110 @@ async fn main() -> anyhow::Result<()> {
         Config::load()?
     };
 
+    // Initialize logging, and trace export for the servers
+    let log_level = if cli.verbose { "debug" } else { "info" };
+    let telemetry_config = match &cli.command {
+        Commands::Serve { path, .. } | Commands::Mcp { path } | Commands::McpHttp { path, .. } => {
+            Some(resolve_config(config.clone(), path.clone(), &cli.vault)?.telemetry)
+        }
+        _ => None,
+    };
+    let telemetry = telemetry::init(log_level, telemetry_config.as_ref())?;
+
     match cli.command {
-        Commands::Init { path } => {
-            let vault_path = path.unwrap_or_else(|| config.vault_path.clone());
+        Commands::Init { path, api_token, yes } => {
+            use std::io::IsTerminal;
+
             let mut init_config = config;
-            init_config.vault_path = vault_path.clone();
+            if let Some(path) = path {
+                init_config.vault_path = path;
+            }
+            let mut plan = InitPlan {
+                api_token,
+                sample_notes: false,
+                index: false,
+            };
+            if !yes && std::io::stdin().is_terminal() && std::io::stdout().is_terminal() {
+                setup_wizard(&mut init_config, &mut plan)?;
+            }
+            let vault_path = init_config.vault_path.clone();
 
             tracing::info!("Initializing vault at {:?}", vault_path);
             init_config.init_vault()?;
+            let token = plan.api_token.then(|| {
+                let (token, hash) = api::generate_api_token();
+                init_config.auth.api_token_hash = Some(hash);
+                token
+            });
             init_config.save()?;
 
             println!("✓ Vault initialized at {}", vault_path.display());
+            if let Some(token) = token {
+                print_api_token(&token);
+            }
+            if !notidium::store::is_case_sensitive(&init_config.data_dir()).unwrap_or(true) {
+                println!("  Filesystem is case-insensitive: notes whose names differ only by case are treated as the same file");
+            }
+            if plan.sample_notes {
+                let added = add_sample_notes(&init_config).await?;
+                println!("✓ Added {} sample notes", added);
+            }
+            if plan.index {
+                println!();
+                index_vault(&init_config, false).await?;
+            }
+
+            let vault_flag = if vault_path == Config::default().vault_path {
+                String::new()
+            } else {
+                format!(" --vault {}", vault_path.display())
+            };
+            let mut steps = vec![format!("Add notes to {}/", init_config.notes_path().display())];
+            if !plan.index {
+                steps.push(format!("Run `notidium{} index` to build the search index", vault_flag));
+            }
+            steps.push(format!("Run `notidium{} serve` to start the API server", vault_flag));
+            steps.push(format!("Run `notidium{} mcp` to start the MCP server for Claude", vault_flag));
             println!("\nNext steps:");
-            println!("  1. Add notes to {}/notes/", vault_path.display());
-            println!("  2. Run `notidium index` to build the search index");
-            println!("  3. Run `notidium serve` to start the API server");
-            println!("  4. Run `notidium mcp` to start the MCP server for Claude");
+            for (i, step) in steps.iter().enumerate() {
+                println!("  {}. {}", i + 1, step);
+            }
         }
 
-        Commands::Serve { path, port, no_mcp } => {
+        Commands::Serve {
+            path,
+            port,
+            bind,
+            open,
+            no_mcp,
+            no_watch,
+        } => {
             let config = resolve_config(config, path, &cli.vault)?;
+            let addr = SocketAddr::new(bind.unwrap_or(config.bind_address), port.unwrap_or(config.http_port));
             let state = initialize_state(&config).await?;
+            if config.search.index.warm_up {
+                warm_up(&state);
+            }
 
-            tracing::info!("Starting HTTP server on port {}", port);
+            // Keep the watcher alive for the lifetime of the server
+            let _watcher = if no_watch {
+                None
+            } else {
+                Some(VaultWatcher::spawn(state.clone())?)
+            };
 
+            let _snapshots = config
+                .snapshot
+                .enabled
+                .then(|| snapshot::spawn_scheduler(SnapshotManager::new(config.clone())));
+            let _webhooks = webhooks::spawn(state.store.clone(), &config.webhooks);
+
+            tracing::info!("Starting HTTP server on {}", addr);
+
+            let user_count = state.users.len();
+            let login_enabled = state.sessions.is_enabled();
+            let token_required = state.api_token.is_enabled();
+            let mcp_clients = config.mcp.clients.len();
             let router = if no_mcp {
                 api::create_router(state)
             } else {
@@ -185,114 +605,357 @@ async fn main() -> anyhow::Result<()> {
                 api::create_router_with_mcp(state)
             };
 
-            let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port)).await?;
+            let listener = tokio::net::TcpListener::bind(addr).await?;
 
-            println!("Notidium server running at http://localhost:{}", port);
-            println!("  UI:       http://localhost:{}/", port);
-            println!("  API:      http://localhost:{}/api/...", port);
-            println!("  API Docs: http://localhost:{}/api/docs", port);
+            let url = base_url(addr);
+            println!("Notidium server running at {}", url);
+            println!("  UI:       {}/", url);
+            println!("  API:      {}/api/...", url);
+            println!("  API Docs: {}/api/docs", url);
             if !no_mcp {
-                println!("  MCP:      http://localhost:{}/mcp", port);
+                println!("  MCP:      {}/mcp", url);
+                if mcp_clients > 0 {
+                    println!("            token required ({} clients)", mcp_clients);
+                }
+            }
+            println!("  Health:   {}/health", url);
+            if login_enabled {
+                println!("  Login:    {}/login", url);
+            }
+            if user_count > 0 {
+                println!("  Auth:     API key required ({} users)", user_count);
+            }
+            if token_required {
+                println!("  Auth:     API token required on /api and /mcp");
+            }
+            warn_if_exposed(addr, user_count > 0 || login_enabled || token_required);
+            if let Some(endpoint) = config.telemetry.otlp_endpoint.as_ref().filter(|_| telemetry.is_exporting()) {
+                println!("  Traces:   exported to {}", endpoint);
+            }
+            if !config.webhooks.endpoints.is_empty() {
+                println!("  Webhooks: {} endpoints", config.webhooks.endpoints.len());
+            }
+            if config.rate_limit.enabled {
+                println!(
+                    "  Limits:   {} req/s per client ({} for semantic search and capture)",
+                    config.rate_limit.requests_per_second, config.rate_limit.expensive_requests_per_second
+                );
             }
-            println!("  Health:   http://localhost:{}/health", port);
 
-            axum::serve(listener, router).await?;
+            if open {
+                if let Err(e) = open_browser(&format!("{}/", url)) {
+                    tracing::warn!("Failed to open the browser: {}", e);
+                }
+            }
+
+            axum::serve(listener, router.into_make_service_with_connect_info::<std::net::SocketAddr>()).await?;
         }
 
         Commands::Mcp { path } => {
             let config = resolve_config(config, path, &cli.vault)?;
             let state = initialize_state(&config).await?;
+            if config.search.index.warm_up {
+                warm_up(&state);
+            }
 
             tracing::info!("Starting MCP server (stdio mode)");
+            let _webhooks = webhooks::spawn(state.store.clone(), &config.webhooks);
 
-            let server = NotidiumServer::new(state.store, state.fulltext, state.semantic, state.embedder, state.chunker);
+            let server = NotidiumServer::new(state.store, state.fulltext, state.semantic, state.embedder, state.chunker)
+                .with_indexer(state.indexer)
+                .with_tools(&ToolFilter::new(&config.mcp, McpTransport::Stdio));
 
             // Run MCP server over stdio
             notidium::mcp::server::serve_stdio(server).await?;
         }
 
-        Commands::McpHttp { path, port } => {
+        Commands::McpHttp { path, port, bind } => {
             let config = resolve_config(config, path, &cli.vault)?;
             let state = initialize_state(&config).await?;
+            if config.search.index.warm_up {
+                warm_up(&state);
+            }
 
-            tracing::info!("Starting MCP server (HTTP mode) on port {}", port);
+            let addr = SocketAddr::new(bind.unwrap_or(config.bind_address), port);
+            tracing::info!("Starting MCP server (HTTP mode) on {}", addr);
+            let _webhooks = webhooks::spawn(state.store.clone(), &config.webhooks);
 
-            let server = NotidiumServer::new(state.store, state.fulltext, state.semantic, state.embedder, state.chunker);
+            let auth = state.auth();
+            let server = NotidiumServer::new(state.store, state.fulltext, state.semantic, state.embedder, state.chunker)
+                .with_indexer(state.indexer)
+                .with_tools(&state.mcp_tools);
 
-            println!("MCP server running at http://localhost:{}/mcp", port);
+            println!("MCP server running at {}/mcp", base_url(addr));
+            if auth.mcp.is_enabled() {
+                println!("  Auth:  token required ({} clients)", config.mcp.clients.len());
+            } else if auth.api_token.is_enabled() {
+                println!("  Auth:  API token required");
+            }
+            warn_if_exposed(
+                addr,
+                auth.mcp.is_enabled() || auth.api_token.is_enabled() || !auth.users.is_empty() || auth.sessions.is_enabled(),
+            );
+            if let Some(endpoint) = config.telemetry.otlp_endpoint.as_ref().filter(|_| telemetry.is_exporting()) {
+                println!("  Traces: exported to {}", endpoint);
+            }
 
             // Run MCP server over HTTP
-            notidium::mcp::server::serve_http(server, port).await?;
+            notidium::mcp::server::serve_http(server, auth, addr).await?;
         }
 
         Commands::Index { force } => {
-            tracing::info!("Indexing notes...");
+            index_vault(&config, force).await?;
+        }
+
+        Commands::Maintain { check } => {
+            if !config.vault_path.exists() {
+                anyhow::bail!(
+                    "Vault not found at {}. Run `notidium init` first.",
+                    config.vault_path.display()
+                );
+            }
 
+            // The manifest as saved, before loading the notes prunes it
+            let manifest = Manifest::load(&config.data_dir().join("manifest.json"))?;
             let store = NoteStore::new(config.clone());
-            let notes = store.load_all().await?;
+            store.load_all().await?;
+            let vectors = VectorStore::open(&config.vectors_path()).await?;
+
+            let report = {
+                let fulltext = FullTextIndex::open_with(&config.tantivy_path(), &config.search)?;
+                let report =
+                    maintain::check(&manifest, &store, &fulltext, &vectors, &config.notes_path()).await?;
+                if !check && !report.is_clean() {
+                    maintain::repair(&report, &store, &fulltext, &vectors).await?;
+                }
+                report
+            };
 
-            println!("Found {} notes", notes.len());
+            println!("{} notes, {} chunks", report.notes, report.chunks);
+            for (count, what) in [
+                (report.orphaned_manifest.len(), "manifest entries of deleted files"),
+                (report.orphaned_documents.len(), "full-text documents of deleted notes"),
+                (report.missing_documents.len(), "notes missing from the full-text index"),
+                (report.orphaned_chunks.len(), "chunks of deleted notes"),
+                (report.invalid_vectors.len(), "notes with invalid embeddings"),
+                (report.missing_vectors.len(), "notes without embeddings"),
+                (report.stale_vectors.len(), "notes changed since they were embedded"),
+            ] {
+                if count > 0 {
+                    println!("  {} {}", count, what);
+                }
+            }
 
-            // Initialize fulltext index
-            let fulltext = FullTextIndex::open(&config.tantivy_path())?;
-            if force {
-                fulltext.rebuild(&notes)?;
+            if report.is_clean() {
+                println!("✓ Indexes match the notes");
+            } else if check {
+                anyhow::bail!("Indexes drifted from the notes, run `notidium maintain` to repair them");
             } else {
-                for note in &notes {
-                    fulltext.index_note(note)?;
+                println!("✓ Repaired indexes");
+                if report.needs_embedding() {
+                    // Embeds exactly the notes missing from the vector store
+                    // or changed since they were embedded
+                    index_vault(&config, false).await?;
                 }
-                fulltext.commit()?;
             }
-            println!("✓ Full-text index updated");
 
-            // Initialize embeddings
-            println!("Loading embedding model (this may take a moment on first run)...");
-            let embedder = Arc::new(Embedder::new()?);
-            let chunker = Chunker::default();
+            if !check {
+                let compaction = vectors.compact().await?;
+                println!(
+                    "✓ Compacted vector store: {} files merged into {}, {} old versions removed ({} bytes)",
+                    compaction.fragments_removed,
+                    compaction.fragments_added,
+                    compaction.versions_removed,
+                    compaction.bytes_removed
+                );
+            }
+        }
 
-            let mut chunks = Vec::new();
-            for note in &notes {
-                let note_chunks = chunker.chunk_note(note);
-                chunks.extend(note_chunks);
+        Commands::Import { source } => match source {
+            ImportSource::Obsidian { path } => {
+                if !config.vault_path.exists() {
+                    anyhow::bail!(
+                        "Vault not found at {}. Run `notidium init` first.",
+                        config.vault_path.display()
+                    );
+                }
+                let summary = notidium::import::obsidian::import_vault(&path, &config)?;
+                println!(
+                    "✓ Imported {} notes and {} attachments from {}",
+                    summary.notes,
+                    summary.attachments,
+                    path.display()
+                );
+                if !summary.skipped.is_empty() {
+                    println!("  Skipped {} notes that already exist in the vault:", summary.skipped.len());
+                    for path in &summary.skipped {
+                        println!("    {}", path.display());
+                    }
+                }
+                if !summary.case_collisions.is_empty() {
+                    println!(
+                        "  Skipped {} notes whose path differs only in case from another note:",
+                        summary.case_collisions.len()
+                    );
+                    for (path, kept) in &summary.case_collisions {
+                        println!("    {} (clashes with {})", path.display(), kept.display());
+                    }
+                }
+                println!();
+                index_vault(&config, false).await?;
+            }
+        },
+
+        Commands::Export {
+            what: Some(ExportTarget::Stats { format, output }),
+            ..
+        } => {
+            let output = output.unwrap_or_else(|| {
+                PathBuf::from(format!(
+                    "notidium-stats-{}.{}",
+                    chrono::Local::now().format("%Y-%m-%d"),
+                    format.extension()
+                ))
+            });
+
+            if !config.vault_path.exists() {
+                anyhow::bail!(
+                    "Vault not found at {}. Run `notidium init` first.",
+                    config.vault_path.display()
+                );
             }
-            println!("Generated {} chunks", chunks.len());
 
-            // Embed chunks in batches
-            let batch_size = config.embedding.batch_size;
-            let total_chunks = chunks.len();
-            let mut embedded_count = 0;
+            let store = NoteStore::new(config.clone());
+            store.load_all().await?;
+            let rows = stats::collect(&store).await?;
+            let file = std::fs::File::create(&output)?;
+            stats::write(&rows, format, std::io::BufWriter::new(file))?;
+            println!("✓ Exported statistics for {} notes to {}", rows.len(), output.display());
+        }
 
-            for batch in chunks.chunks_mut(batch_size) {
-                let texts: Vec<String> = batch.iter().map(|c| c.content.clone()).collect();
-                let embeddings = embedder.embed_batch(texts).await?;
+        Commands::Export {
+            what: Some(ExportTarget::Chunks { output }),
+            ..
+        } => {
+            let output = output.unwrap_or_else(|| {
+                PathBuf::from(format!(
+                    "notidium-chunks-{}.json",
+                    chrono::Local::now().format("%Y-%m-%d")
+                ))
+            });
+
+            let vectors = VectorStore::open(&config.vectors_path()).await?;
+            let file = std::fs::File::create(&output)?;
+            let exported = vectors.export_json(std::io::BufWriter::new(file)).await?;
+            println!("✓ Exported {} chunks to {}", exported, output.display());
+        }
 
-                for (chunk, embedding) in batch.iter_mut().zip(embeddings) {
-                    chunk.prose_embedding = Some(embedding);
-                    chunk.embedded_at = Some(chrono::Utc::now());
-                }
+        Commands::Export {
+            what: Some(ExportTarget::Bundle { title, output }),
+            ..
+        } => {
+            let output = output.unwrap_or_else(|| {
+                PathBuf::from(format!(
+                    "notidium-bundle-{}.html",
+                    chrono::Local::now().format("%Y-%m-%d")
+                ))
+            });
+
+            if !config.vault_path.exists() {
+                anyhow::bail!(
+                    "Vault not found at {}. Run `notidium init` first.",
+                    config.vault_path.display()
+                );
+            }
 
-                embedded_count += batch.len();
-                println!("  Embedded {}/{} chunks", embedded_count, total_chunks);
+            let store = NoteStore::new(config.clone());
+            store.load_all().await?;
+            let file = std::fs::File::create(&output)?;
+            let summary = bundle::write_bundle(&store, &title, std::io::BufWriter::new(file)).await?;
+            println!(
+                "✓ Bundled {} notes and {} images into {}",
+                summary.notes,
+                summary.images,
+                output.display()
+            );
+        }
+
+        Commands::Export { what: None, format, output } => {
+            let output = output.unwrap_or_else(|| {
+                PathBuf::from(format!(
+                    "notidium-export-{}.{}",
+                    chrono::Local::now().format("%Y-%m-%d"),
+                    format.extension()
+                ))
+            });
+
+            if !config.vault_path.exists() {
+                anyhow::bail!(
+                    "Vault not found at {}. Run `notidium init` first.",
+                    config.vault_path.display()
+                );
             }
 
-            // Save chunks to JSON for now (TODO: use LanceDB)
-            let chunks_path = config.data_dir().join("chunks.json");
-            let json = serde_json::to_string_pretty(&chunks)?;
-            std::fs::write(&chunks_path, json)?;
+            match format {
+                ExportFormat::Zip => {
+                    let file = std::fs::File::create(&output)?;
+                    let files = notidium::export::write_zip(&config, file)?;
+                    println!("✓ Exported {} files to {}", files, output.display());
+                }
+                ExportFormat::Json => {
+                    let store = NoteStore::new(config.clone());
+                    store.load_all().await?;
+                    let dump = notidium::export::dump(&store).await;
+                    let file = std::fs::File::create(&output)?;
+                    serde_json::to_writer_pretty(std::io::BufWriter::new(file), &dump)?;
+                    println!("✓ Exported {} notes to {}", dump.notes.len(), output.display());
+                }
+            }
+        }
 
-            println!("✓ Embeddings saved to {}", chunks_path.display());
-            println!("\nIndexing complete!");
+        Commands::Publish { tags, out, title, base_url } => {
+            let store = NoteStore::new(config.clone());
+            store.load_all().await?;
+            let options = notidium::export::site::PublishOptions {
+                tags,
+                out: out.clone(),
+                title,
+                base_url,
+            };
+            let summary = notidium::export::site::publish(&store, &options).await?;
+            if summary.notes == 0 {
+                println!("No notes have the tag{} {}", if options.tags.len() == 1 { "" } else { "s" }, options.tags.join(", "));
+            }
+            println!(
+                "✓ Published {} notes, {} tag pages and {} attachments to {}",
+                summary.notes,
+                summary.tags,
+                summary.attachments,
+                out.display()
+            );
         }
 
-        Commands::Search { query, semantic, limit } => {
+        Commands::Search {
+            query,
+            semantic,
+            mode,
+            limit,
+            fuzzy,
+        } => {
             let state = initialize_state(&config).await?;
 
-            let results = if semantic {
-                let sem = state.semantic.read().await;
-                sem.search(&query, limit).await?
-            } else {
-                state.fulltext.search(&query, limit)?
+            let mode = mode
+                .or(semantic.then_some(SearchMode::Semantic))
+                .unwrap_or_else(|| default_mode(&config.search, SearchClient::Cli, None));
+            let request = SearchRequest {
+                snippets: config.search.snippet_options(None, false),
+                boosts: config.search.boosts,
+                fuzzy,
+                ..SearchRequest::new(&query, mode, limit)
             };
+            let results = SearchDispatcher::new(&state.store, &state.fulltext, &state.semantic)
+                .search(&request)
+                .await?;
 
             if results.is_empty() {
                 println!("No results found for: {}", query);
@@ -324,6 +987,67 @@ async fn main() -> anyhow::Result<()> {
             }
         }
 
+        Commands::Grep {
+            pattern,
+            ignore_case,
+            fixed_strings,
+            limit,
+            json,
+        } => {
+            let store = NoteStore::new(config.clone());
+            let notes = store.load_all().await?;
+            let options = GrepOptions {
+                ignore_case,
+                fixed_strings,
+                max_file_size: Some(config.search.grep_max_file_kb * 1024),
+            };
+
+            if json {
+                let results = grep_notes(&config.notes_path(), &notes, &pattern, options, limit)?;
+                println!("{}", serde_json::to_string_pretty(&results)?);
+            } else {
+                let matches = grep_matches(&config.notes_path(), &notes, &pattern, options, limit)?;
+                if matches.is_empty() {
+                    println!("No matches for: {}", pattern);
+                }
+                for m in &matches {
+                    println!("{}:{}: {}", m.path, m.line, m.text);
+                }
+            }
+        }
+
+        Commands::Seed { notes, code_ratio, seed } => {
+            if !(0.0..=1.0).contains(&code_ratio) {
+                anyhow::bail!("--code-ratio must be between 0 and 1");
+            }
+            if !config.vault_path.exists() {
+                anyhow::bail!(
+                    "Vault not found at {}. Run `notidium init` first.",
+                    config.vault_path.display()
+                );
+            }
+
+            let store = NoteStore::new(config.clone());
+            store.load_all().await?;
+            let generated = seed::generate(&seed::SeedOptions {
+                notes,
+                code_ratio,
+                seed,
+            });
+            let with_code = generated.iter().filter(|n| n.has_code).count();
+            for note in generated {
+                store.create(note.title, note.content, Some(note.tags)).await?;
+            }
+
+            println!(
+                "Created {} notes ({} with code) in {}",
+                notes,
+                with_code,
+                config.notes_path().display()
+            );
+            println!("Run `notidium index` to index them.");
+        }
+
         Commands::Stats => {
             let store = NoteStore::new(config.clone());
             let notes = store.load_all().await?;
@@ -339,11 +1063,8 @@ async fn main() -> anyhow::Result<()> {
             }
 
             // Check for chunks
-            let chunks_path = config.data_dir().join("chunks.json");
-            let chunk_count = if chunks_path.exists() {
-                let content = std::fs::read_to_string(&chunks_path)?;
-                let chunks: Vec<serde_json::Value> = serde_json::from_str(&content)?;
-                chunks.len()
+            let chunk_count = if config.vectors_path().exists() {
+                VectorStore::open(&config.vectors_path()).await?.count().await?
             } else {
                 0
             };
@@ -365,10 +1086,77 @@ async fn main() -> anyhow::Result<()> {
             }
         }
 
-        Commands::List { limit, tag } => {
+        Commands::Lint { fix } => {
+            let store = NoteStore::new(config.clone());
+            let mut notes = store.load_all().await?;
+            notes.retain(|n| !n.is_deleted);
+            notes.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+
+            let attachments = config.attachments_path();
+            let mut fulltext = None;
+            let (mut remaining, mut fixable, mut fixed, mut noted) = (0, 0, 0, 0);
+            for note in notes {
+                let full_path = config.notes_path().join(&note.file_path);
+                let note_dir = full_path.parent().unwrap_or(&config.vault_path).to_path_buf();
+                let mut issues = lint::lint(&note.content, &note_dir, &attachments);
+
+                if fix && issues.iter().any(|i| i.rule.fixable()) {
+                    let before = issues.len();
+                    let note = store.update(note.id, lint::fix(&note.content)).await?;
+                    issues = lint::lint(&note.content, &note_dir, &attachments);
+                    fixed += before - issues.len();
+
+                    if fulltext.is_none() {
+                        fulltext = Some(FullTextIndex::open_with(&config.tantivy_path(), &config.search)?);
+                    }
+                    if let Some(index) = &fulltext {
+                        index.index_note(&note)?;
+                    }
+                }
+
+                for issue in &issues {
+                    println!(
+                        "{}:{}: {}: {}",
+                        note.file_path.display(),
+                        issue.line,
+                        issue.rule,
+                        issue.message
+                    );
+                }
+                if !issues.is_empty() {
+                    noted += 1;
+                }
+                remaining += issues.len();
+                fixable += issues.iter().filter(|i| i.rule.fixable()).count();
+            }
+            if let Some(index) = &fulltext {
+                index.commit()?;
+            }
+
+            if fixed > 0 {
+                println!("✓ Fixed {} issues", fixed);
+            }
+            if remaining == 0 {
+                println!("✓ No issues found");
+            } else {
+                if fixable > 0 {
+                    println!("{} can be fixed with `notidium lint --fix`", fixable);
+                }
+                anyhow::bail!(
+                    "{} issues in {} {}",
+                    remaining,
+                    noted,
+                    if noted == 1 { "note" } else { "notes" }
+                );
+            }
+        }
+
+        Commands::List { limit, tag, author } => {
             let store = NoteStore::new(config);
             let _ = store.load_all().await?;
-            let notes = store.list_paginated(0, limit, tag.as_deref()).await;
+            let notes = store
+                .list_paginated(0, limit, tag.as_deref(), author.as_deref(), NoteSort::default())
+                .await;
 
             if notes.is_empty() {
                 println!("No notes found");
@@ -391,6 +1179,316 @@ async fn main() -> anyhow::Result<()> {
             }
         }
 
+        Commands::New { title, template, folder } => {
+            let store = NoteStore::new(config.clone());
+            let _ = store.load_all().await?;
+            let folder = folder.unwrap_or_default();
+
+            let result = match &template {
+                Some(template) => {
+                    store
+                        .create_from_template(template, &folder, title.clone(), read_clipboard())
+                        .await
+                }
+                None => {
+                    store
+                        .create_in(&folder, title.clone(), format!("# {}\n\n", title), None)
+                        .await
+                }
+            };
+            let note = match result {
+                Err(notidium::error::Error::TemplateNotFound(name)) => {
+                    let available = store.templates().list().await?;
+                    if available.is_empty() {
+                        anyhow::bail!(
+                            "No template named {}; add markdown files to {}",
+                            name,
+                            config.templates_path().display()
+                        );
+                    }
+                    anyhow::bail!("No template named {} (available: {})", name, available.join(", "));
+                }
+                result => result?,
+            };
+
+            let fulltext = FullTextIndex::open_with(&config.tantivy_path(), &config.search)?;
+            fulltext.index_note(&note)?;
+            fulltext.commit()?;
+
+            println!(
+                "✓ Created {}",
+                config.notes_path().join(&note.file_path).display()
+            );
+            println!("  Run `notidium index` to refresh its embeddings");
+        }
+
+        Commands::Snapshot { action } => {
+            let manager = SnapshotManager::new(config.clone());
+
+            match action.unwrap_or(SnapshotAction::Create) {
+                SnapshotAction::Create => {
+                    let today = chrono::Local::now().date_naive();
+                    let stats = manager.create(today)?;
+                    let pruned = manager.prune()?;
+
+                    println!(
+                        "✓ Snapshot {} saved ({} copied, {} hard-linked)",
+                        today, stats.copied, stats.linked
+                    );
+                    if !pruned.is_empty() {
+                        println!("  Pruned {} old snapshot(s)", pruned.len());
+                    }
+                }
+
+                SnapshotAction::List => {
+                    let dates = manager.list()?;
+                    if dates.is_empty() {
+                        println!("No snapshots in {}", config.snapshots_path().display());
+                    } else {
+                        for date in dates.iter().rev() {
+                            println!("• {}", date);
+                        }
+                    }
+                }
+
+                SnapshotAction::Restore { date, path, output } => {
+                    let date = snapshot::parse_date(&date)?;
+                    let restored = manager.restore(date, &path, output.as_deref())?;
+                    println!("✓ Restored {} from {} to {}", path.display(), date, restored.display());
+                }
+            }
+        }
+
+        Commands::Trash { action } => {
+            let store = NoteStore::new(config.clone());
+
+            match action.unwrap_or(TrashAction::List) {
+                TrashAction::List => {
+                    let entries = store.list_trash().await;
+                    if entries.is_empty() {
+                        println!("Trash is empty");
+                    } else {
+                        for entry in entries {
+                            println!(
+                                "• {} ({}, deleted {})\n  {}",
+                                entry.title,
+                                entry.original_path.display(),
+                                entry.deleted_at.format("%Y-%m-%d %H:%M"),
+                                entry.id
+                            );
+                        }
+                    }
+                }
+
+                TrashAction::Restore { id } => {
+                    let _ = store.load_all().await?;
+                    let note = store.restore_from_trash(id).await?;
+
+                    let fulltext = FullTextIndex::open_with(&config.tantivy_path(), &config.search)?;
+                    fulltext.index_note(&note)?;
+                    fulltext.commit()?;
+
+                    println!("✓ Restored {} to {}", note.title, note.file_path.display());
+                    println!("  Run `notidium index` to refresh its embeddings");
+                }
+
+                TrashAction::Purge { id } => {
+                    store.purge_from_trash(id).await?;
+                    println!("✓ Permanently deleted {}", id);
+                }
+            }
+        }
+
+        Commands::User { action } => {
+            let users_path = config.users_path();
+            let mut users = Users::load(&users_path)?;
+
+            match action.unwrap_or(UserAction::List) {
+                UserAction::List => {
+                    if users.is_empty() {
+                        println!("No users (single-user mode, no API key required)");
+                    } else {
+                        for user in users.list() {
+                            println!(
+                                "• {} (added {})",
+                                user.name,
+                                user.created_at.format("%Y-%m-%d")
+                            );
+                            if !user.scopes.is_empty() {
+                                let scopes: Vec<String> =
+                                    user.scopes.iter().map(ToString::to_string).collect();
+                                println!("  Scopes: {}", scopes.join(", "));
+                            }
+                        }
+                    }
+                }
+
+                UserAction::Add { name, scopes } => {
+                    let key = users.add(&name, scopes.clone())?;
+                    users.save(&users_path)?;
+
+                    println!("✓ Added user {}", name.trim());
+                    if !scopes.is_empty() {
+                        let scopes: Vec<String> = scopes.iter().map(ToString::to_string).collect();
+                        println!("  Scopes: {}", scopes.join(", "));
+                    }
+                    println!("  API key: {}", key);
+                    println!("  This key is shown only once. Send it as `Authorization: Bearer <key>`.");
+                    println!("  Restart a running server to apply.");
+                }
+
+                UserAction::Remove { name } => {
+                    if !users.remove(&name) {
+                        anyhow::bail!("No such user: {}", name);
+                    }
+                    users.save(&users_path)?;
+                    println!("✓ Removed user {}", name);
+                    println!("  Restart a running server to apply.");
+                }
+            }
+        }
+
+        Commands::McpClient { action } => {
+            let mut config = config;
+            match action.unwrap_or(McpClientAction::List) {
+                McpClientAction::List => {
+                    if config.mcp.clients.is_empty() {
+                        println!("No MCP clients (the HTTP MCP endpoint needs no token)");
+                    } else {
+                        for client in &config.mcp.clients {
+                            println!("• {}", client.name);
+                        }
+                    }
+                }
+
+                McpClientAction::Add { name } => {
+                    let secret = notidium::mcp::auth::add_client(&mut config.mcp, &name)?;
+                    config.save()?;
+
+                    println!("✓ Added MCP client {}", name.trim());
+                    println!("  Secret: {}", secret);
+                    println!("  This secret is shown only once. Send it as `Authorization: Bearer <secret>`,");
+                    println!("  or exchange it for an access token at /oauth/token (client_credentials grant).");
+                    println!("  Restart a running server to apply.");
+                }
+
+                McpClientAction::Remove { name } => {
+                    if !notidium::mcp::auth::remove_client(&mut config.mcp, &name) {
+                        anyhow::bail!("No such MCP client: {}", name);
+                    }
+                    config.save()?;
+                    println!("✓ Removed MCP client {}", name);
+                    println!("  Restart a running server to apply.");
+                }
+            }
+        }
+
+        Commands::McpInspect { tool, args } => {
+            let state = initialize_state(&config).await?;
+            let server = NotidiumServer::new(state.store, state.fulltext, state.semantic, state.embedder, state.chunker)
+                .with_indexer(state.indexer);
+            let client = notidium::mcp::server::connect_local(server).await?;
+
+            match tool {
+                None => {
+                    let mut tools = client.list_all_tools().await?;
+                    tools.sort_by(|a, b| a.name.cmp(&b.name));
+                    for tool in &tools {
+                        println!("{}", tool.name);
+                        if let Some(description) = &tool.description {
+                            println!("  {}", description);
+                        }
+                        let schema = serde_json::to_string_pretty(&tool.input_schema)?;
+                        for line in schema.lines() {
+                            println!("  {}", line);
+                        }
+                        println!();
+                    }
+                    println!("{} tools", tools.len());
+                }
+
+                Some(name) => {
+                    let arguments = match args {
+                        None => serde_json::Map::new(),
+                        Some(path) => {
+                            let json = if path.as_os_str() == "-" {
+                                std::io::read_to_string(std::io::stdin())?
+                            } else {
+                                std::fs::read_to_string(&path)?
+                            };
+                            match serde_json::from_str(&json)? {
+                                serde_json::Value::Object(arguments) => arguments,
+                                _ => anyhow::bail!("Tool arguments must be a JSON object"),
+                            }
+                        }
+                    };
+                    let result = client
+                        .call_tool(rmcp::model::CallToolRequestParam {
+                            name: name.into(),
+                            arguments: Some(arguments),
+                        })
+                        .await?;
+                    for content in &result.content {
+                        match content.as_text() {
+                            Some(text) => println!("{}", text.text),
+                            None => println!("{}", serde_json::to_string_pretty(content)?),
+                        }
+                    }
+                    if result.is_error == Some(true) {
+                        anyhow::bail!("Tool call failed");
+                    }
+                }
+            }
+            client.cancel().await?;
+        }
+
+        Commands::Password { action } => {
+            let mut config = config;
+            match action {
+                PasswordAction::Set => {
+                    let password = read_password()?;
+                    if password.is_empty() {
+                        anyhow::bail!("Password must not be empty");
+                    }
+                    config.auth.password_hash = Some(api::hash_password(&password)?);
+                    config.save()?;
+                    println!("✓ Password set; the web UI now requires login");
+                }
+
+                PasswordAction::Clear => {
+                    if config.auth.password_hash.take().is_none() {
+                        println!("No password set");
+                        return Ok(());
+                    }
+                    config.save()?;
+                    println!("✓ Password removed; login is off");
+                }
+            }
+            println!("  Restart a running server to apply.");
+        }
+
+        Commands::ApiToken { action } => {
+            let mut config = config;
+            match action {
+                ApiTokenAction::Generate => {
+                    let (token, hash) = api::generate_api_token();
+                    config.auth.api_token_hash = Some(hash);
+                    config.save()?;
+                    print_api_token(&token);
+                }
+
+                ApiTokenAction::Clear => {
+                    if config.auth.api_token_hash.take().is_none() {
+                        println!("No API token set");
+                        return Ok(());
+                    }
+                    config.save()?;
+                    println!("✓ API token removed");
+                }
+            }
+            println!("  Restart a running server to apply.");
+        }
+
         Commands::InstallService { vault, port, force } => {
             let mut cfg = config;
             if let Some(v) = vault {
@@ -478,54 +1576,429 @@ async fn initialize_state(config: &Config) -> anyhow::Result<AppState> {
     tracing::info!("Loaded {} notes", notes.len());
 
     // Initialize fulltext index
-    let fulltext = Arc::new(FullTextIndex::open(&config.tantivy_path())?);
+    let fulltext = Arc::new(FullTextIndex::open_with(&config.tantivy_path(), &config.search)?);
+    if fulltext.needs_rebuild() {
+        fulltext.rebuild(&notes)?;
+        tracing::info!("Rebuilt full-text index for {} notes", notes.len());
+    }
+    if fulltext.analyzer_changed() {
+        tracing::warn!(
+            "Full-text analyzer settings changed since the index was built; run `notidium index --force` to reindex"
+        );
+    }
+    if let Err(e) = index_canvases(&store, &fulltext).await {
+        tracing::warn!("Failed to index canvases: {}", e);
+    }
 
     // Initialize embedder and chunker
-    let embedder = Arc::new(Embedder::new()?);
-    let chunker = Arc::new(Chunker::default());
+    let embedder = Arc::new(Embedder::from_config(&config.embedding)?);
+    let chunker = Arc::new(Chunker::from_config(&config.embedding));
 
     // Initialize semantic search
-    let mut semantic = SemanticSearch::new(embedder.clone());
-
-    // Load chunks if available, filtering out stale chunks whose notes no longer exist
-    let chunks_path = config.data_dir().join("chunks.json");
-    if chunks_path.exists() {
-        let content = std::fs::read_to_string(&chunks_path)?;
-        let chunks: Vec<notidium::types::Chunk> = serde_json::from_str(&content)?;
-        let total_chunks = chunks.len();
-
-        // Get valid note IDs from the store
-        let valid_note_ids: std::collections::HashSet<uuid::Uuid> =
-            notes.iter().map(|n| n.id).collect();
+    let mut semantic = SemanticSearch::with_index(embedder.clone(), config.search.semantic_index)
+        .with_classifier(QueryClassifier::new(&config.search.classifier))
+        .with_quantization(config.embedding.quantization)
+        .with_memory_budget(config.embedding.memory_budget_mb * 1024 * 1024, config.spill_path());
+
+    // Open the vector store, migrating a legacy chunks.json on first start
+    let vectors = VectorStore::open(&config.vectors_path()).await?;
+    let legacy_chunks_path = config.data_dir().join("chunks.json");
+    if legacy_chunks_path.exists() && vectors.count().await? == 0 {
+        let migrated = vectors.migrate_from_json(&legacy_chunks_path).await?;
+        std::fs::rename(&legacy_chunks_path, legacy_chunks_path.with_extension("json.migrated"))?;
+        tracing::info!("Migrated {} chunks from chunks.json into the vector store", migrated);
+    }
 
-        // Filter chunks to only include those with valid note IDs
-        let valid_chunks: Vec<_> = chunks
-            .into_iter()
-            .filter(|c| valid_note_ids.contains(&c.note_id))
-            .collect();
+    // Load chunks a batch at a time, purging stale chunks whose notes no
+    // longer exist
+    let valid_note_ids: std::collections::HashSet<uuid::Uuid> =
+        notes.iter().map(|n| n.id).collect();
+    let mut total_chunks = 0;
+    let mut stale_chunks = 0;
+    let mut stale_notes = std::collections::HashSet::new();
+    let mut models = std::collections::HashSet::new();
+    vectors
+        .for_each_batch(|batch| {
+            total_chunks += batch.len();
+            let (valid_chunks, stale): (Vec<_>, Vec<_>) = batch
+                .into_iter()
+                .partition(|c| valid_note_ids.contains(&c.note_id));
+            stale_chunks += stale.len();
+            stale_notes.extend(stale.iter().map(|c| c.note_id));
+            models.extend(valid_chunks.iter().filter_map(|c| c.embedding_model.clone()));
+            semantic.add_chunks(valid_chunks);
+            Ok(())
+        })
+        .await?;
+
+    if !stale_notes.is_empty() {
+        let stale_notes: Vec<uuid::Uuid> = stale_notes.into_iter().collect();
+        vectors.delete_notes(&stale_notes).await?;
+        tracing::warn!(
+            "Purged {} of {} stale chunks (notes no longer exist)",
+            stale_chunks,
+            total_chunks
+        );
+    }
 
-        let stale_count = total_chunks - valid_chunks.len();
-        if stale_count > 0 {
-            tracing::warn!(
-                "Filtered out {} stale chunks (notes no longer exist). Run `notidium index -f` to rebuild.",
-                stale_count
-            );
-        }
+    embed::check_models(models.iter().map(String::as_str), embedder.prose_model())?;
+    tracing::info!("Loaded {} chunks for semantic search", semantic.chunk_count());
 
-        semantic.load_chunks(valid_chunks);
-        tracing::info!("Loaded {} chunks for semantic search", semantic.chunk_count());
-    }
+    // Chunks embedded from here on are saved as they change
+    let semantic = Arc::new(RwLock::new(semantic.with_vectors(Arc::new(vectors))));
+    let indexer = api::IndexQueue::spawn(
+        store.clone(),
+        fulltext.clone(),
+        semantic.clone(),
+        embedder.clone(),
+        chunker.clone(),
+    );
 
     Ok(AppState {
         store,
         fulltext,
-        semantic: Arc::new(RwLock::new(semantic)),
+        semantic,
         embedder,
         chunker,
         attachments_path: config.attachments_path(),
+        users: Arc::new(Users::load(&config.users_path())?),
+        sessions: Arc::new(api::Sessions::new(&config.auth)),
+        mcp_auth: Arc::new(notidium::mcp::McpAuth::new(&config.mcp)),
+        api_token: Arc::new(api::ApiToken::new(&config.auth)),
+        mcp_tools: ToolFilter::new(&config.mcp, McpTransport::Http),
+        indexer,
+    })
+}
+
+/// Show a newly generated API token and how to use it
+fn print_api_token(token: &str) {
+    println!("  API token: {}", token);
+    println!("  This token is shown only once. Send it as `Authorization: Bearer <token>`;");
+    println!("  in the web UI, enter it as the password when the browser asks to sign in.");
+}
+
+/// Warm up the search indexes and embedding models in the background
+fn warm_up(state: &AppState) {
+    let fulltext = state.fulltext.clone();
+    let semantic = state.semantic.clone();
+    tokio::spawn(async move {
+        let started = std::time::Instant::now();
+        match tokio::task::spawn_blocking(move || fulltext.warm_up()).await {
+            Ok(Err(e)) => tracing::warn!("Full-text warm-up failed: {}", e),
+            Err(e) => tracing::warn!("Full-text warm-up failed: {}", e),
+            Ok(Ok(())) => {}
+        }
+        if let Err(e) = semantic.read().await.warm_up().await {
+            tracing::warn!("Embedding warm-up failed: {}", e);
+        }
+        tracing::info!("Search warmed up in {:.1?}", started.elapsed());
+    });
+}
+
+/// Index the text of every canvas, which shares the full-text index with notes
+async fn index_canvases(store: &NoteStore, fulltext: &FullTextIndex) -> anyhow::Result<usize> {
+    let canvases = store.canvases().list().await?;
+    for info in &canvases {
+        match store.canvases().get(&info.path).await {
+            Ok(canvas) => fulltext.index_canvas(&info.path, &canvas.text())?,
+            Err(e) => tracing::warn!("Skipping canvas {}: {}", info.path, e),
+        }
+    }
+    fulltext.commit()?;
+    Ok(canvases.len())
+}
+
+/// Index all notes in full-text search and the vector store
+async fn index_vault(config: &Config, force: bool) -> anyhow::Result<()> {
+    tracing::info!("Indexing notes...");
+
+    let store = NoteStore::new(config.clone());
+    let notes = store.load_all().await?;
+
+    println!("Found {} notes", notes.len());
+
+    // Initialize fulltext index
+    let fulltext = FullTextIndex::open_with(&config.tantivy_path(), &config.search)?;
+    if force || fulltext.analyzer_changed() {
+        if !force {
+            println!("Analyzer settings changed, rebuilding the full-text index");
+        }
+        fulltext.rebuild(&notes)?;
+    } else {
+        for note in &notes {
+            fulltext.index_note(note)?;
+        }
+        // Documents of notes deleted while no server was running
+        let live: std::collections::HashSet<String> = notes.iter().map(|n| n.id.to_string()).collect();
+        for id in fulltext.document_ids()? {
+            if canvas_path(&id).is_none() && !live.contains(&id) {
+                fulltext.delete_note(&id)?;
+            }
+        }
+        fulltext.commit()?;
+    }
+    let canvases = index_canvases(&store, &fulltext).await?;
+    if canvases > 0 {
+        println!("Indexed {} canvases", canvases);
+    }
+    println!("✓ Full-text index updated");
+    if !config.embedding.enabled {
+        println!("Semantic search is off (embedding.enabled = false), skipping embeddings");
+        println!("\nIndexing complete!");
+        return Ok(());
+    }
+
+    // Only notes changed since the last run, or missing from the vector
+    // store, are re-chunked and re-embedded; the rest keep their embeddings
+    let vectors = VectorStore::open(&config.vectors_path()).await?;
+    if !force {
+        embed::check_models(
+            vectors.embedding_models().await?.iter().map(String::as_str),
+            &config.embedding.prose_model,
+        )?;
+    }
+    let to_embed: Vec<notidium::types::Note> = if force {
+        notes.clone()
+    } else {
+        let embedded: std::collections::HashSet<uuid::Uuid> =
+            vectors.note_ids().await?.into_iter().collect();
+        let changed: std::collections::HashSet<uuid::Uuid> = store
+            .get_notes_needing_reindex()
+            .await
+            .into_iter()
+            .map(|n| n.id)
+            .collect();
+        notes
+            .iter()
+            .filter(|n| changed.contains(&n.id) || !embedded.contains(&n.id))
+            .cloned()
+            .collect()
+    };
+    println!("{} of {} notes changed since the last index", to_embed.len(), notes.len());
+
+    let chunks = if to_embed.is_empty() {
+        Vec::new()
+    } else {
+        println!("Loading embedding model (this may take a moment on first run)...");
+        let embedder = Embedder::from_config(&config.embedding)?;
+        embed::embed_notes(
+            &embedder,
+            &Chunker::from_config(&config.embedding),
+            &to_embed,
+            config.embedding.batch_size,
+            config.embedding.concurrent_batches,
+            |done| println!("  Embedded {} chunks", done),
+        )
+        .await?
+    };
+
+    // Persist chunks to the vector store, replacing each note's previous rows
+    let embedded_ids: Vec<uuid::Uuid> = to_embed.iter().map(|n| n.id).collect();
+    if force {
+        vectors.clear().await?;
+        vectors.add_chunks(&chunks).await?;
+    } else {
+        vectors.replace_notes(&embedded_ids, &chunks).await?;
+
+        // Drop chunks of notes that no longer exist
+        let live: std::collections::HashSet<uuid::Uuid> = notes.iter().map(|n| n.id).collect();
+        let stale: Vec<uuid::Uuid> = vectors
+            .note_ids()
+            .await?
+            .into_iter()
+            .filter(|id| !live.contains(id))
+            .collect();
+        vectors.delete_notes(&stale).await?;
+    }
+    store.mark_indexed(&embedded_ids).await?;
+
+    println!("✓ Embeddings saved to {}", config.vectors_path().display());
+    println!("\nIndexing complete!");
+
+    Ok(())
+}
+
+/// What `notidium init` does beyond creating the vault and its config
+struct InitPlan {
+    api_token: bool,
+    sample_notes: bool,
+    /// Build the indexes once the vault is created
+    index: bool,
+}
+
+/// Ask how the new vault should be set up, filling in `config` and `plan`
+/// from the answers
+fn setup_wizard(config: &mut Config, plan: &mut InitPlan) -> anyhow::Result<()> {
+    use dialoguer::theme::ColorfulTheme;
+    use dialoguer::{Confirm, Input};
+
+    let theme = ColorfulTheme::default();
+    let vault: String = Input::with_theme(&theme)
+        .with_prompt("Vault path")
+        .default(config.vault_path.display().to_string())
+        .interact_text()?;
+    config.vault_path = match (vault.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(vault),
+    };
+
+    config.embedding.enabled = Confirm::with_theme(&theme)
+        .with_prompt("Enable semantic search? Embedding models are downloaded on the first index")
+        .default(config.embedding.enabled)
+        .interact()?;
+    if config.embedding.enabled {
+        config.embedding.code_embeddings = Confirm::with_theme(&theme)
+            .with_prompt("Embed code blocks with a code model as well?")
+            .default(config.embedding.code_embeddings)
+            .interact()?;
+    } else {
+        // MCP clients would otherwise search semantically by default
+        config.search.default_mode = Some(SearchMode::Fulltext);
+    }
+
+    config.http_port = Input::with_theme(&theme)
+        .with_prompt("HTTP port")
+        .default(config.http_port)
+        .interact_text()?;
+    plan.api_token = Confirm::with_theme(&theme)
+        .with_prompt("Require an API token on /api and /mcp?")
+        .default(plan.api_token)
+        .interact()?;
+    plan.sample_notes = Confirm::with_theme(&theme)
+        .with_prompt("Add a few sample notes?")
+        .default(!config.notes_path().exists())
+        .interact()?;
+    plan.index = Confirm::with_theme(&theme)
+        .with_prompt("Build the search index now?")
+        .default(true)
+        .interact()?;
+    println!();
+    Ok(())
+}
+
+/// Notes a new vault can start with: title, content and tags
+const SAMPLE_NOTES: [(&str, &str, &[&str]); 3] = [
+    (
+        "Welcome to Notidium",
+        "Notes are Markdown files in this folder; edit them here, in the web UI or in any editor.\n\n\
+         Link notes with [[Search tips]] style wiki links and tag them with #tags. \
+         Assistants connected over MCP read and write the same notes.",
+        &["notidium"],
+    ),
+    (
+        "Search tips",
+        "Search by keywords, or by meaning with semantic search.\n\n\
+         - `tag:notidium` and `path:inbox/` filter results\n\
+         - `\"exact phrase\"` matches words in order, `title:welcome` only titles\n\
+         - `created:>2024-01-01` and `updated:<=2024-06-30` filter by date\n\n\
+         See [[Welcome to Notidium]] for the basics.",
+        &["notidium", "search"],
+    ),
+    (
+        "Code snippets",
+        "Code blocks are indexed on their own, so code searches find them.\n\n\
+         ```rust\nfn greet(name: &str) -> String {\n    format!(\"Hello, {name}!\")\n}\n```\n",
+        &["notidium", "code"],
+    ),
+];
+
+/// Create the sample notes in `config`'s vault, returning how many were
+/// added
+async fn add_sample_notes(config: &Config) -> anyhow::Result<usize> {
+    let store = NoteStore::new(config.clone());
+    store.load_all().await?;
+    for (title, content, tags) in SAMPLE_NOTES {
+        let tags = tags.iter().map(|t| t.to_string()).collect();
+        store.create(title.to_string(), content.to_string(), Some(tags)).await?;
+    }
+    Ok(SAMPLE_NOTES.len())
+}
+
+/// Read a password from stdin, prompting when it's a terminal
+///
+/// Input is echoed; pipe the password in (`echo "$PW" | notidium password set`)
+/// to keep it off the screen.
+fn read_password() -> anyhow::Result<String> {
+    use std::io::{BufRead, IsTerminal, Write};
+
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        print!("New password: ");
+        std::io::stdout().flush()?;
+    }
+    let mut line = String::new();
+    stdin.lock().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Text on the system clipboard, for the `{{clipboard}}` template placeholder
+///
+/// Uses the platform's clipboard command line tools; `None` when none of them
+/// is available.
+fn read_clipboard() -> Option<String> {
+    const COMMANDS: &[&[&str]] = &[
+        &["pbpaste"],
+        &["wl-paste", "--no-newline"],
+        &["xclip", "-selection", "clipboard", "-o"],
+        &["xsel", "--clipboard", "--output"],
+        &["powershell", "-NoProfile", "-Command", "Get-Clipboard"],
+    ];
+
+    COMMANDS.iter().find_map(|command| {
+        let output = std::process::Command::new(command[0])
+            .args(&command[1..])
+            .stderr(std::process::Stdio::null())
+            .output()
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
     })
 }
 
+/// URL of a server listening on `addr`, naming localhost when it's
+/// reachable there
+fn base_url(addr: SocketAddr) -> String {
+    if addr.ip().is_loopback() || addr.ip().is_unspecified() {
+        format!("http://localhost:{}", addr.port())
+    } else {
+        format!("http://{}", addr)
+    }
+}
+
+/// Warn when a server on `addr` is reachable from other machines without
+/// `authenticated` requests
+fn warn_if_exposed(addr: SocketAddr, authenticated: bool) {
+    if authenticated || addr.ip().is_loopback() {
+        return;
+    }
+    println!(
+        "  Warning:  listening on {} without authentication; anyone on the network can read and edit notes",
+        addr
+    );
+    println!("            (run `notidium api-token generate` to require a token, or pass --bind 127.0.0.1)");
+}
+
+/// Open `url` in the default browser
+fn open_browser(url: &str) -> std::io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    command
+        .arg(url)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
+
 fn truncate(s: &str, max_len: usize) -> String {
     if s.len() <= max_len {
         s.to_string()
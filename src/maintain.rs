@@ -0,0 +1,180 @@
+//! Index maintenance
+//!
+//! The manifest, the full-text index and the vector store each track the
+//! vault on their own, and drift apart when notes change while the server
+//! is down or a write fails halfway. [`check`] compares all three with the
+//! notes on disk, [`repair`] removes what belongs to no note and restores
+//! the full-text documents that are missing; notes whose embeddings are
+//! missing, invalid or out of date are left to be embedded again.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use uuid::Uuid;
+
+use crate::error::Result;
+use crate::search::{canvas_path, FullTextIndex};
+use crate::store::{Manifest, NoteStore, VectorStore};
+
+/// Differences between the notes and what is indexed about them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    /// Notes on disk
+    pub notes: usize,
+    /// Chunks in the vector store
+    pub chunks: usize,
+    /// Manifest entries whose files are gone
+    pub orphaned_manifest: Vec<Uuid>,
+    /// Full-text documents of notes or canvases that no longer exist
+    pub orphaned_documents: Vec<String>,
+    /// Notes missing from the full-text index
+    pub missing_documents: Vec<Uuid>,
+    /// Chunks of notes that no longer exist
+    pub orphaned_chunks: Vec<Uuid>,
+    /// Notes with a chunk lacking a prose embedding, or with one of
+    /// another dimension than the rest
+    pub invalid_vectors: Vec<Uuid>,
+    /// Notes with no chunks in the vector store
+    pub missing_vectors: Vec<Uuid>,
+    /// Notes changed since they were last embedded
+    pub stale_vectors: Vec<Uuid>,
+}
+
+impl Report {
+    /// Whether everything matches
+    pub fn is_clean(&self) -> bool {
+        self.orphaned_manifest.is_empty()
+            && self.orphaned_documents.is_empty()
+            && self.missing_documents.is_empty()
+            && self.orphaned_chunks.is_empty()
+            && self.invalid_vectors.is_empty()
+            && self.missing_vectors.is_empty()
+            && self.stale_vectors.is_empty()
+    }
+
+    /// Whether notes need embedding once [`repair`] has run
+    pub fn needs_embedding(&self) -> bool {
+        !self.missing_vectors.is_empty()
+            || !self.stale_vectors.is_empty()
+            || !self.invalid_vectors.is_empty()
+    }
+}
+
+/// Compare `manifest`, as saved before the notes were loaded, the indexes
+/// and the notes in `store`
+pub async fn check(
+    manifest: &Manifest,
+    store: &NoteStore,
+    fulltext: &FullTextIndex,
+    vectors: &VectorStore,
+    notes_path: &Path,
+) -> Result<Report> {
+    let notes: Vec<_> = store.list().await.into_iter().filter(|n| !n.is_deleted).collect();
+    let live: HashSet<Uuid> = notes.iter().map(|n| n.id).collect();
+    let paths: HashSet<&Path> = notes.iter().map(|n| n.file_path.as_path()).collect();
+
+    let mut report = Report {
+        notes: notes.len(),
+        ..Report::default()
+    };
+
+    report.orphaned_manifest = manifest
+        .entries()
+        .filter(|(path, _)| !paths.contains(path))
+        .map(|(_, entry)| entry.id)
+        .collect();
+
+    let documents = fulltext.document_ids()?;
+    report.orphaned_documents = documents
+        .iter()
+        .filter(|id| match canvas_path(id) {
+            Some(path) => !notes_path.join(path).exists(),
+            None => !id.parse::<Uuid>().is_ok_and(|id| live.contains(&id)),
+        })
+        .cloned()
+        .collect();
+    report.missing_documents = notes
+        .iter()
+        .filter(|n| !documents.contains(&n.id.to_string()))
+        .map(|n| n.id)
+        .collect();
+
+    // Embeddings of the dimension most chunks have are taken as valid
+    let chunks = vectors.load_all().await?;
+    report.chunks = chunks.len();
+    let mut dimensions: HashMap<usize, usize> = HashMap::new();
+    for chunk in &chunks {
+        if let Some(embedding) = &chunk.prose_embedding {
+            *dimensions.entry(embedding.len()).or_default() += 1;
+        }
+    }
+    let dimension = dimensions.into_iter().max_by_key(|&(_, count)| count).map(|(dim, _)| dim);
+
+    let mut embedded = HashSet::new();
+    let mut invalid = HashSet::new();
+    for chunk in &chunks {
+        if !live.contains(&chunk.note_id) {
+            report.orphaned_chunks.push(chunk.id);
+        } else if chunk.prose_embedding.as_ref().map(Vec::len) != dimension {
+            invalid.insert(chunk.note_id);
+        } else {
+            embedded.insert(chunk.note_id);
+        }
+    }
+
+    let stale: HashSet<Uuid> = store.get_notes_needing_reindex().await.iter().map(|n| n.id).collect();
+    for note in &notes {
+        if invalid.contains(&note.id) {
+            report.invalid_vectors.push(note.id);
+        } else if !embedded.contains(&note.id) {
+            report.missing_vectors.push(note.id);
+        } else if stale.contains(&note.id) {
+            report.stale_vectors.push(note.id);
+        }
+    }
+
+    for ids in [
+        &mut report.orphaned_manifest,
+        &mut report.missing_documents,
+        &mut report.orphaned_chunks,
+        &mut report.invalid_vectors,
+        &mut report.missing_vectors,
+        &mut report.stale_vectors,
+    ] {
+        ids.sort();
+    }
+    report.orphaned_documents.sort();
+    Ok(report)
+}
+
+/// Fix what [`check`] found, short of embedding notes
+///
+/// Notes with invalid chunks lose all of them, so that embedding them again
+/// starts from scratch.
+pub async fn repair(
+    report: &Report,
+    store: &NoteStore,
+    fulltext: &FullTextIndex,
+    vectors: &VectorStore,
+) -> Result<()> {
+    // Loading the notes already dropped orphaned entries from the manifest
+    // in memory
+    if !report.orphaned_manifest.is_empty() {
+        store.save_manifest().await?;
+    }
+
+    if !report.orphaned_documents.is_empty() || !report.missing_documents.is_empty() {
+        for id in &report.orphaned_documents {
+            fulltext.delete_note(id)?;
+        }
+        for id in &report.missing_documents {
+            if let Some(note) = store.get(*id).await {
+                fulltext.index_note(&note)?;
+            }
+        }
+        fulltext.commit()?;
+    }
+
+    vectors.delete_chunks(&report.orphaned_chunks).await?;
+    vectors.delete_notes(&report.invalid_vectors).await
+}
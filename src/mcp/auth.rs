@@ -0,0 +1,292 @@
+//! Bearer token authentication for the HTTP MCP endpoint
+//!
+//! Each configured client has a secret. Clients either send the secret itself
+//! as a static bearer token, or exchange it for a short-lived access token with
+//! the OAuth client-credentials grant on `/oauth/token`. Issued access tokens
+//! live in memory and don't survive a restart.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Form, Json, Router,
+};
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::config::{McpClientConfig, McpConfig};
+use crate::error::{Error, Result};
+
+/// Prefix of generated client secrets, to tell them apart from user API keys
+const SECRET_PREFIX: &str = "ntdm_";
+
+/// Prefix of issued access tokens
+const TOKEN_PREFIX: &str = "ntda_";
+
+/// Clients allowed to use the MCP endpoint and the access tokens issued to them
+pub struct McpAuth {
+    clients: Vec<McpClientConfig>,
+    token_lifetime: Duration,
+    /// Issued access tokens by hash, with their client and expiry
+    tokens: Mutex<HashMap<String, (String, DateTime<Utc>)>>,
+}
+
+impl McpAuth {
+    pub fn new(config: &McpConfig) -> Self {
+        Self {
+            clients: config.clients.clone(),
+            token_lifetime: Duration::minutes(config.access_token_minutes.min(i64::MAX as u64 / 60) as i64),
+            tokens: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// MCP auth for a server without configured clients
+    pub fn disabled() -> Self {
+        Self::new(&McpConfig::default())
+    }
+
+    /// Whether clients are configured, and so `/mcp` requires a token
+    pub fn is_enabled(&self) -> bool {
+        !self.clients.is_empty()
+    }
+
+    /// The client a bearer token belongs to, accepting client secrets and
+    /// unexpired access tokens
+    pub fn authenticate(&self, token: &str) -> Option<String> {
+        let hash = hash_secret(token);
+        if let Some(client) = self.clients.iter().find(|c| c.secret_hash == hash) {
+            return Some(client.name.clone());
+        }
+
+        let mut tokens = self.tokens.lock().unwrap();
+        match tokens.get(&hash) {
+            Some((client, expires_at)) if *expires_at > Utc::now() => Some(client.clone()),
+            Some(_) => {
+                tokens.remove(&hash);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Issue an access token for a client's credentials, returning the token
+    /// and its lifetime in seconds
+    pub fn issue_token(&self, client_id: &str, client_secret: &str) -> Option<(String, i64)> {
+        let hash = hash_secret(client_secret);
+        let client = self
+            .clients
+            .iter()
+            .find(|c| c.name == client_id && c.secret_hash == hash)?;
+
+        let token = format!("{}{}", TOKEN_PREFIX, Uuid::new_v4().simple());
+        let now = Utc::now();
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens.retain(|_, (_, expires_at)| *expires_at > now);
+        tokens.insert(hash_secret(&token), (client.name.clone(), now + self.token_lifetime));
+        Some((token, self.token_lifetime.num_seconds()))
+    }
+}
+
+/// Add a client with a freshly generated secret, returning the secret
+pub fn add_client(config: &mut McpConfig, name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() || name.contains(':') {
+        return Err(Error::Other(format!("Invalid client name: {:?}", name)));
+    }
+    if config.clients.iter().any(|c| c.name == name) {
+        return Err(Error::Other(format!("Client already exists: {}", name)));
+    }
+
+    let secret = format!("{}{}", SECRET_PREFIX, Uuid::new_v4().simple());
+    config.clients.push(McpClientConfig {
+        name: name.to_string(),
+        secret_hash: hash_secret(&secret),
+    });
+    Ok(secret)
+}
+
+/// Remove a client, returning whether it existed
+pub fn remove_client(config: &mut McpConfig, name: &str) -> bool {
+    let before = config.clients.len();
+    config.clients.retain(|c| c.name != name);
+    config.clients.len() != before
+}
+
+fn hash_secret(secret: &str) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+/// OAuth token endpoint and discovery metadata
+pub fn routes<S: Clone + Send + Sync + 'static>(auth: Arc<McpAuth>) -> Router<S> {
+    Router::new()
+        .route("/oauth/token", post(token))
+        .route("/.well-known/oauth-authorization-server", get(authorization_server_metadata))
+        .route("/.well-known/oauth-protected-resource", get(protected_resource_metadata))
+        .route("/.well-known/oauth-protected-resource/mcp", get(protected_resource_metadata))
+        .with_state(auth)
+}
+
+/// Paths of [`routes`], reachable without a token
+pub fn is_public_path(path: &str) -> bool {
+    path == "/oauth/token" || path.starts_with("/.well-known/oauth-")
+}
+
+/// `WWW-Authenticate` challenge for requests to `/mcp` without a valid token,
+/// pointing clients at the protected resource metadata
+pub fn challenge(headers: &HeaderMap) -> String {
+    format!(
+        "Bearer realm=\"Notidium MCP\", resource_metadata=\"{}/.well-known/oauth-protected-resource\"",
+        base_url(headers)
+    )
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenRequest {
+    grant_type: String,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct TokenResponse {
+    access_token: String,
+    token_type: &'static str,
+    expires_in: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct TokenError {
+    error: &'static str,
+}
+
+/// Client-credentials grant, with the credentials in the form body or as
+/// HTTP Basic auth
+async fn token(
+    State(auth): State<Arc<McpAuth>>,
+    headers: HeaderMap,
+    Form(req): Form<TokenRequest>,
+) -> Response {
+    if req.grant_type != "client_credentials" {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(TokenError { error: "unsupported_grant_type" }),
+        )
+            .into_response();
+    }
+
+    let credentials = basic_credentials(&headers)
+        .or_else(|| req.client_id.clone().zip(req.client_secret.clone()));
+    let issued = credentials.and_then(|(id, secret)| auth.issue_token(&id, &secret));
+
+    match issued {
+        Some((access_token, expires_in)) => (
+            [(header::CACHE_CONTROL, "no-store")],
+            Json(TokenResponse {
+                access_token,
+                token_type: "Bearer",
+                expires_in,
+            }),
+        )
+            .into_response(),
+        None => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Basic realm=\"Notidium MCP\"")],
+            Json(TokenError { error: "invalid_client" }),
+        )
+            .into_response(),
+    }
+}
+
+/// OAuth authorization server metadata (RFC 8414)
+async fn authorization_server_metadata(headers: HeaderMap) -> Json<serde_json::Value> {
+    let base = base_url(&headers);
+    Json(serde_json::json!({
+        "issuer": base,
+        "token_endpoint": format!("{}/oauth/token", base),
+        "grant_types_supported": ["client_credentials"],
+        "token_endpoint_auth_methods_supported": ["client_secret_basic", "client_secret_post"],
+    }))
+}
+
+/// OAuth protected resource metadata for the MCP endpoint (RFC 9728)
+async fn protected_resource_metadata(headers: HeaderMap) -> Json<serde_json::Value> {
+    let base = base_url(&headers);
+    Json(serde_json::json!({
+        "resource": format!("{}/mcp", base),
+        "authorization_servers": [base],
+        "bearer_methods_supported": ["header"],
+    }))
+}
+
+/// Client ID and secret sent as HTTP Basic auth
+fn basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let encoded = value.strip_prefix("Basic ")?;
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (id, secret) = decoded.split_once(':')?;
+    Some((id.to_string(), secret.to_string()))
+}
+
+/// The URL the server was reached at, as seen by the client
+fn base_url(headers: &HeaderMap) -> String {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("localhost");
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("http");
+    format!("{}://{}", scheme, host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth_with_client(name: &str) -> (McpAuth, String) {
+        let mut config = McpConfig::default();
+        let secret = add_client(&mut config, name).unwrap();
+        (McpAuth::new(&config), secret)
+    }
+
+    #[test]
+    fn test_static_secret_authenticates_client() {
+        let (auth, secret) = auth_with_client("claude");
+        assert!(auth.is_enabled());
+        assert_eq!(auth.authenticate(&secret).as_deref(), Some("claude"));
+        assert_eq!(auth.authenticate("ntdm_wrong"), None);
+        assert!(!McpAuth::disabled().is_enabled());
+    }
+
+    #[test]
+    fn test_client_credentials_issue_access_tokens() {
+        let (auth, secret) = auth_with_client("agent");
+        assert!(auth.issue_token("agent", "wrong").is_none());
+        assert!(auth.issue_token("other", &secret).is_none());
+
+        let (token, expires_in) = auth.issue_token("agent", &secret).unwrap();
+        assert_eq!(expires_in, 3600);
+        assert_eq!(auth.authenticate(&token).as_deref(), Some("agent"));
+    }
+
+    #[test]
+    fn test_add_and_remove_clients() {
+        let mut config = McpConfig::default();
+        add_client(&mut config, "a").unwrap();
+        assert!(add_client(&mut config, "a").is_err());
+        assert!(add_client(&mut config, " ").is_err());
+        assert!(remove_client(&mut config, "a"));
+        assert!(!remove_client(&mut config, "a"));
+    }
+}
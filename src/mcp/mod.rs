@@ -1,5 +1,10 @@
 //! MCP (Model Context Protocol) server
 
+pub mod auth;
+pub mod progress;
 pub mod server;
+pub mod tools;
 
+pub use auth::McpAuth;
 pub use server::NotidiumServer;
+pub use tools::{McpTransport, ToolFilter};
@@ -0,0 +1,43 @@
+//! Progress notifications for long-running tools
+//!
+//! Clients that pass a `progressToken` in a tool call's `_meta` get
+//! `notifications/progress` as the tool works through its items, so they
+//! can show progress instead of timing out. Calls without a token run the
+//! same, silently.
+
+use rmcp::model::{Meta, ProgressNotificationParam, ProgressToken};
+use rmcp::{Peer, RoleServer};
+
+/// Reports a tool's progress through `total` items to the calling client
+pub struct Progress {
+    peer: Peer<RoleServer>,
+    token: Option<ProgressToken>,
+    total: usize,
+}
+
+impl Progress {
+    pub fn new(peer: Peer<RoleServer>, meta: &Meta, total: usize) -> Self {
+        Self {
+            peer,
+            token: meta.get_progress_token(),
+            total,
+        }
+    }
+
+    /// Tell the client `done` items are finished, the last being `message`
+    pub async fn report(&self, done: usize, message: impl Into<String>) {
+        let Some(token) = &self.token else {
+            return;
+        };
+        let param = ProgressNotificationParam {
+            progress_token: token.clone(),
+            progress: done as f64,
+            total: Some(self.total as f64),
+            message: Some(message.into()),
+        };
+        // A client that went away can't be told anything anymore
+        if let Err(e) = self.peer.notify_progress(param).await {
+            tracing::debug!("Failed to send progress notification: {}", e);
+        }
+    }
+}
@@ -1,17 +1,35 @@
 //! MCP server implementation
 
-use rmcp::handler::server::tool::ToolRouter;
+use axum::http::request::Parts;
+use rmcp::handler::server::tool::{ToolCallContext, ToolRouter};
 use rmcp::handler::server::wrapper::Parameters;
-use rmcp::{tool, tool_handler, tool_router, ServerHandler, ServiceExt};
+use rmcp::model::{
+    CallToolRequestParam, CallToolResult, Extensions, ListToolsResult, Meta, PaginatedRequestParam,
+};
+use rmcp::service::{RequestContext, RunningService};
+use rmcp::{tool, tool_router, ErrorData, Peer, RoleClient, RoleServer, ServerHandler, ServiceExt};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
 use crate::embed::{Chunker, Embedder};
-use crate::search::{FullTextIndex, SemanticSearch};
-use crate::store::NoteStore;
-use crate::types::{Note, NoteMeta, SearchResult};
+use crate::search::{
+    default_mode, delete_note_chunks, save_note_chunks, FullTextIndex, SearchClient, SearchDispatcher, SearchMode,
+    SearchRequest, SearchStrategy, SemanticSearch, TagMatch,
+};
+use crate::api::{CurrentUser, IndexQueue, IndexStatus};
+use crate::store::{frontmatter_tags, heading_section, AccessLevel, Contribution, NoteChange, NoteStore, Permissions, VaultEvent};
+use crate::types::{Backlink, Highlight, LinkType, Note, NoteMeta, NoteSort, SearchResult};
+
+use super::progress::Progress;
+use super::tools::ToolFilter;
+
+/// Notes `reindex_vault` embeds between saves of the manifest
+const REINDEX_BATCH: usize = 50;
 
 /// MCP server for Notidium
 #[derive(Clone)]
@@ -21,19 +39,47 @@ pub struct NotidiumServer {
     pub semantic: Arc<RwLock<SemanticSearch>>,
     pub embedder: Arc<Embedder>,
     pub chunker: Arc<Chunker>,
+    /// Background indexing queue reported by `index_status`
+    pub indexer: Option<IndexQueue>,
     tool_router: ToolRouter<Self>,
 }
 
+/// Symbol a code search result was split at, from its chunk type
+fn code_symbol(chunk_type: &str) -> Option<&str> {
+    let start = chunk_type.find("title: Some(\"")? + "title: Some(\"".len();
+    let rest = &chunk_type[start..];
+    rest.get(..rest.find("\")")?)
+}
+
 // Tool parameter types
 
 #[derive(Debug, Deserialize, JsonSchema)]
 pub struct SearchNotesParams {
-    /// Search query
+    /// Search query, with optional filters: `tag:rust`, `path:inbox/`,
+    /// `created:>2024-01-01`, `updated:<=2024-06-30`, `"exact phrase"`
     pub query: String,
     /// Maximum number of results (default: 10)
     pub limit: Option<usize>,
-    /// Use semantic search (default: true)
+    /// fulltext (keywords), semantic (meaning) or hybrid (both); default:
+    /// the mode configured for this client, semantic unless configured
+    pub mode: Option<SearchMode>,
+    /// Shorthand for mode: true for semantic, false for fulltext
     pub semantic: Option<bool>,
+    /// Only notes created or last modified by this user
+    pub author: Option<String>,
+    /// Snippet length in characters (default: 200)
+    pub snippet_len: Option<usize>,
+    /// Return the full text of each matched chunk, saving a get_note call
+    /// (default: false)
+    pub include_full_chunk: Option<bool>,
+    /// Ranking profile weighting title, content, tag and code matches in
+    /// full-text search (default: the configured boosts)
+    pub profile: Option<String>,
+    /// Favor recently updated notes in semantic search, for questions like
+    /// "what was I working on about X" (default: false)
+    pub recency: Option<bool>,
+    /// Days after which the recency bonus halves (default: 30)
+    pub half_life_days: Option<f32>,
 }
 
 #[derive(Debug, Deserialize, JsonSchema)]
@@ -42,6 +88,14 @@ pub struct GetNoteParams {
     pub id: String,
 }
 
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetNoteSectionParams {
+    /// Note ID
+    pub id: String,
+    /// Heading whose section to return (case-insensitive); the whole note when omitted
+    pub heading: Option<String>,
+}
+
 #[derive(Debug, Deserialize, JsonSchema)]
 pub struct GetNoteByTitleParams {
     /// Note title (fuzzy match)
@@ -56,6 +110,8 @@ pub struct ListNotesParams {
     pub offset: Option<usize>,
     /// Filter by tag
     pub tag: Option<String>,
+    /// Only notes created or last modified by this user
+    pub author: Option<String>,
 }
 
 #[derive(Debug, Deserialize, JsonSchema)]
@@ -72,6 +128,9 @@ pub struct SearchCodeParams {
     pub query: String,
     /// Filter by programming language
     pub language: Option<String>,
+    /// Only code defining this symbol (function, class, type...), matched
+    /// case-insensitively against the names code blocks are split at
+    pub symbol: Option<String>,
     /// Maximum number of results (default: 10)
     pub limit: Option<usize>,
 }
@@ -84,6 +143,20 @@ pub struct CreateNoteParams {
     pub content: String,
     /// Tags for the note
     pub tags: Option<Vec<String>>,
+    /// Folder to create the note in, relative to the notes directory
+    pub folder: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CreateFromTemplateParams {
+    /// Template name (file name in the vault's templates folder, without .md)
+    pub template: String,
+    /// Note title, also filled in for {{title}}
+    pub title: String,
+    /// Folder to create the note in, relative to the notes directory
+    pub folder: Option<String>,
+    /// Text filled in for {{clipboard}}
+    pub clipboard: Option<String>,
 }
 
 #[derive(Debug, Deserialize, JsonSchema)]
@@ -110,10 +183,71 @@ pub struct QuickCaptureParams {
     pub source: Option<String>,
 }
 
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CaptureUrlParams {
+    /// Page to capture (http or https)
+    pub url: String,
+}
+
 #[derive(Debug, Deserialize, JsonSchema)]
 pub struct DeleteNoteParams {
     /// Note ID to delete
     pub id: String,
+    /// Delete permanently instead of moving to trash (default: false)
+    pub permanent: Option<bool>,
+    /// Also delete the attachments only this note uses (default: false)
+    pub delete_attachments: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ReindexVaultParams {
+    /// Which notes: incremental (changed or missing, the default) or full
+    pub scope: Option<ReindexScope>,
+    /// Which index: all (the default), fulltext or semantic
+    pub target: Option<ReindexTarget>,
+}
+
+/// Notes `reindex_vault` covers
+#[derive(Debug, Clone, Copy, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReindexScope {
+    /// Notes changed since they were last indexed, or missing from the index
+    #[default]
+    Incremental,
+    /// Every note
+    Full,
+}
+
+/// Indexes `reindex_vault` rebuilds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReindexTarget {
+    #[default]
+    All,
+    Fulltext,
+    Semantic,
+}
+
+impl ReindexTarget {
+    fn fulltext(self) -> bool {
+        self != ReindexTarget::Semantic
+    }
+
+    fn semantic(self) -> bool {
+        self != ReindexTarget::Fulltext
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetBacklinksParams {
+    /// Note ID to find incoming links for
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetHighlightsParams {
+    /// Only highlights in notes with this tag
+    pub tag: Option<String>,
 }
 
 // Response types (serialized as strings for MCP)
@@ -134,6 +268,36 @@ struct NoteResponse {
     updated_at: String,
 }
 
+#[derive(Debug, Serialize)]
+struct NoteSectionResponse {
+    id: String,
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    heading: Option<String>,
+    content: String,
+}
+
+#[derive(Debug, Serialize)]
+struct TitleLookupResponse {
+    #[serde(flatten)]
+    note: NoteResponse,
+    /// The alias the note was found by, when it wasn't its title
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matched_alias: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct BacklinksResponse {
+    backlinks: Vec<Backlink>,
+    total: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct HighlightsResponse {
+    highlights: Vec<Highlight>,
+    total: usize,
+}
+
 #[derive(Debug, Serialize)]
 struct ListResponse {
     notes: Vec<NoteMeta>,
@@ -142,6 +306,113 @@ struct ListResponse {
     limit: usize,
 }
 
+#[derive(Debug, Serialize)]
+struct StarredResponse {
+    notes: Vec<NoteMeta>,
+    total: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct IndexStatusResponse {
+    notes: usize,
+    /// Notes embedded at least once
+    notes_indexed: usize,
+    /// Notes changed since they were last embedded
+    notes_needing_reindex: usize,
+    fulltext_documents: u64,
+    chunks: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_indexed_at: Option<String>,
+    /// Background indexing queue, when the server runs one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    queue: Option<IndexStatus>,
+}
+
+/// What `get_stats` reports, beyond plain counts, to suggest maintenance
+#[derive(Debug, Default)]
+struct VaultHealth {
+    /// Notes changed since they were last embedded
+    needing_reindex: usize,
+    /// Notes missing from the full-text index
+    missing_documents: usize,
+    /// Notes waiting in the background indexing queue
+    queued: usize,
+    last_indexed_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Notes with no links to or from other notes
+    orphans: usize,
+    /// Links to notes that don't exist
+    broken_links: usize,
+    /// Captures waiting in the inbox
+    inbox: usize,
+    trashed: usize,
+    trash_bytes: u64,
+}
+
+impl VaultHealth {
+    /// Maintenance worth suggesting, most pressing first
+    fn hints(&self) -> Vec<String> {
+        let mut hints = Vec::new();
+        let stale = self.needing_reindex.max(self.missing_documents);
+        if stale > 0 && self.queued == 0 {
+            hints.push(format!("{} {} out of date in the indexes: run reindex_vault", stale, notes(stale)));
+        }
+        if self.inbox > 0 {
+            hints.push(format!(
+                "{} {} in the inbox {} triage",
+                self.inbox,
+                if self.inbox == 1 { "capture" } else { "captures" },
+                if self.inbox == 1 { "needs" } else { "need" }
+            ));
+        }
+        if self.broken_links > 0 {
+            hints.push(format!(
+                "{} {} to notes that don't exist: create the notes or fix the links",
+                self.broken_links,
+                if self.broken_links == 1 { "link points" } else { "links point" }
+            ));
+        }
+        if self.orphans > 0 {
+            hints.push(format!(
+                "{} {} no links to or from other notes: link them from related notes",
+                self.orphans,
+                if self.orphans == 1 { "note has" } else { "notes have" }
+            ));
+        }
+        if self.trashed > 0 {
+            hints.push(format!(
+                "{} deleted {} in the trash ({} KB): restore or purge them",
+                self.trashed,
+                notes(self.trashed),
+                self.trash_bytes.div_ceil(1024)
+            ));
+        }
+        hints
+    }
+}
+
+fn notes(count: usize) -> &'static str {
+    if count == 1 {
+        "note"
+    } else {
+        "notes"
+    }
+}
+
+/// Total size of the files under `path`
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
 // Server implementation
 
 #[tool_router]
@@ -159,21 +430,187 @@ impl NotidiumServer {
             semantic,
             embedder,
             chunker,
+            indexer: None,
             tool_router: Self::tool_router(),
         }
     }
 
+    /// Report `indexer`'s progress in `index_status`
+    pub fn with_indexer(mut self, indexer: IndexQueue) -> Self {
+        self.indexer = Some(indexer);
+        self
+    }
+
+    /// Offer only the tools `filter` allows
+    pub fn with_tools(mut self, filter: &ToolFilter) -> Self {
+        for name in filter.named() {
+            if !self.tool_router.has_route(name) {
+                tracing::warn!("Unknown MCP tool in config: {}", name);
+            }
+        }
+        let withheld: Vec<String> = self
+            .tool_router
+            .list_all()
+            .into_iter()
+            .map(|tool| tool.name.to_string())
+            .filter(|name| !filter.allows(name))
+            .collect();
+        for name in &withheld {
+            self.tool_router.remove_route(name);
+        }
+        if !withheld.is_empty() {
+            tracing::info!("MCP tools disabled: {}", withheld.join(", "));
+        }
+        self
+    }
+
+    /// Permissions of the user an HTTP request was authenticated as
+    ///
+    /// Unrestricted on stdio and in vaults without user accounts.
+    fn permissions(extensions: &Extensions) -> Permissions {
+        extensions
+            .get::<Parts>()
+            .map(|parts| crate::api::permissions(&parts.extensions))
+            .unwrap_or_default()
+    }
+
+    /// Name of the user or MCP client an HTTP request was authenticated as
+    fn user_name(extensions: &Extensions) -> Option<String> {
+        extensions
+            .get::<Parts>()
+            .and_then(|parts| parts.extensions.get::<CurrentUser>())
+            .map(|user| user.name.clone())
+    }
+
+    /// Fetch a note the caller has `access` to
+    async fn authorized_note(
+        &self,
+        extensions: &Extensions,
+        id: uuid::Uuid,
+        access: AccessLevel,
+    ) -> crate::error::Result<Note> {
+        let note = self
+            .store
+            .get(id)
+            .await
+            .ok_or_else(|| crate::error::Error::NoteNotFound(id.to_string()))?;
+        Self::permissions(extensions).check_note(access, &note)?;
+        Ok(note)
+    }
+
+    /// Drop results for notes the caller can't read
+    async fn readable_results(
+        &self,
+        permissions: &Permissions,
+        results: Vec<SearchResult>,
+    ) -> Vec<SearchResult> {
+        if !permissions.is_restricted() {
+            return results;
+        }
+        let mut readable = Vec::new();
+        for result in results {
+            let note = match result.note_id.parse::<uuid::Uuid>() {
+                Ok(uuid) => self.store.get(uuid).await,
+                Err(_) => None,
+            };
+            if note.is_some_and(|n| permissions.can_read(&n)) {
+                readable.push(result);
+            }
+        }
+        readable
+    }
+
+    /// How many results to fetch so `limit` remain after filtering
+    fn search_depth(&self, limit: usize, filtered: bool) -> usize {
+        if filtered {
+            self.store.config().search.max_limit.max(limit)
+        } else {
+            limit
+        }
+    }
+
+    /// Save a capture to the inbox and index it
+    async fn store_capture(
+        &self,
+        extensions: &Extensions,
+        content: String,
+        source: Option<String>,
+    ) -> String {
+        match self.store.quick_capture(content, source).await {
+            Ok(note) => {
+                let note = self
+                    .attribute_change(extensions, note, Contribution::Created)
+                    .await;
+                // Index the captured note
+                if let Err(e) = self.index_note(&note).await {
+                    tracing::warn!("Failed to index captured note: {}", e);
+                }
+
+                let tags = note.tags();
+                let response = NoteResponse {
+                    id: note.id.to_string(),
+                    title: note.title,
+                    content: note.content,
+                    tags,
+                    created_at: note.created_at.to_rfc3339(),
+                    updated_at: note.updated_at.to_rfc3339(),
+                };
+                serde_json::to_string_pretty(&response).unwrap_or_else(|e| format!("Error: {}", e))
+            }
+            Err(e) => format!("Error: {}", e),
+        }
+    }
+
+    /// Credit the user an HTTP request was authenticated as, if any, with a
+    /// note change
+    async fn attribute_change(
+        &self,
+        extensions: &Extensions,
+        note: Note,
+        contribution: Contribution,
+    ) -> Note {
+        let Some(CurrentUser { name: user, .. }) = extensions
+            .get::<Parts>()
+            .and_then(|parts| parts.extensions.get::<CurrentUser>())
+        else {
+            return note;
+        };
+        match self.store.attribute(note.id, user, contribution).await {
+            Ok(note) => note,
+            Err(e) => {
+                tracing::warn!("Failed to attribute change to {}: {}", user, e);
+                note
+            }
+        }
+    }
+
     /// Index a note: chunk it, embed chunks, and add to semantic search
     async fn index_note(&self, note: &Note) -> Result<usize, String> {
-        // Remove old chunks for this note
-        {
-            let mut semantic = self.semantic.write().await;
-            semantic.remove_chunks_for_note(note.id);
+        let chunk_count = self.embed_note(note).await?;
+
+        // Index in fulltext as well
+        if let Err(e) = self.fulltext.index_note(note) {
+            tracing::warn!("Failed to index note in fulltext: {}", e);
+        }
+        let _ = self.fulltext.commit();
+
+        Ok(chunk_count)
+    }
+
+    /// Chunk a note, embed the chunks and replace the note's chunks in
+    /// semantic search with them
+    async fn embed_note(&self, note: &Note) -> Result<usize, String> {
+        if !self.store.config().embedding.enabled {
+            return Ok(0);
         }
 
         // Chunk the note
         let mut chunks = self.chunker.chunk_note(note);
+        if let Some(budget) = self.embedder.token_budget().await.map_err(|e| e.to_string())? {
+            chunks = budget.fit(chunks);
+        }
         if chunks.is_empty() {
+            save_note_chunks(&self.semantic, note.id, chunks).await.map_err(|e| e.to_string())?;
             return Ok(0);
         }
 
@@ -192,12 +629,13 @@ impl NotidiumServer {
         if !prose_indices.is_empty() {
             let prose_texts: Vec<String> = prose_indices
                 .iter()
-                .map(|&i| chunks[i].content.clone())
+                .map(|&i| chunks[i].embedding_text())
                 .collect();
             let prose_embeddings = self.embedder.embed_batch_prose(prose_texts).await.map_err(|e| e.to_string())?;
 
             for (idx, embedding) in prose_indices.iter().zip(prose_embeddings) {
                 chunks[*idx].prose_embedding = Some(embedding);
+                chunks[*idx].embedding_model = Some(self.embedder.prose_model().to_string());
                 chunks[*idx].embedded_at = Some(chrono::Utc::now());
             }
         }
@@ -208,67 +646,108 @@ impl NotidiumServer {
         if !code_indices.is_empty() {
             let code_texts: Vec<String> = code_indices
                 .iter()
-                .map(|&i| chunks[i].content.clone())
+                .map(|&i| chunks[i].embedding_text())
                 .collect();
 
-            // Generate both embeddings for code chunks
+            // Generate both embeddings for code chunks, unless code has no
+            // model of its own
             let prose_embeddings = self.embedder.embed_batch_prose(code_texts.clone()).await.map_err(|e| e.to_string())?;
-            let code_embeddings = self.embedder.embed_batch_code(code_texts).await.map_err(|e| e.to_string())?;
+            let code_embeddings = if self.embedder.has_code_model() {
+                self.embedder.embed_batch_code(code_texts).await.map_err(|e| e.to_string())?
+            } else {
+                Vec::new()
+            };
+            let mut code_embeddings = code_embeddings.into_iter();
 
-            for (idx, (prose_emb, code_emb)) in code_indices.iter().zip(prose_embeddings.into_iter().zip(code_embeddings)) {
+            for (idx, prose_emb) in code_indices.iter().zip(prose_embeddings) {
                 chunks[*idx].prose_embedding = Some(prose_emb);
-                chunks[*idx].code_embedding = Some(code_emb);
+                chunks[*idx].code_embedding = code_embeddings.next();
+                chunks[*idx].embedding_model = Some(self.embedder.prose_model().to_string());
                 chunks[*idx].embedded_at = Some(chrono::Utc::now());
             }
         }
 
-        // Add to semantic search
+        // Replace the note's chunks in semantic search
         let chunk_count = chunks.len();
-        {
-            let mut semantic = self.semantic.write().await;
-            for chunk in chunks {
-                semantic.add_chunk(chunk);
-            }
-        }
-
-        // Index in fulltext as well
-        if let Err(e) = self.fulltext.index_note(note) {
-            tracing::warn!("Failed to index note in fulltext: {}", e);
-        }
-        let _ = self.fulltext.commit();
+        save_note_chunks(&self.semantic, note.id, chunks).await.map_err(|e| e.to_string())?;
+        self.store.notify(VaultEvent::NoteIndexed {
+            id: note.id,
+            chunks: chunk_count,
+        });
 
         Ok(chunk_count)
     }
 
     /// Search notes using full-text or semantic search
-    #[tool(description = "Search notes in the knowledge base. Returns ranked results with snippets.")]
-    async fn search_notes(&self, Parameters(params): Parameters<SearchNotesParams>) -> String {
+    #[tool(description = "Search notes in the knowledge base. Returns ranked results with snippets. The query can filter with tag:NAME, path:PREFIX/, created:>YYYY-MM-DD, updated:<=YYYY-MM-DD and \"exact phrase\".")]
+    async fn search_notes(
+        &self,
+        Parameters(params): Parameters<SearchNotesParams>,
+        extensions: Extensions,
+    ) -> String {
         let limit = params.limit.unwrap_or(10);
-        let use_semantic = params.semantic.unwrap_or(true);
-
-        let results = if use_semantic {
-            let semantic = self.semantic.read().await;
-            match semantic.search(&params.query, limit).await {
-                Ok(r) => r,
-                Err(e) => return format!("Error: {}", e),
-            }
-        } else {
-            match self.fulltext.search(&params.query, limit) {
-                Ok(r) => r,
-                Err(e) => return format!("Error: {}", e),
+        let permissions = Self::permissions(&extensions);
+        let config = &self.store.config().search;
+        let mode = params
+            .mode
+            .or(params.semantic.map(|semantic| if semantic { SearchMode::Semantic } else { SearchMode::Fulltext }))
+            .unwrap_or_else(|| default_mode(config, SearchClient::Mcp, Self::user_name(&extensions).as_deref()));
+
+        let Some(boosts) = config.field_boosts(params.profile.as_deref()) else {
+            return format!("Error: Unknown ranking profile: {}", params.profile.unwrap_or_default());
+        };
+        let recency = params.recency.unwrap_or(false).then(|| {
+            let mut recency = config.recency.clone();
+            if let Some(half_life_days) = params.half_life_days {
+                recency.half_life_days = half_life_days;
             }
+            recency
+        });
+        let request = SearchRequest {
+            query: params.query.clone(),
+            mode,
+            // Authorship and scopes aren't indexed, so search as deep as
+            // allowed and filter
+            limit: self.search_depth(limit, params.author.is_some() || permissions.is_restricted()),
+            snippets: config.snippet_options(params.snippet_len, params.include_full_chunk.unwrap_or(false)),
+            boosts,
+            fuzzy: false,
+            query_type: None,
+            strategy: SearchStrategy::default(),
+            recency,
+            tags: Vec::new(),
+            tag_match: TagMatch::default(),
+            created: Vec::new(),
+            updated: Vec::new(),
+            sort: NoteSort::default(),
+        };
+        let dispatcher = SearchDispatcher::new(&self.store, &self.fulltext, &self.semantic);
+        let results = match dispatcher.search(&request).await {
+            Ok(results) => results,
+            Err(e) => return format!("Error: {}", e),
         };
 
         // Enrich results with note titles
         let mut enriched = Vec::new();
         for mut result in results {
-            if let Ok(uuid) = result.note_id.parse::<uuid::Uuid>() {
-                if let Some(note) = self.store.get(uuid).await {
-                    result.title = note.title;
+            let note = match result.note_id.parse::<uuid::Uuid>() {
+                Ok(uuid) => self.store.get(uuid).await,
+                Err(_) => None,
+            };
+            if let Some(author) = &params.author {
+                if !note.as_ref().is_some_and(|n| n.is_authored_by(author)) {
+                    continue;
                 }
             }
+            if permissions.is_restricted() && !note.as_ref().is_some_and(|n| permissions.can_read(n)) {
+                continue;
+            }
+            if let Some(note) = note {
+                result.title = note.title;
+            }
             enriched.push(result);
         }
+        enriched.truncate(limit);
 
         let total = enriched.len();
         let response = SearchResponse {
@@ -281,14 +760,21 @@ impl NotidiumServer {
 
     /// Get a note by its ID
     #[tool(description = "Get full note content by ID")]
-    async fn get_note(&self, Parameters(params): Parameters<GetNoteParams>) -> String {
+    async fn get_note(
+        &self,
+        Parameters(params): Parameters<GetNoteParams>,
+        extensions: Extensions,
+    ) -> String {
         let id = match params.id.parse::<uuid::Uuid>() {
             Ok(id) => id,
             Err(_) => return "Error: Invalid note ID".to_string(),
         };
 
-        match self.store.get(id).await {
-            Some(note) => {
+        match self.authorized_note(&extensions, id, AccessLevel::Read).await {
+            Ok(note) => {
+                if let Err(e) = self.store.record_access(id).await {
+                    tracing::warn!("Failed to record note access: {}", e);
+                }
                 let tags = note.tags();
                 let response = NoteResponse {
                     id: note.id.to_string(),
@@ -300,23 +786,38 @@ impl NotidiumServer {
                 };
                 serde_json::to_string_pretty(&response).unwrap_or_else(|e| format!("Error: {}", e))
             }
-            None => "Error: Note not found".to_string(),
+            Err(e) => format!("Error: {}", e),
         }
     }
 
-    /// Get a note by title (fuzzy match)
-    #[tool(description = "Get note by title with fuzzy matching")]
-    async fn get_note_by_title(&self, Parameters(params): Parameters<GetNoteByTitleParams>) -> String {
-        match self.store.get_by_title(&params.title).await {
-            Some(note) => {
+    /// Get a note by title or alias (fuzzy match)
+    #[tool(description = "Get note by title or frontmatter alias with fuzzy matching; reports the alias that matched")]
+    async fn get_note_by_title(
+        &self,
+        Parameters(params): Parameters<GetNoteByTitleParams>,
+        extensions: Extensions,
+    ) -> String {
+        let found = self
+            .store
+            .get_by_title(&params.title)
+            .await
+            .filter(|(note, _)| Self::permissions(&extensions).can_read(note));
+        match found {
+            Some((note, matched_alias)) => {
+                if let Err(e) = self.store.record_access(note.id).await {
+                    tracing::warn!("Failed to record note access: {}", e);
+                }
                 let tags = note.tags();
-                let response = NoteResponse {
-                    id: note.id.to_string(),
-                    title: note.title,
-                    content: note.content,
-                    tags,
-                    created_at: note.created_at.to_rfc3339(),
-                    updated_at: note.updated_at.to_rfc3339(),
+                let response = TitleLookupResponse {
+                    note: NoteResponse {
+                        id: note.id.to_string(),
+                        title: note.title,
+                        content: note.content,
+                        tags,
+                        created_at: note.created_at.to_rfc3339(),
+                        updated_at: note.updated_at.to_rfc3339(),
+                    },
+                    matched_alias,
                 };
                 serde_json::to_string_pretty(&response).unwrap_or_else(|e| format!("Error: {}", e))
             }
@@ -324,19 +825,76 @@ impl NotidiumServer {
         }
     }
 
+    /// Get a note, or the section under one of its headings, with embedded
+    /// notes inlined
+    #[tool(description = "Get a note, or the section under one of its headings, with notes it embeds via ![[Note]] or ![[Note#Heading]] inlined")]
+    async fn get_note_section(
+        &self,
+        Parameters(params): Parameters<GetNoteSectionParams>,
+        extensions: Extensions,
+    ) -> String {
+        let id = match params.id.parse::<uuid::Uuid>() {
+            Ok(id) => id,
+            Err(_) => return "Error: Invalid note ID".to_string(),
+        };
+
+        let note = match self.authorized_note(&extensions, id, AccessLevel::Read).await {
+            Ok(note) => note,
+            Err(e) => return format!("Error: {}", e),
+        };
+        let text = match &params.heading {
+            Some(heading) => match heading_section(&note.content, heading) {
+                Some(section) => section,
+                None => return format!("Error: Heading not found: {}", heading),
+            },
+            None => note.content.as_str(),
+        };
+
+        let permissions = Self::permissions(&extensions);
+        let content = self
+            .store
+            .transclude(id, text, |n| permissions.can_read(n))
+            .await;
+        if let Err(e) = self.store.record_access(id).await {
+            tracing::warn!("Failed to record note access: {}", e);
+        }
+
+        let response = NoteSectionResponse {
+            id: note.id.to_string(),
+            title: note.title,
+            heading: params.heading,
+            content,
+        };
+        serde_json::to_string_pretty(&response).unwrap_or_else(|e| format!("Error: {}", e))
+    }
+
     /// List notes with pagination
-    #[tool(description = "List notes with pagination and optional tag filter")]
-    async fn list_notes(&self, Parameters(params): Parameters<ListNotesParams>) -> String {
+    #[tool(description = "List notes with pagination and optional tag and author filters")]
+    async fn list_notes(
+        &self,
+        Parameters(params): Parameters<ListNotesParams>,
+        extensions: Extensions,
+    ) -> String {
         let limit = params.limit.unwrap_or(50);
         let offset = params.offset.unwrap_or(0);
+        let permissions = Self::permissions(&extensions);
 
-        let notes = self
+        // Page after applying scopes so restricted users still get full pages
+        let notes: Vec<Note> = self
             .store
-            .list_paginated(offset, limit, params.tag.as_deref())
-            .await;
+            .list_paginated(0, usize::MAX, params.tag.as_deref(), params.author.as_deref(), NoteSort::default())
+            .await
+            .into_iter()
+            .filter(|n| permissions.can_read(n))
+            .skip(offset)
+            .take(limit)
+            .collect();
 
         let all_notes = self.store.list().await;
-        let total = all_notes.iter().filter(|n| !n.is_deleted && !n.is_archived).count();
+        let total = all_notes
+            .iter()
+            .filter(|n| !n.is_deleted && !n.is_archived && permissions.can_read(n))
+            .count();
 
         let response = ListResponse {
             notes: notes.iter().map(NoteMeta::from).collect(),
@@ -348,19 +906,51 @@ impl NotidiumServer {
         serde_json::to_string_pretty(&response).unwrap_or_else(|e| format!("Error: {}", e))
     }
 
+    /// List the caller's starred notes
+    #[tool(description = "List the notes the user starred as personal favorites, most recently starred first")]
+    async fn list_starred_notes(&self, extensions: Extensions) -> String {
+        let permissions = Self::permissions(&extensions);
+        let user = Self::user_name(&extensions).unwrap_or_default();
+        match self.store.starred(&user).await {
+            Ok(notes) => {
+                let notes: Vec<NoteMeta> = notes
+                    .iter()
+                    .filter(|n| permissions.can_read(n))
+                    .map(NoteMeta::from)
+                    .collect();
+                let total = notes.len();
+                let response = StarredResponse { notes, total };
+                serde_json::to_string_pretty(&response).unwrap_or_else(|e| format!("Error: {}", e))
+            }
+            Err(e) => format!("Error: {}", e),
+        }
+    }
+
     /// Find notes related to a given note
     #[tool(description = "Find semantically similar notes to a given note")]
-    async fn find_related(&self, Parameters(params): Parameters<FindRelatedParams>) -> String {
+    async fn find_related(
+        &self,
+        Parameters(params): Parameters<FindRelatedParams>,
+        extensions: Extensions,
+    ) -> String {
         let note_id = match params.note_id.parse::<uuid::Uuid>() {
             Ok(id) => id,
             Err(_) => return "Error: Invalid note ID".to_string(),
         };
+        if let Err(e) = self.authorized_note(&extensions, note_id, AccessLevel::Read).await {
+            return format!("Error: {}", e);
+        }
 
         let limit = params.limit.unwrap_or(5);
+        let permissions = Self::permissions(&extensions);
+        let depth = self.search_depth(limit, permissions.is_restricted());
         let semantic = self.semantic.read().await;
 
-        match semantic.find_similar(note_id, limit).await {
+        match semantic.find_similar(note_id, depth).await {
             Ok(results) => {
+                let mut results = self.readable_results(&permissions, results).await;
+                results.truncate(limit);
+
                 // Enrich with titles
                 let mut enriched = Vec::new();
                 for mut result in results {
@@ -384,31 +974,39 @@ impl NotidiumServer {
     }
 
     /// Search code blocks specifically
-    #[tool(description = "Search code blocks with optional language filter")]
-    async fn search_code(&self, Parameters(params): Parameters<SearchCodeParams>) -> String {
+    #[tool(description = "Search code blocks with optional language and symbol (function, class, type name) filters")]
+    async fn search_code(
+        &self,
+        Parameters(params): Parameters<SearchCodeParams>,
+        extensions: Extensions,
+    ) -> String {
         let limit = params.limit.unwrap_or(10);
+        let permissions = Self::permissions(&extensions);
+        let depth = self.search_depth(limit * 2, permissions.is_restricted());
 
         let semantic = self.semantic.read().await;
-        let results = match semantic.search(&params.query, limit * 2).await {
+        let results = match semantic.search(&params.query, depth).await {
             Ok(r) => r,
             Err(e) => return format!("Error: {}", e),
         };
-
-        // Filter by language if specified
-        let filtered: Vec<_> = if let Some(lang) = &params.language {
-            results
-                .into_iter()
-                .filter(|r| {
-                    r.chunk_type
-                        .as_ref()
-                        .map(|t| t.to_lowercase().contains(&lang.to_lowercase()))
-                        .unwrap_or(false)
-                })
-                .take(limit)
-                .collect()
-        } else {
-            results.into_iter().take(limit).collect()
-        };
+        let results = self.readable_results(&permissions, results).await;
+
+        // Filter by language and symbol if specified
+        let language = params.language.map(|l| l.to_lowercase());
+        let symbol = params.symbol.map(|s| s.to_lowercase());
+        let filtered: Vec<_> = results
+            .into_iter()
+            .filter(|r| {
+                let chunk_type = r.chunk_type.as_deref().unwrap_or_default();
+                language
+                    .as_ref()
+                    .is_none_or(|lang| chunk_type.to_lowercase().contains(lang))
+                    && symbol.as_ref().is_none_or(|symbol| {
+                        code_symbol(chunk_type).is_some_and(|name| name.to_lowercase().contains(symbol))
+                    })
+            })
+            .take(limit)
+            .collect();
 
         let total = filtered.len();
         let response = SearchResponse {
@@ -421,9 +1019,65 @@ impl NotidiumServer {
 
     /// Create a new note
     #[tool(description = "Create a new note with optional tags")]
-    async fn create_note(&self, Parameters(params): Parameters<CreateNoteParams>) -> String {
-        match self.store.create(params.title, params.content, params.tags).await {
+    async fn create_note(
+        &self,
+        Parameters(params): Parameters<CreateNoteParams>,
+        extensions: Extensions,
+    ) -> String {
+        let folder = params.folder.unwrap_or_default();
+        let folder = Path::new(&folder);
+        let tags = params.tags.as_deref().unwrap_or_default();
+        if let Err(e) = Self::permissions(&extensions).check_create(folder, tags) {
+            return format!("Error: {}", e);
+        }
+
+        match self.store.create_in(folder, params.title, params.content, params.tags).await {
+            Ok(note) => {
+                let note = self
+                    .attribute_change(&extensions, note, Contribution::Created)
+                    .await;
+                // Index the note for search
+                if let Err(e) = self.index_note(&note).await {
+                    tracing::warn!("Failed to index note: {}", e);
+                }
+
+                let tags = note.tags();
+                let response = NoteResponse {
+                    id: note.id.to_string(),
+                    title: note.title,
+                    content: note.content,
+                    tags,
+                    created_at: note.created_at.to_rfc3339(),
+                    updated_at: note.updated_at.to_rfc3339(),
+                };
+                serde_json::to_string_pretty(&response).unwrap_or_else(|e| format!("Error: {}", e))
+            }
+            Err(e) => format!("Error: {}", e),
+        }
+    }
+
+    /// Create a note from a template
+    #[tool(description = "Create a new note from a template in the vault's templates folder, filling in {{title}}, {{date}} and {{clipboard}}")]
+    async fn create_from_template(
+        &self,
+        Parameters(params): Parameters<CreateFromTemplateParams>,
+        extensions: Extensions,
+    ) -> String {
+        let folder = params.folder.unwrap_or_default();
+        let folder = Path::new(&folder);
+        if let Err(e) = Self::permissions(&extensions).check_create(folder, &[]) {
+            return format!("Error: {}", e);
+        }
+
+        let result = self
+            .store
+            .create_from_template(&params.template, folder, params.title, params.clipboard)
+            .await;
+        match result {
             Ok(note) => {
+                let note = self
+                    .attribute_change(&extensions, note, Contribution::Created)
+                    .await;
                 // Index the note for search
                 if let Err(e) = self.index_note(&note).await {
                     tracing::warn!("Failed to index note: {}", e);
@@ -440,20 +1094,41 @@ impl NotidiumServer {
                 };
                 serde_json::to_string_pretty(&response).unwrap_or_else(|e| format!("Error: {}", e))
             }
+            Err(e @ crate::error::Error::TemplateNotFound(_)) => {
+                let available = self.store.templates().list().await.unwrap_or_default();
+                format!("Error: {} (available: {})", e, available.join(", "))
+            }
             Err(e) => format!("Error: {}", e),
         }
     }
 
     /// Update a note's content
     #[tool(description = "Replace note content")]
-    async fn update_note(&self, Parameters(params): Parameters<UpdateNoteParams>) -> String {
+    async fn update_note(
+        &self,
+        Parameters(params): Parameters<UpdateNoteParams>,
+        extensions: Extensions,
+    ) -> String {
         let id = match params.id.parse::<uuid::Uuid>() {
             Ok(id) => id,
             Err(_) => return "Error: Invalid note ID".to_string(),
         };
+        let note = match self.authorized_note(&extensions, id, AccessLevel::Write).await {
+            Ok(note) => note,
+            Err(e) => return format!("Error: {}", e),
+        };
+        // The new content's frontmatter replaces the note's tags
+        let tags = frontmatter_tags(&params.content);
+        if !Self::permissions(&extensions).allows(AccessLevel::Write, &note.file_path, &tags) {
+            let e = crate::error::Error::AccessDenied(format!("no write access to notes tagged {}", tags.join(", ")));
+            return format!("Error: {}", e);
+        }
 
         match self.store.update(id, params.content).await {
             Ok(note) => {
+                let note = self
+                    .attribute_change(&extensions, note, Contribution::Updated)
+                    .await;
                 // Re-index the note
                 if let Err(e) = self.index_note(&note).await {
                     tracing::warn!("Failed to re-index note: {}", e);
@@ -476,14 +1151,24 @@ impl NotidiumServer {
 
     /// Append content to a note
     #[tool(description = "Append content to an existing note")]
-    async fn append_to_note(&self, Parameters(params): Parameters<AppendToNoteParams>) -> String {
+    async fn append_to_note(
+        &self,
+        Parameters(params): Parameters<AppendToNoteParams>,
+        extensions: Extensions,
+    ) -> String {
         let id = match params.id.parse::<uuid::Uuid>() {
             Ok(id) => id,
             Err(_) => return "Error: Invalid note ID".to_string(),
         };
+        if let Err(e) = self.authorized_note(&extensions, id, AccessLevel::Write).await {
+            return format!("Error: {}", e);
+        }
 
         match self.store.append(id, params.content).await {
             Ok(note) => {
+                let note = self
+                    .attribute_change(&extensions, note, Contribution::Updated)
+                    .await;
                 // Re-index the note
                 if let Err(e) = self.index_note(&note).await {
                     tracing::warn!("Failed to re-index note: {}", e);
@@ -506,71 +1191,366 @@ impl NotidiumServer {
 
     /// Quick capture to inbox
     #[tool(description = "Quick capture content to inbox with optional source context")]
-    async fn quick_capture(&self, Parameters(params): Parameters<QuickCaptureParams>) -> String {
-        match self.store.quick_capture(params.content, params.source).await {
-            Ok(note) => {
-                // Index the captured note
-                if let Err(e) = self.index_note(&note).await {
-                    tracing::warn!("Failed to index captured note: {}", e);
-                }
+    async fn quick_capture(
+        &self,
+        Parameters(params): Parameters<QuickCaptureParams>,
+        extensions: Extensions,
+    ) -> String {
+        if let Err(e) = Self::permissions(&extensions).check_create(Path::new("inbox"), &[]) {
+            return format!("Error: {}", e);
+        }
 
-                let tags = note.tags();
-                let response = NoteResponse {
-                    id: note.id.to_string(),
-                    title: note.title,
-                    content: note.content,
-                    tags,
-                    created_at: note.created_at.to_rfc3339(),
-                    updated_at: note.updated_at.to_rfc3339(),
-                };
-                serde_json::to_string_pretty(&response).unwrap_or_else(|e| format!("Error: {}", e))
+        self.store_capture(&extensions, params.content, params.source).await
+    }
+
+    /// Clip a web page into the inbox
+    #[tool(description = "Capture a web page as a note: fetches the URL, keeps the main article as markdown and downloads its images")]
+    async fn capture_url(
+        &self,
+        Parameters(params): Parameters<CaptureUrlParams>,
+        extensions: Extensions,
+    ) -> String {
+        if let Err(e) = Self::permissions(&extensions).check_create(Path::new("inbox"), &[]) {
+            return format!("Error: {}", e);
+        }
+
+        let url = match crate::clip::parse_url(&params.url) {
+            Ok(url) => url,
+            Err(e) => return format!("Error: {}", e),
+        };
+        let attachments = self.store.config().attachments_path();
+        match crate::clip::clip(&url, &attachments, &self.store.config().clip).await {
+            Ok(content) => {
+                self.store_capture(&extensions, content, Some(url.to_string()))
+                    .await
             }
             Err(e) => format!("Error: {}", e),
         }
     }
 
     /// Delete a note by ID
-    #[tool(description = "Delete a note by ID (moves to trash)")]
-    async fn delete_note(&self, Parameters(params): Parameters<DeleteNoteParams>) -> String {
+    #[tool(description = "Delete a note by ID (moves to trash, or removes it for good with permanent=true; delete_attachments=true also removes attachments no other note uses)")]
+    async fn delete_note(
+        &self,
+        Parameters(params): Parameters<DeleteNoteParams>,
+        extensions: Extensions,
+    ) -> String {
         let id = match params.id.parse::<uuid::Uuid>() {
             Ok(id) => id,
             Err(_) => return "Error: Invalid note ID".to_string(),
         };
+        let permanent = params.permanent.unwrap_or(false);
+
+        // Get note info before deletion for the response; trashed notes
+        // aren't cached after a restart, so check where they were instead
+        let permissions = Self::permissions(&extensions);
+        let note_title = match self.store.get(id).await {
+            Some(note) => permissions.check_note(AccessLevel::Write, &note).map(|_| Some(note.title)),
+            None => match self.store.get_trashed(id).await {
+                Some(entry) => permissions
+                    .check(AccessLevel::Write, id, &entry.original_path, &[])
+                    .map(|_| None),
+                None => Ok(None),
+            },
+        };
+        let note_title = match note_title {
+            Ok(title) => title,
+            Err(e) => return format!("Error: {}", e),
+        };
+        let attachments = if params.delete_attachments.unwrap_or(false) {
+            self.store.note_attachments(id).await.unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+
+        // Delete the note (moves to trash unless permanent)
+        let result = if permanent {
+            self.store.delete_permanently(id).await
+        } else {
+            self.store.delete(id).await
+        };
+        if let Err(e) = result {
+            return format!("Error: {}", e);
+        }
+
+        // The indexing queue purges deleted notes from the search indexes;
+        // without one, do it here
+        if self.indexer.is_none() {
+            if let Err(e) = delete_note_chunks(&self.semantic, id).await {
+                tracing::warn!("Failed to remove note chunks: {}", e);
+            }
+            if let Err(e) = self.fulltext.delete_note(&id.to_string()) {
+                tracing::warn!("Failed to remove note from fulltext index: {}", e);
+            }
+            let _ = self.fulltext.commit();
+        }
+
+        let title = note_title.unwrap_or_else(|| id.to_string());
+        let mut message = if permanent {
+            format!("Permanently deleted note: {}", title)
+        } else {
+            format!("Successfully deleted note: {}", title)
+        };
+        match self.store.delete_unshared_attachments(id, &attachments).await {
+            Ok(deleted) if !deleted.is_empty() => {
+                message.push_str(&format!(" (and attachments {})", deleted.join(", ")));
+            }
+            Ok(_) => {}
+            Err(e) => message.push_str(&format!(" (failed to delete attachments: {})", e)),
+        }
+        message
+    }
 
-        // Get note info before deletion for the response
-        let note_title = self.store.get(id).await.map(|n| n.title.clone());
+    /// Reindex the vault
+    #[tool(description = "Reindex notes in the full-text index, semantic search or both (target: all, fulltext, semantic). scope=incremental (default) covers notes changed since they were last indexed or missing from the index, scope=full every note. Sends progress notifications when called with a progress token and stops early when cancelled.")]
+    async fn reindex_vault(
+        &self,
+        Parameters(params): Parameters<ReindexVaultParams>,
+        extensions: Extensions,
+        meta: Meta,
+        peer: Peer<RoleServer>,
+        ct: CancellationToken,
+    ) -> String {
+        let permissions = Self::permissions(&extensions);
+        let target = params.target.unwrap_or_default();
+        let mut notes: Vec<Note> = match params.scope.unwrap_or_default() {
+            ReindexScope::Full => self.store.list().await,
+            ReindexScope::Incremental => {
+                let changed: std::collections::HashSet<uuid::Uuid> = self
+                    .store
+                    .get_notes_needing_reindex()
+                    .await
+                    .iter()
+                    .map(|n| n.id)
+                    .collect();
+                let documents = match self.fulltext.document_ids() {
+                    Ok(ids) => ids,
+                    Err(e) => return format!("Error: {}", e),
+                };
+                let semantic = self.semantic.read().await;
+                self.store
+                    .list()
+                    .await
+                    .into_iter()
+                    .filter(|n| {
+                        changed.contains(&n.id)
+                            || (target.fulltext() && !documents.contains(&n.id.to_string()))
+                            || (target.semantic() && semantic.chunks_for_note(n.id).is_empty())
+                    })
+                    .collect()
+            }
+        };
+        notes.retain(|n| !n.is_deleted && permissions.can_write(n));
+        notes.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+
+        let progress = Progress::new(peer, &meta, notes.len());
+        let (mut chunks, mut failed) = (0, 0);
+        let mut indexed = Vec::new();
+        let mut marked = 0;
+        let mut cancelled = false;
+        for (done, note) in notes.iter().enumerate() {
+            if ct.is_cancelled() {
+                cancelled = true;
+                break;
+            }
+            let mut result = Ok(());
+            if target.semantic() {
+                result = self.embed_note(note).await.map(|count| chunks += count);
+            }
+            if target.fulltext() && result.is_ok() {
+                result = self.fulltext.index_note(note).map_err(|e| e.to_string());
+            }
+            match result {
+                Ok(()) => indexed.push(note.id),
+                Err(e) => {
+                    tracing::warn!("Failed to reindex {}: {}", note.title, e);
+                    failed += 1;
+                }
+            }
 
-        // Remove from semantic search index
-        {
-            let mut semantic = self.semantic.write().await;
-            semantic.remove_chunks_for_note(id);
+            // Work done before a cancellation isn't redone on the next run;
+            // notes count as indexed once their embeddings are fresh
+            if indexed.len() - marked == REINDEX_BATCH {
+                if let Err(e) = self.save_reindexed(target, &indexed[marked..]).await {
+                    return format!("Error: {}", e);
+                }
+                marked = indexed.len();
+            }
+            progress.report(done + 1, note.title.clone()).await;
+        }
+        if let Err(e) = self.save_reindexed(target, &indexed[marked..]).await {
+            return format!("Error: {}", e);
         }
 
-        // Remove from fulltext index
-        if let Err(e) = self.fulltext.delete_note(&id.to_string()) {
-            tracing::warn!("Failed to remove note from fulltext index: {}", e);
+        let mut message = if cancelled {
+            format!("Cancelled after reindexing {} of {} notes", indexed.len(), notes.len())
+        } else {
+            format!("Reindexed {} notes", indexed.len())
+        };
+        if target.semantic() {
+            message.push_str(&format!(" ({} chunks)", chunks));
+        }
+        if failed > 0 {
+            message.push_str(&format!(", {} failed", failed));
+        }
+        message
+    }
+
+    /// Commit reindexed notes to the full-text index and the manifest
+    async fn save_reindexed(&self, target: ReindexTarget, ids: &[uuid::Uuid]) -> crate::error::Result<()> {
+        if target.fulltext() {
+            self.fulltext.commit()?;
+        }
+        if target.semantic() {
+            self.store.mark_indexed(ids).await?;
+        }
+        Ok(())
+    }
+
+    /// Report on the indexes
+    #[tool(description = "Report index health: notes, how many are indexed or changed since, full-text documents, semantic chunks, the last index time and the background indexing queue")]
+    async fn index_status(&self, extensions: Extensions) -> String {
+        if Self::permissions(&extensions).is_restricted() {
+            return "Error: Index status requires access to all notes".to_string();
+        }
+        let notes = self.store.list().await.into_iter().filter(|n| !n.is_deleted).count();
+        let manifest = self.store.manifest_stats().await;
+        let status = IndexStatusResponse {
+            notes,
+            notes_indexed: manifest.indexed,
+            notes_needing_reindex: self.store.get_notes_needing_reindex().await.len(),
+            fulltext_documents: self.fulltext.document_count(),
+            chunks: self.semantic.read().await.chunk_count(),
+            last_indexed_at: manifest.last_indexed.map(|at| at.to_rfc3339()),
+            queue: self.indexer.as_ref().map(IndexQueue::status),
+        };
+        serde_json::to_string_pretty(&status).unwrap_or_else(|e| format!("Error: {}", e))
+    }
+
+    /// Pick up notes changed outside the server
+    #[tool(description = "Rescan the vault for notes changed on disk outside Notidium (sync tools, editors) and reload and reindex them, dropping notes whose file is gone")]
+    async fn refresh_vault(&self, extensions: Extensions) -> String {
+        if Self::permissions(&extensions).is_restricted() {
+            return "Error: Refreshing the vault requires access to all notes".to_string();
+        }
+        let changes = match self.store.rescan().await {
+            Ok(changes) => changes,
+            Err(e) => return format!("Error: {}", e),
+        };
+
+        let (mut updated, mut removed) = (0, 0);
+        for change in &changes {
+            match change {
+                NoteChange::Updated(note) => {
+                    updated += 1;
+                    match &self.indexer {
+                        Some(indexer) => {
+                            if let Err(e) = self.fulltext.index_note(note) {
+                                tracing::warn!("Failed to index note in fulltext: {}", e);
+                            }
+                            indexer.index(note);
+                        }
+                        None => {
+                            if let Err(e) = self.index_note(note).await {
+                                tracing::warn!("Failed to re-index note: {}", e);
+                            }
+                        }
+                    }
+                }
+                NoteChange::Removed(id) => {
+                    removed += 1;
+                    // The indexing queue purges removed notes; without one, do it here
+                    if self.indexer.is_none() {
+                        if let Err(e) = delete_note_chunks(&self.semantic, *id).await {
+                            tracing::warn!("Failed to remove note chunks: {}", e);
+                        }
+                        if let Err(e) = self.fulltext.delete_note(&id.to_string()) {
+                            tracing::warn!("Failed to remove note from fulltext index: {}", e);
+                        }
+                    }
+                }
+            }
         }
         let _ = self.fulltext.commit();
 
-        // Delete the note (moves to trash)
-        match self.store.delete(id).await {
-            Ok(()) => {
-                let title = note_title.unwrap_or_else(|| id.to_string());
-                format!("Successfully deleted note: {}", title)
+        format!("Refreshed the vault: {} note(s) reloaded, {} removed", updated, removed)
+    }
+
+    /// Get notes linking to a note
+    #[tool(description = "List notes that link to a given note via [[wiki links]], with the linking line as context")]
+    async fn get_backlinks(
+        &self,
+        Parameters(params): Parameters<GetBacklinksParams>,
+        extensions: Extensions,
+    ) -> String {
+        let id = match params.id.parse::<uuid::Uuid>() {
+            Ok(id) => id,
+            Err(_) => return "Error: Invalid note ID".to_string(),
+        };
+        if let Err(e) = self.authorized_note(&extensions, id, AccessLevel::Read).await {
+            return format!("Error: {}", e);
+        }
+
+        let permissions = Self::permissions(&extensions);
+        match self.store.backlinks(id).await {
+            Ok(backlinks) => {
+                let mut readable = Vec::new();
+                for backlink in backlinks {
+                    let source = match backlink.note_id.parse::<uuid::Uuid>() {
+                        Ok(uuid) => self.store.get(uuid).await,
+                        Err(_) => None,
+                    };
+                    if source.is_some_and(|n| permissions.can_read(&n)) {
+                        readable.push(backlink);
+                    }
+                }
+                let backlinks = readable;
+                let total = backlinks.len();
+                let response = BacklinksResponse { backlinks, total };
+                serde_json::to_string_pretty(&response).unwrap_or_else(|e| format!("Error: {}", e))
+            }
+            Err(e) => format!("Error: {}", e),
+        }
+    }
+
+    /// Get highlighted passages
+    #[tool(description = "List the passages marked ==like this== across the vault, with their notes and lines, optionally only in notes with a tag")]
+    async fn get_highlights(
+        &self,
+        Parameters(params): Parameters<GetHighlightsParams>,
+        extensions: Extensions,
+    ) -> String {
+        let permissions = Self::permissions(&extensions);
+        match self.store.highlights(params.tag.as_deref()).await {
+            Ok(highlights) => {
+                let mut readable = Vec::new();
+                for highlight in highlights {
+                    let note = match highlight.note_id.parse::<uuid::Uuid>() {
+                        Ok(uuid) => self.store.get(uuid).await,
+                        Err(_) => None,
+                    };
+                    if note.is_some_and(|n| permissions.can_read(&n)) {
+                        readable.push(highlight);
+                    }
+                }
+                let total = readable.len();
+                let response = HighlightsResponse {
+                    highlights: readable,
+                    total,
+                };
+                serde_json::to_string_pretty(&response).unwrap_or_else(|e| format!("Error: {}", e))
             }
             Err(e) => format!("Error: {}", e),
         }
     }
 
     /// Get knowledge base statistics
-    #[tool(description = "Get statistics about the knowledge base")]
-    async fn get_stats(&self) -> String {
-        let notes = self.store.list().await;
+    #[tool(description = "Get statistics about the knowledge base, with index freshness, notes pending re-index, orphaned notes, broken links, inbox backlog and trash size, and the maintenance they call for")]
+    async fn get_stats(&self, extensions: Extensions) -> String {
+        let permissions = Self::permissions(&extensions);
+        let notes: Vec<Note> = self.store.list().await.into_iter().filter(|n| permissions.can_read(n)).collect();
         let note_count = notes.iter().filter(|n| !n.is_deleted).count();
 
-        let semantic = self.semantic.read().await;
-        let chunk_count = semantic.chunk_count();
-
         // Count unique tags
         let mut tags = std::collections::HashSet::new();
         for note in &notes {
@@ -579,39 +1559,136 @@ impl NotidiumServer {
             }
         }
 
-        format!(
+        let summary = format!(
+            "- **Tags:** {}\n- **Embedding Model:** {} ({} dimensions)\n",
+            tags.len(),
+            self.embedder.prose_model(),
+            self.embedder.prose_dimension()
+        );
+        // Chunks, the index and maintenance cover the whole vault
+        if permissions.is_restricted() {
+            return format!("# Notidium Knowledge Base Stats\n\n- **Notes:** {}\n{}", note_count, summary);
+        }
+        let health = self.vault_health(&notes).await;
+        let chunk_count = self.semantic.read().await.chunk_count();
+
+        let mut stats = format!(
             "# Notidium Knowledge Base Stats\n\n\
             - **Notes:** {}\n\
             - **Chunks:** {}\n\
-            - **Tags:** {}\n\
-            - **Embedding Model:** BGE-small-en-v1.5 (384 dimensions)\n",
+            {}\n\
+            ## Index\n\n\
+            - **Last indexed:** {}\n\
+            - **Notes pending re-index:** {}\n\
+            - **Notes missing from the full-text index:** {}\n\
+            - **Queued for embedding:** {}\n\n\
+            ## Maintenance\n\n\
+            - **Orphaned notes:** {}\n\
+            - **Broken links:** {}\n\
+            - **Inbox backlog:** {}\n\
+            - **Trash:** {} notes, {} KB\n",
             note_count,
             chunk_count,
-            tags.len()
-        )
+            summary,
+            health.last_indexed_at.map_or_else(|| "never".to_string(), |at| at.to_rfc3339()),
+            health.needing_reindex,
+            health.missing_documents,
+            health.queued,
+            health.orphans,
+            health.broken_links,
+            health.inbox,
+            health.trashed,
+            health.trash_bytes.div_ceil(1024)
+        );
+        let hints = health.hints();
+        if !hints.is_empty() {
+            stats.push_str("\n## Suggested actions\n\n");
+            for hint in hints {
+                stats.push_str(&format!("- {}\n", hint));
+            }
+        }
+        stats
     }
 
-    /// Get all tags
-    #[tool(description = "Get all tags in the knowledge base")]
-    async fn get_tags(&self) -> String {
-        let notes = self.store.list().await;
-        let mut tags = std::collections::HashSet::new();
-
-        for note in &notes {
-            for tag in note.tags() {
-                tags.insert(tag);
+    /// Index freshness, link problems, inbox and trash across `notes`
+    async fn vault_health(&self, notes: &[Note]) -> VaultHealth {
+        let live: Vec<&Note> = notes.iter().filter(|n| !n.is_deleted).collect();
+        let mut linked = std::collections::HashSet::new();
+        let mut broken_links = 0;
+        for link in self.store.links().await {
+            match link.target_note_id {
+                Some(target) if target != link.source_note_id => {
+                    linked.insert(link.source_note_id);
+                    linked.insert(target);
+                }
+                Some(_) => {}
+                None if link.link_type != LinkType::ExternalUrl => broken_links += 1,
+                None => {}
             }
         }
+        let documents = self.fulltext.document_ids().unwrap_or_else(|e| {
+            tracing::warn!("Failed to read full-text documents: {}", e);
+            Default::default()
+        });
+        let trash = self.store.list_trash().await;
+
+        VaultHealth {
+            needing_reindex: self.store.get_notes_needing_reindex().await.len(),
+            missing_documents: live.iter().filter(|n| !documents.contains(&n.id.to_string())).count(),
+            queued: self.indexer.as_ref().map_or(0, |indexer| indexer.status().pending),
+            last_indexed_at: self.store.manifest_stats().await.last_indexed,
+            orphans: live.iter().filter(|n| !linked.contains(&n.id)).count(),
+            broken_links,
+            inbox: live.iter().filter(|n| n.file_path.starts_with("inbox")).count(),
+            trashed: trash.len(),
+            trash_bytes: dir_size(&self.store.config().trash_path()),
+        }
+    }
 
-        let mut sorted: Vec<_> = tags.into_iter().collect();
-        sorted.sort();
+    /// Get all tags
+    #[tool(description = "Get all tags in the knowledge base")]
+    async fn get_tags(&self, extensions: Extensions) -> String {
+        let permissions = Self::permissions(&extensions);
+        let tags: Vec<String> = self
+            .store
+            .tag_counts(&permissions)
+            .await
+            .into_iter()
+            .map(|(tag, _)| tag)
+            .collect();
 
-        serde_json::to_string_pretty(&sorted).unwrap_or_else(|e| format!("Error: {}", e))
+        serde_json::to_string_pretty(&tags).unwrap_or_else(|e| format!("Error: {}", e))
     }
 }
 
-#[tool_handler]
 impl ServerHandler for NotidiumServer {
+    /// Run a tool in its own span, continuing the trace of the HTTP request
+    /// that called it
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let span = tracing::info_span!("mcp_tool", tool = %request.name);
+        if let Some(parts) = context.extensions.get::<Parts>() {
+            crate::telemetry::set_parent_from_headers(&span, &parts.headers);
+        }
+        let tcc = ToolCallContext::new(self, request, context);
+        self.tool_router.call(tcc).instrument(span).await
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, ErrorData> {
+        Ok(ListToolsResult {
+            tools: self.tool_router.list_all(),
+            meta: None,
+            next_cursor: None,
+        })
+    }
+
     fn get_info(&self) -> rmcp::model::ServerInfo {
         rmcp::model::ServerInfo {
             protocol_version: rmcp::model::ProtocolVersion::default(),
@@ -638,20 +1715,33 @@ pub async fn serve_stdio(server: NotidiumServer) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Connect an in-process client to the server, to list and call its tools
+/// without a transport
+pub async fn connect_local(server: NotidiumServer) -> anyhow::Result<RunningService<RoleClient, ()>> {
+    let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+    let (service, client) = tokio::join!(server.serve(server_io), ().serve(client_io));
+    let service = service?;
+    tokio::spawn(async move {
+        if let Err(e) = service.waiting().await {
+            tracing::debug!("Local MCP server stopped: {}", e);
+        }
+    });
+    Ok(client?)
+}
+
 /// Run the MCP server on HTTP
-pub async fn serve_http(server: NotidiumServer, port: u16) -> anyhow::Result<()> {
+pub async fn serve_http(
+    server: NotidiumServer,
+    auth: crate::api::AuthState,
+    addr: std::net::SocketAddr,
+) -> anyhow::Result<()> {
     use axum::routing::get;
-    use axum::Router;
+    use axum::{middleware, Router};
     use rmcp::transport::streamable_http_server::{
         session::local::LocalSessionManager, StreamableHttpService, StreamableHttpServerConfig,
     };
     use tokio_util::sync::CancellationToken;
-
-    let store = server.store.clone();
-    let fulltext = server.fulltext.clone();
-    let semantic = server.semantic.clone();
-    let embedder = server.embedder.clone();
-    let chunker = server.chunker.clone();
+    use tower_http::trace::TraceLayer;
 
     let ct = CancellationToken::new();
 
@@ -661,17 +1751,19 @@ pub async fn serve_http(server: NotidiumServer, port: u16) -> anyhow::Result<()>
     };
 
     let mcp_service = StreamableHttpService::new(
-        move || Ok(NotidiumServer::new(store.clone(), fulltext.clone(), semantic.clone(), embedder.clone(), chunker.clone())),
+        move || Ok(server.clone()),
         Arc::new(LocalSessionManager::default()),
         config,
     );
 
     let app = Router::new()
         .route("/health", get(|| async { "ok" }))
-        .nest_service("/mcp", mcp_service);
+        .nest_service("/mcp", mcp_service)
+        .merge(super::auth::routes(auth.mcp.clone()))
+        .layer(middleware::from_fn_with_state(auth, crate::api::authenticate))
+        .layer(TraceLayer::new_for_http().make_span_with(crate::telemetry::http_request_span));
 
-    let addr = format!("0.0.0.0:{}", port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    let listener = tokio::net::TcpListener::bind(addr).await?;
 
     tracing::info!("Notidium MCP server running at http://{}/mcp", addr);
     tracing::info!("Health check available at http://{}/health", addr);
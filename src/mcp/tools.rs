@@ -0,0 +1,114 @@
+//! Which MCP tools a deployment offers
+//!
+//! `[mcp] enabled_tools` and `disabled_tools` apply to every transport,
+//! `[mcp.stdio]` and `[mcp.http]` narrow them further, so one client can get
+//! a read-only subset while another keeps every tool. A tool is offered when
+//! both levels allow it: it is in `enabled_tools` (or that list is empty)
+//! and not in `disabled_tools`.
+
+use std::collections::HashSet;
+
+use crate::config::{McpConfig, McpToolsConfig};
+
+/// How MCP clients connect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum McpTransport {
+    Stdio,
+    Http,
+}
+
+/// Tools offered on a transport
+#[derive(Debug, Clone, Default)]
+pub struct ToolFilter {
+    levels: Vec<Level>,
+}
+
+#[derive(Debug, Clone)]
+struct Level {
+    enabled: HashSet<String>,
+    disabled: HashSet<String>,
+}
+
+impl Level {
+    fn new(config: &McpToolsConfig) -> Self {
+        Self {
+            enabled: config.enabled_tools.iter().cloned().collect(),
+            disabled: config.disabled_tools.iter().cloned().collect(),
+        }
+    }
+
+    fn allows(&self, tool: &str) -> bool {
+        (self.enabled.is_empty() || self.enabled.contains(tool)) && !self.disabled.contains(tool)
+    }
+}
+
+impl ToolFilter {
+    pub fn new(config: &McpConfig, transport: McpTransport) -> Self {
+        let global = McpToolsConfig {
+            enabled_tools: config.enabled_tools.clone(),
+            disabled_tools: config.disabled_tools.clone(),
+        };
+        let transport = match transport {
+            McpTransport::Stdio => &config.stdio,
+            McpTransport::Http => &config.http,
+        };
+        Self {
+            levels: [&global, transport]
+                .into_iter()
+                .filter(|c| !c.is_empty())
+                .map(Level::new)
+                .collect(),
+        }
+    }
+
+    /// Whether `tool` is offered
+    pub fn allows(&self, tool: &str) -> bool {
+        self.levels.iter().all(|level| level.allows(tool))
+    }
+
+    /// Tools named in the configuration, to catch misspellings
+    pub fn named(&self) -> impl Iterator<Item = &str> {
+        self.levels
+            .iter()
+            .flat_map(|level| level.enabled.iter().chain(&level.disabled))
+            .map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tools(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_everything_allowed_by_default() {
+        let filter = ToolFilter::new(&McpConfig::default(), McpTransport::Http);
+        assert!(filter.allows("delete_note"));
+        assert_eq!(filter.named().count(), 0);
+    }
+
+    #[test]
+    fn test_transport_narrows_global_lists() {
+        let config = McpConfig {
+            disabled_tools: tools(&["delete_note"]),
+            http: McpToolsConfig {
+                enabled_tools: tools(&["search_notes", "get_note", "delete_note"]),
+                disabled_tools: Vec::new(),
+            },
+            ..McpConfig::default()
+        };
+
+        let http = ToolFilter::new(&config, McpTransport::Http);
+        assert!(http.allows("search_notes"));
+        assert!(!http.allows("create_note"));
+        // Disabled globally wins over enabled for the transport
+        assert!(!http.allows("delete_note"));
+
+        let stdio = ToolFilter::new(&config, McpTransport::Stdio);
+        assert!(stdio.allows("create_note"));
+        assert!(!stdio.allows("delete_note"));
+    }
+}
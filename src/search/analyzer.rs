@@ -0,0 +1,225 @@
+//! Tokenizer of the full-text index
+//!
+//! Splits text into runs of alphanumeric characters and lowercases them like
+//! Tantivy's default tokenizer, then drops configured stopwords and stems
+//! words if a stemmer language is set. Protected terms are matched first and
+//! indexed whole, so `C++` or `node.js` aren't split at their punctuation
+//! and are never stemmed.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use rust_stemmers::{Algorithm, Stemmer};
+use tantivy::tokenizer::{Token, TokenStream, Tokenizer};
+
+use crate::config::AnalyzerConfig;
+use crate::error::{Error, Result};
+
+/// Name the tokenizer is registered under in the index
+pub const TOKENIZER_NAME: &str = "notidium";
+
+/// Longest word indexed, in bytes, as Tantivy's `RemoveLongFilter` default
+const MAX_TOKEN_LEN: usize = 40;
+
+/// Tokenizer applying the `[search.analyzer]` settings
+#[derive(Clone)]
+pub struct VaultTokenizer {
+    /// Lowercased protected terms, longest first so the longest match wins
+    protected: Arc<Vec<String>>,
+    stopwords: Arc<HashSet<String>>,
+    stemmer: Option<Arc<Stemmer>>,
+}
+
+impl VaultTokenizer {
+    pub fn new(config: &AnalyzerConfig) -> Result<Self> {
+        let mut protected: Vec<String> = config
+            .protected_terms
+            .iter()
+            .map(|t| t.trim().to_lowercase())
+            .filter(|t| !t.is_empty())
+            .collect();
+        protected.sort_by_key(|t| std::cmp::Reverse(t.len()));
+
+        let stemmer = match &config.stemmer {
+            Some(language) => Some(Arc::new(Stemmer::create(stem_algorithm(language)?))),
+            None => None,
+        };
+
+        Ok(Self {
+            protected: Arc::new(protected),
+            stopwords: Arc::new(config.stopwords.iter().map(|w| w.trim().to_lowercase()).collect()),
+            stemmer,
+        })
+    }
+
+    fn tokenize(&self, text: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut position = 0;
+        let mut i = 0;
+
+        while let Some(c) = text[i..].chars().next() {
+            let at_word_start = !text[..i].chars().next_back().is_some_and(char::is_alphanumeric);
+            if at_word_start {
+                if let Some(len) = self.protected_at(text, i) {
+                    tokens.push(token(i, i + len, position, text[i..i + len].to_lowercase()));
+                    position += 1;
+                    i += len;
+                    continue;
+                }
+            }
+
+            if !c.is_alphanumeric() {
+                i += c.len_utf8();
+                continue;
+            }
+
+            let end = text[i..]
+                .find(|c: char| !c.is_alphanumeric())
+                .map_or(text.len(), |len| i + len);
+            let word = text[i..end].to_lowercase();
+            if word.len() <= MAX_TOKEN_LEN && !self.stopwords.contains(&word) {
+                let word = match &self.stemmer {
+                    Some(stemmer) => stemmer.stem(&word).into_owned(),
+                    None => word,
+                };
+                tokens.push(token(i, end, position, word));
+            }
+            position += 1;
+            i = end;
+        }
+
+        tokens
+    }
+
+    /// Byte length of the protected term starting at `start`, if one ends at
+    /// a word boundary there
+    fn protected_at(&self, text: &str, start: usize) -> Option<usize> {
+        self.protected.iter().find_map(|term| {
+            let end = start + term.len();
+            let candidate = text.get(start..end)?;
+            let ends_word = !text[end..].chars().next().is_some_and(char::is_alphanumeric);
+            (ends_word && candidate.to_lowercase() == *term).then_some(term.len())
+        })
+    }
+}
+
+impl Tokenizer for VaultTokenizer {
+    type TokenStream<'a> = VaultTokenStream;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> Self::TokenStream<'a> {
+        VaultTokenStream {
+            tokens: self.tokenize(text),
+            current: None,
+        }
+    }
+}
+
+/// Tokens of one text, produced up front
+pub struct VaultTokenStream {
+    tokens: Vec<Token>,
+    current: Option<usize>,
+}
+
+impl TokenStream for VaultTokenStream {
+    fn advance(&mut self) -> bool {
+        let next = self.current.map_or(0, |i| i + 1);
+        self.current = Some(next);
+        next < self.tokens.len()
+    }
+
+    fn token(&self) -> &Token {
+        &self.tokens[self.current.unwrap_or(0)]
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.tokens[self.current.unwrap_or(0)]
+    }
+}
+
+fn token(offset_from: usize, offset_to: usize, position: usize, text: String) -> Token {
+    Token {
+        offset_from,
+        offset_to,
+        position,
+        text,
+        position_length: 1,
+    }
+}
+
+/// Snowball stemmer for a language name like `english`
+fn stem_algorithm(language: &str) -> Result<Algorithm> {
+    let algorithm = match language.to_lowercase().as_str() {
+        "arabic" => Algorithm::Arabic,
+        "danish" => Algorithm::Danish,
+        "dutch" => Algorithm::Dutch,
+        "english" => Algorithm::English,
+        "finnish" => Algorithm::Finnish,
+        "french" => Algorithm::French,
+        "german" => Algorithm::German,
+        "greek" => Algorithm::Greek,
+        "hungarian" => Algorithm::Hungarian,
+        "italian" => Algorithm::Italian,
+        "norwegian" => Algorithm::Norwegian,
+        "portuguese" => Algorithm::Portuguese,
+        "romanian" => Algorithm::Romanian,
+        "russian" => Algorithm::Russian,
+        "spanish" => Algorithm::Spanish,
+        "swedish" => Algorithm::Swedish,
+        "tamil" => Algorithm::Tamil,
+        "turkish" => Algorithm::Turkish,
+        _ => return Err(Error::Config(format!("Unknown stemmer language: {}", language))),
+    };
+    Ok(algorithm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(config: &AnalyzerConfig, text: &str) -> Vec<String> {
+        let tokenizer = VaultTokenizer::new(config).unwrap();
+        tokenizer.tokenize(text).into_iter().map(|t| t.text).collect()
+    }
+
+    #[test]
+    fn test_default_splits_and_lowercases() {
+        let config = AnalyzerConfig::default();
+        assert_eq!(texts(&config, "Deploy node.js to K8s!"), ["deploy", "node", "js", "to", "k8s"]);
+    }
+
+    #[test]
+    fn test_protected_terms_stopwords_and_stemming() {
+        let config = AnalyzerConfig {
+            stopwords: vec!["the".into()],
+            protected_terms: vec!["C++".into(), "node.js".into(), "running".into()],
+            stemmer: Some("english".into()),
+        };
+        assert_eq!(
+            texts(&config, "The C++ and Node.js builds, running tests"),
+            ["c++", "and", "node.js", "build", "running", "test"]
+        );
+        // Protected terms only match whole words
+        assert_eq!(texts(&config, "xnode.js"), ["xnode", "js"]);
+    }
+
+    #[test]
+    fn test_positions_skip_stopwords() {
+        let config = AnalyzerConfig {
+            stopwords: vec!["of".into()],
+            ..AnalyzerConfig::default()
+        };
+        let tokens = VaultTokenizer::new(&config).unwrap().tokenize("map of content");
+        let positions: Vec<usize> = tokens.iter().map(|t| t.position).collect();
+        assert_eq!(positions, [0, 2]);
+        assert_eq!((tokens[1].offset_from, tokens[1].offset_to), (7, 14));
+    }
+
+    #[test]
+    fn test_unknown_stemmer_language() {
+        let config = AnalyzerConfig {
+            stemmer: Some("klingon".into()),
+            ..AnalyzerConfig::default()
+        };
+        assert!(VaultTokenizer::new(&config).is_err());
+    }
+}
@@ -0,0 +1,234 @@
+//! Query type classification
+//!
+//! Decides whether a semantic query reads like prose or code, which picks
+//! the embedding model it is matched with. Each signal found in the query
+//! adds its weight to a score; at `hybrid_threshold` the query counts as
+//! hybrid, at `code_threshold` as code. `[search.classifier]` adds signals
+//! and reweighs the built-in ones.
+//!
+//! Searches may name the query type instead; either way, the most recent
+//! decisions are kept for `/api/search/classify`.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::config::ClassifierConfig;
+use crate::types::QueryType;
+
+/// A built-in signal's name and the check finding it in a query
+type BuiltinSignal = (&'static str, fn(&str) -> bool);
+
+/// Built-in signals
+const BUILTIN_SIGNALS: &[BuiltinSignal] = &[
+    ("::", |q| q.contains("::")),
+    ("->", |q| q.contains("->")),
+    ("()", |q| q.contains("()")),
+    ("{}", |q| q.contains("{}")),
+    ("[]", |q| q.contains("[]")),
+    (".unwrap", |q| q.contains(".unwrap")),
+    ("async", |q| q.contains("async ")),
+    ("fn", |q| q.contains("fn ")),
+    ("def", |q| q.contains("def ")),
+    ("func", |q| q.contains("func ")),
+    ("const", |q| q.contains("const ")),
+    ("let", |q| q.contains("let ")),
+    ("var", |q| q.contains("var ")),
+    (".rs", |q| q.contains(".rs")),
+    (".py", |q| q.contains(".py")),
+    (".ts", |q| q.contains(".ts")),
+    (".js", |q| q.contains(".js")),
+    ("camelCase", has_camel_case),
+    ("snake_case", has_snake_case),
+];
+
+/// How a query was classified, and why
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Classification {
+    pub query_type: QueryType,
+    /// Sum of the weights of the signals found
+    pub score: f32,
+    /// Signals found in the query
+    pub signals: Vec<String>,
+}
+
+/// A query type picked for a search
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ClassificationDecision {
+    pub query: String,
+    /// What the rules made of the query
+    pub classification: Classification,
+    /// Type the search asked for, overriding the rules
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requested: Option<QueryType>,
+    /// Type the query was matched as
+    pub query_type: QueryType,
+    pub at: DateTime<Utc>,
+}
+
+/// Most recent classification decisions, oldest first
+#[derive(Debug, Default)]
+pub struct DecisionLog {
+    decisions: Mutex<VecDeque<ClassificationDecision>>,
+}
+
+impl DecisionLog {
+    /// Decisions kept
+    pub const CAPACITY: usize = 50;
+
+    pub fn record(&self, decision: ClassificationDecision) {
+        let mut decisions = self.decisions.lock().unwrap();
+        if decisions.len() == Self::CAPACITY {
+            decisions.pop_front();
+        }
+        decisions.push_back(decision);
+    }
+
+    pub fn recent(&self) -> Vec<ClassificationDecision> {
+        self.decisions.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// Query classifier with a vault's rules
+#[derive(Debug, Clone)]
+pub struct QueryClassifier {
+    /// Extra signals, matched as substrings, with their weights
+    signals: Vec<(String, f32)>,
+    builtin_weight: f32,
+    code_threshold: f32,
+    hybrid_threshold: f32,
+}
+
+impl Default for QueryClassifier {
+    fn default() -> Self {
+        Self::new(&ClassifierConfig::default())
+    }
+}
+
+impl QueryClassifier {
+    pub fn new(config: &ClassifierConfig) -> Self {
+        Self {
+            signals: config
+                .signals
+                .iter()
+                .filter(|s| !s.pattern.is_empty())
+                .map(|s| (s.pattern.clone(), s.weight))
+                .collect(),
+            builtin_weight: config.builtin_weight,
+            code_threshold: config.code_threshold,
+            hybrid_threshold: config.hybrid_threshold,
+        }
+    }
+
+    /// Classify a query as prose-like, code-like, or hybrid
+    pub fn classify(&self, query: &str) -> Classification {
+        let mut score = 0.0;
+        let mut signals = Vec::new();
+        for (name, check) in BUILTIN_SIGNALS {
+            if check(query) {
+                score += self.builtin_weight;
+                signals.push(name.to_string());
+            }
+        }
+        for (pattern, weight) in &self.signals {
+            if query.contains(pattern.as_str()) {
+                score += weight;
+                signals.push(pattern.clone());
+            }
+        }
+
+        let query_type = if score >= self.code_threshold {
+            QueryType::Code
+        } else if score >= self.hybrid_threshold {
+            QueryType::Hybrid
+        } else {
+            QueryType::Prose
+        };
+        Classification {
+            query_type,
+            score,
+            signals,
+        }
+    }
+}
+
+fn has_camel_case(s: &str) -> bool {
+    let chars: Vec<char> = s.chars().collect();
+    for i in 1..chars.len() {
+        if chars[i].is_uppercase() && chars[i - 1].is_lowercase() {
+            return true;
+        }
+    }
+    false
+}
+
+fn has_snake_case(s: &str) -> bool {
+    s.contains('_')
+        && s.chars()
+            .any(|c| c.is_alphabetic() && c.is_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ClassifierSignal;
+
+    #[test]
+    fn test_extra_signals_and_thresholds() {
+        let config = ClassifierConfig {
+            signals: vec![
+                ClassifierSignal {
+                    pattern: "kubectl".to_string(),
+                    weight: 2.0,
+                },
+                ClassifierSignal {
+                    pattern: "recipe".to_string(),
+                    weight: -1.0,
+                },
+            ],
+            ..ClassifierConfig::default()
+        };
+        let classifier = QueryClassifier::new(&config);
+
+        let kubectl = classifier.classify("kubectl rollout");
+        assert_eq!(kubectl.query_type, QueryType::Code);
+        assert_eq!(kubectl.score, 2.0);
+        assert_eq!(kubectl.signals, vec!["kubectl".to_string()]);
+
+        // A prose signal cancels a built-in one
+        assert_eq!(classifier.classify("let recipe rest").query_type, QueryType::Prose);
+    }
+
+    #[test]
+    fn test_builtin_weight() {
+        let config = ClassifierConfig {
+            builtin_weight: 2.0,
+            ..ClassifierConfig::default()
+        };
+        let classifier = QueryClassifier::new(&config);
+        assert_eq!(classifier.classify("parse_config helper").query_type, QueryType::Code);
+        assert_eq!(QueryClassifier::default().classify("parse_config helper").query_type, QueryType::Hybrid);
+    }
+
+    #[test]
+    fn test_decision_log_keeps_most_recent() {
+        let classifier = QueryClassifier::default();
+        let log = DecisionLog::default();
+        for i in 0..DecisionLog::CAPACITY + 2 {
+            let query = format!("query {}", i);
+            log.record(ClassificationDecision {
+                classification: classifier.classify(&query),
+                query,
+                requested: None,
+                query_type: QueryType::Prose,
+                at: Utc::now(),
+            });
+        }
+        let recent = log.recent();
+        assert_eq!(recent.len(), DecisionLog::CAPACITY);
+        assert_eq!(recent[0].query, "query 2");
+    }
+}
@@ -0,0 +1,468 @@
+//! Running searches in the caller's mode
+//!
+//! The HTTP API, the MCP tools and the CLI all search through
+//! [`SearchDispatcher`], so full-text, semantic and hybrid search mean the
+//! same thing everywhere. A search that doesn't name a mode gets the one
+//! [`default_mode`] picks for whoever sent it, and filters in the query
+//! (see [`SearchQuery`]) work in every mode.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use utoipa::ToSchema;
+
+use crate::config::{FieldBoosts, RecencyConfig, SearchConfig};
+use crate::error::{Error, Result};
+use crate::store::NoteStore;
+use crate::types::{NoteSort, QueryType, SearchResult, SortField};
+
+use super::{
+    apply_feedback, apply_recency, feedback_terms, fuse_subquery_results, note_factors, reciprocal_rank_fusion, DateFilter, DocumentFilter, FullTextIndex,
+    QueryDecomposer, SearchQuery, SearchStrategy, SemanticSearch, SnippetOptions, TagMatch,
+};
+
+/// How a search ranks results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// Tantivy keyword search
+    Fulltext,
+    /// Embedding similarity search
+    Semantic,
+    /// Both, merged with reciprocal rank fusion
+    Hybrid,
+}
+
+impl fmt::Display for SearchMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SearchMode::Fulltext => "fulltext",
+            SearchMode::Semantic => "semantic",
+            SearchMode::Hybrid => "hybrid",
+        })
+    }
+}
+
+impl FromStr for SearchMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "fulltext" => Ok(SearchMode::Fulltext),
+            "semantic" => Ok(SearchMode::Semantic),
+            "hybrid" => Ok(SearchMode::Hybrid),
+            _ => Err(Error::Search(format!(
+                "Unknown search mode {:?}, expected fulltext, semantic or hybrid",
+                s
+            ))),
+        }
+    }
+}
+
+/// The interface a search arrives through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchClient {
+    /// `/api/search`, used by the web UI
+    Http,
+    /// The `search_notes` MCP tool, over stdio or HTTP
+    Mcp,
+    /// `notidium search`
+    Cli,
+}
+
+impl SearchClient {
+    /// Key of the interface in `search.client_modes`
+    pub fn key(self) -> &'static str {
+        match self {
+            SearchClient::Http => "http",
+            SearchClient::Mcp => "mcp",
+            SearchClient::Cli => "cli",
+        }
+    }
+
+    /// Mode used when nothing is configured: assistants phrase questions,
+    /// people type keywords
+    fn builtin_mode(self) -> SearchMode {
+        match self {
+            SearchClient::Mcp => SearchMode::Semantic,
+            SearchClient::Http | SearchClient::Cli => SearchMode::Fulltext,
+        }
+    }
+}
+
+/// Mode of a search from `client` that doesn't name one
+///
+/// `user` is the API key account or MCP client making the request, if known.
+/// The first of these wins: `search.client_modes` for `user`, then for the
+/// interface (`http`, `mcp` or `cli`), then `search.default_mode`, then the
+/// interface's own default (semantic for MCP, full-text otherwise).
+pub fn default_mode(config: &SearchConfig, client: SearchClient, user: Option<&str>) -> SearchMode {
+    user.and_then(|user| config.client_modes.get(user))
+        .or_else(|| config.client_modes.get(client.key()))
+        .copied()
+        .or(config.default_mode)
+        .unwrap_or_else(|| client.builtin_mode())
+}
+
+/// A search to run
+#[derive(Debug, Clone)]
+pub struct SearchRequest {
+    pub query: String,
+    pub mode: SearchMode,
+    pub limit: usize,
+    pub snippets: SnippetOptions,
+    /// Full-text field weights (full-text and hybrid modes)
+    pub boosts: FieldBoosts,
+    /// Also match words a typo or two away from the query's (full-text
+    /// and hybrid modes)
+    pub fuzzy: bool,
+    /// Match the query as this instead of classifying it (semantic and
+    /// hybrid modes)
+    pub query_type: Option<QueryType>,
+    /// Whether to split the query into sub-queries (semantic and hybrid
+    /// modes)
+    pub strategy: SearchStrategy,
+    /// Favor recently updated notes this much (semantic and hybrid modes)
+    pub recency: Option<RecencyConfig>,
+    /// Only notes with these tags, on top of `tag:` filters in the query
+    pub tags: Vec<String>,
+    /// Whether a note needs all of `tags` or one of them
+    pub tag_match: TagMatch,
+    /// Only notes created within these, on top of `created:` filters
+    pub created: Vec<DateFilter>,
+    /// Only notes last modified within these, on top of `updated:` filters
+    pub updated: Vec<DateFilter>,
+    /// Order of the results; full-text search picks them from every match,
+    /// semantic and hybrid search reorder their best matches
+    pub sort: NoteSort,
+}
+
+impl SearchRequest {
+    /// Search for `query` in `mode` with default ranking
+    pub fn new(query: &str, mode: SearchMode, limit: usize) -> Self {
+        Self {
+            query: query.to_string(),
+            mode,
+            limit,
+            snippets: SnippetOptions::default(),
+            boosts: FieldBoosts::default(),
+            fuzzy: false,
+            query_type: None,
+            strategy: SearchStrategy::default(),
+            recency: None,
+            tags: Vec::new(),
+            tag_match: TagMatch::default(),
+            created: Vec::new(),
+            updated: Vec::new(),
+            sort: NoteSort::default(),
+        }
+    }
+}
+
+/// Runs searches against a vault's indexes
+pub struct SearchDispatcher<'a> {
+    store: &'a NoteStore,
+    fulltext: &'a FullTextIndex,
+    semantic: &'a RwLock<SemanticSearch>,
+}
+
+impl<'a> SearchDispatcher<'a> {
+    pub fn new(store: &'a NoteStore, fulltext: &'a FullTextIndex, semantic: &'a RwLock<SemanticSearch>) -> Self {
+        Self {
+            store,
+            fulltext,
+            semantic,
+        }
+    }
+
+    /// Run `request`, returning at most `request.limit` results
+    ///
+    /// Results carry note IDs and snippets; titles and other note metadata
+    /// are left to the caller. A query with filters, or a request with tags
+    /// or dates, is matched against the notes first, and only notes that
+    /// pass are ranked; with filters alone, the notes that pass are the
+    /// results, most recently updated first unless sorted otherwise. Unless
+    /// turned off in `search.feedback`, feedback on earlier results for the
+    /// query's terms adjusts the ranking by relevance.
+    pub async fn search(&self, request: &SearchRequest) -> Result<Vec<SearchResult>> {
+        let query = SearchQuery::parse(&request.query)?
+            .with_tags(&request.tags, request.tag_match)
+            .with_dates(&request.created, &request.updated);
+        if !query.has_filters() {
+            return self.tuned(request, &query, None).await;
+        }
+
+        let mut notes: Vec<_> = self
+            .store
+            .list()
+            .await
+            .into_iter()
+            .filter(|n| !n.is_deleted && query.matches(n))
+            .collect();
+        if query.text.trim().is_empty() {
+            notes.sort_by(|a, b| request.sort.compare(a, b));
+            return Ok(notes
+                .into_iter()
+                .take(request.limit)
+                .map(|note| SearchResult {
+                    note_id: note.id.to_string(),
+                    snippet: note.content.chars().take(request.snippets.len).collect(),
+                    title: note.title,
+                    score: 1.0,
+                    chunk_type: None,
+                    tags: Vec::new(),
+                    updated_at: None,
+                    chunk: None,
+                    line: None,
+                    highlights: Vec::new(),
+                })
+                .collect());
+        }
+
+        let passed: HashSet<uuid::Uuid> = notes.iter().map(|n| n.id).collect();
+        self.tuned(request, &query, Some(&passed)).await
+    }
+
+    /// Every live note matching `query`'s words and filters, unranked and
+    /// without a limit
+    pub async fn matching(&self, query: &str) -> Result<HashSet<uuid::Uuid>> {
+        let query = SearchQuery::parse(query)?;
+        let passed: HashSet<uuid::Uuid> = self
+            .store
+            .list()
+            .await
+            .into_iter()
+            .filter(|n| !n.is_deleted && query.matches(n))
+            .map(|n| n.id)
+            .collect();
+        if query.text.trim().is_empty() {
+            return Ok(passed);
+        }
+
+        let filter = DocumentFilter {
+            ids: None,
+            created: query.created.clone(),
+            updated: query.updated.clone(),
+        };
+        Ok(self
+            .fulltext
+            .matching_ids(&query.text, &filter)?
+            .iter()
+            .filter_map(|id| id.parse().ok())
+            .filter(|id| passed.contains(id))
+            .collect())
+    }
+
+    /// [`Self::ranked`] adjusted by feedback on the query's terms
+    async fn tuned(
+        &self,
+        request: &SearchRequest,
+        query: &SearchQuery,
+        notes: Option<&HashSet<uuid::Uuid>>,
+    ) -> Result<Vec<SearchResult>> {
+        let config = &self.store.config().search.feedback;
+        let terms = feedback_terms(&query.text);
+        let factors = if config.enabled && request.sort.field == SortField::Relevance {
+            match self.store.term_feedback(Some(&terms)) {
+                Ok(feedback) => note_factors(&terms, &feedback, config),
+                Err(e) => {
+                    tracing::warn!("Failed to load search feedback: {}", e);
+                    HashMap::new()
+                }
+            }
+        } else {
+            HashMap::new()
+        };
+        if factors.is_empty() {
+            return self.ranked(request, query, request.limit, notes).await;
+        }
+
+        // Feedback reorders results, so fetch more than will be kept
+        let results = self
+            .ranked(request, query, request.limit.saturating_mul(2), notes)
+            .await?;
+        let mut results = apply_feedback(results, &factors);
+        results.truncate(request.limit);
+        Ok(results)
+    }
+
+    /// Rank notes by `query`'s words in the request's mode, only `notes`
+    /// when given, and put them in the request's order
+    async fn ranked(
+        &self,
+        request: &SearchRequest,
+        query: &SearchQuery,
+        limit: usize,
+        notes: Option<&HashSet<uuid::Uuid>>,
+    ) -> Result<Vec<SearchResult>> {
+        if request.mode == SearchMode::Fulltext || request.sort.field == SortField::Relevance {
+            return self.relevant(request, query, limit, notes).await;
+        }
+
+        let results = self.relevant(request, query, limit, notes).await?;
+        let mut sorted = Vec::with_capacity(results.len());
+        for result in results {
+            let note = match result.note_id.parse::<uuid::Uuid>() {
+                Ok(uuid) => self.store.get(uuid).await,
+                Err(_) => None,
+            };
+            sorted.push((note, result));
+        }
+        // Stable sort keeps relevance order for ties; canvases go last
+        sorted.sort_by(|(a, _), (b, _)| match (a, b) {
+            (Some(a), Some(b)) => request.sort.compare(a, b),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        });
+        Ok(sorted.into_iter().map(|(_, result)| result).collect())
+    }
+
+    /// Best matches of `query`'s words in the request's mode, only `notes`
+    /// when given; full-text matches come in the request's order
+    async fn relevant(
+        &self,
+        request: &SearchRequest,
+        query: &SearchQuery,
+        limit: usize,
+        notes: Option<&HashSet<uuid::Uuid>>,
+    ) -> Result<Vec<SearchResult>> {
+        match request.mode {
+            SearchMode::Fulltext => self.fulltext_results(request, query, limit, notes),
+            SearchMode::Semantic => self.semantic_results(request, query, limit, notes).await,
+            SearchMode::Hybrid => {
+                // Fuse deeper candidate lists so notes ranked moderately by
+                // both searches can still make the cut, and keep one
+                // search's results when the other fails
+                let depth = limit.saturating_mul(2);
+                let fulltext = self.fulltext_results(request, query, depth, notes).unwrap_or_else(|e| {
+                    tracing::warn!("Full-text search failed: {}", e);
+                    Vec::new()
+                });
+                let semantic = self.semantic_results(request, query, depth, notes).await.unwrap_or_else(|e| {
+                    tracing::warn!("Semantic search failed: {}", e);
+                    Vec::new()
+                });
+                Ok(reciprocal_rank_fusion(
+                    fulltext,
+                    semantic,
+                    self.store.config().search.semantic_weight,
+                    limit,
+                ))
+            }
+        }
+    }
+
+    fn fulltext_results(
+        &self,
+        request: &SearchRequest,
+        query: &SearchQuery,
+        limit: usize,
+        notes: Option<&HashSet<uuid::Uuid>>,
+    ) -> Result<Vec<SearchResult>> {
+        // The index holds the notes' dates, so only other filters need the
+        // list of notes that passed
+        let filter = DocumentFilter {
+            ids: notes
+                .filter(|_| query.has_content_filters())
+                .map(|notes| notes.iter().map(|id| id.to_string()).collect()),
+            created: query.created.clone(),
+            updated: query.updated.clone(),
+        };
+        self.fulltext.search_within(
+            &query.text,
+            limit,
+            &request.boosts,
+            &request.snippets,
+            &filter,
+            request.sort,
+            request.fuzzy,
+        )
+    }
+
+    async fn semantic_results(
+        &self,
+        request: &SearchRequest,
+        query: &SearchQuery,
+        limit: usize,
+        notes: Option<&HashSet<uuid::Uuid>>,
+    ) -> Result<Vec<SearchResult>> {
+        let text = query.plain_text();
+        let queries = match request.strategy {
+            SearchStrategy::Single => vec![text],
+            SearchStrategy::Multi => {
+                QueryDecomposer::new(&self.store.config().search.multi_query)
+                    .decompose(&text)
+                    .await
+            }
+        };
+
+        // Recency reorders results, so fetch more than will be kept
+        let depth = if request.recency.is_some() {
+            limit.saturating_mul(2)
+        } else {
+            limit
+        };
+        let mut rankings = Vec::with_capacity(queries.len());
+        {
+            let semantic = self.semantic.read().await;
+            for query in &queries {
+                rankings.push(
+                    semantic
+                        .search_within(query, request.query_type, depth, &request.snippets, notes)
+                        .await?,
+                );
+            }
+        }
+        let results = match rankings.len() {
+            1 => rankings.pop().unwrap_or_default(),
+            _ => fuse_subquery_results(rankings, depth),
+        };
+        let Some(recency) = &request.recency else {
+            return Ok(results);
+        };
+
+        let mut updated_at = HashMap::new();
+        for result in &results {
+            if let Ok(uuid) = result.note_id.parse::<uuid::Uuid>() {
+                if let Some(note) = self.store.get(uuid).await {
+                    updated_at.insert(result.note_id.clone(), note.updated_at);
+                }
+            }
+        }
+        let mut results = apply_recency(results, &updated_at, recency, chrono::Utc::now());
+        results.truncate(limit);
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_mode_precedence() {
+        let mut config = SearchConfig::default();
+        assert_eq!(default_mode(&config, SearchClient::Mcp, None), SearchMode::Semantic);
+        assert_eq!(default_mode(&config, SearchClient::Http, Some("alice")), SearchMode::Fulltext);
+
+        config.default_mode = Some(SearchMode::Hybrid);
+        assert_eq!(default_mode(&config, SearchClient::Mcp, None), SearchMode::Hybrid);
+        assert_eq!(default_mode(&config, SearchClient::Cli, None), SearchMode::Hybrid);
+
+        config.client_modes.insert("cli".to_string(), SearchMode::Fulltext);
+        config.client_modes.insert("alice".to_string(), SearchMode::Semantic);
+        assert_eq!(default_mode(&config, SearchClient::Cli, None), SearchMode::Fulltext);
+        assert_eq!(default_mode(&config, SearchClient::Cli, Some("alice")), SearchMode::Semantic);
+        assert_eq!(default_mode(&config, SearchClient::Http, Some("bob")), SearchMode::Hybrid);
+    }
+
+    #[test]
+    fn test_parse_search_mode() {
+        assert_eq!("hybrid".parse::<SearchMode>().unwrap(), SearchMode::Hybrid);
+        assert_eq!(SearchMode::Fulltext.to_string(), "fulltext");
+        assert!("fuzzy".parse::<SearchMode>().is_err());
+    }
+}
@@ -0,0 +1,137 @@
+//! Ranking tuned by feedback on results
+//!
+//! Readers report results they opened and results that didn't belong. Each
+//! report is kept against every term of its query, so what is learned
+//! carries over to other queries sharing the term. For a note and a term,
+//! marks beyond the clicks scale the note's score by `demote` each once
+//! there are at least `min_marks` of them; clicks beyond the marks raise it
+//! by `promote * ln(1 + clicks)`. A note's adjustment for a query is the
+//! mean over the query's terms, terms without feedback counting as 1.
+
+use std::collections::HashMap;
+
+use crate::config::FeedbackConfig;
+use crate::types::{SearchResult, TermFeedback};
+
+use super::SearchQuery;
+
+/// Lowercased words of a query that feedback is kept against, without its
+/// filters and in order of first appearance
+pub fn feedback_terms(query: &str) -> Vec<String> {
+    let text = SearchQuery::parse(query).map_or_else(|_| query.to_string(), |q| q.plain_text());
+    let mut terms: Vec<String> = Vec::new();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() > 1)
+    {
+        let word = word.to_lowercase();
+        if !terms.contains(&word) {
+            terms.push(word);
+        }
+    }
+    terms
+}
+
+/// Score multiplier learned for a note from feedback on one term
+pub fn feedback_factor(clicks: u32, irrelevant: u32, config: &FeedbackConfig) -> f32 {
+    if irrelevant > clicks && irrelevant >= config.min_marks {
+        config.demote.clamp(0.0, 1.0).powi((irrelevant - clicks) as i32)
+    } else if clicks > irrelevant {
+        1.0 + config.promote.max(0.0) * ((clicks - irrelevant) as f32).ln_1p()
+    } else {
+        1.0
+    }
+}
+
+/// Score multiplier of each note feedback on `terms` adjusts, by note ID
+pub fn note_factors(terms: &[String], feedback: &[TermFeedback], config: &FeedbackConfig) -> HashMap<String, f32> {
+    let mut by_note: HashMap<&str, f32> = HashMap::new();
+    for entry in feedback.iter().filter(|e| terms.contains(&e.term)) {
+        let factor = feedback_factor(entry.clicks, entry.irrelevant, config);
+        *by_note.entry(&entry.note_id).or_insert(0.0) += factor - 1.0;
+    }
+    by_note
+        .into_iter()
+        .map(|(note_id, delta)| (note_id.to_string(), 1.0 + delta / terms.len() as f32))
+        .filter(|(_, factor)| *factor != 1.0)
+        .collect()
+}
+
+/// Rescale `results` by `factors` and sort them by the new score
+pub fn apply_feedback(mut results: Vec<SearchResult>, factors: &HashMap<String, f32>) -> Vec<SearchResult> {
+    for result in &mut results {
+        if let Some(factor) = factors.get(&result.note_id) {
+            result.score *= factor;
+        }
+    }
+
+    // Stable sort keeps the search's order for ties
+    results.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feedback(term: &str, note_id: &str, clicks: u32, irrelevant: u32) -> TermFeedback {
+        TermFeedback {
+            term: term.to_string(),
+            note_id: note_id.to_string(),
+            clicks,
+            irrelevant,
+            queries: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_feedback_terms() {
+        assert_eq!(
+            feedback_terms("Rust tag:lang async \"rust BOOK\" a"),
+            vec!["rust", "async", "book"]
+        );
+    }
+
+    #[test]
+    fn test_feedback_factor() {
+        let config = FeedbackConfig::default();
+        // A single mark could be a slip
+        assert_eq!(feedback_factor(0, 1, &config), 1.0);
+        assert_eq!(feedback_factor(0, 2, &config), 0.25);
+        assert_eq!(feedback_factor(1, 3, &config), 0.25);
+        assert_eq!(feedback_factor(2, 2, &config), 1.0);
+        assert!(feedback_factor(3, 0, &config) > 1.1);
+    }
+
+    #[test]
+    fn test_marked_notes_sink() {
+        let config = FeedbackConfig::default();
+        let terms = feedback_terms("rust async");
+        let factors = note_factors(
+            &terms,
+            &[feedback("rust", "a", 0, 2), feedback("async", "a", 0, 2), feedback("tokio", "b", 0, 5)],
+            &config,
+        );
+        assert_eq!(factors, HashMap::from([("a".to_string(), 0.25)]));
+
+        let result = |note_id: &str, score: f32| SearchResult {
+            note_id: note_id.to_string(),
+            title: String::new(),
+            snippet: String::new(),
+            score,
+            chunk_type: None,
+            tags: Vec::new(),
+            updated_at: None,
+            chunk: None,
+            line: None,
+            highlights: Vec::new(),
+        };
+        let ranked = apply_feedback(vec![result("a", 1.0), result("b", 0.5)], &factors);
+        let ids: Vec<&str> = ranked.iter().map(|r| r.note_id.as_str()).collect();
+        assert_eq!(ids, vec!["b", "a"]);
+    }
+}
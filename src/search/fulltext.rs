@@ -1,13 +1,47 @@
 //! Tantivy full-text search index
 
-use std::path::Path;
-use tantivy::collector::TopDocs;
-use tantivy::query::QueryParser;
-use tantivy::schema::{Field, Schema, Value, STORED, TEXT};
-use tantivy::{doc, Index, IndexReader, IndexWriter, ReloadPolicy};
-
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::HashSet;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use tantivy::collector::{DocSetCollector, TopDocs};
+use tantivy::query::{
+    BooleanQuery, BoostQuery, FuzzyTermQuery, Occur, PhraseQuery, Query, QueryParser, RangeQuery, TermQuery,
+    TermSetQuery,
+};
+use tantivy::schema::{
+    Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, Value, FAST, INDEXED, STORED,
+};
+use tantivy::snippet::SnippetGenerator;
+use tantivy::{doc, DocAddress, DocId, Index, IndexReader, IndexWriter, ReloadPolicy, Score, SegmentReader, Term};
+
+use crate::config::{AnalyzerConfig, FieldBoosts, ReaderReload, SearchConfig};
 use crate::error::Result;
-use crate::types::{Note, SearchResult};
+use crate::types::{MatchRange, Note, NoteSort, SearchResult, SortField, SortOrder};
+
+use super::analyzer::{VaultTokenizer, TOKENIZER_NAME};
+use super::query::tokens;
+use super::{DateFilter, SnippetOptions};
+
+/// File in the index directory recording the analyzer settings it was built
+/// with
+const ANALYZER_FILE: &str = "analyzer.json";
+
+/// Prefix of the IDs of canvas documents, which share the index with notes
+const CANVAS_ID_PREFIX: &str = "canvas:";
+
+/// Documents a search is limited to, checked in the index before ranking
+#[derive(Debug, Clone, Default)]
+pub struct DocumentFilter {
+    /// Only the documents with these IDs
+    pub ids: Option<HashSet<String>>,
+    /// Only notes created within every one of these
+    pub created: Vec<DateFilter>,
+    /// Only notes last modified within every one of these
+    pub updated: Vec<DateFilter>,
+}
 
 /// Full-text search index using Tantivy
 pub struct FullTextIndex {
@@ -20,33 +54,89 @@ pub struct FullTextIndex {
     title_field: Field,
     content_field: Field,
     tags_field: Field,
+    code_field: Field,
+    created_field: Field,
+    updated_field: Field,
+    title_sort_field: Field,
+
+    /// Whether the index was created empty or recreated with a new schema
+    /// and should be rebuilt from the notes
+    needs_rebuild: bool,
+
+    path: PathBuf,
+    analyzer: AnalyzerConfig,
+    /// Whether the index was built with different analyzer settings
+    analyzer_changed: AtomicBool,
 }
 
 impl FullTextIndex {
     /// Create or open an index at the given path
     pub fn open(path: &Path) -> Result<Self> {
+        Self::open_with(path, &SearchConfig::default())
+    }
+
+    /// Create or open an index at the given path, with the analyzer and
+    /// tuning of the given search settings
+    pub fn open_with(path: &Path, config: &SearchConfig) -> Result<Self> {
+        let analyzer = &config.analyzer;
         std::fs::create_dir_all(path)?;
 
+        let text = TextOptions::default().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer(TOKENIZER_NAME)
+                .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+        );
+
         let mut schema_builder = Schema::builder();
         // ID field must be STRING (indexed but not tokenized) to support delete_term
         let id_field = schema_builder.add_text_field("id", tantivy::schema::STRING | STORED);
-        let title_field = schema_builder.add_text_field("title", TEXT | STORED);
-        let content_field = schema_builder.add_text_field("content", TEXT | STORED); // Also store content for snippets
-        let tags_field = schema_builder.add_text_field("tags", TEXT | STORED);
+        let title_field = schema_builder.add_text_field("title", text.clone() | STORED);
+        let content_field = schema_builder.add_text_field("content", text.clone() | STORED); // Also store content for snippets
+        let tags_field = schema_builder.add_text_field("tags", text.clone() | STORED);
+        let code_field = schema_builder.add_text_field("code", text);
+        // Notes' timestamps, for date range filters; canvases have none
+        let created_field = schema_builder.add_date_field("created", INDEXED | FAST);
+        let updated_field = schema_builder.add_date_field("updated", INDEXED | FAST);
+        // Lowercased title, for sorting results by title
+        let title_sort_field = schema_builder.add_text_field("title_sort", tantivy::schema::STRING | FAST);
         let schema = schema_builder.build();
 
-        let index = if path.join("meta.json").exists() {
-            Index::open_in_dir(path)?
+        let existing = if path.join("meta.json").exists() {
+            Some(Index::open_in_dir(path)?)
         } else {
-            Index::create_in_dir(path, schema.clone())?
+            None
+        };
+        let (index, needs_rebuild) = match existing {
+            Some(index) if index.schema() == schema => (index, false),
+            Some(_) => {
+                // Indexes from older versions lack fields; start over
+                tracing::info!("Full-text index schema changed, recreating it");
+                std::fs::remove_dir_all(path)?;
+                std::fs::create_dir_all(path)?;
+                (Index::create_in_dir(path, schema)?, true)
+            }
+            None => (Index::create_in_dir(path, schema)?, true),
         };
+        index
+            .tokenizers()
+            .register(TOKENIZER_NAME, VaultTokenizer::new(analyzer)?);
+
+        // An empty index matches any settings
+        if needs_rebuild {
+            write_analyzer(path, analyzer)?;
+        }
+        let analyzer_changed = read_analyzer(path) != *analyzer;
 
+        let reload_policy = match config.index.reader_reload {
+            ReaderReload::OnCommit => ReloadPolicy::OnCommitWithDelay,
+            ReaderReload::Manual => ReloadPolicy::Manual,
+        };
         let reader = index
             .reader_builder()
-            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .reload_policy(reload_policy)
             .try_into()?;
 
-        let writer = index.writer(50_000_000)?; // 50MB heap
+        let writer = index.writer(config.index.writer_heap_mb.saturating_mul(1_000_000))?;
 
         Ok(Self {
             index,
@@ -56,10 +146,45 @@ impl FullTextIndex {
             title_field,
             content_field,
             tags_field,
+            code_field,
+            created_field,
+            updated_field,
+            title_sort_field,
+            needs_rebuild,
+            path: path.to_path_buf(),
+            analyzer: analyzer.clone(),
+            analyzer_changed: AtomicBool::new(analyzer_changed),
         })
     }
 
+    /// Whether the analyzer settings changed since the index was built, so
+    /// it should be rebuilt for searches to match
+    pub fn analyzer_changed(&self) -> bool {
+        self.analyzer_changed.load(Ordering::Relaxed)
+    }
+
+    /// Whether [`open`](Self::open) found no usable index, so the notes have
+    /// to be indexed again with [`rebuild`](Self::rebuild)
+    pub fn needs_rebuild(&self) -> bool {
+        self.needs_rebuild
+    }
+
+    /// Load the index's segments and run a cheap query, so the first real
+    /// search doesn't pay for opening them
+    #[tracing::instrument(skip_all)]
+    pub fn warm_up(&self) -> Result<()> {
+        let searcher = self.reader.searcher();
+        for segment in searcher.segment_readers() {
+            for field in [self.title_field, self.content_field, self.tags_field, self.code_field] {
+                segment.inverted_index(field)?;
+            }
+        }
+        self.search("notidium", 1)?;
+        Ok(())
+    }
+
     /// Index a note
+    #[tracing::instrument(skip_all, fields(note_id = %note.id))]
     pub fn index_note(&self, note: &Note) -> Result<()> {
         let writer = self.writer.lock().unwrap();
 
@@ -72,14 +197,42 @@ impl FullTextIndex {
         writer.add_document(doc!(
             self.id_field => note.id.to_string(),
             self.title_field => note.title.clone(),
+            self.title_sort_field => note.title.to_lowercase(),
             self.content_field => note.content.clone(),
             self.tags_field => tags,
+            self.code_field => code_text(&note.content),
+            self.created_field => tantivy::DateTime::from_timestamp_secs(note.created_at.timestamp()),
+            self.updated_field => tantivy::DateTime::from_timestamp_secs(note.updated_at.timestamp()),
+        ))?;
+
+        Ok(())
+    }
+
+    /// Index the text of the canvas at `path` (relative to the notes
+    /// directory), titled by its file name; see [`canvas_id`]
+    pub fn index_canvas(&self, path: &str, text: &str) -> Result<()> {
+        let writer = self.writer.lock().unwrap();
+        let id = canvas_id(path);
+        writer.delete_term(tantivy::Term::from_field_text(self.id_field, &id));
+
+        let title = Path::new(path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        writer.add_document(doc!(
+            self.id_field => id,
+            self.title_sort_field => title.to_lowercase(),
+            self.title_field => title,
+            self.content_field => text.to_string(),
+            self.tags_field => String::new(),
+            self.code_field => String::new(),
         ))?;
 
         Ok(())
     }
 
     /// Commit pending changes
+    #[tracing::instrument(skip_all)]
     pub fn commit(&self) -> Result<()> {
         let mut writer = self.writer.lock().unwrap();
         writer.commit()?;
@@ -89,19 +242,78 @@ impl FullTextIndex {
     }
 
     /// Search notes
+    ///
+    /// A `"quoted phrase"` must appear, its words in order, in the title,
+    /// content, tags or code of a match. A word scoped to a field, as
+    /// `title:rust`, `tag:draft`, `content:tokio` or `code:spawn`, must
+    /// appear in that field; `title:"two words"` scopes a phrase. The other
+    /// words rank the matches, in tantivy's query syntax, and in fuzzy
+    /// search also match words a typo or two away.
     pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>> {
-        let searcher = self.reader.searcher();
+        self.search_with(query, limit, &FieldBoosts::default(), &SnippetOptions::default())
+    }
 
-        let query_parser = QueryParser::for_index(
-            &self.index,
-            vec![self.title_field, self.content_field, self.tags_field],
-        );
+    /// Search notes, ranking field matches by `boosts` and shaping snippets
+    /// by `snippets`
+    ///
+    /// Notes aren't chunked in this index, so the full chunk of a result is
+    /// the paragraph holding the first match.
+    #[tracing::instrument(skip(self))]
+    pub fn search_with(
+        &self,
+        query: &str,
+        limit: usize,
+        boosts: &FieldBoosts,
+        snippets: &SnippetOptions,
+    ) -> Result<Vec<SearchResult>> {
+        self.search_within(
+            query,
+            limit,
+            boosts,
+            snippets,
+            &DocumentFilter::default(),
+            NoteSort::default(),
+            false,
+        )
+    }
 
+    /// Like [`FullTextIndex::search_with`], matching only the documents that
+    /// pass `filter` and returning the first `limit` in `sort` order;
+    /// `fuzzy` also matches words within a typo or two of the query's
+    ///
+    /// Dates and titles are read from fast fields, so sorting by them picks
+    /// from every match, not just the best ranked. Ties, and canvases when
+    /// sorting by date, go by relevance.
+    #[allow(clippy::too_many_arguments)]
+    pub fn search_within(
+        &self,
+        query: &str,
+        limit: usize,
+        boosts: &FieldBoosts,
+        snippets: &SnippetOptions,
+        filter: &DocumentFilter,
+        sort: NoteSort,
+        fuzzy: bool,
+    ) -> Result<Vec<SearchResult>> {
+        let searcher = self.reader.searcher();
+        let (text_query, parsed_query) = self.filtered_query(query, boosts, filter, fuzzy)?;
+        let mut generator = SnippetGenerator::create(&searcher, &*text_query, self.content_field)?;
+        generator.set_max_num_chars(snippets.len);
         let query_lower = query.to_lowercase();
-        let query_terms: Vec<&str> = query_lower.split_whitespace().collect();
-
-        let parsed_query = query_parser.parse_query(query)?;
-        let top_docs = searcher.search(&parsed_query, &TopDocs::with_limit(limit))?;
+        let query_terms: Vec<&str> = query_lower
+            .split_whitespace()
+            .map(|term| term.trim_matches('"'))
+            .filter(|term| !term.is_empty())
+            .collect();
+
+        let top_docs = match sort.field {
+            SortField::Relevance => searcher.search(&parsed_query, &TopDocs::with_limit(limit))?,
+            field => searcher
+                .search(&parsed_query, &TopDocs::with_limit(limit).tweak_score(sort_key(field, sort.order)))?
+                .into_iter()
+                .map(|((_, score), doc)| (score, doc))
+                .collect(),
+        };
 
         let mut results = Vec::new();
         for (score, doc_address) in top_docs {
@@ -125,8 +337,10 @@ impl FullTextIndex {
                 .unwrap_or("")
                 .to_string();
 
-            // Generate snippet from content
-            let snippet = generate_snippet(&content, &query_terms, 200);
+            let (snippet, highlights) = highlighted_snippet(&generator, &content, snippets.len);
+            let chunk = snippets
+                .full_chunk
+                .then(|| matching_paragraph(&content, &query_terms).to_string());
 
             results.push(SearchResult {
                 note_id: id,
@@ -136,12 +350,188 @@ impl FullTextIndex {
                 chunk_type: None,
                 tags: Vec::new(), // Will be enriched by handler if needed
                 updated_at: None, // Will be enriched by handler if needed
+                chunk,
+                line: None,
+                highlights,
             });
         }
 
         Ok(results)
     }
 
+    /// Every document matching `query` that passes `filter`, by ID
+    pub fn matching_ids(&self, query: &str, filter: &DocumentFilter) -> Result<HashSet<String>> {
+        let searcher = self.reader.searcher();
+        let (_, query) = self.filtered_query(query, &FieldBoosts::default(), filter, false)?;
+        let mut ids = HashSet::new();
+        for doc_address in searcher.search(&query, &DocSetCollector)? {
+            let doc: tantivy::TantivyDocument = searcher.doc(doc_address)?;
+            if let Some(id) = doc.get_first(self.id_field).and_then(|v| v.as_str()) {
+                ids.insert(id.to_string());
+            }
+        }
+        Ok(ids)
+    }
+
+    /// `query` parsed against the text fields, and the same with `filter`
+    /// applied
+    fn filtered_query(
+        &self,
+        query: &str,
+        boosts: &FieldBoosts,
+        filter: &DocumentFilter,
+        fuzzy: bool,
+    ) -> Result<(Box<dyn Query>, Box<dyn Query>)> {
+        let parsed_query = self.text_query(query, boosts, fuzzy)?;
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        if let Some(ids) = &filter.ids {
+            let ids = TermSetQuery::new(ids.iter().map(|id| tantivy::Term::from_field_text(self.id_field, id)));
+            clauses.push((Occur::Must, Box::new(ids)));
+        }
+        for (field, dates) in [("created", &filter.created), ("updated", &filter.updated)] {
+            for date in dates {
+                let (start, end) = date.range();
+                let to_index = |bound: Bound<chrono::DateTime<chrono::Utc>>| {
+                    bound.map(|at| tantivy::DateTime::from_timestamp_secs(at.timestamp()))
+                };
+                let range = RangeQuery::new_date_bounds(field.to_string(), to_index(start), to_index(end));
+                clauses.push((Occur::Must, Box::new(range)));
+            }
+        }
+        if clauses.is_empty() {
+            return Ok((parsed_query.box_clone(), parsed_query));
+        }
+        clauses.insert(0, (Occur::Must, parsed_query.box_clone()));
+        Ok((parsed_query, Box::new(BooleanQuery::new(clauses))))
+    }
+
+    /// `query` as tantivy queries: quoted phrases and words scoped to a
+    /// field must match, and the other words go through tantivy's query
+    /// parser to rank the matches, joined by words a typo or two away from
+    /// them when `fuzzy`
+    fn text_query(&self, query: &str, boosts: &FieldBoosts, fuzzy: bool) -> Result<Box<dyn Query>> {
+        let fields = [
+            (self.title_field, boosts.title),
+            (self.content_field, boosts.content),
+            (self.tags_field, boosts.tags),
+            (self.code_field, boosts.code),
+        ];
+
+        let mut words = Vec::new();
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        for token in tokens(query) {
+            let scoped = token
+                .text
+                .split_once(':')
+                .filter(|_| !token.quoted)
+                .and_then(|(name, value)| Some((self.scoped_field(name)?, value.trim_start_matches('#'))))
+                .filter(|(_, value)| !value.is_empty());
+            let phrase = match scoped {
+                Some((field, value)) => {
+                    let boost = fields.iter().find(|(f, _)| *f == field).map_or(1.0, |(_, boost)| *boost);
+                    self.phrase_query(value, &[(field, boost)])?
+                }
+                None if token.quoted => self.phrase_query(&token.text, &fields)?,
+                None => {
+                    words.push(token.text);
+                    continue;
+                }
+            };
+            // Phrases of nothing but stop words match anything
+            if let Some(phrase) = phrase {
+                clauses.push((Occur::Must, phrase));
+            }
+        }
+
+        let mut query_parser = QueryParser::for_index(&self.index, fields.iter().map(|(field, _)| *field).collect());
+        for (field, boost) in fields {
+            query_parser.set_field_boost(field, boost);
+        }
+        let words = words.join(" ");
+        let mut text = query_parser.parse_query(&words)?;
+        if let Some(typos) = self.fuzzy_query(&words, &fields)?.filter(|_| fuzzy) {
+            // Exact matches still score by term frequency; typos only add
+            text = Box::new(BooleanQuery::new(vec![(Occur::Should, text), (Occur::Should, typos)]));
+        }
+        if clauses.is_empty() {
+            return Ok(text);
+        }
+        if !words.is_empty() {
+            clauses.push((Occur::Should, text));
+        }
+        Ok(Box::new(BooleanQuery::new(clauses)))
+    }
+
+    /// Field a query word scoped as `name:word` searches
+    fn scoped_field(&self, name: &str) -> Option<Field> {
+        match name {
+            "title" => Some(self.title_field),
+            "content" => Some(self.content_field),
+            "tag" | "tags" => Some(self.tags_field),
+            "code" => Some(self.code_field),
+            _ => None,
+        }
+    }
+
+    /// Documents with `text`'s words in order in any of `fields`, scored by
+    /// the field's boost; `None` when the analyzer leaves no words
+    fn phrase_query(&self, text: &str, fields: &[(Field, f32)]) -> Result<Option<Box<dyn Query>>> {
+        let mut alternatives: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        for &(field, boost) in fields {
+            let mut analyzer = self.index.tokenizer_for_field(field)?;
+            let mut terms = Vec::new();
+            analyzer
+                .token_stream(text)
+                .process(&mut |token| terms.push((token.position, Term::from_field_text(field, &token.text))));
+            let query: Box<dyn Query> = match terms.len() {
+                0 => continue,
+                1 => Box::new(TermQuery::new(terms.remove(0).1, IndexRecordOption::WithFreqs)),
+                _ => Box::new(PhraseQuery::new_with_offset(terms)),
+            };
+            alternatives.push((Occur::Should, Box::new(BoostQuery::new(query, boost))));
+        }
+        Ok((!alternatives.is_empty()).then(|| Box::new(BooleanQuery::new(alternatives)) as Box<dyn Query>))
+    }
+
+    /// Documents with a word within [`typo_distance`] of one of `text`'s in
+    /// any of `fields`, scored by the field's boost; `None` when no word is
+    /// long enough to allow typos
+    fn fuzzy_query(&self, text: &str, fields: &[(Field, f32)]) -> Result<Option<Box<dyn Query>>> {
+        let mut alternatives: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        for &(field, boost) in fields {
+            let mut analyzer = self.index.tokenizer_for_field(field)?;
+            analyzer.token_stream(text).process(&mut |token| {
+                let distance = typo_distance(&token.text);
+                if distance > 0 {
+                    let query = FuzzyTermQuery::new(Term::from_field_text(field, &token.text), distance, true);
+                    alternatives.push((Occur::Should, Box::new(BoostQuery::new(Box::new(query), boost))));
+                }
+            });
+        }
+        Ok((!alternatives.is_empty()).then(|| Box::new(BooleanQuery::new(alternatives)) as Box<dyn Query>))
+    }
+
+    /// Number of indexed documents, notes and canvases alike
+    pub fn document_count(&self) -> u64 {
+        self.reader.searcher().num_docs()
+    }
+
+    /// IDs of every indexed document, notes and canvases alike
+    pub fn document_ids(&self) -> Result<HashSet<String>> {
+        self.reader.reload()?;
+        let searcher = self.reader.searcher();
+        let mut ids = HashSet::new();
+        for (segment, reader) in searcher.segment_readers().iter().enumerate() {
+            for doc_id in reader.doc_ids_alive() {
+                let doc: tantivy::TantivyDocument = searcher.doc(DocAddress::new(segment as u32, doc_id))?;
+                if let Some(id) = doc.get_first(self.id_field).and_then(|v| v.as_str()) {
+                    ids.insert(id.to_string());
+                }
+            }
+        }
+        Ok(ids)
+    }
+
     /// Delete a note from the index
     pub fn delete_note(&self, note_id: &str) -> Result<()> {
         let writer = self.writer.lock().unwrap();
@@ -162,74 +552,180 @@ impl FullTextIndex {
         }
 
         self.commit()?;
+        write_analyzer(&self.path, &self.analyzer)?;
+        self.analyzer_changed.store(false, Ordering::Relaxed);
         Ok(())
     }
 }
 
-/// Generate a snippet from content, trying to center around query terms
-fn generate_snippet(content: &str, query_terms: &[&str], max_len: usize) -> String {
-    if content.is_empty() {
-        return String::new();
-    }
+/// A document's value in a sort, compared so that the document to list
+/// first is the greatest
+#[derive(Debug, Clone, PartialEq)]
+struct SortKey {
+    /// None for documents without the field, which go last
+    value: Option<SortValue>,
+    order: SortOrder,
+}
 
-    let content_lower = content.to_lowercase();
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+enum SortValue {
+    Date(i64),
+    Title(String),
+}
 
-    // Try to find the first occurrence of any query term
-    let mut best_pos: Option<usize> = None;
-    for term in query_terms {
-        if let Some(pos) = content_lower.find(term) {
-            match best_pos {
-                None => best_pos = Some(pos),
-                Some(existing) if pos < existing => best_pos = Some(pos),
-                _ => {}
-            }
+impl PartialOrd for SortKey {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        match (&self.value, &other.value) {
+            (Some(a), Some(b)) => a.partial_cmp(b).map(|ordering| match self.order {
+                SortOrder::Asc => ordering.reverse(),
+                SortOrder::Desc => ordering,
+            }),
+            (a, b) => Some(a.is_some().cmp(&b.is_some())),
         }
     }
+}
 
-    // Calculate snippet bounds
-    let (start, end) = match best_pos {
-        Some(pos) => {
-            // Center the snippet around the match
-            let half_len = max_len / 2;
-            let start = pos.saturating_sub(half_len);
-            let end = (pos + half_len).min(content.len());
-            (start, end)
-        }
-        None => {
-            // No match found, just take from the beginning
-            (0, max_len.min(content.len()))
+/// Score tweaker ranking documents by `field` in `order`, then by relevance
+fn sort_key(
+    field: SortField,
+    order: SortOrder,
+) -> impl Fn(&SegmentReader) -> Box<dyn FnMut(DocId, Score) -> (SortKey, Score)> + Send + Sync {
+    move |segment: &SegmentReader| {
+        let fast_fields = segment.fast_fields();
+        let value: Box<dyn Fn(DocId) -> Option<SortValue>> = match field {
+            SortField::CreatedAt | SortField::UpdatedAt => {
+                let name = if field == SortField::CreatedAt { "created" } else { "updated" };
+                match fast_fields.date(name) {
+                    Ok(column) => Box::new(move |doc| {
+                        column.first(doc).map(|at| SortValue::Date(at.into_timestamp_secs()))
+                    }),
+                    Err(_) => Box::new(|_| None),
+                }
+            }
+            SortField::Title | SortField::Relevance => match fast_fields.str("title_sort") {
+                Ok(Some(column)) => Box::new(move |doc| {
+                    let ord = column.term_ords(doc).next()?;
+                    let mut title = String::new();
+                    column.ord_to_str(ord, &mut title).ok()?;
+                    Some(SortValue::Title(title))
+                }),
+                _ => Box::new(|_| None),
+            },
+        };
+        Box::new(move |doc, score| (SortKey { value: value(doc), order }, score))
+    }
+}
+
+/// ID of a canvas's document in the index, which search results report as
+/// their `note_id`
+pub fn canvas_id(path: &str) -> String {
+    format!("{}{}", CANVAS_ID_PREFIX, path)
+}
+
+/// Path of the canvas a search result's `note_id` stands for, if it is one
+pub fn canvas_path(id: &str) -> Option<&str> {
+    id.strip_prefix(CANVAS_ID_PREFIX)
+}
+
+/// Analyzer settings an index was built with; indexes predating the
+/// settings file used the defaults
+fn read_analyzer(path: &Path) -> AnalyzerConfig {
+    std::fs::read_to_string(path.join(ANALYZER_FILE))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn write_analyzer(path: &Path, analyzer: &AnalyzerConfig) -> Result<()> {
+    std::fs::write(path.join(ANALYZER_FILE), serde_json::to_string_pretty(analyzer)?)?;
+    Ok(())
+}
+
+/// Text of the code blocks in a note's markdown
+fn code_text(content: &str) -> String {
+    let mut code = String::new();
+    let mut in_code_block = false;
+    for event in Parser::new(content) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Text(text) if in_code_block => code.push_str(&text),
+            _ => {}
         }
-    };
+    }
+    code
+}
+/// Edits (Levenshtein distance, a transposition counting as one) fuzzy
+/// search allows in an index term: none in short words, where a typo
+/// usually makes another word, one up to seven letters and two beyond
+fn typo_distance(term: &str) -> u8 {
+    match term.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
 
-    // Adjust to word boundaries
-    let adjusted_start = if start > 0 {
-        content[..start]
-            .rfind(char::is_whitespace)
-            .map(|p| p + 1)
-            .unwrap_or(start)
-    } else {
-        0
-    };
+/// The first blank-line separated paragraph mentioning a query term, or the
+/// first paragraph when none does
+fn matching_paragraph<'a>(content: &'a str, query_terms: &[&str]) -> &'a str {
+    let mut paragraphs = content.split("\n\n").map(str::trim).filter(|p| !p.is_empty());
+    let first = paragraphs.clone().next().unwrap_or("");
+    paragraphs
+        .find(|p| {
+            let lower = p.to_lowercase();
+            query_terms.iter().any(|term| lower.contains(term))
+        })
+        .unwrap_or(first)
+}
 
-    let adjusted_end = if end < content.len() {
-        content[end..]
-            .find(char::is_whitespace)
-            .map(|p| end + p)
-            .unwrap_or(end)
-    } else {
-        content.len()
+/// Snippet of `content` around the fragment `generator` scores best, with
+/// the ranges of matched terms in it
+///
+/// Whitespace is collapsed to single spaces, and "..." marks text cut off
+/// on either side. When no term matched the content, as with a title-only
+/// match, the snippet is the start of the content.
+fn highlighted_snippet(generator: &SnippetGenerator, content: &str, max_len: usize) -> (String, Vec<MatchRange>) {
+    let found = generator.snippet(content);
+    let (start, end, matched) = match content.find(found.fragment()) {
+        Some(start) if !found.highlighted().is_empty() => {
+            (start, start + found.fragment().len(), found.highlighted())
+        }
+        _ => {
+            let cut = (max_len.min(content.len())..=content.len())
+                .find(|&i| content.is_char_boundary(i))
+                .unwrap_or(content.len());
+            let end = content[cut..].find(char::is_whitespace).map_or(content.len(), |p| cut + p);
+            (0, end, &[][..])
+        }
     };
 
-    // Build snippet
-    let mut snippet = String::new();
-    if adjusted_start > 0 {
-        snippet.push_str("...");
+    // Output offset of each byte of the fragment, shifted past the "..."
+    let prefix = if start > 0 { "..." } else { "" };
+    let mut snippet = String::from(prefix);
+    let mut offsets = vec![0; end - start + 1];
+    for (i, c) in content[start..end].char_indices() {
+        offsets[i..i + c.len_utf8()].fill(snippet.len());
+        if !c.is_whitespace() {
+            snippet.push(c);
+        } else if !snippet.ends_with(' ') && snippet.len() > prefix.len() {
+            snippet.push(' ');
+        }
+    }
+    if snippet.ends_with(' ') {
+        snippet.pop();
     }
-    snippet.push_str(content[adjusted_start..adjusted_end].trim());
-    if adjusted_end < content.len() {
+    offsets[end - start] = snippet.len();
+    if end < content.len() {
         snippet.push_str("...");
     }
 
-    // Clean up newlines
-    snippet.replace('\n', " ").replace("  ", " ")
+    let highlights = matched
+        .iter()
+        .map(|range| MatchRange {
+            start: offsets[range.start],
+            end: offsets[range.end],
+        })
+        .collect();
+    (snippet, highlights)
 }
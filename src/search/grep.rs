@@ -0,0 +1,352 @@
+//! Line-based search over the note files
+//!
+//! [`grep_notes`] matches a regular expression against the Markdown files
+//! in the notes directory, line by line, and maps every hit back to its note
+//! so scripts get the same [`SearchResult`] shape as the other searches. It
+//! runs ripgrep when it's installed and scans the files itself otherwise.
+//! ripgrep is told to skip its config file and ignore files and to look in
+//! hidden directories, so both search the same notes, and both use Rust's
+//! regex syntax. They can still disagree at the edges, e.g. on how `$`
+//! treats a `\r\n` line ending.
+//!
+//! Both stop reading once `limit` matches are found, walking the files in
+//! path order so the first matches are the same either way.
+//!
+//! Patterns compile to at most [`REGEX_SIZE_LIMIT`] bytes and files over
+//! [`GrepOptions::max_file_size`] are skipped, so one search can't pin a
+//! server on a pathological pattern or a huge file.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::{Error, Result};
+use crate::types::{Note, SearchResult};
+
+/// How [`grep_notes`] interprets its pattern
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GrepOptions {
+    /// Match regardless of letter case
+    pub ignore_case: bool,
+    /// Treat the pattern as a literal string rather than a regex
+    pub fixed_strings: bool,
+    /// Skip files larger than this many bytes
+    pub max_file_size: Option<u64>,
+}
+
+/// Largest compiled pattern, in bytes
+pub const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+/// A matching line, with the note it belongs to
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct GrepMatch {
+    pub note_id: String,
+    pub title: String,
+    /// Path of the note's file, relative to the notes directory
+    pub path: String,
+    /// 1-based line number
+    pub line: u32,
+    /// The matching line, without its line ending
+    pub text: String,
+}
+
+/// A matching line in a file under the notes directory
+struct LineMatch {
+    /// Path relative to the notes directory
+    path: PathBuf,
+    /// 1-based line number
+    line: u32,
+    text: String,
+}
+
+/// Find the lines of `notes` matching `pattern`, ordered by file and line
+///
+/// Files in `notes_path` that aren't one of `notes` are ignored, as are
+/// deleted notes. At most `limit` matches are returned.
+pub fn grep_notes(
+    notes_path: &Path,
+    notes: &[Note],
+    pattern: &str,
+    options: GrepOptions,
+    limit: usize,
+) -> Result<Vec<SearchResult>> {
+    let results = matching_lines(notes_path, notes, pattern, options, limit)?
+        .into_iter()
+        .map(|(note, m)| SearchResult {
+            note_id: note.id.to_string(),
+            title: note.title.clone(),
+            snippet: m.text,
+            score: 1.0,
+            chunk_type: None,
+            tags: note.tags(),
+            updated_at: Some(note.updated_at.to_rfc3339()),
+            chunk: None,
+            line: Some(m.line),
+            highlights: Vec::new(),
+        })
+        .collect();
+    Ok(results)
+}
+
+/// Like [`grep_notes`], but returning each match's file path instead of
+/// note metadata
+pub fn grep_matches(
+    notes_path: &Path,
+    notes: &[Note],
+    pattern: &str,
+    options: GrepOptions,
+    limit: usize,
+) -> Result<Vec<GrepMatch>> {
+    let results = matching_lines(notes_path, notes, pattern, options, limit)?
+        .into_iter()
+        .map(|(note, m)| GrepMatch {
+            note_id: note.id.to_string(),
+            title: note.title.clone(),
+            path: m.path.to_string_lossy().replace('\\', "/"),
+            line: m.line,
+            text: m.text,
+        })
+        .collect();
+    Ok(results)
+}
+
+/// The first `limit` lines of `notes` matching `pattern` with their notes,
+/// ordered by file and line, their line endings trimmed
+fn matching_lines<'a>(
+    notes_path: &Path,
+    notes: &'a [Note],
+    pattern: &str,
+    options: GrepOptions,
+    limit: usize,
+) -> Result<Vec<(&'a Note, LineMatch)>> {
+    let by_path: HashMap<&Path, &Note> = notes
+        .iter()
+        .filter(|n| !n.is_deleted)
+        .map(|n| (n.file_path.as_path(), n))
+        .collect();
+    if limit == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut matches = match ripgrep(notes_path, pattern, options, &by_path, limit)? {
+        Some(matches) => matches,
+        None => scan(notes_path, &by_path, &regex(pattern, options)?, options, limit)?,
+    };
+    matches.sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
+
+    Ok(matches
+        .into_iter()
+        .filter_map(|mut m| {
+            let note = *by_path.get(m.path.as_path())?;
+            m.text.truncate(m.text.trim_end_matches(['\r', '\n']).len());
+            Some((note, m))
+        })
+        .collect())
+}
+
+fn regex(pattern: &str, options: GrepOptions) -> Result<Regex> {
+    let pattern = if options.fixed_strings {
+        regex::escape(pattern)
+    } else {
+        pattern.to_string()
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(options.ignore_case)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| Error::Search(format!("Invalid pattern: {}", e)))
+}
+
+/// Match the files of `notes` with the regex crate, in path order, up to
+/// `limit` matches
+fn scan(
+    notes_path: &Path,
+    notes: &HashMap<&Path, &Note>,
+    regex: &Regex,
+    options: GrepOptions,
+    limit: usize,
+) -> Result<Vec<LineMatch>> {
+    let mut files: Vec<&Path> = notes.keys().copied().collect();
+    files.sort();
+
+    let mut matches = Vec::new();
+    for file in files {
+        let path = notes_path.join(file);
+        if let Some(max) = options.max_file_size {
+            match std::fs::metadata(&path) {
+                Ok(metadata) if metadata.len() > max => continue,
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        for (i, text) in content.lines().enumerate() {
+            if regex.is_match(text) {
+                matches.push(LineMatch {
+                    path: file.to_path_buf(),
+                    line: i as u32 + 1,
+                    text: text.to_string(),
+                });
+                if matches.len() >= limit {
+                    return Ok(matches);
+                }
+            }
+        }
+    }
+    Ok(matches)
+}
+
+/// One line of `rg --json` output; only matches are of interest
+#[derive(Deserialize)]
+struct RgMessage {
+    #[serde(rename = "type")]
+    kind: String,
+    data: Option<RgMatch>,
+}
+
+#[derive(Deserialize)]
+struct RgMatch {
+    path: Option<RgText>,
+    lines: Option<RgText>,
+    line_number: Option<u32>,
+}
+
+/// ripgrep's text fields; non-UTF-8 data comes as `bytes` instead
+#[derive(Deserialize)]
+struct RgText {
+    text: Option<String>,
+}
+
+/// Match the Markdown files under `notes_path` with ripgrep, in path order,
+/// up to `limit` matches in files of `notes`, or `None` when it isn't
+/// installed
+fn ripgrep(
+    notes_path: &Path,
+    pattern: &str,
+    options: GrepOptions,
+    notes: &HashMap<&Path, &Note>,
+    limit: usize,
+) -> Result<Option<Vec<LineMatch>>> {
+    let mut command = Command::new("rg");
+    // Search what `scan` searches, whatever the user's ripgrep setup
+    command.args(["--no-config", "--no-ignore", "--hidden"]);
+    command.args(["--json", "--sort", "path", "--glob", "*.md"]);
+    command.arg("--max-count").arg(limit.to_string());
+    command.arg("--regex-size-limit").arg(REGEX_SIZE_LIMIT.to_string());
+    if let Some(max) = options.max_file_size {
+        command.arg("--max-filesize").arg(max.to_string());
+    }
+    if options.ignore_case {
+        command.arg("--ignore-case");
+    }
+    if options.fixed_strings {
+        command.arg("--fixed-strings");
+    }
+    command.arg("--regexp").arg(pattern).arg("--").arg(".").current_dir(notes_path);
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    // Drain stderr on the side so a chatty ripgrep can't block on it
+    let stderr = child.stderr.take().map(|mut stderr| {
+        std::thread::spawn(move || {
+            let mut errors = String::new();
+            let _ = stderr.read_to_string(&mut errors);
+            errors
+        })
+    });
+
+    let matches = match child.stdout.take() {
+        Some(stdout) => read_matches(BufReader::new(stdout), notes, limit),
+        None => Ok(Vec::new()),
+    };
+    let stopped_early = matches.as_ref().map_or(true, |m| m.len() >= limit);
+    if stopped_early {
+        let _ = child.kill();
+    }
+    let status = child.wait()?;
+    let errors = stderr.and_then(|t| t.join().ok()).unwrap_or_default();
+    let matches = matches?;
+
+    // 1 means nothing matched, 2 an error such as an invalid pattern
+    if !stopped_early && status.code() == Some(2) {
+        return Err(Error::Search(format!("ripgrep failed: {}", errors.trim())));
+    }
+    Ok(Some(matches))
+}
+
+/// Read `rg --json` output until `limit` matches in files of `notes`
+fn read_matches(output: impl BufRead, notes: &HashMap<&Path, &Note>, limit: usize) -> Result<Vec<LineMatch>> {
+    let mut matches = Vec::new();
+    for line in output.split(b'\n') {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let message: RgMessage = serde_json::from_slice(&line)?;
+        let Some(data) = message.data.filter(|_| message.kind == "match") else {
+            continue;
+        };
+        let (Some(path), Some(text), Some(line)) = (
+            data.path.and_then(|p| p.text),
+            data.lines.and_then(|l| l.text),
+            data.line_number,
+        ) else {
+            continue;
+        };
+        let path = Path::new(&path).strip_prefix(".").map(Path::to_path_buf).unwrap_or_else(|_| path.into());
+        if !notes.contains_key(path.as_path()) {
+            continue;
+        }
+        matches.push(LineMatch { path, line, text });
+        if matches.len() >= limit {
+            break;
+        }
+    }
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_strings_escape_pattern() {
+        let options = GrepOptions {
+            fixed_strings: true,
+            ..GrepOptions::default()
+        };
+        assert!(regex("a.b(", options).unwrap().is_match("x a.b( y"));
+        assert!(!regex("a.b", options).unwrap().is_match("axb"));
+        assert!(regex("a.b(", GrepOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_oversized_pattern_is_rejected() {
+        let pattern = format!("(?:{}){{1000}}", "\\w".repeat(100));
+        assert!(regex(&pattern, GrepOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let options = GrepOptions {
+            ignore_case: true,
+            ..GrepOptions::default()
+        };
+        assert!(regex("tokio", options).unwrap().is_match("Tokio runtime"));
+        assert!(!regex("tokio", GrepOptions::default()).unwrap().is_match("Tokio runtime"));
+    }
+}
@@ -0,0 +1,408 @@
+//! Approximate nearest neighbor index
+//!
+//! A hierarchical navigable small world (HNSW) graph over normalized vectors,
+//! scored by cosine similarity. Vectors are added one at a time as chunks are
+//! embedded; removed vectors stay in the graph as tombstones, still used for
+//! navigation but never returned, until [`Hnsw::needs_compaction`] says the
+//! index is worth rebuilding. Vectors are kept at the precision given to
+//! [`Hnsw::with_quantization`].
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use super::quantize::Vector;
+use crate::config::Quantization;
+
+/// Links per node on the upper layers
+const M: usize = 16;
+/// Links per node on the bottom layer
+const M0: usize = 2 * M;
+/// Candidates considered while linking a new node
+const EF_CONSTRUCTION: usize = 100;
+/// Candidates considered while searching, at least
+const EF_SEARCH: usize = 64;
+
+/// A neighbor candidate, ordered by similarity
+#[derive(Debug, Clone, Copy)]
+struct Scored {
+    sim: f32,
+    node: usize,
+}
+
+impl PartialEq for Scored {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sim.total_cmp(&other.sim).then(self.node.cmp(&other.node))
+    }
+}
+
+struct Node {
+    label: usize,
+    vector: Vector,
+    /// Neighbors on each layer the node is on, bottom layer first
+    links: Vec<Vec<usize>>,
+    deleted: bool,
+}
+
+/// HNSW index mapping vectors to caller-chosen labels
+pub struct Hnsw {
+    nodes: Vec<Node>,
+    labels: HashMap<usize, usize>,
+    entry: Option<usize>,
+    deleted: usize,
+    level_mult: f64,
+    rng: u64,
+    quantization: Quantization,
+}
+
+impl Default for Hnsw {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hnsw {
+    pub fn new() -> Self {
+        Self::with_quantization(Quantization::default())
+    }
+
+    /// Index keeping its vectors at `quantization`
+    pub fn with_quantization(quantization: Quantization) -> Self {
+        Self {
+            nodes: Vec::new(),
+            labels: HashMap::new(),
+            entry: None,
+            deleted: 0,
+            level_mult: 1.0 / (M as f64).ln(),
+            rng: 0x9E37_79B9_7F4A_7C15,
+            quantization,
+        }
+    }
+
+    /// Number of vectors that can be returned
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// Whether tombstones outnumber live vectors, making a rebuild worthwhile
+    pub fn needs_compaction(&self) -> bool {
+        self.deleted > 0 && self.deleted >= self.labels.len()
+    }
+
+    /// Add `vector` under `label`, replacing any vector it had
+    pub fn insert(&mut self, label: usize, vector: &[f32]) {
+        self.remove(label);
+        let Some(query) = normalize(vector) else {
+            return;
+        };
+
+        let id = self.nodes.len();
+        let level = self.random_level();
+        self.nodes.push(Node {
+            label,
+            vector: Vector::new(&query, self.quantization),
+            links: vec![Vec::new(); level + 1],
+            deleted: false,
+        });
+        self.labels.insert(label, id);
+
+        let Some(entry) = self.entry else {
+            self.entry = Some(id);
+            return;
+        };
+        let top = self.nodes[entry].links.len() - 1;
+
+        // Descend greedily to the new node's top layer, then link it on
+        // every layer from there down
+        let mut nearest = self.scored(&query, entry);
+        for layer in (level + 1..=top).rev() {
+            nearest = self.greedy(&query, nearest, layer);
+        }
+        let mut entries = vec![nearest];
+        for layer in (0..=level.min(top)).rev() {
+            let candidates = self.search_layer(&query, &entries, EF_CONSTRUCTION, layer);
+            let max_links = if layer == 0 { M0 } else { M };
+            let neighbors = self.select(&candidates, max_links);
+            for &neighbor in &neighbors {
+                self.link(neighbor, id, layer, max_links);
+            }
+            self.nodes[id].links[layer] = neighbors;
+            entries = candidates;
+        }
+
+        if level > top {
+            self.entry = Some(id);
+        }
+    }
+
+    /// Stop returning the vector stored under `label`
+    pub fn remove(&mut self, label: usize) {
+        if let Some(id) = self.labels.remove(&label) {
+            self.nodes[id].deleted = true;
+            self.deleted += 1;
+        }
+    }
+
+    /// Up to `k` labels whose vectors are most similar to `query`, with
+    /// their cosine similarity, most similar first
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<(f32, usize)> {
+        let (Some(entry), Some(query)) = (self.entry, normalize(query)) else {
+            return Vec::new();
+        };
+        if k == 0 {
+            return Vec::new();
+        }
+
+        let mut nearest = self.scored(&query, entry);
+        for layer in (1..self.nodes[entry].links.len()).rev() {
+            nearest = self.greedy(&query, nearest, layer);
+        }
+        // Tombstones take up room among the candidates, so look further
+        let ef = (k + self.deleted.min(k * 4)).max(EF_SEARCH);
+        let mut found = self.search_layer(&query, &[nearest], ef, 0);
+        found.retain(|s| !self.nodes[s.node].deleted);
+        found.truncate(k);
+        found.into_iter().map(|s| (s.sim, self.nodes[s.node].label)).collect()
+    }
+
+    fn scored(&self, query: &[f32], node: usize) -> Scored {
+        Scored {
+            sim: self.nodes[node].vector.dot(query),
+            node,
+        }
+    }
+
+    /// Follow links on `layer` while they lead closer to `query`
+    fn greedy(&self, query: &[f32], mut nearest: Scored, layer: usize) -> Scored {
+        loop {
+            let mut improved = false;
+            for &next in &self.nodes[nearest.node].links[layer] {
+                let candidate = self.scored(query, next);
+                if candidate > nearest {
+                    nearest = candidate;
+                    improved = true;
+                }
+            }
+            if !improved {
+                return nearest;
+            }
+        }
+    }
+
+    /// Best `ef` nodes on `layer` reachable from `entries`, most similar first
+    fn search_layer(&self, query: &[f32], entries: &[Scored], ef: usize, layer: usize) -> Vec<Scored> {
+        let mut visited: HashSet<usize> = entries.iter().map(|s| s.node).collect();
+        let mut candidates: BinaryHeap<Scored> = entries.iter().copied().collect();
+        let mut best: BinaryHeap<Reverse<Scored>> = entries.iter().copied().map(Reverse).collect();
+        while best.len() > ef {
+            best.pop();
+        }
+
+        while let Some(current) = candidates.pop() {
+            let worst = best.peek().map(|r| r.0);
+            if best.len() >= ef && worst.is_some_and(|w| current < w) {
+                break;
+            }
+            let Some(links) = self.nodes[current.node].links.get(layer) else {
+                continue;
+            };
+            for &next in links {
+                if !visited.insert(next) {
+                    continue;
+                }
+                let candidate = self.scored(query, next);
+                if best.len() < ef || best.peek().is_some_and(|w| candidate > w.0) {
+                    candidates.push(candidate);
+                    best.push(Reverse(candidate));
+                    if best.len() > ef {
+                        best.pop();
+                    }
+                }
+            }
+        }
+
+        let mut found: Vec<Scored> = best.into_iter().map(|r| r.0).collect();
+        found.sort_by(|a, b| b.cmp(a));
+        found
+    }
+
+    /// Up to `max` of `candidates` (most similar first) to link to, skipping
+    /// ones closer to an already chosen neighbor than to the new node so
+    /// links spread out in different directions
+    fn select(&self, candidates: &[Scored], max: usize) -> Vec<usize> {
+        let mut chosen: Vec<usize> = Vec::with_capacity(max);
+        for candidate in candidates {
+            if chosen.len() == max {
+                break;
+            }
+            let vector = self.nodes[candidate.node].vector.to_f32();
+            if chosen
+                .iter()
+                .all(|&c| self.nodes[c].vector.dot(&vector) < candidate.sim)
+            {
+                chosen.push(candidate.node);
+            }
+        }
+        // Fill up with the closest skipped candidates
+        for candidate in candidates {
+            if chosen.len() == max {
+                break;
+            }
+            if !chosen.contains(&candidate.node) {
+                chosen.push(candidate.node);
+            }
+        }
+        chosen
+    }
+
+    /// Link `from` to `to` on `layer`, dropping `from`'s least similar link
+    /// when it has too many
+    fn link(&mut self, from: usize, to: usize, layer: usize, max_links: usize) {
+        self.nodes[from].links[layer].push(to);
+        if self.nodes[from].links[layer].len() <= max_links {
+            return;
+        }
+        let vector = self.nodes[from].vector.to_f32();
+        let (worst, _) = self.nodes[from].links[layer]
+            .iter()
+            .enumerate()
+            .map(|(i, &n)| (i, self.nodes[n].vector.dot(&vector)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .expect("links are not empty");
+        self.nodes[from].links[layer].swap_remove(worst);
+    }
+
+    /// Layer a new node tops out at, exponentially less likely the higher
+    fn random_level(&mut self) -> usize {
+        // splitmix64, so the graph is the same for the same inserts
+        self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        let uniform = ((z >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        (-uniform.ln() * self.level_mult) as usize
+    }
+}
+
+fn normalize(vector: &[f32]) -> Option<Vec<f32>> {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    (norm > 0.0).then(|| vector.iter().map(|v| v / norm).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dot(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    fn vectors(count: usize, dim: usize) -> Vec<Vec<f32>> {
+        let mut state = 42u64;
+        let mut next = || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((state >> 33) as f32 / (1u64 << 31) as f32) - 0.5
+        };
+        (0..count).map(|_| (0..dim).map(|_| next()).collect()).collect()
+    }
+
+    fn exact(data: &[Vec<f32>], query: &[f32], k: usize, skip: &HashSet<usize>) -> Vec<usize> {
+        let query = normalize(query).unwrap();
+        let mut scored: Vec<(f32, usize)> = data
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !skip.contains(i))
+            .map(|(i, v)| (dot(&query, &normalize(v).unwrap()), i))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.into_iter().take(k).map(|(_, i)| i).collect()
+    }
+
+    #[test]
+    fn test_recall_matches_exact_search() {
+        let data = vectors(1000, 32);
+        let mut index = Hnsw::new();
+        for (i, v) in data.iter().enumerate() {
+            index.insert(i, v);
+        }
+        assert_eq!(index.len(), 1000);
+
+        let queries = vectors(1050, 32).split_off(1000);
+        let mut hits = 0;
+        for query in &queries {
+            let expected = exact(&data, query, 10, &HashSet::new());
+            let found: Vec<usize> = index.search(query, 10).into_iter().map(|(_, l)| l).collect();
+            hits += found.iter().filter(|l| expected.contains(l)).count();
+        }
+        let recall = hits as f32 / (queries.len() * 10) as f32;
+        assert!(recall > 0.9, "recall {}", recall);
+    }
+
+    #[test]
+    fn test_int8_recall() {
+        let data = vectors(500, 32);
+        let mut index = Hnsw::with_quantization(Quantization::Int8);
+        for (i, v) in data.iter().enumerate() {
+            index.insert(i, v);
+        }
+
+        let queries = vectors(520, 32).split_off(500);
+        let mut hits = 0;
+        for query in &queries {
+            let expected = exact(&data, query, 10, &HashSet::new());
+            let found: Vec<usize> = index.search(query, 10).into_iter().map(|(_, l)| l).collect();
+            hits += found.iter().filter(|l| expected.contains(l)).count();
+        }
+        let recall = hits as f32 / (queries.len() * 10) as f32;
+        assert!(recall > 0.85, "recall {}", recall);
+    }
+
+    #[test]
+    fn test_removed_vectors_are_not_returned() {
+        let data = vectors(300, 8);
+        let mut index = Hnsw::new();
+        for (i, v) in data.iter().enumerate() {
+            index.insert(i, v);
+        }
+
+        let removed: HashSet<usize> = (0..300).step_by(2).collect();
+        for &label in &removed {
+            index.remove(label);
+        }
+        assert_eq!(index.len(), 150);
+        assert!(index.needs_compaction());
+
+        let found = index.search(&data[0], 5);
+        assert_eq!(found.len(), 5);
+        assert!(found.iter().all(|(_, l)| !removed.contains(l)));
+        assert!(found.windows(2).all(|w| w[0].0 >= w[1].0));
+    }
+
+    #[test]
+    fn test_reinsert_replaces_vector() {
+        let mut index = Hnsw::new();
+        index.insert(1, &[1.0, 0.0]);
+        index.insert(2, &[0.0, 1.0]);
+        index.insert(1, &[0.0, 0.9]);
+        assert_eq!(index.len(), 2);
+
+        let found = index.search(&[0.0, 1.0], 2);
+        assert!((found[0].0 - 1.0).abs() < 1e-6);
+        assert!((found[1].0 - 1.0).abs() < 1e-6);
+        assert!(index.search(&[0.0, 0.0], 1).is_empty());
+    }
+}
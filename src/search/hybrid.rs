@@ -0,0 +1,130 @@
+//! Hybrid search via reciprocal rank fusion
+//!
+//! Full-text and semantic scores live on different scales (BM25 vs cosine
+//! similarity), so results are merged by rank instead: each list contributes
+//! `weight / (k + rank)` for every note it returns, and notes are ordered by
+//! the sum.
+
+use std::collections::HashMap;
+
+use crate::types::SearchResult;
+
+/// Rank offset damping the influence of the very top results
+///
+/// 60 is the value from the original RRF paper and works well in practice.
+pub const RRF_K: f32 = 60.0;
+
+/// Merge full-text and semantic results with reciprocal rank fusion
+///
+/// `semantic_weight` (clamped to `0.0..=1.0`) is the share given to the
+/// semantic list; the full-text list gets the rest. Each result's `score` is
+/// replaced by its fused score. When a note appears in both lists, the
+/// full-text result's title and snippet are kept since they carry the
+/// matched terms.
+pub fn reciprocal_rank_fusion(
+    fulltext: Vec<SearchResult>,
+    semantic: Vec<SearchResult>,
+    semantic_weight: f32,
+    limit: usize,
+) -> Vec<SearchResult> {
+    let semantic_weight = semantic_weight.clamp(0.0, 1.0);
+    fuse_rankings(
+        vec![(fulltext, 1.0 - semantic_weight), (semantic, semantic_weight)],
+        limit,
+    )
+}
+
+/// Merge any number of weighted rankings with reciprocal rank fusion
+///
+/// When a note appears in several lists, the result from the earliest list
+/// is kept; ties keep that order too.
+pub(crate) fn fuse_rankings(rankings: Vec<(Vec<SearchResult>, f32)>, limit: usize) -> Vec<SearchResult> {
+    let mut fused: Vec<SearchResult> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    for (results, weight) in rankings {
+        for (rank, mut result) in results.into_iter().enumerate() {
+            let contribution = weight / (RRF_K + rank as f32 + 1.0);
+            match positions.get(&result.note_id) {
+                Some(&i) => fused[i].score += contribution,
+                None => {
+                    result.score = contribution;
+                    positions.insert(result.note_id.clone(), fused.len());
+                    fused.push(result);
+                }
+            }
+        }
+    }
+
+    // Stable sort keeps list order for ties
+    fused.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    fused.truncate(limit);
+    fused
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(note_id: &str, snippet: &str) -> SearchResult {
+        SearchResult {
+            note_id: note_id.to_string(),
+            title: String::new(),
+            snippet: snippet.to_string(),
+            score: 0.0,
+            chunk_type: None,
+            tags: Vec::new(),
+            updated_at: None,
+            chunk: None,
+            line: None,
+            highlights: Vec::new(),
+        }
+    }
+
+    fn ids(results: &[SearchResult]) -> Vec<&str> {
+        results.iter().map(|r| r.note_id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_notes_in_both_lists_rank_first() {
+        let fulltext = vec![result("a", "ft"), result("b", "ft")];
+        let semantic = vec![result("c", "sem"), result("b", "sem")];
+
+        let fused = reciprocal_rank_fusion(fulltext, semantic, 0.5, 10);
+        assert_eq!(ids(&fused), vec!["b", "a", "c"]);
+        assert_eq!(fused[0].snippet, "ft");
+    }
+
+    #[test]
+    fn test_weight_favours_one_list() {
+        let fulltext = vec![result("a", ""), result("b", "")];
+        let semantic = vec![result("b", ""), result("a", "")];
+
+        let fused = reciprocal_rank_fusion(fulltext.clone(), semantic.clone(), 0.8, 10);
+        assert_eq!(ids(&fused), vec!["b", "a"]);
+
+        let fused = reciprocal_rank_fusion(fulltext, semantic, 0.2, 10);
+        assert_eq!(ids(&fused), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_zero_weight_keeps_fulltext_order() {
+        let fulltext = vec![result("a", ""), result("b", "")];
+        let semantic = vec![result("c", ""), result("b", "")];
+
+        let fused = reciprocal_rank_fusion(fulltext, semantic, 0.0, 2);
+        assert_eq!(ids(&fused), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_limit_and_empty_inputs() {
+        assert!(reciprocal_rank_fusion(Vec::new(), Vec::new(), 0.5, 10).is_empty());
+
+        let fulltext = vec![result("a", ""), result("b", ""), result("c", "")];
+        assert_eq!(reciprocal_rank_fusion(fulltext, Vec::new(), 0.5, 2).len(), 2);
+    }
+}
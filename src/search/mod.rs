@@ -1,7 +1,51 @@
-//! Search layer (full-text and semantic)
+//! Search layer (full-text, semantic and hybrid)
 
+mod analyzer;
+mod classifier;
+mod dispatch;
+mod feedback;
 mod fulltext;
+mod grep;
+mod hnsw;
+mod hybrid;
+mod multi_query;
+mod quantize;
+mod query;
+mod recency;
 mod semantic;
+mod spill;
 
-pub use fulltext::FullTextIndex;
-pub use semantic::SemanticSearch;
+pub use analyzer::VaultTokenizer;
+pub use classifier::{Classification, ClassificationDecision, DecisionLog, QueryClassifier};
+pub use dispatch::{default_mode, SearchClient, SearchDispatcher, SearchMode, SearchRequest};
+pub use feedback::{apply_feedback, feedback_factor, feedback_terms, note_factors};
+pub use fulltext::{canvas_id, canvas_path, DocumentFilter, FullTextIndex};
+pub use grep::{grep_matches, grep_notes, GrepMatch, GrepOptions};
+pub use hybrid::{reciprocal_rank_fusion, RRF_K};
+pub use multi_query::{fuse_subquery_results, split_sentences, QueryDecomposer, SearchStrategy};
+pub use query::{DateFilter, DateOp, SearchQuery, TagMatch};
+pub use recency::{apply_recency, recency_decay};
+pub use semantic::{delete_note_chunks, save_note_chunks, SemanticSearch};
+
+/// Snippet length used when a search doesn't ask for one
+pub const DEFAULT_SNIPPET_LEN: usize = 200;
+
+/// How much of the matched text a search result carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnippetOptions {
+    /// Approximate length of the snippet, in characters
+    pub len: usize,
+    /// Also return the whole matched chunk in [`SearchResult::chunk`]
+    ///
+    /// [`SearchResult::chunk`]: crate::types::SearchResult::chunk
+    pub full_chunk: bool,
+}
+
+impl Default for SnippetOptions {
+    fn default() -> Self {
+        Self {
+            len: DEFAULT_SNIPPET_LEN,
+            full_chunk: false,
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Multi-query retrieval
+//!
+//! A long question embedded as one vector blurs its parts together, so it
+//! retrieves worse than each part would on its own. With `strategy=multi`
+//! the query is split into sub-queries, each is searched separately, and
+//! the rankings are fused by reciprocal rank. Queries split at sentence
+//! boundaries unless `[search.multi_query] command` names a program (an LLM
+//! wrapper, say) that reads the query on stdin and prints one sub-query per
+//! line.
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::io::AsyncWriteExt;
+use utoipa::ToSchema;
+
+use crate::config::MultiQueryConfig;
+use crate::error::{Error, Result};
+use crate::types::SearchResult;
+
+use super::hybrid::fuse_rankings;
+
+/// Longest a decomposition command may run
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How a semantic query is retrieved
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchStrategy {
+    /// One embedding of the whole query
+    #[default]
+    Single,
+    /// Split into sub-queries whose results are fused
+    Multi,
+}
+
+/// Splits queries into sub-queries
+#[derive(Debug, Clone)]
+pub struct QueryDecomposer {
+    max_queries: usize,
+    command: Vec<String>,
+}
+
+impl QueryDecomposer {
+    pub fn new(config: &MultiQueryConfig) -> Self {
+        Self {
+            max_queries: config.max_queries.max(1),
+            command: config.command.clone(),
+        }
+    }
+
+    /// Sub-queries of `query`; the query itself when it doesn't split
+    pub async fn decompose(&self, query: &str) -> Vec<String> {
+        let mut queries = if self.command.is_empty() {
+            split_sentences(query)
+        } else {
+            match self.run_command(query).await {
+                Ok(queries) => queries,
+                Err(e) => {
+                    tracing::warn!("Query decomposition failed, splitting sentences: {}", e);
+                    split_sentences(query)
+                }
+            }
+        };
+
+        queries.truncate(self.max_queries);
+        if queries.is_empty() {
+            queries.push(query.trim().to_string());
+        }
+        queries
+    }
+
+    async fn run_command(&self, query: &str) -> Result<Vec<String>> {
+        let (program, args) = self.command.split_first().expect("command is not empty");
+        let mut child = tokio::process::Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| Error::Search(format!("Failed to run {}: {}", program, e)))?;
+
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        stdin.write_all(query.as_bytes()).await?;
+        drop(stdin);
+
+        let output = tokio::time::timeout(COMMAND_TIMEOUT, child.wait_with_output())
+            .await
+            .map_err(|_| Error::Search(format!("{} timed out", program)))??;
+        if !output.status.success() {
+            return Err(Error::Search(format!("{} exited with {}", program, output.status)));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+/// Split a query at sentence ends (`.`, `?`, `!`, `;` before whitespace)
+/// and line breaks
+pub fn split_sentences(query: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        let boundary = match c {
+            '\n' => true,
+            '.' | '?' | '!' | ';' => chars.peek().is_none_or(|next| next.is_whitespace()),
+            _ => false,
+        };
+        if c != '\n' {
+            current.push(c);
+        }
+        if boundary {
+            sentences.push(std::mem::take(&mut current));
+        }
+    }
+    sentences.push(current);
+
+    sentences
+        .into_iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| s.chars().any(char::is_alphanumeric))
+        .collect()
+}
+
+/// Fuse the rankings of each sub-query, weighing them equally
+pub fn fuse_subquery_results(rankings: Vec<Vec<SearchResult>>, limit: usize) -> Vec<SearchResult> {
+    let weight = 1.0 / rankings.len().max(1) as f32;
+    fuse_rankings(rankings.into_iter().map(|r| (r, weight)).collect(), limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_sentences() {
+        assert_eq!(
+            split_sentences("How do I tune tantivy? Also, what about HNSW recall.\nand backups"),
+            vec!["How do I tune tantivy?", "Also, what about HNSW recall.", "and backups"]
+        );
+        // Dots inside words don't split
+        assert_eq!(split_sentences("config.rs vs main.rs"), vec!["config.rs vs main.rs"]);
+        assert!(split_sentences(" ?! ").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_decompose_caps_and_falls_back() {
+        let decomposer = QueryDecomposer::new(&MultiQueryConfig {
+            max_queries: 2,
+            command: Vec::new(),
+        });
+        assert_eq!(decomposer.decompose("a. b. c.").await, vec!["a.", "b."]);
+        assert_eq!(decomposer.decompose("...").await, vec!["..."]);
+
+        let missing = QueryDecomposer::new(&MultiQueryConfig {
+            max_queries: 4,
+            command: vec!["notidium-no-such-decomposer".to_string()],
+        });
+        assert_eq!(missing.decompose("one. two").await, vec!["one.", "two"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_decompose_with_command() {
+        let decomposer = QueryDecomposer::new(&MultiQueryConfig {
+            max_queries: 4,
+            command: vec!["sh".into(), "-c".into(), "tr ',' '\\n'".into()],
+        });
+        assert_eq!(
+            decomposer.decompose("rust, async ,, tokio").await,
+            vec!["rust", "async", "tokio"]
+        );
+    }
+}
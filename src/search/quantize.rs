@@ -0,0 +1,143 @@
+//! Quantized embeddings
+//!
+//! Semantic search holds every embedding in memory, twice with the nearest
+//! neighbor index. Stored as f16 or int8 they take half or a quarter of the
+//! space; they are turned back into f32 one value at a time while scoring,
+//! so no full-precision copy is ever rebuilt.
+
+use half::f16;
+
+use crate::config::Quantization;
+
+/// An embedding at the precision `embedding.quantization` asks for
+#[derive(Debug, Clone, PartialEq)]
+pub enum Vector {
+    F32(Box<[f32]>),
+    F16(Box<[f16]>),
+    /// Each value is `values[i] as f32 * scale`
+    Int8 { scale: f32, values: Box<[i8]> },
+}
+
+impl Vector {
+    pub fn new(vector: &[f32], quantization: Quantization) -> Self {
+        match quantization {
+            Quantization::F32 => Vector::F32(vector.into()),
+            Quantization::F16 => Vector::F16(vector.iter().map(|&v| f16::from_f32(v)).collect()),
+            Quantization::Int8 => {
+                let max = vector.iter().fold(0.0f32, |max, v| max.max(v.abs()));
+                let scale = if max > 0.0 { max / i8::MAX as f32 } else { 1.0 };
+                Vector::Int8 {
+                    scale,
+                    values: vector.iter().map(|&v| (v / scale).round() as i8).collect(),
+                }
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Vector::F32(values) => values.len(),
+            Vector::F16(values) => values.len(),
+            Vector::Int8 { values, .. } => values.len(),
+        }
+    }
+
+    /// Approximate bytes held in memory
+    pub fn byte_len(&self) -> usize {
+        match self {
+            Vector::F32(values) => values.len() * 4,
+            Vector::F16(values) => values.len() * 2,
+            Vector::Int8 { values, .. } => values.len() + 4,
+        }
+    }
+
+    /// Value at `i`, dequantized
+    fn get(&self, i: usize) -> f32 {
+        match self {
+            Vector::F32(values) => values[i],
+            Vector::F16(values) => values[i].to_f32(),
+            Vector::Int8 { scale, values } => values[i] as f32 * scale,
+        }
+    }
+
+    /// Full-precision copy
+    pub fn to_f32(&self) -> Vec<f32> {
+        (0..self.len()).map(|i| self.get(i)).collect()
+    }
+
+    /// Dot product with `other`; 0 when the dimensions differ
+    pub fn dot(&self, other: &[f32]) -> f32 {
+        if self.len() != other.len() {
+            return 0.0;
+        }
+        match self {
+            Vector::F32(values) => values.iter().zip(other).map(|(a, b)| a * b).sum(),
+            Vector::F16(values) => values.iter().zip(other).map(|(a, b)| a.to_f32() * b).sum(),
+            Vector::Int8 { scale, values } => {
+                scale * values.iter().zip(other).map(|(&a, b)| a as f32 * b).sum::<f32>()
+            }
+        }
+    }
+}
+
+/// Cosine similarity between a query and a stored embedding
+pub fn cosine_similarity(query: &[f32], vector: &Vector) -> f32 {
+    if query.len() != vector.len() {
+        return 0.0;
+    }
+
+    let mut dot = 0.0;
+    let mut norm_a = 0.0;
+    let mut norm_b = 0.0;
+
+    for (i, &a) in query.iter().enumerate() {
+        let b = vector.get(i);
+        dot += a * b;
+        norm_a += a * a;
+        norm_b += b * b;
+    }
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vector(seed: u32, dim: usize) -> Vec<f32> {
+        (0..dim)
+            .map(|i| (((seed * 31 + i as u32 * 17) % 101) as f32 / 50.0) - 1.0)
+            .collect()
+    }
+
+    #[test]
+    fn test_quantized_scores_stay_close() {
+        let query = vector(1, 384);
+        for seed in 2..20 {
+            let stored = vector(seed, 384);
+            let exact = cosine_similarity(&query, &Vector::new(&stored, Quantization::F32));
+            let f16 = cosine_similarity(&query, &Vector::new(&stored, Quantization::F16));
+            let int8 = cosine_similarity(&query, &Vector::new(&stored, Quantization::Int8));
+            assert!((exact - f16).abs() < 1e-3, "f16 drifted: {} vs {}", exact, f16);
+            assert!((exact - int8).abs() < 1e-2, "int8 drifted: {} vs {}", exact, int8);
+        }
+    }
+
+    #[test]
+    fn test_dot_and_round_trip() {
+        let stored = [0.5, -1.0, 0.25, 0.0];
+        let query = [1.0, 1.0, 2.0, 3.0];
+        for quantization in [Quantization::F32, Quantization::F16, Quantization::Int8] {
+            let vector = Vector::new(&stored, quantization);
+            assert!((vector.dot(&query) - 0.0).abs() < 0.01);
+            assert_eq!(vector.to_f32().len(), 4);
+            assert!((vector.to_f32()[1] + 1.0).abs() < 0.01);
+        }
+        assert_eq!(Vector::new(&[0.0, 0.0], Quantization::Int8).to_f32(), vec![0.0, 0.0]);
+        assert_eq!(Vector::new(&stored, Quantization::F16).dot(&[1.0]), 0.0);
+    }
+}
@@ -0,0 +1,355 @@
+//! Search query syntax
+//!
+//! A query is words to rank notes by, mixed with filters every result must
+//! pass:
+//!
+//! - `tag:rust` — the note has the tag, in any letter case
+//! - `path:inbox/` — the note's path in the vault starts with this
+//! - `created:>2024-01-01`, `updated:<=2024-06-30` — the day the note was
+//!   created or last changed compares so (`>`, `>=`, `<`, `<=`, or the day
+//!   itself without an operator)
+//! - `"exact phrase"` — the note contains the phrase; the phrase also ranks
+//!
+//! Filter values can be quoted, as in `path:"work notes/"`. Other `word:word`
+//! tokens are ordinary words, so `std::vec` still searches for itself.
+
+use std::ops::Bound;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::{Error, Result};
+use crate::types::Note;
+
+/// How a note's date compares to a filter's
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateOp {
+    Before,
+    OnOrBefore,
+    On,
+    OnOrAfter,
+    After,
+}
+
+/// A `created:` or `updated:` filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateFilter {
+    pub op: DateOp,
+    pub date: NaiveDate,
+}
+
+impl DateFilter {
+    /// Compare by `op` against `date`, given as YYYY-MM-DD
+    pub fn new(op: DateOp, date: &str) -> Result<Self> {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|_| Error::Search(format!("Invalid date {:?}, expected YYYY-MM-DD", date)))?;
+        Ok(Self { op, date })
+    }
+
+    fn parse(value: &str) -> Result<Self> {
+        let (op, date) = if let Some(date) = value.strip_prefix(">=") {
+            (DateOp::OnOrAfter, date)
+        } else if let Some(date) = value.strip_prefix("<=") {
+            (DateOp::OnOrBefore, date)
+        } else if let Some(date) = value.strip_prefix('>') {
+            (DateOp::After, date)
+        } else if let Some(date) = value.strip_prefix('<') {
+            (DateOp::Before, date)
+        } else {
+            (DateOp::On, value.strip_prefix('=').unwrap_or(value))
+        };
+        Self::new(op, date)
+    }
+
+    /// The instants (UTC) the filter's days span, as range bounds
+    pub fn range(&self) -> (Bound<DateTime<Utc>>, Bound<DateTime<Utc>>) {
+        let start = |date: NaiveDate| date.and_time(chrono::NaiveTime::MIN).and_utc();
+        let day_after = |date: NaiveDate| start(date.succ_opt().unwrap_or(NaiveDate::MAX));
+        match self.op {
+            DateOp::Before => (Bound::Unbounded, Bound::Excluded(start(self.date))),
+            DateOp::OnOrBefore => (Bound::Unbounded, Bound::Excluded(day_after(self.date))),
+            DateOp::On => (Bound::Included(start(self.date)), Bound::Excluded(day_after(self.date))),
+            DateOp::OnOrAfter => (Bound::Included(start(self.date)), Bound::Unbounded),
+            DateOp::After => (Bound::Included(day_after(self.date)), Bound::Unbounded),
+        }
+    }
+
+    fn matches(&self, date: NaiveDate) -> bool {
+        match self.op {
+            DateOp::Before => date < self.date,
+            DateOp::OnOrBefore => date <= self.date,
+            DateOp::On => date == self.date,
+            DateOp::OnOrAfter => date >= self.date,
+            DateOp::After => date > self.date,
+        }
+    }
+}
+
+/// How a list of tags given alongside a query filters notes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TagMatch {
+    /// The note has every tag, as `tag:` filters do
+    #[default]
+    All,
+    /// The note has at least one of the tags
+    Any,
+}
+
+/// A search query split into words and filters
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    /// Words to rank by, phrases still quoted
+    pub text: String,
+    /// Phrases the note must contain
+    pub phrases: Vec<String>,
+    pub tags: Vec<String>,
+    /// Tags of which the note must have at least one, if any are given
+    pub any_tags: Vec<String>,
+    pub paths: Vec<String>,
+    pub created: Vec<DateFilter>,
+    pub updated: Vec<DateFilter>,
+}
+
+impl SearchQuery {
+    /// Split `query` into words and filters, failing on a malformed date
+    pub fn parse(query: &str) -> Result<Self> {
+        let mut parsed = SearchQuery::default();
+        let mut words = Vec::new();
+        for token in tokens(query) {
+            if token.quoted {
+                words.push(format!("\"{}\"", token.text));
+                parsed.phrases.push(token.text);
+                continue;
+            }
+            let filter = token
+                .text
+                .split_once(':')
+                .filter(|(key, value)| !value.is_empty() && FILTERS.contains(key));
+            match filter {
+                Some(("tag", value)) => parsed.tags.push(value.trim_start_matches('#').to_string()),
+                Some(("path", value)) => parsed.paths.push(normalize_path(value)),
+                Some(("created", value)) => parsed.created.push(DateFilter::parse(value)?),
+                Some(("updated", value)) => parsed.updated.push(DateFilter::parse(value)?),
+                _ => words.push(token.text),
+            }
+        }
+        parsed.text = words.join(" ");
+        Ok(parsed)
+    }
+
+    /// Also filter by `tags`, matched as `tag_match` says
+    pub fn with_tags(mut self, tags: &[String], tag_match: TagMatch) -> Self {
+        let tags = tags
+            .iter()
+            .map(|t| t.trim().trim_start_matches('#'))
+            .filter(|t| !t.is_empty())
+            .map(str::to_string);
+        match tag_match {
+            TagMatch::All => self.tags.extend(tags),
+            TagMatch::Any => self.any_tags.extend(tags),
+        }
+        self
+    }
+
+    /// Also filter by creation and modification dates
+    pub fn with_dates(mut self, created: &[DateFilter], updated: &[DateFilter]) -> Self {
+        self.created.extend_from_slice(created);
+        self.updated.extend_from_slice(updated);
+        self
+    }
+
+    /// Whether any filter was given
+    pub fn has_filters(&self) -> bool {
+        self.has_content_filters() || !self.created.is_empty() || !self.updated.is_empty()
+    }
+
+    /// Whether any filter other than dates was given; dates are also indexed
+    /// by full-text search
+    pub fn has_content_filters(&self) -> bool {
+        !self.phrases.is_empty()
+            || !self.tags.is_empty()
+            || !self.any_tags.is_empty()
+            || !self.paths.is_empty()
+    }
+
+    /// The words to rank by, with phrases unquoted, for semantic search
+    pub fn plain_text(&self) -> String {
+        self.text.replace('"', "")
+    }
+
+    /// Whether `note` passes every filter
+    pub fn matches(&self, note: &Note) -> bool {
+        let tags = note.tags();
+        let has_tag = |tag: &String| tags.iter().any(|t| t.eq_ignore_ascii_case(tag));
+        let path = normalize_path(&note.file_path.to_string_lossy());
+        let content = collapse_whitespace(&note.content);
+        self.tags.iter().all(has_tag)
+            && (self.any_tags.is_empty() || self.any_tags.iter().any(has_tag))
+            && self.paths.iter().all(|prefix| path.starts_with(prefix.as_str()))
+            && self.created.iter().all(|f| f.matches(note.created_at.date_naive()))
+            && self.updated.iter().all(|f| f.matches(note.updated_at.date_naive()))
+            && self
+                .phrases
+                .iter()
+                .all(|phrase| content.contains(&collapse_whitespace(phrase)))
+    }
+}
+
+/// Keys of the filters a query can hold
+const FILTERS: [&str; 4] = ["tag", "path", "created", "updated"];
+
+pub(super) struct Token {
+    pub text: String,
+    /// Whether the whole token was a quoted phrase
+    pub quoted: bool,
+}
+
+/// Split on whitespace outside quotes; an unclosed quote runs to the end
+pub(super) fn tokens(query: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let quoted = c == '"';
+        let mut text = String::new();
+        let mut in_quotes = false;
+        while let Some(&c) = chars.peek() {
+            if c == '"' {
+                in_quotes = !in_quotes;
+                chars.next();
+                if quoted && !in_quotes {
+                    break;
+                }
+                continue;
+            }
+            if c.is_whitespace() && !in_quotes {
+                break;
+            }
+            text.push(c);
+            chars.next();
+        }
+        if !text.trim().is_empty() {
+            tokens.push(Token {
+                text: if quoted { text.trim().to_string() } else { text },
+                quoted,
+            });
+        }
+    }
+    tokens
+}
+
+/// A vault path as filters compare it: forward slashes, no leading slash,
+/// lowercase
+fn normalize_path(path: &str) -> String {
+    path.replace('\\', "/").trim_start_matches('/').to_lowercase()
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_filters_and_words() {
+        let query = SearchQuery::parse(
+            r#"tag:rust created:>2024-01-01 path:inbox/ "exact phrase" kubernetes std::vec"#,
+        )
+        .unwrap();
+        assert_eq!(query.text, r#""exact phrase" kubernetes std::vec"#);
+        assert_eq!(query.plain_text(), "exact phrase kubernetes std::vec");
+        assert_eq!(query.phrases, vec!["exact phrase"]);
+        assert_eq!(query.tags, vec!["rust"]);
+        assert_eq!(query.paths, vec!["inbox/"]);
+        assert_eq!(
+            query.created,
+            vec![DateFilter {
+                op: DateOp::After,
+                date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()
+            }]
+        );
+        assert!(query.has_filters());
+    }
+
+    #[test]
+    fn test_quoted_values_and_plain_queries() {
+        let query = SearchQuery::parse(r#"path:"Work Notes/" updated:2024-06-30 deploy"#).unwrap();
+        assert_eq!(query.paths, vec!["work notes/"]);
+        assert_eq!(query.updated[0].op, DateOp::On);
+        assert_eq!(query.text, "deploy");
+
+        let plain = SearchQuery::parse("how do I deploy tag:").unwrap();
+        assert!(!plain.has_filters());
+        assert_eq!(plain.text, "how do I deploy tag:");
+    }
+
+    #[test]
+    fn test_tag_list_all_or_any() {
+        let mut note = Note::new("Deploy".to_string(), "Body".to_string(), "deploy.md".into());
+        note.frontmatter = Some(crate::types::Frontmatter {
+            tags: vec!["rust".to_string(), "ops".to_string()],
+            ..Default::default()
+        });
+        let tags = vec!["Rust".to_string(), "#go".to_string(), " ".to_string()];
+
+        let all = SearchQuery::parse("deploy").unwrap().with_tags(&tags, TagMatch::All);
+        assert_eq!(all.tags, vec!["Rust", "go"]);
+        assert!(all.has_filters());
+        assert!(!all.matches(&note));
+
+        let any = SearchQuery::parse("deploy").unwrap().with_tags(&tags, TagMatch::Any);
+        assert!(any.matches(&note));
+        let none = SearchQuery::parse("").unwrap().with_tags(&["go".to_string()], TagMatch::Any);
+        assert!(!none.matches(&note));
+        assert!(!SearchQuery::parse("x").unwrap().with_tags(&[], TagMatch::Any).has_filters());
+    }
+
+    #[test]
+    fn test_date_filter_range() {
+        let day = |d: &str| d.parse::<DateTime<Utc>>().unwrap();
+        let filter = |op| DateFilter::new(op, "2024-03-01").unwrap().range();
+        assert_eq!(filter(DateOp::Before), (Bound::Unbounded, Bound::Excluded(day("2024-03-01T00:00:00Z"))));
+        assert_eq!(
+            filter(DateOp::On),
+            (Bound::Included(day("2024-03-01T00:00:00Z")), Bound::Excluded(day("2024-03-02T00:00:00Z")))
+        );
+        assert_eq!(filter(DateOp::After), (Bound::Included(day("2024-03-02T00:00:00Z")), Bound::Unbounded));
+
+        let query = SearchQuery::parse("deploy").unwrap().with_dates(&[], &[DateFilter::new(DateOp::After, "2024-01-01").unwrap()]);
+        assert!(query.has_filters() && !query.has_content_filters());
+    }
+
+    #[test]
+    fn test_invalid_date_rejected() {
+        assert!(SearchQuery::parse("created:>yesterday").is_err());
+        assert!(SearchQuery::parse("updated:2024-13-01").is_err());
+    }
+
+    #[test]
+    fn test_matches_note() {
+        let mut note = Note::new(
+            "Deploy".to_string(),
+            "Use the\nexact  phrase here".to_string(),
+            "Inbox/deploy.md".into(),
+        );
+        note.created_at = "2024-03-01T10:00:00Z".parse().unwrap();
+        note.frontmatter = Some(crate::types::Frontmatter {
+            tags: vec!["Rust".to_string()],
+            ..Default::default()
+        });
+
+        let matching = SearchQuery::parse(r#"tag:rust path:inbox/ created:>=2024-03-01 "EXACT phrase""#).unwrap();
+        assert!(matching.matches(&note));
+        for query in ["tag:go", "path:archive/", "created:<2024-03-01", r#""other phrase""#] {
+            assert!(!SearchQuery::parse(query).unwrap().matches(&note), "{}", query);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Recency-weighted ranking
+//!
+//! For questions like "what was I working on about X" a note edited last
+//! week beats a slightly closer match from two years ago. Recency scales a
+//! result's score by `1 - weight + weight * decay`, where `decay` halves
+//! every `half_life_days` since the note was last updated: a note edited
+//! today keeps its score, a long-forgotten one keeps `1 - weight` of it.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::config::RecencyConfig;
+use crate::types::SearchResult;
+
+/// Share of a score kept after `age_days`, halving every `half_life_days`
+pub fn recency_decay(age_days: f32, half_life_days: f32) -> f32 {
+    if half_life_days <= 0.0 {
+        return 1.0;
+    }
+    0.5f32.powf(age_days.max(0.0) / half_life_days)
+}
+
+/// Rescale `results` by how recently their notes were updated and sort
+/// them by the new score; notes missing from `updated_at` count as old
+pub fn apply_recency(
+    mut results: Vec<SearchResult>,
+    updated_at: &HashMap<String, DateTime<Utc>>,
+    recency: &RecencyConfig,
+    now: DateTime<Utc>,
+) -> Vec<SearchResult> {
+    let weight = recency.weight.clamp(0.0, 1.0);
+    for result in &mut results {
+        let decay = updated_at.get(&result.note_id).map_or(0.0, |at| {
+            let age_days = (now - *at).num_seconds() as f32 / 86_400.0;
+            recency_decay(age_days, recency.half_life_days)
+        });
+        result.score *= 1.0 - weight + weight * decay;
+    }
+
+    // Stable sort keeps similarity order for ties
+    results.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn result(note_id: &str, score: f32) -> SearchResult {
+        SearchResult {
+            note_id: note_id.to_string(),
+            title: String::new(),
+            snippet: String::new(),
+            score,
+            chunk_type: None,
+            tags: Vec::new(),
+            updated_at: None,
+            chunk: None,
+            line: None,
+            highlights: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_recency_decay() {
+        assert_eq!(recency_decay(0.0, 30.0), 1.0);
+        assert!((recency_decay(30.0, 30.0) - 0.5).abs() < 1e-6);
+        assert!((recency_decay(60.0, 30.0) - 0.25).abs() < 1e-6);
+        // Timestamps in the future count as now, a zero half-life disables decay
+        assert_eq!(recency_decay(-5.0, 30.0), 1.0);
+        assert_eq!(recency_decay(400.0, 0.0), 1.0);
+    }
+
+    #[test]
+    fn test_recent_notes_overtake_closer_old_ones() {
+        let now = Utc::now();
+        let updated_at = HashMap::from([
+            ("old".to_string(), now - Duration::days(365)),
+            ("fresh".to_string(), now - Duration::days(1)),
+        ]);
+        let recency = RecencyConfig {
+            half_life_days: 30.0,
+            weight: 0.5,
+        };
+
+        let ranked = apply_recency(
+            vec![result("old", 0.9), result("fresh", 0.8), result("unknown", 0.85)],
+            &updated_at,
+            &recency,
+            now,
+        );
+        let ids: Vec<&str> = ranked.iter().map(|r| r.note_id.as_str()).collect();
+        assert_eq!(ids, vec!["fresh", "old", "unknown"]);
+        assert!(ranked[1].score > 0.45 && ranked[1].score < 0.46);
+    }
+}
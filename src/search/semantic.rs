@@ -1,134 +1,597 @@
 //! Semantic search using embeddings
 
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 
+use rayon::prelude::*;
+use tokio::sync::RwLock;
+
+use crate::config::{Quantization, SemanticIndex};
 use crate::embed::Embedder;
 use crate::error::Result;
+use crate::store::VectorStore;
 use crate::types::{Chunk, QueryType, SearchResult};
 
+use super::classifier::{ClassificationDecision, DecisionLog, QueryClassifier};
+use super::hnsw::Hnsw;
+use super::quantize::{cosine_similarity, Vector};
+use super::spill::{SpillFile, SpilledChunk};
+use super::SnippetOptions;
+
+/// Slots scored at a time by exact search
+const SCAN_BLOCK: usize = 4096;
+
 /// Semantic search engine
 pub struct SemanticSearch {
     embedder: Arc<Embedder>,
-    chunks: Vec<Chunk>,
+    /// Chunks by slot; removing a chunk empties its slot until the next
+    /// compaction, so slots stay valid labels in the nearest neighbor indexes
+    slots: Vec<Option<Entry>>,
+    live: usize,
+    /// Indexes over prose and code embeddings; `None` for exact search
+    ann: Option<AnnIndexes>,
+    index: SemanticIndex,
+    /// Where chunks saved through [`save_note_chunks`] are persisted
+    vectors: Option<Arc<VectorStore>>,
+    classifier: QueryClassifier,
+    decisions: DecisionLog,
+    /// Precision embeddings are kept at
+    quantization: Quantization,
+    /// Bytes of chunk content and embeddings held in memory before they are
+    /// spilled to a file in `spill_dir`; 0 for no limit
+    memory_budget: usize,
+    spill_dir: Option<PathBuf>,
+    /// Estimate of the bytes held
+    held_bytes: usize,
+    spill: Option<SpillFile>,
+}
+
+/// A chunk held for search
+struct Entry {
+    /// The chunk, its embeddings taken out, and its content too once spilled
+    chunk: Chunk,
+    data: EntryData,
+}
+
+enum EntryData {
+    Held { prose: Option<Vector>, code: Option<Vector> },
+    Spilled(SpilledChunk),
+}
+
+impl Entry {
+    fn new(mut chunk: Chunk, quantization: Quantization) -> Self {
+        let prose = chunk.prose_embedding.take().map(|v| Vector::new(&v, quantization));
+        let code = chunk.code_embedding.take().map(|v| Vector::new(&v, quantization));
+        Self {
+            chunk,
+            data: EntryData::Held { prose, code },
+        }
+    }
+
+    /// Chunk written to a spill file as `spilled`, keeping only what
+    /// identifies it in memory
+    fn spilled(mut chunk: Chunk, spilled: SpilledChunk) -> Self {
+        chunk.prose_embedding = None;
+        chunk.code_embedding = None;
+        chunk.content = String::new();
+        Self {
+            chunk,
+            data: EntryData::Spilled(spilled),
+        }
+    }
+
+    /// Approximate bytes of content and embeddings held in memory
+    fn held_bytes(&self) -> usize {
+        match &self.data {
+            EntryData::Held { prose, code } => {
+                self.chunk.content.len()
+                    + prose.as_ref().map_or(0, Vector::byte_len)
+                    + code.as_ref().map_or(0, Vector::byte_len)
+            }
+            EntryData::Spilled(_) => 0,
+        }
+    }
+
+    /// Similarity of the prose or code embedding to `query`, if there is one
+    fn score(&self, query: &[f32], code: bool, spill: Option<&SpillFile>) -> Option<f32> {
+        match &self.data {
+            EntryData::Held { prose, code: code_emb } => {
+                let emb = if code { code_emb } else { prose };
+                emb.as_ref().map(|emb| cosine_similarity(query, emb))
+            }
+            EntryData::Spilled(spilled) => {
+                let span = if code { spilled.code } else { spilled.prose };
+                Some(spill?.cosine_similarity(query, span?))
+            }
+        }
+    }
+
+    fn prose(&self, spill: Option<&SpillFile>) -> Option<Vec<f32>> {
+        match &self.data {
+            EntryData::Held { prose, .. } => prose.as_ref().map(Vector::to_f32),
+            EntryData::Spilled(spilled) => Some(spill?.vector(spilled.prose?)),
+        }
+    }
+
+    fn code(&self, spill: Option<&SpillFile>) -> Option<Vec<f32>> {
+        match &self.data {
+            EntryData::Held { code, .. } => code.as_ref().map(Vector::to_f32),
+            EntryData::Spilled(spilled) => Some(spill?.vector(spilled.code?)),
+        }
+    }
+
+    fn content<'a>(&'a self, spill: Option<&'a SpillFile>) -> &'a str {
+        match &self.data {
+            EntryData::Held { .. } => &self.chunk.content,
+            EntryData::Spilled(spilled) => spill.map_or("", |spill| spill.text(spilled.content)),
+        }
+    }
+
+    /// The chunk with its content and embeddings put back
+    fn to_chunk(&self, spill: Option<&SpillFile>) -> Chunk {
+        Chunk {
+            content: self.content(spill).to_string(),
+            prose_embedding: self.prose(spill),
+            code_embedding: self.code(spill),
+            ..self.chunk.clone()
+        }
+    }
+}
+
+struct AnnIndexes {
+    prose: Hnsw,
+    code: Hnsw,
+}
+
+impl AnnIndexes {
+    fn new(quantization: Quantization) -> Self {
+        Self {
+            prose: Hnsw::with_quantization(quantization),
+            code: Hnsw::with_quantization(quantization),
+        }
+    }
+
+    fn insert(&mut self, slot: usize, entry: &Entry, spill: Option<&SpillFile>) {
+        if let Some(emb) = entry.prose(spill) {
+            self.prose.insert(slot, &emb);
+        }
+        if let Some(emb) = entry.code(spill) {
+            self.code.insert(slot, &emb);
+        }
+    }
+
+    fn remove(&mut self, slot: usize) {
+        self.prose.remove(slot);
+        self.code.remove(slot);
+    }
 }
 
 impl SemanticSearch {
     pub fn new(embedder: Arc<Embedder>) -> Self {
+        Self::with_index(embedder, SemanticIndex::default())
+    }
+
+    /// Semantic search finding nearest chunks the way `index` says
+    pub fn with_index(embedder: Arc<Embedder>, index: SemanticIndex) -> Self {
+        let ann = match index {
+            SemanticIndex::Hnsw => Some(AnnIndexes::new(Quantization::default())),
+            SemanticIndex::Exact => None,
+        };
         Self {
             embedder,
-            chunks: Vec::new(),
+            slots: Vec::new(),
+            live: 0,
+            ann,
+            index,
+            vectors: None,
+            classifier: QueryClassifier::default(),
+            decisions: DecisionLog::default(),
+            quantization: Quantization::default(),
+            memory_budget: 0,
+            spill_dir: None,
+            held_bytes: 0,
+            spill: None,
         }
     }
 
-    /// Load chunks with embeddings
+    /// Keep embeddings in memory at `quantization`
+    pub fn with_quantization(mut self, quantization: Quantization) -> Self {
+        self.quantization = quantization;
+        let chunks = self.all_chunks();
+        self.load_chunks(chunks);
+        self
+    }
+
+    /// Hold at most about `bytes` of chunk content and embeddings in memory
+    ///
+    /// Past the budget, every chunk's content and embeddings move to a file
+    /// in `dir`, read through a memory map. Nearest chunks are then found
+    /// by exact search, scoring the file a block at a time, and only the
+    /// chunks returned are read in whole. 0 turns the limit off.
+    pub fn with_memory_budget(mut self, bytes: usize, dir: impl Into<PathBuf>) -> Self {
+        self.memory_budget = bytes;
+        self.spill_dir = Some(dir.into());
+        let chunks = self.all_chunks();
+        self.load_chunks(chunks);
+        self
+    }
+
+    /// Whether chunks have been spilled out of memory
+    pub fn is_spilled(&self) -> bool {
+        self.spill.is_some()
+    }
+
+    /// Classify queries with `classifier` instead of the built-in rules
+    pub fn with_classifier(mut self, classifier: QueryClassifier) -> Self {
+        self.classifier = classifier;
+        self
+    }
+
+    /// Rules queries are classified with
+    pub fn classifier(&self) -> &QueryClassifier {
+        &self.classifier
+    }
+
+    /// Most recent query type decisions, oldest first
+    pub fn recent_decisions(&self) -> Vec<ClassificationDecision> {
+        self.decisions.recent()
+    }
+
+    /// Persist chunks saved or deleted through [`save_note_chunks`] and
+    /// [`delete_note_chunks`] in `vectors`, so they survive restarts
+    pub fn with_vectors(mut self, vectors: Arc<VectorStore>) -> Self {
+        self.vectors = Some(vectors);
+        self
+    }
+
+    /// Load chunks with embeddings, replacing those held
     pub fn load_chunks(&mut self, chunks: Vec<Chunk>) {
-        self.chunks = chunks;
+        self.clear();
+        self.add_chunks(chunks);
     }
 
     /// Add a chunk
     pub fn add_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk);
+        self.add_chunks(vec![chunk]);
+    }
+
+    /// Add chunks, spilling them once they no longer fit the memory budget
+    pub fn add_chunks(&mut self, chunks: Vec<Chunk>) {
+        for chunk in chunks {
+            let slot = self.slots.len();
+            let entry = match &mut self.spill {
+                Some(spill) => match spill.append(&chunk) {
+                    Ok(spilled) => Entry::spilled(chunk, spilled),
+                    Err(e) => {
+                        tracing::warn!("Failed to spill chunk, keeping it in memory: {}", e);
+                        Entry::new(chunk, self.quantization)
+                    }
+                },
+                None => Entry::new(chunk, self.quantization),
+            };
+            self.held_bytes += entry.held_bytes();
+            if let Some(ann) = &mut self.ann {
+                ann.insert(slot, &entry, None);
+            }
+            self.slots.push(Some(entry));
+            self.live += 1;
+        }
+
+        if let Some(spill) = &mut self.spill {
+            if let Err(e) = spill.sync() {
+                tracing::warn!("Failed to sync spilled chunks: {}", e);
+            }
+        } else if self.memory_budget > 0 && self.held_bytes > self.memory_budget {
+            self.spill_all();
+        }
+    }
+
+    /// Move every chunk's content and embeddings into a new spill file,
+    /// dropping the nearest neighbor indexes, which hold embeddings too
+    fn spill_all(&mut self) {
+        let Some(dir) = self.spill_dir.clone() else {
+            return;
+        };
+        let spilled = SpillFile::create(&dir).and_then(|mut spill| {
+            let mut slots = Vec::with_capacity(self.slots.len());
+            for entry in &self.slots {
+                slots.push(match entry {
+                    Some(entry) => {
+                        let chunk = entry.to_chunk(None);
+                        let spilled = spill.append(&chunk)?;
+                        Some(Entry::spilled(chunk, spilled))
+                    }
+                    None => None,
+                });
+            }
+            spill.sync()?;
+            Ok((spill, slots))
+        });
+        match spilled {
+            Ok((spill, slots)) => {
+                tracing::info!(
+                    "Chunks outgrew the {} MB memory budget, searching them from {}",
+                    self.memory_budget / (1024 * 1024),
+                    dir.display()
+                );
+                self.slots = slots;
+                self.spill = Some(spill);
+                self.ann = None;
+                self.held_bytes = self.slots.iter().flatten().map(Entry::held_bytes).sum();
+            }
+            Err(e) => {
+                tracing::warn!("Failed to spill chunks, keeping them in memory: {}", e);
+                self.memory_budget = 0;
+            }
+        }
     }
 
     /// Remove all chunks for a given note
     pub fn remove_chunks_for_note(&mut self, note_id: uuid::Uuid) {
-        self.chunks.retain(|c| c.note_id != note_id);
+        for (slot, entry) in self.slots.iter_mut().enumerate() {
+            if entry.as_ref().is_some_and(|e| e.chunk.note_id == note_id) {
+                self.held_bytes -= entry.take().map_or(0, |e| e.held_bytes());
+                self.live -= 1;
+                if let Some(ann) = &mut self.ann {
+                    ann.remove(slot);
+                }
+            }
+        }
+
+        // Rebuild once empty slots and tombstones outweigh live chunks
+        let stale = self
+            .ann
+            .as_ref()
+            .is_some_and(|ann| ann.prose.needs_compaction() || ann.code.needs_compaction());
+        if stale || self.slots.len() > 2 * self.live.max(64) {
+            if self.spill.is_some() {
+                // The spill file only grows, so start a new one with what's left
+                let chunks = self.all_chunks();
+                self.load_chunks(chunks);
+            } else {
+                self.slots.retain(Option::is_some);
+                self.reindex();
+            }
+        }
+    }
+
+    /// Recount live chunks and rebuild the nearest neighbor indexes
+    fn reindex(&mut self) {
+        self.live = self.slots.iter().flatten().count();
+        self.held_bytes = self.slots.iter().flatten().map(Entry::held_bytes).sum();
+        if let Some(ann) = &mut self.ann {
+            *ann = AnnIndexes::new(self.quantization);
+            for (slot, entry) in self.slots.iter().enumerate() {
+                if let Some(entry) = entry {
+                    ann.insert(slot, entry, self.spill.as_ref());
+                }
+            }
+        }
+    }
+
+    fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.slots.iter().flatten()
+    }
+
+    /// Every chunk, with its content and embeddings
+    fn all_chunks(&self) -> Vec<Chunk> {
+        self.entries().map(|e| e.to_chunk(self.spill.as_ref())).collect()
+    }
+
+    /// Notes with at least one chunk
+    pub fn note_ids(&self) -> std::collections::HashSet<uuid::Uuid> {
+        self.entries().map(|e| e.chunk.note_id).collect()
+    }
+
+    /// A note's chunks, in the order they were added, with their embeddings
+    /// as held in memory
+    pub fn chunks_for_note(&self, note_id: uuid::Uuid) -> Vec<Chunk> {
+        self.entries()
+            .filter(|e| e.chunk.note_id == note_id)
+            .map(|e| e.to_chunk(self.spill.as_ref()))
+            .collect()
+    }
+
+    /// Slot of the best-matching chunk of each of up to `limit` notes
+    /// nearest to `query`, most similar first, among the notes `keep`
+    /// accepts
+    fn nearest(
+        &self,
+        query: &[f32],
+        code: bool,
+        limit: usize,
+        keep: impl Fn(uuid::Uuid) -> bool + Sync,
+    ) -> Vec<(f32, usize)> {
+        let Some(ann) = &self.ann else {
+            return self.scan(query, code, limit, keep);
+        };
+
+        // Notes usually have several chunks near the query, so ask for more
+        // than `limit` and widen the search until enough notes turn up
+        let index = if code { &ann.code } else { &ann.prose };
+        let mut k = (limit * 4).max(16);
+        loop {
+            let results = self.best_per_note(index.search(query, k), limit, &keep);
+            if results.len() >= limit || k >= index.len() {
+                return results;
+            }
+            k *= 4;
+        }
+    }
+
+    /// Exact nearest chunks, scoring the slots a block at a time and keeping
+    /// only the best chunk of each note, so memory grows with the notes
+    /// rather than the chunks
+    fn scan(
+        &self,
+        query: &[f32],
+        code: bool,
+        limit: usize,
+        keep: impl Fn(uuid::Uuid) -> bool + Sync,
+    ) -> Vec<(f32, usize)> {
+        let spill = self.spill.as_ref();
+        let best = self
+            .slots
+            .par_chunks(SCAN_BLOCK)
+            .enumerate()
+            .map(|(block, entries)| {
+                let mut best: HashMap<uuid::Uuid, (f32, usize)> = HashMap::new();
+                for (i, entry) in entries.iter().enumerate() {
+                    let Some(entry) = entry.as_ref().filter(|e| keep(e.chunk.note_id)) else {
+                        continue;
+                    };
+                    if let Some(score) = entry.score(query, code, spill) {
+                        keep_better(&mut best, entry.chunk.note_id, (score, block * SCAN_BLOCK + i));
+                    }
+                }
+                best
+            })
+            .reduce(HashMap::new, |mut best, other| {
+                for (note_id, candidate) in other {
+                    keep_better(&mut best, note_id, candidate);
+                }
+                best
+            });
+
+        let mut best: Vec<(f32, usize)> = best.into_values().collect();
+        best.sort_by(|a, b| {
+            b.0.partial_cmp(&a.0)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(a.1.cmp(&b.1))
+        });
+        best.truncate(limit);
+        best
+    }
+
+    /// The first of `scored`'s slots for each note, up to `limit` notes,
+    /// among the notes `keep` accepts
+    fn best_per_note(
+        &self,
+        scored: Vec<(f32, usize)>,
+        limit: usize,
+        keep: impl Fn(uuid::Uuid) -> bool,
+    ) -> Vec<(f32, usize)> {
+        let mut seen_notes = std::collections::HashSet::new();
+        scored
+            .into_iter()
+            .filter(|&(_, slot)| {
+                self.slots[slot]
+                    .as_ref()
+                    .is_some_and(|e| keep(e.chunk.note_id) && seen_notes.insert(e.chunk.note_id))
+            })
+            .take(limit)
+            .collect()
+    }
+
+    /// Search result for the chunk in `slot`, reading its content only now
+    fn result(&self, score: f32, slot: usize, snippets: &SnippetOptions) -> Option<SearchResult> {
+        let entry = self.slots[slot].as_ref()?;
+        let content = entry.content(self.spill.as_ref());
+        Some(SearchResult {
+            note_id: entry.chunk.note_id.to_string(),
+            title: String::new(), // Will be filled in by caller
+            snippet: content.chars().take(snippets.len).collect::<String>().replace('\n', " "),
+            score,
+            chunk_type: Some(format!("{:?}", entry.chunk.chunk_type)),
+            tags: Vec::new(), // Will be filled in by caller
+            updated_at: None, // Will be filled in by caller
+            chunk: snippets.full_chunk.then(|| content.to_string()),
+            line: None,
+            highlights: Vec::new(),
+        })
     }
 
     /// Search using semantic similarity
     pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>> {
-        if self.chunks.is_empty() {
+        self.search_with(query, limit, &SnippetOptions::default()).await
+    }
+
+    /// Search using semantic similarity, with snippets shaped by `snippets`
+    pub async fn search_with(
+        &self,
+        query: &str,
+        limit: usize,
+        snippets: &SnippetOptions,
+    ) -> Result<Vec<SearchResult>> {
+        self.search_as(query, None, limit, snippets).await
+    }
+
+    /// Search using semantic similarity, matching the query as `query_type`
+    /// instead of classifying it when given
+    #[tracing::instrument(skip(self))]
+    pub async fn search_as(
+        &self,
+        query: &str,
+        query_type: Option<QueryType>,
+        limit: usize,
+        snippets: &SnippetOptions,
+    ) -> Result<Vec<SearchResult>> {
+        self.search_within(query, query_type, limit, snippets, None).await
+    }
+
+    /// Like [`SemanticSearch::search_as`], ranking only the chunks of these
+    /// notes when given
+    pub async fn search_within(
+        &self,
+        query: &str,
+        query_type: Option<QueryType>,
+        limit: usize,
+        snippets: &SnippetOptions,
+        notes: Option<&std::collections::HashSet<uuid::Uuid>>,
+    ) -> Result<Vec<SearchResult>> {
+        if self.live == 0 {
             return Ok(Vec::new());
         }
 
-        let query_type = QueryType::classify(query);
+        let classification = self.classifier.classify(query);
+        let decided = query_type.unwrap_or(classification.query_type);
+        tracing::debug!(
+            query_type = ?decided,
+            requested = ?query_type,
+            score = classification.score,
+            signals = ?classification.signals,
+            "Classified query"
+        );
+        self.decisions.record(ClassificationDecision {
+            query: query.to_string(),
+            classification,
+            requested: query_type,
+            query_type: decided,
+            at: chrono::Utc::now(),
+        });
 
         // Embed query and score chunks based on query type:
         // - Prose/Hybrid: use prose_embedding (all chunks have this)
-        // - Code: use code_embedding (only code chunks have this, for specialized matching)
-        let mut scored: Vec<(f32, &Chunk)> = match query_type {
-            QueryType::Prose | QueryType::Hybrid => {
-                // Use prose model - finds all content including code via natural language
-                let query_embedding = self.embedder.embed_prose(query).await?;
-                self.chunks
-                    .iter()
-                    .filter_map(|chunk| {
-                        chunk.prose_embedding.as_ref().map(|emb| {
-                            (cosine_similarity(&query_embedding, emb), chunk)
-                        })
-                    })
-                    .collect()
-            }
-            QueryType::Code => {
+        // - Code: use code_embedding (only code chunks have this, for specialized
+        //   matching), unless code isn't embedded with a model of its own
+        let scored = match decided {
+            QueryType::Code if self.embedder.has_code_model() => {
                 // Use code model - specialized for code syntax queries
                 let query_embedding = self.embedder.embed_code(query).await?;
-                self.chunks
-                    .iter()
-                    .filter_map(|chunk| {
-                        chunk.code_embedding.as_ref().map(|emb| {
-                            (cosine_similarity(&query_embedding, emb), chunk)
-                        })
-                    })
-                    .collect()
+                self.nearest(&query_embedding, true, limit, |id| notes.is_none_or(|n| n.contains(&id)))
             }
-        };
-
-        // Sort by score descending
-        scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
-
-        // Take top results, deduplicating by note_id
-        let mut results = Vec::new();
-        let mut seen_notes = std::collections::HashSet::new();
-
-        for (score, chunk) in scored {
-            if seen_notes.contains(&chunk.note_id) {
-                continue;
-            }
-            seen_notes.insert(chunk.note_id);
-
-            // Create snippet from chunk content
-            let snippet = chunk
-                .content
-                .chars()
-                .take(200)
-                .collect::<String>()
-                .replace('\n', " ");
-
-            results.push(SearchResult {
-                note_id: chunk.note_id.to_string(),
-                title: String::new(), // Will be filled in by caller
-                snippet,
-                score,
-                chunk_type: Some(format!("{:?}", chunk.chunk_type)),
-                tags: Vec::new(), // Will be filled in by caller
-                updated_at: None, // Will be filled in by caller
-            });
-
-            if results.len() >= limit {
-                break;
+            _ => {
+                // Use prose model - finds all content including code via natural language
+                let query_embedding = self.embedder.embed_prose(query).await?;
+                self.nearest(&query_embedding, false, limit, |id| notes.is_none_or(|n| n.contains(&id)))
             }
-        }
+        };
 
-        Ok(results)
+        Ok(scored
+            .into_iter()
+            .filter_map(|(score, slot)| self.result(score, slot, snippets))
+            .collect())
     }
 
     /// Find similar notes to a given note
+    #[tracing::instrument(skip(self))]
     pub async fn find_similar(&self, note_id: uuid::Uuid, limit: usize) -> Result<Vec<SearchResult>> {
-        // Get chunks for this note
-        let note_chunks: Vec<&Chunk> = self
-            .chunks
-            .iter()
-            .filter(|c| c.note_id == note_id)
-            .collect();
-
-        if note_chunks.is_empty() {
-            return Ok(Vec::new());
-        }
-
         // Average the embeddings of this note's chunks
-        let embeddings: Vec<&Vec<f32>> = note_chunks
-            .iter()
-            .filter_map(|c| c.prose_embedding.as_ref())
+        let embeddings: Vec<Vec<f32>> = self
+            .entries()
+            .filter(|e| e.chunk.note_id == note_id)
+            .filter_map(|e| e.prose(self.spill.as_ref()))
             .collect();
 
         if embeddings.is_empty() {
@@ -147,85 +610,83 @@ impl SemanticSearch {
         }
 
         // Score all other notes' chunks
-        let mut scored: Vec<(f32, &Chunk)> = self
-            .chunks
-            .iter()
-            .filter(|c| c.note_id != note_id)
-            .filter_map(|chunk| {
-                chunk.prose_embedding.as_ref().map(|emb| {
-                    let score = cosine_similarity(&avg_embedding, emb);
-                    (score, chunk)
-                })
-            })
-            .collect();
-
-        scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
-
-        // Deduplicate by note_id
-        let mut results = Vec::new();
-        let mut seen_notes = std::collections::HashSet::new();
-
-        for (score, chunk) in scored {
-            if seen_notes.contains(&chunk.note_id) {
-                continue;
-            }
-            seen_notes.insert(chunk.note_id);
-
-            let snippet = chunk
-                .content
-                .chars()
-                .take(200)
-                .collect::<String>()
-                .replace('\n', " ");
-
-            results.push(SearchResult {
-                note_id: chunk.note_id.to_string(),
-                title: String::new(),
-                snippet,
-                score,
-                chunk_type: Some(format!("{:?}", chunk.chunk_type)),
-                tags: Vec::new(),
-                updated_at: None,
-            });
+        let snippets = SnippetOptions::default();
+        Ok(self
+            .nearest(&avg_embedding, false, limit, |id| id != note_id)
+            .into_iter()
+            .filter_map(|(score, slot)| self.result(score, slot, &snippets))
+            .collect())
+    }
 
-            if results.len() >= limit {
-                break;
-            }
+    /// Run throwaway inputs through the embedding models, whose first
+    /// inference is much slower than later ones
+    #[tracing::instrument(skip_all)]
+    pub async fn warm_up(&self) -> Result<()> {
+        self.embedder.embed_prose("warm up").await?;
+        if self.embedder.has_code_model() {
+            self.embedder.embed_code("fn warm_up() {}").await?;
         }
-
-        Ok(results)
+        Ok(())
     }
 
     /// Get chunk count
     pub fn chunk_count(&self) -> usize {
-        self.chunks.len()
+        self.live
     }
 
     /// Clear all chunks
     pub fn clear(&mut self) {
-        self.chunks.clear();
+        self.slots.clear();
+        self.spill = None;
+        self.ann = match self.index {
+            SemanticIndex::Hnsw => Some(AnnIndexes::new(self.quantization)),
+            SemanticIndex::Exact => None,
+        };
+        self.reindex();
     }
 }
 
-/// Compute cosine similarity between two vectors
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
-    if a.len() != b.len() {
-        return 0.0;
-    }
-
-    let mut dot = 0.0;
-    let mut norm_a = 0.0;
-    let mut norm_b = 0.0;
-
-    for i in 0..a.len() {
-        dot += a[i] * b[i];
-        norm_a += a[i] * a[i];
-        norm_b += b[i] * b[i];
+/// Replace a note's chunks with `chunks`, in memory and in the vector store
+pub async fn save_note_chunks(
+    semantic: &RwLock<SemanticSearch>,
+    note_id: uuid::Uuid,
+    chunks: Vec<Chunk>,
+) -> Result<()> {
+    let vectors = {
+        let mut semantic = semantic.write().await;
+        semantic.remove_chunks_for_note(note_id);
+        for chunk in &chunks {
+            semantic.add_chunk(chunk.clone());
+        }
+        semantic.vectors.clone()
+    };
+    match vectors {
+        Some(vectors) => vectors.upsert_note_chunks(note_id, &chunks).await,
+        None => Ok(()),
     }
+}
 
-    if norm_a == 0.0 || norm_b == 0.0 {
-        return 0.0;
+/// Remove a note's chunks, in memory and from the vector store
+pub async fn delete_note_chunks(semantic: &RwLock<SemanticSearch>, note_id: uuid::Uuid) -> Result<()> {
+    let vectors = {
+        let mut semantic = semantic.write().await;
+        semantic.remove_chunks_for_note(note_id);
+        semantic.vectors.clone()
+    };
+    match vectors {
+        Some(vectors) => vectors.delete_note(note_id).await,
+        None => Ok(()),
     }
+}
 
-    dot / (norm_a.sqrt() * norm_b.sqrt())
+/// Keep `candidate` as `note_id`'s best unless it has a better one: a higher
+/// score, or the same score in an earlier slot
+fn keep_better(best: &mut HashMap<uuid::Uuid, (f32, usize)>, note_id: uuid::Uuid, candidate: (f32, usize)) {
+    best.entry(note_id)
+        .and_modify(|current| {
+            if candidate.0 > current.0 || (candidate.0 == current.0 && candidate.1 < current.1) {
+                *current = candidate;
+            }
+        })
+        .or_insert(candidate);
 }
@@ -0,0 +1,172 @@
+//! Chunks spilled out of memory
+//!
+//! Past `embedding.memory_budget_mb`, semantic search moves chunk content
+//! and embeddings into a file under `.notidium/spill` and reads them back
+//! through a memory map. The file is a cache of the vector store: records
+//! are only ever appended, embeddings are written as little-endian `f32`
+//! and the content as UTF-8. It is removed when dropped.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use memmap2::Mmap;
+use uuid::Uuid;
+
+use crate::error::Result;
+use crate::types::Chunk;
+
+/// Byte range of a record's field in the spill file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    offset: usize,
+    len: usize,
+}
+
+/// Where a spilled chunk's content and embeddings are
+#[derive(Debug, Clone, Copy)]
+pub struct SpilledChunk {
+    pub prose: Option<Span>,
+    pub code: Option<Span>,
+    pub content: Span,
+}
+
+/// Append-only file of spilled chunks, read through a memory map
+pub struct SpillFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    len: usize,
+    /// Everything written up to the last [`SpillFile::sync`]; `None` while
+    /// the file is empty
+    map: Option<Mmap>,
+}
+
+impl SpillFile {
+    /// Start a new spill file in `dir`
+    pub fn create(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(format!("chunks-{}.bin", Uuid::new_v4()));
+        let file = File::options().read(true).write(true).create_new(true).open(&path)?;
+        Ok(Self {
+            path,
+            writer: BufWriter::new(file),
+            len: 0,
+            map: None,
+        })
+    }
+
+    /// Write a chunk's embeddings and content; they can be read once the
+    /// file is synced
+    pub fn append(&mut self, chunk: &Chunk) -> Result<SpilledChunk> {
+        let prose = chunk.prose_embedding.as_deref().map(|v| self.write_vector(v)).transpose()?;
+        let code = chunk.code_embedding.as_deref().map(|v| self.write_vector(v)).transpose()?;
+        let content = self.write(chunk.content.as_bytes())?;
+        Ok(SpilledChunk { prose, code, content })
+    }
+
+    /// Make everything appended so far readable
+    pub fn sync(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        if self.len > self.map.as_ref().map_or(0, |map| map.len()) {
+            // SAFETY: the file is private to this process and only appended
+            // to, so bytes under the map never change
+            self.map = Some(unsafe { Mmap::map(self.writer.get_ref())? });
+        }
+        Ok(())
+    }
+
+    /// Embedding stored at `span`
+    pub fn vector(&self, span: Span) -> Vec<f32> {
+        self.values(span).collect()
+    }
+
+    /// Cosine similarity between `query` and the embedding at `span`,
+    /// decoded as it is read
+    pub fn cosine_similarity(&self, query: &[f32], span: Span) -> f32 {
+        if query.len() != span.len / 4 {
+            return 0.0;
+        }
+        let (mut dot, mut norm_a, mut norm_b) = (0.0, 0.0, 0.0);
+        for (a, b) in query.iter().zip(self.values(span)) {
+            dot += a * b;
+            norm_a += a * a;
+            norm_b += b * b;
+        }
+        let denominator = norm_a.sqrt() * norm_b.sqrt();
+        if denominator == 0.0 {
+            0.0
+        } else {
+            dot / denominator
+        }
+    }
+
+    /// Content stored at `span`
+    pub fn text(&self, span: Span) -> &str {
+        std::str::from_utf8(self.bytes(span)).unwrap_or_default()
+    }
+
+    fn write_vector(&mut self, vector: &[f32]) -> Result<Span> {
+        let bytes: Vec<u8> = vector.iter().flat_map(|v| v.to_le_bytes()).collect();
+        self.write(&bytes)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<Span> {
+        self.writer.write_all(bytes)?;
+        let span = Span {
+            offset: self.len,
+            len: bytes.len(),
+        };
+        self.len += bytes.len();
+        Ok(span)
+    }
+
+    fn bytes(&self, span: Span) -> &[u8] {
+        self.map
+            .as_ref()
+            .and_then(|map| map.get(span.offset..span.offset + span.len))
+            .unwrap_or_default()
+    }
+
+    fn values(&self, span: Span) -> impl Iterator<Item = f32> + '_ {
+        self.bytes(span)
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        // Unmapped first; Windows won't remove a mapped file
+        self.map = None;
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            tracing::warn!("Failed to remove {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ChunkType;
+
+    #[test]
+    fn test_spilled_chunks_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spill = SpillFile::create(dir.path()).unwrap();
+
+        let mut chunk = Chunk::new(Uuid::new_v4(), "Spilled ünïcode".to_string(), ChunkType::Prose);
+        chunk.prose_embedding = Some(vec![0.5, -1.0, 2.0]);
+        let spilled = spill.append(&chunk).unwrap();
+        assert!(spilled.code.is_none());
+        spill.sync().unwrap();
+
+        assert_eq!(spill.text(spilled.content), "Spilled ünïcode");
+        assert_eq!(spill.vector(spilled.prose.unwrap()), vec![0.5, -1.0, 2.0]);
+        let similarity = spill.cosine_similarity(&[1.0, -2.0, 4.0], spilled.prose.unwrap());
+        assert!((similarity - 1.0).abs() < 1e-6);
+
+        let path = spill.path.clone();
+        drop(spill);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,288 @@
+//! Synthetic vaults for demos and benchmarks
+//!
+//! [`generate`] makes notes that look like a developer's vault: prose in
+//! paragraphs and sections, tags, wiki links to other generated notes and,
+//! in a share of the notes, code blocks in several languages. The same
+//! options always give the same notes, so benchmark runs compare like with
+//! like.
+
+use std::collections::HashSet;
+
+/// What [`generate`] makes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SeedOptions {
+    /// Number of notes
+    pub notes: usize,
+    /// Share of notes with code blocks, from 0 to 1
+    pub code_ratio: f32,
+    /// Seed of the generator; the same seed gives the same notes
+    pub seed: u64,
+}
+
+impl Default for SeedOptions {
+    fn default() -> Self {
+        Self {
+            notes: 100,
+            code_ratio: 0.3,
+            seed: 42,
+        }
+    }
+}
+
+/// A generated note
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeedNote {
+    pub title: String,
+    pub content: String,
+    pub tags: Vec<String>,
+    /// Whether the content has code blocks
+    pub has_code: bool,
+}
+
+const TOPICS: &[&str] = &[
+    "connection pooling", "retry budgets", "cache invalidation", "feature flags", "schema migrations",
+    "log sampling", "rate limiting", "graceful shutdown", "backpressure", "config reloading",
+    "request tracing", "token refresh", "batch imports", "search ranking", "index compaction",
+    "deploy rollbacks", "secret rotation", "error budgets", "queue consumers", "health checks",
+];
+
+const KINDS: &[&str] = &[
+    "Notes on", "Debugging", "Thoughts on", "How we do", "Checklist for", "Design of", "Lessons from", "Benchmarking",
+];
+
+const TAGS: &[&str] = &[
+    "rust", "python", "go", "typescript", "sql", "ops", "design", "debugging", "performance", "reading", "meeting",
+    "todo",
+];
+
+const SECTIONS: &[&str] = &["Context", "Approach", "Trade-offs", "Open questions", "Follow-ups", "References"];
+
+const WORDS: &[&str] = &[
+    "lorem", "ipsum", "dolor", "sit", "amet", "consectetur", "adipiscing", "elit", "sed", "do", "eiusmod",
+    "tempor", "incididunt", "ut", "labore", "et", "dolore", "magna", "aliqua", "enim", "minim", "veniam",
+    "quis", "nostrud", "exercitation", "ullamco", "laboris", "nisi", "aliquip", "commodo", "consequat",
+    "duis", "aute", "irure", "reprehenderit", "voluptate", "velit", "esse", "cillum", "fugiat", "nulla",
+    "pariatur", "excepteur", "sint", "occaecat", "cupidatat", "proident", "sunt", "culpa", "officia",
+];
+
+const IDENTS: &[&str] = &[
+    "pool", "retries", "cache", "flags", "schema", "sampler", "limiter", "queue", "tracer", "token", "batch",
+    "index", "deploy", "secret", "budget", "consumer",
+];
+
+/// Code block templates by language; `{a}` and `{b}` are replaced by
+/// identifiers
+const CODE: &[(&str, &str)] = &[
+    (
+        "rust",
+        "pub fn {a}_with_{b}(items: &[u64], limit: usize) -> Vec<u64> {\n    \
+         let mut {a}: Vec<u64> = items.iter().copied().filter(|v| v % 2 == 0).collect();\n    \
+         {a}.sort_unstable();\n    \
+         {a}.truncate(limit);\n    \
+         {a}\n}",
+    ),
+    (
+        "rust",
+        "impl {A} {\n    \
+         pub async fn refresh(&mut self) -> Result<(), Error> {\n        \
+         let {b} = self.client.fetch_{b}().await?;\n        \
+         self.{b} = Some({b});\n        \
+         Ok(())\n    \
+         }\n}",
+    ),
+    (
+        "python",
+        "def {a}_by_{b}(rows, limit=10):\n    \
+         \"\"\"Group rows by {b} and keep the largest groups.\"\"\"\n    \
+         groups = {}\n    \
+         for row in rows:\n        \
+         groups.setdefault(row[\"{b}\"], []).append(row)\n    \
+         return sorted(groups.items(), key=lambda kv: -len(kv[1]))[:limit]",
+    ),
+    (
+        "typescript",
+        "export async function load{A}(id: string): Promise<{A} | null> {\n  \
+         const res = await fetch(`/api/{a}/${id}?include={b}`);\n  \
+         if (!res.ok) return null;\n  \
+         return (await res.json()) as {A};\n}",
+    ),
+    (
+        "go",
+        "func (s *{A}Service) {B}(ctx context.Context, id string) error {\n\t\
+         {a}, err := s.store.Get(ctx, id)\n\t\
+         if err != nil {\n\t\treturn fmt.Errorf(\"get {a}: %w\", err)\n\t}\n\t\
+         return s.{b}.Apply(ctx, {a})\n}",
+    ),
+    (
+        "sql",
+        "SELECT {a}.id, count(*) AS {b}_count\nFROM {a}\nJOIN {b} ON {b}.{a}_id = {a}.id\n\
+         WHERE {b}.created_at > now() - interval '7 days'\nGROUP BY {a}.id\nORDER BY {b}_count DESC\nLIMIT 20;",
+    ),
+    (
+        "bash",
+        "#!/usr/bin/env bash\nset -euo pipefail\n\nfor {a} in $(ls ./{b}); do\n  \
+         echo \"checking ${a}\"\n  ./bin/check --{b} \"${a}\" || exit 1\ndone",
+    ),
+];
+
+/// Generate `options.notes` notes
+pub fn generate(options: &SeedOptions) -> Vec<SeedNote> {
+    let mut rng = Rng(options.seed);
+    let code_notes = (options.notes as f32 * options.code_ratio.clamp(0.0, 1.0)).round() as usize;
+    let mut titles: Vec<String> = Vec::with_capacity(options.notes);
+    let mut taken = HashSet::new();
+    let mut notes = Vec::with_capacity(options.notes);
+
+    for i in 0..options.notes {
+        let base = format!("{} {}", rng.pick(KINDS), rng.pick(TOPICS));
+        let mut title = base.clone();
+        let mut n = 2;
+        while !taken.insert(title.clone()) {
+            title = format!("{} {}", base, n);
+            n += 1;
+        }
+
+        let mut tags: Vec<String> = Vec::new();
+        for _ in 0..1 + rng.below(3) {
+            let tag = rng.pick(TAGS).to_string();
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+
+        // Spread code notes evenly rather than bunching them at the start
+        let has_code = (i + 1) * code_notes / options.notes.max(1) > i * code_notes / options.notes.max(1);
+        let content = body(&mut rng, &titles, has_code);
+        titles.push(title.clone());
+        notes.push(SeedNote {
+            title,
+            content,
+            tags,
+            has_code,
+        });
+    }
+    notes
+}
+
+fn body(rng: &mut Rng, earlier: &[String], has_code: bool) -> String {
+    let mut content = paragraph(rng, earlier);
+    let sections = 1 + rng.below(3);
+    let code_section = has_code.then(|| rng.below(sections));
+    for section in 0..sections {
+        content.push_str(&format!("\n\n## {}\n\n", rng.pick(SECTIONS)));
+        content.push_str(&paragraph(rng, earlier));
+        if code_section == Some(section) {
+            for _ in 0..1 + rng.below(2) {
+                content.push_str("\n\n");
+                content.push_str(&code_block(rng));
+            }
+        }
+        if rng.below(3) == 0 {
+            content.push_str("\n\n");
+            for _ in 0..2 + rng.below(3) {
+                content.push_str(&format!("- {}\n", sentence(rng, 4, 8)));
+            }
+            content.pop();
+        }
+    }
+    content.push('\n');
+    content
+}
+
+/// A few sentences, with a wiki link to an earlier note now and then
+fn paragraph(rng: &mut Rng, earlier: &[String]) -> String {
+    let mut sentences: Vec<String> = (0..2 + rng.below(4)).map(|_| sentence(rng, 6, 16)).collect();
+    if !earlier.is_empty() && rng.below(2) == 0 {
+        let target = &earlier[rng.below(earlier.len())];
+        sentences.push(format!("See [[{}]].", target));
+    }
+    sentences.join(" ")
+}
+
+fn sentence(rng: &mut Rng, min_words: usize, max_words: usize) -> String {
+    let count = min_words + rng.below(max_words - min_words + 1);
+    let mut sentence = (0..count).map(|_| *rng.pick(WORDS)).collect::<Vec<_>>().join(" ");
+    sentence[..1].make_ascii_uppercase();
+    sentence.push('.');
+    sentence
+}
+
+fn code_block(rng: &mut Rng) -> String {
+    let (language, template) = rng.pick(CODE);
+    let a = *rng.pick(IDENTS);
+    let b = *rng.pick(IDENTS);
+    let code = template
+        .replace("{A}", &capitalize(a))
+        .replace("{B}", &capitalize(b))
+        .replace("{a}", a)
+        .replace("{b}", b);
+    format!("```{}\n{}\n```", language, code)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|c| c.to_ascii_uppercase().to_string() + chars.as_str())
+        .unwrap_or_default()
+}
+
+/// splitmix64, so the same seed gives the same vault everywhere
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n`
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_notes() {
+        let options = SeedOptions::default();
+        assert_eq!(generate(&options), generate(&options));
+        let other = SeedOptions { seed: 7, ..options };
+        assert_ne!(generate(&options), generate(&other));
+    }
+
+    #[test]
+    fn test_code_ratio_and_links() {
+        let notes = generate(&SeedOptions {
+            notes: 200,
+            code_ratio: 0.3,
+            seed: 1,
+        });
+        assert_eq!(notes.len(), 200);
+        assert_eq!(notes.iter().filter(|n| n.has_code).count(), 60);
+        for note in &notes {
+            assert_eq!(note.has_code, note.content.contains("```"), "{}", note.content);
+            assert!(!note.tags.is_empty());
+        }
+
+        let titles: HashSet<&str> = notes.iter().map(|n| n.title.as_str()).collect();
+        assert_eq!(titles.len(), notes.len());
+        let links: Vec<&str> = notes
+            .iter()
+            .flat_map(|n| n.content.split("[[").skip(1))
+            .map(|rest| rest.split("]]").next().unwrap())
+            .collect();
+        assert!(!links.is_empty());
+        assert!(links.iter().all(|l| titles.contains(l)));
+    }
+}
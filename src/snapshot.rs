@@ -0,0 +1,351 @@
+//! Dated vault snapshots with daily/weekly retention
+//!
+//! Each snapshot is a directory under `.notidium/snapshots/<YYYY-MM-DD>/`
+//! mirroring the vault's notes, attachments and templates plus the manifest.
+//! Files unchanged since the previous snapshot are hard-linked to it rather
+//! than copied, so a snapshot only costs the space of what changed.
+
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{Datelike, Local, NaiveDate};
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// How often the background task checks whether today's snapshot exists
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Outcome of taking a snapshot
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotStats {
+    /// Files written as fresh copies
+    pub copied: usize,
+    /// Files hard-linked to the previous snapshot
+    pub linked: usize,
+}
+
+/// Creates, prunes and restores vault snapshots
+pub struct SnapshotManager {
+    config: Config,
+}
+
+impl SnapshotManager {
+    pub fn new(config: Config) -> Self {
+        Self { config }
+    }
+
+    fn snapshot_dir(&self, date: NaiveDate) -> PathBuf {
+        self.config
+            .snapshots_path()
+            .join(date.format(DATE_FORMAT).to_string())
+    }
+
+    /// Vault-relative paths captured in each snapshot
+    fn sources(&self) -> Vec<PathBuf> {
+        vec![
+            PathBuf::from(&self.config.notes_dir),
+            PathBuf::from(&self.config.attachments_dir),
+            PathBuf::from(&self.config.templates_dir),
+            PathBuf::from(".notidium").join("manifest.json"),
+        ]
+    }
+
+    /// Dates of all existing snapshots, oldest first
+    pub fn list(&self) -> Result<Vec<NaiveDate>> {
+        let root = self.config.snapshots_path();
+        if !root.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut dates = Vec::new();
+        for entry in std::fs::read_dir(&root)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            if let Some(date) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| NaiveDate::parse_from_str(name, DATE_FORMAT).ok())
+            {
+                dates.push(date);
+            }
+        }
+        dates.sort();
+        Ok(dates)
+    }
+
+    /// Take a snapshot for `date`, replacing any existing snapshot for that day
+    pub fn create(&self, date: NaiveDate) -> Result<SnapshotStats> {
+        let target = self.snapshot_dir(date);
+        let partial = target.with_extension("partial");
+        if partial.exists() {
+            std::fs::remove_dir_all(&partial)?;
+        }
+        std::fs::create_dir_all(&partial)?;
+
+        // Link against the newest snapshot up to and including this date
+        let previous = self
+            .list()?
+            .into_iter()
+            .rev()
+            .find(|d| *d <= date)
+            .map(|d| self.snapshot_dir(d));
+
+        let mut stats = SnapshotStats::default();
+        for relative in self.sources() {
+            let source = self.config.vault_path.join(&relative);
+            if source.exists() {
+                let dest = partial.join(&relative);
+                if let Some(parent) = dest.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                let previous = previous.as_ref().map(|p| p.join(&relative));
+                copy_tree(&source, &dest, previous.as_deref(), &mut stats)?;
+            }
+        }
+
+        if target.exists() {
+            std::fs::remove_dir_all(&target)?;
+        }
+        std::fs::rename(&partial, &target)?;
+
+        Ok(stats)
+    }
+
+    /// Delete snapshots outside the configured retention, returning their dates
+    pub fn prune(&self) -> Result<Vec<NaiveDate>> {
+        let dates = self.list()?;
+        let expired = expired_snapshots(
+            &dates,
+            self.config.snapshot.keep_daily,
+            self.config.snapshot.keep_weekly,
+        );
+        for date in &expired {
+            std::fs::remove_dir_all(self.snapshot_dir(*date))?;
+        }
+        Ok(expired)
+    }
+
+    /// Take today's snapshot and prune if it doesn't exist yet
+    pub fn ensure_today(&self) -> Result<Option<SnapshotStats>> {
+        let today = Local::now().date_naive();
+        if self.snapshot_dir(today).exists() {
+            return Ok(None);
+        }
+        let stats = self.create(today)?;
+        self.prune()?;
+        Ok(Some(stats))
+    }
+
+    /// Copy a single file out of a snapshot
+    ///
+    /// `path` is relative to the vault root (e.g. `notes/todo.md`). The file is
+    /// restored to its original location unless `dest` is given; returns the
+    /// path that was written.
+    pub fn restore(&self, date: NaiveDate, path: &Path, dest: Option<&Path>) -> Result<PathBuf> {
+        if !path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(Error::Snapshot(format!(
+                "Path must be relative to the vault: {}",
+                path.display()
+            )));
+        }
+
+        let snapshot = self.snapshot_dir(date);
+        if !snapshot.exists() {
+            return Err(Error::Snapshot(format!(
+                "No snapshot for {}",
+                date.format(DATE_FORMAT)
+            )));
+        }
+
+        let source = snapshot.join(path);
+        if !source.is_file() {
+            return Err(Error::Snapshot(format!(
+                "{} not found in snapshot {}",
+                path.display(),
+                date.format(DATE_FORMAT)
+            )));
+        }
+
+        let dest = dest
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| self.config.vault_path.join(path));
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // Write through a fresh file so the snapshot's inode is never shared
+        if dest.exists() {
+            std::fs::remove_file(&dest)?;
+        }
+        std::fs::copy(&source, &dest)?;
+
+        Ok(dest)
+    }
+}
+
+/// Parse a `YYYY-MM-DD` snapshot date
+pub fn parse_date(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value, DATE_FORMAT).map_err(|_| {
+        Error::Snapshot(format!("Invalid snapshot date (expected YYYY-MM-DD): {}", value))
+    })
+}
+
+/// Run the daily snapshot check in the background while the server is up
+pub fn spawn_scheduler(manager: SnapshotManager) -> tokio::task::JoinHandle<()> {
+    let manager = std::sync::Arc::new(manager);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let manager = manager.clone();
+            match tokio::task::spawn_blocking(move || manager.ensure_today()).await {
+                Ok(Ok(Some(stats))) => tracing::info!(
+                    "Snapshot taken ({} copied, {} linked)",
+                    stats.copied,
+                    stats.linked
+                ),
+                Ok(Ok(None)) => {}
+                Ok(Err(e)) => tracing::warn!("Snapshot failed: {}", e),
+                Err(e) => tracing::warn!("Snapshot task panicked: {}", e),
+            }
+        }
+    })
+}
+
+/// Recursively mirror `source` into `dest`, hard-linking files that are
+/// identical in `previous`
+fn copy_tree(
+    source: &Path,
+    dest: &Path,
+    previous: Option<&Path>,
+    stats: &mut SnapshotStats,
+) -> Result<()> {
+    if source.is_dir() {
+        std::fs::create_dir_all(dest)?;
+        for entry in std::fs::read_dir(source)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            copy_tree(
+                &entry.path(),
+                &dest.join(&name),
+                previous.map(|p| p.join(&name)).as_deref(),
+                stats,
+            )?;
+        }
+        return Ok(());
+    }
+
+    if let Some(previous) = previous.filter(|p| same_contents(source, p)) {
+        if std::fs::hard_link(previous, dest).is_ok() {
+            stats.linked += 1;
+            return Ok(());
+        }
+    }
+
+    std::fs::copy(source, dest)?;
+    stats.copied += 1;
+    Ok(())
+}
+
+fn same_contents(a: &Path, b: &Path) -> bool {
+    let (Ok(meta_a), Ok(meta_b)) = (std::fs::metadata(a), std::fs::metadata(b)) else {
+        return false;
+    };
+    if !meta_b.is_file() || meta_a.len() != meta_b.len() {
+        return false;
+    }
+    match (std::fs::read(a), std::fs::read(b)) {
+        (Ok(x), Ok(y)) => x == y,
+        _ => false,
+    }
+}
+
+/// Snapshots that fall outside the retention policy
+///
+/// The newest `keep_daily` snapshots are kept. Of the remainder, the newest
+/// snapshot in each of the `keep_weekly` most recent ISO weeks is kept.
+fn expired_snapshots(
+    dates: &[NaiveDate],
+    keep_daily: usize,
+    keep_weekly: usize,
+) -> Vec<NaiveDate> {
+    let mut newest_first = dates.to_vec();
+    newest_first.sort_by(|a, b| b.cmp(a));
+
+    let mut weeks = HashSet::new();
+    let mut expired = Vec::new();
+    for (i, date) in newest_first.into_iter().enumerate() {
+        if i < keep_daily {
+            continue;
+        }
+        let week = date.iso_week();
+        if weeks.contains(&(week.year(), week.week())) {
+            expired.push(date);
+        } else if weeks.len() < keep_weekly {
+            weeks.insert((week.year(), week.week()));
+        } else {
+            expired.push(date);
+        }
+    }
+    expired.sort();
+    expired
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        parse_date(s).unwrap()
+    }
+
+    fn days(start: &str, count: i64) -> Vec<NaiveDate> {
+        let start = date(start);
+        (0..count).map(|i| start + chrono::Duration::days(i)).collect()
+    }
+
+    #[test]
+    fn test_keeps_all_within_daily_window() {
+        let dates = days("2024-03-01", 5);
+        assert!(expired_snapshots(&dates, 7, 4).is_empty());
+    }
+
+    #[test]
+    fn test_keeps_one_per_week_beyond_dailies() {
+        // 2024-01-01 is a Monday; 28 consecutive days span four ISO weeks
+        let dates = days("2024-01-01", 28);
+        let expired = expired_snapshots(&dates, 7, 2);
+
+        let kept: Vec<NaiveDate> = dates
+            .iter()
+            .copied()
+            .filter(|d| !expired.contains(d))
+            .collect();
+        // Last 7 days (the whole fourth week) plus the Sundays of weeks 3 and 2
+        assert_eq!(kept.len(), 9);
+        assert!(kept.contains(&date("2024-01-21")));
+        assert!(kept.contains(&date("2024-01-14")));
+        assert!(!kept.contains(&date("2024-01-07")));
+    }
+
+    #[test]
+    fn test_zero_retention_expires_everything() {
+        let dates = days("2024-01-01", 3);
+        assert_eq!(expired_snapshots(&dates, 0, 0), dates);
+    }
+
+    #[test]
+    fn test_parse_date_rejects_garbage() {
+        assert!(parse_date("2024-13-01").is_err());
+        assert!(parse_date("yesterday").is_err());
+    }
+}
@@ -0,0 +1,187 @@
+//! Vault attachments
+//!
+//! Images and other files embedded in notes live flat in the vault's
+//! `attachments/` directory and are linked as `/api/attachments/<name>`. An
+//! attachment counts as used while any note or template mentions its file
+//! name, so one referenced only from a trashed note survives until that note
+//! is purged. Which attachments each live note embeds through markdown
+//! images and links is also tracked in the metadata database.
+
+use std::path::{Component, Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use pulldown_cmark::{Event, Parser, Tag};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::{Error, Result};
+use crate::lint::attachment_path;
+
+/// A file in the attachments directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct AttachmentInfo {
+    pub filename: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Last modification time
+    pub modified: DateTime<Utc>,
+}
+
+/// The attachments of a vault
+pub struct AttachmentStore {
+    dir: PathBuf,
+}
+
+impl AttachmentStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// All attachments, sorted by file name
+    pub async fn list(&self) -> Result<Vec<AttachmentInfo>> {
+        let mut attachments = Vec::new();
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(attachments),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let Ok(filename) = entry.file_name().into_string() else {
+                continue;
+            };
+            let metadata = entry.metadata().await?;
+            if filename.starts_with('.') || !metadata.is_file() {
+                continue;
+            }
+            attachments.push(AttachmentInfo {
+                filename,
+                size: metadata.len(),
+                modified: metadata.modified().map(DateTime::<Utc>::from).unwrap_or_else(|_| Utc::now()),
+            });
+        }
+        attachments.sort_by(|a, b| a.filename.cmp(&b.filename));
+        Ok(attachments)
+    }
+
+    /// Delete the attachment called `filename`
+    pub async fn delete(&self, filename: &str) -> Result<()> {
+        let path = self
+            .path(filename)
+            .ok_or_else(|| Error::AttachmentNotFound(filename.to_string()))?;
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(Error::AttachmentNotFound(filename.to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Attachments whose file name appears in none of `texts`
+    pub async fn unreferenced<'a>(
+        &self,
+        texts: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Vec<AttachmentInfo>> {
+        let mut attachments = self.list().await?;
+        for text in texts {
+            attachments.retain(|a| !is_referenced(text, &a.filename));
+            if attachments.is_empty() {
+                break;
+            }
+        }
+        Ok(attachments)
+    }
+
+    /// File of the attachment called `filename`; `None` for names that
+    /// aren't a plain file name
+    fn path(&self, filename: &str) -> Option<PathBuf> {
+        let is_plain = !filename.is_empty()
+            && !filename.starts_with('.')
+            && Path::new(filename).file_name().is_some_and(|f| f == filename);
+        is_plain.then(|| self.dir.join(filename))
+    }
+}
+
+/// File names of the attachments a note embeds or links to, sorted
+///
+/// Both `/api/attachments/<name>` URLs and paths relative to the note's
+/// directory (`note_dir`) that land in `attachments_dir` count.
+pub fn embedded_attachments(content: &str, note_dir: &Path, attachments_dir: &Path) -> Vec<String> {
+    let attachments_dir = normalize(attachments_dir);
+    let mut filenames: Vec<String> = Parser::new(content)
+        .filter_map(|event| match event {
+            Event::Start(Tag::Image { dest_url, .. }) | Event::Start(Tag::Link { dest_url, .. }) => {
+                let path = normalize(&attachment_path(&dest_url, note_dir, &attachments_dir)?);
+                if path.parent() != Some(attachments_dir.as_path()) {
+                    return None;
+                }
+                path.file_name()?.to_str().map(str::to_string)
+            }
+            _ => None,
+        })
+        .collect();
+    filenames.sort();
+    filenames.dedup();
+    filenames
+}
+
+/// `path` with `.` and `..` components resolved lexically
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Whether a note's text mentions an attachment, as written or URL-encoded
+pub(super) fn is_referenced(text: &str, filename: &str) -> bool {
+    text.contains(filename) || text.contains(&filename.replace(' ', "%20"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_referenced() {
+        assert!(is_referenced("![x](/api/attachments/a_1.png)", "a_1.png"));
+        assert!(is_referenced("![x](../attachments/my%20shot.png)", "my shot.png"));
+        assert!(!is_referenced("![x](/api/attachments/a_10.png)", "a_1.png"));
+    }
+
+    #[test]
+    fn test_embedded_attachments() {
+        let content = "![a](/api/attachments/b.png) [doc](../attachments/my%20doc.pdf)\n\
+                       ![again](/api/attachments/b.png) ![local](img/c.png) [web](https://e.com/d.png)\n\
+                       `![code](/api/attachments/e.png)`";
+        let found = embedded_attachments(
+            content,
+            Path::new("/vault/notes/projects/../work"),
+            Path::new("/vault/attachments"),
+        );
+        assert_eq!(found, ["b.png"]);
+
+        let found = embedded_attachments(
+            content,
+            Path::new("/vault/notes"),
+            Path::new("/vault/notes/../attachments"),
+        );
+        assert_eq!(found, ["b.png", "my doc.pdf"]);
+    }
+
+    #[test]
+    fn test_path_rejects_traversal() {
+        let store = AttachmentStore::new(PathBuf::from("/vault/attachments"));
+        assert_eq!(store.path("a.png"), Some(PathBuf::from("/vault/attachments/a.png")));
+        assert_eq!(store.path("../notes/x.md"), None);
+        assert_eq!(store.path(".hidden"), None);
+        assert_eq!(store.path(""), None);
+    }
+}
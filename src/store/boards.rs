@@ -0,0 +1,347 @@
+//! Kanban boards
+//!
+//! A board lays out the notes carrying a tag as cards in columns, grouped by a
+//! frontmatter property (`status` unless another one is asked for), or the
+//! tasks in those notes (`- [ ]` and `- [x]` items), grouped into `todo` and
+//! `done`. Boards have no storage of their own: moving a card rewrites the
+//! property in the note's frontmatter or ticks the task's checkbox.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::{Error, Result};
+use crate::types::Note;
+
+/// Frontmatter property notes are grouped by unless another one is given
+pub const DEFAULT_BOARD_PROPERTY: &str = "status";
+/// Column of open tasks
+pub const TODO_COLUMN: &str = "todo";
+/// Column of completed tasks
+pub const DONE_COLUMN: &str = "done";
+
+/// What the cards of a board are
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BoardItems {
+    /// One card per note, in the column named by its property
+    #[default]
+    Notes,
+    /// One card per task, in `todo` or `done`
+    Tasks,
+}
+
+/// Cards of a tag, in columns
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Board {
+    pub tag: String,
+    pub items: BoardItems,
+    /// Property the notes are grouped by; absent for task boards
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub property: Option<String>,
+    pub columns: Vec<BoardColumn>,
+    /// Notes without the property
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unsorted: Vec<BoardCard>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct BoardColumn {
+    pub name: String,
+    pub cards: Vec<BoardCard>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct BoardCard {
+    /// ID of the note the card stands for, or holding its task
+    pub note_id: String,
+    pub title: String,
+    /// Text of the task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<String>,
+    /// Line of the task in the note file, from 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+}
+
+/// A checkbox item in a note
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Task {
+    /// Line in the note file, from 1
+    pub line: usize,
+    pub text: String,
+    pub done: bool,
+}
+
+impl Board {
+    /// Board of the notes tagged `tag`, grouped by `property`
+    ///
+    /// `columns` come first, in the order given; any other values of the
+    /// property get columns of their own after them, sorted by name.
+    pub fn of_notes(tag: &str, notes: &[Note], property: &str, columns: &[String]) -> Self {
+        let mut board = Self::new(tag, BoardItems::Notes, Some(property.to_string()));
+        for name in columns {
+            board.column(name);
+        }
+        let fixed = board.columns.len();
+
+        for note in tagged(notes, tag) {
+            let card = BoardCard {
+                note_id: note.id.to_string(),
+                title: note.title.clone(),
+                task: None,
+                line: None,
+            };
+            match property_value(note, property) {
+                Some(value) => board.column(&value).cards.push(card),
+                None => board.unsorted.push(card),
+            }
+        }
+
+        board.columns[fixed..].sort_by(|a, b| a.name.cmp(&b.name));
+        board
+    }
+
+    /// Board of the tasks in the notes tagged `tag`
+    pub fn of_tasks(tag: &str, notes: &[Note]) -> Self {
+        let mut board = Self::new(tag, BoardItems::Tasks, None);
+        board.column(TODO_COLUMN);
+        board.column(DONE_COLUMN);
+
+        for note in tagged(notes, tag) {
+            for task in tasks(&note.content) {
+                let column = if task.done { DONE_COLUMN } else { TODO_COLUMN };
+                board.column(column).cards.push(BoardCard {
+                    note_id: note.id.to_string(),
+                    title: note.title.clone(),
+                    task: Some(task.text),
+                    line: Some(task.line),
+                });
+            }
+        }
+        board
+    }
+
+    fn new(tag: &str, items: BoardItems, property: Option<String>) -> Self {
+        Self {
+            tag: tag.to_string(),
+            items,
+            property,
+            columns: Vec::new(),
+            unsorted: Vec::new(),
+        }
+    }
+
+    /// The column called `name` (case-insensitively), added if it's missing
+    fn column(&mut self, name: &str) -> &mut BoardColumn {
+        let index = match self
+            .columns
+            .iter()
+            .position(|c| c.name.eq_ignore_ascii_case(name))
+        {
+            Some(index) => index,
+            None => {
+                self.columns.push(BoardColumn {
+                    name: name.to_string(),
+                    cards: Vec::new(),
+                });
+                self.columns.len() - 1
+            }
+        };
+        &mut self.columns[index]
+    }
+}
+
+/// Live notes carrying `tag`, sorted by title
+fn tagged<'a>(notes: &'a [Note], tag: &str) -> Vec<&'a Note> {
+    let mut tagged: Vec<&Note> = notes
+        .iter()
+        .filter(|n| !n.is_deleted && !n.is_archived)
+        .filter(|n| n.tags().iter().any(|t| t.eq_ignore_ascii_case(tag)))
+        .collect();
+    tagged.sort_by_key(|n| n.title.to_lowercase());
+    tagged
+}
+
+/// The value of a scalar frontmatter `property` of `note`, as text
+pub fn property_value(note: &Note, property: &str) -> Option<String> {
+    let value = note.frontmatter.as_ref()?.custom.get(property)?;
+    let text = match value {
+        serde_yaml::Value::String(s) => s.trim().to_string(),
+        serde_yaml::Value::Number(n) => n.to_string(),
+        serde_yaml::Value::Bool(b) => b.to_string(),
+        _ => return None,
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+/// The checkbox items of `content`, outside fenced code
+pub fn tasks(content: &str) -> Vec<Task> {
+    let mut tasks = Vec::new();
+    let mut in_code = false;
+    for (index, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        if let Some((done, text)) = checkbox(line) {
+            tasks.push(Task {
+                line: index + 1,
+                text: text.trim().to_string(),
+                done,
+            });
+        }
+    }
+    tasks
+}
+
+/// Whether `line` is a ticked checkbox item, and its text
+fn checkbox(line: &str) -> Option<(bool, &str)> {
+    let item = line.trim_start();
+    let rest = item
+        .strip_prefix("- ")
+        .or_else(|| item.strip_prefix("* "))
+        .or_else(|| item.strip_prefix("+ "))?;
+    let done = match rest.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    let text = &rest[3..];
+    (text.is_empty() || text.starts_with(' ')).then_some((done, text))
+}
+
+/// `content` with its task on `line` ticked or unticked
+pub fn set_task(content: &str, line: usize, done: bool) -> Result<String> {
+    let is_task = tasks(content).iter().any(|t| t.line == line);
+    if !is_task {
+        return Err(Error::InvalidBoardMove(format!("line {} is not a task", line)));
+    }
+
+    let mut lines: Vec<String> = content.split('\n').map(str::to_string).collect();
+    let text = &mut lines[line - 1];
+    let Some(open) = text.find('[') else {
+        return Err(Error::InvalidBoardMove(format!("line {} is not a task", line)));
+    };
+    text.replace_range(open + 1..open + 2, if done { "x" } else { " " });
+    Ok(lines.join("\n"))
+}
+
+/// `content` with the frontmatter `property` set to `value`
+///
+/// Only the property's line is rewritten, so the rest of the frontmatter keeps
+/// its formatting; notes without frontmatter get some.
+pub fn set_property(content: &str, property: &str, value: &str) -> Result<String> {
+    let is_valid = !property.is_empty()
+        && !property.chars().any(|c| c.is_whitespace() || c.is_control() || ":#\"'".contains(c))
+        && !["tags", "aliases"].contains(&property);
+    if !is_valid {
+        return Err(Error::InvalidBoardMove(format!("can't set property {:?}", property)));
+    }
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(Error::InvalidBoardMove("empty column name".to_string()));
+    }
+    let entry = format!(
+        "{}: {}",
+        property,
+        serde_yaml::to_string(&serde_yaml::Value::String(value.to_string()))?.trim()
+    );
+
+    // The same frontmatter bounds the note parser uses
+    let yaml_end = content
+        .starts_with("---")
+        .then(|| content[3..].find("\n---").map(|end| end + 3))
+        .flatten();
+    let Some(yaml_end) = yaml_end else {
+        return Ok(format!("---\n{}\n---\n\n{}", entry, content));
+    };
+
+    let mut lines: Vec<&str> = content[..yaml_end].split('\n').collect();
+    let key = format!("{}:", property);
+    match lines.iter().position(|l| l.starts_with(&key)) {
+        Some(start) => {
+            // Drop the old value, including any indented continuation lines
+            let end = lines[start + 1..]
+                .iter()
+                .position(|l| !l.starts_with([' ', '\t', '-']) || l.trim().is_empty())
+                .map_or(lines.len(), |n| start + 1 + n);
+            lines.splice(start..end, [entry.as_str()]);
+        }
+        None => lines.push(&entry),
+    }
+    Ok(format!("{}{}", lines.join("\n"), &content[yaml_end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Frontmatter;
+
+    fn note(title: &str, content: &str) -> Note {
+        let mut note = Note::new(title.to_string(), content.to_string(), format!("{}.md", title).into());
+        let yaml = content
+            .strip_prefix("---")
+            .and_then(|rest| rest.split_once("\n---"))
+            .map(|(yaml, _)| yaml);
+        note.frontmatter = yaml.map(|yaml| serde_yaml::from_str::<Frontmatter>(yaml).unwrap());
+        note
+    }
+
+    #[test]
+    fn test_note_board() {
+        let notes = vec![
+            note("Beta", "---\ntags: [work]\nstatus: doing\n---\n\nB"),
+            note("Alpha", "---\ntags: [Work]\nstatus: Todo\n---\n\nA"),
+            note("Gamma", "---\ntags: [work]\nstatus: blocked\n---\n\nG"),
+            note("Delta", "---\ntags: [work]\n---\n\nD"),
+            note("Other", "---\ntags: [home]\nstatus: todo\n---\n\nO"),
+        ];
+        let columns = vec!["todo".to_string(), "doing".to_string(), "done".to_string()];
+        let board = Board::of_notes("work", &notes, DEFAULT_BOARD_PROPERTY, &columns);
+
+        let names: Vec<_> = board.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["todo", "doing", "done", "blocked"]);
+        assert_eq!(board.columns[0].cards[0].title, "Alpha");
+        assert_eq!(board.columns[1].cards[0].title, "Beta");
+        assert!(board.columns[2].cards.is_empty());
+        assert_eq!(board.unsorted.len(), 1);
+        assert_eq!(board.unsorted[0].title, "Delta");
+    }
+
+    #[test]
+    fn test_tasks() {
+        let content = "# Plan\n\n- [ ] Write\n  * [x] Draft\n- [] not a task\n```\n- [ ] code\n```\n+ [X] Ship";
+        let tasks = tasks(content);
+        let found: Vec<_> = tasks.iter().map(|t| (t.line, t.text.as_str(), t.done)).collect();
+        assert_eq!(found, [(3, "Write", false), (4, "Draft", true), (9, "Ship", true)]);
+
+        let ticked = set_task(content, 3, true).unwrap();
+        assert!(ticked.contains("- [x] Write"));
+        assert_eq!(set_task(&ticked, 4, false).unwrap().lines().nth(3), Some("  * [ ] Draft"));
+        assert!(set_task(content, 5, true).is_err());
+        assert!(set_task(content, 7, true).is_err());
+    }
+
+    #[test]
+    fn test_set_property() {
+        let content = "---\ntags: [work]\nstatus:\n  - old\npriority: 2\n---\n\nBody\n";
+        assert_eq!(
+            set_property(content, "status", "in review").unwrap(),
+            "---\ntags: [work]\nstatus: in review\npriority: 2\n---\n\nBody\n"
+        );
+        assert_eq!(
+            set_property("---\ntags: [work]\n---\n\nBody", "status", "true").unwrap(),
+            "---\ntags: [work]\nstatus: 'true'\n---\n\nBody"
+        );
+        assert_eq!(
+            set_property("Body", "status", "done").unwrap(),
+            "---\nstatus: done\n---\n\nBody"
+        );
+        assert!(set_property(content, "tags", "done").is_err());
+        assert!(set_property(content, "status", " ").is_err());
+    }
+}
@@ -0,0 +1,316 @@
+//! Canvases
+//!
+//! A canvas is a `.canvas` file in the notes directory holding a board of
+//! nodes and the edges between them, in the JSON Canvas format Obsidian uses.
+//! Text nodes carry their own markdown, file nodes point at notes (paths
+//! relative to the notes directory), link nodes at URLs and group nodes frame
+//! others. Canvases are addressed by their path, e.g. `boards/plan.canvas`, so
+//! folder scopes apply to them as they do to notes.
+
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::{Error, Result};
+
+const EXTENSION: &str = "canvas";
+
+/// A board of nodes and edges
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Canvas {
+    #[serde(default)]
+    pub nodes: Vec<CanvasNode>,
+    #[serde(default)]
+    pub edges: Vec<CanvasEdge>,
+}
+
+/// What a canvas node shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CanvasNodeType {
+    /// Markdown in `text`
+    Text,
+    /// The note (or other vault file) at `file`
+    File,
+    /// The web page at `url`
+    Link,
+    /// A frame around other nodes, titled `label`
+    Group,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CanvasNode {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: CanvasNodeType,
+    pub x: i64,
+    pub y: i64,
+    pub width: i64,
+    pub height: i64,
+    /// Preset number (`"1"`-`"6"`) or hex color
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Path of the file, relative to the notes directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Heading or block within the file, e.g. `#Heading`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subpath: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CanvasEdge {
+    pub id: String,
+    pub from_node: String,
+    /// Side of the node the edge leaves from: top, right, bottom or left
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_side: Option<String>,
+    pub to_node: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_side: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+impl Canvas {
+    /// Check that node IDs are unique, every node has what its type needs
+    /// and every edge joins two nodes of the canvas
+    pub fn validate(&self) -> Result<()> {
+        let mut ids = HashSet::new();
+        for node in &self.nodes {
+            if !ids.insert(node.id.as_str()) {
+                return Err(Error::InvalidCanvas(format!("duplicate node ID {:?}", node.id)));
+            }
+            let missing = match node.kind {
+                CanvasNodeType::Text => node.text.is_none().then_some("text"),
+                CanvasNodeType::File => node.file.is_none().then_some("file"),
+                CanvasNodeType::Link => node.url.is_none().then_some("url"),
+                CanvasNodeType::Group => None,
+            };
+            if let Some(field) = missing {
+                return Err(Error::InvalidCanvas(format!("node {:?} has no {}", node.id, field)));
+            }
+        }
+        for edge in &self.edges {
+            for end in [&edge.from_node, &edge.to_node] {
+                if !ids.contains(end.as_str()) {
+                    return Err(Error::InvalidCanvas(format!(
+                        "edge {:?} refers to unknown node {:?}",
+                        edge.id, end
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The words on the canvas: text nodes, group and edge labels
+    pub fn text(&self) -> String {
+        let nodes = self
+            .nodes
+            .iter()
+            .flat_map(|n| [n.text.as_deref(), n.label.as_deref()]);
+        let edges = self.edges.iter().map(|e| e.label.as_deref());
+        nodes
+            .chain(edges)
+            .flatten()
+            .filter(|t| !t.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+/// A canvas file in the notes directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CanvasInfo {
+    /// Path relative to the notes directory
+    pub path: String,
+    /// Last modification time
+    pub modified: DateTime<Utc>,
+}
+
+/// The canvases of a vault
+pub struct CanvasStore {
+    dir: PathBuf,
+}
+
+impl CanvasStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// All canvases, sorted by path; hidden directories are skipped
+    pub async fn list(&self) -> Result<Vec<CanvasInfo>> {
+        let mut canvases = Vec::new();
+        let mut pending = vec![self.dir.clone()];
+        while let Some(dir) = pending.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if entry.file_name().to_string_lossy().starts_with('.') {
+                    continue;
+                }
+                let metadata = entry.metadata().await?;
+                if metadata.is_dir() {
+                    pending.push(path);
+                } else if path.extension().is_some_and(|ext| ext == EXTENSION) {
+                    let Ok(relative) = path.strip_prefix(&self.dir) else {
+                        continue;
+                    };
+                    canvases.push(CanvasInfo {
+                        path: relative.to_string_lossy().replace('\\', "/"),
+                        modified: metadata.modified().map(DateTime::<Utc>::from).unwrap_or_else(|_| Utc::now()),
+                    });
+                }
+            }
+        }
+        canvases.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(canvases)
+    }
+
+    /// Whether there's a canvas at `path`
+    pub fn exists(&self, path: &str) -> bool {
+        self.file(path).is_ok_and(|file| file.is_file())
+    }
+
+    /// The canvas at `path`
+    pub async fn get(&self, path: &str) -> Result<Canvas> {
+        let file = self.file(path)?;
+        match tokio::fs::read_to_string(&file).await {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(Error::CanvasNotFound(path.to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write a new canvas at `path`, failing if one is already there
+    pub async fn create(&self, path: &str, canvas: &Canvas) -> Result<()> {
+        let file = self.file(path)?;
+        if file.exists() {
+            return Err(Error::CanvasAlreadyExists(path.to_string()));
+        }
+        if let Some(parent) = file.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        self.write(&file, canvas).await
+    }
+
+    /// Replace the canvas at `path`
+    pub async fn update(&self, path: &str, canvas: &Canvas) -> Result<()> {
+        let file = self.file(path)?;
+        if !file.is_file() {
+            return Err(Error::CanvasNotFound(path.to_string()));
+        }
+        self.write(&file, canvas).await
+    }
+
+    /// Delete the canvas at `path`
+    pub async fn delete(&self, path: &str) -> Result<()> {
+        let file = self.file(path)?;
+        match tokio::fs::remove_file(&file).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(Error::CanvasNotFound(path.to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn write(&self, file: &Path, canvas: &Canvas) -> Result<()> {
+        canvas.validate()?;
+        tokio::fs::write(file, serde_json::to_string_pretty(canvas)?).await?;
+        Ok(())
+    }
+
+    /// File of the canvas at `path`, which must be a relative `.canvas` path
+    /// outside hidden directories
+    fn file(&self, path: &str) -> Result<PathBuf> {
+        let relative = Path::new(path);
+        let is_plain = relative.extension().is_some_and(|ext| ext == EXTENSION)
+            && relative.components().all(|c| {
+                matches!(c, Component::Normal(part) if !part.to_string_lossy().starts_with('.'))
+            });
+        if !is_plain {
+            return Err(Error::InvalidNotePath(format!(
+                "{:?} is not a relative .canvas path",
+                path
+            )));
+        }
+        Ok(self.dir.join(relative))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOARD: &str = r##"{
+      "nodes": [
+        {"id": "a", "type": "text", "x": 0, "y": 0, "width": 200, "height": 80, "text": "Plan the **launch**"},
+        {"id": "b", "type": "file", "x": 300, "y": 0, "width": 200, "height": 80, "file": "projects/launch.md"},
+        {"id": "g", "type": "group", "x": -20, "y": -20, "width": 560, "height": 140, "label": "Q3", "color": "#ff0000"}
+      ],
+      "edges": [
+        {"id": "e", "fromNode": "a", "fromSide": "right", "toNode": "b", "toSide": "left", "label": "tracks"}
+      ]
+    }"##;
+
+    #[test]
+    fn test_parse_and_text() {
+        let canvas: Canvas = serde_json::from_str(BOARD).unwrap();
+        assert!(canvas.validate().is_ok());
+        assert_eq!(canvas.nodes[1].kind, CanvasNodeType::File);
+        assert_eq!(canvas.edges[0].to_side.as_deref(), Some("left"));
+        assert_eq!(canvas.text(), "Plan the **launch**\n\nQ3\n\ntracks");
+
+        let json = serde_json::to_value(&canvas).unwrap();
+        assert_eq!(json["edges"][0]["fromNode"], "a");
+        assert_eq!(json["nodes"][0]["type"], "text");
+        assert!(json["nodes"][0].get("file").is_none());
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut canvas: Canvas = serde_json::from_str(BOARD).unwrap();
+        canvas.edges[0].to_node = "missing".to_string();
+        assert!(matches!(canvas.validate(), Err(Error::InvalidCanvas(_))));
+
+        let mut canvas: Canvas = serde_json::from_str(BOARD).unwrap();
+        canvas.nodes[1].id = "a".to_string();
+        assert!(matches!(canvas.validate(), Err(Error::InvalidCanvas(_))));
+
+        let mut canvas: Canvas = serde_json::from_str(BOARD).unwrap();
+        canvas.nodes[0].text = None;
+        assert!(matches!(canvas.validate(), Err(Error::InvalidCanvas(_))));
+    }
+
+    #[test]
+    fn test_canvas_paths() {
+        let store = CanvasStore::new(PathBuf::from("/vault/notes"));
+        assert_eq!(
+            store.file("boards/plan.canvas").unwrap(),
+            PathBuf::from("/vault/notes/boards/plan.canvas")
+        );
+        for bad in ["plan.md", "../plan.canvas", "/plan.canvas", ".hidden/plan.canvas", ""] {
+            assert!(store.file(bad).is_err(), "{:?} should be rejected", bad);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! Checksum snapshots for sync conflict detection
+//!
+//! Sync clients (Dropbox, iCloud, Syncthing, ...) can rewrite a note while
+//! Notidium holds its own edit in memory. A snapshot records, for every note
+//! file, the content hash the store agreed on at a point in time. A file whose
+//! store-side and on-disk hashes have both moved away from the snapshot was
+//! edited on both sides, and one version has to be kept as a conflicted copy.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// Path → content hash of all note files at a point in time
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChecksumSnapshot {
+    /// When the snapshot was taken
+    pub taken_at: Option<DateTime<Utc>>,
+    /// Map from relative file path to SHA-256 content hash
+    files: HashMap<PathBuf, String>,
+}
+
+impl ChecksumSnapshot {
+    /// Snapshot the given `(path, hash)` pairs now
+    pub fn new(files: impl IntoIterator<Item = (PathBuf, String)>) -> Self {
+        Self {
+            taken_at: Some(Utc::now()),
+            files: files.into_iter().collect(),
+        }
+    }
+
+    /// Load a snapshot from disk, or create empty if it doesn't exist
+    pub fn load(path: &Path) -> Result<Self> {
+        if path.exists() {
+            let content = std::fs::read_to_string(path)?;
+            let snapshot: ChecksumSnapshot = serde_json::from_str(&content)
+                .map_err(|e| Error::Other(format!("Failed to parse checksums: {}", e)))?;
+            Ok(snapshot)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Save the snapshot to disk
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Other(format!("Failed to serialize checksums: {}", e)))?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Hash recorded for a path, if it existed when the snapshot was taken
+    pub fn get(&self, path: &Path) -> Option<&str> {
+        self.files.get(path).map(String::as_str)
+    }
+
+    /// Record that the store and disk agree on `hash` for `path`
+    pub fn record(&mut self, path: &Path, hash: &str) {
+        self.files.insert(path.to_path_buf(), hash.to_string());
+    }
+
+    /// Whether both sides changed `path` since the snapshot and disagree
+    ///
+    /// `ours` is the store's hash, `theirs` the hash found on disk. External
+    /// changes the store adopts are recorded as they happen, so a path missing
+    /// from the snapshot was created by the store and counts as changed on our
+    /// side.
+    pub fn is_conflict(&self, path: &Path, ours: &str, theirs: &str) -> bool {
+        let base = self.get(path);
+        ours != theirs && base != Some(ours) && base != Some(theirs)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> ChecksumSnapshot {
+        ChecksumSnapshot::new([(PathBuf::from("note.md"), "base".to_string())])
+    }
+
+    #[test]
+    fn test_one_sided_changes_are_not_conflicts() {
+        let path = Path::new("note.md");
+        // Only the file on disk changed
+        assert!(!snapshot().is_conflict(path, "base", "theirs"));
+        // Only the store changed (e.g. a write the watcher hasn't seen yet)
+        assert!(!snapshot().is_conflict(path, "ours", "base"));
+        // Both sides made the same change
+        assert!(!snapshot().is_conflict(path, "same", "same"));
+    }
+
+    #[test]
+    fn test_both_sides_changed() {
+        assert!(snapshot().is_conflict(Path::new("note.md"), "ours", "theirs"));
+    }
+
+    #[test]
+    fn test_recorded_change_moves_baseline() {
+        let path = Path::new("note.md");
+        let mut snapshot = snapshot();
+        snapshot.record(path, "theirs");
+        assert!(!snapshot.is_conflict(path, "theirs", "newer"));
+    }
+
+    #[test]
+    fn test_untracked_path_counts_as_store_change() {
+        assert!(snapshot().is_conflict(Path::new("new.md"), "ours", "theirs"));
+        assert!(!snapshot().is_conflict(Path::new("new.md"), "same", "same"));
+    }
+}
@@ -0,0 +1,261 @@
+//! Cross-platform filename sanitizing
+//!
+//! Vaults are often synced between macOS, Linux and Windows, so note file
+//! names are restricted to what all three accept: no Windows-reserved
+//! characters or device names (`con`, `nul`, `com1`, ...), no trailing dots or
+//! spaces, and a bounded length. Long *paths* need no special handling: Rust's
+//! standard library switches to `\\?\` verbatim paths on Windows when needed.
+//!
+//! Letter case is the other portability trap: `API.md` and `api.md` are two
+//! files on Linux but one on default macOS and Windows volumes.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Maximum length of a file stem in bytes
+///
+/// Leaves room within the common 255-byte component limit for the `.md`
+/// extension and a numeric suffix added on collision.
+pub const MAX_STEM_LEN: usize = 200;
+
+/// Names Windows reserves for devices, with or without an extension
+const RESERVED_NAMES: &[&str] = &[
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+    "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// Characters not allowed in file names on Windows (or anywhere, for `/`)
+const INVALID_CHARS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Make a file stem (name without extension) safe on every platform
+///
+/// Never returns an empty string; stems that sanitize to nothing become
+/// `untitled`.
+pub fn sanitize_file_stem(stem: &str) -> String {
+    let replaced: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_control() || INVALID_CHARS.contains(&c) {
+                '-'
+            } else {
+                c
+            }
+        })
+        .collect();
+
+    let mut name = trim_name(&replaced).to_string();
+
+    if name.len() > MAX_STEM_LEN {
+        let mut end = MAX_STEM_LEN;
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        name = trim_name(&name[..end]).trim_end_matches('-').to_string();
+    }
+
+    if name.is_empty() {
+        return "untitled".to_string();
+    }
+
+    // Windows treats `con.md` and `con.backup.md` like `con`, so check the
+    // part before the first dot and disambiguate right after it
+    let base_len = name.find('.').unwrap_or(name.len());
+    if is_reserved(&name[..base_len]) {
+        name.insert(base_len, '_');
+    }
+
+    name
+}
+
+/// File stem for a copy of `stem` kept after a sync conflict
+///
+/// Follows the `name (conflicted copy <stamp>)` convention of common sync
+/// clients, shortening `stem` so the result stays within `MAX_STEM_LEN`.
+pub fn conflicted_copy_stem(stem: &str, stamp: &str) -> String {
+    let suffix = format!(" (conflicted copy {})", stamp);
+    let mut end = MAX_STEM_LEN.saturating_sub(suffix.len()).min(stem.len());
+    while !stem.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", stem[..end].trim_end(), suffix)
+}
+
+/// Whether a name is a Windows device name
+pub fn is_reserved(name: &str) -> bool {
+    let name = name.trim_end_matches([' ', '.']).to_ascii_lowercase();
+    RESERVED_NAMES.contains(&name.as_str())
+}
+
+/// Probe whether the filesystem holding `dir` distinguishes letter case
+///
+/// Creates a short-lived lowercase probe file and checks whether its
+/// uppercase spelling resolves to it (it does on default macOS and Windows
+/// volumes).
+pub fn is_case_sensitive(dir: &Path) -> std::io::Result<bool> {
+    let name = format!(".case-probe-{}", uuid::Uuid::new_v4().simple());
+    let probe = dir.join(&name);
+    std::fs::write(&probe, b"")?;
+    let sensitive = !dir.join(name.to_uppercase()).exists();
+    std::fs::remove_file(&probe)?;
+    Ok(sensitive)
+}
+
+/// The spelling a file actually has on disk
+///
+/// On case-insensitive filesystems `path` may name an existing file with
+/// different letter case; this returns the path with the file name as stored
+/// in its directory. Returns `path` unchanged if nothing matches.
+pub fn on_disk_spelling(path: &Path) -> PathBuf {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return path.to_path_buf();
+    };
+    let wanted = name.to_string_lossy().to_lowercase();
+
+    let Ok(entries) = std::fs::read_dir(parent) else {
+        return path.to_path_buf();
+    };
+    let mut found = None;
+    for entry in entries.flatten() {
+        let entry_name = entry.file_name();
+        if entry_name == name {
+            return path.to_path_buf();
+        }
+        if entry_name.to_string_lossy().to_lowercase() == wanted {
+            found = Some(parent.join(entry_name));
+        }
+    }
+    found.unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Groups of paths that differ only by letter case
+///
+/// Such files coexist on Linux but collide once the vault is synced to a
+/// case-insensitive filesystem.
+pub fn case_collisions<'a>(paths: impl IntoIterator<Item = &'a Path>) -> Vec<Vec<PathBuf>> {
+    let mut groups: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    for path in paths {
+        groups
+            .entry(path.to_string_lossy().to_lowercase())
+            .or_default()
+            .push(path.to_path_buf());
+    }
+
+    let mut collisions: Vec<Vec<PathBuf>> = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|mut group| {
+            group.sort();
+            group
+        })
+        .collect();
+    collisions.sort();
+    collisions
+}
+
+/// Strip leading spaces and the trailing dots/spaces Windows silently drops
+fn trim_name(name: &str) -> &str {
+    name.trim_start_matches(' ').trim_end_matches(['.', ' '])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_names_unchanged() {
+        assert_eq!(sanitize_file_stem("rust-async-notes"), "rust-async-notes");
+        assert_eq!(sanitize_file_stem("Meeting 2024-05-01"), "Meeting 2024-05-01");
+    }
+
+    #[test]
+    fn test_reserved_device_names() {
+        assert_eq!(sanitize_file_stem("con"), "con_");
+        assert_eq!(sanitize_file_stem("NUL"), "NUL_");
+        assert_eq!(sanitize_file_stem("com1"), "com1_");
+        assert_eq!(sanitize_file_stem("lpt9.backup"), "lpt9_.backup");
+        assert_eq!(sanitize_file_stem("console"), "console");
+        assert_eq!(sanitize_file_stem("com10"), "com10");
+    }
+
+    #[test]
+    fn test_trailing_dots_and_spaces() {
+        assert_eq!(sanitize_file_stem("draft..."), "draft");
+        assert_eq!(sanitize_file_stem("  spaced out . "), "spaced out");
+        assert_eq!(sanitize_file_stem("aux. "), "aux_");
+    }
+
+    #[test]
+    fn test_invalid_characters() {
+        assert_eq!(sanitize_file_stem("a<b>c:d\"e/f\\g|h?i*j"), "a-b-c-d-e-f-g-h-i-j");
+        assert_eq!(sanitize_file_stem("tab\there"), "tab-here");
+    }
+
+    #[test]
+    fn test_empty_results_become_untitled() {
+        assert_eq!(sanitize_file_stem(""), "untitled");
+        assert_eq!(sanitize_file_stem("..."), "untitled");
+        assert_eq!(sanitize_file_stem("   "), "untitled");
+    }
+
+    #[test]
+    fn test_conflicted_copy_stem() {
+        assert_eq!(
+            conflicted_copy_stem("todo", "2024-05-01 101500"),
+            "todo (conflicted copy 2024-05-01 101500)"
+        );
+
+        let copy = conflicted_copy_stem(&"é".repeat(150), "2024-05-01 101500");
+        assert!(copy.len() <= MAX_STEM_LEN);
+        assert!(copy.ends_with(" (conflicted copy 2024-05-01 101500)"));
+        assert_eq!(sanitize_file_stem(&copy), copy);
+    }
+
+    #[test]
+    fn test_case_collisions() {
+        let paths = [
+            PathBuf::from("API Notes.md"),
+            PathBuf::from("api notes.md"),
+            PathBuf::from("other.md"),
+            PathBuf::from("Dir/Other.md"),
+        ];
+        let collisions = case_collisions(paths.iter().map(|p| p.as_path()));
+        assert_eq!(
+            collisions,
+            vec![vec![PathBuf::from("API Notes.md"), PathBuf::from("api notes.md")]]
+        );
+    }
+
+    #[test]
+    fn test_case_probe_cleans_up() {
+        let dir = tempfile::TempDir::new().unwrap();
+        is_case_sensitive(dir.path()).unwrap();
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_on_disk_spelling() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("Mixed Case.md"), "").unwrap();
+
+        assert_eq!(
+            on_disk_spelling(&dir.path().join("mixed case.md")),
+            dir.path().join("Mixed Case.md")
+        );
+        assert_eq!(
+            on_disk_spelling(&dir.path().join("missing.md")),
+            dir.path().join("missing.md")
+        );
+    }
+
+    #[test]
+    fn test_long_names_truncated_on_char_boundary() {
+        let long = "é".repeat(300);
+        let sanitized = sanitize_file_stem(&long);
+        assert!(sanitized.len() <= MAX_STEM_LEN);
+        assert!(sanitized.chars().all(|c| c == 'é'));
+
+        let long_slug = format!("{}-{}", "a".repeat(199), "b".repeat(50));
+        let sanitized = sanitize_file_stem(&long_slug);
+        assert_eq!(sanitized, "a".repeat(199));
+    }
+}
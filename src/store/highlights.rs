@@ -0,0 +1,85 @@
+//! Highlighted passages
+//!
+//! Text marked `==like this==` is a highlight: a passage worth coming back
+//! to. Highlights are extracted whenever a note is indexed and kept in the
+//! metadata database, so every highlight in the vault can be listed without
+//! reading the notes again.
+
+/// The highlights of `content` as `(line, text)`, lines from 1
+///
+/// A highlight starts and ends on the same line, and its text doesn't start
+/// or end with a space. Highlights in fenced code or inline code are ignored.
+pub fn extract_highlights(content: &str) -> Vec<(usize, String)> {
+    let mut highlights = Vec::new();
+    let mut in_code = false;
+    for (index, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        for text in line_highlights(line) {
+            highlights.push((index + 1, text));
+        }
+    }
+    highlights
+}
+
+/// The highlights on one line, outside inline code
+fn line_highlights(line: &str) -> Vec<String> {
+    let mut highlights = Vec::new();
+    // Byte where the open highlight's text starts
+    let mut open: Option<usize> = None;
+    let mut in_code = false;
+    let mut rest = line.char_indices().peekable();
+    while let Some((i, c)) = rest.next() {
+        match c {
+            '`' => {
+                in_code = !in_code;
+                open = None;
+            }
+            '=' if !in_code && rest.peek().is_some_and(|&(_, next)| next == '=') => {
+                rest.next();
+                match open {
+                    Some(start) => {
+                        let text = &line[start..i];
+                        if text.is_empty() || text.trim() != text {
+                            // `====` or `== a ==`: not a highlight, but the
+                            // second marker may open one
+                            open = Some(i + 2);
+                        } else {
+                            highlights.push(text.to_string());
+                            open = None;
+                        }
+                    }
+                    None => open = Some(i + 2),
+                }
+            }
+            _ => {}
+        }
+    }
+    highlights
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_highlights() {
+        let content = "# Notes\n\nThe ==key insight== is here, and ==another one==.\n\n```\n==not this==\n```\n\nSkip `==code==` and == spaced == and a==b.\n";
+        assert_eq!(
+            extract_highlights(content),
+            vec![(3, "key insight".to_string()), (3, "another one".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_unclosed_highlight_ignored() {
+        assert!(extract_highlights("a ==dangling\nhighlight== b").is_empty());
+        assert_eq!(extract_highlights("x ==== ==y=="), vec![(1, "y".to_string())]);
+    }
+}
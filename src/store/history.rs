@@ -0,0 +1,244 @@
+//! Git-backed version history of the vault
+//!
+//! History lives in a bare repository at `.notidium/history` whose work tree
+//! is the vault itself, so it never touches a git repository the user keeps
+//! in the vault. The store commits after every change it makes; old versions
+//! of a note are read back with `git log --follow` and `git show`.
+
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+use chrono::{DateTime, Utc};
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+use crate::error::{Error, Result};
+use crate::types::NoteVersion;
+
+/// Separates commits in `git log` output
+const RECORD_SEP: char = '\x1e';
+/// Separates fields of a commit header in `git log` output
+const FIELD_SEP: char = '\x1f';
+
+/// A commit that touched a note
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// Full commit hash
+    pub rev: String,
+    pub committed_at: DateTime<Utc>,
+    /// Commit message
+    pub message: String,
+    /// Vault-relative path of the note in this commit (notes can be renamed)
+    pub path: PathBuf,
+}
+
+impl From<&HistoryEntry> for NoteVersion {
+    fn from(entry: &HistoryEntry) -> Self {
+        Self {
+            rev: entry.rev.clone(),
+            committed_at: entry.committed_at.to_rfc3339(),
+            message: entry.message.clone(),
+        }
+    }
+}
+
+/// Version history of a vault, kept in its own git repository
+pub struct GitHistory {
+    git_dir: PathBuf,
+    work_tree: PathBuf,
+    /// Serializes commits; concurrent `git add` runs fight over the index lock
+    lock: Mutex<()>,
+}
+
+impl GitHistory {
+    /// History of `work_tree` stored in `git_dir`, created on first commit
+    pub fn new(git_dir: PathBuf, work_tree: PathBuf) -> Self {
+        Self {
+            git_dir,
+            work_tree,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Commit the current state of the vault, returning the new revision
+    ///
+    /// Returns `None` when nothing changed since the last commit.
+    pub async fn commit(&self, message: &str) -> Result<Option<String>> {
+        let _guard = self.lock.lock().await;
+        self.ensure_initialized().await?;
+
+        self.git(&["add", "--all"]).await?;
+        let staged = self.run(&["diff", "--cached", "--quiet"]).await?;
+        if staged.status.success() {
+            return Ok(None);
+        }
+
+        self.git(&["commit", "--quiet", "--no-verify", "-m", message])
+            .await?;
+        let rev = self.git(&["rev-parse", "HEAD"]).await?;
+        Ok(Some(rev.trim().to_string()))
+    }
+
+    /// Commits that added or changed the note at `path`, newest first
+    ///
+    /// Follows renames, so versions from before the note was moved are
+    /// included with the path it had at the time.
+    pub async fn versions(&self, path: &Path) -> Result<Vec<HistoryEntry>> {
+        if !self.git_dir.join("HEAD").exists() {
+            return Ok(Vec::new());
+        }
+
+        let path = path.to_string_lossy();
+        let output = self
+            .run(&[
+                "log",
+                "--follow",
+                "--diff-filter=d",
+                "--name-only",
+                "--format=%x1e%H%x1f%aI%x1f%s",
+                "--",
+                path.as_ref(),
+            ])
+            .await?;
+        // A repository without commits has no log
+        if !output.status.success() {
+            return Ok(Vec::new());
+        }
+
+        Ok(parse_log(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Content of the file at `path` as of `rev`
+    pub async fn content_at(&self, rev: &str, path: &Path) -> Result<String> {
+        if !is_revision(rev) {
+            return Err(Error::VersionNotFound(rev.to_string()));
+        }
+        let spec = format!("{}:{}", rev, path.to_string_lossy());
+        self.git(&["show", &spec]).await
+    }
+
+    async fn ensure_initialized(&self) -> Result<()> {
+        if self.git_dir.join("HEAD").exists() {
+            return Ok(());
+        }
+
+        let git_dir = self.git_dir.to_string_lossy().into_owned();
+        run_git(Command::new("git").args(["init", "--quiet", "--bare", &git_dir])).await?;
+
+        // The vault's own app data is not part of its history
+        let info = self.git_dir.join("info");
+        tokio::fs::create_dir_all(&info).await?;
+        tokio::fs::write(info.join("exclude"), "/.notidium/\n").await?;
+        Ok(())
+    }
+
+    /// Run a git command against the history repository, failing on error
+    async fn git(&self, args: &[&str]) -> Result<String> {
+        run_git(&mut self.command(args)).await
+    }
+
+    /// Run a git command against the history repository, returning its output
+    async fn run(&self, args: &[&str]) -> Result<Output> {
+        self.command(args)
+            .output()
+            .await
+            .map_err(|e| Error::History(format!("Failed to run git: {}", e)))
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new("git");
+        command
+            .arg("--git-dir")
+            .arg(&self.git_dir)
+            .arg("--work-tree")
+            .arg(&self.work_tree)
+            .args([
+                "-c",
+                "user.name=Notidium",
+                "-c",
+                "user.email=notidium@localhost",
+                "-c",
+                "commit.gpgsign=false",
+                "-c",
+                "core.quotepath=false",
+            ])
+            .args(args);
+        command
+    }
+}
+
+async fn run_git(command: &mut Command) -> Result<String> {
+    let output = command
+        .output()
+        .await
+        .map_err(|e| Error::History(format!("Failed to run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(Error::History(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Whether `rev` looks like a (possibly abbreviated) commit hash
+pub fn is_revision(rev: &str) -> bool {
+    (4..=64).contains(&rev.len()) && rev.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Parse `git log --name-only` output written with [`RECORD_SEP`] and
+/// [`FIELD_SEP`] separated headers
+fn parse_log(log: &str) -> Vec<HistoryEntry> {
+    log.split(RECORD_SEP)
+        .filter_map(|record| {
+            let mut lines = record.lines();
+            let mut header = lines.next()?.split(FIELD_SEP);
+            let rev = header.next()?.to_string();
+            let committed_at = DateTime::parse_from_rfc3339(header.next()?)
+                .ok()?
+                .with_timezone(&Utc);
+            let message = header.next().unwrap_or_default().to_string();
+            let path = lines.rfind(|line| !line.trim().is_empty())?;
+            Some(HistoryEntry {
+                rev,
+                committed_at,
+                message,
+                path: PathBuf::from(path),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log_follows_renames() {
+        let log = "\x1eaaaa1111\x1f2024-05-02T10:00:00+02:00\x1fUpdate Plan\n\nnotes/plan.md\n\
+                   \x1ebbbb2222\x1f2024-05-01T09:00:00+00:00\x1fCreate Draft\n\nnotes/draft.md\n";
+
+        let entries = parse_log(log);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].rev, "aaaa1111");
+        assert_eq!(entries[0].message, "Update Plan");
+        assert_eq!(entries[0].path, PathBuf::from("notes/plan.md"));
+        assert_eq!(entries[0].committed_at.to_rfc3339(), "2024-05-02T08:00:00+00:00");
+        assert_eq!(entries[1].path, PathBuf::from("notes/draft.md"));
+    }
+
+    #[test]
+    fn test_parse_log_skips_malformed_records() {
+        assert!(parse_log("").is_empty());
+        assert!(parse_log("\x1eabc\x1fnot a date\x1fmsg\n\nnotes/a.md\n").is_empty());
+        assert!(parse_log("\x1eabc\x1f2024-05-01T09:00:00Z\x1fmsg\n").is_empty());
+    }
+
+    #[test]
+    fn test_is_revision() {
+        assert!(is_revision("0123abcd"));
+        assert!(is_revision(&"f".repeat(40)));
+        assert!(!is_revision("abc"));
+        assert!(!is_revision("--output=x"));
+        assert!(!is_revision("HEAD~1"));
+    }
+}
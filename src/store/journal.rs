@@ -0,0 +1,285 @@
+//! Operation journal for multi-step changes
+//!
+//! Some changes touch several files at once: deleting a note moves its file
+//! into the trash and rewrites the trash index, renaming a tag rewrites every
+//! note carrying it. Before such a change writes anything, the state every
+//! file should end up in is recorded in `.notidium/journal/<id>.json`, along
+//! with the state it was in. The entry is removed once the change is done.
+//!
+//! An entry still there on startup belongs to a change that was cut short.
+//! It is replayed, finishing the change; if that fails, the files it reached
+//! are put back as they were. A file changed again since the entry was
+//! written is left alone either way.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+
+/// One file's content before and after an operation, `None` for no file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileWrite {
+    /// Path relative to the vault root
+    pub path: PathBuf,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl FileWrite {
+    pub fn new(path: impl Into<PathBuf>, before: Option<String>, after: Option<String>) -> Self {
+        Self {
+            path: path.into(),
+            before,
+            after,
+        }
+    }
+}
+
+/// A multi-step change, journaled before it starts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Operation {
+    pub id: Uuid,
+    pub description: String,
+    pub started_at: DateTime<Utc>,
+    /// Applied in order
+    pub writes: Vec<FileWrite>,
+}
+
+/// What startup recovery did with the entries it found
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Recovery {
+    pub replayed: usize,
+    pub rolled_back: usize,
+}
+
+/// Write-ahead log of operations in progress
+#[derive(Debug, Clone)]
+pub struct Journal {
+    /// Vault root that file paths are relative to
+    root: PathBuf,
+    dir: PathBuf,
+}
+
+impl Journal {
+    pub fn new(root: impl Into<PathBuf>, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            dir: dir.into(),
+        }
+    }
+
+    /// Journal `writes` and apply them
+    ///
+    /// If a write fails, the ones already made are undone and the entry is
+    /// dropped. Otherwise the entry stays until [`Journal::finish`], so a crash
+    /// before then replays the operation on the next startup.
+    pub fn run(&self, description: &str, writes: Vec<FileWrite>) -> Result<Operation> {
+        let writes = writes
+            .into_iter()
+            .map(|w| FileWrite {
+                path: w.path.strip_prefix(&self.root).map(Path::to_path_buf).unwrap_or(w.path),
+                ..w
+            })
+            .collect();
+        let op = Operation {
+            id: Uuid::new_v4(),
+            description: description.to_string(),
+            started_at: Utc::now(),
+            writes,
+        };
+        self.record(&op)?;
+
+        if let Err(e) = self.apply(&op) {
+            self.roll_back(&op);
+            self.finish(&op)?;
+            return Err(e);
+        }
+        Ok(op)
+    }
+
+    /// Forget a finished operation
+    pub fn finish(&self, op: &Operation) -> Result<()> {
+        match std::fs::remove_file(self.entry_path(op.id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Operations left unfinished, oldest first
+    ///
+    /// Entries that can't be read were cut short while being written, before
+    /// their operation touched anything, and are removed.
+    pub fn pending(&self) -> Vec<Operation> {
+        let Ok(dir) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut ops = Vec::new();
+        for entry in dir.flatten() {
+            let path = entry.path();
+            let op = std::fs::read_to_string(&path)
+                .ok()
+                .filter(|_| path.extension().is_some_and(|ext| ext == "json"))
+                .and_then(|content| serde_json::from_str::<Operation>(&content).ok());
+            match op {
+                Some(op) => ops.push(op),
+                None => {
+                    tracing::warn!("Removing incomplete journal entry {}", path.display());
+                    let _ = std::fs::remove_file(&path);
+                }
+            }
+        }
+        ops.sort_by_key(|op| op.started_at);
+        ops
+    }
+
+    /// Finish or undo every operation left unfinished
+    pub fn recover(&self) -> Result<Recovery> {
+        let mut recovery = Recovery::default();
+        for op in self.pending() {
+            match self.apply(&op) {
+                Ok(()) => {
+                    tracing::info!("Finished interrupted operation: {}", op.description);
+                    recovery.replayed += 1;
+                }
+                Err(e) => {
+                    tracing::warn!("Undoing interrupted operation {}: {}", op.description, e);
+                    self.roll_back(&op);
+                    recovery.rolled_back += 1;
+                }
+            }
+            self.finish(&op)?;
+        }
+        Ok(recovery)
+    }
+
+    fn record(&self, op: &Operation) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let content = serde_json::to_string(op)
+            .map_err(|e| Error::Other(format!("Failed to serialize journal entry: {}", e)))?;
+        // Written aside and renamed, so an entry is never seen half written
+        let tmp = self.dir.join(format!("{}.tmp", op.id));
+        std::fs::write(&tmp, content)?;
+        std::fs::rename(&tmp, self.entry_path(op.id))?;
+        Ok(())
+    }
+
+    /// Move each file from its `before` to its `after` state
+    fn apply(&self, op: &Operation) -> Result<()> {
+        for write in &op.writes {
+            let path = self.root.join(&write.path);
+            let current = read_state(&path)?;
+            if current == write.after {
+                continue;
+            }
+            if current != write.before {
+                tracing::warn!("{} changed since {} started, leaving it", path.display(), op.description);
+                continue;
+            }
+            set_state(&path, write.after.as_deref())?;
+        }
+        Ok(())
+    }
+
+    /// Put files the operation reached back in their `before` state
+    fn roll_back(&self, op: &Operation) {
+        for write in op.writes.iter().rev() {
+            let path = self.root.join(&write.path);
+            let reached = read_state(&path).is_ok_and(|current| current == write.after);
+            if reached && write.before != write.after {
+                if let Err(e) = set_state(&path, write.before.as_deref()) {
+                    tracing::warn!("Failed to restore {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+
+    fn entry_path(&self, id: Uuid) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+}
+
+fn read_state(path: &Path) -> Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn set_state(path: &Path, content: Option<&str>) -> Result<()> {
+    match content {
+        Some(content) => {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, content)?;
+        }
+        None => {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_and_finish() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = Journal::new(dir.path(), dir.path().join("journal"));
+        std::fs::write(dir.path().join("a.md"), "old").unwrap();
+
+        let op = journal
+            .run(
+                "Move a",
+                vec![
+                    FileWrite::new(dir.path().join("b.md"), None, Some("old".into())),
+                    FileWrite::new(dir.path().join("a.md"), Some("old".into()), None),
+                ],
+            )
+            .unwrap();
+        assert_eq!(op.writes[0].path, PathBuf::from("b.md"));
+        assert!(!dir.path().join("a.md").exists());
+        assert_eq!(journal.pending().len(), 1);
+
+        journal.finish(&op).unwrap();
+        assert!(journal.pending().is_empty());
+    }
+
+    #[test]
+    fn test_recover_replays_interrupted_operation() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = Journal::new(dir.path(), dir.path().join("journal"));
+        std::fs::write(dir.path().join("a.md"), "a1").unwrap();
+        std::fs::write(dir.path().join("b.md"), "b1").unwrap();
+        std::fs::write(dir.path().join("c.md"), "edited since").unwrap();
+
+        // As if the process died after journaling, before writing
+        let op = Operation {
+            id: Uuid::new_v4(),
+            description: "Retag".into(),
+            started_at: Utc::now(),
+            writes: vec![
+                FileWrite::new("a.md", Some("a1".into()), Some("a2".into())),
+                FileWrite::new("b.md", Some("b1".into()), Some("b2".into())),
+                FileWrite::new("c.md", Some("c1".into()), Some("c2".into())),
+            ],
+        };
+        journal.record(&op).unwrap();
+        std::fs::write(journal.dir.join("garbage.json"), "{\"id\":").unwrap();
+
+        let recovery = journal.recover().unwrap();
+        assert_eq!(recovery, Recovery { replayed: 1, rolled_back: 0 });
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.md")).unwrap(), "a2");
+        assert_eq!(std::fs::read_to_string(dir.path().join("b.md")).unwrap(), "b2");
+        assert_eq!(std::fs::read_to_string(dir.path().join("c.md")).unwrap(), "edited since");
+        assert_eq!(std::fs::read_dir(&journal.dir).unwrap().count(), 0);
+    }
+}
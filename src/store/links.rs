@@ -0,0 +1,261 @@
+//! Link extraction from note content
+//!
+//! Recognizes Obsidian-style wiki links (`[[Note]]`, `[[Note|alias]]`,
+//! `[[Note#Heading]]`, `[[Note#^block]]`) and external `http(s)` links.
+//! Anything inside inline code or code blocks is ignored. Typed relations are
+//! read from the `relates_to`, `supersedes` and `blocked_by` frontmatter
+//! properties, each naming one note or a list of them.
+
+use std::ops::Range;
+
+use pulldown_cmark::{Event, Parser, Tag};
+use uuid::Uuid;
+
+use crate::types::{Frontmatter, Link, LinkType, RelationKind};
+
+/// Extract all links from a note's markdown, ordered by position
+///
+/// `target_note_id` is only filled in for same-note heading links
+/// (`[[#Heading]]`); resolving other targets is up to the caller.
+pub fn extract_links(source_note_id: Uuid, content: &str) -> Vec<Link> {
+    let mut code_ranges: Vec<Range<usize>> = Vec::new();
+    let mut links = Vec::new();
+
+    for (event, range) in Parser::new(content).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) | Event::Code(_) => code_ranges.push(range),
+            Event::Start(Tag::Link { dest_url, .. })
+                if dest_url.starts_with("http://") || dest_url.starts_with("https://") =>
+            {
+                links.push(new_link(
+                    source_note_id,
+                    dest_url.to_string(),
+                    LinkType::ExternalUrl,
+                    range.start,
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    let mut search_from = 0;
+    while let Some(found) = content[search_from..].find("[[") {
+        let start = search_from + found;
+        let inner_start = start + 2;
+        let Some(len) = content[inner_start..].find("]]") else {
+            break;
+        };
+        let inner = &content[inner_start..inner_start + len];
+        search_from = inner_start + len + 2;
+
+        if inner.contains('\n') || inner.contains("[[") {
+            search_from = inner_start;
+            continue;
+        }
+        if code_ranges.iter().any(|r| r.contains(&start)) {
+            continue;
+        }
+
+        if let Some(mut link) = parse_wiki_link(source_note_id, inner, start) {
+            if link.target_raw.is_empty() {
+                link.target_note_id = Some(source_note_id);
+            }
+            links.push(link);
+        }
+    }
+
+    links.sort_by_key(|l| l.position);
+    links
+}
+
+/// Parse the text between `[[` and `]]`
+fn parse_wiki_link(source_note_id: Uuid, inner: &str, position: usize) -> Option<Link> {
+    let target = inner.split('|').next().unwrap_or("").trim();
+    if target.is_empty() {
+        return None;
+    }
+
+    let (page, link_type) = match target.split_once('#') {
+        Some((page, anchor)) if anchor.starts_with('^') => (page, LinkType::BlockReference),
+        Some((page, _)) => (page, LinkType::HeadingLink),
+        None => (target, LinkType::WikiLink),
+    };
+
+    Some(new_link(
+        source_note_id,
+        page.trim().to_string(),
+        link_type,
+        position,
+    ))
+}
+
+fn new_link(
+    source_note_id: Uuid,
+    target_raw: String,
+    link_type: LinkType,
+    position: usize,
+) -> Link {
+    Link {
+        id: Uuid::new_v4(),
+        source_note_id,
+        target_note_id: None,
+        target_raw,
+        link_type,
+        position: position as u32,
+        relation: None,
+    }
+}
+
+/// Extract the typed relations declared in a note's frontmatter
+///
+/// Targets may be written as plain names or wiki links (`"[[Note]]"`, or
+/// unquoted `[[Note]]`, which YAML reads as a nested list). Each relation is
+/// positioned at its property's line in `content`.
+pub fn extract_relations(source_note_id: Uuid, frontmatter: &Frontmatter, content: &str) -> Vec<Link> {
+    let mut relations = Vec::new();
+    for kind in RelationKind::ALL {
+        let Some(value) = frontmatter.custom.get(kind.as_str()) else {
+            continue;
+        };
+        let key = format!("\n{}:", kind.as_str());
+        let position = content.find(&key).map_or(0, |i| i + 1);
+
+        let mut targets = Vec::new();
+        relation_targets(value, &mut targets);
+        for target in targets {
+            let mut link = new_link(source_note_id, target, LinkType::Relation, position);
+            link.relation = Some(kind);
+            relations.push(link);
+        }
+    }
+    relations
+}
+
+/// Note names in a relation property's value
+fn relation_targets(value: &serde_yaml::Value, targets: &mut Vec<String>) {
+    match value {
+        serde_yaml::Value::String(s) => {
+            let name = s.trim();
+            let name = name
+                .strip_prefix("[[")
+                .and_then(|n| n.strip_suffix("]]"))
+                .unwrap_or(name);
+            let name = name.split(['|', '#']).next().unwrap_or("").trim();
+            if !name.is_empty() {
+                targets.push(name.to_string());
+            }
+        }
+        serde_yaml::Value::Sequence(items) => {
+            for item in items {
+                relation_targets(item, targets);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The line of `content` containing byte offset `position`, trimmed
+pub fn link_context(content: &str, position: u32) -> String {
+    let position = (position as usize).min(content.len());
+    let start = content[..position].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let end = content[position..]
+        .find('\n')
+        .map(|i| position + i)
+        .unwrap_or(content.len());
+    content[start..end].trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets(content: &str) -> Vec<(String, LinkType)> {
+        extract_links(Uuid::new_v4(), content)
+            .into_iter()
+            .map(|l| (l.target_raw, l.link_type))
+            .collect()
+    }
+
+    #[test]
+    fn test_wiki_link_variants() {
+        let links = targets(
+            "See [[Rust Notes]], [[Async|async stuff]], [[Tokio#Runtime]] and [[Pin#^def]].",
+        );
+        assert_eq!(
+            links,
+            vec![
+                ("Rust Notes".to_string(), LinkType::WikiLink),
+                ("Async".to_string(), LinkType::WikiLink),
+                ("Tokio".to_string(), LinkType::HeadingLink),
+                ("Pin".to_string(), LinkType::BlockReference),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_external_urls() {
+        let links = targets(
+            "Docs at [tokio](https://tokio.rs) and <http://example.com>, not [local](./x.md).",
+        );
+        assert_eq!(
+            links,
+            vec![
+                ("https://tokio.rs".to_string(), LinkType::ExternalUrl),
+                ("http://example.com".to_string(), LinkType::ExternalUrl),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ignores_code() {
+        let content = "Real [[Link]]\n\n```\n[[Not A Link]]\n```\n\nInline `[[Also Not]]` too.";
+        assert_eq!(
+            targets(content),
+            vec![("Link".to_string(), LinkType::WikiLink)]
+        );
+    }
+
+    #[test]
+    fn test_same_note_heading_link() {
+        let id = Uuid::new_v4();
+        let links = extract_links(id, "Jump to [[#Setup]]");
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].link_type, LinkType::HeadingLink);
+        assert_eq!(links[0].target_note_id, Some(id));
+    }
+
+    #[test]
+    fn test_malformed_links_skipped() {
+        assert!(targets("[[]] and [[ | alias]] and [[unclosed\n]]").is_empty());
+    }
+
+    #[test]
+    fn test_link_context() {
+        let content = "first line\nsee [[Target]] here\nlast";
+        let link = &extract_links(Uuid::new_v4(), content)[0];
+        assert_eq!(link_context(content, link.position), "see [[Target]] here");
+    }
+
+    #[test]
+    fn test_frontmatter_relations() {
+        let content = "---\nsupersedes: [[Old Plan]]\nblocked_by:\n  - \"[[Budget|the budget]]\"\n  - Hiring\nrelates_to: 3\n---\n\nBody";
+        let yaml = content[3..].split("\n---").next().unwrap();
+        let frontmatter: Frontmatter = serde_yaml::from_str(yaml).unwrap();
+
+        let relations = extract_relations(Uuid::new_v4(), &frontmatter, content);
+        let found: Vec<_> = relations
+            .iter()
+            .map(|l| (l.relation.unwrap(), l.target_raw.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (RelationKind::Supersedes, "Old Plan"),
+                (RelationKind::BlockedBy, "Budget"),
+                (RelationKind::BlockedBy, "Hiring"),
+            ]
+        );
+        assert!(relations.iter().all(|l| l.link_type == LinkType::Relation));
+        assert_eq!(link_context(content, relations[1].position), "blocked_by:");
+    }
+}
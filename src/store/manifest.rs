@@ -1,6 +1,11 @@
 //! Internal manifest for tracking note metadata
 //!
 //! Maps file paths to stable UUIDs and content hashes without polluting user files.
+//!
+//! The file carries a format `version`. Older formats are upgraded on load;
+//! a manifest written by a newer notidium loads with the fields this version
+//! doesn't know kept as they were, and keeps its version when saved, so a
+//! vault synced between machines running different versions loses nothing.
 
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -10,6 +15,12 @@ use uuid::Uuid;
 
 use crate::error::{Error, Result};
 
+/// Manifest format written by this version; files without a version are 0
+///
+/// - 0: entries only
+/// - 1: `version` recorded, unknown fields kept
+pub const MANIFEST_VERSION: u32 = 1;
+
 /// Entry for a single note in the manifest
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ManifestEntry {
@@ -19,19 +30,47 @@ pub struct ManifestEntry {
     pub content_hash: String,
     /// Last indexed timestamp
     pub indexed_at: Option<DateTime<Utc>>,
+    /// Content hash the note had when it was last indexed
+    #[serde(default)]
+    pub indexed_hash: Option<String>,
     /// When the note was first seen
     #[serde(default)]
     pub created_at: Option<DateTime<Utc>>,
     /// When the note content was last modified
     #[serde(default)]
     pub updated_at: Option<DateTime<Utc>>,
+    /// User who created the note, in multi-user vaults
+    #[serde(default)]
+    pub created_by: Option<String>,
+    /// User who last modified the note, if known
+    #[serde(default)]
+    pub updated_by: Option<String>,
+    /// Fields from newer versions, kept as they were
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 /// Internal manifest tracking note paths to IDs and hashes
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Manifest {
+    /// Format the manifest is in
+    #[serde(default)]
+    version: u32,
     /// Map from relative file path to entry
     entries: HashMap<PathBuf, ManifestEntry>,
+    /// Fields from newer versions, kept as they were
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            entries: HashMap::new(),
+            extra: serde_json::Map::new(),
+        }
+    }
 }
 
 impl Manifest {
@@ -39,14 +78,35 @@ impl Manifest {
     pub fn load(path: &Path) -> Result<Self> {
         if path.exists() {
             let content = std::fs::read_to_string(path)?;
-            let manifest: Manifest = serde_json::from_str(&content)
+            let mut manifest: Manifest = serde_json::from_str(&content)
                 .map_err(|e| Error::Other(format!("Failed to parse manifest: {}", e)))?;
+            manifest.upgrade();
             Ok(manifest)
         } else {
             Ok(Self::default())
         }
     }
 
+    /// Bring an older format up to [`MANIFEST_VERSION`]
+    fn upgrade(&mut self) {
+        if self.version > MANIFEST_VERSION {
+            tracing::warn!(
+                "Manifest was written by a newer notidium (format {}, this version knows {}); keeping what it doesn't understand",
+                self.version,
+                MANIFEST_VERSION
+            );
+            return;
+        }
+        // Format 0 reads the same as 1
+        self.version = MANIFEST_VERSION;
+    }
+
+    /// Format the manifest is in, newer than [`MANIFEST_VERSION`] when it
+    /// was written by a newer notidium
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
     /// Save manifest to disk
     pub fn save(&self, path: &Path) -> Result<()> {
         if let Some(parent) = path.parent() {
@@ -69,8 +129,12 @@ impl Manifest {
                 id,
                 content_hash: content_hash.to_string(),
                 indexed_at: None,
+                indexed_hash: None,
                 created_at: Some(now),
                 updated_at: Some(now),
+                created_by: None,
+                updated_by: None,
+                extra: serde_json::Map::new(),
             });
             id
         }
@@ -87,9 +151,12 @@ impl Manifest {
     }
 
     /// Update timestamps when note content changes
+    ///
+    /// The change is unattributed until [`Manifest::attribute`] says otherwise.
     pub fn update_timestamps(&mut self, path: &Path, updated_at: DateTime<Utc>) {
         if let Some(entry) = self.entries.get_mut(path) {
             entry.updated_at = Some(updated_at);
+            entry.updated_by = None;
             // Ensure created_at is set (migration for old entries)
             if entry.created_at.is_none() {
                 entry.created_at = Some(updated_at);
@@ -97,6 +164,17 @@ impl Manifest {
         }
     }
 
+    /// Credit `user` with the latest change to a note, and with creating it
+    /// if `created` is set
+    pub fn attribute(&mut self, path: &Path, user: &str, created: bool) {
+        if let Some(entry) = self.entries.get_mut(path) {
+            if created {
+                entry.created_by = Some(user.to_string());
+            }
+            entry.updated_by = Some(user.to_string());
+        }
+    }
+
     /// Get the ID for a note path (if exists)
     pub fn get_id(&self, path: &Path) -> Option<Uuid> {
         self.entries.get(path).map(|e| e.id)
@@ -110,6 +188,33 @@ impl Manifest {
             .map(|(path, _)| path.as_path())
     }
 
+    /// A tracked path equal to `path` ignoring letter case, other than `path` itself
+    pub fn find_case_variant(&self, path: &Path) -> Option<PathBuf> {
+        let wanted = path.to_string_lossy().to_lowercase();
+        self.entries
+            .keys()
+            .find(|p| p.as_path() != path && p.to_string_lossy().to_lowercase() == wanted)
+            .cloned()
+    }
+
+    /// Move an entry to a new path, keeping its ID and timestamps
+    pub fn rename(&mut self, from: &Path, to: &Path) -> Option<Uuid> {
+        let entry = self.entries.remove(from)?;
+        let id = entry.id;
+        self.entries.insert(to.to_path_buf(), entry);
+        Some(id)
+    }
+
+    /// Track `path` with an existing entry, e.g. when restoring a deleted note
+    pub fn insert(&mut self, path: &Path, entry: ManifestEntry) {
+        self.entries.insert(path.to_path_buf(), entry);
+    }
+
+    /// Remove the entry for a path, returning its ID
+    pub fn remove(&mut self, path: &Path) -> Option<Uuid> {
+        self.entries.remove(path).map(|e| e.id)
+    }
+
     /// Update the content hash for a note
     pub fn update_hash(&mut self, path: &Path, content_hash: &str) {
         if let Some(entry) = self.entries.get_mut(path) {
@@ -117,19 +222,21 @@ impl Manifest {
         }
     }
 
-    /// Mark a note as indexed
+    /// Mark a note as indexed at its current content hash
     pub fn mark_indexed(&mut self, path: &Path) {
         if let Some(entry) = self.entries.get_mut(path) {
             entry.indexed_at = Some(Utc::now());
+            entry.indexed_hash = Some(entry.content_hash.clone());
         }
     }
 
-    /// Check if a note needs re-indexing (hash changed or never indexed)
+    /// Check if a note needs re-indexing (changed since indexed or never indexed)
+    ///
+    /// Compares against the hash recorded by [`Manifest::mark_indexed`], not
+    /// `content_hash`, which writes keep current.
     pub fn needs_reindex(&self, path: &Path, current_hash: &str) -> bool {
         match self.entries.get(path) {
-            Some(entry) => {
-                entry.indexed_at.is_none() || entry.content_hash != current_hash
-            }
+            Some(entry) => entry.indexed_hash.as_deref() != Some(current_hash),
             None => true, // New note
         }
     }
@@ -164,11 +271,21 @@ impl Manifest {
         deleted_ids
     }
 
+    /// Every entry, by relative file path
+    pub fn entries(&self) -> impl Iterator<Item = (&Path, &ManifestEntry)> {
+        self.entries.iter().map(|(path, entry)| (path.as_path(), entry))
+    }
+
     /// Get statistics
     pub fn stats(&self) -> ManifestStats {
         let total = self.entries.len();
         let indexed = self.entries.values().filter(|e| e.indexed_at.is_some()).count();
-        ManifestStats { total, indexed }
+        let last_indexed = self.entries.values().filter_map(|e| e.indexed_at).max();
+        ManifestStats {
+            total,
+            indexed,
+            last_indexed,
+        }
     }
 }
 
@@ -176,6 +293,8 @@ impl Manifest {
 pub struct ManifestStats {
     pub total: usize,
     pub indexed: usize,
+    /// When a note was last embedded
+    pub last_indexed: Option<DateTime<Utc>>,
 }
 
 #[cfg(test)]
@@ -213,6 +332,25 @@ mod tests {
 
         // After hash change, needs reindex again
         assert!(manifest.needs_reindex(&path, "hash2"));
+
+        // Even once a write has recorded the new hash
+        manifest.update_hash(&path, "hash2");
+        assert!(manifest.needs_reindex(&path, "hash2"));
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut manifest = Manifest::default();
+        manifest.get_or_create_id(Path::new("a.md"), "h1");
+        manifest.get_or_create_id(Path::new("b.md"), "h2");
+        let stats = manifest.stats();
+        assert_eq!((stats.total, stats.indexed), (2, 0));
+        assert!(stats.last_indexed.is_none());
+
+        manifest.mark_indexed(Path::new("b.md"));
+        let stats = manifest.stats();
+        assert_eq!(stats.indexed, 1);
+        assert_eq!(stats.last_indexed, manifest.get_entry(Path::new("b.md")).unwrap().indexed_at);
     }
 
     #[test]
@@ -233,6 +371,53 @@ mod tests {
         assert!(manifest.get_id(&path2).is_none());
     }
 
+    #[test]
+    fn test_rename_keeps_id() {
+        let mut manifest = Manifest::default();
+        let old_path = PathBuf::from("old.md");
+        let new_path = PathBuf::from("folder/new.md");
+
+        let id = manifest.get_or_create_id(&old_path, "h1");
+
+        assert_eq!(manifest.rename(&old_path, &new_path), Some(id));
+        assert!(manifest.get_id(&old_path).is_none());
+        assert_eq!(manifest.get_id(&new_path), Some(id));
+        assert_eq!(manifest.rename(&old_path, &new_path), None);
+    }
+
+    #[test]
+    fn test_find_case_variant() {
+        let mut manifest = Manifest::default();
+        manifest.get_or_create_id(Path::new("API Notes.md"), "h1");
+
+        assert_eq!(
+            manifest.find_case_variant(Path::new("api notes.md")),
+            Some(PathBuf::from("API Notes.md"))
+        );
+        assert_eq!(manifest.find_case_variant(Path::new("API Notes.md")), None);
+        assert_eq!(manifest.find_case_variant(Path::new("other.md")), None);
+    }
+
+    #[test]
+    fn test_attribution_cleared_by_unattributed_change() {
+        let mut manifest = Manifest::default();
+        let path = PathBuf::from("shared.md");
+        manifest.get_or_create_id(&path, "h1");
+
+        manifest.attribute(&path, "alice", true);
+        manifest.update_timestamps(&path, Utc::now());
+        manifest.attribute(&path, "bob", false);
+        let entry = manifest.get_entry(&path).unwrap();
+        assert_eq!(entry.created_by.as_deref(), Some("alice"));
+        assert_eq!(entry.updated_by.as_deref(), Some("bob"));
+
+        // An edit nobody is credited with, e.g. from a sync client
+        manifest.update_timestamps(&path, Utc::now());
+        let entry = manifest.get_entry(&path).unwrap();
+        assert_eq!(entry.created_by.as_deref(), Some("alice"));
+        assert_eq!(entry.updated_by, None);
+    }
+
     #[test]
     fn test_save_and_load() {
         let temp_dir = TempDir::new().unwrap();
@@ -246,5 +431,49 @@ mod tests {
 
         let loaded = Manifest::load(&manifest_path).unwrap();
         assert_eq!(loaded.get_id(&path), Some(id));
+        assert_eq!(loaded.version(), MANIFEST_VERSION);
+    }
+
+    #[test]
+    fn test_load_unversioned_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        let manifest_path = temp_dir.path().join("manifest.json");
+        let id = Uuid::new_v4();
+        let legacy = serde_json::json!({
+            "entries": {"old.md": {"id": id, "content_hash": "h", "indexed_at": null}}
+        });
+        std::fs::write(&manifest_path, legacy.to_string()).unwrap();
+
+        let manifest = Manifest::load(&manifest_path).unwrap();
+        assert_eq!(manifest.version(), MANIFEST_VERSION);
+        assert_eq!(manifest.get_id(Path::new("old.md")), Some(id));
+    }
+
+    #[test]
+    fn test_newer_manifest_keeps_unknown_fields() {
+        let temp_dir = TempDir::new().unwrap();
+        let manifest_path = temp_dir.path().join("manifest.json");
+        let newer = serde_json::json!({
+            "version": MANIFEST_VERSION + 1,
+            "sync_peer": "laptop",
+            "entries": {"note.md": {
+                "id": Uuid::new_v4(),
+                "content_hash": "h",
+                "indexed_at": null,
+                "word_count": 42
+            }}
+        });
+        std::fs::write(&manifest_path, newer.to_string()).unwrap();
+
+        let mut manifest = Manifest::load(&manifest_path).unwrap();
+        manifest.update_hash(Path::new("note.md"), "h2");
+        manifest.save(&manifest_path).unwrap();
+
+        let saved: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&manifest_path).unwrap()).unwrap();
+        assert_eq!(saved["version"], MANIFEST_VERSION + 1);
+        assert_eq!(saved["sync_peer"], "laptop");
+        assert_eq!(saved["entries"]["note.md"]["word_count"], 42);
+        assert_eq!(saved["entries"]["note.md"]["content_hash"], "h2");
     }
 }
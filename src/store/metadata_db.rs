@@ -1,11 +1,13 @@
 //! SQLite metadata database
 
 use rusqlite::{params, Connection};
+use std::collections::HashSet;
 use std::path::Path;
 use std::sync::Mutex;
 
 use crate::error::Result;
-use crate::types::Note;
+use crate::types::{Annotation, FeedbackKind, Link, LinkType, Note, ReadPosition, RelationKind, TermFeedback};
+use super::manifest::Manifest;
 
 /// SQLite database for note metadata
 pub struct MetadataDb {
@@ -23,6 +25,16 @@ impl MetadataDb {
         Ok(db)
     }
 
+    /// Open a throwaway database that lives only in memory
+    pub fn open_in_memory() -> Result<Self> {
+        let conn = Connection::open_in_memory()?;
+        let db = Self {
+            conn: Mutex::new(conn),
+        };
+        db.init_schema()?;
+        Ok(db)
+    }
+
     /// Initialize database schema
     fn init_schema(&self) -> Result<()> {
         let conn = self.conn.lock().unwrap();
@@ -86,131 +98,936 @@ impl MetadataDb {
                 target_raw TEXT NOT NULL,
                 link_type TEXT NOT NULL,
                 position INTEGER NOT NULL,
+                relation TEXT,
                 FOREIGN KEY (source_note_id) REFERENCES notes(id),
                 FOREIGN KEY (target_note_id) REFERENCES notes(id)
             );
 
             CREATE INDEX IF NOT EXISTS idx_links_source ON links(source_note_id);
             CREATE INDEX IF NOT EXISTS idx_links_target ON links(target_note_id);
+
+            CREATE TABLE IF NOT EXISTS note_attachments (
+                note_id TEXT NOT NULL,
+                filename TEXT NOT NULL,
+                PRIMARY KEY (note_id, filename),
+                FOREIGN KEY (note_id) REFERENCES notes(id)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_note_attachments_file ON note_attachments(filename);
+
+            CREATE TABLE IF NOT EXISTS highlights (
+                note_id TEXT NOT NULL,
+                line INTEGER NOT NULL,
+                text TEXT NOT NULL,
+                FOREIGN KEY (note_id) REFERENCES notes(id)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_highlights_note ON highlights(note_id);
+
+            CREATE TABLE IF NOT EXISTS note_access (
+                note_id TEXT NOT NULL,
+                accessed_at INTEGER NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_note_access_note ON note_access(note_id);
+            CREATE INDEX IF NOT EXISTS idx_note_access_time ON note_access(accessed_at);
+
+            CREATE TABLE IF NOT EXISTS read_positions (
+                note_id TEXT NOT NULL,
+                client TEXT NOT NULL,
+                heading TEXT,
+                line INTEGER,
+                scroll REAL,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (note_id, client)
+            );
+
+            CREATE TABLE IF NOT EXISTS starred_notes (
+                note_id TEXT NOT NULL,
+                client TEXT NOT NULL,
+                starred_at TEXT NOT NULL,
+                PRIMARY KEY (note_id, client)
+            );
+
+            CREATE TABLE IF NOT EXISTS annotations (
+                id TEXT PRIMARY KEY,
+                note_id TEXT NOT NULL,
+                heading TEXT,
+                line_start INTEGER,
+                line_end INTEGER,
+                reply_to TEXT,
+                author TEXT,
+                body TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_annotations_note ON annotations(note_id);
+
+            CREATE TABLE IF NOT EXISTS search_feedback (
+                query TEXT NOT NULL,
+                term TEXT NOT NULL,
+                note_id TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                client TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_search_feedback_term ON search_feedback(term);
             "#,
         )?;
 
+        // Databases created before typed relations lack the column
+        let has_relation = conn
+            .prepare("SELECT 1 FROM pragma_table_info('links') WHERE name = 'relation'")?
+            .exists([])?;
+        if !has_relation {
+            conn.execute("ALTER TABLE links ADD COLUMN relation TEXT", [])?;
+        }
+
         Ok(())
     }
 
     /// Insert or update a note
     pub fn upsert_note(&self, note: &Note) -> Result<()> {
         let conn = self.conn.lock().unwrap();
+        upsert_note(&conn, note)
+    }
 
-        conn.execute(
-            r#"
-            INSERT INTO notes (id, title, slug, file_path, content_hash, created_at, updated_at, accessed_at, is_pinned, is_archived, is_deleted, deleted_at)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
-            ON CONFLICT(id) DO UPDATE SET
-                title = excluded.title,
-                slug = excluded.slug,
-                file_path = excluded.file_path,
-                content_hash = excluded.content_hash,
-                updated_at = excluded.updated_at,
-                accessed_at = excluded.accessed_at,
-                is_pinned = excluded.is_pinned,
-                is_archived = excluded.is_archived,
-                is_deleted = excluded.is_deleted,
-                deleted_at = excluded.deleted_at
-            "#,
-            params![
-                note.id.to_string(),
-                note.title,
-                note.slug,
-                note.file_path.to_string_lossy().to_string(),
-                note.content_hash,
-                note.created_at.to_rfc3339(),
-                note.updated_at.to_rfc3339(),
-                note.accessed_at.to_rfc3339(),
-                note.is_pinned,
-                note.is_archived,
-                note.is_deleted,
-                note.deleted_at.map(|dt| dt.to_rfc3339()),
-            ],
-        )?;
+    /// Insert or update several notes in one transaction
+    pub fn upsert_notes(&self, notes: &[&Note]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for note in notes {
+            upsert_note(&tx, note)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
 
-        // Update tags
-        if let Some(fm) = &note.frontmatter {
-            for tag in &fm.tags {
-                self.ensure_tag(tag)?;
-                self.link_note_tag(&note.id.to_string(), tag)?;
+    /// Whether no notes are stored yet, as in a database just created
+    pub fn is_empty(&self) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM notes", [], |row| row.get(0))?;
+        Ok(count == 0)
+    }
+
+    /// Seed the notes table from a `manifest.json` written before the
+    /// database kept note metadata, returning how many notes were added
+    ///
+    /// Titles are taken from file names until the notes are next loaded.
+    pub fn import_manifest(&self, manifest: &Manifest) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut imported = 0;
+        {
+            let mut stmt = tx.prepare(
+                r#"
+                INSERT INTO notes (id, title, slug, file_path, content_hash, created_at, updated_at, accessed_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)
+                ON CONFLICT(id) DO NOTHING
+                "#,
+            )?;
+            let now = chrono::Utc::now();
+            for (path, entry) in manifest.entries() {
+                let title = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let updated_at = entry.updated_at.or(entry.created_at).unwrap_or(now);
+                imported += stmt.execute(params![
+                    entry.id.to_string(),
+                    title,
+                    slug::slugify(&title),
+                    path.to_string_lossy().to_string(),
+                    entry.content_hash,
+                    entry.created_at.unwrap_or(updated_at).to_rfc3339(),
+                    updated_at.to_rfc3339(),
+                ])?;
             }
         }
+        tx.commit()?;
+        Ok(imported)
+    }
+
+    /// Delete the rows of notes other than `ids`, such as notes removed
+    /// from disk while notidium wasn't running
+    pub fn retain_notes(&self, ids: &[uuid::Uuid]) -> Result<usize> {
+        let keep: HashSet<String> = ids.iter().map(|id| id.to_string()).collect();
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let stale: Vec<String> = tx
+            .prepare("SELECT id FROM notes")?
+            .query_map([], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .filter(|id: &String| !keep.contains(id))
+            .collect();
+        for id in &stale {
+            delete_note_rows(&tx, id)?;
+        }
+        tx.commit()?;
+        Ok(stale.len())
+    }
+
+    /// Tags with the number of notes carrying each, by name
+    ///
+    /// Tags differing only by letter case are one tag, shown as last written.
+    pub fn get_tag_counts(&self) -> Result<Vec<(String, u32)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT display_name, note_count FROM tags WHERE note_count > 0 ORDER BY name",
+        )?;
+
+        let counts = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(counts)
+    }
+
+    /// Get all unique tags
+    pub fn get_tags(&self) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT display_name FROM tags ORDER BY name")?;
+
+        let tags: Vec<String> = stmt
+            .query_map([], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(tags)
+    }
+
+    /// Replace the outgoing links of the given notes
+    pub fn replace_links(&self, source_note_ids: &[uuid::Uuid], links: &[Link]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        for id in source_note_ids {
+            tx.execute(
+                "DELETE FROM links WHERE source_note_id = ?1",
+                params![id.to_string()],
+            )?;
+        }
+        insert_links(&tx, links)?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Replace every stored link (used after a full vault load)
+    pub fn replace_all_links(&self, links: &[Link]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute("DELETE FROM links", [])?;
+        insert_links(&tx, links)?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Replace the attachments a note embeds
+    pub fn replace_attachments(&self, note_id: uuid::Uuid, filenames: &[String]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "DELETE FROM note_attachments WHERE note_id = ?1",
+            params![note_id.to_string()],
+        )?;
+        insert_attachments(&tx, note_id, filenames)?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Replace every stored attachment reference (used after a full vault load)
+    pub fn replace_all_attachments(&self, refs: &[(uuid::Uuid, Vec<String>)]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute("DELETE FROM note_attachments", [])?;
+        for (note_id, filenames) in refs {
+            insert_attachments(&tx, *note_id, filenames)?;
+        }
 
+        tx.commit()?;
         Ok(())
     }
 
-    /// Ensure a tag exists
-    fn ensure_tag(&self, tag: &str) -> Result<()> {
+    /// File names of the attachments a note embeds, sorted
+    pub fn get_note_attachments(&self, note_id: uuid::Uuid) -> Result<Vec<String>> {
         let conn = self.conn.lock().unwrap();
-        let tag_lower = tag.to_lowercase();
+        let mut stmt = conn.prepare(
+            "SELECT filename FROM note_attachments WHERE note_id = ?1 ORDER BY filename",
+        )?;
 
-        conn.execute(
+        let filenames = stmt
+            .query_map(params![note_id.to_string()], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(filenames)
+    }
+
+    /// IDs of the notes that embed an attachment
+    pub fn get_attachment_notes(&self, filename: &str) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT note_id FROM note_attachments WHERE filename = ?1 ORDER BY note_id",
+        )?;
+
+        let ids = stmt
+            .query_map(params![filename], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(ids)
+    }
+
+    /// Replace the highlights of a note, given as `(line, text)`
+    pub fn replace_highlights(&self, note_id: uuid::Uuid, highlights: &[(usize, String)]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "DELETE FROM highlights WHERE note_id = ?1",
+            params![note_id.to_string()],
+        )?;
+        insert_highlights(&tx, note_id, highlights)?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Replace every stored highlight (used after a full vault load)
+    pub fn replace_all_highlights(&self, highlights: &[(uuid::Uuid, Vec<(usize, String)>)]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute("DELETE FROM highlights", [])?;
+        for (note_id, note_highlights) in highlights {
+            insert_highlights(&tx, *note_id, note_highlights)?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Every stored highlight as `(note_id, line, text)`, by note and line
+    pub fn get_highlights(&self) -> Result<Vec<(String, usize, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT note_id, line, text FROM highlights ORDER BY note_id, line, rowid")?;
+
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(rows)
+    }
+
+    /// Links that may point at a note: those resolved to it plus all
+    /// unresolved note links, which the caller matches by name
+    pub fn get_backlink_candidates(&self, target_note_id: uuid::Uuid) -> Result<Vec<Link>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT id, source_note_id, target_note_id, target_raw, link_type, position, relation
+            FROM links
+            WHERE source_note_id != ?1
+              AND (target_note_id = ?1
+                   OR (target_note_id IS NULL AND link_type != 'ExternalUrl'))
+            ORDER BY source_note_id, position
+            "#,
+        )?;
+
+        let links = stmt
+            .query_map(params![target_note_id.to_string()], row_to_link)?
+            .filter_map(|r| r.ok())
+            .flatten()
+            .collect();
+
+        Ok(links)
+    }
+
+    /// Typed relations that may involve a note: those it declares, those
+    /// resolved to it, and all unresolved ones, which the caller matches by name
+    pub fn get_relation_candidates(&self, note_id: uuid::Uuid) -> Result<Vec<Link>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
             r#"
-            INSERT INTO tags (id, name, display_name, source, note_count)
-            VALUES (?1, ?2, ?3, 'Manual', 0)
-            ON CONFLICT(name) DO NOTHING
+            SELECT id, source_note_id, target_note_id, target_raw, link_type, position, relation
+            FROM links
+            WHERE relation IS NOT NULL
+              AND (source_note_id = ?1 OR target_note_id = ?1 OR target_note_id IS NULL)
+            ORDER BY source_note_id, position
             "#,
-            params![uuid::Uuid::new_v4().to_string(), tag_lower, tag],
         )?;
 
+        let links = stmt
+            .query_map(params![note_id.to_string()], row_to_link)?
+            .filter_map(|r| r.ok())
+            .flatten()
+            .collect();
+
+        Ok(links)
+    }
+
+    /// Log that a note was opened at `at` (Unix seconds)
+    ///
+    /// Opens of the same note less than `dedupe_secs` apart are logged once,
+    /// and entries older than `retain_secs` are dropped.
+    pub fn record_access(
+        &self,
+        note_id: uuid::Uuid,
+        at: i64,
+        dedupe_secs: i64,
+        retain_secs: i64,
+    ) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            r#"
+            INSERT INTO note_access (note_id, accessed_at)
+            SELECT ?1, ?2
+            WHERE NOT EXISTS (
+                SELECT 1 FROM note_access WHERE note_id = ?1 AND accessed_at > ?2 - ?3
+            )
+            "#,
+            params![note_id.to_string(), at, dedupe_secs],
+        )?;
+        conn.execute(
+            "DELETE FROM note_access WHERE accessed_at < ?1",
+            params![at - retain_secs],
+        )?;
         Ok(())
     }
 
-    /// Link a note to a tag
-    fn link_note_tag(&self, note_id: &str, tag: &str) -> Result<()> {
+    /// Number of logged opens of each note that has any
+    pub fn get_access_counts(&self) -> Result<Vec<(String, u32)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT note_id, COUNT(*) FROM note_access GROUP BY note_id")?;
+
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(rows)
+    }
+
+    /// Notes opened within `window_secs` of an open of `note_id`
+    ///
+    /// Returns `(note_id, count)` pairs, where `count` is the number of
+    /// opens of `note_id` the other note was opened near, most frequent first.
+    pub fn get_co_accessed(
+        &self,
+        note_id: uuid::Uuid,
+        window_secs: i64,
+        limit: usize,
+    ) -> Result<Vec<(String, u32)>> {
         let conn = self.conn.lock().unwrap();
-        let tag_lower = tag.to_lowercase();
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT b.note_id, COUNT(DISTINCT a.rowid) AS together
+            FROM note_access a
+            JOIN note_access b
+              ON b.accessed_at BETWEEN a.accessed_at - ?2 AND a.accessed_at + ?2
+             AND b.note_id != a.note_id
+            WHERE a.note_id = ?1
+            GROUP BY b.note_id
+            ORDER BY together DESC, MAX(b.accessed_at) DESC
+            LIMIT ?3
+            "#,
+        )?;
+
+        let rows = stmt
+            .query_map(
+                params![note_id.to_string(), window_secs, limit as i64],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?
+            .filter_map(|r| r.ok())
+            .collect();
 
+        Ok(rows)
+    }
+
+    /// Save where `client` left off in a note, replacing its last position
+    pub fn set_read_position(&self, note_id: uuid::Uuid, client: &str, position: &ReadPosition) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
         conn.execute(
             r#"
-            INSERT INTO note_tags (note_id, tag_id)
-            SELECT ?1, id FROM tags WHERE name = ?2
-            ON CONFLICT DO NOTHING
+            INSERT INTO read_positions (note_id, client, heading, line, scroll, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            ON CONFLICT(note_id, client) DO UPDATE SET
+                heading = excluded.heading,
+                line = excluded.line,
+                scroll = excluded.scroll,
+                updated_at = excluded.updated_at
             "#,
-            params![note_id, tag_lower],
+            params![
+                note_id.to_string(),
+                client,
+                position.heading,
+                position.line,
+                position.scroll,
+                position.updated_at,
+            ],
         )?;
+        Ok(())
+    }
 
-        // Update tag count
+    /// Where `client` last left off in a note, if it saved a position
+    pub fn get_read_position(&self, note_id: uuid::Uuid, client: &str) -> Result<Option<ReadPosition>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT heading, line, scroll, updated_at FROM read_positions WHERE note_id = ?1 AND client = ?2",
+        )?;
+
+        let position = stmt
+            .query_map(params![note_id.to_string(), client], |row| {
+                Ok(ReadPosition {
+                    heading: row.get(0)?,
+                    line: row.get(1)?,
+                    scroll: row.get(2)?,
+                    updated_at: row.get(3)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .next();
+
+        Ok(position)
+    }
+
+    /// Star or unstar a note for `client`
+    pub fn set_starred(&self, note_id: uuid::Uuid, client: &str, starred: bool, at: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        if starred {
+            conn.execute(
+                r#"
+                INSERT INTO starred_notes (note_id, client, starred_at)
+                VALUES (?1, ?2, ?3)
+                ON CONFLICT(note_id, client) DO NOTHING
+                "#,
+                params![note_id.to_string(), client, at],
+            )?;
+        } else {
+            conn.execute(
+                "DELETE FROM starred_notes WHERE note_id = ?1 AND client = ?2",
+                params![note_id.to_string(), client],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// IDs of the notes `client` starred, most recently starred first
+    pub fn get_starred(&self, client: &str) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT note_id FROM starred_notes WHERE client = ?1 ORDER BY starred_at DESC, rowid DESC",
+        )?;
+
+        let ids = stmt
+            .query_map(params![client], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(ids)
+    }
+
+    /// Save a new annotation
+    pub fn insert_annotation(&self, annotation: &Annotation) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
         conn.execute(
             r#"
-            UPDATE tags SET note_count = (
-                SELECT COUNT(*) FROM note_tags WHERE tag_id = tags.id
-            ) WHERE name = ?1
+            INSERT INTO annotations
+                (id, note_id, heading, line_start, line_end, reply_to, author, body, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
             "#,
-            params![tag_lower],
+            params![
+                annotation.id,
+                annotation.note_id,
+                annotation.heading,
+                annotation.line_start,
+                annotation.line_end,
+                annotation.reply_to,
+                annotation.author,
+                annotation.body,
+                annotation.created_at,
+                annotation.updated_at,
+            ],
         )?;
+        Ok(())
+    }
+
+    /// Annotations on a note, oldest first
+    pub fn get_annotations(&self, note_id: &str) -> Result<Vec<Annotation>> {
+        self.query_annotations("note_id = ?1", note_id)
+    }
 
+    /// An annotation by ID
+    pub fn get_annotation(&self, id: &str) -> Result<Option<Annotation>> {
+        Ok(self.query_annotations("id = ?1", id)?.into_iter().next())
+    }
+
+    /// Replace an annotation's body
+    pub fn update_annotation(&self, id: &str, body: &str, updated_at: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE annotations SET body = ?2, updated_at = ?3 WHERE id = ?1",
+            params![id, body, updated_at],
+        )?;
         Ok(())
     }
 
-    /// Get all unique tags
-    pub fn get_tags(&self) -> Result<Vec<String>> {
+    /// Delete an annotation and the replies in its thread
+    pub fn delete_annotation(&self, id: &str) -> Result<()> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare("SELECT display_name FROM tags ORDER BY name")?;
+        conn.execute(
+            "DELETE FROM annotations WHERE id = ?1 OR reply_to = ?1",
+            params![id],
+        )?;
+        Ok(())
+    }
 
-        let tags: Vec<String> = stmt
-            .query_map([], |row| row.get(0))?
+    fn query_annotations(&self, filter: &str, value: &str) -> Result<Vec<Annotation>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            r#"
+            SELECT id, note_id, heading, line_start, line_end, reply_to, author, body, created_at, updated_at
+            FROM annotations WHERE {}
+            ORDER BY created_at, rowid
+            "#,
+            filter
+        ))?;
+
+        let annotations = stmt
+            .query_map(params![value], |row| {
+                Ok(Annotation {
+                    id: row.get(0)?,
+                    note_id: row.get(1)?,
+                    heading: row.get(2)?,
+                    line_start: row.get(3)?,
+                    line_end: row.get(4)?,
+                    reply_to: row.get(5)?,
+                    author: row.get(6)?,
+                    body: row.get(7)?,
+                    created_at: row.get(8)?,
+                    updated_at: row.get(9)?,
+                })
+            })?
             .filter_map(|r| r.ok())
             .collect();
 
-        Ok(tags)
+        Ok(annotations)
+    }
+
+    /// Record feedback from `client` on a result of `query`, once for each
+    /// of its terms
+    pub fn add_search_feedback(
+        &self,
+        query: &str,
+        terms: &[String],
+        note_id: &str,
+        kind: FeedbackKind,
+        client: &str,
+        at: &str,
+    ) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for term in terms {
+            tx.execute(
+                r#"
+                INSERT INTO search_feedback (query, term, note_id, kind, client, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                "#,
+                params![query, term, note_id, kind.as_str(), client, at],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Feedback totals per term and note, for `terms` or every term
+    pub fn get_term_feedback(&self, terms: Option<&[String]>) -> Result<Vec<TermFeedback>> {
+        let conn = self.conn.lock().unwrap();
+        let filter = match terms {
+            Some([]) => return Ok(Vec::new()),
+            Some(terms) => format!("WHERE term IN ({})", vec!["?"; terms.len()].join(", ")),
+            None => String::new(),
+        };
+        let mut stmt = conn.prepare(&format!(
+            "SELECT term, note_id, kind, query FROM search_feedback {} ORDER BY term, note_id, created_at",
+            filter
+        ))?;
+
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(terms.unwrap_or_default()), |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })?
+            .filter_map(|r| r.ok());
+
+        let mut feedback: Vec<TermFeedback> = Vec::new();
+        for (term, note_id, kind, query) in rows {
+            let entry = match feedback.last_mut() {
+                Some(last) if last.term == term && last.note_id == note_id => last,
+                _ => {
+                    feedback.push(TermFeedback {
+                        term,
+                        note_id,
+                        clicks: 0,
+                        irrelevant: 0,
+                        queries: Vec::new(),
+                    });
+                    feedback.last_mut().unwrap()
+                }
+            };
+            if kind == FeedbackKind::Clicked.as_str() {
+                entry.clicks += 1;
+            } else {
+                entry.irrelevant += 1;
+            }
+            if !entry.queries.contains(&query) {
+                entry.queries.push(query);
+            }
+        }
+
+        Ok(feedback)
+    }
+
+    /// Forget all feedback on search results
+    pub fn clear_search_feedback(&self) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM search_feedback", [])?;
+        Ok(())
     }
 
     /// Delete a note
     pub fn delete_note(&self, id: &str) -> Result<()> {
         let conn = self.conn.lock().unwrap();
+        delete_note_rows(&conn, id)
+    }
 
-        conn.execute("DELETE FROM note_tags WHERE note_id = ?1", params![id])?;
-        conn.execute("DELETE FROM chunks WHERE note_id = ?1", params![id])?;
-        conn.execute("DELETE FROM links WHERE source_note_id = ?1", params![id])?;
-        conn.execute("DELETE FROM notes WHERE id = ?1", params![id])?;
-
+    /// Delete a note, its access history, read positions, stars,
+    /// annotations and search feedback in one transaction
+    pub fn purge_note(&self, id: &str) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        delete_note_rows(&tx, id)?;
+        tx.execute("DELETE FROM note_access WHERE note_id = ?1", params![id])?;
+        tx.execute("DELETE FROM read_positions WHERE note_id = ?1", params![id])?;
+        tx.execute("DELETE FROM starred_notes WHERE note_id = ?1", params![id])?;
+        tx.execute("DELETE FROM annotations WHERE note_id = ?1", params![id])?;
+        tx.execute("DELETE FROM search_feedback WHERE note_id = ?1", params![id])?;
+        tx.commit()?;
         Ok(())
     }
 }
+
+fn delete_note_rows(conn: &Connection, id: &str) -> Result<()> {
+    // Links from other notes become unresolved rather than dangling
+    conn.execute(
+        "UPDATE links SET target_note_id = NULL WHERE target_note_id = ?1",
+        params![id],
+    )?;
+    let tag_ids = note_tag_ids(conn, id)?;
+    conn.execute("DELETE FROM note_tags WHERE note_id = ?1", params![id])?;
+    recount_tags(conn, &tag_ids)?;
+    conn.execute("DELETE FROM chunks WHERE note_id = ?1", params![id])?;
+    conn.execute("DELETE FROM links WHERE source_note_id = ?1", params![id])?;
+    conn.execute("DELETE FROM note_attachments WHERE note_id = ?1", params![id])?;
+    conn.execute("DELETE FROM highlights WHERE note_id = ?1", params![id])?;
+    conn.execute("DELETE FROM notes WHERE id = ?1", params![id])?;
+
+    Ok(())
+}
+
+fn upsert_note(conn: &Connection, note: &Note) -> Result<()> {
+    conn.execute(
+        r#"
+        INSERT INTO notes (id, title, slug, file_path, content_hash, created_at, updated_at, accessed_at, is_pinned, is_archived, is_deleted, deleted_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        ON CONFLICT(id) DO UPDATE SET
+            title = excluded.title,
+            slug = excluded.slug,
+            file_path = excluded.file_path,
+            content_hash = excluded.content_hash,
+            updated_at = excluded.updated_at,
+            accessed_at = excluded.accessed_at,
+            is_pinned = excluded.is_pinned,
+            is_archived = excluded.is_archived,
+            is_deleted = excluded.is_deleted,
+            deleted_at = excluded.deleted_at
+        "#,
+        params![
+            note.id.to_string(),
+            note.title,
+            note.slug,
+            note.file_path.to_string_lossy().to_string(),
+            note.content_hash,
+            note.created_at.to_rfc3339(),
+            note.updated_at.to_rfc3339(),
+            note.accessed_at.to_rfc3339(),
+            note.is_pinned,
+            note.is_archived,
+            note.is_deleted,
+            note.deleted_at.map(|dt| dt.to_rfc3339()),
+        ],
+    )?;
+
+    // Replace the note's tags, recounting the ones it gained or lost
+    let id = note.id.to_string();
+    let mut tag_ids = note_tag_ids(conn, &id)?;
+    conn.execute("DELETE FROM note_tags WHERE note_id = ?1", params![id])?;
+    for tag in note.tags() {
+        ensure_tag(conn, &tag)?;
+        link_note_tag(conn, &id, &tag)?;
+    }
+    tag_ids.extend(note_tag_ids(conn, &id)?);
+    recount_tags(conn, &tag_ids)?;
+
+    Ok(())
+}
+
+/// IDs of the tags a note carries
+fn note_tag_ids(conn: &Connection, note_id: &str) -> Result<Vec<String>> {
+    let ids = conn
+        .prepare("SELECT tag_id FROM note_tags WHERE note_id = ?1")?
+        .query_map(params![note_id], |row| row.get(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(ids)
+}
+
+/// Bring the note counts of tags up to date
+fn recount_tags(conn: &Connection, tag_ids: &[String]) -> Result<()> {
+    let mut stmt = conn.prepare(
+        r#"
+        UPDATE tags SET note_count = (
+            SELECT COUNT(*) FROM note_tags WHERE tag_id = tags.id
+        ) WHERE id = ?1
+        "#,
+    )?;
+    for id in tag_ids {
+        stmt.execute(params![id])?;
+    }
+    Ok(())
+}
+
+/// Ensure a tag exists
+fn ensure_tag(conn: &Connection, tag: &str) -> Result<()> {
+    let tag_lower = tag.to_lowercase();
+
+    conn.execute(
+        r#"
+        INSERT INTO tags (id, name, display_name, source, note_count)
+        VALUES (?1, ?2, ?3, 'Manual', 0)
+        ON CONFLICT(name) DO UPDATE SET display_name = excluded.display_name
+        "#,
+        params![uuid::Uuid::new_v4().to_string(), tag_lower, tag],
+    )?;
+
+    Ok(())
+}
+
+/// Link a note to a tag
+fn link_note_tag(conn: &Connection, note_id: &str, tag: &str) -> Result<()> {
+    let tag_lower = tag.to_lowercase();
+
+    conn.execute(
+        r#"
+        INSERT INTO note_tags (note_id, tag_id)
+        SELECT ?1, id FROM tags WHERE name = ?2
+        ON CONFLICT DO NOTHING
+        "#,
+        params![note_id, tag_lower],
+    )?;
+
+    Ok(())
+}
+
+fn insert_links(conn: &Connection, links: &[Link]) -> Result<()> {
+    let mut stmt = conn.prepare(
+        r#"
+        INSERT INTO links (id, source_note_id, target_note_id, target_raw, link_type, position, relation)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        "#,
+    )?;
+
+    for link in links {
+        stmt.execute(params![
+            link.id.to_string(),
+            link.source_note_id.to_string(),
+            link.target_note_id.map(|id| id.to_string()),
+            link.target_raw,
+            link.link_type.as_str(),
+            link.position,
+            link.relation.map(|r| r.as_str()),
+        ])?;
+    }
+
+    Ok(())
+}
+
+fn insert_attachments(conn: &Connection, note_id: uuid::Uuid, filenames: &[String]) -> Result<()> {
+    let mut stmt = conn.prepare(
+        r#"
+        INSERT INTO note_attachments (note_id, filename)
+        VALUES (?1, ?2)
+        ON CONFLICT DO NOTHING
+        "#,
+    )?;
+
+    for filename in filenames {
+        stmt.execute(params![note_id.to_string(), filename])?;
+    }
+
+    Ok(())
+}
+
+fn insert_highlights(conn: &Connection, note_id: uuid::Uuid, highlights: &[(usize, String)]) -> Result<()> {
+    let mut stmt = conn.prepare("INSERT INTO highlights (note_id, line, text) VALUES (?1, ?2, ?3)")?;
+
+    for (line, text) in highlights {
+        stmt.execute(params![note_id.to_string(), *line as i64, text])?;
+    }
+
+    Ok(())
+}
+
+/// Map a `links` row, skipping rows with unparseable IDs or link types
+fn row_to_link(row: &rusqlite::Row) -> rusqlite::Result<Option<Link>> {
+    let id: String = row.get(0)?;
+    let source: String = row.get(1)?;
+    let target: Option<String> = row.get(2)?;
+    let link_type: String = row.get(4)?;
+
+    let (Ok(id), Ok(source_note_id), Some(link_type)) = (
+        id.parse(),
+        source.parse(),
+        LinkType::parse(&link_type),
+    ) else {
+        return Ok(None);
+    };
+
+    Ok(Some(Link {
+        id,
+        source_note_id,
+        target_note_id: target.and_then(|t| t.parse().ok()),
+        target_raw: row.get(3)?,
+        link_type,
+        position: row.get(5)?,
+        relation: row
+            .get::<_, Option<String>>(6)?
+            .and_then(|r| RelationKind::parse(&r)),
+    }))
+}
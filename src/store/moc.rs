@@ -0,0 +1,153 @@
+//! Maps of content
+//!
+//! A map of content (MOC) is a note linking to every note on a topic, grouped
+//! by folder, each with a one-line summary. The generated list sits between
+//! [`BEGIN`] and [`END`] markers: regenerating replaces only what's between
+//! them, so text written around the list is kept, and a list that hasn't
+//! changed leaves the note untouched.
+
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+/// Marker opening the generated list
+pub const BEGIN: &str = "<!-- moc:begin -->";
+/// Marker closing the generated list
+pub const END: &str = "<!-- moc:end -->";
+
+/// Tag given to new maps of content
+pub const MOC_TAG: &str = "moc";
+
+/// Longest summary, in characters
+const SUMMARY_CHARS: usize = 120;
+
+/// A note listed in a map of content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MocEntry {
+    pub title: String,
+    /// Folder of the note relative to the notes directory, `""` at the top
+    pub folder: String,
+    pub summary: Option<String>,
+}
+
+/// The generated list, markers included: top-level notes first, then a
+/// section per folder, each sorted by title
+pub fn render(entries: &[MocEntry]) -> String {
+    let mut entries: Vec<&MocEntry> = entries.iter().collect();
+    entries.sort_by_key(|e| (e.folder.to_lowercase(), e.title.to_lowercase()));
+
+    let mut block = format!("{}\n", BEGIN);
+    let mut folder = None;
+    for entry in entries {
+        if folder != Some(entry.folder.as_str()) {
+            if !entry.folder.is_empty() {
+                if folder.is_some() {
+                    block.push('\n');
+                }
+                block.push_str(&format!("## {}\n\n", entry.folder));
+            }
+            folder = Some(entry.folder.as_str());
+        }
+        match &entry.summary {
+            Some(summary) => block.push_str(&format!("- [[{}]] — {}\n", entry.title, summary)),
+            None => block.push_str(&format!("- [[{}]]\n", entry.title)),
+        }
+    }
+    block.push_str(END);
+    block
+}
+
+/// `content` with its generated list replaced by `block`, or with `block`
+/// appended when it has none yet
+pub fn splice(content: &str, block: &str) -> String {
+    if let Some(start) = content.find(BEGIN) {
+        if let Some(len) = content[start..].find(END) {
+            let end = start + len + END.len();
+            return format!("{}{}{}", &content[..start], block, &content[end..]);
+        }
+    }
+    format!("{}\n\n{}\n", content.trim_end(), block)
+}
+
+/// The first paragraph of a note body as one line of plain text, shortened
+/// to [`SUMMARY_CHARS`]
+pub fn summary(body: &str) -> Option<String> {
+    let mut text = String::new();
+    let mut in_paragraph = false;
+    for event in Parser::new(body) {
+        match event {
+            Event::Start(Tag::Paragraph) => in_paragraph = true,
+            Event::End(TagEnd::Paragraph) if !text.trim().is_empty() => break,
+            Event::End(TagEnd::Paragraph) => in_paragraph = false,
+            Event::Text(t) | Event::Code(t) if in_paragraph => text.push_str(&t),
+            Event::SoftBreak | Event::HardBreak if in_paragraph => text.push(' '),
+            _ => {}
+        }
+    }
+
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let text = match text.char_indices().nth(SUMMARY_CHARS) {
+        Some((i, _)) => format!("{}…", text[..i].trim_end()),
+        None => text,
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(title: &str, folder: &str, summary: Option<&str>) -> MocEntry {
+        MocEntry {
+            title: title.to_string(),
+            folder: folder.to_string(),
+            summary: summary.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_render_groups_by_folder() {
+        let block = render(&[
+            entry("Tokio", "rust/async", Some("The runtime")),
+            entry("Ownership", "", None),
+            entry("Borrowing", "", Some("Shared and mutable references")),
+            entry("Pin", "rust/async", None),
+        ]);
+        assert_eq!(
+            block,
+            concat!(
+                "<!-- moc:begin -->\n",
+                "- [[Borrowing]] — Shared and mutable references\n",
+                "- [[Ownership]]\n",
+                "\n",
+                "## rust/async\n",
+                "\n",
+                "- [[Pin]]\n",
+                "- [[Tokio]] — The runtime\n",
+                "<!-- moc:end -->",
+            )
+        );
+    }
+
+    #[test]
+    fn test_splice_keeps_surrounding_text() {
+        let block = format!("{}\n- [[A]]\n{}", BEGIN, END);
+        let created = splice("# Rust\n\nMy intro.", &block);
+        assert_eq!(created, format!("# Rust\n\nMy intro.\n\n{}\n", block));
+
+        let refreshed_block = format!("{}\n- [[A]]\n- [[B]]\n{}", BEGIN, END);
+        let refreshed = splice(&format!("{}\nOutro.\n", created), &refreshed_block);
+        assert_eq!(refreshed, format!("# Rust\n\nMy intro.\n\n{}\n\nOutro.\n", refreshed_block));
+        assert_eq!(splice(&refreshed, &refreshed_block), refreshed);
+    }
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(
+            summary("# Title\n\nFirst *paragraph*\nwraps `here`.\n\nSecond."),
+            Some("First paragraph wraps here.".to_string())
+        );
+        assert_eq!(summary("# Only a heading\n\n```\ncode\n```"), None);
+        let long = summary(&"word ".repeat(50)).unwrap();
+        assert!(long.ends_with('…'));
+        assert_eq!(long.chars().count(), SUMMARY_CHARS);
+    }
+}
@@ -3,7 +3,45 @@
 mod note_store;
 mod metadata_db;
 mod manifest;
+mod title_index;
+mod links;
+mod filename;
+mod vector_store;
+mod sqlite_vectors;
+mod trash;
+mod checksums;
+mod users;
+mod history;
+mod templates;
+mod attachments;
+mod transclusion;
+mod canvases;
+mod boards;
+mod moc;
+mod highlights;
+mod journal;
 
-pub use note_store::NoteStore;
+pub use note_store::{frontmatter_tags, Contribution, MocOutcome, NoteChange, NoteStore, SyncConflict, VaultEvent};
 pub use metadata_db::MetadataDb;
-pub use manifest::{Manifest, ManifestEntry};
+pub use manifest::{Manifest, ManifestEntry, ManifestStats, MANIFEST_VERSION};
+pub use title_index::{TitleIndex, TitleMatch};
+pub use links::{extract_links, link_context};
+pub use filename::{
+    case_collisions, conflicted_copy_stem, is_case_sensitive, is_reserved, on_disk_spelling,
+    sanitize_file_stem, MAX_STEM_LEN,
+};
+pub use vector_store::{Compaction, VectorStore, VECTOR_STORE_VERSION};
+pub use trash::{Trash, TrashEntry};
+pub use checksums::ChecksumSnapshot;
+pub use users::{AccessLevel, Permissions, Scope, ScopeTarget, UserAccount, Users};
+pub use history::{GitHistory, HistoryEntry};
+pub use templates::{render as render_template, TemplateStore, TemplateVars};
+pub use attachments::{AttachmentInfo, AttachmentStore};
+pub use transclusion::heading_section;
+pub use canvases::{Canvas, CanvasEdge, CanvasInfo, CanvasNode, CanvasNodeType, CanvasStore};
+pub use boards::{
+    Board, BoardCard, BoardColumn, BoardItems, DEFAULT_BOARD_PROPERTY, DONE_COLUMN, TODO_COLUMN,
+};
+pub use moc::MOC_TAG;
+pub use highlights::extract_highlights;
+pub use journal::{FileWrite, Journal, Operation, Recovery};
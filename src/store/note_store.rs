@@ -1,46 +1,395 @@
 //! File-based note storage with manifest-based ID tracking
 
 use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use serde::Serialize;
+use tokio::sync::{broadcast, RwLock};
 
 use crate::config::Config;
 use crate::error::{Error, Result};
-use crate::types::{Frontmatter, Note};
-use super::manifest::Manifest;
+use crate::formatter::format_markdown;
+use crate::types::{
+    Annotation, Backlink, FeedbackKind, NoteSort, TermFeedback, Frontmatter, Highlight, Link, Note, NoteRelation, ReadPosition, RelationDirection,
+    RelationKind,
+};
+use super::checksums::ChecksumSnapshot;
+use super::history::{is_revision, GitHistory, HistoryEntry};
+use super::filename::{
+    case_collisions, conflicted_copy_stem, is_case_sensitive, on_disk_spelling, sanitize_file_stem,
+};
+use super::links::{extract_links, extract_relations, link_context};
+use super::manifest::{Manifest, ManifestEntry, ManifestStats};
+use super::metadata_db::MetadataDb;
+use super::users::Permissions;
+use super::title_index::TitleIndex;
+use super::trash::{Trash, TrashEntry};
+use super::templates::{render as render_template, TemplateStore, TemplateVars};
+use super::attachments::{embedded_attachments, is_referenced, AttachmentInfo, AttachmentStore};
+use super::transclusion::{heading_section, transclude};
+use super::vector_store::VectorStore;
+use super::canvases::CanvasStore;
+use super::boards;
+use super::moc::{self, MocEntry, MOC_TAG};
+use super::highlights::extract_highlights;
+use super::journal::{FileWrite, Journal};
+
+/// Notes opened within this many seconds of each other count as used together
+const CO_ACCESS_WINDOW_SECS: i64 = 30 * 60;
+
+/// Repeated opens of one note within this many seconds are logged once
+const ACCESS_DEDUPE_SECS: i64 = 60;
+
+/// How long note opens are kept in the access log
+const ACCESS_RETENTION_SECS: i64 = 90 * 24 * 60 * 60;
+
+/// A note change picked up from files edited outside the store
+#[derive(Debug, Clone)]
+pub enum NoteChange {
+    /// Note was created, edited or moved on disk
+    Updated(Box<Note>),
+    /// Note file was removed from disk
+    Removed(uuid::Uuid),
+}
+
+/// A change to the vault that live clients may want to refresh for
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum VaultEvent {
+    /// A note was created, restored from the trash or added on disk
+    NoteCreated { id: uuid::Uuid, title: String },
+    /// A note was added by quick capture
+    NoteCaptured { id: uuid::Uuid, title: String },
+    /// A note's content or metadata changed
+    NoteUpdated { id: uuid::Uuid, title: String },
+    /// A note was moved to the trash, deleted or removed from disk
+    NoteDeleted { id: uuid::Uuid },
+    /// A note's chunks were embedded for semantic search
+    NoteIndexed { id: uuid::Uuid, chunks: usize },
+}
+
+impl VaultEvent {
+    pub(crate) fn created(note: &Note) -> Self {
+        VaultEvent::NoteCreated {
+            id: note.id,
+            title: note.title.clone(),
+        }
+    }
+
+    pub(crate) fn updated(note: &Note) -> Self {
+        VaultEvent::NoteUpdated {
+            id: note.id,
+            title: note.title.clone(),
+        }
+    }
+
+    /// Name of the event, as in its serialized `type`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            VaultEvent::NoteCreated { .. } => "note_created",
+            VaultEvent::NoteCaptured { .. } => "note_captured",
+            VaultEvent::NoteUpdated { .. } => "note_updated",
+            VaultEvent::NoteDeleted { .. } => "note_deleted",
+            VaultEvent::NoteIndexed { .. } => "note_indexed",
+        }
+    }
+
+    /// The note the event is about
+    pub fn note_id(&self) -> uuid::Uuid {
+        match self {
+            VaultEvent::NoteCreated { id, .. }
+            | VaultEvent::NoteCaptured { id, .. }
+            | VaultEvent::NoteUpdated { id, .. }
+            | VaultEvent::NoteDeleted { id }
+            | VaultEvent::NoteIndexed { id, .. } => *id,
+        }
+    }
+}
+
+/// A map of content written by [`NoteStore::generate_moc`]
+#[derive(Debug, Clone)]
+pub struct MocOutcome {
+    pub note: Note,
+    /// Notes listed
+    pub listed: usize,
+    /// Whether the note was created rather than refreshed
+    pub created: bool,
+    /// Whether the note was written; a refresh listing the same notes
+    /// leaves it alone
+    pub changed: bool,
+}
+
+/// What a user is credited with by [`NoteStore::attribute`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Contribution {
+    /// Created the note (which also makes them its latest editor)
+    Created,
+    /// Made the note's latest change
+    Updated,
+}
+
+/// A note edited both through the store and on disk since the last checksum
+/// snapshot
+///
+/// The version that lost the note's path is kept as `copy`, a new
+/// `... (conflicted copy ...)` note next to the original.
+#[derive(Debug, Clone)]
+pub struct SyncConflict {
+    pub note_id: uuid::Uuid,
+    /// Path of the conflicting note, relative to the notes directory
+    pub path: PathBuf,
+    pub copy: Box<Note>,
+}
 
 /// File-based note storage with in-memory cache and manifest-based ID tracking
 pub struct NoteStore {
     config: Config,
     notes: Arc<RwLock<HashMap<uuid::Uuid, Note>>>,
     manifest: Arc<RwLock<Manifest>>,
+    title_index: Arc<RwLock<TitleIndex>>,
+    trash: Arc<RwLock<Trash>>,
+    checksums: Arc<RwLock<ChecksumSnapshot>>,
+    conflicts: broadcast::Sender<SyncConflict>,
+    events: broadcast::Sender<VaultEvent>,
+    metadata: MetadataDb,
+    /// Git history of the vault, when enabled in the config
+    history: Option<GitHistory>,
+    templates: TemplateStore,
+    attachments: AttachmentStore,
+    canvases: CanvasStore,
+    /// Intent log making multi-file changes crash-safe
+    journal: Journal,
+    /// Whether the vault's filesystem distinguishes `A.md` from `a.md`
+    case_sensitive: bool,
 }
 
 impl NoteStore {
     pub fn new(config: Config) -> Self {
+        // Finish changes cut short by a crash before reading any state they touch
+        let journal = Journal::new(config.vault_path.clone(), config.journal_path());
+        match journal.recover() {
+            Ok(recovery) if recovery.replayed + recovery.rolled_back > 0 => tracing::info!(
+                "Recovered interrupted operations: {} finished, {} undone",
+                recovery.replayed,
+                recovery.rolled_back
+            ),
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Failed to recover interrupted operations: {}", e),
+        }
+
         // Load or create manifest
         let manifest_path = config.data_dir().join("manifest.json");
-        let manifest = Manifest::load(&manifest_path).unwrap_or_default();
+        let manifest = Manifest::load(&manifest_path).unwrap_or_else(|e| {
+            // Keep the unreadable file rather than overwrite it on the next save
+            let backup = manifest_path.with_extension("json.unreadable");
+            tracing::warn!("{}; starting a new manifest, the old one is kept as {}", e, backup.display());
+            if let Err(e) = std::fs::rename(&manifest_path, &backup) {
+                tracing::warn!("Failed to keep the unreadable manifest: {}", e);
+            }
+            Manifest::default()
+        });
+        let trash = Trash::load(&config.trash_path().join("index.json")).unwrap_or_default();
+        let checksums = ChecksumSnapshot::load(&config.checksums_path()).unwrap_or_default();
+
+        let metadata = MetadataDb::open(&config.db_path()).unwrap_or_else(|e| {
+            tracing::warn!("Failed to open metadata database, links won't persist: {}", e);
+            MetadataDb::open_in_memory().expect("in-memory SQLite database")
+        });
+        // Vaults from before the database kept note metadata only have the
+        // manifest; seed the database from it until the notes are loaded
+        if manifest.entries().next().is_some() && metadata.is_empty().unwrap_or(false) {
+            match metadata.import_manifest(&manifest) {
+                Ok(count) => tracing::info!("Imported {} notes from the manifest into the metadata database", count),
+                Err(e) => tracing::warn!("Failed to import the manifest into the metadata database: {}", e),
+            }
+        }
+
+        let case_sensitive = detect_case_sensitivity(&config);
+        let history = config
+            .history
+            .enabled
+            .then(|| GitHistory::new(config.history_path(), config.vault_path.clone()));
+        let templates = TemplateStore::new(config.templates_path());
+        let attachments = AttachmentStore::new(config.attachments_path());
+        let canvases = CanvasStore::new(config.notes_path());
 
         Self {
             config,
             notes: Arc::new(RwLock::new(HashMap::new())),
             manifest: Arc::new(RwLock::new(manifest)),
+            title_index: Arc::new(RwLock::new(TitleIndex::new())),
+            trash: Arc::new(RwLock::new(trash)),
+            checksums: Arc::new(RwLock::new(checksums)),
+            conflicts: broadcast::channel(64).0,
+            events: broadcast::channel(256).0,
+            metadata,
+            history,
+            templates,
+            attachments,
+            canvases,
+            journal,
+            case_sensitive,
+        }
+    }
+
+    /// The vault's note templates
+    pub fn templates(&self) -> &TemplateStore {
+        &self.templates
+    }
+
+    /// The vault's attachments
+    pub fn attachments(&self) -> &AttachmentStore {
+        &self.attachments
+    }
+
+    /// The vault's canvases
+    pub fn canvases(&self) -> &CanvasStore {
+        &self.canvases
+    }
+
+    /// Attachments that no note or template mentions, counting notes in
+    /// the trash
+    pub async fn orphaned_attachments(&self) -> Result<Vec<AttachmentInfo>> {
+        let mut texts: Vec<String> = self
+            .notes
+            .read()
+            .await
+            .values()
+            .map(|n| n.content.clone())
+            .collect();
+        texts.extend(self.trash_and_template_texts(None).await?);
+        self.attachments
+            .unreferenced(texts.iter().map(String::as_str))
+            .await
+    }
+
+    /// File names of the attachments a live note embeds
+    pub async fn note_attachments(&self, id: uuid::Uuid) -> Result<Vec<String>> {
+        if self.notes.read().await.get(&id).is_none_or(|n| n.is_deleted) {
+            return Err(Error::NoteNotFound(id.to_string()));
+        }
+        self.metadata.get_note_attachments(id)
+    }
+
+    /// Live notes that embed the attachment `filename`
+    pub async fn attachment_notes(&self, filename: &str) -> Result<Vec<Note>> {
+        let ids = self.metadata.get_attachment_notes(filename)?;
+        let notes = self.notes.read().await;
+        Ok(ids
+            .iter()
+            .filter_map(|id| id.parse::<uuid::Uuid>().ok())
+            .filter_map(|id| notes.get(&id))
+            .filter(|n| !n.is_deleted)
+            .cloned()
+            .collect())
+    }
+
+    /// Delete those of `filenames` that nothing but note `id` uses
+    ///
+    /// Meant for a note's attachments once the note itself is deleted.
+    /// Attachments another live note embeds, or that a template or another
+    /// trashed note mentions, are kept. Returns the deleted file names.
+    pub async fn delete_unshared_attachments(
+        &self,
+        id: uuid::Uuid,
+        filenames: &[String],
+    ) -> Result<Vec<String>> {
+        let own_id = id.to_string();
+        let texts = self.trash_and_template_texts(Some(id)).await?;
+        let mut deleted = Vec::new();
+        for filename in filenames {
+            let shared = self
+                .metadata
+                .get_attachment_notes(filename)?
+                .iter()
+                .any(|note_id| *note_id != own_id)
+                || texts.iter().any(|text| is_referenced(text, filename));
+            if shared {
+                continue;
+            }
+            match self.attachments.delete(filename).await {
+                Ok(()) => deleted.push(filename.clone()),
+                // Already gone
+                Err(Error::AttachmentNotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Contents of the trashed notes, other than `except`, and of the templates
+    async fn trash_and_template_texts(&self, except: Option<uuid::Uuid>) -> Result<Vec<String>> {
+        let mut texts = Vec::new();
+        for entry in self.list_trash().await {
+            if Some(entry.id) == except {
+                continue;
+            }
+            let path = self.trash_file_path(entry.id);
+            match tokio::fs::read_to_string(&path).await {
+                Ok(content) => texts.push(content),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        for name in self.templates.list().await? {
+            texts.push(self.templates.get(&name).await?);
+        }
+        Ok(texts)
+    }
+
+    /// Attachments a note embeds, resolved from where its file lives
+    fn attachments_of(&self, note: &Note) -> Vec<String> {
+        let full_path = self.config.notes_path().join(&note.file_path);
+        let note_dir = full_path.parent().unwrap_or(&self.config.vault_path);
+        embedded_attachments(&note.content, note_dir, &self.config.attachments_path())
+    }
+
+    /// Note content as saved, formatted if `[format]` is enabled
+    fn formatted(&self, content: String) -> String {
+        if self.config.format.enabled {
+            format_markdown(&content, &self.config.format)
+        } else {
+            content
         }
     }
 
+    /// Whether note file names are case-sensitive on this vault's filesystem
+    pub fn is_case_sensitive(&self) -> bool {
+        self.case_sensitive
+    }
+
     /// Get the manifest path
     fn manifest_path(&self) -> PathBuf {
         self.config.data_dir().join("manifest.json")
     }
 
     /// Save the manifest to disk
-    async fn save_manifest(&self) -> Result<()> {
+    pub(crate) async fn save_manifest(&self) -> Result<()> {
         let manifest = self.manifest.read().await;
         manifest.save(&self.manifest_path())
     }
 
+    /// Get the trash index path
+    fn trash_index_path(&self) -> PathBuf {
+        self.config.trash_path().join("index.json")
+    }
+
+    /// Journaled rewrite of the trash index to `trash`
+    fn trash_index_write(&self, trash: &Trash) -> Result<FileWrite> {
+        let path = self.trash_index_path();
+        let before = match std::fs::read_to_string(&path) {
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        Ok(FileWrite::new(path, before, Some(trash.to_json()?)))
+    }
+
+    /// Where a deleted note's file is kept
+    fn trash_file_path(&self, id: uuid::Uuid) -> PathBuf {
+        self.config.trash_path().join(format!("{}.md", id))
+    }
+
     /// Load all notes from disk
     pub async fn load_all(&self) -> Result<Vec<Note>> {
         let notes_path = self.config.notes_path();
@@ -66,9 +415,49 @@ impl NoteStore {
             cache.insert(note.id, note.clone());
         }
 
+        for group in case_collisions(notes.iter().map(|n| n.file_path.as_path())) {
+            tracing::warn!(
+                "Notes {:?} differ only by letter case and will collide on case-insensitive filesystems",
+                group
+            );
+        }
+
+        // Rebuild the title index from the refreshed cache
+        {
+            let mut title_index = self.title_index.write().await;
+            title_index.clear();
+            for note in cache.values().filter(|n| !n.is_deleted) {
+                title_index.insert_with_aliases(note.id, &note.title, note.aliases());
+            }
+        }
+
+        // Rebuild the link index from the refreshed cache
+        let live: Vec<&Note> = cache.values().filter(|n| !n.is_deleted).collect();
+        let names = name_index(&cache);
+        let links: Vec<Link> = live.iter().flat_map(|n| resolved_links(n, &names)).collect();
+        let attachments: Vec<(uuid::Uuid, Vec<String>)> =
+            live.iter().map(|n| (n.id, self.attachments_of(n))).collect();
+        let highlights: Vec<(uuid::Uuid, Vec<(usize, String)>)> =
+            live.iter().map(|n| (n.id, extract_highlights(&n.content))).collect();
+        let live_ids: Vec<uuid::Uuid> = live.iter().map(|n| n.id).collect();
+        if let Err(e) = self
+            .metadata
+            .retain_notes(&live_ids)
+            .and_then(|_| self.metadata.upsert_notes(&live))
+            .and_then(|_| self.metadata.replace_all_links(&links))
+            .and_then(|_| self.metadata.replace_all_attachments(&attachments))
+            .and_then(|_| self.metadata.replace_all_highlights(&highlights))
+        {
+            tracing::warn!("Failed to store links: {}", e);
+        }
+        drop(cache);
+
         // Save manifest after loading
         self.save_manifest().await?;
 
+        // The cache now matches disk, which is the baseline for conflicts
+        self.snapshot_checksums().await?;
+
         Ok(notes)
     }
 
@@ -135,19 +524,23 @@ impl NoteStore {
         let content_hash = compute_hash(&content);
 
         // Get or create stable ID and retrieve persisted timestamps from manifest
-        let (id, persisted_created_at, persisted_updated_at) = {
+        let (id, persisted_created_at, persisted_updated_at, created_by, updated_by) = {
             let mut manifest = self.manifest.write().await;
             let id = manifest.get_or_create_id(&relative_path, &content_hash);
             let entry = manifest.get_entry(&relative_path);
             let created_at = entry.and_then(|e| e.created_at);
             let updated_at = entry.and_then(|e| e.updated_at);
-            (id, created_at, updated_at)
+            let created_by = entry.and_then(|e| e.created_by.clone());
+            let updated_by = entry.and_then(|e| e.updated_by.clone());
+            (id, created_at, updated_at, created_by, updated_by)
         };
 
         let mut note = Note::new(title, content.clone(), relative_path);
         note.id = id;
         note.content_hash = content_hash;
         note.frontmatter = frontmatter;
+        note.created_by = created_by;
+        note.updated_by = updated_by;
 
         // Restore timestamps from manifest, falling back to file modification time
         let file_mtime = std::fs::metadata(path)
@@ -186,21 +579,23 @@ impl NoteStore {
         cache.get(&id).cloned()
     }
 
-    /// Get a note by title (fuzzy match)
-    pub async fn get_by_title(&self, title: &str) -> Option<Note> {
-        let cache = self.notes.read().await;
-        let title_lower = title.to_lowercase();
-
-        // Exact match first
-        if let Some(note) = cache.values().find(|n| n.title.to_lowercase() == title_lower) {
-            return Some(note.clone());
-        }
+    /// Get a note by title or alias (exact match first, then best fuzzy
+    /// match), along with the alias that matched if it wasn't the title
+    pub async fn get_by_title(&self, title: &str) -> Option<(Note, Option<String>)> {
+        let best = self.title_index.read().await.search(title, 1).into_iter().next()?;
+        let note = self.notes.read().await.get(&best.id).cloned()?;
+        Some((note, best.alias))
+    }
 
-        // Fuzzy match
-        cache
-            .values()
-            .find(|n| n.title.to_lowercase().contains(&title_lower))
-            .cloned()
+    /// Rank notes by title similarity, best match first
+    #[tracing::instrument(skip(self))]
+    pub async fn search_titles(&self, query: &str, limit: usize) -> Vec<(Note, f32)> {
+        let matches = self.title_index.read().await.search(query, limit);
+        let cache = self.notes.read().await;
+        matches
+            .into_iter()
+            .filter_map(|m| cache.get(&m.id).map(|note| (note.clone(), m.score)))
+            .collect()
     }
 
     /// Get all notes
@@ -209,12 +604,16 @@ impl NoteStore {
         cache.values().cloned().collect()
     }
 
-    /// Get notes with pagination
+    /// Get notes with pagination, in `sort` order, optionally only those with
+    /// a tag or by an author
+    #[tracing::instrument(skip_all)]
     pub async fn list_paginated(
         &self,
         offset: usize,
         limit: usize,
         tag: Option<&str>,
+        author: Option<&str>,
+        sort: NoteSort,
     ) -> Vec<Note> {
         let cache = self.notes.read().await;
         let mut notes: Vec<_> = cache
@@ -227,25 +626,50 @@ impl NoteStore {
                     true
                 }
             })
+            .filter(|n| author.is_none_or(|a| n.is_authored_by(a)))
             .cloned()
             .collect();
 
-        // Sort by updated_at descending
-        notes.sort_by_key(|n| std::cmp::Reverse(n.updated_at));
+        notes.sort_by(|a, b| sort.compare(a, b));
 
         notes.into_iter().skip(offset).take(limit).collect()
     }
 
     /// Create a new note
     pub async fn create(&self, title: String, content: String, tags: Option<Vec<String>>) -> Result<Note> {
-        let slug = slug::slugify(&title);
-        let filename = format!("{}.md", slug);
-        let file_path = PathBuf::from(&filename);
+        self.create_in(Path::new(""), title, content, tags).await
+    }
+
+    /// Create a new note in `folder`, relative to the notes directory
+    #[tracing::instrument(skip_all, fields(title = %title))]
+    pub async fn create_in(
+        &self,
+        folder: &Path,
+        title: String,
+        content: String,
+        tags: Option<Vec<String>>,
+    ) -> Result<Note> {
+        if !folder.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(Error::InvalidNotePath(folder.display().to_string()));
+        }
+
+        let filename = format!("{}.md", sanitize_file_stem(&slug::slugify(&title)));
+        let file_path = folder.join(&filename);
         let full_path = self.config.notes_path().join(&file_path);
 
         if full_path.exists() {
             return Err(Error::NoteAlreadyExists(title));
         }
+        if let Some(existing) = self.manifest.read().await.find_case_variant(&file_path) {
+            if !self.case_sensitive {
+                return Err(Error::NoteAlreadyExists(title));
+            }
+            tracing::warn!(
+                "{:?} differs from existing {:?} only by letter case; they will collide on case-insensitive filesystems",
+                file_path,
+                existing
+            );
+        }
 
         // Build frontmatter if tags provided
         let mut note_content = String::new();
@@ -257,6 +681,7 @@ impl NoteStore {
             }
         }
         note_content.push_str(&content);
+        let note_content = self.formatted(note_content);
 
         let content_hash = compute_hash(&note_content);
 
@@ -267,6 +692,9 @@ impl NoteStore {
         };
 
         // Write to disk
+        if let Some(parent) = full_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
         tokio::fs::write(&full_path, &note_content).await?;
 
         // Create note object with the stable ID
@@ -278,29 +706,69 @@ impl NoteStore {
                 tags,
                 ..Default::default()
             });
+        } else {
+            note.frontmatter = parse_frontmatter(&note.content).0;
         }
 
         // Update cache
+        self.title_index.write().await.insert_with_aliases(note.id, &note.title, note.aliases());
         let mut cache = self.notes.write().await;
         cache.insert(note.id, note.clone());
+        self.index_links(&note, &cache);
+        drop(cache);
 
         // Save manifest
         self.save_manifest().await?;
 
+        self.record_history(&format!("Create {}", note.title)).await;
+        self.notify(VaultEvent::created(&note));
+
         Ok(note)
     }
 
+    /// Create a note in `folder` from the template called `template`
+    ///
+    /// The template's placeholders are filled in with the title, today's date
+    /// and `clipboard`; frontmatter in the template (e.g. tags) is kept.
+    pub async fn create_from_template(
+        &self,
+        template: &str,
+        folder: &Path,
+        title: String,
+        clipboard: Option<String>,
+    ) -> Result<Note> {
+        let template = self.templates.get(template).await?;
+        let content = render_template(
+            &template,
+            &TemplateVars {
+                title: title.clone(),
+                date: chrono::Local::now().date_naive(),
+                clipboard: clipboard.unwrap_or_default(),
+            },
+        );
+        self.create_in(folder, title, content, None).await
+    }
+
     /// Update a note's content
+    #[tracing::instrument(skip(self, content))]
     pub async fn update(&self, id: uuid::Uuid, content: String) -> Result<Note> {
+        let content = self.formatted(content);
         let mut cache = self.notes.write().await;
+        self.preserve_external_edit(&mut cache, id).await?;
 
         let note = cache
             .get_mut(&id)
             .ok_or_else(|| Error::NoteNotFound(id.to_string()))?;
 
         note.content = content.clone();
+        note.frontmatter = parse_frontmatter(&content).0;
         note.updated_at = chrono::Utc::now();
+        note.updated_by = None;
         note.content_hash = compute_hash(&content);
+        self.title_index
+            .write()
+            .await
+            .insert_with_aliases(note.id, &note.title, note.aliases());
 
         // Update manifest hash and timestamps
         {
@@ -314,14 +782,101 @@ impl NoteStore {
         tokio::fs::write(&full_path, &content).await?;
 
         let result = note.clone();
+        self.index_links(&result, &cache);
         drop(cache);
 
         self.save_manifest().await?;
 
+        self.record_history(&format!("Update {}", result.title)).await;
+        self.notify(VaultEvent::updated(&result));
+
         Ok(result)
     }
 
+    /// Set a frontmatter property of a note, leaving the rest of the file as it is
+    pub async fn set_property(&self, id: uuid::Uuid, property: &str, value: &str) -> Result<Note> {
+        let note = self
+            .get(id)
+            .await
+            .ok_or_else(|| Error::NoteNotFound(id.to_string()))?;
+        let content = boards::set_property(&note.content, property, value)?;
+        self.update(id, content).await
+    }
+
+    /// Tick or untick the task on `line` of a note
+    pub async fn set_task(&self, id: uuid::Uuid, line: usize, done: bool) -> Result<Note> {
+        let note = self
+            .get(id)
+            .await
+            .ok_or_else(|| Error::NoteNotFound(id.to_string()))?;
+        let content = boards::set_task(&note.content, line, done)?;
+        self.update(id, content).await
+    }
+
+    /// Create or refresh the map of content titled `title`, listing the notes
+    /// tagged `tag` that `can_read` accepts
+    ///
+    /// An existing note with that title keeps everything outside its generated
+    /// list; a new one is created at the top of the notes directory, tagged
+    /// `moc`.
+    #[tracing::instrument(skip(self, can_read))]
+    pub async fn generate_moc(
+        &self,
+        tag: &str,
+        title: &str,
+        can_read: impl Fn(&Note) -> bool,
+    ) -> Result<MocOutcome> {
+        let tag = validate_tag(tag)?;
+        let (existing, entries) = {
+            let cache = self.notes.read().await;
+            let existing = cache
+                .values()
+                .find(|n| !n.is_deleted && n.title.eq_ignore_ascii_case(title))
+                .cloned();
+            let entries: Vec<MocEntry> = cache
+                .values()
+                .filter(|n| !n.is_deleted && !n.is_archived && can_read(n))
+                .filter(|n| existing.as_ref().is_none_or(|e| e.id != n.id))
+                .filter(|n| n.tags().iter().any(|t| t.eq_ignore_ascii_case(&tag)))
+                .map(|n| MocEntry {
+                    title: n.title.clone(),
+                    folder: n
+                        .file_path
+                        .parent()
+                        .map(|p| p.to_string_lossy().replace('\\', "/"))
+                        .unwrap_or_default(),
+                    summary: moc::summary(&parse_frontmatter(&n.content).1),
+                })
+                .collect();
+            (existing, entries)
+        };
+        if entries.is_empty() {
+            return Err(Error::TagNotFound(tag));
+        }
+        let block = moc::render(&entries);
+        let listed = entries.len();
+
+        match existing {
+            Some(note) => {
+                let content = moc::splice(&note.content, &block);
+                if content == note.content {
+                    return Ok(MocOutcome { note, listed, created: false, changed: false });
+                }
+                let note = self.update(note.id, content).await?;
+                Ok(MocOutcome { note, listed, created: false, changed: true })
+            }
+            None => {
+                let content = format!("# {}\n\n{}\n", title, block);
+                let note = self
+                    .create(title.to_string(), content, Some(vec![MOC_TAG.to_string()]))
+                    .await?;
+                Ok(MocOutcome { note, listed, created: true, changed: true })
+            }
+        }
+    }
+
     /// Update a note with all fields
+    #[tracing::instrument(skip_all, fields(id = %id))]
     pub async fn update_full(
         &self,
         id: uuid::Uuid,
@@ -332,6 +887,7 @@ impl NoteStore {
         is_archived: Option<bool>,
     ) -> Result<Note> {
         let mut cache = self.notes.write().await;
+        self.preserve_external_edit(&mut cache, id).await?;
 
         let note = cache
             .get_mut(&id)
@@ -339,6 +895,10 @@ impl NoteStore {
 
         // Update fields if provided
         if let Some(new_title) = title {
+            self.title_index
+                .write()
+                .await
+                .insert_with_aliases(note.id, &new_title, note.aliases());
             note.title = new_title;
         }
         if let Some(pinned) = is_pinned {
@@ -373,29 +933,11 @@ impl NoteStore {
         };
 
         // Rebuild content with frontmatter
-        let mut new_file_content = String::new();
-        if let Some(ref fm) = note.frontmatter {
-            if !fm.tags.is_empty() || !fm.custom.is_empty() {
-                new_file_content.push_str("---\n");
-                if !fm.tags.is_empty() {
-                    new_file_content.push_str(&format!("tags: [{}]\n", fm.tags.join(", ")));
-                }
-                for (key, value) in &fm.custom {
-                    if key != "tags" {
-                        // Serialize the YAML value back to string
-                        if let Ok(yaml_str) = serde_yaml::to_string(value) {
-                            let yaml_str = yaml_str.trim();
-                            new_file_content.push_str(&format!("{}: {}\n", key, yaml_str));
-                        }
-                    }
-                }
-                new_file_content.push_str("---\n\n");
-            }
-        }
-        new_file_content.push_str(&body_content);
+        let new_file_content = self.formatted(file_content(note.frontmatter.as_ref(), &body_content));
 
         note.content = new_file_content.clone();
         note.updated_at = chrono::Utc::now();
+        note.updated_by = None;
         note.content_hash = compute_hash(&new_file_content);
 
         // Update manifest hash and timestamps
@@ -410,13 +952,125 @@ impl NoteStore {
         tokio::fs::write(&full_path, &new_file_content).await?;
 
         let result = note.clone();
+        self.index_links(&result, &cache);
         drop(cache);
 
         self.save_manifest().await?;
 
+        self.record_history(&format!("Update {}", result.title)).await;
+        self.notify(VaultEvent::updated(&result));
+
         Ok(result)
     }
 
+    /// Rename a tag on every note carrying it, returning the changed notes
+    ///
+    /// Tags are matched case-insensitively. Renaming onto a tag a note already
+    /// has leaves the note with a single copy of it.
+    pub async fn rename_tag(&self, from: &str, to: &str) -> Result<Vec<Note>> {
+        let to = validate_tag(to)?;
+        self.retag(&[from.to_string()], Some(&to), &format!("Rename tag {} to {}", from, to))
+            .await
+    }
+
+    /// Replace several tags with one on every note carrying any of them,
+    /// returning the changed notes
+    pub async fn merge_tags(&self, tags: &[String], into: &str) -> Result<Vec<Note>> {
+        let into = validate_tag(into)?;
+        self.retag(tags, Some(&into), &format!("Merge tags {} into {}", tags.join(", "), into))
+            .await
+    }
+
+    /// Remove a tag from every note carrying it, returning the changed notes
+    pub async fn delete_tag(&self, tag: &str) -> Result<Vec<Note>> {
+        self.retag(&[tag.to_string()], None, &format!("Delete tag {}", tag))
+            .await
+    }
+
+    /// Rewrite the frontmatter of every note tagged with any of `from`,
+    /// replacing those tags with `into` or dropping them
+    #[tracing::instrument(skip(self))]
+    async fn retag(&self, from: &[String], into: Option<&str>, message: &str) -> Result<Vec<Note>> {
+        let from: Vec<String> = from.iter().map(|t| t.trim().to_lowercase()).collect();
+        let matches = |tag: &str| from.contains(&tag.to_lowercase());
+        let is_into = |tag: &str| into.is_some_and(|into| into.to_lowercase() == tag.to_lowercase());
+
+        let mut cache = self.notes.write().await;
+        let ids: Vec<uuid::Uuid> = cache
+            .values()
+            .filter(|n| !n.is_deleted && n.tags().iter().any(|t| matches(t)))
+            .map(|n| n.id)
+            .collect();
+        if ids.is_empty() {
+            return Err(Error::TagNotFound(from.join(", ")));
+        }
+
+        let mut changed = Vec::with_capacity(ids.len());
+        let mut writes = Vec::with_capacity(ids.len());
+        for id in ids {
+            self.preserve_external_edit(&mut cache, id).await?;
+            let Some(mut note) = cache.get(&id).cloned() else {
+                continue;
+            };
+            let before = note.content.clone();
+
+            let fm = note.frontmatter.get_or_insert_with(Frontmatter::default);
+            let mut tags: Vec<String> = Vec::with_capacity(fm.tags.len());
+            for tag in fm.tags.drain(..) {
+                let tag = match (matches(&tag), into) {
+                    (false, _) => tag,
+                    (true, Some(into)) => into.to_string(),
+                    (true, None) => continue,
+                };
+                if is_into(&tag) && tags.iter().any(|t| is_into(t)) {
+                    continue;
+                }
+                tags.push(tag);
+            }
+            fm.tags = tags;
+
+            let (_, body) = parse_frontmatter(&note.content);
+            let content = file_content(note.frontmatter.as_ref(), &body);
+            note.content_hash = compute_hash(&content);
+            note.content = content;
+            note.updated_at = chrono::Utc::now();
+            note.updated_by = None;
+
+            writes.push(FileWrite::new(
+                self.config.notes_path().join(&note.file_path),
+                Some(before),
+                Some(note.content.clone()),
+            ));
+            changed.push(note);
+        }
+
+        // Every note is rewritten or none is
+        let op = self.journal.run(message, writes)?;
+        {
+            let mut manifest = self.manifest.write().await;
+            for note in &changed {
+                manifest.update_hash(&note.file_path, &note.content_hash);
+                manifest.update_timestamps(&note.file_path, note.updated_at);
+            }
+        }
+        for note in &changed {
+            cache.insert(note.id, note.clone());
+        }
+        for note in &changed {
+            self.index_links(note, &cache);
+        }
+        drop(cache);
+
+        self.save_manifest().await?;
+        self.journal.finish(&op)?;
+        self.record_history(message).await;
+        for note in &changed {
+            self.notify(VaultEvent::updated(note));
+        }
+
+        Ok(changed)
+    }
+
     /// Append content to a note
     pub async fn append(&self, id: uuid::Uuid, content: String) -> Result<Note> {
         let note = self
@@ -429,113 +1083,1219 @@ impl NoteStore {
     }
 
     /// Delete a note (soft delete)
+    #[tracing::instrument(skip(self))]
     pub async fn delete(&self, id: uuid::Uuid) -> Result<()> {
         let mut cache = self.notes.write().await;
 
         let note = cache
             .get_mut(&id)
+            .filter(|n| !n.is_deleted)
             .ok_or_else(|| Error::NoteNotFound(id.to_string()))?;
 
-        note.is_deleted = true;
-        note.deleted_at = Some(chrono::Utc::now());
-
-        // Move to trash folder
+        // Move to trash folder first: if that fails the note is left intact
         let full_path = self.config.notes_path().join(&note.file_path);
-        let trash_path = self.config.data_dir().join("trash").join(&note.file_path);
+        let content = tokio::fs::read_to_string(&full_path).await?;
+        let deleted_at = chrono::Utc::now();
+        let entry = self.manifest.read().await.get_entry(&note.file_path).cloned();
+
+        let mut trash = self.trash.write().await;
+        let mut updated = trash.clone();
+        updated.insert(TrashEntry {
+            id,
+            title: note.title.clone(),
+            original_path: note.file_path.clone(),
+            deleted_at,
+            created_at: entry.as_ref().and_then(|e| e.created_at),
+            updated_at: entry.as_ref().and_then(|e| e.updated_at),
+            created_by: entry.as_ref().and_then(|e| e.created_by.clone()),
+            updated_by: entry.as_ref().and_then(|e| e.updated_by.clone()),
+        });
+        let op = self.journal.run(
+            &format!("Delete {}", note.title),
+            vec![
+                FileWrite::new(self.trash_file_path(id), None, Some(content.clone())),
+                self.trash_index_write(&updated)?,
+                FileWrite::new(full_path, Some(content), None),
+            ],
+        )?;
+        *trash = updated;
+        drop(trash);
 
-        if let Some(parent) = trash_path.parent() {
-            tokio::fs::create_dir_all(parent).await?;
+        note.is_deleted = true;
+        note.deleted_at = Some(deleted_at);
+        let title = note.title.clone();
+        self.title_index.write().await.remove(id);
+        if let Err(e) = self.metadata.delete_note(&id.to_string()) {
+            tracing::warn!("Failed to remove links: {}", e);
         }
 
-        tokio::fs::rename(&full_path, &trash_path).await?;
+        // Release the path so a new note created there gets a fresh ID
+        self.manifest.write().await.remove(&note.file_path);
+        drop(cache);
+
+        self.save_manifest().await?;
+        self.journal.finish(&op)?;
+
+        self.record_history(&format!("Delete {}", title)).await;
+        self.notify(VaultEvent::NoteDeleted { id });
 
         Ok(())
     }
 
-    /// Quick capture to inbox
-    pub async fn quick_capture(&self, content: String, source: Option<String>) -> Result<Note> {
-        let now = chrono::Utc::now();
-        let title = format!("Capture {}", now.format("%Y-%m-%d %H:%M"));
-
-        let mut note_content = String::new();
-        note_content.push_str("---\n");
-        note_content.push_str(&format!("captured_at: {}\n", now.to_rfc3339()));
-        if let Some(source) = &source {
-            note_content.push_str(&format!("source: \"{}\"\n", source));
-        }
-        note_content.push_str("---\n\n");
-        note_content.push_str(&content);
+    /// Notes in the trash, most recently deleted first
+    pub async fn list_trash(&self) -> Vec<TrashEntry> {
+        self.trash.read().await.entries().into_iter().cloned().collect()
+    }
 
-        let slug = slug::slugify(&title);
-        let filename = format!("{}.md", slug);
-        let file_path = PathBuf::from("inbox").join(&filename);
-        let full_path = self.config.notes_path().join(&file_path);
+    /// A note in the trash
+    pub async fn get_trashed(&self, id: uuid::Uuid) -> Option<TrashEntry> {
+        self.trash.read().await.get(id).cloned()
+    }
 
-        // Ensure inbox exists
-        tokio::fs::create_dir_all(full_path.parent().unwrap()).await?;
+    /// Move a deleted note back to its original path, keeping its ID
+    ///
+    /// Fails with `NoteAlreadyExists` if another file now occupies that path.
+    pub async fn restore_from_trash(&self, id: uuid::Uuid) -> Result<Note> {
+        let entry = self
+            .trash
+            .read()
+            .await
+            .get(id)
+            .cloned()
+            .ok_or_else(|| Error::NoteNotFound(id.to_string()))?;
 
-        let content_hash = compute_hash(&note_content);
+        let full_path = self.config.notes_path().join(&entry.original_path);
+        if full_path.exists() || self.manifest.read().await.get_id(&entry.original_path).is_some() {
+            return Err(Error::NoteAlreadyExists(
+                entry.original_path.display().to_string(),
+            ));
+        }
 
-        // Get ID from manifest
-        let note_id = {
-            let mut manifest = self.manifest.write().await;
-            manifest.get_or_create_id(&file_path, &content_hash)
+        let trash_path = self.trash_file_path(id);
+        let content = tokio::fs::read_to_string(&trash_path).await?;
+        let mut trash = self.trash.write().await;
+        let mut updated = trash.clone();
+        updated.remove(id);
+        let index_write = self.trash_index_write(&updated)?;
+
+        // Re-register the path under the old ID before the file reappears
+        self.manifest.write().await.insert(
+            &entry.original_path,
+            ManifestEntry {
+                id,
+                content_hash: compute_hash(&content),
+                indexed_at: None,
+                indexed_hash: None,
+                created_at: entry.created_at,
+                updated_at: entry.updated_at,
+                created_by: entry.created_by.clone(),
+                updated_by: entry.updated_by.clone(),
+                extra: serde_json::Map::new(),
+            },
+        );
+        let op = self.journal.run(
+            &format!("Restore {}", entry.title),
+            vec![
+                FileWrite::new(&full_path, None, Some(content.clone())),
+                index_write,
+                FileWrite::new(trash_path, Some(content), None),
+            ],
+        );
+        let op = match op {
+            Ok(op) => op,
+            Err(e) => {
+                self.manifest.write().await.remove(&entry.original_path);
+                return Err(e);
+            }
         };
+        *trash = updated;
+        drop(trash);
 
-        // Write to disk
-        tokio::fs::write(&full_path, &note_content).await?;
-
-        // Create note object with the stable ID
-        let mut note = Note::new(title, note_content, file_path);
-        note.id = note_id;
-        note.content_hash = content_hash;
-
-        // Update cache
+        let note = self.load_note_from_file(&full_path).await?;
+        self.title_index.write().await.insert_with_aliases(note.id, &note.title, note.aliases());
         let mut cache = self.notes.write().await;
         cache.insert(note.id, note.clone());
+        self.index_links(&note, &cache);
+        drop(cache);
 
-        // Save manifest
         self.save_manifest().await?;
+        self.journal.finish(&op)?;
+
+        self.record_history(&format!("Restore {}", note.title)).await;
+        self.notify(VaultEvent::created(&note));
 
         Ok(note)
     }
 
-    /// Check which notes need re-indexing
-    pub async fn get_notes_needing_reindex(&self) -> Vec<Note> {
-        let cache = self.notes.read().await;
-        let manifest = self.manifest.read().await;
+    /// Permanently delete a note from the trash
+    pub async fn purge_from_trash(&self, id: uuid::Uuid) -> Result<()> {
+        let mut cache = self.notes.write().await;
+        let mut trash = self.trash.write().await;
+        if trash.get(id).is_none() {
+            return Err(Error::NoteNotFound(id.to_string()));
+        }
 
-        cache
-            .values()
-            .filter(|note| manifest.needs_reindex(&note.file_path, &note.content_hash))
-            .cloned()
-            .collect()
-    }
+        self.purge_vectors(id).await?;
+        remove_file_if_exists(&self.trash_file_path(id)).await?;
 
-    /// Mark a note as indexed
-    pub async fn mark_indexed(&self, id: uuid::Uuid) -> Result<()> {
-        let cache = self.notes.read().await;
-        if let Some(note) = cache.get(&id) {
-            let mut manifest = self.manifest.write().await;
-            manifest.mark_indexed(&note.file_path);
-            drop(manifest);
-            drop(cache);
-            self.save_manifest().await?;
+        trash.remove(id);
+        trash.save(&self.trash_index_path())?;
+        self.metadata.purge_note(&id.to_string())?;
+
+        if cache.get(&id).is_some_and(|n| n.is_deleted) {
+            cache.remove(&id);
         }
+
         Ok(())
     }
 
-    /// Get config reference
-    pub fn config(&self) -> &Config {
-        &self.config
-    }
-}
+    /// Permanently delete a note, live or trashed, skipping the trash
+    ///
+    /// Removes the note file, its manifest entry, its metadata database rows
+    /// (including links and access history) and its persisted embeddings.
+    /// Removing the note from the in-memory search indexes is up to the caller.
+    pub async fn delete_permanently(&self, id: uuid::Uuid) -> Result<()> {
+        if self.trash.read().await.get(id).is_some() {
+            return self.purge_from_trash(id).await;
+        }
 
-/// Parse frontmatter from markdown content
-fn parse_frontmatter(content: &str) -> (Option<Frontmatter>, String) {
-    if !content.starts_with("---") {
-        return (None, content.to_string());
-    }
+        let mut cache = self.notes.write().await;
+        let (file_path, title) = cache
+            .get(&id)
+            .filter(|n| !n.is_deleted)
+            .map(|n| (n.file_path.clone(), n.title.clone()))
+            .ok_or_else(|| Error::NoteNotFound(id.to_string()))?;
+
+        // Drop the embeddings first: if that fails the note is left intact
+        self.purge_vectors(id).await?;
+        remove_file_if_exists(&self.config.notes_path().join(&file_path)).await?;
+
+        cache.remove(&id);
+        self.title_index.write().await.remove(id);
+        self.manifest.write().await.remove(&file_path);
+        self.metadata.purge_note(&id.to_string())?;
+        drop(cache);
+
+        self.save_manifest().await?;
+
+        self.record_history(&format!("Permanently delete {}", title)).await;
+        self.notify(VaultEvent::NoteDeleted { id });
+
+        Ok(())
+    }
+
+    /// Delete a note's embeddings from the on-disk vector store, if any
+    async fn purge_vectors(&self, id: uuid::Uuid) -> Result<()> {
+        let path = self.config.vectors_path();
+        if path.exists() {
+            VectorStore::open(&path).await?.delete_note(id).await?;
+        }
+        Ok(())
+    }
+
+    /// Commit the vault to version history, if enabled
+    ///
+    /// Failures are logged rather than returned: the change itself is already
+    /// on disk and will be part of the next successful commit.
+    #[tracing::instrument(skip(self))]
+    async fn record_history(&self, message: &str) {
+        if let Some(history) = &self.history {
+            if let Err(e) = history.commit(message).await {
+                tracing::warn!("Failed to record version history: {}", e);
+            }
+        }
+    }
+
+    /// Saved versions of a note, newest first
+    ///
+    /// Empty when version history is disabled.
+    pub async fn versions(&self, id: uuid::Uuid) -> Result<Vec<HistoryEntry>> {
+        let note = self
+            .get(id)
+            .await
+            .filter(|n| !n.is_deleted)
+            .ok_or_else(|| Error::NoteNotFound(id.to_string()))?;
+        match &self.history {
+            Some(history) => {
+                let path = Path::new(&self.config.notes_dir).join(&note.file_path);
+                history.versions(&path).await
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// A saved version of a note and its content
+    ///
+    /// `rev` may be abbreviated but must name a commit in the note's history.
+    pub async fn version(&self, id: uuid::Uuid, rev: &str) -> Result<(HistoryEntry, String)> {
+        let not_found = || Error::VersionNotFound(rev.to_string());
+        if !is_revision(rev) {
+            return Err(not_found());
+        }
+
+        let entry = self
+            .versions(id)
+            .await?
+            .into_iter()
+            .find(|v| v.rev.starts_with(&rev.to_ascii_lowercase()))
+            .ok_or_else(not_found)?;
+        let history = self.history.as_ref().ok_or_else(not_found)?;
+        let content = history.content_at(&entry.rev, &entry.path).await?;
+        Ok((entry, content))
+    }
+
+    /// Replace a note's content with a saved version
+    ///
+    /// The note keeps its current path, ID and pin/archive state; the restore
+    /// is itself committed as a new version.
+    pub async fn restore_version(&self, id: uuid::Uuid, rev: &str) -> Result<Note> {
+        let (entry, content) = self.version(id, rev).await?;
+
+        let mut cache = self.notes.write().await;
+        self.preserve_external_edit(&mut cache, id).await?;
+        let previous = cache
+            .get(&id)
+            .filter(|n| !n.is_deleted)
+            .cloned()
+            .ok_or_else(|| Error::NoteNotFound(id.to_string()))?;
+
+        let full_path = self.config.notes_path().join(&previous.file_path);
+        tokio::fs::write(&full_path, &content).await?;
+
+        // Reload to pick up the old version's title and frontmatter
+        let mut note = self.load_note_from_file(&full_path).await?;
+        note.is_pinned = previous.is_pinned;
+        note.is_archived = previous.is_archived;
+        note.updated_at = chrono::Utc::now();
+        note.updated_by = None;
+        {
+            let mut manifest = self.manifest.write().await;
+            manifest.update_hash(&note.file_path, &note.content_hash);
+            manifest.update_timestamps(&note.file_path, note.updated_at);
+        }
+
+        self.title_index.write().await.insert_with_aliases(note.id, &note.title, note.aliases());
+        cache.insert(note.id, note.clone());
+        self.index_links(&note, &cache);
+        drop(cache);
+
+        self.save_manifest().await?;
+        self.record_history(&format!(
+            "Restore {} to {}",
+            note.title,
+            &entry.rev[..entry.rev.len().min(8)]
+        ))
+        .await;
+        self.notify(VaultEvent::updated(&note));
+
+        Ok(note)
+    }
+
+    /// Quick capture to inbox
+    pub async fn quick_capture(&self, content: String, source: Option<String>) -> Result<Note> {
+        let now = chrono::Utc::now();
+        let title = format!("Capture {}", now.format("%Y-%m-%d %H:%M"));
+
+        let mut note_content = String::new();
+        note_content.push_str("---\n");
+        note_content.push_str(&format!("captured_at: {}\n", now.to_rfc3339()));
+        if let Some(source) = &source {
+            note_content.push_str(&format!("source: \"{}\"\n", source));
+        }
+        note_content.push_str("---\n\n");
+        note_content.push_str(&content);
+        let note_content = self.formatted(note_content);
+
+        let filename = format!("{}.md", sanitize_file_stem(&slug::slugify(&title)));
+        let file_path = PathBuf::from("inbox").join(&filename);
+        let full_path = self.config.notes_path().join(&file_path);
+
+        // Ensure inbox exists
+        tokio::fs::create_dir_all(full_path.parent().unwrap()).await?;
+
+        let content_hash = compute_hash(&note_content);
+
+        // Get ID from manifest
+        let note_id = {
+            let mut manifest = self.manifest.write().await;
+            manifest.get_or_create_id(&file_path, &content_hash)
+        };
+
+        // Write to disk
+        tokio::fs::write(&full_path, &note_content).await?;
+
+        // Create note object with the stable ID
+        let mut note = Note::new(title, note_content, file_path);
+        note.id = note_id;
+        note.content_hash = content_hash;
+
+        // Update cache
+        self.title_index.write().await.insert_with_aliases(note.id, &note.title, note.aliases());
+        let mut cache = self.notes.write().await;
+        cache.insert(note.id, note.clone());
+        self.index_links(&note, &cache);
+        drop(cache);
+
+        // Save manifest
+        self.save_manifest().await?;
+
+        self.record_history(&format!("Capture {}", note.title)).await;
+        self.notify(VaultEvent::NoteCaptured {
+            id: note.id,
+            title: note.title.clone(),
+        });
+
+        Ok(note)
+    }
+
+    /// Check which notes need re-indexing
+    pub async fn get_notes_needing_reindex(&self) -> Vec<Note> {
+        let cache = self.notes.read().await;
+        let manifest = self.manifest.read().await;
+
+        cache
+            .values()
+            .filter(|note| manifest.needs_reindex(&note.file_path, &note.content_hash))
+            .cloned()
+            .collect()
+    }
+
+    /// How many notes the manifest tracks and has seen embedded
+    pub async fn manifest_stats(&self) -> ManifestStats {
+        self.manifest.read().await.stats()
+    }
+
+    /// Mark notes as indexed at their current content
+    pub async fn mark_indexed(&self, ids: &[uuid::Uuid]) -> Result<()> {
+        {
+            let cache = self.notes.read().await;
+            let mut manifest = self.manifest.write().await;
+            for note in ids.iter().filter_map(|id| cache.get(id)) {
+                // Files edited on disk are loaded without touching the manifest
+                manifest.update_hash(&note.file_path, &note.content_hash);
+                manifest.mark_indexed(&note.file_path);
+            }
+        }
+        self.save_manifest().await
+    }
+
+    /// Notes linking to the given note, in source-note order
+    ///
+    /// Links resolved to the note at indexing time count, as do unresolved
+    /// links whose target matches the note's title, file name or an alias.
+    #[tracing::instrument(skip(self))]
+    pub async fn backlinks(&self, id: uuid::Uuid) -> Result<Vec<Backlink>> {
+        let cache = self.notes.read().await;
+        let target = cache
+            .get(&id)
+            .filter(|n| !n.is_deleted)
+            .ok_or_else(|| Error::NoteNotFound(id.to_string()))?;
+        let names = note_names(target);
+
+        let backlinks = self
+            .metadata
+            .get_backlink_candidates(id)?
+            .into_iter()
+            .filter(|link| {
+                link.target_note_id == Some(id) || names.contains(&normalize_name(&link.target_raw))
+            })
+            .filter_map(|link| {
+                let source = cache.get(&link.source_note_id).filter(|n| !n.is_deleted)?;
+                Some(Backlink {
+                    note_id: source.id.to_string(),
+                    title: source.title.clone(),
+                    context: link_context(&source.content, link.position),
+                    link_type: link.link_type,
+                })
+            })
+            .collect();
+
+        Ok(backlinks)
+    }
+
+    /// Typed relations a note declares in its frontmatter, then those other
+    /// notes declare to it, optionally only of one kind
+    ///
+    /// As with backlinks, incoming relations whose target matches the note's
+    /// title, file name or an alias count even if they weren't resolved.
+    #[tracing::instrument(skip(self))]
+    pub async fn relations(
+        &self,
+        id: uuid::Uuid,
+        kind: Option<RelationKind>,
+    ) -> Result<Vec<NoteRelation>> {
+        let cache = self.notes.read().await;
+        let note = cache
+            .get(&id)
+            .filter(|n| !n.is_deleted)
+            .ok_or_else(|| Error::NoteNotFound(id.to_string()))?;
+        let wanted = |link: &Link| link.relation.is_some_and(|r| kind.is_none_or(|k| k == r));
+
+        let declared = note
+            .frontmatter
+            .as_ref()
+            .map(|fm| extract_relations(id, fm, &note.content))
+            .unwrap_or_default();
+        let mut relations: Vec<NoteRelation> = resolve_targets(declared, &name_index(&cache))
+            .into_iter()
+            .filter(wanted)
+            .filter_map(|link| {
+                let target = link.target_note_id.and_then(|t| cache.get(&t)).filter(|n| !n.is_deleted);
+                Some(NoteRelation {
+                    relation: link.relation?,
+                    direction: RelationDirection::Outgoing,
+                    note_id: target.map(|n| n.id.to_string()),
+                    title: target.map_or(link.target_raw, |n| n.title.clone()),
+                })
+            })
+            .collect();
+
+        let names = note_names(note);
+        let incoming = self
+            .metadata
+            .get_relation_candidates(id)?
+            .into_iter()
+            .filter(|link| link.source_note_id != id && wanted(link))
+            .filter(|link| {
+                link.target_note_id == Some(id) || names.contains(&normalize_name(&link.target_raw))
+            })
+            .filter_map(|link| {
+                let source = cache.get(&link.source_note_id).filter(|n| !n.is_deleted)?;
+                Some(NoteRelation {
+                    relation: link.relation?,
+                    direction: RelationDirection::Incoming,
+                    note_id: Some(source.id.to_string()),
+                    title: source.title.clone(),
+                })
+            });
+        relations.extend(incoming);
+
+        Ok(relations)
+    }
+
+    /// Outgoing links of every live note, with wiki link targets resolved
+    /// against the current notes
+    pub async fn links(&self) -> Vec<Link> {
+        let cache = self.notes.read().await;
+        let names = name_index(&cache);
+        cache
+            .values()
+            .filter(|n| !n.is_deleted)
+            .flat_map(|n| resolved_links(n, &names))
+            .collect()
+    }
+
+    /// `content` of note `id` with the notes it embeds (`![[Note]]`,
+    /// `![[Note#Heading]]`) inlined; notes `can_read` rejects stay embeds
+    pub async fn transclude(&self, id: uuid::Uuid, content: &str, can_read: impl Fn(&Note) -> bool) -> String {
+        let cache = self.notes.read().await;
+        let names = name_index(&cache);
+        transclude(id, content, &|current, name| {
+            let target = if name.is_empty() {
+                current
+            } else {
+                *names.get(&normalize_name(name))?
+            };
+            let note = cache.get(&target).filter(|n| !n.is_deleted && can_read(n))?;
+            Some((note.id, parse_frontmatter(&note.content).1))
+        })
+    }
+
+    /// Outgoing links of `content`, written in note `id`, with wiki link
+    /// targets resolved against the current notes
+    pub async fn resolve_links(&self, id: uuid::Uuid, content: &str) -> Vec<Link> {
+        let cache = self.notes.read().await;
+        resolve_targets(extract_links(id, content), &name_index(&cache))
+    }
+
+    /// Credit `user` with a change just made to a note
+    ///
+    /// Store writes are unattributed; callers acting on behalf of a signed-in
+    /// user call this right after `create`/`update` to record who made them.
+    pub async fn attribute(
+        &self,
+        id: uuid::Uuid,
+        user: &str,
+        contribution: Contribution,
+    ) -> Result<Note> {
+        let mut cache = self.notes.write().await;
+        let note = cache
+            .get_mut(&id)
+            .filter(|n| !n.is_deleted)
+            .ok_or_else(|| Error::NoteNotFound(id.to_string()))?;
+
+        let created = contribution == Contribution::Created;
+        if created {
+            note.created_by = Some(user.to_string());
+        }
+        note.updated_by = Some(user.to_string());
+        self.manifest
+            .write()
+            .await
+            .attribute(&note.file_path, user, created);
+
+        let result = note.clone();
+        drop(cache);
+
+        self.save_manifest().await?;
+
+        Ok(result)
+    }
+
+    /// Record that a note was opened by a reader
+    pub async fn record_access(&self, id: uuid::Uuid) -> Result<()> {
+        let now = chrono::Utc::now();
+        let mut cache = self.notes.write().await;
+        let note = cache
+            .get_mut(&id)
+            .filter(|n| !n.is_deleted)
+            .ok_or_else(|| Error::NoteNotFound(id.to_string()))?;
+        note.accessed_at = now;
+
+        self.metadata.record_access(
+            id,
+            now.timestamp(),
+            ACCESS_DEDUPE_SECS,
+            ACCESS_RETENTION_SECS,
+        )
+    }
+
+    /// Save where `client` (a user name, or empty in single-user vaults)
+    /// left off in a note
+    pub async fn set_read_position(
+        &self,
+        id: uuid::Uuid,
+        client: &str,
+        heading: Option<String>,
+        line: Option<u32>,
+        scroll: Option<f64>,
+    ) -> Result<ReadPosition> {
+        if self.get(id).await.is_none_or(|n| n.is_deleted) {
+            return Err(Error::NoteNotFound(id.to_string()));
+        }
+        let position = ReadPosition {
+            heading,
+            line,
+            scroll,
+            updated_at: chrono::Utc::now().to_rfc3339(),
+        };
+        self.metadata.set_read_position(id, client, &position)?;
+        Ok(position)
+    }
+
+    /// Where `client` last left off in a note
+    pub fn read_position(&self, id: uuid::Uuid, client: &str) -> Result<Option<ReadPosition>> {
+        self.metadata.get_read_position(id, client)
+    }
+
+    /// Annotations on a note, oldest first
+    pub fn annotations(&self, id: uuid::Uuid) -> Result<Vec<Annotation>> {
+        self.metadata.get_annotations(&id.to_string())
+    }
+
+    /// An annotation on a note
+    pub fn annotation(&self, id: uuid::Uuid, annotation_id: &str) -> Result<Annotation> {
+        self.metadata
+            .get_annotation(annotation_id)?
+            .filter(|a| a.note_id == id.to_string())
+            .ok_or_else(|| Error::AnnotationNotFound(annotation_id.to_string()))
+    }
+
+    /// Comment on a note without touching its file
+    ///
+    /// `heading` must name a section of the note and the lines, counted from
+    /// 1 over the whole file, must lie within it. A reply joins the thread of
+    /// the annotation it answers and takes that thread's anchor.
+    pub async fn annotate(
+        &self,
+        id: uuid::Uuid,
+        author: Option<String>,
+        body: String,
+        heading: Option<String>,
+        lines: Option<(u32, u32)>,
+        reply_to: Option<String>,
+    ) -> Result<Annotation> {
+        let note = self
+            .get(id)
+            .await
+            .filter(|n| !n.is_deleted)
+            .ok_or_else(|| Error::NoteNotFound(id.to_string()))?;
+        if body.trim().is_empty() {
+            return Err(Error::InvalidAnnotation("body is empty".into()));
+        }
+
+        let (heading, lines, reply_to) = match reply_to {
+            Some(parent) => {
+                let parent = self.annotation(id, &parent)?;
+                let lines = parent.line_start.zip(parent.line_end);
+                (parent.heading, lines, Some(parent.reply_to.unwrap_or(parent.id)))
+            }
+            None => {
+                if let Some(heading) = &heading {
+                    if heading_section(&note.content, heading).is_none() {
+                        return Err(Error::InvalidAnnotation(format!("no heading {:?} in the note", heading)));
+                    }
+                }
+                if let Some((start, end)) = lines {
+                    let count = note.content.lines().count() as u32;
+                    if start == 0 || end < start || end > count {
+                        return Err(Error::InvalidAnnotation(format!(
+                            "lines {}-{} are outside the note's {} lines",
+                            start, end, count
+                        )));
+                    }
+                }
+                (heading, lines, None)
+            }
+        };
+
+        let now = chrono::Utc::now().to_rfc3339();
+        let annotation = Annotation {
+            id: uuid::Uuid::new_v4().to_string(),
+            note_id: id.to_string(),
+            heading,
+            line_start: lines.map(|(start, _)| start),
+            line_end: lines.map(|(_, end)| end),
+            reply_to,
+            author,
+            body,
+            created_at: now.clone(),
+            updated_at: now,
+        };
+        self.metadata.insert_annotation(&annotation)?;
+        Ok(annotation)
+    }
+
+    /// Replace the body of an annotation on a note
+    pub fn update_annotation(&self, id: uuid::Uuid, annotation_id: &str, body: String) -> Result<Annotation> {
+        if body.trim().is_empty() {
+            return Err(Error::InvalidAnnotation("body is empty".into()));
+        }
+        let mut annotation = self.annotation(id, annotation_id)?;
+        annotation.body = body;
+        annotation.updated_at = chrono::Utc::now().to_rfc3339();
+        self.metadata
+            .update_annotation(&annotation.id, &annotation.body, &annotation.updated_at)?;
+        Ok(annotation)
+    }
+
+    /// Delete an annotation on a note, with the replies if it opened a thread
+    pub fn delete_annotation(&self, id: uuid::Uuid, annotation_id: &str) -> Result<()> {
+        let annotation = self.annotation(id, annotation_id)?;
+        self.metadata.delete_annotation(&annotation.id)
+    }
+
+    /// Record what `client` said about a note in the results of `query`,
+    /// against each of the query's `terms`
+    pub async fn record_search_feedback(
+        &self,
+        query: &str,
+        terms: &[String],
+        id: uuid::Uuid,
+        kind: FeedbackKind,
+        client: &str,
+    ) -> Result<()> {
+        if self.get(id).await.is_none_or(|n| n.is_deleted) {
+            return Err(Error::NoteNotFound(id.to_string()));
+        }
+        if terms.is_empty() {
+            return Err(Error::Search("Query has no words to learn from".into()));
+        }
+        let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+        self.metadata.add_search_feedback(
+            &query,
+            terms,
+            &id.to_string(),
+            kind,
+            client,
+            &chrono::Utc::now().to_rfc3339(),
+        )
+    }
+
+    /// Feedback on search results per term and note, for `terms` or all
+    pub fn term_feedback(&self, terms: Option<&[String]>) -> Result<Vec<TermFeedback>> {
+        self.metadata.get_term_feedback(terms)
+    }
+
+    /// Forget all feedback on search results
+    pub fn clear_search_feedback(&self) -> Result<()> {
+        self.metadata.clear_search_feedback()
+    }
+
+    /// Star or unstar a note for `client` (a user name, or empty in
+    /// single-user vaults)
+    ///
+    /// Stars are personal, unlike pinning, which orders everyone's lists.
+    pub async fn set_starred(&self, id: uuid::Uuid, client: &str, starred: bool) -> Result<()> {
+        if self.get(id).await.is_none_or(|n| n.is_deleted) {
+            return Err(Error::NoteNotFound(id.to_string()));
+        }
+        self.metadata
+            .set_starred(id, client, starred, &chrono::Utc::now().to_rfc3339())
+    }
+
+    /// Live notes `client` starred, most recently starred first
+    pub async fn starred(&self, client: &str) -> Result<Vec<Note>> {
+        let ids = self.metadata.get_starred(client)?;
+        let cache = self.notes.read().await;
+        Ok(ids
+            .iter()
+            .filter_map(|id| cache.get(&id.parse().ok()?))
+            .filter(|n| !n.is_deleted)
+            .cloned()
+            .collect())
+    }
+
+    /// Times each note was opened over the access log's retention period
+    ///
+    /// Opens less than a minute apart count once; notes never opened are left out.
+    pub async fn access_counts(&self) -> Result<HashMap<uuid::Uuid, u32>> {
+        Ok(self
+            .metadata
+            .get_access_counts()?
+            .into_iter()
+            .filter_map(|(id, count)| Some((id.parse().ok()?, count)))
+            .collect())
+    }
+
+    /// Notes frequently opened around the same time as a note
+    ///
+    /// Returns each note with the number of times both were opened within
+    /// the same window, most frequent first.
+    pub async fn co_accessed(&self, id: uuid::Uuid, limit: usize) -> Result<Vec<(Note, u32)>> {
+        let cache = self.notes.read().await;
+        if cache.get(&id).is_none_or(|n| n.is_deleted) {
+            return Err(Error::NoteNotFound(id.to_string()));
+        }
+
+        // Over-fetch since some logged notes may have been deleted since
+        let notes = self
+            .metadata
+            .get_co_accessed(id, CO_ACCESS_WINDOW_SECS, limit.saturating_mul(2))?
+            .into_iter()
+            .filter_map(|(other, count)| {
+                let other = other.parse::<uuid::Uuid>().ok()?;
+                let note = cache.get(&other).filter(|n| !n.is_deleted)?;
+                Some((note.clone(), count))
+            })
+            .take(limit)
+            .collect();
+
+        Ok(notes)
+    }
+
+    /// The `==highlighted==` passages of live notes, by note title then
+    /// line, optionally only of notes tagged `tag`
+    pub async fn highlights(&self, tag: Option<&str>) -> Result<Vec<Highlight>> {
+        let cache = self.notes.read().await;
+        let mut highlights: Vec<Highlight> = self
+            .metadata
+            .get_highlights()?
+            .into_iter()
+            .filter_map(|(note_id, line, text)| {
+                let note = cache.get(&note_id.parse().ok()?).filter(|n| !n.is_deleted)?;
+                let tags = note.tags();
+                if tag.is_some_and(|tag| !tags.iter().any(|t| t.eq_ignore_ascii_case(tag))) {
+                    return None;
+                }
+                Some(Highlight {
+                    note_id,
+                    title: note.title.clone(),
+                    tags,
+                    line,
+                    text,
+                })
+            })
+            .collect();
+        highlights.sort_by(|a, b| {
+            a.title
+                .to_lowercase()
+                .cmp(&b.title.to_lowercase())
+                .then_with(|| a.note_id.cmp(&b.note_id))
+                .then(a.line.cmp(&b.line))
+        });
+        Ok(highlights)
+    }
+
+    /// Tags of live notes readable with `permissions`, with how many notes
+    /// carry each, by name
+    ///
+    /// Counts come from the metadata database unless access is restricted,
+    /// in which case only the readable notes are counted.
+    pub async fn tag_counts(&self, permissions: &Permissions) -> Vec<(String, u32)> {
+        if !permissions.is_restricted() {
+            match self.metadata.get_tag_counts() {
+                Ok(counts) => return counts,
+                Err(e) => tracing::warn!("Failed to count tags: {}", e),
+            }
+        }
+        let cache = self.notes.read().await;
+        count_tags(
+            cache
+                .values()
+                .filter(|n| !n.is_deleted && permissions.can_read(n)),
+        )
+    }
+
+    /// Tags of the notes in `ids` the caller can read, with how many of
+    /// them carry each
+    pub async fn tag_counts_among(
+        &self,
+        ids: &std::collections::HashSet<uuid::Uuid>,
+        permissions: &Permissions,
+    ) -> Vec<(String, u32)> {
+        let cache = self.notes.read().await;
+        count_tags(
+            ids.iter()
+                .filter_map(|id| cache.get(id))
+                .filter(|n| !n.is_deleted && permissions.can_read(n)),
+        )
+    }
+
+    /// Re-extract and store a note's outgoing links, embedded attachments
+    /// and highlights
+    fn index_links(&self, note: &Note, cache: &HashMap<uuid::Uuid, Note>) {
+        let links = resolved_links(note, &name_index(cache));
+        if let Err(e) = self
+            .metadata
+            .upsert_note(note)
+            .and_then(|_| self.metadata.replace_links(&[note.id], &links))
+            .and_then(|_| self.metadata.replace_attachments(note.id, &self.attachments_of(note)))
+            .and_then(|_| self.metadata.replace_highlights(note.id, &extract_highlights(&note.content)))
+        {
+            tracing::warn!("Failed to store links for {}: {}", note.id, e);
+        }
+    }
+
+    /// Reconcile the cache with files that changed on disk
+    ///
+    /// `paths` are absolute paths reported by a filesystem watcher. Non-note
+    /// paths are ignored. A vanished note whose exact content reappears at an
+    /// untracked path is treated as a rename and keeps its ID. Writes made by
+    /// the store itself are detected by content hash and produce no change.
+    #[tracing::instrument(skip_all, fields(count = paths.len()))]
+    pub async fn sync_paths(&self, paths: &[PathBuf]) -> Result<Vec<NoteChange>> {
+        let notes_path = self.config.notes_path();
+
+        let mut present: Vec<(PathBuf, PathBuf)> = Vec::new();
+        let mut gone: Vec<PathBuf> = Vec::new();
+        for path in paths {
+            // Case-insensitive filesystems may report a file under any spelling
+            let path = if self.case_sensitive {
+                path.clone()
+            } else {
+                on_disk_spelling(path)
+            };
+            let path = &path;
+            let Some(relative) = note_relative_path(&notes_path, path) else {
+                continue;
+            };
+            if path.is_file() {
+                if !present.iter().any(|(_, r)| *r == relative) {
+                    present.push((path.clone(), relative));
+                }
+            } else if !gone.contains(&relative) {
+                gone.push(relative);
+            }
+        }
+
+        let mut changes = Vec::new();
+
+        for (path, relative) in present {
+            let content = match tokio::fs::read_to_string(&path).await {
+                Ok(content) => content,
+                Err(e) => {
+                    tracing::warn!("Failed to read changed note {:?}: {}", path, e);
+                    continue;
+                }
+            };
+
+            // Pair untracked files with a vanished note of identical content
+            if self.manifest.read().await.get_id(&relative).is_none() {
+                let hash = compute_hash(&content);
+                if let Some(pos) = self.find_vanished_with_hash(&gone, &hash).await {
+                    let old = gone.remove(pos);
+                    self.manifest.write().await.rename(&old, &relative);
+                } else if !self.case_sensitive {
+                    // A case-only rename leaves the old spelling resolvable
+                    let mut manifest = self.manifest.write().await;
+                    if let Some(old) = manifest.find_case_variant(&relative) {
+                        manifest.rename(&old, &relative);
+                        gone.retain(|p| *p != old);
+                    }
+                }
+            }
+
+            match self.refresh_note(&path).await {
+                Ok(Some(note)) => changes.push(NoteChange::Updated(Box::new(note))),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Failed to reload note {:?}: {}", path, e),
+            }
+        }
+
+        for relative in gone {
+            if let Some(id) = self.forget_path(&relative).await {
+                changes.push(NoteChange::Removed(id));
+            }
+        }
+
+        if !changes.is_empty() {
+            self.save_manifest().await?;
+            self.record_history("Sync external changes").await;
+        }
+
+        Ok(changes)
+    }
+
+    /// Reconcile the cache with every note file that differs from the
+    /// manifest
+    ///
+    /// Catches up on changes the watcher missed, such as files a sync client
+    /// wrote while the server wasn't watching. New files, files whose content
+    /// hash moved, and tracked notes whose file is gone are synced as
+    /// [`NoteStore::sync_paths`] does.
+    pub async fn rescan(&self) -> Result<Vec<NoteChange>> {
+        let notes_path = self.config.notes_path();
+        let mut files = Vec::new();
+        if notes_path.exists() {
+            note_files(&notes_path, &mut files)?;
+        }
+
+        let mut changed = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        {
+            let manifest = self.manifest.read().await;
+            for path in files {
+                let Some(relative) = note_relative_path(&notes_path, &path) else {
+                    continue;
+                };
+                let hash = match std::fs::read_to_string(&path) {
+                    Ok(content) => compute_hash(&content),
+                    Err(e) => {
+                        tracing::warn!("Failed to read note {:?}: {}", path, e);
+                        continue;
+                    }
+                };
+                if manifest.get_entry(&relative).is_none_or(|e| e.content_hash != hash) {
+                    changed.push(path);
+                }
+                seen.insert(relative);
+            }
+            changed.extend(
+                manifest
+                    .entries()
+                    .filter(|(path, _)| !seen.contains(*path))
+                    .map(|(path, _)| notes_path.join(path)),
+            );
+        }
+
+        self.sync_paths(&changed).await
+    }
+
+    /// Index into `gone` of the tracked note whose cached content has this hash
+    async fn find_vanished_with_hash(&self, gone: &[PathBuf], hash: &str) -> Option<usize> {
+        let cache = self.notes.read().await;
+        let manifest = self.manifest.read().await;
+        gone.iter().position(|old| {
+            manifest
+                .get_id(old)
+                .and_then(|id| cache.get(&id))
+                .is_some_and(|note| !note.is_deleted && note.content_hash == hash)
+        })
+    }
+
+    /// Reload a note file into the cache, returning it if anything changed
+    async fn refresh_note(&self, path: &Path) -> Result<Option<Note>> {
+        let mut note = self.load_note_from_file(path).await?;
+
+        let previous = self.notes.read().await.get(&note.id).cloned();
+        if let Some(previous) = &previous {
+            if !previous.is_deleted
+                && previous.content_hash == note.content_hash
+                && previous.file_path == note.file_path
+            {
+                return Ok(None);
+            }
+            note.is_pinned = previous.is_pinned;
+            note.is_archived = previous.is_archived;
+
+            // Both sides edited since the last snapshot: keep ours as a copy
+            // before the file's version replaces it
+            if !previous.is_deleted
+                && previous.file_path == note.file_path
+                && self.checksums.read().await.is_conflict(
+                    &note.file_path,
+                    &previous.content_hash,
+                    &note.content_hash,
+                )
+            {
+                let mut cache = self.notes.write().await;
+                self.write_conflicted_copy(&mut cache, previous, &previous.content)
+                    .await?;
+            }
+
+            if previous.content_hash != note.content_hash {
+                note.updated_at = chrono::Utc::now();
+                note.updated_by = None;
+                let mut manifest = self.manifest.write().await;
+                manifest.update_hash(&note.file_path, &note.content_hash);
+                manifest.update_timestamps(&note.file_path, note.updated_at);
+            }
+        }
+
+        self.title_index.write().await.insert_with_aliases(note.id, &note.title, note.aliases());
+        let mut cache = self.notes.write().await;
+        cache.insert(note.id, note.clone());
+        self.index_links(&note, &cache);
+        self.checksums
+            .write()
+            .await
+            .record(&note.file_path, &note.content_hash);
+        drop(cache);
+
+        match previous.filter(|p| !p.is_deleted) {
+            Some(_) => self.notify(VaultEvent::updated(&note)),
+            None => self.notify(VaultEvent::created(&note)),
+        }
+
+        Ok(Some(note))
+    }
+
+    /// Drop a note whose file was removed outside the store
+    ///
+    /// Notes already soft-deleted through the store are left alone.
+    async fn forget_path(&self, relative: &Path) -> Option<uuid::Uuid> {
+        let mut cache = self.notes.write().await;
+        let mut manifest = self.manifest.write().await;
+        let id = manifest.get_id(relative)?;
+        if cache.get(&id).is_some_and(|n| n.is_deleted) {
+            return None;
+        }
+
+        manifest.remove(relative);
+        cache.remove(&id);
+        self.title_index.write().await.remove(id);
+        if let Err(e) = self.metadata.delete_note(&id.to_string()) {
+            tracing::warn!("Failed to remove links: {}", e);
+        }
+        self.notify(VaultEvent::NoteDeleted { id });
+
+        Some(id)
+    }
+
+    /// Subscribe to sync conflicts detected by the store
+    pub fn subscribe_conflicts(&self) -> broadcast::Receiver<SyncConflict> {
+        self.conflicts.subscribe()
+    }
+
+    /// Subscribe to changes made to the vault's notes
+    pub fn subscribe_events(&self) -> broadcast::Receiver<VaultEvent> {
+        self.events.subscribe()
+    }
+
+    /// Tell subscribers about a change; with none listening it's dropped
+    pub fn notify(&self, event: VaultEvent) {
+        let _ = self.events.send(event);
+    }
+
+    /// Record the current hash of every note as the conflict baseline
+    pub async fn snapshot_checksums(&self) -> Result<()> {
+        let cache = self.notes.read().await;
+        let snapshot = ChecksumSnapshot::new(
+            cache
+                .values()
+                .filter(|n| !n.is_deleted)
+                .map(|n| (n.file_path.clone(), n.content_hash.clone())),
+        );
+        drop(cache);
+
+        snapshot.save(&self.config.checksums_path())?;
+        *self.checksums.write().await = snapshot;
+        Ok(())
+    }
+
+    /// Keep the on-disk version of a note as a conflicted copy if it was
+    /// changed outside the store, so the write that follows doesn't lose it
+    async fn preserve_external_edit(
+        &self,
+        cache: &mut HashMap<uuid::Uuid, Note>,
+        id: uuid::Uuid,
+    ) -> Result<()> {
+        let Some(note) = cache.get(&id).filter(|n| !n.is_deleted).cloned() else {
+            return Ok(());
+        };
+        let full_path = self.config.notes_path().join(&note.file_path);
+        let Ok(theirs) = tokio::fs::read_to_string(&full_path).await else {
+            return Ok(());
+        };
+        if compute_hash(&theirs) != note.content_hash {
+            self.write_conflicted_copy(cache, &note, &theirs).await?;
+        }
+        Ok(())
+    }
+
+    /// Save `content` as a new conflicted-copy note beside `original`
+    async fn write_conflicted_copy(
+        &self,
+        cache: &mut HashMap<uuid::Uuid, Note>,
+        original: &Note,
+        content: &str,
+    ) -> Result<Note> {
+        let notes_path = self.config.notes_path();
+        let stem = original
+            .file_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "untitled".to_string());
+        let stamp = chrono::Local::now().format("%Y-%m-%d %H%M%S").to_string();
+
+        let mut copy_path = original
+            .file_path
+            .with_file_name(format!("{}.md", conflicted_copy_stem(&stem, &stamp)));
+        let mut attempt = 1;
+        while notes_path.join(&copy_path).exists() {
+            attempt += 1;
+            let stamp = format!("{} {}", stamp, attempt);
+            copy_path = original
+                .file_path
+                .with_file_name(format!("{}.md", conflicted_copy_stem(&stem, &stamp)));
+        }
+
+        let full_path = notes_path.join(&copy_path);
+        tokio::fs::write(&full_path, content).await?;
+        let copy = self.load_note_from_file(&full_path).await?;
+
+        self.title_index.write().await.insert_with_aliases(copy.id, &copy.title, copy.aliases());
+        cache.insert(copy.id, copy.clone());
+        self.index_links(&copy, cache);
+        self.save_manifest().await?;
+
+        tracing::warn!(
+            "{:?} was changed both in Notidium and on disk; kept the other version as {:?}",
+            original.file_path,
+            copy.file_path
+        );
+        let _ = self.conflicts.send(SyncConflict {
+            note_id: original.id,
+            path: original.file_path.clone(),
+            copy: Box::new(copy.clone()),
+        });
+        self.notify(VaultEvent::created(&copy));
+
+        Ok(copy)
+    }
+
+    /// Get config reference
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+}
+
+/// Parse frontmatter from markdown content
+fn parse_frontmatter(content: &str) -> (Option<Frontmatter>, String) {
+    if !content.starts_with("---") {
+        return (None, content.to_string());
+    }
 
     let rest = &content[3..];
     if let Some(end_idx) = rest.find("\n---") {
@@ -551,6 +2311,177 @@ fn parse_frontmatter(content: &str) -> (Option<Frontmatter>, String) {
     }
 }
 
+/// Tags in the frontmatter of markdown content
+pub fn frontmatter_tags(content: &str) -> Vec<String> {
+    parse_frontmatter(content).0.map(|fm| fm.tags).unwrap_or_default()
+}
+
+/// Full file content for a note body and its frontmatter
+fn file_content(frontmatter: Option<&Frontmatter>, body: &str) -> String {
+    let mut content = String::new();
+    if let Some(fm) = frontmatter {
+        if !fm.tags.is_empty() || !fm.aliases.is_empty() || !fm.custom.is_empty() {
+            content.push_str("---\n");
+            if !fm.tags.is_empty() {
+                content.push_str(&format!("tags: [{}]\n", fm.tags.join(", ")));
+            }
+            if !fm.aliases.is_empty() {
+                content.push_str(&format!("aliases: [{}]\n", fm.aliases.join(", ")));
+            }
+            for (key, value) in &fm.custom {
+                if key != "tags" {
+                    // Serialize the YAML value back to string
+                    if let Ok(yaml_str) = serde_yaml::to_string(value) {
+                        let yaml_str = yaml_str.trim();
+                        content.push_str(&format!("{}: {}\n", key, yaml_str));
+                    }
+                }
+            }
+            content.push_str("---\n\n");
+        }
+    }
+    content.push_str(body);
+    content
+}
+
+/// Trim a tag name and check it can be written into a frontmatter tag list
+/// Tags of `notes` with how many notes carry each, by name, treating tags
+/// that differ only by letter case as one
+fn count_tags<'a>(notes: impl Iterator<Item = &'a Note>) -> Vec<(String, u32)> {
+    let mut counts: HashMap<String, (String, u32)> = HashMap::new();
+    for note in notes {
+        let mut seen = std::collections::HashSet::new();
+        for tag in note.tags() {
+            let name = tag.to_lowercase();
+            if seen.insert(name.clone()) {
+                counts.entry(name).or_insert((tag, 0)).1 += 1;
+            }
+        }
+    }
+    let mut counts: Vec<(String, (String, u32))> = counts.into_iter().collect();
+    counts.sort();
+    counts.into_iter().map(|(_, count)| count).collect()
+}
+
+fn validate_tag(tag: &str) -> Result<String> {
+    let tag = tag.trim();
+    let tag = tag.strip_prefix('#').unwrap_or(tag);
+    let is_valid = !tag.is_empty()
+        && !tag.chars().any(|c| c.is_control() || ",[]{}:#\"'".contains(c));
+    if is_valid {
+        Ok(tag.to_string())
+    } else {
+        Err(Error::InvalidTag(tag.to_string()))
+    }
+}
+
+async fn remove_file_if_exists(path: &Path) -> Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Probe the vault's filesystem, assuming case sensitivity if it can't be checked
+fn detect_case_sensitivity(config: &Config) -> bool {
+    let dir = [config.data_dir(), config.notes_path()]
+        .into_iter()
+        .find(|d| d.is_dir());
+    match dir.map(|d| is_case_sensitive(&d)) {
+        Some(Ok(sensitive)) => sensitive,
+        Some(Err(e)) => {
+            tracing::warn!("Could not detect filesystem case sensitivity: {}", e);
+            true
+        }
+        None => true,
+    }
+}
+
+/// Normalize a link target or note name for comparison
+fn normalize_name(name: &str) -> String {
+    let name = name.trim();
+    name.strip_suffix(".md").unwrap_or(name).to_lowercase()
+}
+
+/// Names a wiki link can use to refer to a note
+fn note_names(note: &Note) -> Vec<String> {
+    let mut names = vec![normalize_name(&note.title)];
+    let path = note.file_path.with_extension("");
+    names.push(normalize_name(&path.to_string_lossy()));
+    if let Some(stem) = path.file_name().and_then(|s| s.to_str()) {
+        names.push(normalize_name(stem));
+    }
+    if let Some(fm) = &note.frontmatter {
+        names.extend(fm.aliases.iter().map(|a| normalize_name(a)));
+    }
+    names
+}
+
+/// Map every name of every live note to its ID
+fn name_index(cache: &HashMap<uuid::Uuid, Note>) -> HashMap<String, uuid::Uuid> {
+    let mut index = HashMap::new();
+    for note in cache.values().filter(|n| !n.is_deleted) {
+        for name in note_names(note) {
+            index.entry(name).or_insert(note.id);
+        }
+    }
+    index
+}
+
+/// Extract a note's links and frontmatter relations, resolving wiki link
+/// targets where possible
+fn resolved_links(note: &Note, names: &HashMap<String, uuid::Uuid>) -> Vec<Link> {
+    let mut links = extract_links(note.id, &note.content);
+    if let Some(fm) = &note.frontmatter {
+        links.extend(extract_relations(note.id, fm, &note.content));
+    }
+    resolve_targets(links, names)
+}
+
+/// Fill in the targets of wiki links naming a note in `names`
+fn resolve_targets(mut links: Vec<Link>, names: &HashMap<String, uuid::Uuid>) -> Vec<Link> {
+    for link in &mut links {
+        if link.target_note_id.is_none() && link.link_type != crate::types::LinkType::ExternalUrl {
+            link.target_note_id = names.get(&normalize_name(&link.target_raw)).copied();
+        }
+    }
+    links
+}
+
+/// Path of a markdown note relative to the notes directory
+///
+/// Returns `None` for non-markdown files and anything inside hidden directories.
+/// Markdown files under `dir`, skipping hidden directories
+fn note_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with('.'));
+        if path.is_dir() {
+            if !hidden {
+                note_files(&path, files)?;
+            }
+        } else if path.extension().and_then(|e| e.to_str()) == Some("md") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn note_relative_path(notes_path: &Path, path: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(notes_path).ok()?;
+    if relative.extension().and_then(|e| e.to_str()) != Some("md") {
+        return None;
+    }
+    let hidden = relative
+        .components()
+        .any(|c| c.as_os_str().to_str().is_some_and(|s| s.starts_with('.')));
+    (!hidden).then(|| relative.to_path_buf())
+}
+
 /// Extract title from first heading or first line
 fn extract_title_from_content(content: &str) -> Option<String> {
     for line in content.lines() {
@@ -0,0 +1,210 @@
+//! SQLite-backed storage for embedded chunks
+//!
+//! The alternative to LanceDB, picked with `embedding.vector_store =
+//! "sqlite"`: chunks are rows of a `vector_chunks` table in `index.db`, so a
+//! note's metadata, chunks and embeddings are all in one file that's easy to
+//! back up. Embeddings are kept in the float32 vector format of the
+//! sqlite-vec extension, which is loaded into every connection; chunks are
+//! JSON without their embeddings.
+
+use std::collections::HashSet;
+use std::os::raw::{c_char, c_int};
+use std::path::Path;
+use std::sync::{Mutex, Once};
+
+use rusqlite::{ffi, params, Connection};
+use uuid::Uuid;
+
+use crate::error::Result;
+use crate::types::Chunk;
+
+/// Rows decoded at a time when loading the store
+const BATCH_SIZE: usize = 1024;
+
+/// Chunks and their embeddings in a SQLite database
+pub(crate) struct SqliteVectors {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteVectors {
+    /// Open the database at `path`, creating the table if needed
+    pub fn open(path: &Path) -> Result<Self> {
+        register_sqlite_vec();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS vector_chunks (
+                id TEXT PRIMARY KEY,
+                note_id TEXT NOT NULL,
+                chunk TEXT NOT NULL,
+                embedding_model TEXT,
+                prose_embedding BLOB,
+                code_embedding BLOB
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_vector_chunks_note ON vector_chunks(note_id);
+            "#,
+        )?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Delete the chunks of `note_ids` and store `chunks`, in one transaction
+    pub fn replace_notes(&self, note_ids: &[Uuid], chunks: &[Chunk]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut delete = tx.prepare("DELETE FROM vector_chunks WHERE note_id = ?1")?;
+            for id in note_ids {
+                delete.execute(params![id.to_string()])?;
+            }
+
+            let mut insert = tx.prepare(
+                r#"
+                INSERT OR REPLACE INTO vector_chunks
+                    (id, note_id, chunk, embedding_model, prose_embedding, code_embedding)
+                VALUES (?1, ?2, ?3, ?4,
+                    CASE WHEN ?5 IS NULL THEN NULL ELSE vec_f32(?5) END,
+                    CASE WHEN ?6 IS NULL THEN NULL ELSE vec_f32(?6) END)
+                "#,
+            )?;
+            for chunk in chunks {
+                let stored = Chunk {
+                    prose_embedding: None,
+                    code_embedding: None,
+                    context: None,
+                    ..chunk.clone()
+                };
+                insert.execute(params![
+                    chunk.id.to_string(),
+                    chunk.note_id.to_string(),
+                    serde_json::to_string(&stored)?,
+                    chunk.embedding_model,
+                    chunk.prose_embedding.as_deref().map(vector_blob),
+                    chunk.code_embedding.as_deref().map(vector_blob),
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Remove chunks by their own IDs
+    pub fn delete_chunks(&self, chunk_ids: &[Uuid]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut delete = tx.prepare("DELETE FROM vector_chunks WHERE id = ?1")?;
+            for id in chunk_ids {
+                delete.execute(params![id.to_string()])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Remove every chunk
+    pub fn clear(&self) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM vector_chunks", [])?;
+        Ok(())
+    }
+
+    /// Number of stored chunks
+    pub fn count(&self) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM vector_chunks", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// IDs of all notes that have at least one stored chunk
+    pub fn note_ids(&self) -> Result<HashSet<Uuid>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT DISTINCT note_id FROM vector_chunks")?;
+        let ids = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .filter_map(|r| r.ok())
+            .filter_map(|id| id.parse().ok())
+            .collect();
+        Ok(ids)
+    }
+
+    /// Distinct models the stored chunks were embedded with
+    pub fn embedding_models(&self) -> Result<HashSet<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT embedding_model FROM vector_chunks WHERE embedding_model IS NOT NULL",
+        )?;
+        let models = stmt
+            .query_map([], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(models)
+    }
+
+    /// Decode stored chunks [`BATCH_SIZE`] rows at a time, in insertion order
+    pub fn for_each_batch(&self, mut f: impl FnMut(Vec<Chunk>) -> Result<()>) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT rowid, chunk, prose_embedding, code_embedding FROM vector_chunks
+            WHERE rowid > ?1 ORDER BY rowid LIMIT ?2
+            "#,
+        )?;
+        let mut after = 0i64;
+        loop {
+            let mut batch = Vec::new();
+            let mut rows = stmt.query(params![after, BATCH_SIZE as i64])?;
+            while let Some(row) = rows.next()? {
+                after = row.get(0)?;
+                let mut chunk: Chunk = serde_json::from_str(&row.get::<_, String>(1)?)?;
+                chunk.prose_embedding = row.get::<_, Option<Vec<u8>>>(2)?.map(|b| blob_vector(&b));
+                chunk.code_embedding = row.get::<_, Option<Vec<u8>>>(3)?.map(|b| blob_vector(&b));
+                batch.push(chunk);
+            }
+            if batch.is_empty() {
+                return Ok(());
+            }
+            f(batch)?;
+        }
+    }
+
+    /// Rebuild the database file without its free pages, returning the
+    /// bytes that freed
+    pub fn vacuum(&self) -> Result<u64> {
+        let conn = self.conn.lock().unwrap();
+        let page_size: i64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+        let free_pages: i64 = conn.query_row("PRAGMA freelist_count", [], |row| row.get(0))?;
+        conn.execute_batch("VACUUM")?;
+        Ok((free_pages * page_size) as u64)
+    }
+}
+
+/// Make sqlite-vec's functions available in every connection opened from
+/// now on
+fn register_sqlite_vec() {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| unsafe {
+        let init = std::mem::transmute::<
+            *const (),
+            unsafe extern "C" fn(*mut ffi::sqlite3, *mut *mut c_char, *const ffi::sqlite3_api_routines) -> c_int,
+        >(sqlite_vec::sqlite3_vec_init as *const ());
+        ffi::sqlite3_auto_extension(Some(init));
+    });
+}
+
+/// An embedding as sqlite-vec stores float32 vectors: little-endian floats
+fn vector_blob(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+fn blob_vector(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
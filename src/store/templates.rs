@@ -0,0 +1,152 @@
+//! Note templates
+//!
+//! Templates are markdown files in the vault's `templates/` directory, named
+//! by their file stem (`templates/meeting.md` is the `meeting` template). When
+//! a note is created from one, `{{title}}`, `{{date}}` and `{{clipboard}}`
+//! placeholders are filled in; anything else in braces is left as written.
+
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+
+use crate::error::{Error, Result};
+
+/// Values substituted for template placeholders
+#[derive(Debug, Clone)]
+pub struct TemplateVars {
+    /// Title of the new note, for `{{title}}`
+    pub title: String,
+    /// Day the note is created, for `{{date}}` (formatted `YYYY-MM-DD`)
+    pub date: NaiveDate,
+    /// Clipboard contents, for `{{clipboard}}`; empty when not available
+    pub clipboard: String,
+}
+
+/// The templates of a vault
+pub struct TemplateStore {
+    dir: PathBuf,
+}
+
+impl TemplateStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Names of all templates, sorted
+    pub async fn list(&self) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(names),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "md") && path.is_file() {
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    names.push(stem.to_string());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Raw content of the template called `name`
+    pub async fn get(&self, name: &str) -> Result<String> {
+        let path = self
+            .path(name)
+            .ok_or_else(|| Error::TemplateNotFound(name.to_string()))?;
+        match tokio::fs::read_to_string(&path).await {
+            Ok(content) => Ok(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(Error::TemplateNotFound(name.to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// File of the template called `name`; `None` for names that aren't a
+    /// plain file stem
+    fn path(&self, name: &str) -> Option<PathBuf> {
+        let file = format!("{}.md", name.strip_suffix(".md").unwrap_or(name));
+        let is_plain = !name.is_empty()
+            && !name.starts_with('.')
+            && Path::new(&file).file_name().is_some_and(|f| f == file.as_str());
+        is_plain.then(|| self.dir.join(file))
+    }
+}
+
+/// Fill in the placeholders of a template
+///
+/// Placeholder names are case-insensitive and may be padded with spaces, as
+/// in `{{ Title }}`.
+pub fn render(template: &str, vars: &TemplateVars) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+
+        let value = match after[..end].trim().to_lowercase().as_str() {
+            "title" => Some(vars.title.clone()),
+            "date" => Some(vars.date.format("%Y-%m-%d").to_string()),
+            "clipboard" => Some(vars.clipboard.clone()),
+            _ => None,
+        };
+        match value {
+            Some(value) => rendered.push_str(&value),
+            None => rendered.push_str(&rest[start..start + 2 + end + 2]),
+        }
+        rest = &after[end + 2..];
+    }
+
+    rendered.push_str(rest);
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> TemplateVars {
+        TemplateVars {
+            title: "Standup".to_string(),
+            date: NaiveDate::from_ymd_opt(2024, 5, 2).unwrap(),
+            clipboard: "https://example.com".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_render_placeholders() {
+        let rendered = render("# {{title}}\n\n{{ Date }} — {{clipboard}}\n", &vars());
+        assert_eq!(rendered, "# Standup\n\n2024-05-02 — https://example.com\n");
+    }
+
+    #[test]
+    fn test_render_keeps_unknown_and_unclosed_braces() {
+        assert_eq!(render("{{author}} {{title}}", &vars()), "{{author}} Standup");
+        assert_eq!(render("{{title}} {{date", &vars()), "Standup {{date");
+    }
+
+    #[test]
+    fn test_template_names_stay_in_directory() {
+        let templates = TemplateStore::new(PathBuf::from("/vault/templates"));
+        assert_eq!(
+            templates.path("meeting"),
+            Some(PathBuf::from("/vault/templates/meeting.md"))
+        );
+        assert_eq!(
+            templates.path("meeting.md"),
+            Some(PathBuf::from("/vault/templates/meeting.md"))
+        );
+        for name in ["", "../secret", "a/b", ".hidden"] {
+            assert_eq!(templates.path(name), None, "{}", name);
+        }
+    }
+}
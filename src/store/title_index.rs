@@ -0,0 +1,299 @@
+//! Trigram index over note titles and aliases
+//!
+//! Backs fuzzy title lookup without scanning every cached note. Titles (and
+//! frontmatter aliases) are split into words, each padded pg_trgm-style
+//! (`"  word "`), and broken into trigrams; a query only scores notes sharing
+//! at least one trigram with it.
+
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// Minimum score for a non-exact match to be returned
+const MIN_SCORE: f32 = 0.3;
+
+/// A ranked title match
+#[derive(Debug, Clone, PartialEq)]
+pub struct TitleMatch {
+    pub id: Uuid,
+    /// 1.0 for an exact (case-insensitive) match, lower for fuzzy matches
+    pub score: f32,
+    /// The alias that matched, when it scored better than the title
+    pub alias: Option<String>,
+}
+
+/// One name a note can be found by
+#[derive(Debug)]
+struct IndexedName {
+    /// As written, for reporting which alias matched
+    original: String,
+    lower: String,
+    grams: HashSet<String>,
+}
+
+impl IndexedName {
+    fn new(name: &str) -> Self {
+        let lower = name.to_lowercase();
+        Self {
+            original: name.to_string(),
+            grams: trigrams(&lower),
+            lower,
+        }
+    }
+}
+
+/// Trigram index mapping title fragments to note IDs
+#[derive(Debug, Default)]
+pub struct TitleIndex {
+    /// Title, then aliases, per note
+    names: HashMap<Uuid, Vec<IndexedName>>,
+    /// Trigram -> notes with a name containing it
+    postings: HashMap<String, HashSet<Uuid>>,
+}
+
+impl TitleIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert or replace the title for a note
+    pub fn insert(&mut self, id: Uuid, title: &str) {
+        self.insert_with_aliases(id, title, &[]);
+    }
+
+    /// Insert or replace the title and aliases for a note
+    pub fn insert_with_aliases(&mut self, id: Uuid, title: &str, aliases: &[String]) {
+        self.remove(id);
+
+        let names: Vec<IndexedName> = std::iter::once(title)
+            .chain(aliases.iter().map(String::as_str))
+            .filter(|name| !name.trim().is_empty())
+            .map(IndexedName::new)
+            .collect();
+        for gram in names.iter().flat_map(|n| &n.grams) {
+            self.postings.entry(gram.clone()).or_default().insert(id);
+        }
+        self.names.insert(id, names);
+    }
+
+    /// Remove a note from the index
+    pub fn remove(&mut self, id: Uuid) {
+        let Some(names) = self.names.remove(&id) else {
+            return;
+        };
+        for gram in names.iter().flat_map(|n| &n.grams) {
+            if let Some(ids) = self.postings.get_mut(gram) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    self.postings.remove(gram);
+                }
+            }
+        }
+    }
+
+    /// Remove everything from the index
+    pub fn clear(&mut self) {
+        self.names.clear();
+        self.postings.clear();
+    }
+
+    /// Number of indexed notes
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Rank notes by how well their title or one of their aliases matches
+    /// the query
+    ///
+    /// Exact matches score 1.0; names containing the query score at least
+    /// 0.6; other names are scored by trigram (Dice) similarity and dropped
+    /// below a minimum threshold. A note scores as its best-matching name,
+    /// with the title winning ties.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<TitleMatch> {
+        let query_lower = query.trim().to_lowercase();
+        if query_lower.is_empty() || limit == 0 {
+            return Vec::new();
+        }
+
+        let query_grams = trigrams(&query_lower);
+
+        // Candidates share at least one trigram with the query
+        let mut candidates: HashSet<Uuid> = query_grams
+            .iter()
+            .filter_map(|gram| self.postings.get(gram))
+            .flatten()
+            .copied()
+            .collect();
+
+        // Very short queries produce few trigrams; fall back to substring scan
+        if query_lower.chars().count() < 3 {
+            for (id, names) in &self.names {
+                if names.iter().any(|n| n.lower.contains(&query_lower)) {
+                    candidates.insert(*id);
+                }
+            }
+        }
+
+        let mut matches: Vec<TitleMatch> = candidates
+            .into_iter()
+            .filter_map(|id| {
+                let names = self.names.get(&id)?;
+                let (index, score) = names
+                    .iter()
+                    .map(|name| score(name, &query_lower, &query_grams))
+                    .enumerate()
+                    .fold(None, |best: Option<(usize, f32)>, (i, score)| match best {
+                        Some((_, best_score)) if best_score >= score => best,
+                        _ => Some((i, score)),
+                    })?;
+                let alias = (index > 0).then(|| names[index].original.clone());
+                (score >= MIN_SCORE).then_some(TitleMatch { id, score, alias })
+            })
+            .collect();
+
+        matches.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| self.names[&a.id][0].lower.cmp(&self.names[&b.id][0].lower))
+        });
+        matches.truncate(limit);
+        matches
+    }
+}
+
+/// Score one name against a query
+fn score(name: &IndexedName, query_lower: &str, query_grams: &HashSet<String>) -> f32 {
+    if name.lower == query_lower {
+        return 1.0;
+    }
+
+    let common = query_grams.intersection(&name.grams).count();
+    let total = query_grams.len() + name.grams.len();
+    let similarity = if total == 0 {
+        0.0
+    } else {
+        (2 * common) as f32 / total as f32
+    };
+    if name.lower.contains(query_lower) {
+        0.6 + 0.39 * similarity
+    } else {
+        similarity.min(0.99)
+    }
+}
+
+/// Split text into padded word trigrams
+fn trigrams(text: &str) -> HashSet<String> {
+    let mut grams = HashSet::new();
+    for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+        let padded: Vec<char> = format!("  {} ", word).chars().collect();
+        for window in padded.windows(3) {
+            grams.insert(window.iter().collect());
+        }
+    }
+    grams
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exact_match_scores_highest() {
+        let mut index = TitleIndex::new();
+        let exact = Uuid::new_v4();
+        let partial = Uuid::new_v4();
+        index.insert(exact, "Rust Async");
+        index.insert(partial, "Rust Async Patterns");
+
+        let results = index.search("rust async", 10);
+        assert_eq!(results[0].id, exact);
+        assert_eq!(results[0].score, 1.0);
+        assert_eq!(results[1].id, partial);
+    }
+
+    #[test]
+    fn test_substring_match() {
+        let mut index = TitleIndex::new();
+        let id = Uuid::new_v4();
+        index.insert(id, "My Long Note Title");
+
+        let results = index.search("long note", 10);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, id);
+        assert!(results[0].score >= 0.6);
+    }
+
+    #[test]
+    fn test_typo_tolerant_match() {
+        let mut index = TitleIndex::new();
+        let id = Uuid::new_v4();
+        index.insert(id, "Kubernetes Deployment");
+
+        let results = index.search("kubernets deployment", 10);
+        assert_eq!(results.first().map(|m| m.id), Some(id));
+    }
+
+    #[test]
+    fn test_unrelated_query_returns_nothing() {
+        let mut index = TitleIndex::new();
+        index.insert(Uuid::new_v4(), "Cooking Recipes");
+
+        assert!(index.search("kubernetes", 10).is_empty());
+    }
+
+    #[test]
+    fn test_remove_and_reinsert() {
+        let mut index = TitleIndex::new();
+        let id = Uuid::new_v4();
+        index.insert(id, "Old Title");
+        index.insert(id, "New Title");
+
+        assert!(index.search("old title", 10).iter().all(|m| m.score < 1.0));
+        assert_eq!(index.search("new title", 10)[0].score, 1.0);
+
+        index.remove(id);
+        assert!(index.is_empty());
+        assert!(index.search("new title", 10).is_empty());
+    }
+
+    #[test]
+    fn test_short_query_uses_substring() {
+        let mut index = TitleIndex::new();
+        let id = Uuid::new_v4();
+        index.insert(id, "Go");
+
+        assert_eq!(index.search("go", 10)[0].id, id);
+    }
+
+    #[test]
+    fn test_aliases_match_and_are_reported() {
+        let mut index = TitleIndex::new();
+        let id = Uuid::new_v4();
+        index.insert_with_aliases(id, "Kubernetes", &["k8s".to_string(), "Kube Cluster".to_string()]);
+
+        let results = index.search("K8S", 10);
+        assert_eq!(results[0].id, id);
+        assert_eq!(results[0].score, 1.0);
+        assert_eq!(results[0].alias.as_deref(), Some("k8s"));
+
+        let results = index.search("kube clustr", 10);
+        assert_eq!(results[0].alias.as_deref(), Some("Kube Cluster"));
+
+        assert_eq!(index.search("kubernetes", 10)[0].alias, None);
+    }
+
+    #[test]
+    fn test_reinsert_drops_old_aliases() {
+        let mut index = TitleIndex::new();
+        let id = Uuid::new_v4();
+        index.insert_with_aliases(id, "Postgres", &["pg".to_string()]);
+        index.insert(id, "Postgres");
+
+        assert!(index.search("pg", 10).is_empty());
+        assert!(index.postings.keys().all(|gram| !gram.contains("pg")));
+    }
+}
@@ -0,0 +1,174 @@
+//! Note transclusion
+//!
+//! `![[Note]]` embeds another note and `![[Note#Heading]]` one of its
+//! sections: the embedded text is inlined in place of the link, so a
+//! composite document can be assembled from notes kept elsewhere. Embeds in
+//! embedded text are expanded too, up to [`MAX_DEPTH`] levels. An embed that
+//! would loop back to a note or section already being expanded, or nest any
+//! deeper, is left as an ordinary wiki link. Embeds whose target isn't a
+//! readable note, such as `![[diagram.png]]`, are kept as they are.
+
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use uuid::Uuid;
+
+use super::links::extract_links;
+use crate::types::LinkType;
+
+/// How many levels of embeds are expanded
+pub const MAX_DEPTH: usize = 5;
+
+/// `content` of note `id` with the notes it embeds inlined
+///
+/// `resolve(note, name)` returns the ID and body (without frontmatter) of the
+/// note a link in `note` names, an empty name meaning `note` itself.
+pub fn transclude(
+    id: Uuid,
+    content: &str,
+    resolve: &impl Fn(Uuid, &str) -> Option<(Uuid, String)>,
+) -> String {
+    let mut stack = vec![(id, None)];
+    expand(id, content, resolve, &mut stack)
+}
+
+/// `text` of note `id` with its embeds expanded; `stack` holds the notes and
+/// sections being expanded
+fn expand(
+    id: Uuid,
+    text: &str,
+    resolve: &impl Fn(Uuid, &str) -> Option<(Uuid, String)>,
+    stack: &mut Vec<(Uuid, Option<String>)>,
+) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut cursor = 0;
+    for link in extract_links(id, text) {
+        let start = link.position as usize;
+        if start < cursor
+            || !text[..start].ends_with('!')
+            || matches!(link.link_type, LinkType::ExternalUrl | LinkType::BlockReference)
+        {
+            continue;
+        }
+        let Some(len) = text[start + 2..].find("]]") else {
+            continue;
+        };
+        let end = start + 2 + len + 2;
+        let target = text[start + 2..end - 2].split('|').next().unwrap_or("").trim();
+        let (page, heading) = match target.split_once('#') {
+            Some((page, heading)) => (page.trim(), Some(heading.trim())),
+            None => (target, None),
+        };
+
+        let Some((target_id, body)) = resolve(id, page) else {
+            continue;
+        };
+        let embedded = match heading {
+            Some(heading) => match heading_section(&body, heading) {
+                Some(section) => section,
+                None => continue,
+            },
+            None => body.as_str(),
+        };
+
+        expanded.push_str(&text[cursor..start - 1]);
+        let key = (target_id, heading.map(str::to_lowercase));
+        if stack.contains(&key) || stack.len() > MAX_DEPTH {
+            expanded.push_str(&text[start..end]);
+        } else {
+            stack.push(key);
+            expanded.push_str(expand(target_id, embedded, resolve, stack).trim());
+            stack.pop();
+        }
+        cursor = end;
+    }
+    expanded.push_str(&text[cursor..]);
+    expanded
+}
+
+/// The section of `body` under `heading` (matched case-insensitively),
+/// from the heading to the next heading of the same or a higher level
+pub fn heading_section<'a>(body: &'a str, heading: &str) -> Option<&'a str> {
+    let wanted = heading.trim().to_lowercase();
+    let mut headings = Vec::new();
+    let mut current: Option<(usize, usize, String)> = None;
+    for (event, range) in Parser::new(body).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                current = Some((level as usize, range.start, String::new()));
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, _, title)) = current.as_mut() {
+                    title.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => headings.extend(current.take()),
+            _ => {}
+        }
+    }
+
+    let found = headings
+        .iter()
+        .position(|(_, _, title)| title.trim().to_lowercase() == wanted)?;
+    let (level, start, _) = headings[found];
+    let end = headings[found + 1..]
+        .iter()
+        .find(|(l, _, _)| *l <= level)
+        .map_or(body.len(), |(_, s, _)| *s);
+    Some(&body[start..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notes() -> Vec<(Uuid, &'static str, &'static str)> {
+        vec![
+            (Uuid::from_u128(1), "Main", "# Main\n\n![[Parts#Second]]\n\n![[Loop]]\n"),
+            (
+                Uuid::from_u128(2),
+                "Parts",
+                "# First\n\nOne.\n\n# Second\n\nTwo.\n\n## Detail\n\nMore.\n\n# Third\n\nThree.\n",
+            ),
+            (Uuid::from_u128(3), "Loop", "Loop start ![[Main]] and ![[missing.png]]"),
+        ]
+    }
+
+    fn resolve(_: Uuid, name: &str) -> Option<(Uuid, String)> {
+        notes()
+            .into_iter()
+            .find(|(_, title, _)| title.eq_ignore_ascii_case(name))
+            .map(|(id, _, content)| (id, content.to_string()))
+    }
+
+    #[test]
+    fn test_section() {
+        let body = notes()[1].2;
+        assert_eq!(heading_section(body, "second"), Some("# Second\n\nTwo.\n\n## Detail\n\nMore.\n\n"));
+        assert_eq!(heading_section(body, "Third"), Some("# Third\n\nThree.\n"));
+        assert_eq!(heading_section(body, "Nope"), None);
+    }
+
+    #[test]
+    fn test_transclude_sections_and_cycles() {
+        let (id, _, content) = notes()[0];
+        let expanded = transclude(id, content, &resolve);
+        assert_eq!(
+            expanded,
+            "# Main\n\n# Second\n\nTwo.\n\n## Detail\n\nMore.\n\nLoop start [[Main]] and ![[missing.png]]\n"
+        );
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        // Every level embeds a different section of the same note
+        let content: String = (0..10)
+            .map(|i| format!("# H{}\n\n![[#H{}]]\n\n", i, i + 1))
+            .collect();
+        let id = Uuid::from_u128(9);
+        let resolve = |_: Uuid, name: &str| name.is_empty().then(|| (id, content.clone()));
+
+        let expanded = transclude(id, "![[#H0]]", &resolve);
+        assert!(expanded.contains(&format!("# H{}", MAX_DEPTH - 1)));
+        assert!(expanded.contains(&format!("[[#H{}]]", MAX_DEPTH)));
+        assert!(!expanded.contains(&format!("# H{}", MAX_DEPTH)));
+    }
+}
@@ -0,0 +1,99 @@
+//! Trash index for soft-deleted notes
+//!
+//! Deleted note files are moved to `.notidium/trash/<id>.md`. The index kept
+//! alongside them remembers where each note lived and when it was deleted, so
+//! notes can be listed and restored with their original ID and timestamps.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::types::TrashedNote;
+
+/// A note sitting in the trash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    /// Stable UUID the note had before deletion
+    pub id: Uuid,
+    pub title: String,
+    /// Path relative to the notes directory the note was deleted from
+    pub original_path: PathBuf,
+    pub deleted_at: DateTime<Utc>,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub created_by: Option<String>,
+    #[serde(default)]
+    pub updated_by: Option<String>,
+}
+
+impl From<&TrashEntry> for TrashedNote {
+    fn from(entry: &TrashEntry) -> Self {
+        Self {
+            id: entry.id.to_string(),
+            title: entry.title.clone(),
+            original_path: entry.original_path.to_string_lossy().into_owned(),
+            deleted_at: entry.deleted_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Index of trashed notes, keyed by note ID
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Trash {
+    entries: HashMap<Uuid, TrashEntry>,
+}
+
+impl Trash {
+    /// Load the trash index from disk, or create empty if it doesn't exist
+    pub fn load(path: &Path) -> Result<Self> {
+        if path.exists() {
+            let content = std::fs::read_to_string(path)?;
+            let trash: Trash = serde_json::from_str(&content)
+                .map_err(|e| Error::Other(format!("Failed to parse trash index: {}", e)))?;
+            Ok(trash)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Save the trash index to disk
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    /// The index as saved to disk
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Other(format!("Failed to serialize trash index: {}", e)))
+    }
+
+    pub fn insert(&mut self, entry: TrashEntry) {
+        self.entries.insert(entry.id, entry);
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<&TrashEntry> {
+        self.entries.get(&id)
+    }
+
+    pub fn remove(&mut self, id: Uuid) -> Option<TrashEntry> {
+        self.entries.remove(&id)
+    }
+
+    /// All trashed notes, most recently deleted first
+    pub fn entries(&self) -> Vec<&TrashEntry> {
+        let mut entries: Vec<&TrashEntry> = self.entries.values().collect();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.deleted_at));
+        entries
+    }
+}
@@ -0,0 +1,374 @@
+//! User accounts for shared vaults
+//!
+//! Accounts are a name plus an API key. Only a SHA-256 hash of each key is
+//! kept in `.notidium/users.json`; the key itself is shown once when the user
+//! is added. A vault without accounts runs in single-user mode and doesn't
+//! require keys at all.
+//!
+//! An account can be limited to notes in some folders or with some tags by
+//! giving it [`Scope`]s; an account without scopes can access every note.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::types::Note;
+
+/// Prefix of generated API keys, to make them recognizable in config files
+const KEY_PREFIX: &str = "ntd_";
+
+/// A user who can sign in with an API key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserAccount {
+    pub name: String,
+    /// SHA-256 hash of the user's API key
+    key_hash: String,
+    pub created_at: DateTime<Utc>,
+    /// Notes the user may access; empty means all notes
+    #[serde(default)]
+    pub scopes: Vec<Scope>,
+}
+
+impl UserAccount {
+    pub fn permissions(&self) -> Permissions {
+        Permissions {
+            scopes: self.scopes.clone(),
+        }
+    }
+}
+
+/// What a scope allows doing with the notes it covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLevel {
+    Read,
+    /// Read, create, change and delete
+    Write,
+}
+
+/// Notes a scope covers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScopeTarget {
+    /// Notes in this folder (relative to the notes directory) or below it
+    Folder(PathBuf),
+    /// Notes with this tag
+    Tag(String),
+}
+
+/// Access to a set of notes, written `read:reports/` or `write:#drafts`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Scope {
+    pub access: AccessLevel,
+    pub target: ScopeTarget,
+}
+
+impl Scope {
+    fn covers(&self, path: &Path, tags: &[String]) -> bool {
+        match &self.target {
+            ScopeTarget::Folder(folder) => path.starts_with(folder),
+            ScopeTarget::Tag(tag) => tags.iter().any(|t| t.eq_ignore_ascii_case(tag)),
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            Error::Other(format!(
+                "Invalid scope {:?}, expected e.g. read:notes/folder or write:#tag",
+                s
+            ))
+        };
+
+        let (access, target) = s.split_once(':').ok_or_else(invalid)?;
+        let access = match access {
+            "read" => AccessLevel::Read,
+            "write" => AccessLevel::Write,
+            _ => return Err(invalid()),
+        };
+        let target = match target.strip_prefix('#') {
+            Some(tag) if !tag.is_empty() => ScopeTarget::Tag(tag.to_string()),
+            Some(_) => return Err(invalid()),
+            None => {
+                let folder = PathBuf::from(target.trim_end_matches('/'));
+                let is_relative = folder
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_)));
+                if folder.as_os_str().is_empty() || !is_relative {
+                    return Err(invalid());
+                }
+                ScopeTarget::Folder(folder)
+            }
+        };
+        Ok(Scope { access, target })
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let access = match self.access {
+            AccessLevel::Read => "read",
+            AccessLevel::Write => "write",
+        };
+        match &self.target {
+            ScopeTarget::Folder(folder) => write!(f, "{}:{}/", access, folder.display()),
+            ScopeTarget::Tag(tag) => write!(f, "{}:#{}", access, tag),
+        }
+    }
+}
+
+/// The notes a caller may read and write
+///
+/// This is the single place access rules are decided; the API and MCP layers
+/// ask it about every note they return or change. The default grants
+/// everything, which is what single-user vaults and unscoped accounts get.
+#[derive(Debug, Clone, Default)]
+pub struct Permissions {
+    scopes: Vec<Scope>,
+}
+
+impl Permissions {
+    /// Whether any access is limited at all
+    pub fn is_restricted(&self) -> bool {
+        !self.scopes.is_empty()
+    }
+
+    /// Whether `access` is allowed to a note at `path` (relative to the
+    /// notes directory) with `tags`
+    pub fn allows(&self, access: AccessLevel, path: &Path, tags: &[String]) -> bool {
+        self.scopes.is_empty()
+            || self
+                .scopes
+                .iter()
+                .any(|scope| scope.access >= access && scope.covers(path, tags))
+    }
+
+    pub fn can_read(&self, note: &Note) -> bool {
+        self.allows(AccessLevel::Read, &note.file_path, &note.tags())
+    }
+
+    pub fn can_write(&self, note: &Note) -> bool {
+        self.allows(AccessLevel::Write, &note.file_path, &note.tags())
+    }
+
+    /// Whether write access is allowed to any notes at all
+    pub fn can_write_any(&self) -> bool {
+        self.scopes.is_empty() || self.scopes.iter().any(|s| s.access == AccessLevel::Write)
+    }
+
+    /// Require `access` to the note `id` at `path` with `tags`
+    ///
+    /// Notes the caller can't read are reported as not found, so scoped keys
+    /// can't probe for notes outside their scopes.
+    pub fn check(&self, access: AccessLevel, id: Uuid, path: &Path, tags: &[String]) -> Result<()> {
+        if !self.allows(AccessLevel::Read, path, tags) {
+            return Err(Error::NoteNotFound(id.to_string()));
+        }
+        if !self.allows(access, path, tags) {
+            return Err(Error::AccessDenied(format!("no write access to {}", path.display())));
+        }
+        Ok(())
+    }
+
+    /// Require `access` to a note
+    pub fn check_note(&self, access: AccessLevel, note: &Note) -> Result<()> {
+        self.check(access, note.id, &note.file_path, &note.tags())
+    }
+
+    /// Require permission to create a note with `tags` in `folder`
+    pub fn check_create(&self, folder: &Path, tags: &[String]) -> Result<()> {
+        if self.allows(AccessLevel::Write, folder, tags) {
+            Ok(())
+        } else if folder.as_os_str().is_empty() {
+            Err(Error::AccessDenied("no write access to the top-level folder".into()))
+        } else {
+            Err(Error::AccessDenied(format!("no write access to {}/", folder.display())))
+        }
+    }
+}
+
+/// Registry of user accounts, keyed by name
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Users {
+    users: BTreeMap<String, UserAccount>,
+}
+
+impl Users {
+    /// Load accounts from disk, or create empty if the file doesn't exist
+    pub fn load(path: &Path) -> Result<Self> {
+        if path.exists() {
+            let content = std::fs::read_to_string(path)?;
+            let users: Users = serde_json::from_str(&content)
+                .map_err(|e| Error::Other(format!("Failed to parse users: {}", e)))?;
+            Ok(users)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Save accounts to disk
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Other(format!("Failed to serialize users: {}", e)))?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Add a user with a freshly generated API key, returning the key
+    ///
+    /// The user can access the notes covered by `scopes`, or all notes if
+    /// there are none.
+    pub fn add(&mut self, name: &str, scopes: Vec<Scope>) -> Result<String> {
+        let name = name.trim();
+        if name.is_empty() || name.contains(':') {
+            return Err(Error::Other(format!("Invalid user name: {:?}", name)));
+        }
+        if self.users.contains_key(name) {
+            return Err(Error::Other(format!("User already exists: {}", name)));
+        }
+
+        let key = format!("{}{}", KEY_PREFIX, Uuid::new_v4().simple());
+        self.users.insert(
+            name.to_string(),
+            UserAccount {
+                name: name.to_string(),
+                key_hash: hash_key(&key),
+                created_at: Utc::now(),
+                scopes,
+            },
+        );
+        Ok(key)
+    }
+
+    /// Remove a user, returning whether it existed
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.users.remove(name).is_some()
+    }
+
+    /// The account an API key belongs to
+    pub fn authenticate(&self, key: &str) -> Option<&UserAccount> {
+        let hash = hash_key(key);
+        self.users.values().find(|u| u.key_hash == hash)
+    }
+
+    /// All accounts, ordered by name
+    pub fn list(&self) -> Vec<&UserAccount> {
+        self.users.values().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.users.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+}
+
+fn hash_key(key: &str) -> String {
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    hasher.update(key.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_authenticate_their_user() {
+        let mut users = Users::default();
+        let alice = users.add("alice", Vec::new()).unwrap();
+        let bob = users.add("bob", Vec::new()).unwrap();
+
+        assert_ne!(alice, bob);
+        assert_eq!(users.authenticate(&alice).unwrap().name, "alice");
+        assert_eq!(users.authenticate(&bob).unwrap().name, "bob");
+        assert!(users.authenticate("ntd_wrong").is_none());
+    }
+
+    #[test]
+    fn test_names_are_unique_and_valid() {
+        let mut users = Users::default();
+        users.add("alice", Vec::new()).unwrap();
+        assert!(users.add("alice", Vec::new()).is_err());
+        assert!(users.add("  ", Vec::new()).is_err());
+        assert!(users.add("a:b", Vec::new()).is_err());
+    }
+
+    fn scoped(scopes: &[&str]) -> Permissions {
+        Permissions {
+            scopes: scopes.iter().map(|s| s.parse().unwrap()).collect(),
+        }
+    }
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_scopes() {
+        let scope: Scope = "write:reports/".parse().unwrap();
+        assert_eq!(scope.access, AccessLevel::Write);
+        assert_eq!(scope.target, ScopeTarget::Folder(PathBuf::from("reports")));
+        assert_eq!(scope.to_string(), "write:reports/");
+
+        let scope: Scope = "read:#public".parse().unwrap();
+        assert_eq!(scope.target, ScopeTarget::Tag("public".to_string()));
+        assert_eq!(scope.to_string(), "read:#public");
+
+        for invalid in ["reports/", "admin:reports", "read:", "read:#", "write:../x", "write:/abs"] {
+            assert!(invalid.parse::<Scope>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_folder_scope() {
+        let perms = scoped(&["write:reports/", "read:docs/"]);
+        let report = Path::new("reports/2024/q1.md");
+
+        assert!(perms.allows(AccessLevel::Write, report, &[]));
+        assert!(perms.allows(AccessLevel::Read, report, &[]));
+        assert!(perms.allows(AccessLevel::Read, Path::new("docs/a.md"), &[]));
+        assert!(!perms.allows(AccessLevel::Write, Path::new("docs/a.md"), &[]));
+        assert!(!perms.allows(AccessLevel::Read, Path::new("reports-old/a.md"), &[]));
+        assert!(!perms.allows(AccessLevel::Read, Path::new("private.md"), &[]));
+    }
+
+    #[test]
+    fn test_tag_scope() {
+        let perms = scoped(&["read:#Public"]);
+        assert!(perms.allows(AccessLevel::Read, Path::new("a.md"), &tags(&["public"])));
+        assert!(!perms.allows(AccessLevel::Write, Path::new("a.md"), &tags(&["public"])));
+        assert!(!perms.allows(AccessLevel::Read, Path::new("a.md"), &tags(&["private"])));
+    }
+
+    #[test]
+    fn test_no_scopes_allow_everything() {
+        let perms = Permissions::default();
+        assert!(!perms.is_restricted());
+        assert!(perms.allows(AccessLevel::Write, Path::new("any/where.md"), &[]));
+    }
+
+    #[test]
+    fn test_removed_user_cannot_authenticate() {
+        let mut users = Users::default();
+        let key = users.add("alice", Vec::new()).unwrap();
+        assert!(users.remove("alice"));
+        assert!(!users.remove("alice"));
+        assert!(users.authenticate(&key).is_none());
+    }
+}
@@ -0,0 +1,609 @@
+//! Vector store for embedded chunks
+//!
+//! By default the store is LanceDB; with `embedding.vector_store = "sqlite"`
+//! it is a table of the metadata database instead, described in
+//! `sqlite_vectors.rs`. The rest of this applies to LanceDB.
+//!
+//! Chunks are stored in a single `chunks` table keyed by note ID, so re-indexing
+//! a note only replaces that note's rows instead of rewriting the whole store.
+//! Embeddings are Arrow float32 lists on disk and are decoded one record
+//! batch at a time, so loading never holds the store twice in memory. JSON
+//! is only an interchange format: [`VectorStore::export_json`] writes what
+//! [`VectorStore::migrate_from_json`] reads.
+//!
+//! `format.json` next to the table records the store's format version. A
+//! store written by a newer notidium stays usable: columns this version
+//! doesn't know are left alone on read and filled with nulls on write.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow_array::builder::{Float32Builder, ListBuilder};
+use arrow_array::{
+    new_null_array, Array, ArrayRef, ListArray, RecordBatch, RecordBatchIterator, StringArray,
+    UInt32Array,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use lancedb::query::{ExecutableQuery, QueryBase, Select};
+use lancedb::table::{CompactionOptions, Duration, OptimizeAction};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::types::{Chunk, ChunkType};
+use super::sqlite_vectors::SqliteVectors;
+
+const TABLE_NAME: &str = "chunks";
+
+/// Store format written by this version; stores without `format.json` are 0
+///
+/// - 0: the `chunks` table
+/// - 1: version recorded, columns of newer formats kept
+pub const VECTOR_STORE_VERSION: u32 = 1;
+
+const FORMAT_FILE: &str = "format.json";
+
+/// Contents of `format.json`
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreFormat {
+    version: u32,
+    /// Fields from newer versions, kept as they were
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// What compacting the vector store did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Compaction {
+    /// Data files merged into larger ones
+    pub fragments_removed: usize,
+    pub fragments_added: usize,
+    /// Superseded table versions deleted, with the space they took
+    pub versions_removed: u64,
+    pub bytes_removed: u64,
+}
+
+/// Persistent chunk + embedding store, backed by LanceDB or by SQLite
+pub struct VectorStore {
+    backend: Backend,
+    version: u32,
+}
+
+enum Backend {
+    Lance(LanceTable),
+    Sqlite(SqliteVectors),
+}
+
+/// The LanceDB `chunks` table
+struct LanceTable {
+    table: lancedb::Table,
+    /// The table's schema, which may have columns newer than [`chunk_schema`]
+    schema: SchemaRef,
+}
+
+impl VectorStore {
+    /// Open the store at `path`, creating it if needed: a `.db` file is a
+    /// SQLite database, anything else a LanceDB directory
+    pub async fn open(path: &Path) -> Result<Self> {
+        if path.extension().is_some_and(|ext| ext == "db") {
+            return Ok(Self {
+                backend: Backend::Sqlite(SqliteVectors::open(path)?),
+                version: VECTOR_STORE_VERSION,
+            });
+        }
+
+        std::fs::create_dir_all(path)?;
+
+        let uri = path.to_string_lossy().to_string();
+        let db = lancedb::connect(&uri).execute().await?;
+        let schema = chunk_schema();
+
+        let table_names = db.table_names().execute().await?;
+        let table = if table_names.iter().any(|n| n == TABLE_NAME) {
+            db.open_table(TABLE_NAME).execute().await?
+        } else {
+            db.create_empty_table(TABLE_NAME, schema)
+                .execute()
+                .await?
+        };
+        let schema = table.schema().await?;
+        let version = open_format(path)?;
+
+        Ok(Self {
+            backend: Backend::Lance(LanceTable { table, schema }),
+            version,
+        })
+    }
+
+    /// Format the store is in, newer than [`VECTOR_STORE_VERSION`] when it
+    /// was written by a newer notidium
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Replace all chunks belonging to a note
+    pub async fn upsert_note_chunks(&self, note_id: Uuid, chunks: &[Chunk]) -> Result<()> {
+        self.delete_note(note_id).await?;
+        self.add_chunks(chunks).await
+    }
+
+    /// Replace the chunks of several notes in one write
+    pub async fn replace_notes(&self, note_ids: &[Uuid], chunks: &[Chunk]) -> Result<()> {
+        match &self.backend {
+            Backend::Lance(lance) => lance.replace_notes(note_ids, chunks).await,
+            Backend::Sqlite(sqlite) => sqlite.replace_notes(note_ids, chunks),
+        }
+    }
+
+    /// Append chunks without removing existing rows
+    pub async fn add_chunks(&self, chunks: &[Chunk]) -> Result<()> {
+        match &self.backend {
+            Backend::Lance(lance) => lance.add_chunks(chunks).await,
+            Backend::Sqlite(sqlite) => sqlite.replace_notes(&[], chunks),
+        }
+    }
+
+    /// Remove all chunks belonging to a note
+    pub async fn delete_note(&self, note_id: Uuid) -> Result<()> {
+        self.delete_notes(std::slice::from_ref(&note_id)).await
+    }
+
+    /// Remove all chunks belonging to any of the given notes
+    pub async fn delete_notes(&self, note_ids: &[Uuid]) -> Result<()> {
+        if note_ids.is_empty() {
+            return Ok(());
+        }
+        match &self.backend {
+            Backend::Lance(lance) => {
+                lance.table.delete(&note_id_predicate(note_ids)).await?;
+                Ok(())
+            }
+            Backend::Sqlite(sqlite) => sqlite.replace_notes(note_ids, &[]),
+        }
+    }
+
+    /// Remove chunks by their own IDs
+    pub async fn delete_chunks(&self, chunk_ids: &[Uuid]) -> Result<()> {
+        if chunk_ids.is_empty() {
+            return Ok(());
+        }
+        match &self.backend {
+            Backend::Lance(lance) => {
+                let list = chunk_ids
+                    .iter()
+                    .map(|id| format!("'{}'", id))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                lance.table.delete(&format!("id IN ({})", list)).await?;
+                Ok(())
+            }
+            Backend::Sqlite(sqlite) => sqlite.delete_chunks(chunk_ids),
+        }
+    }
+
+    /// Merge the small files every write leaves behind and delete table
+    /// versions older than the current one
+    ///
+    /// Files from the last week that no version lists yet are kept, in case
+    /// a write elsewhere is still using them. A SQLite store is vacuumed
+    /// instead, which reports only the space it freed.
+    pub async fn compact(&self) -> Result<Compaction> {
+        match &self.backend {
+            Backend::Lance(lance) => lance.compact().await,
+            Backend::Sqlite(sqlite) => Ok(Compaction {
+                bytes_removed: sqlite.vacuum()?,
+                ..Compaction::default()
+            }),
+        }
+    }
+
+    /// Remove every chunk from the store
+    pub async fn clear(&self) -> Result<()> {
+        match &self.backend {
+            Backend::Lance(lance) => {
+                lance.table.delete("true").await?;
+                Ok(())
+            }
+            Backend::Sqlite(sqlite) => sqlite.clear(),
+        }
+    }
+
+    /// Number of stored chunks
+    pub async fn count(&self) -> Result<usize> {
+        match &self.backend {
+            Backend::Lance(lance) => Ok(lance.table.count_rows(None).await?),
+            Backend::Sqlite(sqlite) => sqlite.count(),
+        }
+    }
+
+    /// IDs of all notes that have at least one stored chunk
+    pub async fn note_ids(&self) -> Result<HashSet<Uuid>> {
+        match &self.backend {
+            Backend::Lance(lance) => lance.note_ids().await,
+            Backend::Sqlite(sqlite) => sqlite.note_ids(),
+        }
+    }
+
+    /// Load every stored chunk, including embeddings
+    pub async fn load_all(&self) -> Result<Vec<Chunk>> {
+        let mut chunks = Vec::new();
+        self.for_each_batch(|batch| {
+            chunks.extend(batch);
+            Ok(())
+        })
+        .await?;
+        Ok(chunks)
+    }
+
+    /// Write every stored chunk, with embeddings, as a JSON array in the
+    /// legacy `chunks.json` layout; returns how many were written
+    pub async fn export_json<W: Write>(&self, mut out: W) -> Result<usize> {
+        let mut written = 0;
+        out.write_all(b"[")?;
+        self.for_each_batch(|batch| {
+            for chunk in batch {
+                if written > 0 {
+                    out.write_all(b",")?;
+                }
+                serde_json::to_writer(&mut out, &chunk)?;
+                written += 1;
+            }
+            Ok(())
+        })
+        .await?;
+        out.write_all(b"]")?;
+        out.flush()?;
+        Ok(written)
+    }
+
+    /// Decode stored chunks one batch at a time
+    pub async fn for_each_batch(&self, f: impl FnMut(Vec<Chunk>) -> Result<()>) -> Result<()> {
+        match &self.backend {
+            Backend::Lance(lance) => lance.for_each_batch(f).await,
+            Backend::Sqlite(sqlite) => sqlite.for_each_batch(f),
+        }
+    }
+
+    /// Distinct models the stored chunks were embedded with
+    pub async fn embedding_models(&self) -> Result<HashSet<String>> {
+        match &self.backend {
+            Backend::Lance(lance) => lance.embedding_models().await,
+            Backend::Sqlite(sqlite) => sqlite.embedding_models(),
+        }
+    }
+
+    /// Import chunks from a legacy `chunks.json` file, returning how many were imported
+    pub async fn migrate_from_json(&self, json_path: &Path) -> Result<usize> {
+        let content = std::fs::read_to_string(json_path)?;
+        let chunks: Vec<Chunk> = serde_json::from_str(&content)?;
+        self.add_chunks(&chunks).await?;
+        Ok(chunks.len())
+    }
+}
+
+impl LanceTable {
+    async fn replace_notes(&self, note_ids: &[Uuid], chunks: &[Chunk]) -> Result<()> {
+        if !note_ids.is_empty() {
+            self.table.delete(&note_id_predicate(note_ids)).await?;
+        }
+        self.add_chunks(chunks).await
+    }
+
+    async fn add_chunks(&self, chunks: &[Chunk]) -> Result<()> {
+        if chunks.is_empty() {
+            return Ok(());
+        }
+
+        let batch = chunks_to_batch(self.schema.clone(), chunks)?;
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], self.schema.clone());
+        self.table.add(reader).execute().await?;
+        Ok(())
+    }
+
+    async fn compact(&self) -> Result<Compaction> {
+        let compacted = self
+            .table
+            .optimize(OptimizeAction::Compact {
+                options: CompactionOptions::default(),
+                remap_options: None,
+            })
+            .await?;
+        let pruned = self
+            .table
+            .optimize(OptimizeAction::Prune {
+                older_than: Some(Duration::zero()),
+                delete_unverified: None,
+                error_if_tagged_old_versions: None,
+            })
+            .await?;
+
+        let mut compaction = Compaction::default();
+        if let Some(metrics) = compacted.compaction {
+            compaction.fragments_removed = metrics.fragments_removed;
+            compaction.fragments_added = metrics.fragments_added;
+        }
+        if let Some(stats) = pruned.prune {
+            compaction.versions_removed = stats.old_versions;
+            compaction.bytes_removed = stats.bytes_removed;
+        }
+        Ok(compaction)
+    }
+
+    async fn note_ids(&self) -> Result<HashSet<Uuid>> {
+        let count = self.table.count_rows(None).await?;
+        if count == 0 {
+            return Ok(HashSet::new());
+        }
+
+        let batches: Vec<RecordBatch> = self
+            .table
+            .query()
+            .select(Select::Columns(vec!["note_id".to_string()]))
+            .limit(count)
+            .execute()
+            .await?
+            .try_collect()
+            .await?;
+
+        let mut ids = HashSet::new();
+        for batch in &batches {
+            let column = string_column(batch, "note_id")?;
+            for i in 0..column.len() {
+                if let Ok(id) = column.value(i).parse::<Uuid>() {
+                    ids.insert(id);
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Decode stored chunks one record batch at a time
+    async fn for_each_batch(&self, mut f: impl FnMut(Vec<Chunk>) -> Result<()>) -> Result<()> {
+        let count = self.table.count_rows(None).await?;
+        if count == 0 {
+            return Ok(());
+        }
+
+        let mut stream = self.table.query().limit(count).execute().await?;
+        while let Some(batch) = stream.try_next().await? {
+            f(batch_to_chunks(&batch)?)?;
+        }
+        Ok(())
+    }
+
+    async fn embedding_models(&self) -> Result<HashSet<String>> {
+        let count = self.table.count_rows(None).await?;
+        if count == 0 {
+            return Ok(HashSet::new());
+        }
+
+        let batches: Vec<RecordBatch> = self
+            .table
+            .query()
+            .select(Select::Columns(vec!["embedding_model".to_string()]))
+            .limit(count)
+            .execute()
+            .await?
+            .try_collect()
+            .await?;
+
+        let mut models = HashSet::new();
+        for batch in &batches {
+            let column = string_column(batch, "embedding_model")?;
+            for i in 0..column.len() {
+                if let Some(model) = optional_string(column, i) {
+                    models.insert(model);
+                }
+            }
+        }
+        Ok(models)
+    }
+}
+
+/// Read the store's format version, recording the current one for older
+/// stores; formats 0 and 1 share a table layout
+fn open_format(path: &Path) -> Result<u32> {
+    let format_path = path.join(FORMAT_FILE);
+    let version = if format_path.exists() {
+        let format: StoreFormat = serde_json::from_str(&std::fs::read_to_string(&format_path)?)?;
+        format.version
+    } else {
+        0
+    };
+
+    if version > VECTOR_STORE_VERSION {
+        tracing::warn!(
+            "Vector store was written by a newer notidium (format {}, this version knows {}); keeping the columns it doesn't understand",
+            version,
+            VECTOR_STORE_VERSION
+        );
+        return Ok(version);
+    }
+    if version < VECTOR_STORE_VERSION {
+        let format = StoreFormat {
+            version: VECTOR_STORE_VERSION,
+            extra: serde_json::Map::new(),
+        };
+        std::fs::write(&format_path, serde_json::to_string_pretty(&format)?)?;
+    }
+    Ok(VECTOR_STORE_VERSION)
+}
+
+/// Arrow schema of the `chunks` table
+///
+/// Embeddings are variable-length lists so that switching models (and thus
+/// dimensions) does not require a schema migration.
+fn chunk_schema() -> SchemaRef {
+    let vector = DataType::List(Arc::new(Field::new("item", DataType::Float32, true)));
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("note_id", DataType::Utf8, false),
+        Field::new("content", DataType::Utf8, false),
+        Field::new("chunk_type", DataType::Utf8, false),
+        Field::new("language", DataType::Utf8, true),
+        Field::new("start_line", DataType::UInt32, false),
+        Field::new("end_line", DataType::UInt32, false),
+        Field::new("start_offset", DataType::UInt32, false),
+        Field::new("end_offset", DataType::UInt32, false),
+        Field::new("prose_embedding", vector.clone(), true),
+        Field::new("code_embedding", vector, true),
+        Field::new("embedding_model", DataType::Utf8, true),
+        Field::new("embedded_at", DataType::Utf8, true),
+    ]))
+}
+
+fn note_id_predicate(note_ids: &[Uuid]) -> String {
+    // UUIDs only contain hex digits and dashes, so quoting is safe
+    let list = note_ids
+        .iter()
+        .map(|id| format!("'{}'", id))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("note_id IN ({})", list)
+}
+
+fn chunks_to_batch(schema: SchemaRef, chunks: &[Chunk]) -> Result<RecordBatch> {
+    let chunk_types = chunks
+        .iter()
+        .map(|c| serde_json::to_string(&c.chunk_type))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let mut known: HashMap<&str, ArrayRef> = HashMap::from([
+        ("id", Arc::new(StringArray::from_iter_values(chunks.iter().map(|c| c.id.to_string()))) as ArrayRef),
+        ("note_id", Arc::new(StringArray::from_iter_values(chunks.iter().map(|c| c.note_id.to_string())))),
+        ("content", Arc::new(StringArray::from_iter_values(chunks.iter().map(|c| c.content.as_str())))),
+        ("chunk_type", Arc::new(StringArray::from_iter_values(chunk_types))),
+        ("language", Arc::new(StringArray::from_iter(chunks.iter().map(|c| c.language.as_deref())))),
+        ("start_line", Arc::new(UInt32Array::from_iter_values(chunks.iter().map(|c| c.start_line)))),
+        ("end_line", Arc::new(UInt32Array::from_iter_values(chunks.iter().map(|c| c.end_line)))),
+        ("start_offset", Arc::new(UInt32Array::from_iter_values(chunks.iter().map(|c| c.start_offset)))),
+        ("end_offset", Arc::new(UInt32Array::from_iter_values(chunks.iter().map(|c| c.end_offset)))),
+        ("prose_embedding", Arc::new(vector_column(chunks.iter().map(|c| c.prose_embedding.as_deref())))),
+        ("code_embedding", Arc::new(vector_column(chunks.iter().map(|c| c.code_embedding.as_deref())))),
+        ("embedding_model", Arc::new(StringArray::from_iter(chunks.iter().map(|c| c.embedding_model.as_deref())))),
+        (
+            "embedded_at",
+            Arc::new(StringArray::from_iter(chunks.iter().map(|c| c.embedded_at.map(|t| t.to_rfc3339())))),
+        ),
+    ]);
+
+    // Columns of newer formats get nulls
+    let columns: Vec<ArrayRef> = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            known
+                .remove(field.name().as_str())
+                .unwrap_or_else(|| new_null_array(field.data_type(), chunks.len()))
+        })
+        .collect();
+
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+fn vector_column<'a>(vectors: impl Iterator<Item = Option<&'a [f32]>>) -> ListArray {
+    let mut builder = ListBuilder::new(Float32Builder::new());
+    for vector in vectors {
+        match vector {
+            Some(values) => {
+                builder.values().append_slice(values);
+                builder.append(true);
+            }
+            None => builder.append(false),
+        }
+    }
+    builder.finish()
+}
+
+fn batch_to_chunks(batch: &RecordBatch) -> Result<Vec<Chunk>> {
+    let ids = string_column(batch, "id")?;
+    let note_ids = string_column(batch, "note_id")?;
+    let contents = string_column(batch, "content")?;
+    let chunk_types = string_column(batch, "chunk_type")?;
+    let start_lines = u32_column(batch, "start_line")?;
+    let end_lines = u32_column(batch, "end_line")?;
+    let start_offsets = u32_column(batch, "start_offset")?;
+    let end_offsets = u32_column(batch, "end_offset")?;
+    // Optional fields may be missing from stores of other formats
+    let languages = batch_column::<StringArray>(batch, "language");
+    let prose = batch_column::<ListArray>(batch, "prose_embedding");
+    let code = batch_column::<ListArray>(batch, "code_embedding");
+    let models = batch_column::<StringArray>(batch, "embedding_model");
+    let embedded = batch_column::<StringArray>(batch, "embedded_at");
+
+    let mut chunks = Vec::with_capacity(batch.num_rows());
+    for i in 0..batch.num_rows() {
+        let id = ids
+            .value(i)
+            .parse::<Uuid>()
+            .map_err(|e| Error::VectorStore(format!("Invalid chunk id: {}", e)))?;
+        let note_id = note_ids
+            .value(i)
+            .parse::<Uuid>()
+            .map_err(|e| Error::VectorStore(format!("Invalid note id: {}", e)))?;
+        let chunk_type: ChunkType = serde_json::from_str(chunk_types.value(i))?;
+
+        chunks.push(Chunk {
+            id,
+            note_id,
+            content: contents.value(i).to_string(),
+            chunk_type,
+            language: languages.and_then(|a| optional_string(a, i)),
+            start_line: start_lines.value(i),
+            end_line: end_lines.value(i),
+            start_offset: start_offsets.value(i),
+            end_offset: end_offsets.value(i),
+            prose_embedding: prose.map(|a| optional_vector(a, i)).transpose()?.flatten(),
+            code_embedding: code.map(|a| optional_vector(a, i)).transpose()?.flatten(),
+            embedding_model: models.and_then(|a| optional_string(a, i)),
+            embedded_at: embedded
+                .and_then(|a| optional_string(a, i))
+                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                .map(|t| t.with_timezone(&Utc)),
+            context: None,
+        });
+    }
+
+    Ok(chunks)
+}
+
+fn batch_column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> Option<&'a T> {
+    batch.column_by_name(name).and_then(|c| c.as_any().downcast_ref::<T>())
+}
+
+fn column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> Result<&'a T> {
+    batch_column(batch, name)
+        .ok_or_else(|| Error::VectorStore(format!("Missing or mistyped column: {}", name)))
+}
+
+fn string_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a StringArray> {
+    column(batch, name)
+}
+
+fn u32_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a UInt32Array> {
+    column(batch, name)
+}
+
+fn optional_string(array: &StringArray, i: usize) -> Option<String> {
+    if array.is_null(i) {
+        None
+    } else {
+        Some(array.value(i).to_string())
+    }
+}
+
+fn optional_vector(array: &ListArray, i: usize) -> Result<Option<Vec<f32>>> {
+    if array.is_null(i) {
+        return Ok(None);
+    }
+    let values = array.value(i);
+    let floats = values
+        .as_any()
+        .downcast_ref::<arrow_array::Float32Array>()
+        .ok_or_else(|| Error::VectorStore("Embedding column is not float32".into()))?;
+    Ok(Some(floats.values().to_vec()))
+}
@@ -0,0 +1,170 @@
+//! Logging and OpenTelemetry trace export
+//!
+//! With an OTLP endpoint configured, spans are exported alongside the log
+//! output. Incoming HTTP requests and MCP tool calls continue the trace of
+//! their caller when it sends a W3C `traceparent` header, so a slow search
+//! can be followed from the client through storage and embedding.
+
+use axum::http::{HeaderMap, Request};
+use opentelemetry::global;
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+use crate::config::TelemetryConfig;
+use crate::error::{Error, Result};
+
+/// Installed logging and tracing; flushes pending spans when dropped
+pub struct Telemetry {
+    provider: Option<SdkTracerProvider>,
+}
+
+impl Telemetry {
+    /// Whether spans are being exported
+    pub fn is_exporting(&self) -> bool {
+        self.provider.is_some()
+    }
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("Failed to flush traces: {}", e);
+            }
+        }
+    }
+}
+
+/// Install the global subscriber: log output at `log_level` (unless
+/// overridden by `RUST_LOG`), plus trace export when `config` has an endpoint
+pub fn init(log_level: &str, config: Option<&TelemetryConfig>) -> Result<Telemetry> {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| format!("notidium={},tower_http=debug", log_level).into());
+
+    let provider = match config.and_then(|c| c.otlp_endpoint.as_deref().map(|e| (c, e))) {
+        Some((config, endpoint)) => Some(tracer_provider(config, endpoint)?),
+        None => None,
+    };
+    let otel = provider
+        .as_ref()
+        .map(|p| tracing_opentelemetry::layer().with_tracer(p.tracer("notidium")));
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .with(otel)
+        .init();
+
+    Ok(Telemetry { provider })
+}
+
+fn tracer_provider(config: &TelemetryConfig, endpoint: &str) -> Result<SdkTracerProvider> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(traces_url(endpoint))
+        .build()
+        .map_err(|e| Error::Telemetry(e.to_string()))?;
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.sample_ratio.clamp(0.0, 1.0),
+        ))))
+        .with_resource(
+            Resource::builder()
+                .with_service_name(config.service_name.clone())
+                .build(),
+        )
+        .build())
+}
+
+/// OTLP/HTTP traces URL for a collector's base URL
+fn traces_url(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{}/v1/traces", endpoint)
+    }
+}
+
+/// Continue the trace propagated in `headers`, if any, in `span`
+pub fn set_parent_from_headers(span: &Span, headers: &HeaderMap) {
+    let context = global::get_text_map_propagator(|p| p.extract(&HeaderExtractor(headers)));
+    // Fails only when trace export is off, leaving the span a root
+    let _ = span.set_parent(context);
+}
+
+/// Span for an incoming HTTP request, continuing the caller's trace
+pub fn http_request_span<B>(request: &Request<B>) -> Span {
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+    );
+    set_parent_from_headers(&span, request.headers());
+    span
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traces_url() {
+        assert_eq!(traces_url("http://localhost:4318"), "http://localhost:4318/v1/traces");
+        assert_eq!(traces_url("http://localhost:4318/"), "http://localhost:4318/v1/traces");
+        assert_eq!(
+            traces_url("https://otel.example/v1/traces"),
+            "https://otel.example/v1/traces"
+        );
+    }
+
+    #[test]
+    fn test_request_span_continues_caller_trace() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = SdkTracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let request = Request::builder()
+                .uri("/api/search?q=slow")
+                .header("traceparent", "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+                .body(())
+                .unwrap();
+            let span = http_request_span(&request);
+            let child = span.in_scope(|| tracing::info_span!("storage"));
+
+            use opentelemetry::trace::TraceContextExt;
+            for span in [span, child] {
+                assert_eq!(
+                    span.context().span().span_context().trace_id().to_string(),
+                    "4bf92f3577b34da6a3ce929d0e0e4736"
+                );
+            }
+        });
+    }
+}
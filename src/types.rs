@@ -22,6 +22,11 @@ pub struct Note {
     pub updated_at: DateTime<Utc>,
     pub accessed_at: DateTime<Utc>,
 
+    /// User who created the note, in multi-user vaults
+    pub created_by: Option<String>,
+    /// User who last modified the note, if known
+    pub updated_by: Option<String>,
+
     pub is_pinned: bool,
     pub is_archived: bool,
     pub is_deleted: bool,
@@ -46,6 +51,8 @@ impl Note {
             created_at: now,
             updated_at: now,
             accessed_at: now,
+            created_by: None,
+            updated_by: None,
             is_pinned: false,
             is_archived: false,
             is_deleted: false,
@@ -67,6 +74,16 @@ impl Note {
 
         tags
     }
+
+    /// Alternative names from the frontmatter `aliases` field
+    pub fn aliases(&self) -> &[String] {
+        self.frontmatter.as_ref().map_or(&[], |fm| fm.aliases.as_slice())
+    }
+
+    /// Whether `user` created the note or made its latest change
+    pub fn is_authored_by(&self, user: &str) -> bool {
+        self.created_by.as_deref() == Some(user) || self.updated_by.as_deref() == Some(user)
+    }
 }
 
 /// YAML frontmatter metadata
@@ -99,6 +116,11 @@ pub struct Chunk {
 
     pub embedding_model: Option<String>,
     pub embedded_at: Option<DateTime<Utc>>,
+
+    /// Note title and headings the chunk sits under, like `Title >
+    /// Deployment > Kubernetes`; embedded with the chunk but not stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
 }
 
 impl Chunk {
@@ -117,12 +139,21 @@ impl Chunk {
             code_embedding: None,
             embedding_model: None,
             embedded_at: None,
+            context: None,
         }
     }
 
     pub fn is_code(&self) -> bool {
         matches!(self.chunk_type, ChunkType::CodeBlock { .. })
     }
+
+    /// Text the chunk is embedded as: its content, after its context
+    pub fn embedding_text(&self) -> String {
+        match &self.context {
+            Some(context) => format!("{}: {}", context, self.content),
+            None => self.content.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -163,14 +194,223 @@ pub struct Link {
     pub target_raw: String,
     pub link_type: LinkType,
     pub position: u32,
+    /// Kind of relation, for links declared in frontmatter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relation: Option<RelationKind>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema, ToSchema)]
 pub enum LinkType {
     WikiLink,
     HeadingLink,
     BlockReference,
     ExternalUrl,
+    /// A typed relation declared in frontmatter
+    Relation,
+}
+
+impl LinkType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkType::WikiLink => "WikiLink",
+            LinkType::HeadingLink => "HeadingLink",
+            LinkType::BlockReference => "BlockReference",
+            LinkType::ExternalUrl => "ExternalUrl",
+            LinkType::Relation => "Relation",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "WikiLink" => Some(LinkType::WikiLink),
+            "HeadingLink" => Some(LinkType::HeadingLink),
+            "BlockReference" => Some(LinkType::BlockReference),
+            "ExternalUrl" => Some(LinkType::ExternalUrl),
+            "Relation" => Some(LinkType::Relation),
+            _ => None,
+        }
+    }
+}
+
+/// How a note relates to another, declared by a frontmatter property of the
+/// same name (`supersedes: "[[Old Plan]]"`)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, JsonSchema, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RelationKind {
+    RelatesTo,
+    Supersedes,
+    BlockedBy,
+}
+
+impl RelationKind {
+    pub const ALL: [RelationKind; 3] = [
+        RelationKind::RelatesTo,
+        RelationKind::Supersedes,
+        RelationKind::BlockedBy,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RelationKind::RelatesTo => "relates_to",
+            RelationKind::Supersedes => "supersedes",
+            RelationKind::BlockedBy => "blocked_by",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == s)
+    }
+}
+
+/// A link pointing at a note from another note
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+pub struct Backlink {
+    /// ID of the linking note
+    pub note_id: String,
+    /// Title of the linking note
+    pub title: String,
+    pub link_type: LinkType,
+    /// Line of the linking note containing the link
+    pub context: String,
+}
+
+/// A `==highlighted==` passage of a note
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+pub struct Highlight {
+    /// ID of the note holding the passage
+    pub note_id: String,
+    /// Title of the note
+    pub title: String,
+    /// Tags of the note
+    pub tags: Vec<String>,
+    /// Line of the passage in the note, from 1
+    pub line: usize,
+    /// The highlighted text, without the `==` markers
+    pub text: String,
+}
+
+/// Where a reader left off in a note
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema, ToSchema)]
+pub struct ReadPosition {
+    /// Heading of the section being read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heading: Option<String>,
+    /// Line at the top of the view, from 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    /// How far down the note the view was scrolled, from 0 to 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scroll: Option<f64>,
+    /// ISO 8601 time the position was saved
+    pub updated_at: String,
+}
+
+/// A comment on a note, kept outside its markdown
+///
+/// Anchored to a heading, a range of lines, or to the whole note when it has
+/// neither. Replies carry the ID of the comment that opened the thread and
+/// share its anchor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema, ToSchema)]
+pub struct Annotation {
+    pub id: String,
+    pub note_id: String,
+    /// Heading of the section commented on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heading: Option<String>,
+    /// First line commented on, from 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_start: Option<u32>,
+    /// Last line commented on, inclusive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_end: Option<u32>,
+    /// Annotation that opened the thread this one replies in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+    /// User who wrote it, none in single-user vaults
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    pub body: String,
+    /// ISO 8601 time it was written
+    pub created_at: String,
+    /// ISO 8601 time it was last edited
+    pub updated_at: String,
+}
+
+/// What a reader said about a search result
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedbackKind {
+    /// The result was opened
+    Clicked,
+    /// The result doesn't belong with the query
+    Irrelevant,
+}
+
+impl FeedbackKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FeedbackKind::Clicked => "clicked",
+            FeedbackKind::Irrelevant => "irrelevant",
+        }
+    }
+}
+
+/// Feedback on search results for one query term and note
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema, ToSchema)]
+pub struct TermFeedback {
+    /// Lowercased query word
+    pub term: String,
+    pub note_id: String,
+    /// Times the note was opened from results for queries with the term
+    pub clicks: u32,
+    /// Times it was marked irrelevant to them
+    pub irrelevant: u32,
+    /// Queries the feedback came from
+    pub queries: Vec<String>,
+}
+
+/// Which end of a typed relation a note is
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RelationDirection {
+    /// The note declares the relation
+    Outgoing,
+    /// Another note declares the relation to this one
+    Incoming,
+}
+
+/// A typed relation between a note and another
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+pub struct NoteRelation {
+    pub relation: RelationKind,
+    pub direction: RelationDirection,
+    /// ID of the other note; absent when an outgoing target matches no note
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note_id: Option<String>,
+    /// Title of the other note, or the target as written
+    pub title: String,
+}
+
+/// A soft-deleted note that can be restored or purged
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+pub struct TrashedNote {
+    pub id: String,
+    pub title: String,
+    /// Path relative to the notes directory the note will be restored to
+    pub original_path: String,
+    /// ISO 8601 deletion timestamp
+    pub deleted_at: String,
+}
+
+/// A saved version of a note in the vault's git history
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+pub struct NoteVersion {
+    /// Commit hash identifying the version
+    pub rev: String,
+    /// ISO 8601 commit timestamp
+    pub committed_at: String,
+    /// Commit message describing the change
+    pub message: String,
 }
 
 /// Search result with score
@@ -188,10 +428,27 @@ pub struct SearchResult {
     /// ISO 8601 last update timestamp
     #[serde(skip_serializing_if = "Option::is_none")]
     pub updated_at: Option<String>,
+    /// Full text of the matched chunk, when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk: Option<String>,
+    /// 1-based line of the match in the note's file, for line-based searches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    /// Where query terms matched in `snippet`, for full-text results
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub highlights: Vec<MatchRange>,
+}
+
+/// Byte range of `SearchResult::snippet` a query term matched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, ToSchema)]
+pub struct MatchRange {
+    pub start: usize,
+    pub end: usize,
 }
 
 /// Query type classification
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "lowercase")]
 pub enum QueryType {
     Prose,
     Code,
@@ -199,38 +456,66 @@ pub enum QueryType {
 }
 
 impl QueryType {
-    /// Classify a query as prose-like, code-like, or hybrid
+    /// Classify a query as prose-like, code-like, or hybrid with the
+    /// built-in rules
     pub fn classify(query: &str) -> Self {
-        let code_signals = [
-            query.contains("::"),
-            query.contains("->"),
-            query.contains("()"),
-            query.contains("{}"),
-            query.contains("[]"),
-            query.contains(".unwrap"),
-            query.contains("async "),
-            query.contains("fn "),
-            query.contains("def "),
-            query.contains("func "),
-            query.contains("const "),
-            query.contains("let "),
-            query.contains("var "),
-            query.contains(".rs"),
-            query.contains(".py"),
-            query.contains(".ts"),
-            query.contains(".js"),
-            has_camel_case(query),
-            has_snake_case(query),
-        ];
-
-        let code_score = code_signals.iter().filter(|&&x| x).count();
-
-        if code_score >= 2 {
-            QueryType::Code
-        } else if code_score == 1 {
-            QueryType::Hybrid
-        } else {
-            QueryType::Prose
+        crate::search::QueryClassifier::default().classify(query).query_type
+    }
+}
+
+/// What notes and search results are ordered by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    /// Best match first; lists, which have no query, use `updated_at`
+    #[default]
+    Relevance,
+    UpdatedAt,
+    CreatedAt,
+    Title,
+}
+
+/// Direction of a sort
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// A field to order by and its direction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NoteSort {
+    pub field: SortField,
+    pub order: SortOrder,
+}
+
+impl NoteSort {
+    /// Sort by `field`, in `order` or else the field's natural direction:
+    /// titles A to Z, everything else highest or newest first
+    pub fn new(field: SortField, order: Option<SortOrder>) -> Self {
+        let natural = match field {
+            SortField::Title => SortOrder::Asc,
+            _ => SortOrder::Desc,
+        };
+        Self {
+            field,
+            order: order.unwrap_or(natural),
+        }
+    }
+
+    /// How `a` and `b` compare in this sort, relevance counting as last
+    /// update since notes alone have none
+    pub fn compare(&self, a: &Note, b: &Note) -> std::cmp::Ordering {
+        let ordering = match self.field {
+            SortField::Relevance | SortField::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+            SortField::CreatedAt => a.created_at.cmp(&b.created_at),
+            SortField::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+        };
+        match self.order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
         }
     }
 }
@@ -243,6 +528,12 @@ pub struct NoteMeta {
     pub slug: String,
     pub created_at: String,
     pub updated_at: String,
+    /// User who created the note, in multi-user vaults
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    /// User who last modified the note, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_by: Option<String>,
     pub tags: Vec<String>,
     pub is_pinned: bool,
     pub is_archived: bool,
@@ -256,6 +547,8 @@ impl From<&Note> for NoteMeta {
             slug: note.slug.clone(),
             created_at: note.created_at.to_rfc3339(),
             updated_at: note.updated_at.to_rfc3339(),
+            created_by: note.created_by.clone(),
+            updated_by: note.updated_by.clone(),
             tags: note.tags(),
             is_pinned: note.is_pinned,
             is_archived: note.is_archived,
@@ -271,19 +564,3 @@ fn compute_hash(content: &str) -> String {
     hasher.update(content.as_bytes());
     hex::encode(hasher.finalize())
 }
-
-fn has_camel_case(s: &str) -> bool {
-    let chars: Vec<char> = s.chars().collect();
-    for i in 1..chars.len() {
-        if chars[i].is_uppercase() && chars[i - 1].is_lowercase() {
-            return true;
-        }
-    }
-    false
-}
-
-fn has_snake_case(s: &str) -> bool {
-    s.contains('_')
-        && s.chars()
-            .any(|c| c.is_alphabetic() && c.is_lowercase())
-}
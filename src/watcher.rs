@@ -0,0 +1,172 @@
+//! Filesystem watcher for live reindexing
+//!
+//! While the server runs, edits made to the vault by other tools (editors,
+//! sync clients, `git pull`) are picked up from `notes/` and fed back into the
+//! note store, the full-text index and semantic search. The watcher also
+//! refreshes the store's checksum snapshot periodically and indexes the
+//! conflicted copies the store creates when a note was edited on both sides.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use crate::api::{self, AppState};
+use crate::error::{Error, Result};
+use crate::store::{NoteChange, SyncConflict};
+
+/// How long to wait for a burst of filesystem events to settle
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// How often the conflict-detection baseline is refreshed
+///
+/// Edits made both in Notidium and outside it within one interval are
+/// treated as conflicting.
+const CHECKSUM_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Handle to a running vault watcher; watching stops when dropped
+pub struct VaultWatcher {
+    _debouncer: Debouncer<RecommendedWatcher>,
+    task: JoinHandle<()>,
+}
+
+impl VaultWatcher {
+    /// Start watching the notes directory of the vault behind `state`
+    pub fn spawn(state: AppState) -> Result<Self> {
+        let notes_path = state.store.config().notes_path();
+        std::fs::create_dir_all(&notes_path)?;
+
+        // Events may be reported against the canonical path (e.g. /private/var
+        // on macOS), so watch that and map paths back onto the configured root
+        let watch_root = notes_path.canonicalize()?;
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<DebounceEventResult>();
+        let mut debouncer = new_debouncer(DEBOUNCE, move |result: DebounceEventResult| {
+            let _ = tx.send(result);
+        })
+        .map_err(|e| Error::Watcher(e.to_string()))?;
+
+        debouncer
+            .watcher()
+            .watch(&watch_root, RecursiveMode::Recursive)
+            .map_err(|e| Error::Watcher(e.to_string()))?;
+
+        tracing::info!("Watching {} for changes", notes_path.display());
+
+        let mut conflicts = state.store.subscribe_conflicts();
+        let mut checksum_interval = tokio::time::interval_at(
+            tokio::time::Instant::now() + CHECKSUM_INTERVAL,
+            CHECKSUM_INTERVAL,
+        );
+
+        let task = tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    result = rx.recv() => match result {
+                        Some(Ok(events)) => {
+                            let paths: Vec<PathBuf> = events
+                                .into_iter()
+                                .map(|e| remap(&e.path, &watch_root, &notes_path))
+                                .collect();
+                            sync(&state, &paths).await;
+                        }
+                        Some(Err(e)) => tracing::warn!("File watcher error: {}", e),
+                        None => break,
+                    },
+                    conflict = conflicts.recv() => match conflict {
+                        Ok(conflict) => index_conflicted_copy(&state, &conflict).await,
+                        Err(RecvError::Lagged(missed)) => {
+                            tracing::warn!("Missed {} sync conflict event(s)", missed)
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                    _ = checksum_interval.tick() => {
+                        if let Err(e) = state.store.snapshot_checksums().await {
+                            tracing::warn!("Failed to snapshot checksums: {}", e);
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            _debouncer: debouncer,
+            task,
+        })
+    }
+}
+
+impl Drop for VaultWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Map a path under the watched root onto the configured notes directory
+fn remap(path: &Path, watch_root: &Path, notes_path: &Path) -> PathBuf {
+    match path.strip_prefix(watch_root) {
+        Ok(relative) => notes_path.join(relative),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// Reconcile changed paths and update the search indexes
+async fn sync(state: &AppState, paths: &[PathBuf]) {
+    let changes = match state.store.sync_paths(paths).await {
+        Ok(changes) => changes,
+        Err(e) => {
+            tracing::warn!("Failed to sync changed notes: {}", e);
+            return;
+        }
+    };
+
+    if changes.is_empty() {
+        return;
+    }
+
+    reindex_changes(state, &changes);
+    tracing::info!("Applied {} external note change(s)", changes.len());
+}
+
+/// Update the search indexes for notes the store synced from disk
+pub fn reindex_changes(state: &AppState, changes: &[NoteChange]) {
+    if changes.is_empty() {
+        return;
+    }
+
+    for change in changes {
+        match change {
+            NoteChange::Updated(note) => {
+                tracing::debug!("Reindexing externally changed note {:?}", note.file_path);
+                if let Err(e) = state.fulltext.index_note(note) {
+                    tracing::warn!("Failed to index note: {}", e);
+                }
+                api::remove_note_chunks(state, note.id);
+                api::index_note_chunks(state, note);
+            }
+            NoteChange::Removed(id) => {
+                // The indexing queue purges it from the search indexes
+                tracing::debug!("Externally deleted note {}", id);
+            }
+        }
+    }
+
+    if let Err(e) = state.fulltext.commit() {
+        tracing::warn!("Failed to commit index: {}", e);
+    }
+}
+
+/// Make a conflicted copy searchable alongside the original
+async fn index_conflicted_copy(state: &AppState, conflict: &SyncConflict) {
+    let copy = &conflict.copy;
+    if let Err(e) = state.fulltext.index_note(copy) {
+        tracing::warn!("Failed to index conflicted copy: {}", e);
+    }
+    if let Err(e) = state.fulltext.commit() {
+        tracing::warn!("Failed to commit index: {}", e);
+    }
+    api::index_note_chunks(state, copy);
+}
@@ -0,0 +1,245 @@
+//! Outgoing webhooks
+//!
+//! Each endpoint in `[webhooks]` is sent a JSON POST when a note is created,
+//! captured, updated or deleted, whichever way the change was made: through
+//! the API, MCP, or an edit on disk picked up by the watcher. Deliveries that
+//! fail with a network error, a timeout or a retryable status are tried
+//! again with exponential backoff, so an endpoint that's briefly down still
+//! gets its events.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::{StatusCode, Url};
+use serde::Serialize;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::config::{WebhookConfig, WebhookEndpoint};
+use crate::store::{NoteStore, VaultEvent};
+
+/// Events that can be sent to webhooks
+pub const EVENTS: &[&str] = &["note_created", "note_captured", "note_updated", "note_deleted"];
+
+/// Header naming the event a delivery is for
+const EVENT_HEADER: &str = "X-Notidium-Event";
+
+/// Body of a webhook delivery
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookPayload {
+    /// The event, with its `type`, note `id` and, except for deletions, `title`
+    #[serde(flatten)]
+    pub event: VaultEvent,
+    pub timestamp: DateTime<Utc>,
+    /// Path of the note relative to the notes directory, while it exists
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// Whether `endpoint` wants events of `kind`
+pub fn wants(endpoint: &WebhookEndpoint, kind: &str) -> bool {
+    EVENTS.contains(&kind)
+        && (endpoint.events.is_empty() || endpoint.events.iter().any(|e| e == kind))
+}
+
+/// Wait before retrying after `failures` failed attempts
+pub fn backoff(config: &WebhookConfig, failures: u32) -> Duration {
+    let factor = 2u64.saturating_pow(failures.saturating_sub(1));
+    Duration::from_millis(config.retry_delay_ms.saturating_mul(factor))
+}
+
+/// Whether a delivery answered with `status` is worth trying again
+fn is_retryable(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Send the store's note events to the configured endpoints in the
+/// background; `None` when there are no endpoints
+pub fn spawn(store: Arc<NoteStore>, config: &WebhookConfig) -> Option<tokio::task::JoinHandle<()>> {
+    let endpoints: Vec<(Url, WebhookEndpoint)> = config
+        .endpoints
+        .iter()
+        .filter_map(|endpoint| {
+            for event in endpoint.events.iter().filter(|e| !EVENTS.contains(&e.as_str())) {
+                tracing::warn!("Webhook {} filters on unknown event {:?}", endpoint.url, event);
+            }
+            match Url::parse(&endpoint.url) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => Some((url, endpoint.clone())),
+                _ => {
+                    tracing::warn!("Skipping webhook with invalid URL {:?}", endpoint.url);
+                    None
+                }
+            }
+        })
+        .collect();
+    if endpoints.is_empty() {
+        return None;
+    }
+
+    let client = match reqwest::Client::builder()
+        .user_agent(concat!("notidium/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(config.timeout_secs))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::warn!("Webhooks disabled: {}", e);
+            return None;
+        }
+    };
+
+    let config = Arc::new(config.clone());
+    let mut events = store.subscribe_events();
+    Some(tokio::spawn(async move {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::warn!("Webhooks fell behind and dropped {} events", missed);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let kind = event.kind();
+            let targets: Vec<&Url> = endpoints
+                .iter()
+                .filter(|(_, endpoint)| wants(endpoint, kind))
+                .map(|(url, _)| url)
+                .collect();
+            if targets.is_empty() {
+                continue;
+            }
+
+            let note = store.get(event.note_id()).await.filter(|n| !n.is_deleted);
+            let payload = WebhookPayload {
+                timestamp: Utc::now(),
+                path: note
+                    .as_ref()
+                    .map(|n| n.file_path.to_string_lossy().replace('\\', "/")),
+                tags: note.map(|n| n.tags()).unwrap_or_default(),
+                event,
+            };
+            let body = match serde_json::to_vec(&payload) {
+                Ok(body) => body,
+                Err(e) => {
+                    tracing::warn!("Failed to serialize webhook payload: {}", e);
+                    continue;
+                }
+            };
+
+            // Each endpoint is delivered to on its own, so a slow or failing
+            // one doesn't hold up the others
+            for url in targets {
+                tokio::spawn(deliver(
+                    client.clone(),
+                    config.clone(),
+                    url.clone(),
+                    kind,
+                    body.clone(),
+                ));
+            }
+        }
+    }))
+}
+
+/// POST `body` to `url`, retrying as configured
+async fn deliver(
+    client: reqwest::Client,
+    config: Arc<WebhookConfig>,
+    url: Url,
+    kind: &'static str,
+    body: Vec<u8>,
+) {
+    let attempts = config.max_attempts.max(1);
+    for attempt in 1..=attempts {
+        let result = client
+            .post(url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, kind)
+            .body(body.clone())
+            .send()
+            .await;
+        let error = match result {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) if !is_retryable(response.status()) => {
+                tracing::warn!("Webhook {} rejected {}: {}", url, kind, response.status());
+                return;
+            }
+            Ok(response) => response.status().to_string(),
+            Err(e) => e.to_string(),
+        };
+
+        if attempt == attempts {
+            tracing::warn!(
+                "Giving up on webhook {} for {} after {} attempts: {}",
+                url,
+                kind,
+                attempts,
+                error
+            );
+        } else {
+            let wait = backoff(&config, attempt);
+            tracing::debug!("Webhook {} failed ({}), retrying in {:?}", url, error, wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_filters() {
+        let all = WebhookEndpoint {
+            url: "http://localhost/hook".to_string(),
+            events: Vec::new(),
+        };
+        assert!(wants(&all, "note_created"));
+        assert!(wants(&all, "note_captured"));
+        assert!(!wants(&all, "note_indexed"));
+
+        let deletions = WebhookEndpoint {
+            events: vec!["note_deleted".to_string()],
+            ..all
+        };
+        assert!(wants(&deletions, "note_deleted"));
+        assert!(!wants(&deletions, "note_updated"));
+    }
+
+    #[test]
+    fn test_backoff_doubles() {
+        let config = WebhookConfig {
+            retry_delay_ms: 500,
+            ..WebhookConfig::default()
+        };
+        let waits: Vec<_> = (1..=4).map(|n| backoff(&config, n).as_millis()).collect();
+        assert_eq!(waits, [500, 1000, 2000, 4000]);
+        assert!(is_retryable(StatusCode::BAD_GATEWAY));
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_retryable(StatusCode::NOT_FOUND));
+    }
+
+    #[test]
+    fn test_payload() {
+        let id = uuid::Uuid::from_u128(7);
+        let payload = WebhookPayload {
+            event: VaultEvent::NoteCaptured {
+                id,
+                title: "Capture".to_string(),
+            },
+            timestamp: Utc::now(),
+            path: Some("inbox/capture.md".to_string()),
+            tags: Vec::new(),
+        };
+        let json = serde_json::to_value(&payload).unwrap();
+        assert_eq!(json["type"], "note_captured");
+        assert_eq!(json["id"], id.to_string());
+        assert_eq!(json["path"], "inbox/capture.md");
+        assert!(json.get("tags").is_none());
+    }
+}
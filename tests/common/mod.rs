@@ -0,0 +1,354 @@
+//! In-process server for end-to-end tests
+//!
+//! [`TestServer`] runs the full router, MCP included, over a fresh vault on
+//! an ephemeral port, with [`MockBackend`] standing in for the embedding
+//! models so semantic search works offline. [`TestClient`] talks to it over
+//! real HTTP, like the web UI or any other client would.
+
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest::{Method, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tempfile::TempDir;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+use notidium::api::{
+    self, AppState, AttachmentResponse, CreateNoteRequest, NoteResponse, SearchResponse,
+    UploadAttachmentRequest,
+};
+use notidium::config::Config;
+use notidium::embed::{Chunker, Embedder, EmbedderBackend};
+use notidium::error::Result;
+use notidium::mcp::{McpAuth, McpTransport, ToolFilter};
+use notidium::search::{FullTextIndex, SemanticSearch};
+use notidium::store::{NoteStore, Users, VectorStore};
+
+/// How long to wait for the server before failing a test
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Embeds text as hashed word counts, so texts sharing words are similar
+pub struct MockBackend;
+
+impl MockBackend {
+    pub const DIMENSION: usize = 64;
+
+    /// Embedder using the mock for prose and code alike
+    pub fn embedder() -> Embedder {
+        Embedder::from_backends("mock", Box::new(MockBackend), None)
+    }
+
+    fn embed_text(text: &str) -> Vec<f32> {
+        let mut embedding = vec![0.0; Self::DIMENSION];
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+            // FNV-1a, stable across runs unlike the std hasher
+            let hash = word
+                .to_lowercase()
+                .bytes()
+                .fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+            embedding[(hash % Self::DIMENSION as u64) as usize] += 1.0;
+        }
+        let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            embedding.iter_mut().for_each(|x| *x /= norm);
+        }
+        embedding
+    }
+}
+
+#[async_trait]
+impl EmbedderBackend for MockBackend {
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|text| Self::embed_text(text)).collect())
+    }
+
+    fn dimension(&self) -> usize {
+        Self::DIMENSION
+    }
+}
+
+/// The full HTTP server over a temporary vault, stopped when dropped
+pub struct TestServer {
+    pub addr: SocketAddr,
+    pub state: AppState,
+    pub config: Config,
+    /// Taken by [`TestServer::restart`]
+    _vault: Option<TempDir>,
+    server: JoinHandle<()>,
+}
+
+impl TestServer {
+    /// Start a server with the default configuration
+    pub async fn start() -> Self {
+        Self::start_with(|_| {}).await
+    }
+
+    /// Start a server with the configuration `configure` leaves
+    pub async fn start_with(configure: impl FnOnce(&mut Config)) -> Self {
+        let vault = TempDir::new().expect("Failed to create temp dir");
+        let mut config = Config {
+            vault_path: vault.path().to_path_buf(),
+            ..Config::default()
+        };
+        configure(&mut config);
+        config.init_vault().expect("Failed to init vault");
+        Self::start_in(vault, config).await
+    }
+
+    /// Stop the server and start a new one over the same vault, as after a
+    /// process restart; users are read again and nothing cached survives
+    pub async fn restart(mut self) -> Self {
+        let vault = self._vault.take().expect("vault is kept until drop");
+        let config = self.config.clone();
+        drop(self);
+
+        // The old state lets go of the index writer once its tasks wind down
+        tokio::time::timeout(TIMEOUT, async {
+            while FullTextIndex::open_with(&config.tantivy_path(), &config.search).is_err() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Old server didn't stop");
+        Self::start_in(vault, config).await
+    }
+
+    async fn start_in(vault: TempDir, config: Config) -> Self {
+        let store = Arc::new(NoteStore::new(config.clone()));
+        store.load_all().await.expect("Failed to load notes");
+        let fulltext = Arc::new(
+            FullTextIndex::open_with(&config.tantivy_path(), &config.search)
+                .expect("Failed to open fulltext index"),
+        );
+        let embedder = Arc::new(MockBackend::embedder());
+        let chunker = Arc::new(Chunker::default());
+        let vectors = VectorStore::open(&config.vectors_path())
+            .await
+            .expect("Failed to open vector store");
+        let semantic = Arc::new(RwLock::new(
+            SemanticSearch::with_index(embedder.clone(), config.search.semantic_index)
+                .with_vectors(Arc::new(vectors)),
+        ));
+        let indexer = api::IndexQueue::spawn(store.clone(), fulltext.clone(), semantic.clone(), embedder.clone(), chunker.clone());
+
+        let state = AppState {
+            store,
+            fulltext,
+            semantic,
+            embedder,
+            chunker,
+            attachments_path: config.attachments_path(),
+            users: Arc::new(Users::load(&config.users_path()).expect("Failed to load users")),
+            sessions: Arc::new(api::Sessions::new(&config.auth)),
+            mcp_auth: Arc::new(McpAuth::new(&config.mcp)),
+            api_token: Arc::new(api::ApiToken::new(&config.auth)),
+            mcp_tools: ToolFilter::new(&config.mcp, McpTransport::Http),
+            indexer,
+        };
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind");
+        let addr = listener.local_addr().unwrap();
+        let router = api::create_router_with_mcp(state.clone());
+        let server = tokio::spawn(async move {
+            axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .expect("Server failed");
+        });
+
+        Self {
+            addr,
+            state,
+            config,
+            _vault: Some(vault),
+            server,
+        }
+    }
+
+    /// Add a user with `scopes`, returning their API key; servers read
+    /// users when they start, so it works after [`TestServer::restart`]
+    pub fn add_user(&self, name: &str, scopes: &[&str]) -> String {
+        let path = self.config.users_path();
+        let mut users = Users::load(&path).expect("Failed to load users");
+        let key = users
+            .add(name, scopes.iter().map(|s| s.parse().unwrap()).collect())
+            .expect("Failed to add user");
+        users.save(&path).expect("Failed to save users");
+        key
+    }
+
+    /// Client without credentials
+    pub fn client(&self) -> TestClient {
+        TestClient {
+            http: reqwest::Client::new(),
+            base: format!("http://{}", self.addr),
+            token: None,
+        }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// HTTP client for a [`TestServer`], failing the test on unexpected answers
+#[derive(Clone)]
+pub struct TestClient {
+    http: reqwest::Client,
+    base: String,
+    token: Option<String>,
+}
+
+impl TestClient {
+    /// Send `token` as a bearer token with every request
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    /// URL of `path`, with `query` appended
+    pub fn url(&self, path: &str, query: &[(&str, &str)]) -> Url {
+        let mut url = Url::parse(&format!("{}{}", self.base, path)).unwrap();
+        if !query.is_empty() {
+            url.query_pairs_mut().extend_pairs(query);
+        }
+        url
+    }
+
+    /// Send a request with an optional JSON body
+    pub async fn send(&self, method: Method, url: Url, body: Option<&impl Serialize>) -> reqwest::Response {
+        let mut request = self.http.request(method, url);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        if let Some(body) = body {
+            request = request
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(serde_json::to_vec(body).unwrap());
+        }
+        tokio::time::timeout(TIMEOUT, request.send())
+            .await
+            .expect("Request timed out")
+            .expect("Request failed")
+    }
+
+    /// Status of a body-less request to `path`
+    pub async fn status(&self, method: Method, path: &str) -> StatusCode {
+        self.send(method, self.url(path, &[]), None::<&()>).await.status()
+    }
+
+    /// GET `path` and parse the JSON answer
+    pub async fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, &str)]) -> T {
+        let response = self.send(Method::GET, self.url(path, query), None::<&()>).await;
+        parse(response).await
+    }
+
+    /// POST `body` to `path` and parse the JSON answer
+    pub async fn post<T: DeserializeOwned>(&self, path: &str, body: &impl Serialize) -> T {
+        let response = self.send(Method::POST, self.url(path, &[]), Some(body)).await;
+        parse(response).await
+    }
+
+    pub async fn create_note(&self, title: &str, content: &str, tags: &[&str]) -> NoteResponse {
+        let request = CreateNoteRequest {
+            title: title.to_string(),
+            content: content.to_string(),
+            tags: Some(tags.iter().map(|t| t.to_string()).collect()),
+            folder: None,
+        };
+        self.post("/api/notes", &request).await
+    }
+
+    pub async fn get_note(&self, id: &str) -> NoteResponse {
+        self.get(&format!("/api/notes/{}", id), &[]).await
+    }
+
+    /// Search in `mode`: `fulltext`, `semantic` or `hybrid`
+    pub async fn search(&self, query: &str, mode: &str) -> SearchResponse {
+        self.get("/api/search", &[("q", query), ("mode", mode)]).await
+    }
+
+    pub async fn upload_attachment(&self, data: &[u8], mime_type: &str) -> AttachmentResponse {
+        use base64::Engine;
+        let request = UploadAttachmentRequest {
+            data: base64::engine::general_purpose::STANDARD.encode(data),
+            mime_type: mime_type.to_string(),
+            filename: None,
+        };
+        self.post("/api/attachments", &request).await
+    }
+
+    /// Subscribe to `/api/events`
+    pub async fn events(&self) -> EventStream {
+        let response = self.send(Method::GET, self.url("/api/events", &[]), None::<&()>).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        EventStream {
+            response,
+            buffer: String::new(),
+        }
+    }
+}
+
+/// Parse a successful JSON answer, failing with its body otherwise
+async fn parse<T: DeserializeOwned>(response: reqwest::Response) -> T {
+    let status = response.status();
+    let body = response.bytes().await.expect("Failed to read body");
+    assert!(status.is_success(), "{}: {}", status, String::from_utf8_lossy(&body));
+    serde_json::from_slice(&body).unwrap_or_else(|e| panic!("{}: {}", e, String::from_utf8_lossy(&body)))
+}
+
+/// Server-sent events as they arrive
+pub struct EventStream {
+    response: reqwest::Response,
+    buffer: String,
+}
+
+impl EventStream {
+    /// Next event's name and JSON data, skipping keep-alives
+    pub async fn next(&mut self) -> (String, serde_json::Value) {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let block: String = self.buffer.drain(..end + 2).collect();
+                let mut name = String::new();
+                let mut data = String::new();
+                for line in block.lines() {
+                    if let Some(value) = line.strip_prefix("event:") {
+                        name = value.trim().to_string();
+                    } else if let Some(value) = line.strip_prefix("data:") {
+                        data.push_str(value.trim());
+                    }
+                }
+                if name.is_empty() {
+                    continue;
+                }
+                let data = serde_json::from_str(&data).unwrap_or(serde_json::Value::String(data));
+                return (name, data);
+            }
+            let chunk = tokio::time::timeout(TIMEOUT, self.response.chunk())
+                .await
+                .expect("No event arrived")
+                .expect("Event stream failed")
+                .expect("Event stream ended");
+            self.buffer.push_str(&String::from_utf8_lossy(&chunk));
+        }
+    }
+
+    /// Wait for the next event named `name`
+    pub async fn next_named(&mut self, name: &str) -> serde_json::Value {
+        loop {
+            let (event, data) = self.next().await;
+            if event == name {
+                return data;
+            }
+        }
+    }
+}
@@ -0,0 +1,794 @@
+//! End-to-end tests against the HTTP server
+//!
+//! Run with: cargo test --test http_test
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::{MockBackend, TestServer};
+use notidium::api::{
+    generate_api_token, AnnotationsResponse, CreateNoteRequest, IndexQueue, IndexStatus, NoteResponse, HealthResponse, RefreshResponse, RegexSearchResponse, SearchFeedbackResponse,
+    SearchResponse, StarredResponse, TagFacetsResponse, UploadAttachmentRequest,
+};
+use notidium::embed::{Embedder, EmbedderBackend};
+use notidium::search::SearchMode;
+use notidium::store::Users;
+use notidium::types::Annotation;
+use reqwest::{Method, StatusCode};
+
+#[tokio::test]
+async fn test_health() {
+    let server = TestServer::start().await;
+    let health: HealthResponse = server.client().get("/health", &[]).await;
+    assert_eq!(health.status, "ok");
+}
+
+#[tokio::test]
+async fn test_semantic_search_enriches_results() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let mut events = client.events().await;
+
+    let rust = client
+        .create_note("Ownership", "Rust ownership and borrowing rules for references.", &["rust"])
+        .await;
+    let garden = client
+        .create_note("Tomatoes", "Tomatoes want full sun and steady watering.", &["garden"])
+        .await;
+    // Notes are embedded in the background
+    for _ in 0..2 {
+        events.next_named("note_indexed").await;
+    }
+
+    let response = client.search("borrowing references ownership", "semantic").await;
+    assert_eq!(response.results.len(), 2);
+    let top = &response.results[0];
+    assert_eq!(top.note_id, rust.id);
+    assert_eq!(top.title, "Ownership");
+    assert_eq!(top.tags, vec!["rust"]);
+    assert_eq!(top.updated_at.as_deref(), Some(rust.updated_at.as_str()));
+    assert_eq!(response.results[1].note_id, garden.id);
+
+    let fulltext = client.search("tomatoes", "fulltext").await;
+    assert_eq!(fulltext.results[0].note_id, garden.id);
+}
+
+#[tokio::test]
+async fn test_attachment_roundtrip() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let png = b"\x89PNG\r\n\x1a\nnot really an image";
+
+    let attachment = client.upload_attachment(png, "image/png").await;
+    assert!(attachment.filename.ends_with(".png"));
+    assert!(attachment.markdown.contains(&attachment.url));
+
+    let response = client.send(Method::GET, client.url(&attachment.url, &[]), None::<&()>).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[reqwest::header::CONTENT_TYPE], "image/png");
+    assert_eq!(response.bytes().await.unwrap().as_ref(), png);
+}
+
+/// Create a note in `folder` as `client`
+async fn create_in(client: &common::TestClient, folder: &str, title: &str, content: &str) -> NoteResponse {
+    let request = CreateNoteRequest {
+        title: title.to_string(),
+        content: content.to_string(),
+        tags: None,
+        folder: Some(folder.to_string()),
+    };
+    client.post("/api/notes", &request).await
+}
+
+#[tokio::test]
+async fn test_read_scoped_key_attachments() {
+    use base64::Engine;
+
+    let server = TestServer::start().await;
+    let admin = server.client();
+    let png = b"\x89PNG\r\n\x1a\nnot really an image";
+    let report = admin.upload_attachment(png, "image/png").await;
+    let payroll = admin.upload_attachment(png, "image/png").await;
+    create_in(&admin, "reports", "Chart", &report.markdown).await;
+    create_in(&admin, "hr", "Payroll", &payroll.markdown).await;
+    let key = server.add_user("reader", &["read:reports/"]);
+
+    let server = server.restart().await;
+    let reader = server.client().with_token(&key);
+    assert_eq!(reader.status(Method::GET, &report.url).await, StatusCode::OK);
+    assert_eq!(reader.status(Method::GET, &payroll.url).await, StatusCode::FORBIDDEN);
+    assert_eq!(
+        reader.status(Method::GET, "/api/attachments/missing.png").await,
+        StatusCode::FORBIDDEN
+    );
+
+    let upload = UploadAttachmentRequest {
+        data: base64::engine::general_purpose::STANDARD.encode(png),
+        mime_type: "image/png".to_string(),
+        filename: None,
+    };
+    let response = reader.send(Method::POST, reader.url("/api/attachments", &[]), Some(&upload)).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_scoped_key_lists_only_its_attachments() {
+    let server = TestServer::start().await;
+    let admin = server.client();
+    let png = b"\x89PNG\r\n\x1a\nnot really an image";
+    let report = admin.upload_attachment(png, "image/png").await;
+    let payroll = admin.upload_attachment(png, "image/png").await;
+    create_in(&admin, "reports", "Chart", &report.markdown).await;
+    create_in(&admin, "hr", "Payroll", &payroll.markdown).await;
+    let key = server.add_user("reader", &["read:reports/"]);
+    let admin_key = server.add_user("admin", &[]);
+
+    let server = server.restart().await;
+    let all: serde_json::Value = server.client().with_token(&admin_key).get("/api/attachments", &[]).await;
+    assert_eq!(all["total"], 2);
+
+    let reader = server.client().with_token(&key);
+    let listed: serde_json::Value = reader.get("/api/attachments", &[]).await;
+    assert_eq!(listed["total"], 1);
+    assert_eq!(listed["attachments"][0]["filename"], report.filename.as_str());
+
+    // Notes the key can't see may embed any attachment
+    assert_eq!(reader.status(Method::DELETE, &report.url).await, StatusCode::FORBIDDEN);
+    assert_eq!(reader.status(Method::POST, "/api/attachments/gc").await, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_tag_scoped_key_cannot_retag_notes_out_of_its_scope() {
+    let server = TestServer::start_with(|config| config.history.enabled = true).await;
+    let admin = server.client();
+    let note = admin.create_note("Weekly", "Numbers", &["hr"]).await;
+    let path = format!("/api/notes/{}", note.id);
+    let retag = |tags: &[&str]| serde_json::json!({ "tags": tags });
+    let response = admin.send(Method::PUT, admin.url(&path, &[]), Some(&retag(&["reports"]))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let key = server.add_user("writer", &["write:#reports"]);
+
+    let server = server.restart().await;
+    let writer = server.client().with_token(&key);
+    let response = writer.send(Method::PUT, writer.url(&path, &[]), Some(&retag(&["hr"]))).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = writer.send(Method::PUT, writer.url(&path, &[]), Some(&retag(&["reports", "q3"]))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The first version was tagged hr only
+    let versions: serde_json::Value = writer.get(&format!("{}/versions", path), &[]).await;
+    let first = versions["versions"].as_array().unwrap().last().unwrap()["rev"].as_str().unwrap();
+    let restore = format!("{}/versions/{}/restore", path, first);
+    assert_eq!(writer.status(Method::POST, &restore).await, StatusCode::FORBIDDEN);
+    assert_eq!(writer.get_note(&note.id).await.tags, vec!["reports", "q3"]);
+}
+
+#[tokio::test]
+async fn test_api_token_required() {
+    let (token, hash) = generate_api_token();
+    let server = TestServer::start_with(|config| config.auth.api_token_hash = Some(hash)).await;
+
+    let anonymous = server.client();
+    assert_eq!(anonymous.status(Method::GET, "/api/notes").await, StatusCode::UNAUTHORIZED);
+    assert_eq!(anonymous.status(Method::POST, "/mcp").await, StatusCode::UNAUTHORIZED);
+    assert_eq!(anonymous.status(Method::GET, "/health").await, StatusCode::OK);
+
+    let client = server.client().with_token(&token);
+    let note = client.create_note("Private", "Only with the token", &[]).await;
+    assert_eq!(client.get_note(&note.id).await.title, "Private");
+
+    let wrong = server.client().with_token("ntdt_wrong");
+    assert_eq!(wrong.status(Method::GET, "/api/notes").await, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_scoped_key_cannot_purge_trashed_note_outside_its_folder() {
+    let server = TestServer::start().await;
+    let request = CreateNoteRequest {
+        title: "Payroll".to_string(),
+        content: "Salaries".to_string(),
+        tags: None,
+        folder: Some("b".to_string()),
+    };
+    let note: NoteResponse = server.client().post("/api/notes", &request).await;
+    let path = format!("/api/notes/{}", note.id);
+    assert_eq!(server.client().status(Method::DELETE, &path).await, StatusCode::NO_CONTENT);
+
+    let users_path = server.config.users_path();
+    let mut users = Users::load(&users_path).unwrap();
+    let key = users.add("bot", vec!["write:a/".parse().unwrap()]).unwrap();
+    users.save(&users_path).unwrap();
+
+    // Trashed notes aren't cached after a restart
+    let server = server.restart().await;
+    let bot = server.client().with_token(&key);
+    let url = bot.url(&path, &[("permanent", "true")]);
+    assert_eq!(bot.send(Method::DELETE, url, None::<&()>).await.status(), StatusCode::NOT_FOUND);
+
+    let id = note.id.parse().unwrap();
+    assert!(server.state.store.get_trashed(id).await.is_some());
+}
+
+#[tokio::test]
+async fn test_events_follow_note_lifecycle() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let mut events = client.events().await;
+
+    let note = client.create_note("Short-lived", "Soon gone", &[]).await;
+    let created = events.next_named("note_created").await;
+    assert_eq!(created["id"], note.id);
+    assert_eq!(created["title"], "Short-lived");
+
+    let path = format!("/api/notes/{}", note.id);
+    assert!(client.status(Method::DELETE, &path).await.is_success());
+    let deleted = events.next_named("note_deleted").await;
+    assert_eq!(deleted["id"], note.id);
+
+    let trash: serde_json::Value = client.get("/api/trash", &[]).await;
+    assert_eq!(trash["total"], 1);
+}
+
+#[tokio::test]
+async fn test_search_query_filters() {
+    let server = TestServer::start().await;
+    let client = server.client();
+
+    let rust = client.create_note("Rust deploys", "How we deploy the service", &["rust"]).await;
+    client.create_note("Go deploys", "How we deploy the other service", &["go"]).await;
+
+    let results = client.search("tag:rust deploy", "fulltext").await;
+    let ids: Vec<&str> = results.results.iter().map(|r| r.note_id.as_str()).collect();
+    assert_eq!(ids, vec![rust.id.as_str()]);
+
+    // Filters alone list the notes that pass them
+    let results = client.search("tag:RUST", "fulltext").await;
+    assert_eq!(results.total, 1);
+    assert_eq!(results.results[0].title, "Rust deploys");
+    assert_eq!(client.search(r#""the other service""#, "fulltext").await.total, 1);
+    assert_eq!(client.search("deploy created:<2000-01-01", "fulltext").await.total, 0);
+
+    let url = client.url("/api/search", &[("q", "created:>soon")]);
+    let response = client.send(Method::GET, url, None::<&()>).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_search_tags_parameter() {
+    let server = TestServer::start().await;
+    let client = server.client();
+
+    let rust = client.create_note("Rust deploys", "How we deploy the service", &["rust", "ops"]).await;
+    let go = client.create_note("Go deploys", "How we deploy the other service", &["go"]).await;
+    client.create_note("Shell deploys", "How we deploy by hand", &["ops"]).await;
+
+    let titles = |response: SearchResponse| {
+        let mut titles: Vec<String> = response.results.into_iter().map(|r| r.title).collect();
+        titles.sort();
+        titles
+    };
+    let all: SearchResponse = client.get("/api/search", &[("q", "deploy"), ("tags", "rust,ops")]).await;
+    assert_eq!(all.total, 1);
+    assert_eq!(all.results[0].note_id, rust.id);
+
+    let any: SearchResponse = client
+        .get("/api/search", &[("q", "deploy"), ("tags", "rust, go"), ("tag_match", "any")])
+        .await;
+    assert_eq!(titles(any), vec!["Go deploys", "Rust deploys"]);
+
+    // Combined with a tag filter in the query, both must hold
+    let both: SearchResponse = client.get("/api/search", &[("q", "tag:ops deploy"), ("tags", "go")]).await;
+    assert_eq!(both.total, 0);
+
+    // Semantic results are limited to the tagged notes before ranking
+    let mut semantic = SearchResponse { results: Vec::new(), total: 0 };
+    for _ in 0..100 {
+        semantic = client.get("/api/search/semantic", &[("q", "deploy the service"), ("tags", "go"), ("limit", "1")]).await;
+        if semantic.total > 0 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(semantic.total, 1);
+    assert_eq!(semantic.results[0].note_id, go.id);
+}
+
+#[tokio::test]
+async fn test_scoped_key_stats_cover_only_readable_notes() {
+    let server = TestServer::start().await;
+    let admin = server.client();
+    create_in(&admin, "reports", "Nightly", "---\ntags: [ci]\n---\nok").await;
+    create_in(&admin, "hr", "Payroll", "---\ntags: [hr, pay]\n---\nsecret").await;
+    let key = server.add_user("reader", &["read:reports/"]);
+    let admin_key = server.add_user("admin", &[]);
+
+    let server = server.restart().await;
+    let admin = server.client().with_token(&admin_key);
+    let stats: serde_json::Value = admin.get("/api/stats", &[]).await;
+    assert_eq!(stats["note_count"], 2);
+    assert_eq!(stats["tag_count"], 3);
+    assert!(stats["chunk_count"].is_u64());
+    assert_eq!(admin.status(Method::GET, "/api/index/status").await, StatusCode::OK);
+
+    let reader = server.client().with_token(&key);
+    let stats: serde_json::Value = reader.get("/api/stats", &[]).await;
+    assert_eq!(stats["note_count"], 1);
+    assert_eq!(stats["tag_count"], 1);
+    assert!(stats.get("chunk_count").is_none());
+    assert_eq!(reader.status(Method::GET, "/api/index/status").await, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_scoped_key_does_not_see_recent_queries() {
+    let server = TestServer::start().await;
+    let key = server.add_user("reader", &["read:reports/"]);
+    let admin_key = server.add_user("admin", &[]);
+
+    let server = server.restart().await;
+    let admin = server.client().with_token(&admin_key);
+    admin.create_note("Payroll", "Salaries for the hr team", &[]).await;
+    // Searches are only classified once the note has been embedded
+    for _ in 0..100 {
+        let semantic: SearchResponse = admin.get("/api/search/semantic", &[("q", "payroll for the hr team")]).await;
+        if semantic.total > 0 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let classify: serde_json::Value = admin.get("/api/search/classify", &[]).await;
+    assert!(!classify["recent"].as_array().unwrap().is_empty());
+
+    let reader = server.client().with_token(&key);
+    let classify: serde_json::Value = reader.get("/api/search/classify", &[("q", "quarterly report")]).await;
+    assert!(classify["classification"].is_object());
+    assert!(classify["recent"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_search_fuzzy_parameter() {
+    let server = TestServer::start().await;
+    let client = server.client();
+
+    let kubernetes = client.create_note("Cluster setup", "Deploying to Kubernetes with Helm", &[]).await;
+    client.create_note("Cat", "The cat sat on the mat", &[]).await;
+
+    let exact: SearchResponse = client.get("/api/search", &[("q", "kubernets")]).await;
+    assert_eq!(exact.total, 0);
+
+    let fuzzy: SearchResponse = client.get("/api/search", &[("q", "kubernets"), ("fuzzy", "true")]).await;
+    assert_eq!(fuzzy.total, 1);
+    assert_eq!(fuzzy.results[0].note_id, kubernetes.id);
+
+    // Short words would match too many others with a typo
+    let short: SearchResponse = client.get("/api/search", &[("q", "bat"), ("fuzzy", "true")]).await;
+    assert_eq!(short.total, 0);
+}
+
+#[tokio::test]
+async fn test_regex_search() {
+    let server = TestServer::start().await;
+    let client = server.client();
+
+    let runtime = client.create_note("Runtime", "Spawn with tokio::spawn\nthen TOKIO::join", &[]).await;
+    client.create_note("Garden", "Nothing async here", &[]).await;
+
+    let found: RegexSearchResponse = client.get("/api/search/regex", &[("pattern", r"tokio::\w+")]).await;
+    assert_eq!(found.total, 1);
+    assert_eq!(found.matches[0].note_id, runtime.id);
+    assert_eq!(found.matches[0].text, "Spawn with tokio::spawn");
+    assert!(found.matches[0].path.ends_with(".md"));
+    assert!(found.matches[0].line > 0);
+
+    let found: RegexSearchResponse = client
+        .get("/api/search/regex", &[("pattern", "tokio::"), ("ignore_case", "true"), ("fixed_strings", "true")])
+        .await;
+    assert_eq!(found.total, 2);
+    assert!(found.matches[0].line < found.matches[1].line);
+
+    let url = client.url("/api/search/regex", &[("pattern", "(")]);
+    let response = client.send(Method::GET, url, None::<&()>).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_search_date_range_parameters() {
+    let server = TestServer::start().await;
+    let client = server.client();
+
+    client.create_note("Deploy log", "Deployments went fine", &[]).await;
+    client.create_note("Deploy plan", "Deployments to come", &[]).await;
+
+    let today = chrono::Utc::now().date_naive();
+    let yesterday = today.pred_opt().unwrap().to_string();
+    let tomorrow = today.succ_opt().unwrap().to_string();
+    // Wait for the indexing queue to embed both notes
+    for _ in 0..100 {
+        if client.search("deployments", "semantic").await.total == 2 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+
+    let cases = [
+        (vec![("created_after", yesterday.as_str())], 2),
+        (vec![("created_before", yesterday.as_str())], 0),
+        (vec![("updated_after", tomorrow.as_str())], 0),
+        (vec![("updated_after", yesterday.as_str()), ("updated_before", tomorrow.as_str())], 2),
+    ];
+    for mode in ["fulltext", "semantic"] {
+        for (params, expected) in &cases {
+            let mut query = vec![("q", "deployments"), ("mode", mode)];
+            query.extend(params.iter().copied());
+            let total = client.get::<SearchResponse>("/api/search", &query).await.total;
+            assert_eq!(total, *expected, "{:?}", query);
+        }
+    }
+
+    let url = client.url("/api/search", &[("q", "deployments"), ("created_after", "last month")]);
+    let response = client.send(Method::GET, url, None::<&()>).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_search_feedback_demotes_irrelevant_results() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let noisy = client.create_note("Kafka everywhere", "Kafka kafka kafka, all about kafka", &[]).await;
+    let useful = client.create_note("Kafka broker setup", "How we run the kafka broker", &[]).await;
+
+    let ids = |response: SearchResponse| -> Vec<String> { response.results.into_iter().map(|r| r.note_id).collect() };
+    let before = client.search("kafka", "fulltext").await;
+    assert_eq!(ids(before), vec![noisy.id.clone(), useful.id.clone()]);
+
+    for kind in ["irrelevant", "irrelevant", "irrelevant", "clicked"] {
+        let feedback = serde_json::json!({"query": "Kafka  tag:x", "note_id": noisy.id, "kind": kind});
+        let response = client.send(Method::POST, client.url("/api/search/feedback", &[]), Some(&feedback)).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+    let no_words = serde_json::json!({"query": "tag:x", "note_id": noisy.id, "kind": "clicked"});
+    let response = client.send(Method::POST, client.url("/api/search/feedback", &[]), Some(&no_words)).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let after = client.search("kafka", "fulltext").await;
+    assert_eq!(ids(after), vec![useful.id.clone(), noisy.id.clone()]);
+
+    let learned: SearchFeedbackResponse = client.get("/api/search/feedback", &[("term", "KAFKA")]).await;
+    assert_eq!(learned.total, 1);
+    let adjustment = &learned.adjustments[0];
+    assert_eq!((adjustment.term.as_str(), adjustment.note_id.as_str()), ("kafka", noisy.id.as_str()));
+    assert_eq!((adjustment.clicks, adjustment.irrelevant), (1, 3));
+    assert_eq!(adjustment.factor, 0.25);
+    assert_eq!(adjustment.queries, vec!["Kafka tag:x"]);
+
+    assert_eq!(client.status(Method::DELETE, "/api/search/feedback").await, StatusCode::NO_CONTENT);
+    let restored = client.search("kafka", "fulltext").await;
+    assert_eq!(ids(restored), vec![noisy.id, useful.id]);
+}
+
+#[tokio::test]
+async fn test_tag_facets_count_matching_notes() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    client.create_note("Broker", "Running kafka in production", &["ops", "kafka"]).await;
+    client.create_note("Consumers", "Kafka consumer groups", &["kafka", "Dev"]).await;
+    client.create_note("Garden", "Tomatoes and basil", &["home"]).await;
+
+    let facets = |response: TagFacetsResponse| -> Vec<(String, u32)> {
+        response.facets.into_iter().map(|f| (f.tag, f.count)).collect()
+    };
+    let matching: TagFacetsResponse = client.get("/api/tags/facets", &[("q", "kafka")]).await;
+    assert_eq!(matching.matches, 2);
+    assert_eq!(
+        facets(matching),
+        vec![("kafka".to_string(), 2), ("Dev".to_string(), 1), ("ops".to_string(), 1)]
+    );
+
+    let filtered: TagFacetsResponse = client.get("/api/tags/facets", &[("q", "kafka tag:ops")]).await;
+    assert_eq!(filtered.matches, 1);
+
+    let vault: TagFacetsResponse = client.get("/api/tags/facets", &[]).await;
+    assert_eq!(vault.matches, 3);
+    assert_eq!(vault.facets.len(), 4);
+
+    let url = client.url("/api/tags/facets", &[("q", "created:>soon")]);
+    let response = client.send(Method::GET, url, None::<&()>).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_refresh_picks_up_external_changes() {
+    let server = TestServer::start().await;
+    let client = server.client();
+
+    let edited = client.create_note("Edited", "Before the sync", &[]).await;
+    let removed = client.create_note("Removed", "Soon gone", &[]).await;
+
+    // A sync client rewrites the vault behind the server's back
+    let notes = server.config.notes_path();
+    std::fs::write(notes.join("edited.md"), "# Edited\n\nMentions quokka after the sync\n").unwrap();
+    std::fs::remove_file(notes.join("removed.md")).unwrap();
+    std::fs::write(notes.join("synced.md"), "# Synced\n\nAlso mentions quokka\n").unwrap();
+
+    let refresh: RefreshResponse = client.post("/api/admin/refresh", &()).await;
+    assert_eq!(refresh.updated.len(), 2);
+    assert!(refresh.updated.contains(&edited.id));
+    assert_eq!(refresh.removed, vec![removed.id.clone()]);
+    assert_eq!(client.search("quokka", "fulltext").await.total, 2);
+    assert!(client.get_note(&edited.id).await.content.contains("quokka"));
+
+    let again: RefreshResponse = client.post("/api/admin/refresh", &()).await;
+    assert!(again.updated.is_empty() && again.removed.is_empty());
+}
+
+#[tokio::test]
+async fn test_deleted_notes_leave_the_search_index() {
+    let server = TestServer::start().await;
+    let client = server.client();
+
+    let note = client.create_note("Ephemeral", "Mentions zanzibar once", &[]).await;
+    assert_eq!(client.search("zanzibar", "fulltext").await.total, 1);
+
+    // The indexing queue purges it shortly after
+    let path = format!("/api/notes/{}", note.id);
+    assert!(client.status(Method::DELETE, &path).await.is_success());
+    let mut total = 1;
+    for _ in 0..100 {
+        total = client.search("zanzibar", "fulltext").await.total;
+        if total == 0 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(total, 0);
+}
+
+#[tokio::test]
+async fn test_configured_default_search_mode() {
+    let server = TestServer::start_with(|config| {
+        config.search.default_mode = Some(SearchMode::Hybrid);
+        config.search.client_modes.insert("http".to_string(), SearchMode::Semantic);
+    })
+    .await;
+    let client = server.client();
+    let mut events = client.events().await;
+    client.create_note("Ownership", "Rust borrowing rules.", &[]).await;
+    client.create_note("Tomatoes", "Tomatoes want full sun.", &[]).await;
+    for _ in 0..2 {
+        events.next_named("note_indexed").await;
+    }
+
+    // Semantic search ranks every note; full-text only the ones matching
+    let default: SearchResponse = client.get("/api/search", &[("q", "borrowing")]).await;
+    assert_eq!(default.total, 2);
+    assert_eq!(client.search("borrowing", "fulltext").await.total, 1);
+}
+
+#[tokio::test]
+async fn test_read_position_returned_with_note() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let note = client
+        .create_note("Reference", "# Reference\n\n## Setup\n\nSteps.\n\n## Usage\n\nMore.", &[])
+        .await;
+    assert!(note.position.is_none());
+
+    let url = client.url(&format!("/api/notes/{}/position", note.id), &[]);
+    let body = serde_json::json!({"heading": "Usage", "line": 7, "scroll": 0.6});
+    let response = client.send(Method::PUT, url.clone(), Some(&body)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let position = client.get_note(&note.id).await.position.expect("Should have a position");
+    assert_eq!(position.heading.as_deref(), Some("Usage"));
+    assert_eq!(position.line, Some(7));
+    assert_eq!(position.scroll, Some(0.6));
+
+    let out_of_range = serde_json::json!({"scroll": 1.5});
+    let response = client.send(Method::PUT, url, Some(&out_of_range)).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_starred_notes() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let first = client.create_note("First", "One", &[]).await;
+    let second = client.create_note("Second", "Two", &[]).await;
+    client.create_note("Third", "Three", &[]).await;
+
+    for note in [&first, &second] {
+        let path = format!("/api/notes/{}/star", note.id);
+        assert_eq!(client.status(Method::PUT, &path).await, StatusCode::NO_CONTENT);
+    }
+    let starred: StarredResponse = client.get("/api/notes/starred", &[]).await;
+    let ids: Vec<&str> = starred.notes.iter().map(|n| n.id.as_str()).collect();
+    assert_eq!(ids, vec![second.id.as_str(), first.id.as_str()]);
+
+    let path = format!("/api/notes/{}/star", second.id);
+    assert_eq!(client.status(Method::DELETE, &path).await, StatusCode::NO_CONTENT);
+    let starred: StarredResponse = client.get("/api/notes/starred", &[]).await;
+    assert_eq!(starred.total, 1);
+    assert_eq!(starred.notes[0].id, first.id);
+
+    let missing = format!("/api/notes/{}/star", uuid::Uuid::new_v4());
+    assert_eq!(client.status(Method::PUT, &missing).await, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_annotations() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let content = "# Plan\n\nShip it.\n\n## Risks\n\nNone known.\n\n## Timeline\n\nNext week.\n";
+    let note = client.create_note("Plan", content, &[]).await;
+    let before = client.get_note(&note.id).await.content;
+    let path = format!("/api/notes/{}/annotations", note.id);
+
+    let on_heading: Annotation = client
+        .post(&path, &serde_json::json!({"body": "What about <the> budget?", "heading": "Risks"}))
+        .await;
+    assert_eq!(on_heading.heading.as_deref(), Some("Risks"));
+    let reply: Annotation = client
+        .post(&path, &serde_json::json!({"body": "Covered", "reply_to": on_heading.id}))
+        .await;
+    assert_eq!(reply.reply_to.as_deref(), Some(on_heading.id.as_str()));
+    assert_eq!(reply.heading.as_deref(), Some("Risks"));
+    let line_count = before.lines().count() as u32;
+    let on_lines: Annotation = client
+        .post(&path, &serde_json::json!({"body": "Too soon", "line_start": line_count}))
+        .await;
+    assert_eq!(on_lines.line_end, Some(line_count));
+
+    for bad in [
+        serde_json::json!({"body": " "}),
+        serde_json::json!({"body": "x", "heading": "Nowhere"}),
+        serde_json::json!({"body": "x", "line_start": 3, "line_end": 2}),
+        serde_json::json!({"body": "x", "line_start": line_count + 1}),
+    ] {
+        let response = client.send(Method::POST, client.url(&path, &[]), Some(&bad)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", bad);
+    }
+
+    let edit = serde_json::json!({"body": "Edited"});
+    let edit_url = client.url(&format!("{}/{}", path, on_lines.id), &[]);
+    let response = client.send(Method::PUT, edit_url, Some(&edit)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Annotation>().await.unwrap().body, "Edited");
+
+    let list: AnnotationsResponse = client.get(&path, &[]).await;
+    assert_eq!(list.total, 3);
+    assert_eq!(client.get_note(&note.id).await.content, before);
+
+    let export = client
+        .send(Method::GET, client.url(&format!("/api/notes/{}/export", note.id), &[]), None::<&()>)
+        .await
+        .text()
+        .await
+        .unwrap();
+    let risks = export.find("<h2>Risks</h2>").unwrap();
+    let margin = export.find("What about &lt;the&gt; budget?").unwrap();
+    let timeline = export.find("<h2>Timeline</h2>").unwrap();
+    assert!(risks < margin && margin < timeline);
+    assert!(export.contains("<div class=\"annotation-reply\">\n<p class=\"annotation-meta\">"));
+    assert!(export.find("Edited").unwrap() > export.find("Next week.").unwrap());
+
+    let thread = format!("{}/{}", path, on_heading.id);
+    assert_eq!(client.status(Method::DELETE, &thread).await, StatusCode::NO_CONTENT);
+    assert_eq!(client.status(Method::DELETE, &thread).await, StatusCode::NOT_FOUND);
+    let list: AnnotationsResponse = client.get(&path, &[]).await;
+    assert_eq!(list.total, 1);
+    assert_eq!(list.annotations[0].id, on_lines.id);
+}
+
+/// Wait for the indexing queue to run out of jobs
+async fn drained(queue: &IndexQueue) -> IndexStatus {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let status = queue.status();
+            if status.pending == 0 {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("Indexing queue didn't drain")
+}
+
+/// Fails every embedding, to exercise the queue's error reporting
+struct FailingBackend;
+
+#[async_trait::async_trait]
+impl EmbedderBackend for FailingBackend {
+    async fn embed(&self, _texts: Vec<String>) -> notidium::error::Result<Vec<Vec<f32>>> {
+        Err(notidium::Error::Embedding("model unavailable".to_string()))
+    }
+
+    fn dimension(&self) -> usize {
+        MockBackend::DIMENSION
+    }
+}
+
+#[tokio::test]
+async fn test_created_note_is_searchable_once_queue_drains() {
+    let server = TestServer::start().await;
+    let client = server.client();
+
+    let note = client
+        .create_note("Lifetimes", "Lifetimes tie references to the values they borrow.", &[])
+        .await;
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let status: serde_json::Value = client.get("/api/index/status", &[]).await;
+            if status["pending"] == 0 && status["completed"].as_u64() >= Some(1) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("Indexing queue didn't drain");
+
+    let response = client.search("lifetimes references borrow", "semantic").await;
+    assert_eq!(response.results.len(), 1);
+    assert_eq!(response.results[0].note_id, note.id);
+}
+
+#[tokio::test]
+async fn test_remove_queued_after_index_wins() {
+    let server = TestServer::start().await;
+    let state = &server.state;
+    let removed = state
+        .store
+        .create("Removed".to_string(), "Embedded, then dropped again.".to_string(), None)
+        .await
+        .unwrap();
+    let kept = state
+        .store
+        .create("Kept".to_string(), "Embedded and left alone.".to_string(), None)
+        .await
+        .unwrap();
+
+    state.indexer.index(&removed);
+    state.indexer.remove(removed.id);
+    state.indexer.index(&kept);
+    drained(&state.indexer).await;
+
+    let indexed = state.semantic.read().await.note_ids();
+    assert!(!indexed.contains(&removed.id));
+    assert!(indexed.contains(&kept.id));
+}
+
+#[tokio::test]
+async fn test_index_status_counts_jobs_and_keeps_last_error() {
+    let server = TestServer::start().await;
+    let state = &server.state;
+    let embedder = Arc::new(Embedder::from_backends("failing", Box::new(FailingBackend), None));
+    let queue = IndexQueue::spawn(
+        state.store.clone(),
+        state.fulltext.clone(),
+        state.semantic.clone(),
+        embedder,
+        state.chunker.clone(),
+    );
+    assert_eq!(queue.status().pending, 0);
+    assert!(queue.status().last_error.is_none());
+
+    let note = state
+        .store
+        .create("Broken".to_string(), "This never gets embedded.".to_string(), None)
+        .await
+        .unwrap();
+    queue.index(&note);
+    queue.remove(note.id);
+    // Finishing a job moves it from pending to completed in one step
+    let status = queue.status();
+    assert_eq!(status.pending + status.completed, 2);
+
+    let status = drained(&queue).await;
+    assert_eq!(status.completed, 2);
+    assert!(status.current.is_none());
+    let error = status.last_error.expect("Embedding failure should be reported");
+    assert_eq!(error.note_id, note.id);
+    assert!(error.message.contains("model unavailable"), "{}", error.message);
+}
@@ -1,12 +1,14 @@
 //! Integration tests for Notidium core functionality
 //! Tests the store and search components
 
+use std::path::PathBuf;
 use std::sync::Arc;
 use tempfile::TempDir;
 
 use notidium::config::Config;
 use notidium::search::FullTextIndex;
 use notidium::store::NoteStore;
+use notidium::types::NoteSort;
 
 /// Simple test fixture for store-only tests (no embedder needed)
 struct StoreTestFixture {
@@ -137,7 +139,9 @@ mod store_tests {
 
         let retrieved = fixture.store.get_by_title("Unique Title Here").await;
         assert!(retrieved.is_some());
-        assert_eq!(retrieved.unwrap().title, "Unique Title Here");
+        let (note, alias) = retrieved.unwrap();
+        assert_eq!(note.title, "Unique Title Here");
+        assert_eq!(alias, None);
     }
 
     #[tokio::test]
@@ -151,7 +155,7 @@ mod store_tests {
         // Search with partial title (lowercase)
         let retrieved = fixture.store.get_by_title("long note").await;
         assert!(retrieved.is_some());
-        assert_eq!(retrieved.unwrap().title, "My Long Note Title");
+        assert_eq!(retrieved.unwrap().0.title, "My Long Note Title");
     }
 
     #[tokio::test]
@@ -162,6 +166,40 @@ mod store_tests {
         assert!(retrieved.is_none());
     }
 
+    #[tokio::test]
+    async fn test_get_note_by_alias() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture
+            .create_test_note("Kubernetes", "---\naliases: [k8s, Kube Cluster]\n---\n\nContent", None)
+            .await;
+
+        let (note, alias) = fixture.store.get_by_title("K8s").await.unwrap();
+        assert_eq!(note.id, id);
+        assert_eq!(alias.as_deref(), Some("k8s"));
+
+        let (note, alias) = fixture.store.get_by_title("kube clustr").await.unwrap();
+        assert_eq!(note.id, id);
+        assert_eq!(alias.as_deref(), Some("Kube Cluster"));
+    }
+
+    #[tokio::test]
+    async fn test_alias_edits_update_title_lookup() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Postgres", "Content", None).await;
+        assert!(fixture.store.get_by_title("pgsql").await.is_none());
+
+        fixture
+            .store
+            .update(id, "---\naliases: [pgsql]\n---\n\n# Postgres\n\nContent".to_string())
+            .await
+            .unwrap();
+        let (note, alias) = fixture.store.get_by_title("pgsql").await.unwrap();
+        assert_eq!(note.id, id);
+        assert_eq!(alias.as_deref(), Some("pgsql"));
+    }
+
     #[tokio::test]
     async fn test_list_notes_empty() {
         let fixture = StoreTestFixture::new().await;
@@ -193,15 +231,15 @@ mod store_tests {
         }
 
         // Get first 3
-        let notes = fixture.store.list_paginated(0, 3, None).await;
+        let notes = fixture.store.list_paginated(0, 3, None, None, NoteSort::default()).await;
         assert_eq!(notes.len(), 3);
 
         // Get next 3
-        let notes = fixture.store.list_paginated(3, 3, None).await;
+        let notes = fixture.store.list_paginated(3, 3, None, None, NoteSort::default()).await;
         assert_eq!(notes.len(), 3);
 
         // Get all 10
-        let notes = fixture.store.list_paginated(0, 100, None).await;
+        let notes = fixture.store.list_paginated(0, 100, None, None, NoteSort::default()).await;
         assert_eq!(notes.len(), 10);
     }
 
@@ -222,12 +260,29 @@ mod store_tests {
 
         let notes = fixture
             .store
-            .list_paginated(0, 100, Some("important"))
+            .list_paginated(0, 100, Some("important"), None, NoteSort::default())
             .await;
         assert_eq!(notes.len(), 1);
         assert_eq!(notes[0].title, "Tagged Note");
     }
 
+    #[tokio::test]
+    async fn test_list_notes_sorted() {
+        use notidium::types::{SortField, SortOrder};
+
+        let fixture = StoreTestFixture::new().await;
+        for title in ["beta", "Alpha", "gamma"] {
+            fixture.create_test_note(title, "Content", None).await;
+        }
+
+        let titles = |notes: Vec<notidium::types::Note>| -> Vec<String> { notes.into_iter().map(|n| n.title).collect() };
+        let list = |field, order| fixture.store.list_paginated(0, 100, None, None, NoteSort::new(field, order));
+        assert_eq!(titles(list(SortField::Title, None).await), vec!["Alpha", "beta", "gamma"]);
+        assert_eq!(titles(list(SortField::Title, Some(SortOrder::Desc)).await), vec!["gamma", "beta", "Alpha"]);
+        assert_eq!(titles(list(SortField::CreatedAt, Some(SortOrder::Asc)).await), vec!["beta", "Alpha", "gamma"]);
+        assert_eq!(titles(list(SortField::Relevance, None).await), vec!["gamma", "Alpha", "beta"]);
+    }
+
     #[tokio::test]
     async fn test_update_note() {
         let fixture = StoreTestFixture::new().await;
@@ -812,6 +867,66 @@ mod fulltext_tests {
 
         assert_eq!(results.len(), 2, "Should find both Rust notes");
     }
+
+    #[tokio::test]
+    async fn test_fulltext_phrases_and_scoped_fields() {
+        let fixture = StoreTestFixture::new().await;
+        let runtime = fixture
+            .store
+            .create(
+                "Async runtime".to_string(),
+                "Tokio is an async runtime for Rust.".to_string(),
+                Some(vec!["draft".to_string()]),
+            )
+            .await
+            .unwrap();
+        let scattered = fixture
+            .store
+            .create(
+                "Runtime notes".to_string(),
+                "The runtime starts tasks; async code awaits them.".to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        for note in [&runtime, &scattered] {
+            fixture.fulltext.index_note(note).unwrap();
+        }
+        fixture.fulltext.commit().unwrap();
+
+        let ids = |query: &str| {
+            let mut ids: Vec<String> = fixture
+                .fulltext
+                .search(query, 10)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.note_id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        let both = {
+            let mut both = vec![runtime.id.to_string(), scattered.id.to_string()];
+            both.sort();
+            both
+        };
+
+        assert_eq!(ids("async runtime"), both);
+        assert_eq!(ids("\"async runtime\""), vec![runtime.id.to_string()]);
+        assert_eq!(ids("title:notes"), vec![scattered.id.to_string()]);
+        assert_eq!(ids("title:\"async runtime\""), vec![runtime.id.to_string()]);
+        assert_eq!(ids("tag:#draft runtime"), vec![runtime.id.to_string()]);
+        assert!(ids("tag:tokio").is_empty());
+
+        // The phrase's words are highlighted in the snippet
+        let results = fixture.fulltext.search("\"async runtime\"", 10).unwrap();
+        let highlighted: Vec<&str> = results[0]
+            .highlights
+            .iter()
+            .map(|r| &results[0].snippet[r.start..r.end])
+            .collect();
+        assert_eq!(highlighted, vec!["async", "runtime"]);
+    }
 }
 
 // ============================================================================
@@ -838,6 +953,9 @@ mod semantic_structure_tests {
             chunk_type: Some("Prose".to_string()),
             tags: vec!["test".to_string()],
             updated_at: Some("2024-01-01T00:00:00Z".to_string()),
+            chunk: None,
+            line: None,
+            highlights: Vec::new(),
         };
 
         // note_id should be parseable back to UUID
@@ -856,6 +974,9 @@ mod semantic_structure_tests {
             chunk_type: None,
             tags: Vec::new(),
             updated_at: None,
+            chunk: None,
+            line: None,
+            highlights: Vec::new(),
         };
 
         assert!(!result.title.is_empty(), "Title should not be empty");
@@ -872,6 +993,9 @@ mod semantic_structure_tests {
             chunk_type: Some("Prose".to_string()),
             tags: vec!["example".to_string()],
             updated_at: Some("2024-01-01T00:00:00Z".to_string()),
+            chunk: None,
+            line: None,
+            highlights: Vec::new(),
         };
 
         // Snippet should contain meaningful content, not just tags
@@ -1166,6 +1290,61 @@ mod stale_chunk_tests {
 
         assert_eq!(valid_chunks.len(), 3, "Should keep all chunks from valid note");
     }
+
+    #[tokio::test]
+    async fn test_spilled_chunks_search_like_held_ones() {
+        use notidium::config::SemanticIndex;
+        use notidium::embed::Embedder;
+        use notidium::search::SemanticSearch;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let spill_dir = temp_dir.path().join(".notidium/spill");
+        // Local models load on first use, which finding similar notes never needs
+        let embedder = std::sync::Arc::new(Embedder::new().unwrap());
+        let notes: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+        let chunks: Vec<Chunk> = notes
+            .iter()
+            .enumerate()
+            .flat_map(|(n, &note_id)| {
+                (0..3).map(move |c| {
+                    let mut chunk = Chunk::new(note_id, format!("Note {} chunk {}", n, c), ChunkType::Prose);
+                    chunk.prose_embedding = Some((0..8).map(|i| ((n * 3 + c + i) % 7) as f32 - 3.0).collect());
+                    chunk
+                })
+            })
+            .collect();
+
+        let mut held = SemanticSearch::with_index(embedder.clone(), SemanticIndex::Exact);
+        held.load_chunks(chunks.clone());
+        let mut spilled = SemanticSearch::new(embedder.clone()).with_memory_budget(1, &spill_dir);
+        spilled.load_chunks(chunks);
+        assert!(!held.is_spilled());
+        assert!(spilled.is_spilled());
+        assert_eq!(std::fs::read_dir(&spill_dir).unwrap().count(), 1);
+
+        async fn ranked(semantic: &SemanticSearch, note_id: Uuid) -> Vec<(String, String, f32)> {
+            semantic
+                .find_similar(note_id, 10)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|r| (r.note_id, r.snippet, (r.score * 1e4).round()))
+                .collect()
+        }
+        assert_eq!(ranked(&spilled, notes[0]).await, ranked(&held, notes[0]).await);
+        assert_eq!(ranked(&spilled, notes[0]).await.len(), 4);
+        let chunk = |semantic: &SemanticSearch| {
+            let chunks = semantic.chunks_for_note(notes[1]);
+            (chunks[2].content.clone(), chunks[2].prose_embedding.clone())
+        };
+        assert_eq!(chunk(&spilled), chunk(&held));
+
+        // Removing chunks keeps them out of results, and clearing drops the file
+        spilled.remove_chunks_for_note(notes[1]);
+        assert!(ranked(&spilled, notes[0]).await.iter().all(|(id, _, _)| *id != notes[1].to_string()));
+        spilled.clear();
+        assert_eq!(std::fs::read_dir(&spill_dir).unwrap().count(), 0);
+    }
 }
 
 // ============================================================================
@@ -1242,6 +1421,234 @@ mod fulltext_search_extended_tests {
         assert!(!results_upper.is_empty(), "Should find with uppercase query");
     }
 
+    #[tokio::test]
+    async fn test_fulltext_search_snippet_options() {
+        use notidium::config::SearchConfig;
+
+        let fixture = StoreTestFixture::new().await;
+
+        let intro = "Intro words ".repeat(40);
+        let note = fixture
+            .store
+            .create(
+                "Long Note".to_string(),
+                format!("{}\n\nThe kestrel paragraph sits here.\n\nOutro.", intro),
+                None,
+            )
+            .await
+            .expect("Should create note");
+
+        fixture.fulltext.index_note(&note).expect("Should index");
+        fixture.fulltext.commit().expect("Should commit");
+
+        let default = fixture.fulltext.search("kestrel", 10).expect("Should search");
+        assert!(default[0].chunk.is_none(), "Full chunk is opt-in");
+
+        let search = SearchConfig::default();
+        let snippets = search.snippet_options(Some(40), true);
+        let results = fixture
+            .fulltext
+            .search_with("kestrel", 10, &search.boosts, &snippets)
+            .expect("Should search");
+        assert!(results[0].snippet.len() < default[0].snippet.len());
+        assert!(results[0].snippet.contains("kestrel"));
+        assert_eq!(
+            results[0].chunk.as_deref(),
+            Some("The kestrel paragraph sits here.")
+        );
+
+        let capped = search.snippet_options(Some(usize::MAX), false);
+        assert_eq!(capped.len, search.max_snippet_len);
+        assert_eq!(search.snippet_options(None, false).len, 200);
+    }
+
+    #[tokio::test]
+    async fn test_fulltext_snippet_highlights() {
+        let fixture = StoreTestFixture::new().await;
+
+        let intro = "Filler text here.\n".repeat(30);
+        let note = fixture
+            .store
+            .create(
+                "Birds".to_string(),
+                format!("{}\nA Kestrel hovers,\n\nthen the kestrels dive.", intro),
+                None,
+            )
+            .await
+            .expect("Should create note");
+        fixture.fulltext.index_note(&note).expect("Should index");
+        fixture.fulltext.commit().expect("Should commit");
+
+        let results = fixture.fulltext.search("kestrel", 10).expect("Should search");
+        let snippet = &results[0].snippet;
+        assert!(snippet.starts_with("..."), "Cut snippet is marked: {}", snippet);
+        assert!(!snippet.contains('\n') && !snippet.contains("  "));
+        let matched: Vec<&str> = results[0]
+            .highlights
+            .iter()
+            .map(|range| &snippet[range.start..range.end])
+            .collect();
+        assert_eq!(matched, vec!["Kestrel"]);
+
+        // A title-only match has nothing to highlight
+        let results = fixture.fulltext.search("birds", 10).expect("Should search");
+        assert!(results[0].highlights.is_empty());
+        assert!(results[0].snippet.starts_with("Filler text here. Filler"));
+    }
+
+    #[tokio::test]
+    async fn test_fulltext_field_boosts_and_profiles() {
+        use notidium::config::{RankingProfile, SearchConfig};
+
+        let fixture = StoreTestFixture::new().await;
+
+        let titled = fixture
+            .store
+            .create("Heron".to_string(), "Notes about wading birds.".to_string(), None)
+            .await
+            .expect("Should create note");
+        let tagged = fixture
+            .store
+            .create(
+                "Birding log".to_string(),
+                "Saw a grey one at the lake.".to_string(),
+                Some(vec!["heron".to_string()]),
+            )
+            .await
+            .expect("Should create note");
+        for note in [&titled, &tagged] {
+            fixture.fulltext.index_note(note).expect("Should index");
+        }
+        fixture.fulltext.commit().expect("Should commit");
+
+        let mut search = SearchConfig::default();
+        search.profiles.insert(
+            "tags-first".to_string(),
+            RankingProfile {
+                tags: Some(10.0),
+                ..RankingProfile::default()
+            },
+        );
+        let snippets = search.snippet_options(None, false);
+
+        let top = |profile: Option<&str>| {
+            let boosts = search.field_boosts(profile).expect("Known profile");
+            fixture
+                .fulltext
+                .search_with("heron", 10, &boosts, &snippets)
+                .expect("Should search")[0]
+                .note_id
+                .clone()
+        };
+        assert_eq!(top(None), titled.id.to_string());
+        assert_eq!(top(Some("tags-first")), tagged.id.to_string());
+
+        let profile = search.field_boosts(Some("tags-first")).unwrap();
+        assert_eq!(profile.title, search.boosts.title, "Unset boosts are inherited");
+        assert!(search.field_boosts(Some("missing")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fulltext_search_matches_code_blocks() {
+        let fixture = StoreTestFixture::new().await;
+
+        let note = fixture
+            .store
+            .create(
+                "Snippets".to_string(),
+                "Example:\n\n```rust\nfn frobnicate() {}\n```\n".to_string(),
+                None,
+            )
+            .await
+            .expect("Should create note");
+        fixture.fulltext.index_note(&note).expect("Should index");
+        fixture.fulltext.commit().expect("Should commit");
+
+        let results = fixture.fulltext.search("code:frobnicate", 10).expect("Should search");
+        assert_eq!(results.len(), 1, "Code block text is indexed in its own field");
+    }
+
+    #[tokio::test]
+    async fn test_fulltext_analyzer_settings() {
+        use notidium::config::{AnalyzerConfig, SearchConfig};
+
+        let fixture = StoreTestFixture::new().await;
+        let index_dir = TempDir::new().expect("Failed to create temp dir");
+
+        let analyzer = AnalyzerConfig {
+            stopwords: vec!["project".to_string()],
+            protected_terms: vec!["node.js".to_string()],
+            stemmer: None,
+        };
+        let notes = vec![
+            fixture
+                .store
+                .create("Runtime".to_string(), "Upgrade the node.js project next week".to_string(), None)
+                .await
+                .expect("Should create note"),
+            fixture
+                .store
+                .create("Graph".to_string(), "Each node has a js handle".to_string(), None)
+                .await
+                .expect("Should create note"),
+        ];
+
+        {
+            let config = SearchConfig {
+                analyzer,
+                ..SearchConfig::default()
+            };
+            let index = FullTextIndex::open_with(index_dir.path(), &config).expect("Should open");
+            assert!(index.needs_rebuild());
+            index.rebuild(&notes).expect("Should rebuild");
+
+            let results = index.search("node.js", 10).expect("Should search");
+            assert_eq!(results.len(), 1, "Protected term isn't split");
+            assert_eq!(results[0].note_id, notes[0].id.to_string());
+            assert!(index.search("project", 10).expect("Should search").is_empty());
+        }
+
+        let changed = SearchConfig::default();
+        let index = FullTextIndex::open_with(index_dir.path(), &changed).expect("Should reopen");
+        assert!(!index.needs_rebuild());
+        assert!(index.analyzer_changed(), "Changed settings ask for a reindex");
+        index.rebuild(&notes).expect("Should rebuild");
+        assert!(!index.analyzer_changed());
+        assert_eq!(index.search("node", 10).expect("Should search").len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fulltext_index_settings_and_warm_up() {
+        use notidium::config::{IndexConfig, ReaderReload, SearchConfig};
+
+        let fixture = StoreTestFixture::new().await;
+        let index_dir = TempDir::new().expect("Failed to create temp dir");
+        let config = SearchConfig {
+            index: IndexConfig {
+                writer_heap_mb: 20,
+                reader_reload: ReaderReload::Manual,
+                warm_up: true,
+            },
+            ..SearchConfig::default()
+        };
+        let index = FullTextIndex::open_with(index_dir.path(), &config).expect("Should open");
+        index.warm_up().expect("Empty index warms up");
+
+        let note = fixture
+            .store
+            .create("Warm".to_string(), "Cache the searcher".to_string(), None)
+            .await
+            .expect("Should create note");
+        index.index_note(&note).expect("Should index");
+        index.commit().expect("Should commit");
+        index.warm_up().expect("Should warm up");
+
+        // Commits reload the reader even without automatic reloads
+        let results = index.search("searcher", 10).expect("Should search");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].note_id, note.id.to_string());
+    }
+
     #[tokio::test]
     async fn test_fulltext_search_empty_query() {
         let fixture = StoreTestFixture::new().await;
@@ -1365,6 +1772,54 @@ mod fulltext_search_extended_tests {
         let results = fixture.fulltext.search("Deletable", 10).expect("Should search");
         assert!(results.is_empty(), "Deleted note should not appear in search");
     }
+
+    #[tokio::test]
+    async fn test_fulltext_search_sorted_by_fast_fields() {
+        use notidium::config::FieldBoosts;
+        use notidium::search::{DocumentFilter, SnippetOptions};
+        use notidium::types::{Note, NoteSort, SortField, SortOrder};
+
+        let fixture = StoreTestFixture::new().await;
+        let now = chrono::Utc::now();
+        for (title, content, age_days) in [
+            ("banana", "fruit fruit fruit fruit", 3),
+            ("Apple", "fruit", 1),
+            ("cherry", "fruit fruit", 2),
+        ] {
+            let mut note = Note::new(title.to_string(), content.to_string(), PathBuf::from(format!("{}.md", title)));
+            note.created_at = now - chrono::Duration::days(age_days);
+            note.updated_at = now - chrono::Duration::days(10 - age_days);
+            fixture.fulltext.index_note(&note).expect("Should index");
+        }
+        fixture.fulltext.index_canvas("board.canvas", "fruit stand").expect("Should index");
+        fixture.fulltext.commit().expect("Should commit");
+
+        let titles = |field: SortField, order: Option<SortOrder>, limit: usize| -> Vec<String> {
+            fixture
+                .fulltext
+                .search_within(
+                    "fruit",
+                    limit,
+                    &FieldBoosts::default(),
+                    &SnippetOptions::default(),
+                    &DocumentFilter::default(),
+                    NoteSort::new(field, order),
+                    false,
+                )
+                .expect("Should search")
+                .into_iter()
+                .map(|r| r.title)
+                .collect()
+        };
+
+        // The newest note wins a sort by date even though it ranks lowest
+        assert_eq!(titles(SortField::CreatedAt, None, 1), vec!["Apple"]);
+        // Canvases have no dates and go last either way
+        assert_eq!(titles(SortField::CreatedAt, Some(SortOrder::Asc), 4), vec!["banana", "cherry", "Apple", "board"]);
+        assert_eq!(titles(SortField::UpdatedAt, None, 3), vec!["banana", "cherry", "Apple"]);
+        assert_eq!(titles(SortField::Title, None, 4), vec!["Apple", "banana", "board", "cherry"]);
+        assert_eq!(titles(SortField::Title, Some(SortOrder::Desc), 2), vec!["cherry", "board"]);
+    }
 }
 
 // ============================================================================
@@ -1523,7 +1978,7 @@ mod store_edge_case_tests {
 // ============================================================================
 
 mod chunker_extended_tests {
-    use notidium::embed::Chunker;
+    use notidium::embed::{heading_paths, Chunker};
     use notidium::types::{ChunkType, Note};
     use std::path::PathBuf;
 
@@ -1562,6 +2017,25 @@ Some text after the list."#;
         assert!(!code_chunks.is_empty(), "Should handle code blocks");
     }
 
+    #[test]
+    fn test_heading_paths() {
+        let chunker = Chunker::default();
+        let content = "# Guide\n\nIntro.\n\n## Setup\n\nInstall it.\n\n### Linux\n\n```sh\napt install x\n```\n\n## Usage\n\nRun it.";
+        let chunks = chunker.chunk_note(&create_test_note("Guide", content));
+        let refs: Vec<_> = chunks.iter().collect();
+        let paths = heading_paths(&refs);
+
+        let path_of = |text: &str| {
+            let i = chunks.iter().position(|c| c.content == text).unwrap();
+            paths[i].join(" > ")
+        };
+        assert_eq!(path_of("Guide"), "");
+        assert_eq!(path_of("Intro."), "Guide");
+        assert_eq!(path_of("Linux"), "Guide > Setup");
+        assert_eq!(path_of("apt install x"), "Guide > Setup > Linux");
+        assert_eq!(path_of("Run it."), "Guide > Usage");
+    }
+
     #[test]
     fn test_chunk_very_long_content() {
         let chunker = Chunker::default();
@@ -1905,6 +2379,54 @@ mod config_tests {
         assert!(config.notes_path().ends_with("notes"));
         assert!(config.tantivy_path().ends_with("tantivy"));
     }
+
+    #[test]
+    fn test_bind_address_defaults_to_loopback() {
+        let config: Config = toml::from_str("vault_path = \"/tmp/vault\"").unwrap();
+        assert!(config.bind_address.is_loopback());
+        let config: Config = toml::from_str("vault_path = \"/tmp/vault\"\nbind_address = \"0.0.0.0\"").unwrap();
+        assert!(config.bind_address.is_unspecified());
+    }
+
+    #[test]
+    fn test_embedding_quantization() {
+        use notidium::config::{EmbeddingConfig, Quantization};
+
+        assert_eq!(Config::default().embedding.quantization, Quantization::F32);
+        let config: EmbeddingConfig = toml::from_str("quantization = \"int8\"").unwrap();
+        assert_eq!(config.quantization, Quantization::Int8);
+        assert!(toml::from_str::<EmbeddingConfig>("quantization = \"f8\"").is_err());
+    }
+
+    #[test]
+    fn test_embedding_execution_provider() {
+        use notidium::config::{EmbeddingConfig, ExecutionProvider};
+
+        let defaults = EmbeddingConfig::default();
+        assert_eq!(defaults.execution_provider, ExecutionProvider::Cpu);
+        assert_eq!(defaults.threads, None);
+        let config: EmbeddingConfig = toml::from_str("execution_provider = \"coreml\"\nthreads = 4").unwrap();
+        assert_eq!(config.execution_provider, ExecutionProvider::CoreMl);
+        assert_eq!(config.threads, Some(4));
+        assert!(toml::from_str::<EmbeddingConfig>("execution_provider = \"tpu\"").is_err());
+    }
+
+    #[test]
+    fn test_embedding_can_be_turned_off() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let mut config = Config {
+            vault_path: temp_dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        assert!(config.embedding.enabled);
+
+        config.embedding.enabled = false;
+        config.http_port = 4000;
+        config.save().unwrap();
+        let loaded = Config::load_from_vault(temp_dir.path().to_path_buf()).unwrap();
+        assert!(!loaded.embedding.enabled);
+        assert_eq!(loaded.http_port, 4000);
+    }
 }
 
 // ============================================================================
@@ -1916,9 +2438,10 @@ mod config_tests {
 // ============================================================================
 
 mod chunker_tests {
-    use notidium::embed::Chunker;
+    use notidium::embed::{Chunker, TokenBudget, TokenCounter};
     use notidium::types::{ChunkType, Note};
     use std::path::PathBuf;
+    use std::sync::Arc;
 
     fn create_test_note(title: &str, content: &str) -> Note {
         Note::new(title.to_string(), content.to_string(), PathBuf::from("test.md"))
@@ -1935,6 +2458,192 @@ mod chunker_tests {
         assert!(matches!(chunks[0].chunk_type, ChunkType::Prose));
     }
 
+    #[test]
+    fn test_chunk_overlap() {
+        let chunker = Chunker::new(5).with_overlap(2);
+        let note = create_test_note(
+            "Test",
+            "one two three four five\nsix seven eight nine ten\neleven twelve\n\n# Heading\n\nafter",
+        );
+
+        let contents: Vec<String> = chunker
+            .chunk_note(&note)
+            .iter()
+            .map(|c| c.content.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                "one two three four five",
+                "four five six seven eight nine ten",
+                "nine ten eleven twelve",
+                "Heading",
+                "after",
+            ]
+        );
+
+        // Words carried over alone never make a chunk, nor spill into a heading
+        let note = create_test_note("Test", "one two three four five\n\n# Heading");
+        let contents: Vec<String> = chunker.chunk_note(&note).into_iter().map(|c| c.content).collect();
+        assert_eq!(contents, vec!["one two three four five", "Heading"]);
+    }
+
+    #[test]
+    fn test_chunks_carry_heading_context() {
+        let content = "# Guide\n\nIntro.\n\n## Deployment\n\n### Kubernetes\n\nUse helm.\n\n## Testing\n\nRun cargo test.";
+        let note = create_test_note("Ops", content);
+        let chunks = Chunker::default().chunk_note(&note);
+        let context = |text: &str| {
+            let chunk = chunks.iter().find(|c| c.content == text).unwrap();
+            chunk.context.clone().unwrap()
+        };
+
+        assert_eq!(context("Guide"), "Ops");
+        assert_eq!(context("Intro."), "Ops > Guide");
+        assert_eq!(context("Kubernetes"), "Ops > Guide > Deployment");
+        assert_eq!(context("Use helm."), "Ops > Guide > Deployment > Kubernetes");
+        assert_eq!(context("Run cargo test."), "Ops > Guide > Testing");
+        let helm = chunks.iter().find(|c| c.content == "Use helm.").unwrap();
+        assert_eq!(helm.embedding_text(), "Ops > Guide > Deployment > Kubernetes: Use helm.");
+
+        let plain = Chunker::default().with_heading_context(false).chunk_note(&note);
+        assert!(plain.iter().all(|c| c.context.is_none() && c.embedding_text() == c.content));
+    }
+
+    #[test]
+    fn test_prose_split_at_sentence_ends() {
+        let note = create_test_note(
+            "Test",
+            "One two three. Four five six seven. Eight nine ten eleven twelve.",
+        );
+
+        let chunks = Chunker::new(8).with_min_words(0).chunk_note(&note);
+        let contents: Vec<&str> = chunks.iter().map(|c| c.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            vec!["One two three. Four five six seven.", "Eight nine ten eleven twelve."]
+        );
+
+        // A sentence longer than the limit is cut at the limit
+        let note = create_test_note("Test", "a b c d e f g h i j k l m n o p q r s t");
+        let chunks = Chunker::new(8).with_min_words(0).chunk_note(&note);
+        assert_eq!(chunks[0].content, "a b c d e f g h");
+    }
+
+    #[test]
+    fn test_short_remainders_merge_into_previous_chunk() {
+        let note = create_test_note(
+            "Test",
+            "One two three. Four five six seven. Eight nine ten eleven twelve.\n\n# Next\n\nAfter.",
+        );
+
+        let chunks = Chunker::new(8).with_min_words(6).chunk_note(&note);
+        let contents: Vec<&str> = chunks.iter().map(|c| c.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            vec![
+                "One two three. Four five six seven. Eight nine ten eleven twelve.",
+                "Next",
+                "After.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lists_become_list_item_chunks() {
+        let content = "Setup steps:\n\n1. Install Rust\n2. Clone the repo\n   - use SSH\n   - or HTTPS\n3. Run make\n\nThat's all.\n";
+        let note = create_test_note("Setup", content);
+        let chunks = Chunker::default().chunk_note(&note);
+
+        let types: Vec<&ChunkType> = chunks.iter().map(|c| &c.chunk_type).collect();
+        assert_eq!(types, vec![&ChunkType::Prose, &ChunkType::ListItem, &ChunkType::Prose]);
+        assert_eq!(
+            chunks[1].content,
+            "1. Install Rust\n2. Clone the repo\n  - use SSH\n  - or HTTPS\n3. Run make"
+        );
+        // One line per item, nested ones included
+        assert_eq!(chunks[1].end_line - chunks[1].start_line, 5);
+        assert_eq!(chunks[2].content, "That's all.");
+    }
+
+    #[test]
+    fn test_long_lists_split_between_items() {
+        let content = "- alpha beta gamma\n  - delta\n- epsilon zeta\n- eta theta iota\n";
+        let note = create_test_note("Greek", content);
+        let chunks = Chunker::new(4).chunk_note(&note);
+
+        let contents: Vec<&str> = chunks.iter().map(|c| c.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            vec!["- alpha beta gamma\n  - delta", "- epsilon zeta\n- eta theta iota"]
+        );
+        assert!(chunks.iter().all(|c| c.chunk_type == ChunkType::ListItem));
+    }
+
+    #[test]
+    #[cfg(feature = "code-symbols")]
+    fn test_code_blocks_split_at_symbols() {
+        let content = "## Parsing\n\n```rust\nuse std::fs;\n\n/// Read the config\nfn load() -> String {\n    fs::read_to_string(\"a\").unwrap()\n}\n\nstruct Config;\n```\n";
+        let note = create_test_note("Helpers", content);
+        let chunks = Chunker::default().chunk_note(&note);
+        let code: Vec<_> = chunks.iter().filter(|c| c.is_code()).collect();
+        let titles: Vec<Option<&str>> = code
+            .iter()
+            .map(|c| match &c.chunk_type {
+                ChunkType::CodeBlock { title, .. } => title.as_deref(),
+                _ => unreachable!(),
+            })
+            .collect();
+
+        assert_eq!(titles, vec![None, Some("load"), Some("Config")]);
+        assert!(code[1].content.starts_with("/// Read the config\nfn load()"));
+        assert_eq!(code[1].language.as_deref(), Some("rust"));
+        assert_eq!(code[1].context.as_deref(), Some("Helpers > Parsing > load"));
+        assert!(code[1].start_line < code[2].start_line);
+
+        let whole = Chunker::default().with_code_symbols(false).chunk_note(&note);
+        assert_eq!(whole.iter().filter(|c| c.is_code()).count(), 1);
+    }
+
+    /// Every character but whitespace is a token
+    struct CharTokens;
+
+    impl TokenCounter for CharTokens {
+        fn token_offsets(&self, text: &str) -> Vec<(usize, usize)> {
+            text.char_indices()
+                .filter(|(_, c)| !c.is_whitespace())
+                .map(|(i, c)| (i, i + c.len_utf8()))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_token_budget_splits_long_chunks() {
+        let code = (0..30).map(|i| format!("let v{} = {};", i % 10, i % 10)).collect::<Vec<_>>().join("\n");
+        let content = format!("Short intro.\n\n```rust\n{}\n```\n", code);
+        let note = create_test_note("Test", &content);
+        let chunks = Chunker::default().chunk_note(&note);
+        assert_eq!(chunks.len(), 2);
+
+        let budget = TokenBudget {
+            max_tokens: 50,
+            counter: Arc::new(CharTokens),
+        };
+        let fitted = budget.fit(chunks);
+        assert_eq!(fitted[0].content, "Short intro.");
+        let pieces = &fitted[1..];
+        assert!(pieces.len() > 1);
+        for piece in pieces {
+            assert!(CharTokens.token_offsets(&piece.content).len() <= 50);
+            assert!(matches!(piece.chunk_type, ChunkType::CodeBlock { .. }));
+            // Cut at line breaks, so every line stays whole
+            assert!(piece.content.lines().all(|l| l.starts_with("let ") && l.ends_with(';')));
+        }
+        let rejoined: Vec<&str> = pieces.iter().flat_map(|p| p.content.lines()).collect();
+        assert_eq!(rejoined.join("\n"), code);
+        assert!(pieces.windows(2).all(|w| w[0].start_line < w[1].start_line));
+    }
+
     #[test]
     fn test_chunk_code_block() {
         let chunker = Chunker::default();
@@ -2038,8 +2747,4359 @@ console.log("World");
     }
 }
 
-#[cfg(feature = "expensive_tests")]
-mod mcp_server_tests {
+// ============================================================================
+// Vector Store Tests
+// ============================================================================
+
+mod vector_store_tests {
+    use super::*;
+    use notidium::store::VectorStore;
+    use notidium::types::{Chunk, ChunkType};
+    use uuid::Uuid;
+
+    fn embedded_chunk(note_id: Uuid, content: &str, embedding: Vec<f32>) -> Chunk {
+        let mut chunk = Chunk::new(note_id, content.to_string(), ChunkType::Prose);
+        chunk.prose_embedding = Some(embedding);
+        chunk.embedded_at = Some(chrono::Utc::now());
+        chunk
+    }
+
+    #[tokio::test]
+    async fn test_store_records_format_version() {
+        use notidium::store::VECTOR_STORE_VERSION;
+
+        let temp_dir = TempDir::new().unwrap();
+        let store = VectorStore::open(temp_dir.path()).await.unwrap();
+        assert_eq!(store.version(), VECTOR_STORE_VERSION);
+        let format: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(temp_dir.path().join("format.json")).unwrap()).unwrap();
+        assert_eq!(format["version"], VECTOR_STORE_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_store_shares_the_metadata_database() {
+        use notidium::config::VectorBackend;
+        use notidium::store::MetadataDb;
+
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = Config {
+            vault_path: temp_dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        config.embedding.vector_store = VectorBackend::Sqlite;
+        config.init_vault().unwrap();
+        assert_eq!(config.vectors_path(), config.db_path());
+        let _metadata = MetadataDb::open(&config.db_path()).unwrap();
+
+        let store = VectorStore::open(&config.vectors_path()).await.unwrap();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut code = Chunk::new(a, "fn main() {}".to_string(), ChunkType::CodeBlock { language: "rust".to_string(), title: None });
+        code.code_embedding = Some(vec![0.25, -1.0]);
+        code.embedding_model = Some("model".to_string());
+        store
+            .add_chunks(&[embedded_chunk(a, "first", vec![0.5, 0.5]), code, embedded_chunk(b, "other", vec![1.0, 0.0])])
+            .await
+            .unwrap();
+        store
+            .upsert_note_chunks(b, &[embedded_chunk(b, "replaced", vec![0.0, 1.0])])
+            .await
+            .unwrap();
+
+        let reopened = VectorStore::open(&config.vectors_path()).await.unwrap();
+        assert_eq!(reopened.count().await.unwrap(), 3);
+        assert_eq!(reopened.note_ids().await.unwrap(), [a, b].into_iter().collect());
+        assert_eq!(reopened.embedding_models().await.unwrap(), ["model".to_string()].into_iter().collect());
+        let loaded = reopened.load_all().await.unwrap();
+        let contents: Vec<&str> = loaded.iter().map(|c| c.content.as_str()).collect();
+        assert_eq!(contents, vec!["first", "fn main() {}", "replaced"]);
+        assert_eq!(loaded[0].prose_embedding, Some(vec![0.5, 0.5]));
+        assert_eq!(loaded[1].code_embedding, Some(vec![0.25, -1.0]));
+        assert_eq!(loaded[1].prose_embedding, None);
+
+        reopened.delete_note(a).await.unwrap();
+        assert_eq!(reopened.count().await.unwrap(), 1);
+        reopened.compact().await.unwrap();
+        assert!(!config.data_dir().join("vectors").exists());
+    }
+
+    #[tokio::test]
+    async fn test_newer_store_keeps_unknown_columns() {
+        use arrow_schema::{DataType, Field, Schema};
+        use notidium::store::VECTOR_STORE_VERSION;
+
+        let temp_dir = TempDir::new().unwrap();
+        // A store from a newer version with a column this one doesn't know
+        let db = lancedb::connect(&temp_dir.path().to_string_lossy()).execute().await.unwrap();
+        let vector = DataType::List(Arc::new(Field::new("item", DataType::Float32, true)));
+        let fields = vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("note_id", DataType::Utf8, false),
+            Field::new("content", DataType::Utf8, false),
+            Field::new("chunk_type", DataType::Utf8, false),
+            Field::new("start_line", DataType::UInt32, false),
+            Field::new("end_line", DataType::UInt32, false),
+            Field::new("start_offset", DataType::UInt32, false),
+            Field::new("end_offset", DataType::UInt32, false),
+            Field::new("prose_embedding", vector, true),
+            Field::new("summary", DataType::Utf8, true),
+            Field::new("embedding_model", DataType::Utf8, true),
+        ];
+        db.create_empty_table("chunks", Arc::new(Schema::new(fields))).execute().await.unwrap();
+        let format = serde_json::json!({"version": VECTOR_STORE_VERSION + 1, "layout": "v2"});
+        std::fs::write(temp_dir.path().join("format.json"), format.to_string()).unwrap();
+
+        let store = VectorStore::open(temp_dir.path()).await.unwrap();
+        assert_eq!(store.version(), VECTOR_STORE_VERSION + 1);
+        let note_id = Uuid::new_v4();
+        let mut chunk = embedded_chunk(note_id, "kept", vec![0.5, 0.5]);
+        chunk.language = Some("rust".to_string());
+        chunk.embedding_model = Some("model".to_string());
+        store.add_chunks(std::slice::from_ref(&chunk)).await.unwrap();
+
+        let loaded = store.load_all().await.unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].content, "kept");
+        assert_eq!(loaded[0].prose_embedding, Some(vec![0.5, 0.5]));
+        assert_eq!(loaded[0].embedding_model.as_deref(), Some("model"));
+        // Columns the newer table lacks are dropped, not failed on
+        assert_eq!(loaded[0].language, None);
+        assert_eq!(loaded[0].embedded_at, None);
+
+        let format: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(temp_dir.path().join("format.json")).unwrap()).unwrap();
+        assert_eq!(format["layout"], "v2");
+    }
+
+    #[tokio::test]
+    async fn test_roundtrip_preserves_chunk_fields() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = VectorStore::open(temp_dir.path()).await.expect("Should open store");
+
+        let note_id = Uuid::new_v4();
+        let mut code = Chunk::new(
+            note_id,
+            "fn main() {}".to_string(),
+            ChunkType::CodeBlock { language: "rust".to_string(), title: None },
+        );
+        code.language = Some("rust".to_string());
+        code.start_line = 3;
+        code.end_line = 5;
+        code.prose_embedding = Some(vec![0.1, 0.2, 0.3]);
+        code.code_embedding = Some(vec![0.4, 0.5, 0.6, 0.7]);
+
+        store.add_chunks(std::slice::from_ref(&code)).await.unwrap();
+
+        let loaded = store.load_all().await.unwrap();
+        assert_eq!(loaded.len(), 1);
+        let chunk = &loaded[0];
+        assert_eq!(chunk.id, code.id);
+        assert_eq!(chunk.note_id, note_id);
+        assert_eq!(chunk.content, "fn main() {}");
+        assert_eq!(chunk.chunk_type, code.chunk_type);
+        assert_eq!(chunk.language.as_deref(), Some("rust"));
+        assert_eq!((chunk.start_line, chunk.end_line), (3, 5));
+        assert_eq!(chunk.prose_embedding, code.prose_embedding);
+        assert_eq!(chunk.code_embedding, code.code_embedding);
+    }
+
+    #[tokio::test]
+    async fn test_upsert_replaces_only_that_note() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = VectorStore::open(temp_dir.path()).await.unwrap();
+
+        let note_a = Uuid::new_v4();
+        let note_b = Uuid::new_v4();
+        store
+            .add_chunks(&[
+                embedded_chunk(note_a, "a1", vec![1.0, 0.0]),
+                embedded_chunk(note_a, "a2", vec![0.0, 1.0]),
+                embedded_chunk(note_b, "b1", vec![1.0, 1.0]),
+            ])
+            .await
+            .unwrap();
+
+        store
+            .upsert_note_chunks(note_a, &[embedded_chunk(note_a, "a-new", vec![0.5, 0.5])])
+            .await
+            .unwrap();
+
+        let loaded = store.load_all().await.unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert!(loaded.iter().any(|c| c.content == "a-new"));
+        assert!(loaded.iter().any(|c| c.content == "b1"));
+        assert!(!loaded.iter().any(|c| c.content == "a1" || c.content == "a2"));
+    }
+
+    #[tokio::test]
+    async fn test_delete_note_and_note_ids() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = VectorStore::open(temp_dir.path()).await.unwrap();
+
+        let note_a = Uuid::new_v4();
+        let note_b = Uuid::new_v4();
+        store
+            .add_chunks(&[
+                embedded_chunk(note_a, "a", vec![1.0]),
+                embedded_chunk(note_b, "b", vec![1.0]),
+            ])
+            .await
+            .unwrap();
+
+        store.delete_note(note_a).await.unwrap();
+
+        let ids = store.note_ids().await.unwrap();
+        assert_eq!(ids.len(), 1);
+        assert!(ids.contains(&note_b));
+        assert_eq!(store.count().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_load_all_returns_more_than_default_page() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = VectorStore::open(temp_dir.path()).await.unwrap();
+
+        let note_id = Uuid::new_v4();
+        let chunks: Vec<Chunk> = (0..25)
+            .map(|i| embedded_chunk(note_id, &format!("chunk {}", i), vec![i as f32]))
+            .collect();
+        store.add_chunks(&chunks).await.unwrap();
+
+        assert_eq!(store.load_all().await.unwrap().len(), 25);
+    }
+
+    #[tokio::test]
+    async fn test_reopen_persists_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let note_id = Uuid::new_v4();
+
+        {
+            let store = VectorStore::open(temp_dir.path()).await.unwrap();
+            store
+                .add_chunks(&[embedded_chunk(note_id, "persisted", vec![0.3, 0.4])])
+                .await
+                .unwrap();
+        }
+
+        let reopened = VectorStore::open(temp_dir.path()).await.unwrap();
+        let loaded = reopened.load_all().await.unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].content, "persisted");
+    }
+
+    #[tokio::test]
+    async fn test_migrate_from_json() {
+        let temp_dir = TempDir::new().unwrap();
+        let json_path = temp_dir.path().join("chunks.json");
+        let note_id = Uuid::new_v4();
+        let chunks = vec![
+            embedded_chunk(note_id, "legacy 1", vec![0.1]),
+            embedded_chunk(note_id, "legacy 2", vec![0.2]),
+        ];
+        std::fs::write(&json_path, serde_json::to_string_pretty(&chunks).unwrap()).unwrap();
+
+        let store = VectorStore::open(&temp_dir.path().join("vectors")).await.unwrap();
+        let migrated = store.migrate_from_json(&json_path).await.unwrap();
+
+        assert_eq!(migrated, 2);
+        assert_eq!(store.count().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_export_json_round_trips() {
+        let temp_dir = TempDir::new().unwrap();
+        let note_id = Uuid::new_v4();
+        let store = VectorStore::open(&temp_dir.path().join("vectors")).await.unwrap();
+        store
+            .add_chunks(&[
+                embedded_chunk(note_id, "first", vec![0.5, -0.25]),
+                embedded_chunk(note_id, "second", vec![1.0, 0.0]),
+            ])
+            .await
+            .unwrap();
+
+        let mut json = Vec::new();
+        assert_eq!(store.export_json(&mut json).await.unwrap(), 2);
+        let json_path = temp_dir.path().join("chunks.json");
+        std::fs::write(&json_path, &json).unwrap();
+
+        let imported = VectorStore::open(&temp_dir.path().join("imported")).await.unwrap();
+        assert_eq!(imported.migrate_from_json(&json_path).await.unwrap(), 2);
+        let mut chunks = imported.load_all().await.unwrap();
+        chunks.sort_by(|a, b| a.content.cmp(&b.content));
+        assert_eq!(chunks[0].content, "first");
+        assert_eq!(chunks[0].prose_embedding, Some(vec![0.5, -0.25]));
+    }
+
+    #[tokio::test]
+    async fn test_export_json_empty_store() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = VectorStore::open(temp_dir.path()).await.unwrap();
+
+        let mut json = Vec::new();
+        assert_eq!(store.export_json(&mut json).await.unwrap(), 0);
+        assert_eq!(json, b"[]");
+    }
+
+    #[tokio::test]
+    async fn test_embedding_models() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = VectorStore::open(temp_dir.path()).await.unwrap();
+        assert!(store.embedding_models().await.unwrap().is_empty());
+
+        let note_id = Uuid::new_v4();
+        let mut bge = embedded_chunk(note_id, "a", vec![0.1; 4]);
+        bge.embedding_model = Some("BAAI/bge-small-en-v1.5".to_string());
+        let mut other = embedded_chunk(note_id, "b", vec![0.1; 8]);
+        other.embedding_model = Some("bge-base-en-v1.5".to_string());
+        let legacy = embedded_chunk(note_id, "c", vec![0.1; 4]);
+        store.add_chunks(&[bge, other, legacy]).await.unwrap();
+
+        let mut models: Vec<String> = store.embedding_models().await.unwrap().into_iter().collect();
+        models.sort();
+        assert_eq!(models, vec!["BAAI/bge-small-en-v1.5", "bge-base-en-v1.5"]);
+    }
+}
+
+// ============================================================================
+// Title Index Tests
+// ============================================================================
+
+mod title_index_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_search_titles_ranks_exact_first() {
+        let fixture = StoreTestFixture::new().await;
+
+        fixture.create_test_note("Rust Async Patterns", "Content", None).await;
+        let exact = fixture.create_test_note("Rust Async", "Content", None).await;
+
+        let results = fixture.store.search_titles("rust async", 10).await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0.id, exact);
+        assert!(results[0].1 > results[1].1);
+    }
+
+    #[tokio::test]
+    async fn test_get_by_title_tolerates_typos() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Kubernetes Deployment", "Content", None).await;
+
+        let retrieved = fixture.store.get_by_title("kubernets deployment").await;
+        assert_eq!(retrieved.map(|(n, _)| n.id), Some(id));
+    }
+
+    #[tokio::test]
+    async fn test_title_index_follows_rename() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Original Name", "Content", None).await;
+        fixture
+            .store
+            .update_full(id, Some("Renamed Note".to_string()), None, None, None, None)
+            .await
+            .unwrap();
+
+        let results = fixture.store.search_titles("renamed note", 1).await;
+        assert_eq!(results[0].0.id, id);
+        assert_eq!(results[0].1, 1.0);
+        assert!(fixture.store.search_titles("original name", 10).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_deleted_note_not_found_by_title() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Doomed Note", "Content", None).await;
+        fixture.store.delete(id).await.unwrap();
+
+        assert!(fixture.store.get_by_title("Doomed Note").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_title_index_rebuilt_on_load() {
+        let fixture = StoreTestFixture::new().await;
+
+        std::fs::write(
+            fixture.config.notes_path().join("external.md"),
+            "# Externally Added\n\nWritten outside the app.",
+        )
+        .unwrap();
+        fixture.store.load_all().await.unwrap();
+
+        let retrieved = fixture.store.get_by_title("externally added").await;
+        assert_eq!(retrieved.map(|(n, _)| n.title), Some("Externally Added".to_string()));
+    }
+}
+
+// ============================================================================
+// External Change Sync Tests
+// ============================================================================
+
+mod sync_paths_tests {
+    use super::*;
+    use notidium::store::NoteChange;
+
+    #[tokio::test]
+    async fn test_sync_picks_up_new_file() {
+        let fixture = StoreTestFixture::new().await;
+
+        let path = fixture.config.notes_path().join("external.md");
+        std::fs::write(&path, "# External Note\n\nAdded by another editor.").unwrap();
+
+        let changes = fixture.store.sync_paths(std::slice::from_ref(&path)).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        let NoteChange::Updated(note) = &changes[0] else {
+            panic!("Expected an update");
+        };
+        assert_eq!(note.title, "External Note");
+        assert!(fixture.store.get(note.id).await.is_some());
+        assert!(fixture.store.get_by_title("External Note").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_sync_detects_external_edit() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Edited Note", "Original body", None).await;
+        let note = fixture.store.get(id).await.unwrap();
+        let path = fixture.config.notes_path().join(&note.file_path);
+        std::fs::write(&path, "# Edited Note\n\nChanged outside the app.").unwrap();
+
+        let changes = fixture.store.sync_paths(std::slice::from_ref(&path)).await.unwrap();
+        assert!(matches!(&changes[..], [NoteChange::Updated(n)] if n.id == id));
+
+        let reloaded = fixture.store.get(id).await.unwrap();
+        assert!(reloaded.content.contains("Changed outside the app."));
+        assert_ne!(reloaded.content_hash, note.content_hash);
+    }
+
+    #[tokio::test]
+    async fn test_sync_ignores_own_writes() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Own Write", "Body", None).await;
+        let note = fixture.store.get(id).await.unwrap();
+        let path = fixture.config.notes_path().join(&note.file_path);
+
+        let changes = fixture.store.sync_paths(&[path]).await.unwrap();
+        assert!(changes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sync_detects_external_delete() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Removed Note", "Body", None).await;
+        let note = fixture.store.get(id).await.unwrap();
+        let path = fixture.config.notes_path().join(&note.file_path);
+        std::fs::remove_file(&path).unwrap();
+
+        let changes = fixture.store.sync_paths(&[path]).await.unwrap();
+        assert!(matches!(&changes[..], [NoteChange::Removed(removed)] if *removed == id));
+        assert!(fixture.store.get(id).await.is_none());
+        assert!(fixture.store.get_by_title("Removed Note").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sync_ignores_store_soft_delete() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Trashed Note", "Body", None).await;
+        let note = fixture.store.get(id).await.unwrap();
+        let path = fixture.config.notes_path().join(&note.file_path);
+        fixture.store.delete(id).await.unwrap();
+
+        let changes = fixture.store.sync_paths(&[path]).await.unwrap();
+        assert!(changes.is_empty());
+        assert!(fixture.store.get(id).await.unwrap().is_deleted);
+    }
+
+    #[tokio::test]
+    async fn test_sync_rename_keeps_id() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Moving Note", "Body", None).await;
+        let note = fixture.store.get(id).await.unwrap();
+        let old_path = fixture.config.notes_path().join(&note.file_path);
+        let new_dir = fixture.config.notes_path().join("archive");
+        std::fs::create_dir_all(&new_dir).unwrap();
+        let new_path = new_dir.join("moving-note.md");
+        std::fs::rename(&old_path, &new_path).unwrap();
+
+        let changes = fixture
+            .store
+            .sync_paths(&[old_path, new_path])
+            .await
+            .unwrap();
+        assert_eq!(changes.len(), 1);
+        let NoteChange::Updated(moved) = &changes[0] else {
+            panic!("Expected an update");
+        };
+        assert_eq!(moved.id, id);
+        assert_eq!(moved.file_path, PathBuf::from("archive/moving-note.md"));
+        assert_eq!(fixture.store.list().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_sync_ignores_non_note_paths() {
+        let fixture = StoreTestFixture::new().await;
+
+        let notes_path = fixture.config.notes_path();
+        std::fs::write(notes_path.join("image.png"), b"not a note").unwrap();
+        std::fs::create_dir_all(notes_path.join(".obsidian")).unwrap();
+        std::fs::write(notes_path.join(".obsidian/workspace.md"), "# Hidden").unwrap();
+
+        let changes = fixture
+            .store
+            .sync_paths(&[
+                notes_path.join("image.png"),
+                notes_path.join(".obsidian/workspace.md"),
+            ])
+            .await
+            .unwrap();
+        assert!(changes.is_empty());
+    }
+}
+
+// ============================================================================
+// Snapshot Tests
+// ============================================================================
+
+mod snapshot_tests {
+    use super::*;
+    use notidium::snapshot::{parse_date, SnapshotManager};
+
+    #[tokio::test]
+    async fn test_snapshot_captures_notes_and_manifest() {
+        let fixture = StoreTestFixture::new().await;
+        fixture.create_test_note("Snapshot Me", "Body", None).await;
+
+        let manager = SnapshotManager::new(fixture.config.clone());
+        let date = parse_date("2024-05-01").unwrap();
+        let stats = manager.create(date).unwrap();
+
+        let snapshot = fixture.config.snapshots_path().join("2024-05-01");
+        assert!(snapshot.join("notes/snapshot-me.md").exists());
+        assert!(snapshot.join(".notidium/manifest.json").exists());
+        assert_eq!(stats.linked, 0);
+        assert!(stats.copied >= 2);
+        assert_eq!(manager.list().unwrap(), vec![date]);
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_files_are_hard_linked() {
+        let fixture = StoreTestFixture::new().await;
+        let unchanged = fixture.create_test_note("Unchanged", "Same body", None).await;
+        let changed = fixture.create_test_note("Changed", "Old body", None).await;
+
+        let manager = SnapshotManager::new(fixture.config.clone());
+        manager.create(parse_date("2024-05-01").unwrap()).unwrap();
+
+        fixture.store.update(changed, "New body".to_string()).await.unwrap();
+        let stats = manager.create(parse_date("2024-05-02").unwrap()).unwrap();
+        assert!(stats.linked >= 1);
+
+        let unchanged_path = fixture.store.get(unchanged).await.unwrap().file_path;
+        let snapshots = fixture.config.snapshots_path();
+        let first = snapshots.join("2024-05-01/notes").join(&unchanged_path);
+        let second = snapshots.join("2024-05-02/notes").join(&unchanged_path);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            assert_eq!(
+                std::fs::metadata(&first).unwrap().ino(),
+                std::fs::metadata(&second).unwrap().ino()
+            );
+        }
+
+        let changed_snapshot = fixture
+            .config
+            .snapshots_path()
+            .join("2024-05-02/notes/changed.md");
+        assert_eq!(std::fs::read_to_string(changed_snapshot).unwrap(), "New body");
+    }
+
+    #[tokio::test]
+    async fn test_restore_single_file() {
+        let fixture = StoreTestFixture::new().await;
+        let id = fixture.create_test_note("Precious", "Original text", None).await;
+
+        let manager = SnapshotManager::new(fixture.config.clone());
+        let date = parse_date("2024-05-01").unwrap();
+        manager.create(date).unwrap();
+
+        fixture.store.update(id, "Clobbered".to_string()).await.unwrap();
+
+        let restored = manager
+            .restore(date, std::path::Path::new("notes/precious.md"), None)
+            .unwrap();
+        assert_eq!(restored, fixture.config.notes_path().join("precious.md"));
+        assert_eq!(std::fs::read_to_string(&restored).unwrap(), "Original text");
+
+        // Editing the restored file must not alter the snapshot
+        std::fs::write(&restored, "Edited again").unwrap();
+        let snapshot_copy = fixture
+            .config
+            .snapshots_path()
+            .join("2024-05-01/notes/precious.md");
+        assert_eq!(std::fs::read_to_string(snapshot_copy).unwrap(), "Original text");
+    }
+
+    #[tokio::test]
+    async fn test_restore_to_output_path() {
+        let fixture = StoreTestFixture::new().await;
+        fixture.create_test_note("Elsewhere", "Text", None).await;
+
+        let manager = SnapshotManager::new(fixture.config.clone());
+        let date = parse_date("2024-05-01").unwrap();
+        manager.create(date).unwrap();
+
+        let output = fixture.config.vault_path.join("recovered/elsewhere.md");
+        let restored = manager
+            .restore(date, std::path::Path::new("notes/elsewhere.md"), Some(&output))
+            .unwrap();
+        assert_eq!(restored, output);
+        assert_eq!(std::fs::read_to_string(output).unwrap(), "Text");
+    }
+
+    #[tokio::test]
+    async fn test_restore_rejects_bad_requests() {
+        let fixture = StoreTestFixture::new().await;
+        fixture.create_test_note("Guarded", "Text", None).await;
+
+        let manager = SnapshotManager::new(fixture.config.clone());
+        let date = parse_date("2024-05-01").unwrap();
+        manager.create(date).unwrap();
+
+        assert!(manager
+            .restore(date, std::path::Path::new("../outside.md"), None)
+            .is_err());
+        assert!(manager
+            .restore(date, std::path::Path::new("notes/missing.md"), None)
+            .is_err());
+        assert!(manager
+            .restore(
+                parse_date("2023-01-01").unwrap(),
+                std::path::Path::new("notes/guarded.md"),
+                None
+            )
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_prune_applies_retention() {
+        let mut fixture = StoreTestFixture::new().await;
+        fixture.config.snapshot.keep_daily = 2;
+        fixture.config.snapshot.keep_weekly = 1;
+
+        let manager = SnapshotManager::new(fixture.config.clone());
+        for day in ["2024-04-20", "2024-04-27", "2024-04-28", "2024-05-01", "2024-05-02"] {
+            manager.create(parse_date(day).unwrap()).unwrap();
+        }
+
+        let pruned = manager.prune().unwrap();
+        assert_eq!(
+            pruned,
+            vec![parse_date("2024-04-20").unwrap(), parse_date("2024-04-27").unwrap()]
+        );
+        assert_eq!(
+            manager.list().unwrap(),
+            vec![
+                parse_date("2024-04-28").unwrap(),
+                parse_date("2024-05-01").unwrap(),
+                parse_date("2024-05-02").unwrap(),
+            ]
+        );
+    }
+}
+
+// ============================================================================
+// Backlink Tests
+// ============================================================================
+
+mod backlink_tests {
+    use super::*;
+    use notidium::LinkType;
+
+    #[tokio::test]
+    async fn test_backlinks_by_title() {
+        let fixture = StoreTestFixture::new().await;
+
+        let target = fixture.create_test_note("Rust Ownership", "Borrowing rules.", None).await;
+        let source = fixture
+            .create_test_note("Reading List", "Intro\nStart with [[Rust Ownership]] first.", None)
+            .await;
+
+        let backlinks = fixture.store.backlinks(target).await.unwrap();
+        assert_eq!(backlinks.len(), 1);
+        assert_eq!(backlinks[0].note_id, source.to_string());
+        assert_eq!(backlinks[0].title, "Reading List");
+        assert_eq!(backlinks[0].link_type, LinkType::WikiLink);
+        assert_eq!(backlinks[0].context, "Start with [[Rust Ownership]] first.");
+    }
+
+    #[tokio::test]
+    async fn test_backlink_created_before_target() {
+        let fixture = StoreTestFixture::new().await;
+
+        // Link to a note that doesn't exist yet
+        fixture
+            .create_test_note("Index", "See [[Later Note#Details]]", None)
+            .await;
+        let target = fixture.create_test_note("Later Note", "Body", None).await;
+
+        let backlinks = fixture.store.backlinks(target).await.unwrap();
+        assert_eq!(backlinks.len(), 1);
+        assert_eq!(backlinks[0].link_type, LinkType::HeadingLink);
+    }
+
+    #[tokio::test]
+    async fn test_backlinks_by_file_name_and_alias() {
+        let fixture = StoreTestFixture::new().await;
+
+        std::fs::write(
+            fixture.config.notes_path().join("k8s.md"),
+            "---\naliases: [Kubernetes]\n---\n\n# Cluster Notes\n",
+        )
+        .unwrap();
+        std::fs::write(
+            fixture.config.notes_path().join("a.md"),
+            "# A\n\nLinks to [[k8s]].",
+        )
+        .unwrap();
+        std::fs::write(
+            fixture.config.notes_path().join("b.md"),
+            "# B\n\nLinks to [[kubernetes|the cluster]].",
+        )
+        .unwrap();
+        fixture.store.load_all().await.unwrap();
+
+        let (target, _) = fixture.store.get_by_title("Cluster Notes").await.unwrap();
+        let mut titles: Vec<String> = fixture
+            .store
+            .backlinks(target.id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|b| b.title)
+            .collect();
+        titles.sort();
+        assert_eq!(titles, vec!["A", "B"]);
+    }
+
+    #[tokio::test]
+    async fn test_backlinks_follow_edits_and_deletes() {
+        let fixture = StoreTestFixture::new().await;
+
+        let target = fixture.create_test_note("Target", "Body", None).await;
+        let editor = fixture.create_test_note("Editor", "Mentions [[Target]]", None).await;
+        let deleted = fixture.create_test_note("Deleted", "Also [[Target]]", None).await;
+        assert_eq!(fixture.store.backlinks(target).await.unwrap().len(), 2);
+
+        fixture
+            .store
+            .update(editor, "No more links".to_string())
+            .await
+            .unwrap();
+        fixture.store.delete(deleted).await.unwrap();
+
+        assert!(fixture.store.backlinks(target).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_self_links_and_code_excluded() {
+        let fixture = StoreTestFixture::new().await;
+
+        let target = fixture
+            .create_test_note("Self Ref", "Jump to [[#Setup]] or [[Self Ref]]", None)
+            .await;
+        fixture
+            .create_test_note("Code Sample", "```\n[[Self Ref]]\n```", None)
+            .await;
+
+        assert!(fixture.store.backlinks(target).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_backlinks_unknown_note() {
+        let fixture = StoreTestFixture::new().await;
+
+        let result = fixture.store.backlinks(uuid::Uuid::new_v4()).await;
+        assert!(matches!(result, Err(notidium::Error::NoteNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_links_persist_in_metadata_db() {
+        let fixture = StoreTestFixture::new().await;
+
+        let target = fixture.create_test_note("Persisted", "Body", None).await;
+        fixture
+            .create_test_note("Linker", "To [[Persisted]] and https://example.com", None)
+            .await;
+
+        // A fresh store reading the same vault sees the stored links after load
+        let reopened = NoteStore::new(fixture.config.clone());
+        reopened.load_all().await.unwrap();
+        assert_eq!(reopened.backlinks(target).await.unwrap().len(), 1);
+        assert!(fixture.config.db_path().exists());
+    }
+}
+
+// ============================================================================
+// Filename Sanitizing Tests
+// ============================================================================
+
+mod filename_tests {
+    use super::*;
+    use notidium::store::MAX_STEM_LEN;
+
+    #[tokio::test]
+    async fn test_reserved_title_gets_safe_filename() {
+        let fixture = StoreTestFixture::new().await;
+
+        let note = fixture
+            .store
+            .create("CON".to_string(), "Device name".to_string(), None)
+            .await
+            .unwrap();
+
+        assert_eq!(note.file_path, PathBuf::from("con_.md"));
+        assert_eq!(note.title, "CON");
+        assert!(fixture.config.notes_path().join("con_.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_punctuation_only_title() {
+        let fixture = StoreTestFixture::new().await;
+
+        let note = fixture
+            .store
+            .create("...".to_string(), "Dots".to_string(), None)
+            .await
+            .unwrap();
+
+        assert_eq!(note.file_path, PathBuf::from("untitled.md"));
+    }
+
+    #[tokio::test]
+    async fn test_very_long_title_is_truncated() {
+        let fixture = StoreTestFixture::new().await;
+
+        let title = "A really long title ".repeat(30);
+        let note = fixture
+            .store
+            .create(title.clone(), "Body".to_string(), None)
+            .await
+            .unwrap();
+
+        let stem = note.file_path.file_stem().unwrap().to_str().unwrap();
+        assert!(stem.len() <= MAX_STEM_LEN);
+        assert!(!stem.ends_with('-'));
+        assert_eq!(note.title, title);
+        assert!(fixture.config.notes_path().join(&note.file_path).exists());
+    }
+}
+
+// ============================================================================
+// Case Sensitivity Tests
+// ============================================================================
+
+mod case_sensitivity_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_store_detects_case_sensitivity() {
+        let fixture = StoreTestFixture::new().await;
+
+        let expected = notidium::store::is_case_sensitive(&fixture.config.data_dir()).unwrap();
+        assert_eq!(fixture.store.is_case_sensitive(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_create_case_variant_of_existing_file() {
+        let fixture = StoreTestFixture::new().await;
+
+        std::fs::write(fixture.config.notes_path().join("API-Notes.md"), "# API Notes\n").unwrap();
+        fixture.store.load_all().await.unwrap();
+
+        // Slugs are lowercase, so this targets api-notes.md
+        let result = fixture
+            .store
+            .create("Api Notes".to_string(), "Body".to_string(), None)
+            .await;
+
+        if fixture.store.is_case_sensitive() {
+            // Allowed (with a warning) where both files can coexist
+            assert!(result.is_ok());
+        } else {
+            assert!(matches!(result, Err(notidium::Error::NoteAlreadyExists(_))));
+        }
+    }
+}
+
+// ============================================================================
+// Trash Tests
+// ============================================================================
+
+mod trash_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_deleted_note_is_listed_in_trash() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Old Idea", "Body", None).await;
+        fixture.store.delete(id).await.unwrap();
+
+        let trash = fixture.store.list_trash().await;
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].id, id);
+        assert_eq!(trash[0].title, "Old Idea");
+        assert_eq!(trash[0].original_path, PathBuf::from("old-idea.md"));
+        assert!(!fixture.config.notes_path().join("old-idea.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_trash_survives_reload() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Persistent", "Body", None).await;
+        fixture.store.delete(id).await.unwrap();
+
+        let reopened = NoteStore::new(fixture.config.clone());
+        reopened.load_all().await.unwrap();
+        let trash = reopened.list_trash().await;
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].id, id);
+    }
+
+    #[tokio::test]
+    async fn test_restore_keeps_id_and_content() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Comeback", "Original body", None).await;
+        fixture.store.delete(id).await.unwrap();
+
+        // Restore from a fresh process, where the note is no longer cached
+        let reopened = NoteStore::new(fixture.config.clone());
+        reopened.load_all().await.unwrap();
+        let created_at = reopened.list_trash().await[0].created_at.unwrap();
+        let note = reopened.restore_from_trash(id).await.unwrap();
+
+        assert_eq!(note.id, id);
+        assert_eq!(note.content, "Original body");
+        assert_eq!(note.created_at, created_at);
+        assert!(!note.is_deleted);
+        assert!(reopened.list_trash().await.is_empty());
+        assert!(fixture.config.notes_path().join("comeback.md").exists());
+
+        // The ID sticks after another reload
+        let again = NoteStore::new(fixture.config.clone());
+        again.load_all().await.unwrap();
+        assert!(again.get(id).await.is_some_and(|n| n.content == "Original body"));
+    }
+
+    #[tokio::test]
+    async fn test_new_note_at_deleted_path_gets_fresh_id() {
+        let fixture = StoreTestFixture::new().await;
+
+        let old_id = fixture.create_test_note("Reused", "Old", None).await;
+        fixture.store.delete(old_id).await.unwrap();
+        let new_id = fixture.create_test_note("Reused", "New", None).await;
+
+        assert_ne!(old_id, new_id);
+        assert!(fixture.store.get(old_id).await.unwrap().is_deleted);
+    }
+
+    #[tokio::test]
+    async fn test_restore_refuses_occupied_path() {
+        let fixture = StoreTestFixture::new().await;
+
+        let old_id = fixture.create_test_note("Taken", "Old", None).await;
+        fixture.store.delete(old_id).await.unwrap();
+        fixture.create_test_note("Taken", "New", None).await;
+
+        let result = fixture.store.restore_from_trash(old_id).await;
+        assert!(matches!(result, Err(notidium::Error::NoteAlreadyExists(_))));
+        assert_eq!(fixture.store.list_trash().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_purge_removes_file_and_entry() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Gone For Good", "Body", None).await;
+        fixture.store.delete(id).await.unwrap();
+        fixture.store.purge_from_trash(id).await.unwrap();
+
+        assert!(fixture.store.list_trash().await.is_empty());
+        assert!(fixture.store.get(id).await.is_none());
+        assert!(!fixture.config.trash_path().join(format!("{}.md", id)).exists());
+        assert!(matches!(
+            fixture.store.restore_from_trash(id).await,
+            Err(notidium::Error::NoteNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_id_not_found() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = uuid::Uuid::new_v4();
+        assert!(matches!(
+            fixture.store.purge_from_trash(id).await,
+            Err(notidium::Error::NoteNotFound(_))
+        ));
+    }
+}
+
+// ============================================================================
+// Operation Journal Tests
+// ============================================================================
+
+mod journal_tests {
+    use super::*;
+    use notidium::store::{FileWrite, Operation, Trash, TrashEntry};
+
+    fn journal_entries(config: &Config) -> usize {
+        std::fs::read_dir(config.journal_path()).map_or(0, |dir| dir.count())
+    }
+
+    #[tokio::test]
+    async fn test_finished_operations_leave_no_entry() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Tagged", "Body", Some(vec!["old".into()])).await;
+        fixture.store.rename_tag("old", "new").await.unwrap();
+        fixture.store.delete(id).await.unwrap();
+        fixture.store.restore_from_trash(id).await.unwrap();
+
+        assert_eq!(journal_entries(&fixture.config), 0);
+        assert_eq!(fixture.store.get(id).await.unwrap().tags(), vec!["new"]);
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_delete_finished_on_reopen() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Half Gone", "Body", None).await;
+        let note_path = fixture.config.notes_path().join("half-gone.md");
+        let trash_path = fixture.config.trash_path().join(format!("{}.md", id));
+        let content = std::fs::read_to_string(&note_path).unwrap();
+
+        let mut trash = Trash::default();
+        trash.insert(TrashEntry {
+            id,
+            title: "Half Gone".into(),
+            original_path: PathBuf::from("half-gone.md"),
+            deleted_at: chrono::Utc::now(),
+            created_at: None,
+            updated_at: None,
+            created_by: None,
+            updated_by: None,
+        });
+        let op = Operation {
+            id: uuid::Uuid::new_v4(),
+            description: "Delete Half Gone".into(),
+            started_at: chrono::Utc::now(),
+            writes: vec![
+                FileWrite::new(&trash_path, None, Some(content.clone())),
+                FileWrite::new(
+                    fixture.config.trash_path().join("index.json"),
+                    None,
+                    Some(trash.to_json().unwrap()),
+                ),
+                FileWrite::new(&note_path, Some(content.clone()), None),
+            ],
+        };
+        // The process died after moving the file into the trash
+        std::fs::create_dir_all(fixture.config.journal_path()).unwrap();
+        std::fs::write(
+            fixture.config.journal_path().join(format!("{}.json", op.id)),
+            serde_json::to_string(&op).unwrap(),
+        )
+        .unwrap();
+        std::fs::create_dir_all(fixture.config.trash_path()).unwrap();
+        std::fs::write(&trash_path, &content).unwrap();
+
+        let reopened = NoteStore::new(fixture.config.clone());
+        reopened.load_all().await.unwrap();
+
+        assert!(!note_path.exists());
+        assert!(reopened.get(id).await.is_none());
+        assert_eq!(reopened.list_trash().await[0].id, id);
+        assert_eq!(journal_entries(&fixture.config), 0);
+
+        reopened.restore_from_trash(id).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&note_path).unwrap(), content);
+    }
+}
+
+// ============================================================================
+// Sync Conflict Tests
+// ============================================================================
+
+mod sync_conflict_tests {
+    use super::*;
+
+    fn conflicted_copies(fixture: &StoreTestFixture) -> Vec<PathBuf> {
+        std::fs::read_dir(fixture.config.notes_path())
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.to_string_lossy().contains("(conflicted copy"))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_update_keeps_external_edit_as_copy() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Shared", "Original", None).await;
+        let path = fixture.config.notes_path().join("shared.md");
+        std::fs::write(&path, "Edited by sync client").unwrap();
+
+        let mut conflicts = fixture.store.subscribe_conflicts();
+        fixture
+            .store
+            .update(id, "Edited in app".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "Edited in app");
+        let copies = conflicted_copies(&fixture);
+        assert_eq!(copies.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&copies[0]).unwrap(),
+            "Edited by sync client"
+        );
+
+        let conflict = conflicts.try_recv().unwrap();
+        assert_eq!(conflict.note_id, id);
+        assert_eq!(conflict.path, PathBuf::from("shared.md"));
+        assert_eq!(conflict.copy.content, "Edited by sync client");
+        assert!(fixture.store.get(conflict.copy.id).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_sync_keeps_app_edit_as_copy() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Both Sides", "Original", None).await;
+        fixture.store.snapshot_checksums().await.unwrap();
+        fixture
+            .store
+            .update(id, "Edited in app".to_string())
+            .await
+            .unwrap();
+
+        let path = fixture.config.notes_path().join("both-sides.md");
+        std::fs::write(&path, "Edited by sync client").unwrap();
+        fixture.store.sync_paths(&[path]).await.unwrap();
+
+        let note = fixture.store.get(id).await.unwrap();
+        assert_eq!(note.content, "Edited by sync client");
+        let copies = conflicted_copies(&fixture);
+        assert_eq!(copies.len(), 1);
+        assert_eq!(std::fs::read_to_string(&copies[0]).unwrap(), "Edited in app");
+    }
+
+    #[tokio::test]
+    async fn test_one_sided_changes_make_no_copy() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Calm", "Original", None).await;
+        fixture.store.snapshot_checksums().await.unwrap();
+        let path = fixture.config.notes_path().join("calm.md");
+
+        // External edit only
+        std::fs::write(&path, "External").unwrap();
+        fixture.store.sync_paths(std::slice::from_ref(&path)).await.unwrap();
+        // A second external edit after the first was adopted
+        std::fs::write(&path, "External again").unwrap();
+        fixture.store.sync_paths(std::slice::from_ref(&path)).await.unwrap();
+
+        // App edit, snapshot, then external edit
+        fixture.store.update(id, "App".to_string()).await.unwrap();
+        fixture.store.snapshot_checksums().await.unwrap();
+        std::fs::write(&path, "External last").unwrap();
+        fixture.store.sync_paths(&[path]).await.unwrap();
+
+        assert!(conflicted_copies(&fixture).is_empty());
+        assert_eq!(fixture.store.get(id).await.unwrap().content, "External last");
+    }
+
+    #[tokio::test]
+    async fn test_checksums_persisted_on_load() {
+        let fixture = StoreTestFixture::new().await;
+
+        fixture.create_test_note("Tracked", "Body", None).await;
+        fixture.store.load_all().await.unwrap();
+
+        let snapshot =
+            notidium::store::ChecksumSnapshot::load(&fixture.config.checksums_path()).unwrap();
+        assert!(snapshot.taken_at.is_some());
+        assert!(snapshot.get(std::path::Path::new("tracked.md")).is_some());
+    }
+}
+
+// ============================================================================
+// Co-access Tests
+// ============================================================================
+
+mod co_access_tests {
+    use super::*;
+    use notidium::store::MetadataDb;
+
+    #[tokio::test]
+    async fn test_notes_opened_together() {
+        let fixture = StoreTestFixture::new().await;
+
+        let a = fixture.create_test_note("Alpha", "A", None).await;
+        let b = fixture.create_test_note("Beta", "B", None).await;
+        let c = fixture.create_test_note("Gamma", "C", None).await;
+
+        fixture.store.record_access(a).await.unwrap();
+        fixture.store.record_access(b).await.unwrap();
+
+        let together = fixture.store.co_accessed(a, 10).await.unwrap();
+        assert_eq!(together.len(), 1);
+        assert_eq!(together[0].0.id, b);
+        assert_eq!(together[0].1, 1);
+
+        assert!(fixture.store.co_accessed(c, 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_deleted_notes_excluded() {
+        let fixture = StoreTestFixture::new().await;
+
+        let a = fixture.create_test_note("Alpha", "A", None).await;
+        let b = fixture.create_test_note("Beta", "B", None).await;
+        fixture.store.record_access(a).await.unwrap();
+        fixture.store.record_access(b).await.unwrap();
+        fixture.store.delete(b).await.unwrap();
+
+        assert!(fixture.store.co_accessed(a, 10).await.unwrap().is_empty());
+        assert!(matches!(
+            fixture.store.co_accessed(b, 10).await,
+            Err(notidium::Error::NoteNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_window_dedupe_and_ranking() {
+        let db = MetadataDb::open_in_memory().unwrap();
+        let (a, b, c, far) = (
+            uuid::Uuid::new_v4(),
+            uuid::Uuid::new_v4(),
+            uuid::Uuid::new_v4(),
+            uuid::Uuid::new_v4(),
+        );
+        let record = |id, at| db.record_access(id, at, 60, 1_000_000).unwrap();
+
+        // Two sessions with `a`; `b` shows up in both, `c` in one
+        record(a, 10_000);
+        record(a, 10_030); // deduplicated
+        record(b, 10_100);
+        record(c, 10_200);
+        record(a, 20_000);
+        record(b, 20_500);
+        // Outside the 30 minute window of any `a` open
+        record(far, 30_000);
+
+        let together = db.get_co_accessed(a, 1800, 10).unwrap();
+        assert_eq!(
+            together,
+            vec![(b.to_string(), 2), (c.to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_old_entries_pruned() {
+        let db = MetadataDb::open_in_memory().unwrap();
+        let (a, b) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+
+        db.record_access(a, 1_000, 60, 500).unwrap();
+        db.record_access(b, 1_100, 60, 500).unwrap();
+        // Far enough later that both earlier opens fall out of retention
+        db.record_access(a, 5_000, 60, 500).unwrap();
+
+        assert!(db.get_co_accessed(a, 1800, 10).unwrap().is_empty());
+    }
+}
+
+// ============================================================================
+// Permanent Delete Tests
+// ============================================================================
+
+mod permanent_delete_tests {
+    use super::*;
+    use notidium::store::VectorStore;
+    use notidium::types::{Chunk, ChunkType};
+
+    #[tokio::test]
+    async fn test_permanent_delete_removes_everything() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Secret", "Linked from [[Other]]", None).await;
+        let other = fixture.create_test_note("Other", "Mentions [[Secret]]", None).await;
+        fixture.store.record_access(id).await.unwrap();
+        fixture.store.record_access(other).await.unwrap();
+
+        let mut chunk = Chunk::new(id, "Secret".to_string(), ChunkType::Prose);
+        chunk.prose_embedding = Some(vec![1.0, 0.0]);
+        let vectors = VectorStore::open(&fixture.config.vectors_path()).await.unwrap();
+        vectors.add_chunks(&[chunk]).await.unwrap();
+
+        fixture.store.delete_permanently(id).await.unwrap();
+
+        assert!(fixture.store.get(id).await.is_none());
+        assert!(!fixture.config.notes_path().join("secret.md").exists());
+        assert!(fixture.store.list_trash().await.is_empty());
+        assert!(fixture.store.co_accessed(other, 10).await.unwrap().is_empty());
+        assert!(fixture.store.backlinks(other).await.unwrap().is_empty());
+        let vectors = VectorStore::open(&fixture.config.vectors_path()).await.unwrap();
+        assert_eq!(vectors.count().await.unwrap(), 0);
+
+        // Nothing comes back after a reload
+        let reopened = NoteStore::new(fixture.config.clone());
+        let notes = reopened.load_all().await.unwrap();
+        assert!(notes.iter().all(|n| n.id != id));
+    }
+
+    #[tokio::test]
+    async fn test_permanent_delete_of_trashed_note() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Trashed First", "Body", None).await;
+        fixture.store.delete(id).await.unwrap();
+        fixture.store.delete_permanently(id).await.unwrap();
+
+        assert!(fixture.store.list_trash().await.is_empty());
+        assert!(fixture.store.get(id).await.is_none());
+        assert!(matches!(
+            fixture.store.delete_permanently(id).await,
+            Err(notidium::Error::NoteNotFound(_))
+        ));
+    }
+}
+
+// ============================================================================
+// Multi-User Tests
+// ============================================================================
+
+mod multi_user_tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::{middleware, routing::get, Extension, Router};
+    use notidium::api::{authenticate, AuthState, CurrentUser};
+    use notidium::store::{Contribution, Users};
+    use notidium::types::NoteMeta;
+    use tower::ServiceExt;
+
+    fn whoami_router(users: Users) -> Router {
+        Router::new()
+            .route(
+                "/api/whoami",
+                get(|user: Option<Extension<CurrentUser>>| async move {
+                    user.map(|Extension(user)| user.name).unwrap_or_default()
+                }),
+            )
+            .route("/health", get(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(AuthState::from(Arc::new(users)), authenticate))
+    }
+
+    async fn get_status(router: Router, uri: &str, auth: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().uri(uri);
+        if let Some(auth) = auth {
+            request = request.header(header::AUTHORIZATION, auth);
+        }
+        let response = router
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        response.status()
+    }
+
+    #[tokio::test]
+    async fn test_attribution_shows_in_note_meta_and_persists() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Shared Plan", "Draft", None).await;
+        fixture.store.attribute(id, "alice", Contribution::Created).await.unwrap();
+        fixture.store.update(id, "# Shared Plan\n\nRevised".to_string()).await.unwrap();
+        let note = fixture.store.attribute(id, "bob", Contribution::Updated).await.unwrap();
+
+        let meta = NoteMeta::from(&note);
+        assert_eq!(meta.created_by.as_deref(), Some("alice"));
+        assert_eq!(meta.updated_by.as_deref(), Some("bob"));
+
+        let reopened = NoteStore::new(fixture.config.clone());
+        reopened.load_all().await.unwrap();
+        let reloaded = reopened.get(id).await.unwrap();
+        assert_eq!(reloaded.created_by.as_deref(), Some("alice"));
+        assert_eq!(reloaded.updated_by.as_deref(), Some("bob"));
+    }
+
+    #[tokio::test]
+    async fn test_unattributed_update_clears_last_editor() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Edited Anonymously", "Draft", None).await;
+        fixture.store.attribute(id, "alice", Contribution::Created).await.unwrap();
+        let note = fixture.store.update(id, "Changed".to_string()).await.unwrap();
+
+        assert_eq!(note.created_by.as_deref(), Some("alice"));
+        assert_eq!(note.updated_by, None);
+    }
+
+    #[tokio::test]
+    async fn test_list_filters_by_author() {
+        let fixture = StoreTestFixture::new().await;
+
+        let by_alice = fixture.create_test_note("Alice Note", "A", None).await;
+        let edited_by_alice = fixture.create_test_note("Bob Note", "B", None).await;
+        let by_bob = fixture.create_test_note("Other Bob Note", "C", None).await;
+        fixture.create_test_note("Nobody's Note", "D", None).await;
+
+        fixture.store.attribute(by_alice, "alice", Contribution::Created).await.unwrap();
+        fixture.store.attribute(edited_by_alice, "bob", Contribution::Created).await.unwrap();
+        fixture.store.attribute(edited_by_alice, "alice", Contribution::Updated).await.unwrap();
+        fixture.store.attribute(by_bob, "bob", Contribution::Created).await.unwrap();
+
+        let mut alice: Vec<_> = fixture
+            .store
+            .list_paginated(0, 100, None, Some("alice"), NoteSort::default())
+            .await
+            .into_iter()
+            .map(|n| n.id)
+            .collect();
+        alice.sort();
+        let mut expected = vec![by_alice, edited_by_alice];
+        expected.sort();
+        assert_eq!(alice, expected);
+
+        let bob = fixture.store.list_paginated(0, 100, None, Some("bob"), NoteSort::default()).await;
+        assert_eq!(bob.len(), 2);
+        assert!(fixture.store.list_paginated(0, 100, None, Some("carol"), NoteSort::default()).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_trash_round_trip_keeps_authorship() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Authored", "Body", None).await;
+        fixture.store.attribute(id, "alice", Contribution::Created).await.unwrap();
+        fixture.store.delete(id).await.unwrap();
+
+        let restored = fixture.store.restore_from_trash(id).await.unwrap();
+        assert_eq!(restored.created_by.as_deref(), Some("alice"));
+        assert_eq!(restored.updated_by.as_deref(), Some("alice"));
+    }
+
+    #[tokio::test]
+    async fn test_users_persist_by_key_hash() {
+        let fixture = StoreTestFixture::new().await;
+        let path = fixture.config.users_path();
+
+        let mut users = Users::default();
+        let key = users.add("alice", Vec::new()).unwrap();
+        users.save(&path).unwrap();
+
+        assert!(!std::fs::read_to_string(&path).unwrap().contains(&key));
+        let loaded = Users::load(&path).unwrap();
+        assert_eq!(loaded.authenticate(&key).unwrap().name, "alice");
+    }
+
+    #[tokio::test]
+    async fn test_single_user_mode_needs_no_key() {
+        let router = whoami_router(Users::default());
+        assert_eq!(get_status(router, "/api/whoami", None).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_api_key_required_once_users_exist() {
+        let mut users = Users::default();
+        let key = users.add("alice", Vec::new()).unwrap();
+        let router = whoami_router(users);
+
+        assert_eq!(
+            get_status(router.clone(), "/api/whoami", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            get_status(router.clone(), "/api/whoami", Some("Bearer ntd_wrong")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(get_status(router.clone(), "/health", None).await, StatusCode::OK);
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/api/whoami")
+                    .header(header::AUTHORIZATION, format!("Bearer {}", key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), 1024).await.unwrap();
+        assert_eq!(&body[..], b"alice");
+    }
+}
+
+// ============================================================================
+// Version History Tests
+// ============================================================================
+
+mod version_history_tests {
+    use super::*;
+    use notidium::Error;
+
+    async fn history_store(fixture: &StoreTestFixture) -> NoteStore {
+        let mut config = fixture.config.clone();
+        config.history.enabled = true;
+        let store = NoteStore::new(config);
+        store.load_all().await.unwrap();
+        store
+    }
+
+    #[tokio::test]
+    async fn test_every_change_is_a_version() {
+        let fixture = StoreTestFixture::new().await;
+        let store = history_store(&fixture).await;
+
+        let note = store
+            .create("Plan".to_string(), "# Plan\n\nFirst draft".to_string(), None)
+            .await
+            .unwrap();
+        store
+            .update(note.id, "# Plan\n\nSecond draft".to_string())
+            .await
+            .unwrap();
+
+        let versions = store.versions(note.id).await.unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].message, "Update Plan");
+        assert_eq!(versions[1].message, "Create Plan");
+
+        let (entry, content) = store.version(note.id, &versions[1].rev[..8]).await.unwrap();
+        assert_eq!(entry.rev, versions[1].rev);
+        assert_eq!(content, "# Plan\n\nFirst draft");
+    }
+
+    #[tokio::test]
+    async fn test_restore_version() {
+        let fixture = StoreTestFixture::new().await;
+        let store = history_store(&fixture).await;
+
+        let note = store
+            .create("Original".to_string(), "# Original\n\nKeep me".to_string(), None)
+            .await
+            .unwrap();
+        store.update(note.id, "# Clobbered\n\n".to_string()).await.unwrap();
+        let first = store.versions(note.id).await.unwrap().pop().unwrap();
+
+        let restored = store.restore_version(note.id, &first.rev).await.unwrap();
+        assert_eq!(restored.id, note.id);
+        assert_eq!(restored.title, "Original");
+        assert_eq!(restored.content, "# Original\n\nKeep me");
+        assert_eq!(store.get(note.id).await.unwrap().content, restored.content);
+
+        let on_disk =
+            std::fs::read_to_string(fixture.config.notes_path().join(&note.file_path)).unwrap();
+        assert_eq!(on_disk, "# Original\n\nKeep me");
+
+        let versions = store.versions(note.id).await.unwrap();
+        assert_eq!(versions.len(), 3);
+        assert!(versions[0].message.starts_with("Restore Original to "));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_versions_are_not_found() {
+        let fixture = StoreTestFixture::new().await;
+        let store = history_store(&fixture).await;
+
+        let a = store.create("A".to_string(), "a".to_string(), None).await.unwrap();
+        let b = store.create("B".to_string(), "b".to_string(), None).await.unwrap();
+        let b_rev = store.versions(b.id).await.unwrap()[0].rev.clone();
+
+        // A revision that exists, but never touched this note
+        assert!(matches!(
+            store.version(a.id, &b_rev).await,
+            Err(Error::VersionNotFound(_))
+        ));
+        assert!(matches!(
+            store.version(a.id, "HEAD~1").await,
+            Err(Error::VersionNotFound(_))
+        ));
+        assert!(matches!(
+            store.versions(uuid::Uuid::new_v4()).await,
+            Err(Error::NoteNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_history_disabled_by_default() {
+        let fixture = StoreTestFixture::new().await;
+
+        let id = fixture.create_test_note("Untracked", "Body", None).await;
+        fixture.store.update(id, "Changed".to_string()).await.unwrap();
+
+        assert!(fixture.store.versions(id).await.unwrap().is_empty());
+        assert!(!fixture.config.history_path().exists());
+    }
+}
+
+// ============================================================================
+// Access Control Tests
+// ============================================================================
+
+mod access_control_tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{header, Request};
+    use axum::{middleware, routing::get, Router};
+    use notidium::api::{authenticate, AuthState};
+    use notidium::error::Error;
+    use notidium::store::{AccessLevel, Permissions, Users};
+    use std::path::Path;
+    use tower::ServiceExt;
+
+    /// Permissions of a user with the given scopes, as the auth layer sees them
+    fn permissions(scopes: &[&str]) -> Permissions {
+        let mut users = Users::default();
+        let key = users
+            .add("bot", scopes.iter().map(|s| s.parse().unwrap()).collect())
+            .unwrap();
+        users.authenticate(&key).unwrap().permissions()
+    }
+
+    #[tokio::test]
+    async fn test_create_in_folder() {
+        let fixture = StoreTestFixture::new().await;
+
+        let note = fixture
+            .store
+            .create_in(Path::new("reports/daily"), "CI Run".into(), "green".into(), None)
+            .await
+            .unwrap();
+        assert_eq!(note.file_path, PathBuf::from("reports/daily/ci-run.md"));
+        assert!(fixture.config.notes_path().join(&note.file_path).exists());
+
+        for folder in ["../outside", "/tmp"] {
+            let result = fixture
+                .store
+                .create_in(Path::new(folder), "Escape".into(), String::new(), None)
+                .await;
+            assert!(matches!(result, Err(Error::InvalidNotePath(_))), "{}", folder);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_folder_scoped_key_writes_only_its_folder() {
+        let fixture = StoreTestFixture::new().await;
+        let report = fixture
+            .store
+            .create_in(Path::new("reports"), "Nightly".into(), "ok".into(), None)
+            .await
+            .unwrap();
+        let private = fixture.create_test_note("Private", "secret", None).await;
+        let private = fixture.store.get(private).await.unwrap();
+
+        let perms = permissions(&["write:reports/"]);
+        assert!(perms.check_note(AccessLevel::Write, &report).is_ok());
+        assert!(perms.check_create(Path::new("reports"), &[]).is_ok());
+        assert!(matches!(
+            perms.check_create(Path::new(""), &[]),
+            Err(Error::AccessDenied(_))
+        ));
+        // Notes outside the scopes look like they don't exist
+        assert!(matches!(
+            perms.check_note(AccessLevel::Read, &private),
+            Err(Error::NoteNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_read_scope_denies_writes() {
+        let fixture = StoreTestFixture::new().await;
+        let id = fixture
+            .create_test_note("Handbook", "Be nice", Some(vec!["public".into()]))
+            .await;
+        let note = fixture.store.get(id).await.unwrap();
+
+        let perms = permissions(&["read:#public"]);
+        assert!(perms.check_note(AccessLevel::Read, &note).is_ok());
+        assert!(matches!(
+            perms.check_note(AccessLevel::Write, &note),
+            Err(Error::AccessDenied(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_auth_layer_attaches_scopes() {
+        let mut users = Users::default();
+        let key = users
+            .add("bot", vec!["write:reports/".parse().unwrap()])
+            .unwrap();
+        let router = Router::new()
+            .route(
+                "/api/can-write",
+                get(|permissions: Permissions| async move {
+                    let can_write = |path| permissions.allows(AccessLevel::Write, Path::new(path), &[]);
+                    format!("{},{}", can_write("reports/a.md"), can_write("notes/a.md"))
+                }),
+            )
+            .layer(middleware::from_fn_with_state(AuthState::from(Arc::new(users)), authenticate));
+
+        let response = router
+            .oneshot(
+                Request::builder()
+                    .uri("/api/can-write")
+                    .header(header::AUTHORIZATION, format!("Bearer {}", key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), 1024).await.unwrap();
+        assert_eq!(&body[..], b"true,false");
+    }
+}
+
+// ============================================================================
+// Template Tests
+// ============================================================================
+
+mod template_tests {
+    use super::*;
+    use notidium::error::Error;
+    use std::path::Path;
+
+    fn write_template(fixture: &StoreTestFixture, name: &str, content: &str) {
+        std::fs::write(fixture.config.templates_path().join(name), content).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_create_from_template_fills_placeholders() {
+        let fixture = StoreTestFixture::new().await;
+        write_template(
+            &fixture,
+            "meeting.md",
+            "---\ntags: [meeting]\n---\n\n# {{title}}\n\nDate: {{date}}\n\nLinks: {{clipboard}}\n",
+        );
+
+        let note = fixture
+            .store
+            .create_from_template(
+                "meeting",
+                Path::new("meetings"),
+                "Standup".into(),
+                Some("https://example.com".into()),
+            )
+            .await
+            .unwrap();
+
+        let today = chrono::Local::now().date_naive().format("%Y-%m-%d").to_string();
+        assert!(note.content.contains("# Standup"));
+        assert!(note.content.contains(&format!("Date: {}", today)));
+        assert!(note.content.contains("Links: https://example.com"));
+        assert_eq!(note.file_path, PathBuf::from("meetings/standup.md"));
+        // Frontmatter from the template applies to the new note
+        assert_eq!(note.tags(), vec!["meeting".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_missing_template() {
+        let fixture = StoreTestFixture::new().await;
+        let result = fixture
+            .store
+            .create_from_template("nope", Path::new(""), "Title".into(), None)
+            .await;
+        assert!(matches!(result, Err(Error::TemplateNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_list_templates() {
+        let fixture = StoreTestFixture::new().await;
+        write_template(&fixture, "weekly.md", "# {{title}}");
+        write_template(&fixture, "daily.md", "# {{date}}");
+        write_template(&fixture, "notes.txt", "not a template");
+
+        let templates = fixture.store.templates().list().await.unwrap();
+        assert_eq!(templates, vec!["daily".to_string(), "weekly".to_string()]);
+    }
+}
+
+// ============================================================================
+// Session Login Tests
+// ============================================================================
+
+mod session_login_tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{header, Method, Request, StatusCode};
+    use axum::{middleware, routing::get, Router};
+    use notidium::api::{authenticate, hash_password, ApiToken, AuthState, Sessions};
+    use notidium::config::AuthConfig;
+    use notidium::mcp::McpAuth;
+    use notidium::store::Users;
+    use tower::ServiceExt;
+
+    fn login_state() -> AuthState {
+        let sessions = Sessions::new(&AuthConfig {
+            password_hash: Some(hash_password("correct horse").unwrap()),
+            ..AuthConfig::default()
+        });
+        AuthState {
+            users: Arc::new(Users::default()),
+            sessions: Arc::new(sessions),
+            mcp: Arc::new(McpAuth::disabled()),
+            api_token: Arc::new(ApiToken::disabled()),
+        }
+    }
+
+    fn router(auth: AuthState) -> Router {
+        Router::new()
+            .route("/", get(|| async { "ui" }))
+            .route("/login", get(|| async { "login" }))
+            .route("/api/notes", get(|| async { "notes" }).post(|| async { "created" }))
+            .layer(middleware::from_fn_with_state(auth, authenticate))
+    }
+
+    async fn send(router: Router, method: Method, uri: &str, headers: &[(&str, &str)]) -> axum::response::Response {
+        let mut request = Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        router.oneshot(request.body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    #[test]
+    fn test_password_check() {
+        let auth = login_state();
+        assert!(auth.sessions.is_enabled());
+        assert!(auth.sessions.verify_password("correct horse"));
+        assert!(!auth.sessions.verify_password("wrong"));
+    }
+
+    #[tokio::test]
+    async fn test_pages_redirect_to_login_without_session() {
+        let auth = login_state();
+
+        let response = send(router(auth.clone()), Method::GET, "/", &[]).await;
+        assert!(response.status().is_redirection());
+        assert_eq!(response.headers()[header::LOCATION], "/login?next=/");
+
+        let response = send(router(auth.clone()), Method::GET, "/login", &[]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = send(router(auth), Method::GET, "/api/notes", &[]).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(response.headers().get(header::WWW_AUTHENTICATE).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_session_cookie_grants_access() {
+        let auth = login_state();
+        let (token, _) = auth.sessions.create();
+        let cookie = format!("notidium_session={}", token);
+
+        let response = send(router(auth.clone()), Method::GET, "/api/notes", &[("cookie", &cookie)]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        auth.sessions.remove(&token);
+        let response = send(router(auth), Method::GET, "/api/notes", &[("cookie", &cookie)]).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_mutating_requests_need_csrf_proof() {
+        let auth = login_state();
+        let (token, session) = auth.sessions.create();
+        let cookie = format!("notidium_session={}", token);
+
+        let response = send(router(auth.clone()), Method::POST, "/api/notes", &[("cookie", &cookie)]).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = send(
+            router(auth.clone()),
+            Method::POST,
+            "/api/notes",
+            &[("cookie", &cookie), ("origin", "https://evil.example"), ("host", "localhost:3939")],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = send(
+            router(auth.clone()),
+            Method::POST,
+            "/api/notes",
+            &[("cookie", &cookie), ("x-csrf-token", &session.csrf_token)],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = send(
+            router(auth),
+            Method::POST,
+            "/api/notes",
+            &[("cookie", &cookie), ("sec-fetch-site", "same-origin")],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
+
+// ============================================================================
+// MCP Auth Tests
+// ============================================================================
+
+mod mcp_auth_tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::{middleware, routing::get, Extension, Router};
+    use notidium::api::{authenticate, ApiToken, AuthState, CurrentUser, Sessions};
+    use notidium::config::McpConfig;
+    use notidium::mcp::auth::{add_client, routes};
+    use notidium::mcp::McpAuth;
+    use notidium::store::Users;
+    use tower::ServiceExt;
+
+    struct Server {
+        router: Router,
+        secret: String,
+        user_key: String,
+    }
+
+    fn server() -> Server {
+        let mut config = McpConfig::default();
+        let secret = add_client(&mut config, "claude").unwrap();
+        let mut users = Users::default();
+        let user_key = users.add("alice", Vec::new()).unwrap();
+        let mcp = Arc::new(McpAuth::new(&config));
+
+        let auth = AuthState {
+            users: Arc::new(users),
+            sessions: Arc::new(Sessions::disabled()),
+            mcp: mcp.clone(),
+            api_token: Arc::new(ApiToken::disabled()),
+        };
+        let router = Router::new()
+            .route(
+                "/mcp",
+                get(|user: Option<Extension<CurrentUser>>| async move {
+                    user.map(|Extension(user)| user.name).unwrap_or_default()
+                }),
+            )
+            .merge(routes(mcp))
+            .layer(middleware::from_fn_with_state(auth, authenticate));
+
+        Server { router, secret, user_key }
+    }
+
+    async fn call_mcp(router: Router, token: Option<&str>) -> (StatusCode, String) {
+        let mut request = Request::builder().uri("/mcp").header(header::HOST, "notes.example:3939");
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let response = router.oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let challenge = response
+            .headers()
+            .get(header::WWW_AUTHENTICATE)
+            .map(|v| v.to_str().unwrap().to_string());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, challenge.unwrap_or_else(|| String::from_utf8(body.to_vec()).unwrap()))
+    }
+
+    async fn request_token(router: Router, form: String) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/oauth/token")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(form))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_mcp_requires_token() {
+        let server = server();
+
+        let (status, challenge) = call_mcp(server.router.clone(), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(challenge.starts_with("Bearer "));
+        assert!(challenge.contains("http://notes.example:3939/.well-known/oauth-protected-resource"));
+
+        let (status, _) = call_mcp(server.router, Some("ntdm_wrong")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_static_token_and_user_key_are_accepted() {
+        let server = server();
+
+        let (status, name) = call_mcp(server.router.clone(), Some(&server.secret)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(name, "claude");
+
+        let (status, name) = call_mcp(server.router, Some(&server.user_key)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(name, "alice");
+    }
+
+    #[tokio::test]
+    async fn test_client_credentials_grant() {
+        let server = server();
+
+        let form = format!(
+            "grant_type=client_credentials&client_id=claude&client_secret={}",
+            server.secret
+        );
+        let (status, body) = request_token(server.router.clone(), form).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["token_type"], "Bearer");
+        assert_eq!(body["expires_in"], 3600);
+
+        let token = body["access_token"].as_str().unwrap();
+        let (status, name) = call_mcp(server.router.clone(), Some(token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(name, "claude");
+
+        let form = "grant_type=client_credentials&client_id=claude&client_secret=wrong".to_string();
+        let (status, body) = request_token(server.router.clone(), form).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"], "invalid_client");
+
+        let (status, body) = request_token(server.router, "grant_type=password".to_string()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "unsupported_grant_type");
+    }
+
+    #[tokio::test]
+    async fn test_discovery_metadata_is_public() {
+        let server = server();
+
+        let request = Request::builder()
+            .uri("/.well-known/oauth-authorization-server")
+            .header(header::HOST, "localhost:3939")
+            .body(Body::empty())
+            .unwrap();
+        let response = server.router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let metadata: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(metadata["token_endpoint"], "http://localhost:3939/oauth/token");
+        assert_eq!(metadata["grant_types_supported"][0], "client_credentials");
+    }
+}
+
+// ============================================================================
+// API Token Tests
+// ============================================================================
+
+mod api_token_tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::{middleware, routing::get, Router};
+    use base64::Engine;
+    use notidium::api::{authenticate, generate_api_token, ApiToken, AuthState, Sessions};
+    use notidium::config::{AuthConfig, McpConfig};
+    use notidium::mcp::auth::add_client;
+    use notidium::mcp::McpAuth;
+    use notidium::store::Users;
+    use tower::ServiceExt;
+
+    fn router(mcp: McpConfig) -> (Router, String) {
+        let (token, hash) = generate_api_token();
+        let auth = AuthState {
+            users: Arc::new(Users::default()),
+            sessions: Arc::new(Sessions::disabled()),
+            mcp: Arc::new(McpAuth::new(&mcp)),
+            api_token: Arc::new(ApiToken::new(&AuthConfig {
+                api_token_hash: Some(hash),
+                ..AuthConfig::default()
+            })),
+        };
+        let router = Router::new()
+            .route("/", get(|| async { "ui" }))
+            .route("/health", get(|| async { "ok" }))
+            .route("/api/notes", get(|| async { "notes" }))
+            .route("/mcp", get(|| async { "mcp" }))
+            .layer(middleware::from_fn_with_state(auth, authenticate));
+        (router, token)
+    }
+
+    async fn get_status(router: Router, uri: &str, auth: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().uri(uri);
+        if let Some(auth) = auth {
+            request = request.header(header::AUTHORIZATION, auth);
+        }
+        router.oneshot(request.body(Body::empty()).unwrap()).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_token_required_on_api_and_mcp() {
+        let (router, token) = router(McpConfig::default());
+        let bearer = format!("Bearer {}", token);
+
+        assert_eq!(get_status(router.clone(), "/api/notes", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(get_status(router.clone(), "/mcp", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            get_status(router.clone(), "/api/notes", Some("Bearer ntdt_wrong")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(get_status(router.clone(), "/api/notes", Some(&bearer)).await, StatusCode::OK);
+        assert_eq!(get_status(router.clone(), "/mcp", Some(&bearer)).await, StatusCode::OK);
+
+        // The UI shell and health checks stay reachable
+        assert_eq!(get_status(router.clone(), "/", None).await, StatusCode::OK);
+        assert_eq!(get_status(router, "/health", None).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_browser_sends_token_as_basic_password() {
+        let (router, token) = router(McpConfig::default());
+
+        let response = router
+            .clone()
+            .oneshot(Request::builder().uri("/api/notes").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert!(response.headers()[header::WWW_AUTHENTICATE]
+            .to_str()
+            .unwrap()
+            .starts_with("Basic "));
+
+        let encoded = base64::engine::general_purpose::STANDARD.encode(format!("me:{}", token));
+        let basic = format!("Basic {}", encoded);
+        assert_eq!(get_status(router, "/api/notes", Some(&basic)).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_token_accepted_alongside_mcp_clients() {
+        let mut mcp = McpConfig::default();
+        let secret = add_client(&mut mcp, "claude").unwrap();
+        let (router, token) = router(mcp);
+
+        for credential in [&token, &secret] {
+            let bearer = format!("Bearer {}", credential);
+            assert_eq!(get_status(router.clone(), "/mcp", Some(&bearer)).await, StatusCode::OK);
+        }
+        assert_eq!(get_status(router, "/mcp", None).await, StatusCode::UNAUTHORIZED);
+    }
+}
+
+// ============================================================================
+// Tag Management Tests
+// ============================================================================
+
+mod tag_management_tests {
+    use super::*;
+    use notidium::error::Error;
+    use notidium::store::{MetadataDb, Permissions};
+
+    #[tokio::test]
+    async fn test_rename_tag_rewrites_frontmatter() {
+        let fixture = StoreTestFixture::new().await;
+        let a = fixture
+            .create_test_note("Alpha", "Body A", Some(vec!["Rust".into(), "draft".into()]))
+            .await;
+        let b = fixture
+            .create_test_note("Beta", "Body B", Some(vec!["rust".into()]))
+            .await;
+        let c = fixture.create_test_note("Gamma", "Body C", Some(vec!["go".into()])).await;
+
+        let changed = fixture.store.rename_tag("rust", "rustlang").await.unwrap();
+        let mut ids: Vec<_> = changed.iter().map(|n| n.id).collect();
+        ids.sort();
+        let mut expected = vec![a, b];
+        expected.sort();
+        assert_eq!(ids, expected);
+
+        let note = fixture.store.get(a).await.unwrap();
+        assert_eq!(note.tags(), vec!["rustlang", "draft"]);
+        assert!(note.content.contains("Body A"));
+        assert_eq!(fixture.store.get(c).await.unwrap().tags(), vec!["go"]);
+
+        // The rewrite is on disk and the manifest hash matches it
+        let reopened = NoteStore::new(fixture.config.clone());
+        reopened.load_all().await.unwrap();
+        assert_eq!(reopened.get(b).await.unwrap().tags(), vec!["rustlang"]);
+        assert_eq!(
+            reopened.get(a).await.unwrap().content_hash,
+            note.content_hash
+        );
+    }
+
+    #[tokio::test]
+    async fn test_merge_tags_keeps_one_copy() {
+        let fixture = StoreTestFixture::new().await;
+        let id = fixture
+            .create_test_note("Mixed", "Body", Some(vec!["js".into(), "web".into(), "javascript".into()]))
+            .await;
+
+        let changed = fixture
+            .store
+            .merge_tags(&["js".into(), "javascript".into()], "JavaScript")
+            .await
+            .unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(fixture.store.get(id).await.unwrap().tags(), vec!["JavaScript", "web"]);
+    }
+
+    #[tokio::test]
+    async fn test_delete_tag() {
+        let fixture = StoreTestFixture::new().await;
+        let id = fixture
+            .create_test_note("Tagged", "Body", Some(vec!["old".into(), "keep".into()]))
+            .await;
+
+        fixture.store.delete_tag("OLD").await.unwrap();
+        assert_eq!(fixture.store.get(id).await.unwrap().tags(), vec!["keep"]);
+
+        assert!(matches!(
+            fixture.store.delete_tag("old").await,
+            Err(Error::TagNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_tag_names_are_rejected() {
+        let fixture = StoreTestFixture::new().await;
+        let id = fixture.create_test_note("Note", "Body", Some(vec!["a".into()])).await;
+
+        for name in ["", "  ", "a, b", "x: y", "[z]"] {
+            assert!(
+                matches!(fixture.store.rename_tag("a", name).await, Err(Error::InvalidTag(_))),
+                "{:?}",
+                name
+            );
+        }
+        assert_eq!(fixture.store.get(id).await.unwrap().tags(), vec!["a"]);
+
+        fixture.store.rename_tag("a", "#b").await.unwrap();
+        assert_eq!(fixture.store.get(id).await.unwrap().tags(), vec!["b"]);
+    }
+
+    #[tokio::test]
+    async fn test_tag_counts_follow_changes() {
+        let fixture = StoreTestFixture::new().await;
+        let all = Permissions::default();
+        let a = fixture
+            .create_test_note("Alpha", "Body A", Some(vec!["Rust".into(), "draft".into()]))
+            .await;
+        let b = fixture.create_test_note("Beta", "Body B", Some(vec!["rust".into()])).await;
+        let counts = |pairs: &[(&str, u32)]| -> Vec<(String, u32)> {
+            pairs.iter().map(|(tag, n)| (tag.to_string(), *n)).collect()
+        };
+
+        assert_eq!(
+            fixture.store.tag_counts(&all).await,
+            counts(&[("draft", 1), ("rust", 2)])
+        );
+
+        fixture.store.delete_tag("draft").await.unwrap();
+        fixture.store.rename_tag("rust", "Rustlang").await.unwrap();
+        assert_eq!(fixture.store.tag_counts(&all).await, counts(&[("Rustlang", 2)]));
+
+        fixture.store.delete(a).await.unwrap();
+        assert_eq!(fixture.store.tag_counts(&all).await, counts(&[("Rustlang", 1)]));
+
+        // Notes removed while the vault was closed are dropped on load
+        let beta = fixture.store.get(b).await.unwrap().file_path;
+        tokio::fs::remove_file(fixture.config.notes_path().join(beta)).await.unwrap();
+        let reopened = NoteStore::new(fixture.config.clone());
+        reopened.load_all().await.unwrap();
+        assert!(reopened.tag_counts(&all).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_manifest_imported_into_new_database() {
+        let fixture = StoreTestFixture::new().await;
+        fixture.create_test_note("Alpha", "Body", Some(vec!["a".into()])).await;
+        std::fs::remove_file(fixture.config.db_path()).unwrap();
+
+        let _reopened = NoteStore::new(fixture.config.clone());
+        let db = MetadataDb::open(&fixture.config.db_path()).unwrap();
+        assert!(!db.is_empty().unwrap());
+    }
+}
+
+// =============================================================================
+// Obsidian Import Tests
+// =============================================================================
+
+mod obsidian_import_tests {
+    use super::*;
+    use notidium::import::obsidian::import_vault;
+
+    #[tokio::test]
+    async fn test_import_obsidian_vault() {
+        let fixture = StoreTestFixture::new().await;
+        let source = TempDir::new().expect("Failed to create temp dir");
+        let root = source.path();
+
+        std::fs::create_dir_all(root.join(".obsidian")).unwrap();
+        std::fs::write(root.join(".obsidian/app.json"), "{}").unwrap();
+        std::fs::create_dir_all(root.join("Projects")).unwrap();
+        std::fs::create_dir_all(root.join("Assets")).unwrap();
+        std::fs::write(root.join("Assets/Board Photo.png"), b"png").unwrap();
+        std::fs::write(
+            root.join("Projects/Roadmap.md"),
+            "---\ntags: planning, q3\nalias: Plan\n---\n# Roadmap\n\n![[Board Photo.png]]\n\nSee [[Ideas.md]].\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("Ideas.md"), "# Ideas\n\nBack to [[Projects/Roadmap]].\n").unwrap();
+
+        let summary = import_vault(root, &fixture.config).expect("Should import");
+        assert_eq!(summary.notes, 2);
+        assert_eq!(summary.attachments, 1);
+        assert!(fixture.config.attachments_path().join("Board_Photo.png").exists());
+        assert!(!fixture.config.notes_path().join(".obsidian").exists());
+
+        let notes = fixture.store.load_all().await.expect("Should load");
+        assert_eq!(notes.len(), 2);
+
+        let (roadmap, alias) = fixture.store.get_by_title("Plan").await.expect("Alias should resolve");
+        assert_eq!(alias.as_deref(), Some("Plan"));
+        assert_eq!(roadmap.tags(), vec!["planning", "q3"]);
+        assert!(roadmap
+            .content
+            .contains("![Board Photo.png](/api/attachments/Board_Photo.png)"));
+        assert!(roadmap.content.contains("See [[Ideas]]."));
+
+        let ideas = notes.iter().find(|n| n.title == "Ideas").unwrap();
+        let backlinks = fixture.store.backlinks(roadmap.id).await.expect("Should get backlinks");
+        assert!(backlinks.iter().any(|b| b.note_id == ideas.id.to_string()));
+
+        // Importing again leaves the imported notes alone
+        let again = import_vault(root, &fixture.config).expect("Should import");
+        assert_eq!(again.notes, 0);
+        assert_eq!(again.attachments, 0);
+        assert_eq!(again.skipped.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_import_sanitizes_names_and_reports_case_collisions() {
+        let fixture = StoreTestFixture::new().await;
+        let notes_path = fixture.config.notes_path();
+        std::fs::create_dir_all(&notes_path).unwrap();
+        std::fs::write(notes_path.join("API Notes.md"), "# API Notes\n").unwrap();
+
+        let source = TempDir::new().expect("Failed to create temp dir");
+        let root = source.path();
+        std::fs::write(root.join("Api Notes.md"), "# Api Notes\n").unwrap();
+        std::fs::write(root.join("Q3: plan?.md"), "# Plan\n").unwrap();
+        let mut expected = vec![(PathBuf::from("Api Notes.md"), PathBuf::from("API Notes.md"))];
+        // Both spellings can only coexist in the source on a case-sensitive
+        // file system
+        if fixture.store.is_case_sensitive() {
+            std::fs::write(root.join("Draft.md"), "# Draft\n").unwrap();
+            std::fs::write(root.join("draft.md"), "# draft\n").unwrap();
+            expected.push((PathBuf::from("draft.md"), PathBuf::from("Draft.md")));
+        } else {
+            std::fs::write(root.join("Draft.md"), "# Draft\n").unwrap();
+        }
+
+        let summary = import_vault(root, &fixture.config).expect("Should import");
+        assert_eq!(summary.notes, 2);
+        assert!(summary.skipped.is_empty());
+        assert_eq!(summary.case_collisions, expected);
+        assert_eq!(
+            std::fs::read_to_string(notes_path.join("API Notes.md")).unwrap(),
+            "# API Notes\n"
+        );
+        assert!(notes_path.join("Draft.md").exists());
+        assert!(notes_path.join("Q3- plan-.md").exists());
+    }
+}
+
+// =============================================================================
+// Vault Export Tests
+// =============================================================================
+
+mod export_tests {
+    use super::*;
+    use notidium::export::{dump, write_zip, ExportFormat};
+    use notidium::LinkType;
+
+    #[tokio::test]
+    async fn test_json_dump_includes_metadata_and_links() {
+        let fixture = StoreTestFixture::new().await;
+        let target = fixture
+            .create_test_note("Target", "# Target\n\nLinked to.", Some(vec!["ref".to_string()]))
+            .await;
+        let source = fixture
+            .create_test_note("Source", "# Source\n\nSee [[Target]] and [[Missing]].", None)
+            .await;
+
+        let dump = dump(&fixture.store).await;
+        assert_eq!(dump.notes.len(), 2);
+
+        let target_dump = dump.notes.iter().find(|n| n.id == target).unwrap();
+        assert_eq!(target_dump.tags, vec!["ref"]);
+        assert!(target_dump.path.ends_with(".md"));
+
+        let source_dump = dump.notes.iter().find(|n| n.id == source).unwrap();
+        assert_eq!(source_dump.links.len(), 2);
+        assert_eq!(source_dump.links[0].target, "Target");
+        assert_eq!(source_dump.links[0].target_id, Some(target));
+        assert_eq!(source_dump.links[0].link_type, LinkType::WikiLink);
+        assert_eq!(source_dump.links[1].target_id, None);
+
+        let json = serde_json::to_value(&dump).unwrap();
+        assert!(json["notes"][0]["content"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_zip_archive_mirrors_vault_layout() {
+        let fixture = StoreTestFixture::new().await;
+        let note = fixture.create_test_note("Archived", "# Archived\n\nBody", None).await;
+        std::fs::write(fixture.config.attachments_path().join("photo.png"), b"png").unwrap();
+
+        let mut buffer = std::io::Cursor::new(Vec::new());
+        let files = write_zip(&fixture.config, &mut buffer).expect("Should export");
+
+        let mut archive = zip::ZipArchive::new(buffer).expect("Should be a zip");
+        assert_eq!(archive.len(), files);
+        let names: Vec<String> = archive.file_names().map(String::from).collect();
+        assert!(names.contains(&"attachments/photo.png".to_string()));
+        assert!(names.contains(&".notidium/manifest.json".to_string()));
+
+        let note = fixture.store.get(note).await.unwrap();
+        let note_path = format!("notes/{}", note.file_path.display());
+        let mut content = String::new();
+        std::io::Read::read_to_string(&mut archive.by_name(&note_path).unwrap(), &mut content).unwrap();
+        assert_eq!(content, note.content);
+
+        assert_eq!("json".parse::<ExportFormat>().unwrap(), ExportFormat::Json);
+        assert!("tar".parse::<ExportFormat>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bundle_is_one_self_contained_page() {
+        use notidium::export::bundle::write_bundle;
+
+        let fixture = StoreTestFixture::new().await;
+        std::fs::write(fixture.config.attachments_path().join("map.png"), b"png").unwrap();
+        let trail = fixture
+            .create_test_note("Trail", "# Trail\n\n![Map](/api/attachments/map.png)", Some(vec!["hiking".to_string()]))
+            .await;
+        fixture
+            .create_test_note("Packing", "# Packing\n\nBring water for the [[Trail]].", None)
+            .await;
+
+        let mut buffer = Vec::new();
+        let summary = write_bundle(&fixture.store, "Travel", &mut buffer).await.expect("Should bundle");
+        assert_eq!(summary.notes, 2);
+        assert_eq!(summary.images, 1);
+
+        let html = String::from_utf8(buffer).unwrap();
+        assert!(html.contains("<title>Travel</title>"));
+        assert!(html.contains("src=\"data:image/png;base64,"));
+        assert!(html.contains(&format!("<a href=\"#note-{}\">Trail</a>", trail)));
+        assert!(!html.contains("/api/"), "Nothing is fetched from a server");
+
+        let start = html.find("<script type=\"application/json\" id=\"index\">").unwrap();
+        let end = start + html[start..].find("</script>").unwrap();
+        let index: serde_json::Value = serde_json::from_str(html[start..end].split_once('>').unwrap().1).unwrap();
+        assert!(index["terms"]["hiking"].is_array());
+        assert!(index["terms"]["water"].is_array());
+    }
+}
+
+// ============================================================================
+// Web Clipper Tests
+// ============================================================================
+
+mod clip_tests {
+    use super::*;
+    use axum::http::header;
+    use axum::routing::get;
+    use axum::Router;
+    use notidium::clip::{clip, parse_url};
+    use notidium::error::Error;
+    use notidium::config::ClipConfig;
+
+    /// The test pages are served on loopback
+    fn local() -> ClipConfig {
+        ClipConfig {
+            allow_private_hosts: true,
+        }
+    }
+
+    const PAGE: &str = r#"<html><head><title>Release Notes | Example</title></head><body>
+        <nav><a href="/">Home</a> <a href="/blog">Blog</a></nav>
+        <main><h1>Release Notes</h1>
+        <p>Version 2 ships a <em>faster</em> indexer.</p>
+        <img src="/chart.png" alt="Chart"><img src="/missing.png" alt="Gone"></main>
+        <footer>Contact us</footer></body></html>"#;
+
+    /// Serve a page and one of its two images on a local port
+    async fn serve() -> String {
+        let app = Router::new()
+            .route("/post", get(|| async { ([(header::CONTENT_TYPE, "text/html")], PAGE) }))
+            .route(
+                "/chart.png",
+                get(|| async { ([(header::CONTENT_TYPE, "image/png")], b"\x89PNG fake".to_vec()) }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_clip_page_into_inbox() {
+        let fixture = StoreTestFixture::new().await;
+        let base = serve().await;
+        let url = parse_url(&format!("{}/post", base)).expect("Should parse");
+
+        let content = clip(&url, &fixture.config.attachments_path(), &local())
+            .await
+            .expect("Should clip");
+        assert!(content.starts_with("# Release Notes | Example\n\n"));
+        assert!(content.contains("Version 2 ships a *faster* indexer."));
+        assert!(!content.contains("Contact us"), "Footer is dropped");
+        assert!(!content.contains("Blog"), "Navigation is dropped");
+
+        // The image that exists is stored locally, the other keeps its URL
+        let attachments: Vec<_> = std::fs::read_dir(fixture.config.attachments_path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(attachments.len(), 1);
+        assert!(attachments[0].starts_with("chart_") && attachments[0].ends_with(".png"));
+        assert!(content.contains(&format!("![Chart](/api/attachments/{})", attachments[0])));
+        assert!(content.contains(&format!("![Gone]({}/missing.png)", base)));
+
+        let note = fixture
+            .store
+            .quick_capture(content, Some(url.to_string()))
+            .await
+            .expect("Should capture");
+        assert!(note.file_path.starts_with("inbox"));
+        assert!(note.content.contains(&format!("source: \"{}\"", url)));
+    }
+
+    #[tokio::test]
+    async fn test_clip_rejects_non_html() {
+        let fixture = StoreTestFixture::new().await;
+        let base = serve().await;
+        let url = parse_url(&format!("{}/chart.png", base)).expect("Should parse");
+
+        let result = clip(&url, &fixture.config.attachments_path(), &local()).await;
+        assert!(result.is_err());
+        assert!(parse_url("ftp://example.com/file").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_clip_rejects_private_hosts() {
+        let fixture = StoreTestFixture::new().await;
+        let base = serve().await;
+        let port = base.rsplit(':').next().unwrap();
+        let attachments = fixture.config.attachments_path();
+
+        for url in [
+            format!("{}/post", base),
+            format!("http://localhost:{}/post", port),
+            format!("http://[::1]:{}/post", port),
+            "http://169.254.169.254/latest/meta-data/".to_string(),
+        ] {
+            let url = parse_url(&url).unwrap();
+            let result = clip(&url, &attachments, &ClipConfig::default()).await;
+            assert!(matches!(result, Err(Error::Capture(_))), "{}", url);
+        }
+        assert!(std::fs::read_dir(&attachments).unwrap().next().is_none());
+    }
+}
+
+// ============================================================================
+// Lint Tests
+// ============================================================================
+
+mod lint_tests {
+    use super::*;
+    use notidium::lint::{fix, lint, LintRule};
+
+    #[tokio::test]
+    async fn test_lint_note_in_vault_and_fix() {
+        let fixture = StoreTestFixture::new().await;
+        std::fs::write(fixture.config.attachments_path().join("shot.png"), b"png").unwrap();
+        std::fs::create_dir_all(fixture.config.notes_path().join("img")).unwrap();
+        std::fs::write(fixture.config.notes_path().join("img/local.png"), b"png").unwrap();
+
+        let id = fixture
+            .create_test_note(
+                "Linted",
+                "# Linted\n\n![a](/api/attachments/shot.png) ![b](img/local.png) ![c](img/gone.png)\n\n\
+                 Docs at https://docs.rs \n",
+                None,
+            )
+            .await;
+        let note = fixture.store.get(id).await.expect("Note exists");
+        let note_dir = fixture.config.notes_path().join(&note.file_path);
+        let note_dir = note_dir.parent().unwrap();
+
+        let issues = lint(&note.content, note_dir, &fixture.config.attachments_path());
+        let rules: Vec<LintRule> = issues.iter().map(|i| i.rule).collect();
+        assert_eq!(
+            rules,
+            [LintRule::MissingAttachment, LintRule::BareUrl, LintRule::TrailingWhitespace]
+        );
+        assert!(issues[0].message.contains("img/gone.png"));
+
+        let note = fixture
+            .store
+            .update(id, fix(&note.content))
+            .await
+            .expect("Should update");
+        assert!(note.content.ends_with("Docs at <https://docs.rs>\n"));
+        let issues = lint(&note.content, note_dir, &fixture.config.attachments_path());
+        assert!(issues.iter().all(|i| !i.rule.fixable()));
+        assert_eq!(issues.len(), 1);
+    }
+}
+
+// ============================================================================
+// Attachment Management Tests
+// ============================================================================
+
+mod attachment_tests {
+    use super::*;
+    use notidium::error::Error;
+
+    #[tokio::test]
+    async fn test_list_delete_and_find_orphans() {
+        let fixture = StoreTestFixture::new().await;
+        let attachments = fixture.config.attachments_path();
+        for name in ["kept.png", "trashed.png", "template.png", "orphan.png"] {
+            std::fs::write(attachments.join(name), name.as_bytes()).unwrap();
+        }
+        std::fs::write(
+            fixture.config.templates_path().join("shot.md"),
+            "![t](/api/attachments/template.png)\n",
+        )
+        .unwrap();
+        fixture
+            .create_test_note("Kept", "![k](/api/attachments/kept.png)", None)
+            .await;
+        let trashed = fixture
+            .create_test_note("Trashed", "![t](/api/attachments/trashed.png)", None)
+            .await;
+        fixture.store.delete(trashed).await.expect("Should trash");
+
+        let listed = fixture.store.attachments().list().await.expect("Should list");
+        let names: Vec<&str> = listed.iter().map(|a| a.filename.as_str()).collect();
+        assert_eq!(names, ["kept.png", "orphan.png", "template.png", "trashed.png"]);
+        assert_eq!(listed[0].size, "kept.png".len() as u64);
+
+        // Notes in the trash and templates still count as references
+        let orphans = fixture.store.orphaned_attachments().await.expect("Should scan");
+        assert_eq!(orphans.len(), 1);
+        assert_eq!(orphans[0].filename, "orphan.png");
+
+        fixture
+            .store
+            .attachments()
+            .delete("orphan.png")
+            .await
+            .expect("Should delete");
+        assert!(!attachments.join("orphan.png").exists());
+        assert!(matches!(
+            fixture.store.attachments().delete("orphan.png").await,
+            Err(Error::AttachmentNotFound(_))
+        ));
+        assert!(matches!(
+            fixture.store.attachments().delete("../notes/kept.md").await,
+            Err(Error::AttachmentNotFound(_))
+        ));
+
+        // Purging the trashed note frees its image
+        fixture
+            .store
+            .purge_from_trash(trashed)
+            .await
+            .expect("Should purge");
+        let orphans = fixture.store.orphaned_attachments().await.expect("Should scan");
+        assert_eq!(orphans.len(), 1);
+        assert_eq!(orphans[0].filename, "trashed.png");
+    }
+    #[tokio::test]
+    async fn test_note_attachment_references() {
+        let fixture = StoreTestFixture::new().await;
+        let attachments = fixture.config.attachments_path();
+        for name in ["own.png", "shared.png", "doc.pdf"] {
+            std::fs::write(attachments.join(name), name.as_bytes()).unwrap();
+        }
+        let id = fixture
+            .create_test_note(
+                "Report",
+                "![o](/api/attachments/own.png) ![s](/api/attachments/shared.png)\n\
+                 [d](../attachments/doc.pdf) ![m](/api/attachments/missing.png)",
+                None,
+            )
+            .await;
+        fixture
+            .create_test_note("Other", "![s](/api/attachments/shared.png)", None)
+            .await;
+
+        let names = fixture.store.note_attachments(id).await.expect("Should list");
+        assert_eq!(names, ["doc.pdf", "missing.png", "own.png", "shared.png"]);
+
+        // Edits are tracked
+        fixture
+            .store
+            .update(id, "![o](/api/attachments/own.png) ![s](/api/attachments/shared.png)".into())
+            .await
+            .expect("Should update");
+        let names = fixture.store.note_attachments(id).await.expect("Should list");
+        assert_eq!(names, ["own.png", "shared.png"]);
+
+        // So are vault reloads
+        let store = NoteStore::new(fixture.config.clone());
+        store.load_all().await.expect("Should load");
+        assert_eq!(store.note_attachments(id).await.unwrap(), names);
+
+        fixture.store.delete(id).await.expect("Should trash");
+        assert!(matches!(
+            fixture.store.note_attachments(id).await,
+            Err(Error::NoteNotFound(_))
+        ));
+        let deleted = fixture
+            .store
+            .delete_unshared_attachments(id, &names)
+            .await
+            .expect("Should delete");
+        assert_eq!(deleted, ["own.png"]);
+        assert!(!attachments.join("own.png").exists());
+        assert!(attachments.join("shared.png").exists());
+        assert!(attachments.join("doc.pdf").exists());
+    }
+}
+
+// ============================================================================
+// Formatting On Save Tests
+// ============================================================================
+
+mod format_on_save_tests {
+    use super::*;
+
+    async fn formatting_store(fixture: &StoreTestFixture) -> NoteStore {
+        let mut config = fixture.config.clone();
+        config.format.enabled = true;
+        let store = NoteStore::new(config);
+        store.load_all().await.unwrap();
+        store
+    }
+
+    #[tokio::test]
+    async fn test_create_and_update_are_formatted() {
+        let fixture = StoreTestFixture::new().await;
+        let store = formatting_store(&fixture).await;
+
+        let note = store
+            .create(
+                "Formatted".to_string(),
+                "# Formatted #\n* one\n* two\n```PY\nx  =  1\n```\n".to_string(),
+                Some(vec!["fmt".to_string()]),
+            )
+            .await
+            .expect("Should create");
+        assert_eq!(
+            note.content,
+            "---\ntags: [fmt]\n---\n\n# Formatted\n\n- one\n- two\n```python\nx  =  1\n```\n"
+        );
+        let on_disk =
+            std::fs::read_to_string(fixture.config.notes_path().join(&note.file_path)).unwrap();
+        assert_eq!(on_disk, note.content);
+
+        let note = store
+            .update(note.id, "Intro\n## Table\n|a|b|\n|-|-|\n|long|x|\n".to_string())
+            .await
+            .expect("Should update");
+        assert_eq!(
+            note.content,
+            "Intro\n\n## Table\n\n| a    | b   |\n| ---- | --- |\n| long | x   |\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_formatting_is_off_by_default() {
+        let fixture = StoreTestFixture::new().await;
+        let content = "# Raw #\n* kept\n";
+        let note = fixture
+            .store
+            .create("Raw".to_string(), content.to_string(), None)
+            .await
+            .expect("Should create");
+        assert_eq!(note.content, content);
+    }
+}
+
+// ============================================================================
+// Note Export Tests
+// ============================================================================
+
+mod note_export_tests {
+    use super::*;
+    use notidium::export::note::{self, NoteFormat};
+
+    #[tokio::test]
+    async fn test_html_export_inlines_attachments() {
+        let fixture = StoreTestFixture::new().await;
+        std::fs::write(fixture.config.attachments_path().join("chart.png"), b"chart").unwrap();
+        let id = fixture
+            .create_test_note(
+                "Quarterly Report",
+                "Numbers are up.\n\n![chart](/api/attachments/chart.png)\n\n| a | b |\n|---|---|\n| 1 | 2 |\n",
+                Some(vec!["work".to_string()]),
+            )
+            .await;
+        let saved = fixture.store.get(id).await.unwrap();
+
+        let html = note::render(&fixture.config, &saved, &[], NoteFormat::Html)
+            .await
+            .expect("Should render");
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<h1>Quarterly Report</h1>"));
+        assert!(html.contains("src=\"data:image/png;base64,Y2hhcnQ=\""));
+        assert!(html.contains("<table>"));
+        assert!(!html.contains("/api/attachments/"));
+        assert!(!html.contains("tags:"));
+    }
+}
+
+// ============================================================================
+// Static Site Publishing Tests
+// ============================================================================
+
+mod publish_tests {
+    use super::*;
+    use notidium::export::site::{publish, PublishOptions, PublishSummary};
+
+    #[tokio::test]
+    async fn test_publish_selected_notes() {
+        let fixture = StoreTestFixture::new().await;
+        std::fs::write(fixture.config.attachments_path().join("tree.png"), b"png").unwrap();
+        fixture
+            .create_test_note(
+                "Garden",
+                "# Garden\n\nGrowing [[Seeds]] and [[Diary]].\n\n![tree](/api/attachments/tree.png)",
+                Some(vec!["public".to_string(), "plants".to_string()]),
+            )
+            .await;
+        fixture
+            .create_test_note("Seeds", "Sowing calendar", Some(vec!["Public".to_string()]))
+            .await;
+        fixture
+            .create_test_note("Diary", "Private thoughts", Some(vec!["private".to_string()]))
+            .await;
+
+        let out = fixture.config.vault_path.join("site");
+        let options = PublishOptions {
+            tags: vec!["public".to_string()],
+            out: out.clone(),
+            title: "My Garden".to_string(),
+            base_url: Some("https://example.com/garden/".to_string()),
+        };
+        let summary = publish(&fixture.store, &options).await.expect("Should publish");
+        assert_eq!(
+            summary,
+            PublishSummary {
+                notes: 2,
+                tags: 2,
+                attachments: 1
+            }
+        );
+
+        let garden = std::fs::read_to_string(out.join("notes/garden.html")).unwrap();
+        assert!(garden.contains("<a href=\"seeds.html\">Seeds</a>"));
+        assert!(garden.contains("and Diary."));
+        assert!(garden.contains("src=\"../attachments/tree.png\""));
+        assert!(garden.contains("<a href=\"../tags/plants.html\">#plants</a>"));
+        assert!(out.join("attachments/tree.png").exists());
+        assert!(out.join("notes/seeds.html").exists());
+        assert!(!out.join("notes/diary.html").exists());
+
+        let index = std::fs::read_to_string(out.join("index.html")).unwrap();
+        assert!(index.contains("<a href=\"notes/garden.html\">Garden</a>"));
+        assert!(!index.contains("Private thoughts"));
+        let tag = std::fs::read_to_string(out.join("tags/plants.html")).unwrap();
+        assert!(tag.contains("../notes/garden.html"));
+        assert!(!tag.contains("seeds.html"));
+
+        let feed = std::fs::read_to_string(out.join("feed.xml")).unwrap();
+        assert!(feed.contains("<link>https://example.com/garden/notes/seeds.html</link>"));
+        assert_eq!(feed.matches("<item>").count(), 2);
+
+        let search: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(out.join("search-index.json")).unwrap()).unwrap();
+        assert_eq!(search["docs"].as_array().unwrap().len(), 2);
+        assert!(search["terms"]["sowing"].is_array());
+        assert!(search["terms"].get("private").is_none());
+        assert!(out.join("search.js").exists());
+        assert!(out.join("style.css").exists());
+    }
+}
+
+// ============================================================================
+// Rate Limiting Tests
+// ============================================================================
+
+mod rate_limit_tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::extract::ConnectInfo;
+    use axum::http::{header, Request, StatusCode};
+    use axum::{middleware, routing::get, Router};
+    use notidium::api::{rate_limit, RateLimiter};
+    use notidium::config::RateLimitConfig;
+    use std::net::SocketAddr;
+    use tower::ServiceExt;
+
+    fn router() -> Router {
+        let limiter = Arc::new(RateLimiter::new(&RateLimitConfig {
+            enabled: true,
+            requests_per_second: 0.01,
+            burst: 3,
+            expensive_requests_per_second: 0.01,
+            expensive_burst: 1,
+        }));
+        Router::new()
+            .route("/api/notes", get(|| async { "notes" }))
+            .route("/api/search/semantic", get(|| async { "results" }))
+            .layer(middleware::from_fn_with_state(limiter, rate_limit))
+    }
+
+    async fn get_from(router: Router, uri: &str, addr: &str) -> axum::response::Response {
+        let mut request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(addr.parse::<SocketAddr>().unwrap()));
+        router.oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_limits_per_client() {
+        let router = router();
+
+        assert_eq!(get_from(router.clone(), "/api/search/semantic?q=x", "10.0.0.1:1000").await.status(), StatusCode::OK);
+        let limited = get_from(router.clone(), "/api/search/semantic?q=x", "10.0.0.1:1000").await;
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(limited.headers().contains_key(header::RETRY_AFTER));
+
+        // Cheap requests still have general allowance left
+        assert_eq!(get_from(router.clone(), "/api/notes", "10.0.0.1:1001").await.status(), StatusCode::OK);
+        assert_eq!(get_from(router.clone(), "/api/notes", "10.0.0.1:1002").await.status(), StatusCode::OK);
+        assert_eq!(
+            get_from(router.clone(), "/api/notes", "10.0.0.1:1003").await.status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+
+        // Another client isn't affected
+        assert_eq!(get_from(router, "/api/search/semantic?q=x", "10.0.0.2:1000").await.status(), StatusCode::OK);
+    }
+}
+
+// ============================================================================
+// Transclusion Tests
+// ============================================================================
+
+mod transclusion_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_transclude_notes_and_sections() {
+        let fixture = StoreTestFixture::new().await;
+        fixture
+            .create_test_note(
+                "Glossary",
+                "# Glossary\n\n## Token\n\nA credential.\n\n## Scope\n\nWhat it grants.\n",
+                None,
+            )
+            .await;
+        fixture
+            .create_test_note("Private", "Secret plans. ![[Guide]]", None)
+            .await;
+        let guide = fixture
+            .create_test_note(
+                "Guide",
+                "# Guide\n\n![[Glossary#token]]\n\n![[Private]]\n\n![[Guide]]\n",
+                None,
+            )
+            .await;
+
+        let note = fixture.store.get(guide).await.unwrap();
+        let everything = fixture
+            .store
+            .transclude(guide, &note.content, |_| true)
+            .await;
+        assert!(everything.contains("## Token\n\nA credential."));
+        assert!(!everything.contains("What it grants"));
+        // Cycles back to the guide stay links
+        assert!(everything.contains("Secret plans. [[Guide]]"));
+        assert!(everything.contains("\n[[Guide]]\n"));
+
+        let public = fixture
+            .store
+            .transclude(guide, &note.content, |n| n.title != "Private")
+            .await;
+        assert!(!public.contains("Secret plans"));
+        assert!(public.contains("![[Private]]"));
+    }
+
+    #[test]
+    fn test_heading_section() {
+        let body = "# A\n\nintro\n\n## B\n\nbee\n\n### C\n\nsea\n\n## D\n\ndee\n";
+        assert_eq!(
+            notidium::store::heading_section(body, "b"),
+            Some("## B\n\nbee\n\n### C\n\nsea\n\n")
+        );
+    }
+}
+
+// ============================================================================
+// Vault Event Tests
+// ============================================================================
+
+mod vault_event_tests {
+    use super::*;
+    use notidium::store::VaultEvent;
+
+    #[tokio::test]
+    async fn test_store_changes_are_broadcast() {
+        let fixture = StoreTestFixture::new().await;
+        let mut events = fixture.store.subscribe_events();
+
+        let id = fixture.create_test_note("Live", "First", None).await;
+        fixture
+            .store
+            .update(id, "# Live\n\nSecond".to_string())
+            .await
+            .expect("Should update");
+        fixture.store.delete(id).await.expect("Should delete");
+        fixture.store.restore_from_trash(id).await.expect("Should restore");
+
+        let mut received = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            received.push(event);
+        }
+        assert_eq!(
+            received,
+            vec![
+                VaultEvent::NoteCreated { id, title: "Live".to_string() },
+                VaultEvent::NoteUpdated { id, title: "Live".to_string() },
+                VaultEvent::NoteDeleted { id },
+                VaultEvent::NoteCreated { id, title: "Live".to_string() },
+            ]
+        );
+        assert_eq!(received[2].kind(), "note_deleted");
+        assert_eq!(
+            serde_json::to_value(&received[2]).unwrap(),
+            serde_json::json!({ "type": "note_deleted", "id": id })
+        );
+    }
+}
+
+// ============================================================================
+// Canvas Tests
+// ============================================================================
+
+mod canvas_tests {
+    use super::*;
+    use notidium::error::Error;
+    use notidium::search::{canvas_id, canvas_path};
+    use notidium::store::Canvas;
+
+    fn board() -> Canvas {
+        serde_json::from_value(serde_json::json!({
+            "nodes": [
+                {"id": "idea", "type": "text", "x": 0, "y": 0, "width": 250, "height": 60, "text": "Zeppelin logistics"},
+                {"id": "spec", "type": "file", "x": 300, "y": 0, "width": 250, "height": 60, "file": "Spec.md"}
+            ],
+            "edges": [{"id": "e1", "fromNode": "idea", "toNode": "spec"}]
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_canvas_crud() {
+        let fixture = StoreTestFixture::new().await;
+        let canvases = fixture.store.canvases();
+
+        canvases.create("boards/plan.canvas", &board()).await.expect("Should create");
+        assert!(matches!(
+            canvases.create("boards/plan.canvas", &board()).await,
+            Err(Error::CanvasAlreadyExists(_))
+        ));
+        assert!(fixture.config.notes_path().join("boards/plan.canvas").is_file());
+
+        let listed = canvases.list().await.expect("Should list");
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].path, "boards/plan.canvas");
+        assert_eq!(canvases.get("boards/plan.canvas").await.unwrap(), board());
+
+        let mut broken = board();
+        broken.edges[0].to_node = "nowhere".to_string();
+        assert!(matches!(
+            canvases.update("boards/plan.canvas", &broken).await,
+            Err(Error::InvalidCanvas(_))
+        ));
+
+        canvases.delete("boards/plan.canvas").await.expect("Should delete");
+        assert!(matches!(
+            canvases.get("boards/plan.canvas").await,
+            Err(Error::CanvasNotFound(_))
+        ));
+
+        // Canvases aren't loaded as notes
+        assert!(fixture.store.load_all().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_canvas_text_is_searchable() {
+        let fixture = StoreTestFixture::new().await;
+        fixture
+            .fulltext
+            .index_canvas("boards/plan.canvas", &board().text())
+            .unwrap();
+        fixture.fulltext.commit().unwrap();
+
+        let results = fixture.fulltext.search("zeppelin", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].note_id, canvas_id("boards/plan.canvas"));
+        assert_eq!(results[0].title, "plan");
+        assert_eq!(canvas_path(&results[0].note_id), Some("boards/plan.canvas"));
+    }
+}
+
+// ============================================================================
+// Kanban Board Tests
+// ============================================================================
+
+mod board_tests {
+    use super::*;
+    use notidium::error::Error;
+    use notidium::store::{Board, DEFAULT_BOARD_PROPERTY, DONE_COLUMN};
+
+    #[tokio::test]
+    async fn test_moving_note_cards_sets_status() {
+        let fixture = StoreTestFixture::new().await;
+        let launch = fixture
+            .create_test_note("Launch", "---\ntags: [sprint]\nstatus: todo\n---\n\nShip it", None)
+            .await;
+        fixture
+            .create_test_note("Docs", "---\ntags: [sprint]\n---\n\nWrite docs", None)
+            .await;
+
+        let columns = vec!["todo".to_string(), "doing".to_string()];
+        let board = Board::of_notes("sprint", &fixture.store.list().await, DEFAULT_BOARD_PROPERTY, &columns);
+        assert_eq!(board.columns[0].cards.len(), 1);
+        assert_eq!(board.unsorted[0].title, "Docs");
+
+        let moved = fixture
+            .store
+            .set_property(launch, "status", "doing")
+            .await
+            .expect("Should move");
+        assert!(moved.content.contains("status: doing"));
+        assert!(moved.tags().contains(&"sprint".to_string()));
+
+        let board = Board::of_notes("sprint", &fixture.store.list().await, DEFAULT_BOARD_PROPERTY, &columns);
+        assert!(board.columns[0].cards.is_empty());
+        assert_eq!(board.columns[1].cards[0].note_id, launch.to_string());
+
+        // The change is on disk, so it survives a reload
+        let path = fixture.config.notes_path().join(&moved.file_path);
+        assert!(std::fs::read_to_string(path).unwrap().contains("status: doing"));
+    }
+
+    #[tokio::test]
+    async fn test_moving_task_cards_ticks_checkboxes() {
+        let fixture = StoreTestFixture::new().await;
+        let id = fixture
+            .create_test_note("Errands", "- [ ] Groceries\n- [x] Laundry\n", Some(vec!["home".to_string()]))
+            .await;
+
+        let board = Board::of_tasks("home", &fixture.store.list().await);
+        let todo = &board.columns[0].cards[0];
+        assert_eq!(todo.task.as_deref(), Some("Groceries"));
+
+        fixture
+            .store
+            .set_task(id, todo.line.unwrap(), true)
+            .await
+            .expect("Should tick");
+        let board = Board::of_tasks("home", &fixture.store.list().await);
+        assert!(board.columns[0].cards.is_empty());
+        assert_eq!(board.columns[1].name, DONE_COLUMN);
+        assert_eq!(board.columns[1].cards.len(), 2);
+
+        assert!(matches!(
+            fixture.store.set_task(id, 1, true).await,
+            Err(Error::InvalidBoardMove(_))
+        ));
+    }
+}
+
+// ============================================================================
+// Webhook Tests
+// ============================================================================
+
+mod webhook_tests {
+    use super::*;
+    use axum::{extract::State, http::{HeaderMap, StatusCode}, routing::post, Router};
+    use notidium::config::{WebhookConfig, WebhookEndpoint};
+    use notidium::webhooks;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    type Received = Arc<Mutex<Vec<(String, serde_json::Value)>>>;
+
+    /// A receiver that fails the first `failures` deliveries with a 503
+    async fn receiver(failures: usize) -> (String, Received) {
+        let received: Received = Arc::new(Mutex::new(Vec::new()));
+        let attempts = Arc::new(Mutex::new(0usize));
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(
+                    move |State((received, attempts)): State<(Received, Arc<Mutex<usize>>)>,
+                          headers: HeaderMap,
+                          body: String| async move {
+                        let mut attempts = attempts.lock().unwrap();
+                        *attempts += 1;
+                        if *attempts <= failures {
+                            return StatusCode::SERVICE_UNAVAILABLE;
+                        }
+                        let event = headers["x-notidium-event"].to_str().unwrap().to_string();
+                        received
+                            .lock()
+                            .unwrap()
+                            .push((event, serde_json::from_str(&body).unwrap()));
+                        StatusCode::NO_CONTENT
+                    },
+                ),
+            )
+            .with_state((received.clone(), attempts));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, received)
+    }
+
+    async fn wait_for(received: &Received, count: usize) -> Vec<(String, serde_json::Value)> {
+        for _ in 0..100 {
+            if received.lock().unwrap().len() >= count {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        received.lock().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn test_note_lifecycle_is_posted() {
+        let fixture = StoreTestFixture::new().await;
+        let (url, received) = receiver(0).await;
+        let config = WebhookConfig {
+            endpoints: vec![WebhookEndpoint { url, events: Vec::new() }],
+            ..WebhookConfig::default()
+        };
+        let _task = webhooks::spawn(fixture.store.clone(), &config).expect("Should start");
+
+        let id = fixture
+            .create_test_note("Hooked", "Body", Some(vec!["ops".to_string()]))
+            .await;
+        fixture.store.quick_capture("Idea".to_string(), None).await.unwrap();
+        fixture.store.delete(id).await.unwrap();
+
+        let deliveries = wait_for(&received, 3).await;
+        let mut kinds: Vec<&str> = deliveries.iter().map(|(kind, _)| kind.as_str()).collect();
+        kinds.sort();
+        assert_eq!(kinds, ["note_captured", "note_created", "note_deleted"]);
+
+        let (_, created) = deliveries.iter().find(|(kind, _)| kind == "note_created").unwrap();
+        assert_eq!(created["type"], "note_created");
+        assert_eq!(created["id"], id.to_string());
+        assert_eq!(created["title"], "Hooked");
+        assert!(created["timestamp"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_failed_deliveries_are_retried_and_filtered() {
+        let fixture = StoreTestFixture::new().await;
+        let (url, received) = receiver(2).await;
+        let config = WebhookConfig {
+            retry_delay_ms: 10,
+            endpoints: vec![WebhookEndpoint {
+                url,
+                events: vec!["note_updated".to_string()],
+            }],
+            ..WebhookConfig::default()
+        };
+        let _task = webhooks::spawn(fixture.store.clone(), &config).expect("Should start");
+
+        let id = fixture.create_test_note("Filtered", "v1", None).await;
+        fixture.store.update(id, "v2".to_string()).await.unwrap();
+
+        let deliveries = wait_for(&received, 1).await;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].0, "note_updated");
+        assert_eq!(deliveries[0].1["id"], id.to_string());
+    }
+}
+
+// ============================================================================
+// Typed Relation Tests
+// ============================================================================
+
+mod relation_tests {
+    use super::*;
+    use notidium::types::{LinkType, RelationDirection, RelationKind};
+
+    #[tokio::test]
+    async fn test_frontmatter_relations() {
+        let fixture = StoreTestFixture::new().await;
+        // Declared before its targets exist, so they resolve by name later
+        let new = fixture
+            .create_test_note(
+                "New Plan",
+                "---\nsupersedes: \"[[Old Plan]]\"\nblocked_by: [Budget, Hiring]\n---\n\n# New Plan\n",
+                None,
+            )
+            .await;
+        let old = fixture.create_test_note("Old Plan", "# Old Plan\n", None).await;
+        let budget = fixture.create_test_note("Budget", "# Budget\n", None).await;
+
+        let relations = fixture.store.relations(new, None).await.unwrap();
+        let found: Vec<_> = relations
+            .iter()
+            .map(|r| (r.relation, r.direction, r.note_id.clone(), r.title.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (RelationKind::Supersedes, RelationDirection::Outgoing, Some(old.to_string()), "Old Plan"),
+                (RelationKind::BlockedBy, RelationDirection::Outgoing, Some(budget.to_string()), "Budget"),
+                (RelationKind::BlockedBy, RelationDirection::Outgoing, None, "Hiring"),
+            ]
+        );
+
+        let supersedes = fixture
+            .store
+            .relations(new, Some(RelationKind::Supersedes))
+            .await
+            .unwrap();
+        assert_eq!(supersedes.len(), 1);
+
+        let incoming = fixture.store.relations(old, None).await.unwrap();
+        assert_eq!(incoming.len(), 1);
+        assert_eq!(incoming[0].direction, RelationDirection::Incoming);
+        assert_eq!(incoming[0].relation, RelationKind::Supersedes);
+        assert_eq!(incoming[0].note_id, Some(new.to_string()));
+        assert!(fixture
+            .store
+            .relations(old, Some(RelationKind::BlockedBy))
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Relations count as backlinks and show up among the vault's links
+        let backlinks = fixture.store.backlinks(budget).await.unwrap();
+        assert_eq!(backlinks.len(), 1);
+        assert_eq!(backlinks[0].link_type, LinkType::Relation);
+        assert_eq!(backlinks[0].context, "blocked_by: [Budget, Hiring]");
+        let labelled = fixture
+            .store
+            .links()
+            .await
+            .into_iter()
+            .filter(|l| l.target_note_id == Some(old))
+            .filter_map(|l| l.relation)
+            .collect::<Vec<_>>();
+        assert_eq!(labelled, vec![RelationKind::Supersedes]);
+    }
+}
+
+// ============================================================================
+// Map of Content Tests
+// ============================================================================
+
+mod moc_tests {
+    use super::*;
+    use notidium::error::Error;
+
+    #[tokio::test]
+    async fn test_generate_and_refresh_moc() {
+        let fixture = StoreTestFixture::new().await;
+        let rust = Some(vec!["rust".to_string()]);
+        fixture
+            .create_test_note("Ownership", "# Ownership\n\nEvery value has one owner.", rust.clone())
+            .await;
+        fixture
+            .store
+            .create_in(
+                std::path::Path::new("async"),
+                "Tokio".to_string(),
+                "The async runtime.".to_string(),
+                rust.clone(),
+            )
+            .await
+            .unwrap();
+        fixture.create_test_note("Gardening", "Tomatoes.", Some(vec!["home".to_string()])).await;
+
+        let first = fixture
+            .store
+            .generate_moc("rust", "Rust MOC", |_| true)
+            .await
+            .expect("Should create");
+        assert!(first.created && first.changed);
+        assert_eq!(first.listed, 2);
+        assert!(first.note.tags().contains(&"moc".to_string()));
+        let content = &first.note.content;
+        assert!(content.contains("- [[Ownership]] — Every value has one owner.\n"));
+        assert!(content.contains("## async\n\n- [[Tokio]] — The async runtime.\n"));
+        assert!(!content.contains("Gardening"));
+
+        // Nothing changed, so nothing is written
+        let again = fixture.store.generate_moc("rust", "rust moc", |_| true).await.unwrap();
+        assert!(!again.created && !again.changed);
+        assert_eq!(again.note.id, first.note.id);
+
+        // Text around the list survives a refresh that adds a note
+        let edited = format!("{}\nMy own notes.\n", first.note.content);
+        fixture.store.update(first.note.id, edited).await.unwrap();
+        fixture.create_test_note("Borrowing", "References.", rust).await;
+        let refreshed = fixture.store.generate_moc("rust", "Rust MOC", |_| true).await.unwrap();
+        assert!(refreshed.changed && !refreshed.created);
+        assert_eq!(refreshed.listed, 3);
+        assert!(refreshed.note.content.contains("- [[Borrowing]] — References.\n"));
+        assert!(refreshed.note.content.ends_with("My own notes.\n"));
+        assert_eq!(refreshed.note.content.matches("<!-- moc:begin -->").count(), 1);
+
+        assert!(matches!(
+            fixture.store.generate_moc("nothing", "Nothing MOC", |_| true).await,
+            Err(Error::TagNotFound(_))
+        ));
+    }
+}
+
+// =============================================================================
+// Incremental Indexing Tests
+// =============================================================================
+
+mod incremental_index_tests {
+    use super::*;
+    use uuid::Uuid;
+
+    async fn needing_reindex(store: &NoteStore) -> Vec<Uuid> {
+        let mut ids: Vec<Uuid> = store.get_notes_needing_reindex().await.iter().map(|n| n.id).collect();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test]
+    async fn test_only_changed_notes_need_reindex() {
+        let fixture = StoreTestFixture::new().await;
+        let a = fixture.create_test_note("Alpha", "First.", None).await;
+        let b = fixture.create_test_note("Beta", "Second.", None).await;
+
+        let mut both = vec![a, b];
+        both.sort();
+        assert_eq!(needing_reindex(&fixture.store).await, both);
+
+        fixture.store.mark_indexed(&[a, b]).await.unwrap();
+        assert!(needing_reindex(&fixture.store).await.is_empty());
+
+        // An edit through the store updates the manifest hash, but the note
+        // still needs indexing until it's marked again
+        fixture.store.update(a, "# Alpha\n\nFirst, edited.".to_string()).await.unwrap();
+        assert_eq!(needing_reindex(&fixture.store).await, vec![a]);
+        fixture.store.mark_indexed(&[a]).await.unwrap();
+        assert!(needing_reindex(&fixture.store).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_edits_on_disk_need_reindex_after_reload() {
+        let fixture = StoreTestFixture::new().await;
+        let a = fixture.create_test_note("Alpha", "First.", None).await;
+        let b = fixture.create_test_note("Beta", "Second.", None).await;
+        fixture.store.mark_indexed(&[a, b]).await.unwrap();
+
+        let path = fixture.store.get(b).await.unwrap().file_path;
+        std::fs::write(fixture.config.notes_path().join(path), "# Beta\n\nRewritten.").unwrap();
+
+        let reloaded = NoteStore::new(fixture.config.clone());
+        reloaded.load_all().await.unwrap();
+        assert_eq!(needing_reindex(&reloaded).await, vec![b]);
+
+        reloaded.mark_indexed(&[b]).await.unwrap();
+        let again = NoteStore::new(fixture.config.clone());
+        again.load_all().await.unwrap();
+        assert!(needing_reindex(&again).await.is_empty());
+    }
+}
+
+// =============================================================================
+// Statistics Export Tests
+// =============================================================================
+
+mod stats_export_tests {
+    use super::*;
+    use notidium::export::stats;
+
+    #[tokio::test]
+    async fn test_collect_note_stats() {
+        let fixture = StoreTestFixture::new().await;
+        let tokio = fixture
+            .create_test_note(
+                "Tokio",
+                "---\ntags: [rust, async]\n---\n# Tokio\n\nSee [[Pin]] and [[Missing]].",
+                None,
+            )
+            .await;
+        let pin = fixture.create_test_note("Pin", "# Pin\n\nPinned values stay put.", None).await;
+        fixture.store.record_access(pin).await.unwrap();
+
+        let rows = stats::collect(&fixture.store).await.unwrap();
+        assert_eq!(rows.len(), 2);
+        let tokio_row = rows.iter().find(|r| r.id == tokio).unwrap();
+        assert_eq!(tokio_row.tags, "rust;async");
+        assert_eq!(tokio_row.tag_count, 2);
+        assert_eq!(tokio_row.links, 2);
+        assert_eq!(tokio_row.backlinks, 0);
+        assert_eq!(tokio_row.words, 6);
+
+        let pin_row = rows.iter().find(|r| r.id == pin).unwrap();
+        assert_eq!(pin_row.backlinks, 1);
+        assert_eq!(pin_row.accesses, 1);
+        assert!(pin_row.tags.is_empty());
+    }
+}
+
+// ============================================================================
+// Configurable Query Classifier Tests
+// ============================================================================
+
+mod query_classifier_tests {
+    use notidium::config::SearchConfig;
+    use notidium::search::QueryClassifier;
+    use notidium::types::QueryType;
+
+    #[test]
+    fn test_classifier_rules_from_config() {
+        let config: SearchConfig = toml::from_str(
+            r#"
+            [classifier]
+            code_threshold = 3.0
+            signals = [
+              { pattern = "terraform", weight = 3.0 },
+              { pattern = "meeting" },
+            ]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.classifier.builtin_weight, 1.0);
+        assert_eq!(config.classifier.signals[1].weight, 1.0);
+
+        let classifier = QueryClassifier::new(&config.classifier);
+        assert_eq!(classifier.classify("terraform modules").query_type, QueryType::Code);
+        assert_eq!(classifier.classify("meeting notes").query_type, QueryType::Hybrid);
+        // Two built-in signals no longer reach the raised code threshold
+        assert_eq!(classifier.classify("fn main()").query_type, QueryType::Hybrid);
+    }
+
+    #[test]
+    fn test_query_type_parameter() {
+        let parsed: QueryType = serde_json::from_str("\"code\"").unwrap();
+        assert_eq!(parsed, QueryType::Code);
+        assert!(serde_json::from_str::<QueryType>("\"sql\"").is_err());
+    }
+}
+
+// ============================================================================
+// Multi-Query Retrieval Tests
+// ============================================================================
+
+mod multi_query_tests {
+    use notidium::config::SearchConfig;
+    use notidium::search::{fuse_subquery_results, split_sentences, QueryDecomposer};
+    use notidium::types::SearchResult;
+
+    fn result(note_id: &str) -> SearchResult {
+        SearchResult {
+            note_id: note_id.to_string(),
+            title: String::new(),
+            snippet: String::new(),
+            score: 0.0,
+            chunk_type: None,
+            tags: Vec::new(),
+            updated_at: None,
+            chunk: None,
+            line: None,
+            highlights: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_notes_found_by_several_subqueries_rank_first() {
+        let fused = fuse_subquery_results(
+            vec![
+                vec![result("a"), result("shared")],
+                vec![result("b"), result("shared")],
+                vec![result("c")],
+            ],
+            3,
+        );
+        let ids: Vec<&str> = fused.iter().map(|r| r.note_id.as_str()).collect();
+        assert_eq!(ids, vec!["shared", "a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_decomposer_from_config() {
+        let config: SearchConfig = toml::from_str("[multi_query]\nmax_queries = 2\n").unwrap();
+        let decomposer = QueryDecomposer::new(&config.multi_query);
+        let queries = decomposer
+            .decompose("How do backlinks work? How are tags indexed? What about canvases?")
+            .await;
+        assert_eq!(queries, vec!["How do backlinks work?", "How are tags indexed?"]);
+        assert_eq!(split_sentences("single question"), vec!["single question"]);
+    }
+}
+
+// ============================================================================
+// Recency Ranking Tests
+// ============================================================================
+
+mod recency_ranking_tests {
+    use std::collections::HashMap;
+
+    use chrono::{Duration, Utc};
+    use notidium::config::SearchConfig;
+    use notidium::search::apply_recency;
+    use notidium::types::SearchResult;
+
+    #[test]
+    fn test_configured_half_life_controls_reordering() {
+        let config: SearchConfig = toml::from_str("[recency]\nhalf_life_days = 7\n").unwrap();
+        assert_eq!(config.recency.weight, 0.5);
+
+        let now = Utc::now();
+        let result = |note_id: &str, score: f32| SearchResult {
+            note_id: note_id.to_string(),
+            title: String::new(),
+            snippet: String::new(),
+            score,
+            chunk_type: None,
+            tags: Vec::new(),
+            updated_at: None,
+            chunk: None,
+            line: None,
+            highlights: Vec::new(),
+        };
+        let updated_at = HashMap::from([
+            ("last-month".to_string(), now - Duration::days(30)),
+            ("this-week".to_string(), now - Duration::days(3)),
+        ]);
+
+        let ranked = apply_recency(
+            vec![result("last-month", 0.9), result("this-week", 0.7)],
+            &updated_at,
+            &config.recency,
+            now,
+        );
+        assert_eq!(ranked[0].note_id, "this-week");
+
+        // Without decay the closer match stays ahead
+        let mut slow = config.recency.clone();
+        slow.half_life_days = 3650.0;
+        let ranked = apply_recency(
+            vec![result("last-month", 0.9), result("this-week", 0.7)],
+            &updated_at,
+            &slow,
+            now,
+        );
+        assert_eq!(ranked[0].note_id, "last-month");
+    }
+}
+
+// ============================================================================
+// Maintenance Tests
+// ============================================================================
+
+mod maintain_tests {
+    use super::*;
+    use notidium::maintain;
+    use notidium::store::{Manifest, VectorStore};
+    use notidium::types::{Chunk, ChunkType};
+    use uuid::Uuid;
+
+    fn embedded_chunk(note_id: Uuid, dimension: usize) -> Chunk {
+        let mut chunk = Chunk::new(note_id, "chunk".to_string(), ChunkType::Prose);
+        chunk.prose_embedding = Some(vec![0.5; dimension]);
+        chunk
+    }
+
+    #[tokio::test]
+    async fn test_check_finds_and_repair_fixes_drift() {
+        let fixture = StoreTestFixture::new().await;
+        let vectors = VectorStore::open(&fixture.config.vectors_path()).await.unwrap();
+
+        let kept = fixture.create_test_note("Kept", "Still here", None).await;
+        let gone = fixture.create_test_note("Gone", "Deleted behind our back", None).await;
+        let bad = fixture.create_test_note("Bad", "Embedded with another model", None).await;
+        for id in [kept, gone, bad] {
+            let note = fixture.store.get(id).await.unwrap();
+            fixture.fulltext.index_note(&note).unwrap();
+        }
+        fixture.fulltext.commit().unwrap();
+        let orphan = embedded_chunk(Uuid::new_v4(), 4);
+        let gone_chunk = embedded_chunk(gone, 4);
+        vectors
+            .add_chunks(&[embedded_chunk(kept, 4), gone_chunk.clone(), orphan.clone(), embedded_chunk(bad, 8)])
+            .await
+            .unwrap();
+        fixture.store.mark_indexed(&[kept, gone, bad]).await.unwrap();
+
+        // Created without being indexed anywhere
+        let fresh = fixture.create_test_note("Fresh", "Never indexed", None).await;
+        let gone_path = fixture.store.get(gone).await.unwrap().file_path;
+        std::fs::remove_file(fixture.config.notes_path().join(gone_path)).unwrap();
+
+        let manifest_path = fixture.config.data_dir().join("manifest.json");
+        let manifest = Manifest::load(&manifest_path).unwrap();
+        let store = NoteStore::new(fixture.config.clone());
+        store.load_all().await.unwrap();
+
+        let notes_path = fixture.config.notes_path();
+        let report = maintain::check(&manifest, &store, &fixture.fulltext, &vectors, &notes_path)
+            .await
+            .unwrap();
+        assert_eq!(report.notes, 3);
+        assert_eq!(report.chunks, 4);
+        assert_eq!(report.orphaned_manifest, vec![gone]);
+        assert_eq!(report.orphaned_documents, vec![gone.to_string()]);
+        assert_eq!(report.missing_documents, vec![fresh]);
+        let mut orphaned = vec![gone_chunk.id, orphan.id];
+        orphaned.sort();
+        assert_eq!(report.orphaned_chunks, orphaned);
+        assert_eq!(report.invalid_vectors, vec![bad]);
+        assert_eq!(report.missing_vectors, vec![fresh]);
+        assert!(report.stale_vectors.is_empty());
+
+        maintain::repair(&report, &store, &fixture.fulltext, &vectors).await.unwrap();
+
+        let manifest = Manifest::load(&manifest_path).unwrap();
+        let report = maintain::check(&manifest, &store, &fixture.fulltext, &vectors, &notes_path)
+            .await
+            .unwrap();
+        assert!(report.orphaned_manifest.is_empty());
+        assert!(report.orphaned_documents.is_empty());
+        assert!(report.missing_documents.is_empty());
+        assert!(report.orphaned_chunks.is_empty());
+        assert!(report.invalid_vectors.is_empty());
+        // Left to be embedded
+        let mut missing = vec![bad, fresh];
+        missing.sort();
+        assert_eq!(report.missing_vectors, missing);
+        assert!(report.needs_embedding());
+        assert_eq!(vectors.load_all().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_compact_keeps_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let vectors = VectorStore::open(temp_dir.path()).await.unwrap();
+        let note_id = Uuid::new_v4();
+        for _ in 0..3 {
+            vectors.add_chunks(&[embedded_chunk(note_id, 4)]).await.unwrap();
+        }
+        let first = vectors.load_all().await.unwrap()[0].id;
+        vectors.delete_chunks(&[first]).await.unwrap();
+
+        let compaction = vectors.compact().await.unwrap();
+        assert!(compaction.fragments_removed > 0);
+        assert!(compaction.versions_removed > 0);
+        assert_eq!(vectors.load_all().await.unwrap().len(), 2);
+
+        // Nothing left to merge
+        let again = vectors.compact().await.unwrap();
+        assert_eq!(again.fragments_removed, 0);
+    }
+}
+
+// ============================================================================
+// Remote Embedding Tests
+// ============================================================================
+
+mod remote_embedding_tests {
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use notidium::config::{EmbeddingConfig, EmbeddingProvider, RemoteEmbeddingConfig};
+    use notidium::embed::Embedder;
+    use serde_json::{json, Value};
+
+    /// Serve an OpenAI-style embeddings API embedding each text as its
+    /// length (ten times that with a code model), answering in reverse order
+    async fn serve() -> String {
+        let app = Router::new().route(
+            "/v1/embeddings",
+            post(|headers: HeaderMap, Json(body): Json<Value>| async move {
+                if headers.get("authorization").and_then(|v| v.to_str().ok()) != Some("Bearer sk-test") {
+                    return (StatusCode::UNAUTHORIZED, Json(json!({"error": "bad key"})));
+                }
+                let dims = body["dimensions"].as_u64().unwrap_or(3) as usize;
+                let scale = if body["model"].as_str().unwrap().contains("code") { 10.0 } else { 1.0 };
+                let data: Vec<Value> = body["input"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(i, text)| {
+                        let len = text.as_str().unwrap().len() as f32 * scale;
+                        json!({"index": i, "embedding": vec![len; dims]})
+                    })
+                    .collect();
+                (StatusCode::OK, Json(json!({"model": body["model"], "data": data})))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/v1", addr)
+    }
+
+    fn config(url: String, api_key: &str) -> EmbeddingConfig {
+        EmbeddingConfig {
+            provider: EmbeddingProvider::Remote,
+            prose_model: "text-embedding-3-small".to_string(),
+            code_model: "code-embedder".to_string(),
+            remote: RemoteEmbeddingConfig {
+                url,
+                api_key: Some(api_key.to_string()),
+                ..RemoteEmbeddingConfig::default()
+            },
+            ..EmbeddingConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remote_embeddings_keep_input_order() {
+        let embedder = Embedder::from_config(&config(serve().await, "sk-test")).unwrap();
+        assert_eq!(embedder.prose_model(), "text-embedding-3-small");
+        assert_eq!(embedder.prose_dimension(), 0, "Unknown until the API answers");
+
+        let embeddings = embedder
+            .embed_batch(vec!["a".to_string(), "abc".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(embeddings, vec![vec![1.0; 3], vec![3.0; 3]]);
+        assert_eq!(embedder.prose_dimension(), 3);
+        assert_eq!(embedder.embed_code("fn x()").await.unwrap(), vec![60.0; 3]);
+    }
+
+    #[tokio::test]
+    async fn test_code_falls_back_to_prose_model() {
+        let mut config = config(serve().await, "sk-test");
+        config.code_embeddings = false;
+        let embedder = Embedder::from_config(&config).unwrap();
+        assert!(!embedder.has_code_model());
+        assert_eq!(embedder.embed_code("fn x()").await.unwrap(), vec![6.0; 3]);
+        assert_eq!(embedder.code_dimension(), embedder.prose_dimension());
+    }
+
+    #[tokio::test]
+    async fn test_remote_dimensions_and_errors() {
+        let url = serve().await;
+        let mut shortened = config(url.clone(), "sk-test");
+        shortened.remote.dimensions = Some(2);
+        let embedder = Embedder::from_config(&shortened).unwrap();
+        assert_eq!(embedder.prose_dimension(), 2);
+        assert_eq!(embedder.embed("ab").await.unwrap(), vec![2.0, 2.0]);
+
+        let rejected = Embedder::from_config(&config(url, "sk-wrong")).unwrap();
+        let err = rejected.embed("ab").await.unwrap_err().to_string();
+        assert!(err.contains("401"), "{}", err);
+    }
+}
+
+// ============================================================================
+// Local MCP Client Tests
+// ============================================================================
+
+mod mcp_inspect_tests {
+    use super::*;
+    use notidium::embed::{Chunker, Embedder, EmbedderBackend};
+    use notidium::mcp::server::connect_local;
+    use notidium::mcp::NotidiumServer;
+    use notidium::search::SemanticSearch;
+    use rmcp::model::{CallToolRequestParam, Extensions, Meta, NumberOrString, ProgressToken};
+    use rmcp::service::RequestContext;
+    use rmcp::{ServerHandler, ServiceExt};
+    use serde_json::json;
+    use tokio::sync::RwLock;
+    use tokio_util::sync::CancellationToken;
+
+    #[tokio::test]
+    async fn test_list_and_call_tools_without_transport() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config {
+            vault_path: temp_dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        config.init_vault().unwrap();
+        let store = Arc::new(NoteStore::new(config.clone()));
+        let fulltext = Arc::new(FullTextIndex::open(&config.tantivy_path()).unwrap());
+        // Local models load on first use, which these tools never need
+        let embedder = Arc::new(Embedder::new().unwrap());
+        let semantic = Arc::new(RwLock::new(SemanticSearch::new(embedder.clone())));
+        let note = store.create("Inspected".to_string(), "Body".to_string(), None).await.unwrap();
+
+        let server = NotidiumServer::new(store, fulltext, semantic, embedder, Arc::new(Chunker::default()));
+        let client = connect_local(server).await.unwrap();
+
+        let tools = client.list_all_tools().await.unwrap();
+        let get_note = tools.iter().find(|t| t.name == "get_note").unwrap();
+        assert!(get_note.input_schema["properties"].get("id").is_some());
+
+        let result = client
+            .call_tool(CallToolRequestParam {
+                name: "get_note".into(),
+                arguments: json!({"id": note.id.to_string()}).as_object().cloned(),
+            })
+            .await
+            .unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("Inspected"));
+
+        let unknown = client
+            .call_tool(CallToolRequestParam {
+                name: "no_such_tool".into(),
+                arguments: None,
+            })
+            .await;
+        assert!(unknown.is_err());
+        client.cancel().await.unwrap();
+    }
+
+    /// Embeds everything as the same vector, cancelling `ct` once it sees
+    /// `marker`
+    struct CancellingBackend {
+        marker: String,
+        ct: CancellationToken,
+    }
+
+    #[async_trait::async_trait]
+    impl EmbedderBackend for CancellingBackend {
+        async fn embed(&self, texts: Vec<String>) -> notidium::error::Result<Vec<Vec<f32>>> {
+            if texts.iter().any(|t| t.contains(&self.marker)) {
+                self.ct.cancel();
+            }
+            Ok(vec![vec![1.0; 8]; texts.len()])
+        }
+
+        fn dimension(&self) -> usize {
+            8
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_reindex_keeps_finished_notes() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config {
+            vault_path: temp_dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        config.init_vault().unwrap();
+        let store = Arc::new(NoteStore::new(config.clone()));
+        for i in 0..60 {
+            store
+                .create(format!("Note {:03}", i), format!("Body {:03}", i), None)
+                .await
+                .unwrap();
+        }
+        assert_eq!(store.get_notes_needing_reindex().await.len(), 60);
+
+        // Cancelled while embedding the 55th note, after one full batch
+        let ct = CancellationToken::new();
+        let backend = CancellingBackend {
+            marker: "Body 054".to_string(),
+            ct: ct.clone(),
+        };
+        let embedder = Arc::new(Embedder::from_backends("cancelling", Box::new(backend), None));
+        let fulltext = Arc::new(FullTextIndex::open(&config.tantivy_path()).unwrap());
+        let semantic = Arc::new(RwLock::new(SemanticSearch::new(embedder.clone())));
+        let server = NotidiumServer::new(store.clone(), fulltext, semantic, embedder, Arc::new(Chunker::default()));
+
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let (service, client) = tokio::join!(server.clone().serve(server_io), ().serve(client_io));
+        let (service, client) = (service.unwrap(), client.unwrap());
+        let mut meta = Meta::new();
+        meta.set_progress_token(ProgressToken(NumberOrString::Number(1)));
+        let context = RequestContext {
+            ct,
+            id: NumberOrString::Number(1),
+            meta,
+            extensions: Extensions::new(),
+            peer: service.peer().clone(),
+        };
+
+        let result = server
+            .call_tool(
+                CallToolRequestParam {
+                    name: "reindex_vault".into(),
+                    arguments: json!({"scope": "full"}).as_object().cloned(),
+                },
+                context,
+            )
+            .await
+            .unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.starts_with("Cancelled after reindexing 55 of 60 notes"), "{}", text);
+
+        // The notes finished before the cancel aren't redone next time
+        let mut pending: Vec<String> = store
+            .get_notes_needing_reindex()
+            .await
+            .into_iter()
+            .map(|n| n.title)
+            .collect();
+        pending.sort();
+        assert_eq!(pending, (55..60).map(|i| format!("Note {:03}", i)).collect::<Vec<_>>());
+
+        client.cancel().await.unwrap();
+        service.cancel().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_get_stats_suggests_maintenance() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config {
+            vault_path: temp_dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        config.init_vault().unwrap();
+        let store = Arc::new(NoteStore::new(config.clone()));
+        let fulltext = Arc::new(FullTextIndex::open(&config.tantivy_path()).unwrap());
+        let embedder = Arc::new(Embedder::new().unwrap());
+        let semantic = Arc::new(RwLock::new(SemanticSearch::new(embedder.clone())));
+        store.create("Hub".to_string(), "See [[Spoke]] and [[Nowhere]].".to_string(), None).await.unwrap();
+        let spoke = store.create("Spoke".to_string(), "Linked from the hub".to_string(), None).await.unwrap();
+        let lonely = store.create("Lonely".to_string(), "No links at all".to_string(), None).await.unwrap();
+        store.quick_capture("Call the plumber".to_string(), None).await.unwrap();
+        store.delete(lonely.id).await.unwrap();
+        fulltext.index_note(&spoke).unwrap();
+        fulltext.commit().unwrap();
+
+        let server = NotidiumServer::new(store, fulltext, semantic, embedder, Arc::new(Chunker::default()));
+        let client = connect_local(server).await.unwrap();
+        let result = client
+            .call_tool(CallToolRequestParam {
+                name: "get_stats".into(),
+                arguments: None,
+            })
+            .await
+            .unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("- **Notes:** 3"), "{}", text);
+        assert!(text.contains("- **Notes missing from the full-text index:** 2"));
+        assert!(text.contains("- **Orphaned notes:** 1"));
+        assert!(text.contains("- **Broken links:** 1"));
+        assert!(text.contains("- **Inbox backlog:** 1"));
+        assert!(text.contains("- **Trash:** 1 notes"));
+        assert!(text.contains("## Suggested actions"));
+        assert!(text.contains("out of date in the indexes: run reindex_vault"), "{}", text);
+        assert!(text.contains("- 1 capture in the inbox needs triage"));
+        assert!(text.contains("- 1 link points to notes that don't exist"));
+        client.cancel().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_scoped_stats_count_only_readable_notes() {
+        use notidium::api::CurrentUser;
+        use notidium::store::Users;
+
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config {
+            vault_path: temp_dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        config.init_vault().unwrap();
+        let store = Arc::new(NoteStore::new(config.clone()));
+        let fulltext = Arc::new(FullTextIndex::open(&config.tantivy_path()).unwrap());
+        let embedder = Arc::new(Embedder::new().unwrap());
+        let semantic = Arc::new(RwLock::new(SemanticSearch::new(embedder.clone())));
+        store
+            .create_in(std::path::Path::new("reports"), "Nightly".into(), "ok".into(), Some(vec!["ci".into()]))
+            .await
+            .unwrap();
+        store
+            .create("Payroll".into(), "secret".into(), Some(vec!["hr".into(), "pay".into()]))
+            .await
+            .unwrap();
+        let server = NotidiumServer::new(store, fulltext, semantic, embedder, Arc::new(Chunker::default()));
+
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let (service, client) = tokio::join!(server.clone().serve(server_io), ().serve(client_io));
+        let (service, client) = (service.unwrap(), client.unwrap());
+        // Tool calls as a key that can only read reports
+        let mut users = Users::default();
+        let key = users.add("bot", vec!["read:reports/".parse().unwrap()]).unwrap();
+        let (mut parts, _) = axum::http::Request::new(()).into_parts();
+        parts.extensions.insert(CurrentUser {
+            name: "bot".into(),
+            permissions: users.authenticate(&key).unwrap().permissions(),
+        });
+        let call = |name: &'static str| {
+            let mut extensions = Extensions::new();
+            extensions.insert(parts.clone());
+            let context = RequestContext {
+                ct: CancellationToken::new(),
+                id: NumberOrString::Number(1),
+                meta: Meta::new(),
+                extensions,
+                peer: service.peer().clone(),
+            };
+            server.call_tool(CallToolRequestParam { name: name.into(), arguments: None }, context)
+        };
+
+        let result = call("get_stats").await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.contains("- **Notes:** 1"), "{}", text);
+        assert!(text.contains("- **Tags:** 1"), "{}", text);
+        assert!(!text.contains("Chunks") && !text.contains("## Index"), "{}", text);
+
+        let result = call("index_status").await.unwrap();
+        let text = &result.content[0].as_text().unwrap().text;
+        assert!(text.starts_with("Error:"), "{}", text);
+
+        client.cancel().await.unwrap();
+        service.cancel().await.unwrap();
+    }
+}
+
+// ============================================================================
+// Grep Tests
+// ============================================================================
+
+mod grep_tests {
+    use super::*;
+    use notidium::search::{grep_notes, GrepOptions};
+
+    #[tokio::test]
+    async fn test_grep_maps_lines_to_notes() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config {
+            vault_path: temp_dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        config.init_vault().unwrap();
+        let store = NoteStore::new(config.clone());
+        let tokio = store
+            .create(
+                "Runtimes".to_string(),
+                "# Runtimes\n\nUse Tokio::spawn for tasks.\n\nThen tokio::join!".to_string(),
+                Some(vec!["rust".to_string()]),
+            )
+            .await
+            .unwrap();
+        store.create("Garden".to_string(), "Nothing async here".to_string(), None).await.unwrap();
+        let notes = store.load_all().await.unwrap();
+
+        let results = grep_notes(&config.notes_path(), &notes, r"tokio::\w+", GrepOptions::default(), 10).unwrap();
+        assert_eq!(results.len(), 1);
+        let file = std::fs::read_to_string(config.notes_path().join(&tokio.file_path)).unwrap();
+        let expected = file.lines().position(|l| l == "Then tokio::join!").unwrap() as u32 + 1;
+        assert_eq!(results[0].note_id, tokio.id.to_string());
+        assert_eq!(results[0].title, "Runtimes");
+        assert_eq!(results[0].line, Some(expected));
+        assert_eq!(results[0].snippet, "Then tokio::join!");
+        assert_eq!(results[0].tags, vec!["rust"]);
+
+        let options = GrepOptions {
+            ignore_case: true,
+            fixed_strings: true,
+            ..GrepOptions::default()
+        };
+        let results = grep_notes(&config.notes_path(), &notes, "TOKIO::", options, 10).unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].line < results[1].line);
+        assert_eq!(grep_notes(&config.notes_path(), &notes, "TOKIO::", options, 1).unwrap().len(), 1);
+
+        assert!(grep_notes(&config.notes_path(), &notes, "(", GrepOptions::default(), 10).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_grep_ignores_ignore_files_and_stops_at_limit() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config {
+            vault_path: temp_dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        config.init_vault().unwrap();
+        let notes_path = config.notes_path();
+        // ripgrep would skip these unless told not to
+        std::fs::write(notes_path.join(".ignore"), "archive/\n").unwrap();
+        std::fs::create_dir_all(notes_path.join("archive")).unwrap();
+        std::fs::write(notes_path.join("archive/Old.md"), "# Old\n\nneedle one\n").unwrap();
+        std::fs::write(notes_path.join("Newer.md"), "# Newer\n\nneedle two\nneedle three\n").unwrap();
+        let store = NoteStore::new(config.clone());
+        let notes = store.load_all().await.unwrap();
+
+        let results = grep_notes(&notes_path, &notes, "needle", GrepOptions::default(), 10).unwrap();
+        let snippets: Vec<&str> = results.iter().map(|r| r.snippet.as_str()).collect();
+        assert_eq!(snippets, vec!["needle two", "needle three", "needle one"]);
+
+        // The first matches in path order, whichever way the files are searched
+        let results = grep_notes(&notes_path, &notes, "needle", GrepOptions::default(), 2).unwrap();
+        let snippets: Vec<&str> = results.iter().map(|r| r.snippet.as_str()).collect();
+        assert_eq!(snippets, vec!["needle two", "needle three"]);
+    }
+}
+
+// ============================================================================
+// Highlight Tests
+// ============================================================================
+
+mod highlight_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_highlights_across_vault() {
+        let fixture = StoreTestFixture::new().await;
+        let rust = fixture
+            .create_test_note(
+                "Rust Notes",
+                "Intro\n\nOwnership means ==one owner per value==.\nAnd ==borrows end at last use==.",
+                Some(vec!["rust".to_string()]),
+            )
+            .await;
+        fixture
+            .create_test_note("Cooking", "Always ==salt the pasta water==.", None)
+            .await;
+
+        let all = fixture.store.highlights(None).await.unwrap();
+        let texts: Vec<&str> = all.iter().map(|h| h.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec!["salt the pasta water", "one owner per value", "borrows end at last use"]
+        );
+        assert_eq!(all[1].title, "Rust Notes");
+        assert_eq!(all[1].note_id, rust.to_string());
+        assert_eq!(all[2].line, all[1].line + 1);
+
+        let tagged = fixture.store.highlights(Some("Rust")).await.unwrap();
+        assert_eq!(tagged.len(), 2);
+        assert!(tagged.iter().all(|h| h.tags.contains(&"rust".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_highlights_follow_edits_and_deletes() {
+        let fixture = StoreTestFixture::new().await;
+        let id = fixture
+            .create_test_note("Draft", "An ==early idea== here.", None)
+            .await;
+
+        fixture
+            .store
+            .update(id, "# Draft\n\nA ==better idea== now.".to_string())
+            .await
+            .unwrap();
+        let highlights = fixture.store.highlights(None).await.unwrap();
+        assert_eq!(highlights.len(), 1);
+        assert_eq!(highlights[0].text, "better idea");
+        assert_eq!(highlights[0].line, 3);
+
+        fixture.store.delete(id).await.unwrap();
+        assert!(fixture.store.highlights(None).await.unwrap().is_empty());
+    }
+}
+
+// ============================================================================
+// Read Position Tests
+// ============================================================================
+
+mod read_position_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_positions_kept_per_client() {
+        let fixture = StoreTestFixture::new().await;
+        let id = fixture.create_test_note("Manual", "Long text", None).await;
+
+        fixture
+            .store
+            .set_read_position(id, "alice", Some("Install".to_string()), Some(12), None)
+            .await
+            .unwrap();
+        fixture
+            .store
+            .set_read_position(id, "alice", None, Some(40), Some(0.5))
+            .await
+            .unwrap();
+
+        let position = fixture.store.read_position(id, "alice").unwrap().unwrap();
+        assert_eq!(position.heading, None);
+        assert_eq!(position.line, Some(40));
+        assert_eq!(position.scroll, Some(0.5));
+        assert!(fixture.store.read_position(id, "bob").unwrap().is_none());
+
+        let missing = uuid::Uuid::new_v4();
+        assert!(fixture
+            .store
+            .set_read_position(missing, "alice", None, Some(1), None)
+            .await
+            .is_err());
+    }
+}
+
+// ============================================================================
+// Starred Note Tests
+// ============================================================================
+
+mod starred_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stars_are_per_user() {
+        let fixture = StoreTestFixture::new().await;
+        let id = fixture.create_test_note("Favorite", "Body", None).await;
+
+        fixture.store.set_starred(id, "alice", true).await.unwrap();
+        // Starring twice keeps one star
+        fixture.store.set_starred(id, "alice", true).await.unwrap();
+        let starred = fixture.store.starred("alice").await.unwrap();
+        assert_eq!(starred.len(), 1);
+        assert_eq!(starred[0].id, id);
+        assert!(fixture.store.starred("bob").await.unwrap().is_empty());
+
+        // Trashed notes drop out of the list
+        fixture.store.delete(id).await.unwrap();
+        assert!(fixture.store.starred("alice").await.unwrap().is_empty());
+    }
+}
+
+#[cfg(feature = "expensive_tests")]
+mod mcp_server_tests {
     use super::*;
     use tokio::sync::RwLock;
     use notidium::embed::{Chunker, Embedder};
@@ -2518,4 +7578,30 @@ Unsupervised learning finds patterns in unlabeled data. Examples include cluster
         assert!(retrieved.is_some(), "Note should be retrievable from store");
         assert_eq!(retrieved.unwrap().title, "GraphQL API Design");
     }
+
+    #[tokio::test]
+    async fn test_saved_chunks_survive_restart() {
+        use notidium::search::{delete_note_chunks, save_note_chunks};
+        use notidium::store::VectorStore;
+        use notidium::types::{Chunk, ChunkType};
+
+        let fixture = FullTestFixture::new().await;
+        let path = fixture._temp_dir.path().join(".notidium/vectors");
+        let vectors = Arc::new(VectorStore::open(&path).await.unwrap());
+        let semantic = RwLock::new(SemanticSearch::new(fixture.embedder.clone()).with_vectors(vectors));
+
+        let note_id = uuid::Uuid::new_v4();
+        let mut chunk = Chunk::new(note_id, "Saved chunk".to_string(), ChunkType::Prose);
+        chunk.prose_embedding = Some(vec![0.5; 384]);
+        save_note_chunks(&semantic, note_id, vec![chunk.clone(), chunk]).await.unwrap();
+        assert_eq!(semantic.read().await.chunk_count(), 2);
+
+        // A fresh store sees what was saved
+        let reopened = VectorStore::open(&path).await.unwrap();
+        assert_eq!(reopened.load_all().await.unwrap().len(), 2);
+
+        delete_note_chunks(&semantic, note_id).await.unwrap();
+        assert_eq!(semantic.read().await.chunk_count(), 0);
+        assert_eq!(VectorStore::open(&path).await.unwrap().count().await.unwrap(), 0);
+    }
 }